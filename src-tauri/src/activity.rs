@@ -0,0 +1,244 @@
+use crate::models::ActivityImport;
+use crate::privacy;
+use crate::storage::Database;
+use log::{info, warn};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+
+/// Folder under the app data dir scanned for `*.jsonl` activity logs before each
+/// analysis run; imported files are renamed to `*.jsonl.imported`.
+pub const ACTIVITY_INBOX_DIR: &str = "activity_inbox";
+
+/// Longest title or app name kept, in characters. Page titles can be arbitrarily long.
+pub const MAX_TITLE_CHARS: usize = 200;
+
+/// Most activity lines added to one prompt.
+pub const PROMPT_ACTIVITY_CAP: usize = 10;
+
+/// How far back before a capture group its activity window reaches, at most. The window
+/// otherwise starts at the previous group of the run.
+pub const ACTIVITY_LOOKBACK_SECS: i64 = 120;
+
+/// Numeric timestamps at or above this are taken as milliseconds (it's the year 5138 in seconds).
+const MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+#[derive(Error, Debug)]
+pub enum ActivityError {
+    #[error("Unsupported activity log format \"{0}\" (expected \"jsonl\")")]
+    UnsupportedFormat(String),
+    #[error("Failed to read activity log: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// An activity log timestamp before normalization to the database format (UTC,
+/// `YYYY-MM-DDTHH:MM:SS`), which `Database::insert_activity_samples` does with SQLite's `strftime`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActivityTime {
+    /// Seconds since the Unix epoch.
+    Unix(i64),
+    /// ISO 8601, with an optional UTC offset (`2025-01-01T10:00:00+02:00`).
+    Text(String),
+}
+
+/// A line of the log as written by the extension.
+#[derive(Deserialize)]
+struct RawRecord {
+    timestamp: serde_json::Value,
+    title: String,
+    #[serde(default)]
+    app: Option<String>,
+}
+
+/// One parsed and sanitized log record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityRecord {
+    pub timestamp: ActivityTime,
+    pub title: String,
+    /// Empty when the record names no app.
+    pub app: String,
+}
+
+/// Parse one JSONL line: `{"timestamp": <unix s/ms or ISO 8601>, "title": ..., "app"?: ...}`.
+/// None for anything malformed, including an empty title.
+pub fn parse_line(line: &str) -> Option<ActivityRecord> {
+    let raw: RawRecord = serde_json::from_str(line).ok()?;
+    let timestamp = match raw.timestamp {
+        serde_json::Value::Number(n) => {
+            let n = n.as_i64()?;
+            ActivityTime::Unix(if n >= MILLIS_THRESHOLD { n / 1000 } else { n })
+        }
+        serde_json::Value::String(s) if !s.trim().is_empty() => ActivityTime::Text(s.trim().to_string()),
+        _ => return None,
+    };
+    let title = sanitize(&raw.title);
+    if title.is_empty() {
+        return None;
+    }
+    Some(ActivityRecord {
+        timestamp,
+        title,
+        app: raw.app.as_deref().map(sanitize).unwrap_or_default(),
+    })
+}
+
+/// Collapse whitespace and control characters (newlines would break the prompt's list)
+/// into single spaces and cut to `MAX_TITLE_CHARS`.
+pub fn sanitize(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_TITLE_CHARS)
+        .collect()
+}
+
+/// Import an activity log into `activity_samples`. Malformed lines (bad JSON, missing
+/// title, unreadable timestamp) and records matching `blocked` title patterns are
+/// skipped and counted; records already imported are ignored.
+pub fn import_activity_log(db: &Database, path: &Path, format: &str, blocked: &[String]) -> Result<ActivityImport, ActivityError> {
+    if format != "jsonl" {
+        return Err(ActivityError::UnsupportedFormat(format.to_string()));
+    }
+    let mut result = ActivityImport::default();
+    let mut records = Vec::new();
+    for line in BufReader::new(File::open(path)?).split(b'\n') {
+        let line = line?;
+        let Ok(line) = std::str::from_utf8(&line) else {
+            result.skipped_malformed += 1;
+            continue;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            None => result.skipped_malformed += 1,
+            Some(record) if privacy::matches_patterns(&[&record.title, &record.app], blocked).is_some() => {
+                result.skipped_blocked += 1;
+            }
+            Some(record) => records.push(record),
+        }
+    }
+
+    let inserted = db.insert_activity_samples(&records)?;
+    result.imported = inserted.imported;
+    result.in_session = inserted.in_session;
+    result.skipped_duplicate = inserted.duplicates;
+    result.skipped_malformed += inserted.bad_timestamps;
+    Ok(result)
+}
+
+/// Import every `*.jsonl` file dropped into `inbox`, renaming each to `*.jsonl.imported`
+/// once done. Files that fail are logged and left for the next run.
+pub fn import_inbox(db: &Database, inbox: &Path, blocked: &[String]) -> ActivityImport {
+    let mut total = ActivityImport::default();
+    let Ok(entries) = std::fs::read_dir(inbox) else {
+        return total;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        match import_activity_log(db, &path, "jsonl", blocked) {
+            Ok(imported) => {
+                info!(
+                    "Imported {} activity samples from {} ({} malformed, {} blocked, {} duplicate)",
+                    imported.imported, path.display(), imported.skipped_malformed, imported.skipped_blocked, imported.skipped_duplicate
+                );
+                let mut done = path.clone().into_os_string();
+                done.push(".imported");
+                if let Err(e) = std::fs::rename(&path, &done) {
+                    warn!("Failed to mark {} as imported: {}", path.display(), e);
+                }
+                total.add(&imported);
+            }
+            Err(e) => warn!("Failed to import activity log {}: {}", path.display(), e),
+        }
+    }
+    total
+}
+
+/// "App: title" for the prompt, or just the title when there's no app.
+pub fn prompt_line(title: &str, app: &str) -> String {
+    if app.is_empty() {
+        title.to_string()
+    } else {
+        format!("{}: {}", app, title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line(r#"{"timestamp": "2025-01-01T10:00:00Z", "title": "PR #12 - GitHub", "app": "Firefox"}"#),
+            Some(ActivityRecord {
+                timestamp: ActivityTime::Text("2025-01-01T10:00:00Z".to_string()),
+                title: "PR #12 - GitHub".to_string(),
+                app: "Firefox".to_string(),
+            })
+        );
+        // Seconds and milliseconds both work; the app is optional
+        let secs = parse_line(r#"{"timestamp": 1735725600, "title": "main.rs"}"#).unwrap();
+        assert_eq!(secs.timestamp, ActivityTime::Unix(1_735_725_600));
+        assert_eq!(secs.app, "");
+        let millis = parse_line(r#"{"timestamp": 1735725600123, "title": "main.rs"}"#).unwrap();
+        assert_eq!(millis.timestamp, ActivityTime::Unix(1_735_725_600));
+
+        assert_eq!(parse_line("not json"), None);
+        assert_eq!(parse_line(r#"{"timestamp": 1735725600}"#), None);
+        assert_eq!(parse_line(r#"{"timestamp": 1735725600, "title": "  \n "}"#), None);
+        assert_eq!(parse_line(r#"{"timestamp": null, "title": "x"}"#), None);
+        assert_eq!(parse_line(r#"{"timestamp": 1.5, "title": "x"}"#), None);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("  Inbox\n(3)\t- Mail\u{7}  "), "Inbox (3) - Mail");
+        assert_eq!(sanitize(&"é".repeat(MAX_TITLE_CHARS + 5)).chars().count(), MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn test_import_activity_log_counts() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_activity_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.jsonl");
+        std::fs::write(&path, concat!(
+            "{\"timestamp\": \"2025-01-01T10:00:00Z\", \"title\": \"Docs\", \"app\": \"Firefox\"}\n",
+            "\n",
+            "{\"timestamp\": \"yesterday\", \"title\": \"Bad time\"}\n",
+            "{\"broken\n",
+            "{\"timestamp\": \"2025-01-01T10:01:00Z\", \"title\": \"Online Banking\"}\n",
+            "{\"timestamp\": \"2025-01-01T10:00:00Z\", \"title\": \"Docs\", \"app\": \"Firefox\"}\n",
+        )).unwrap();
+
+        let db = Database::in_memory().unwrap();
+        assert!(matches!(
+            import_activity_log(&db, &path, "csv", &[]),
+            Err(ActivityError::UnsupportedFormat(_))
+        ));
+        let result = import_activity_log(&db, &path, "jsonl", &["bank*".to_string()]).unwrap();
+        assert_eq!(result, ActivityImport {
+            imported: 1,
+            in_session: 0,
+            skipped_malformed: 2,
+            skipped_blocked: 1,
+            skipped_duplicate: 1,
+        });
+
+        // The inbox imports the file again (all duplicates now) and marks it done
+        let total = import_inbox(&db, &dir, &[]);
+        assert_eq!(total.skipped_duplicate, 2);
+        assert!(!path.exists());
+        assert!(dir.join("log.jsonl.imported").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,15 @@
 use base64::Engine;
-use log::{error, info};
+use log::{error, info, trace};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use crate::capture;
+use crate::image_cache::ImageCache;
+use crate::models::ProviderProbe;
+use crate::prompt_template::{self, PromptVars, TemplateError};
 
 #[derive(Error, Debug)]
 pub enum AiError {
@@ -17,6 +21,65 @@ pub enum AiError {
     ApiError(String),
     #[error("Ollama is not available: {0}")]
     OllamaUnavailable(String),
+    #[error("Invalid API key")]
+    InvalidApiKey,
+    #[error("Invalid prompt template: {0}")]
+    PromptTemplate(#[from] TemplateError),
+}
+
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434";
+
+/// Claude model used for capture analysis.
+pub const CLAUDE_MODEL: &str = "claude-sonnet-4-5-20250929";
+
+/// Task categories the AI chooses from.
+pub const CATEGORIES: &[&str] = &["coding", "browsing", "writing", "communication", "design", "other"];
+
+/// Instruction used in place of an inline JSON example when Ollama's `format` field carries the schema.
+const OLLAMA_JSON_INSTRUCTION: &str = "Respond with JSON matching the schema provided in the format field.";
+
+/// Task history shown to the model.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PromptContext<'a> {
+    /// Recent task contexts, most recent first (`context_depth` of them).
+    pub recent: &'a [String],
+    /// "Title (category)" of tasks already identified in the session, oldest first
+    /// (`include_session_task_list`).
+    pub session_tasks: &'a [String],
+    /// Tasks carried over from the previous session (`carry_context_from_previous`).
+    pub carried: Option<CarriedTasks<'a>>,
+    /// Page/app titles from an imported activity log around the capture, most recent first.
+    pub activity: &'a [String],
+    /// The previous batch's last screenshots, oldest first (`context_overlap`).
+    pub overlap: &'a [String],
+}
+
+/// The end of the previous session, mentioned in a new session's first prompts.
+#[derive(Debug, Clone, Copy)]
+pub struct CarriedTasks<'a> {
+    /// Task titles, most recent first.
+    pub titles: &'a [String],
+    /// Minutes between the previous session's end and this session's start.
+    pub gap_minutes: i64,
+}
+
+/// "45 minutes", "1h 05m".
+fn format_gap(minutes: i64) -> String {
+    if minutes < 60 {
+        format!("{} minute{}", minutes, if minutes == 1 { "" } else { "s" })
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// User-supplied prompt templates (`prompt_template_single` / `prompt_template_multi`).
+/// None means use the built-in prompt.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PromptTemplates<'a> {
+    pub single: Option<&'a str>,
+    pub multi: Option<&'a str>,
 }
 
 #[derive(Debug, Serialize)]
@@ -68,6 +131,56 @@ pub struct TaskAnalysis {
     pub is_new_task: bool,
     #[serde(default)]
     pub monitor_summaries: HashMap<String, String>,
+    /// Exact title of the session task this capture continues, when the prompt listed them.
+    #[serde(default)]
+    pub continues_task_title: Option<String>,
+    /// Any other fields in the response, e.g. the user's `custom_fields`.
+    #[serde(flatten, deserialize_with = "deserialize_extra")]
+    pub extra: HashMap<String, String>,
+}
+
+/// Collect unknown response fields as strings. Numbers and booleans are kept in
+/// their JSON form and nulls are dropped, so a model volunteering a non-string
+/// field doesn't fail the whole analysis.
+fn deserialize_extra<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw: HashMap<String, serde_json::Value> = HashMap::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(k, v)| match v {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some((k, s)),
+            other => Some((k, other.to_string())),
+        })
+        .collect())
+}
+
+/// Response fields the analysis schema always has; custom fields may not reuse them.
+const KNOWN_FIELDS: &[&str] = &[
+    "task_title", "task_description", "category", "reasoning", "is_new_task", "monitor_summaries",
+    "continues_task_title",
+];
+
+/// Parse the `custom_fields` setting: comma-separated names of extra string fields
+/// the AI should fill in. Names must be identifiers and not clash with built-in fields.
+pub fn parse_custom_fields(setting: &str) -> Result<Vec<String>, String> {
+    let mut fields: Vec<String> = Vec::new();
+    for name in setting.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let valid_start = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+        if !valid_start || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("'{}' is not a valid field name (letters, digits and _ only)", name));
+        }
+        if KNOWN_FIELDS.contains(&name) {
+            return Err(format!("'{}' is a built-in field", name));
+        }
+        if fields.iter().any(|f| f == name) {
+            return Err(format!("'{}' is listed twice", name));
+        }
+        fields.push(name.to_string());
+    }
+    Ok(fields)
 }
 
 /// Info about a changed monitor whose image will be sent to the AI.
@@ -77,6 +190,10 @@ pub struct ChangedMonitor<'a> {
     pub width: u32,
     pub height: u32,
     pub is_primary: bool,
+    /// The monitor the user just switched away from.
+    pub previous_active: bool,
+    /// The display's scale factor when captured, for `active_window` cropping.
+    pub scale_factor: f64,
 }
 
 /// Info about an unchanged monitor (text summary only).
@@ -85,8 +202,19 @@ pub struct UnchangedMonitor<'a> {
     pub summary: &'a str,
 }
 
-/// Load an image from disk, apply preprocessing based on image_mode, and return base64 + media type.
-fn preprocess_and_encode(image_path: &Path, image_mode: &str) -> Result<(String, &'static str), AiError> {
+/// Media type of the preprocessed images sent to the AI.
+const IMAGE_MEDIA_TYPE: &str = "image/webp";
+
+/// What one analysis request sent, recorded when `save_analysis_inputs` is on.
+#[derive(Debug, Default)]
+pub struct SentInputs {
+    pub prompt: String,
+    /// Preprocessed WebP bytes of each changed monitor, in `changed` order.
+    pub images: Vec<Vec<u8>>,
+}
+
+/// Load an image from disk, apply preprocessing based on image_mode, and return the WebP bytes sent to the AI.
+fn preprocess_image(image_path: &Path, image_mode: &str, scale_factor: f64) -> Result<Vec<u8>, AiError> {
     let raw_bytes = std::fs::read(image_path).map_err(|e| {
         error!("Failed to read image {}: {}", image_path.display(), e);
         AiError::ImageReadFailed(e.to_string())
@@ -98,66 +226,134 @@ fn preprocess_and_encode(image_path: &Path, image_mode: &str) -> Result<(String,
 
     let processed = match image_mode {
         "active_window" => {
-            let cropped = capture::crop_active_window(&img);
+            let cropped = capture::crop_active_window(&img, scale_factor);
             capture::resize_for_analysis(&cropped, 1280)
         }
         _ => capture::resize_for_analysis(&img, 1280),
     };
 
-    let webp_bytes = capture::encode_webp_bytes(&processed)
-        .map_err(|e| AiError::ImageReadFailed(format!("Failed to encode preprocessed image: {}", e)))?;
+    capture::encode_webp_bytes(&processed)
+        .map_err(|e| AiError::ImageReadFailed(format!("Failed to encode preprocessed image: {}", e)))
+}
 
-    let b64 = base64::engine::general_purpose::STANDARD.encode(&webp_bytes);
-    Ok((b64, "image/webp"))
+/// Preprocess every changed monitor's image and base64-encode it, reusing payloads
+/// already in `cache`. The WebP bytes are kept in `sent` when it's given.
+fn encode_images(
+    changed: &[ChangedMonitor<'_>],
+    image_mode: &str,
+    cache: Option<&ImageCache>,
+    sent: &mut Option<&mut SentInputs>,
+) -> Result<Vec<String>, AiError> {
+    encode_images_with(changed, image_mode, cache, sent, preprocess_image)
+}
+
+/// `encode_images` with the preprocessing step injected.
+fn encode_images_with(
+    changed: &[ChangedMonitor<'_>],
+    image_mode: &str,
+    cache: Option<&ImageCache>,
+    sent: &mut Option<&mut SentInputs>,
+    preprocess: impl Fn(&Path, &str, f64) -> Result<Vec<u8>, AiError>,
+) -> Result<Vec<String>, AiError> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut encoded = Vec::with_capacity(changed.len());
+    for cm in changed {
+        let make = || preprocess(cm.image_path, image_mode, cm.scale_factor).map(|webp| engine.encode(webp));
+        let payload = match cache {
+            Some(cache) => cache.get_or_try_insert(cm.image_path, image_mode, make)?,
+            None => make()?,
+        };
+        if let Some(sent) = sent.as_deref_mut() {
+            let webp = engine.decode(&payload).map_err(|e| AiError::ImageReadFailed(e.to_string()))?;
+            sent.images.push(webp);
+        }
+        encoded.push(payload);
+    }
+    Ok(encoded)
 }
 
 // --- Prompt builders ---
 
+/// Inline JSON example of the expected response, optionally with a
+/// `monitor_summaries` object (`summaries_example` is its contents),
+/// followed by the user's custom fields.
+fn json_example(summaries_example: Option<&str>, custom_fields: &[String]) -> String {
+    let categories = CATEGORIES.join("|");
+    let summaries = summaries_example
+        .map(|example| format!(", \"monitor_summaries\": {{{example}}}"))
+        .unwrap_or_default();
+    let custom: String = custom_fields
+        .iter()
+        .map(|f| format!(", \"{f}\": \"{f} value\""))
+        .collect();
+    format!(
+        "{{\"task_title\": \"short title\", \"task_description\": \"what they're doing\", \
+         \"category\": \"{categories}\", \
+         \"reasoning\": \"why you think this\", \"is_new_task\": true/false{summaries}{custom}}}"
+    )
+}
+
+/// Render a user prompt template with this capture's values.
+fn render_template(
+    template: &str,
+    context_section: &str,
+    session_description: Option<&str>,
+    monitors_section: &str,
+    json_schema: &str,
+) -> Result<String, TemplateError> {
+    prompt_template::render(template, &PromptVars {
+        context_section,
+        session_description: session_description.unwrap_or(""),
+        categories: &CATEGORIES.join("|"),
+        monitors_section,
+        json_schema,
+    })
+}
+
 /// Build the analysis prompt for single-monitor mode.
-fn build_prompt(previous_contexts: &[String], session_description: Option<&str>) -> String {
-    let context_section = build_context_section(previous_contexts);
+fn build_prompt(
+    context: PromptContext<'_>,
+    session_description: Option<&str>,
+    template: Option<&str>,
+    custom_fields: &[String],
+) -> Result<String, TemplateError> {
+    let context_section = build_context_section(context);
+    let json = json_example(None, custom_fields);
+
+    if let Some(template) = template {
+        return render_template(template, &context_section, session_description, "", &json);
+    }
 
-    if let Some(desc) = session_description {
+    Ok(if let Some(desc) = session_description {
         format!(
             "The user is working on: {desc}. \
              Look at this screenshot and briefly describe what specific step or subtask they are currently on.\n\
              {context_section}\
              Respond with JSON only, no other text:\n\
-             {{\"task_title\": \"short title\", \"task_description\": \"what they're doing\", \
-             \"category\": \"coding|browsing|writing|communication|design|other\", \
-             \"reasoning\": \"why you think this\", \"is_new_task\": true/false}}"
+             {json}"
         )
     } else {
         format!(
             "Analyze this screenshot of a user's screen. Determine what task they are working on.\n\
              {context_section}\
              Respond with JSON only, no other text:\n\
-             {{\"task_title\": \"short title\", \"task_description\": \"what they're doing\", \
-             \"category\": \"coding|browsing|writing|communication|design|other\", \
-             \"reasoning\": \"why you think this\", \"is_new_task\": true/false}}"
+             {json}"
         )
-    }
+    })
 }
 
-/// Build the analysis prompt for multi-monitor mode (Claude).
-fn build_multi_prompt(
-    changed: &[ChangedMonitor<'_>],
-    unchanged: &[UnchangedMonitor<'_>],
-    previous_contexts: &[String],
-    session_description: Option<&str>,
-    total_monitors: usize,
-) -> String {
-    let context_section = build_context_section(previous_contexts);
-
+/// List changed monitors (images attached) and unchanged monitors (text summaries).
+fn build_monitors_section(changed: &[ChangedMonitor<'_>], unchanged: &[UnchangedMonitor<'_>]) -> String {
     let mut monitors_section = String::new();
 
     // Changed monitors (images attached)
     monitors_section.push_str("MONITORS WITH NEW SCREENSHOTS (images attached in order):\n");
     for (i, cm) in changed.iter().enumerate() {
         let primary_tag = if cm.is_primary { ", primary" } else { "" };
+        let previous_tag = if cm.previous_active { ", previously active — the user just switched away from it" } else { "" };
         monitors_section.push_str(&format!(
-            "- Monitor \"{}\" ({}x{}{}): see image {}\n",
-            cm.monitor_name, cm.width, cm.height, primary_tag, i + 1
+            "- Monitor \"{}\" ({}x{}{}{}): see image {}\n",
+            cm.monitor_name, cm.width, cm.height, primary_tag, previous_tag, i + 1
         ));
     }
 
@@ -171,12 +367,21 @@ fn build_multi_prompt(
             ));
         }
     }
+    monitors_section
+}
 
-    let session_ctx = if let Some(desc) = session_description {
-        format!("The user is working on: {}.\n", desc)
-    } else {
-        String::new()
-    };
+/// Build the analysis prompt for multi-monitor mode (Claude).
+fn build_multi_prompt(
+    changed: &[ChangedMonitor<'_>],
+    unchanged: &[UnchangedMonitor<'_>],
+    context: PromptContext<'_>,
+    session_description: Option<&str>,
+    total_monitors: usize,
+    template: Option<&str>,
+    custom_fields: &[String],
+) -> Result<String, TemplateError> {
+    let context_section = build_context_section(context);
+    let monitors_section = build_monitors_section(changed, unchanged);
 
     // Build monitor_summaries keys for the JSON schema
     let monitor_names: Vec<String> = changed.iter().map(|m| m.monitor_name.to_string())
@@ -186,8 +391,19 @@ fn build_multi_prompt(
         .map(|n| format!("\"{}\": \"1-sentence description\"", n))
         .collect::<Vec<_>>()
         .join(", ");
+    let json = json_example(Some(&summaries_example), custom_fields);
 
-    format!(
+    if let Some(template) = template {
+        return render_template(template, &context_section, session_description, &monitors_section, &json);
+    }
+
+    let session_ctx = if let Some(desc) = session_description {
+        format!("The user is working on: {}.\n", desc)
+    } else {
+        String::new()
+    };
+
+    Ok(format!(
         "You are analyzing a multi-monitor desktop capture taken at a single moment.\n\
          The user has {total_monitors} monitors.\n\n\
          {monitors_section}\n\
@@ -196,22 +412,61 @@ fn build_multi_prompt(
          Analyze what the user is doing across all monitors. Focus on the changed \
          monitor(s) — a change on any monitor may indicate a task switch.\n\n\
          Respond with JSON only, no other text:\n\
-         {{\"task_title\": \"short title\", \"task_description\": \"what they're doing\", \
-         \"category\": \"coding|browsing|writing|communication|design|other\", \
-         \"reasoning\": \"why you think this\", \"is_new_task\": true/false, \
-         \"monitor_summaries\": {{{summaries_example}}}}}"
-    )
+         {json}"
+    ))
 }
 
-fn build_context_section(previous_contexts: &[String]) -> String {
-    if previous_contexts.is_empty() {
-        return String::new();
+/// Append the `prompt_language` instruction to a built prompt. JSON keys and the
+/// category stay English so responses still parse; only the free text is localized.
+fn with_language(prompt: String, language: Option<&str>) -> String {
+    match language {
+        Some(language) => format!(
+            "{prompt}\n\
+             Respond in {language}: write task_title, task_description, reasoning and any other \
+             text values in {language}, but keep the JSON keys and the category value in English."
+        ),
+        None => prompt,
     }
-    let mut section = String::from("Recent task history (most recent first):\n");
-    for (i, ctx) in previous_contexts.iter().enumerate() {
-        section.push_str(&format!("  {}. {}\n", i + 1, ctx));
+}
+
+fn build_context_section(context: PromptContext<'_>) -> String {
+    let mut section = String::new();
+    if let Some(carried) = context.carried.filter(|c| !c.titles.is_empty()) {
+        section.push_str(&format!(
+            "The user recently worked on: {} (in a previous session that ended {} before this one started).\n",
+            carried.titles.join("; "),
+            format_gap(carried.gap_minutes)
+        ));
+    }
+    if !context.recent.is_empty() {
+        section.push_str("Recent task history (most recent first):\n");
+        for (i, ctx) in context.recent.iter().enumerate() {
+            section.push_str(&format!("  {}. {}\n", i + 1, ctx));
+        }
+        section.push_str("Use this context to decide if the current screenshot shows a continuation of a recent task or a new one.\n");
+    }
+    if !context.overlap.is_empty() {
+        section.push_str("The previous batch (already analyzed) ended with these captures, oldest first:\n");
+        for line in context.overlap {
+            section.push_str(&format!("  - {}\n", line));
+        }
+    }
+    if !context.activity.is_empty() {
+        section.push_str("Window and page titles the user had open around this capture (from their activity log):\n");
+        for line in context.activity {
+            section.push_str(&format!("  - {}\n", line));
+        }
+    }
+    if !context.session_tasks.is_empty() {
+        section.push_str("Tasks already identified in this session:\n");
+        for task in context.session_tasks {
+            section.push_str(&format!("  - {}\n", task));
+        }
+        section.push_str(
+            "If the screenshot continues one of these, set \"continues_task_title\" to its exact title \
+             and is_new_task to false rather than starting a near-duplicate task.\n",
+        );
     }
-    section.push_str("Use this context to decide if the current screenshot shows a continuation of a recent task or a new one.\n");
     section
 }
 
@@ -229,19 +484,229 @@ fn strip_code_fences(text: &str) -> &str {
     }
 }
 
+/// Remove reasoning models' `<think>…</think>` blocks. A closing tag without an
+/// opening one (some chat templates emit the opening tag themselves) drops everything
+/// before it.
+fn strip_think_blocks(text: &str) -> String {
+    const OPEN: &str = "<think>";
+    const CLOSE: &str = "</think>";
+    let mut text = text.to_string();
+    while let Some(end) = text.find(CLOSE) {
+        let start = text[..end].rfind(OPEN).unwrap_or(0);
+        text.replace_range(start..end + CLOSE.len(), "");
+    }
+    text
+}
+
+/// The JSON object in an AI response: `<think>` blocks and code fences removed, then
+/// from the first `{` to its matching `}`, dropping reasoning prose around it.
+/// Without a `{` the cleaned text is returned as is, so the parse error shows it.
+fn extract_json_object(text: &str) -> String {
+    let text = strip_think_blocks(text);
+    let cleaned = strip_code_fences(&text);
+    let Some(start) = cleaned.find('{') else {
+        return cleaned.to_string();
+    };
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in cleaned[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return cleaned[start..=start + i].to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+    // Unbalanced (e.g. a truncated reply): let the parser report it
+    cleaned[start..].to_string()
+}
+
+/// Header and field names whose values are credentials.
+const SECRET_KEYS: &[&str] = &["x-api-key", "api-key", "api_key", "apikey", "authorization"];
+
+/// Mask anything that looks like a credential in `text` before it is logged or shown:
+/// Anthropic keys (`sk-ant-…`) anywhere, and the value after a `key: value` /
+/// `key=value` for any of `SECRET_KEYS` (after `Bearer ` for Authorization).
+/// Error bodies can echo request headers.
+pub fn redact_secrets(text: &str) -> String {
+    const REDACTED: &str = "[REDACTED]";
+    let bytes = text.as_bytes();
+    // ASCII lowercasing keeps byte offsets identical to `text`
+    let lower = text.to_ascii_lowercase();
+    let token_end = |mut i: usize| {
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || matches!(bytes[i], b'-' | b'_' | b'.')) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut spans: Vec<(usize, usize)> = lower
+        .match_indices("sk-ant-")
+        .map(|(start, _)| (start, token_end(start)))
+        .collect();
+    for key in SECRET_KEYS {
+        for (pos, _) in lower.match_indices(key) {
+            let mut i = pos + key.len();
+            let mut assigned = false;
+            while i < bytes.len() && matches!(bytes[i], b'"' | b'\'' | b' ' | b':' | b'=') {
+                assigned |= matches!(bytes[i], b':' | b'=');
+                i += 1;
+            }
+            if !assigned {
+                continue;
+            }
+            if lower.as_bytes()[i..].starts_with(b"bearer ") {
+                i += "bearer ".len();
+            }
+            let end = token_end(i);
+            if end > i {
+                spans.push((i, end));
+            }
+        }
+    }
+
+    spans.sort_unstable();
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in spans {
+        if end <= last {
+            continue;
+        }
+        out.push_str(&text[last..start.max(last)]);
+        out.push_str(REDACTED);
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
 // --- Claude API ---
 
+/// Where Claude requests go: `claude_base_url` (a Claude-compatible gateway, say) plus
+/// the `claude_extra_headers` sent with every request.
+#[derive(Debug, Clone)]
+pub struct ClaudeEndpoint {
+    pub base_url: String,
+    pub extra_headers: reqwest::header::HeaderMap,
+}
+
+impl Default for ClaudeEndpoint {
+    fn default() -> Self {
+        ClaudeEndpoint { base_url: ANTHROPIC_API_BASE.to_string(), extra_headers: Default::default() }
+    }
+}
+
+impl ClaudeEndpoint {
+    /// `path` (starting with `/`) under the base URL, which may end with a slash or
+    /// carry a path prefix of its own.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    /// A request to `path` authenticated with `api_key`. Extra headers are applied last,
+    /// so they can override `anthropic-version` (or the key header itself).
+    pub fn request(&self, client: &Client, method: reqwest::Method, path: &str, api_key: &str) -> reqwest::RequestBuilder {
+        client
+            .request(method, self.url(path))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            // Replaces headers of the same name rather than adding to them
+            .headers(self.extra_headers.clone())
+    }
+
+    /// `redact_secrets`, plus masking the extra header values, which hold gateway
+    /// credentials as a rule.
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = redact_secrets(text);
+        for value in self.extra_headers.values() {
+            match value.to_str() {
+                Ok(value) if !value.is_empty() => text = text.replace(value, "[REDACTED]"),
+                _ => {}
+            }
+        }
+        text
+    }
+}
+
+/// Parse the `claude_base_url` setting: an http(s) URL; empty means `ANTHROPIC_API_BASE`.
+pub fn parse_claude_base_url(setting: &str) -> Result<String, String> {
+    let setting = setting.trim();
+    if setting.is_empty() {
+        return Ok(ANTHROPIC_API_BASE.to_string());
+    }
+    let url = reqwest::Url::parse(setting).map_err(|e| format!("not a valid URL: {}", e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("expected an http or https URL, got {}", url.scheme()));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("the URL can't have a query or fragment".to_string());
+    }
+    Ok(setting.to_string())
+}
+
+/// Parse the `claude_extra_headers` setting: a JSON object of header names to string
+/// values; empty means none. `content-type`, `content-length` and `host` are set by the
+/// request itself and can't be overridden.
+pub fn parse_extra_headers(setting: &str) -> Result<reqwest::header::HeaderMap, String> {
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+    let mut headers = HeaderMap::new();
+    if setting.trim().is_empty() {
+        return Ok(headers);
+    }
+    let object: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(setting).map_err(|e| format!("expected a JSON object of header names to values: {}", e))?;
+    for (name, value) in object {
+        let header = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("'{}' is not a valid header name", name))?;
+        if matches!(header.as_str(), "content-type" | "content-length" | "host") {
+            return Err(format!("'{}' can't be overridden", header));
+        }
+        let serde_json::Value::String(value) = value else {
+            return Err(format!("the value of '{}' must be a string", header));
+        };
+        // The value is never echoed: it's usually a credential
+        let value = HeaderValue::from_str(&value).map_err(|_| format!("the value of '{}' is not a valid header value", header))?;
+        if headers.insert(header.clone(), value).is_some() {
+            return Err(format!("'{}' is listed twice", header));
+        }
+    }
+    Ok(headers)
+}
+
 /// Analyze one or more monitor captures using the Claude API.
 /// For single-monitor: pass one image in `changed`, empty `unchanged`.
 /// For multi-monitor: pass changed images + unchanged summaries.
+#[allow(clippy::too_many_arguments)]
 pub async fn analyze_capture(
     client: &Client,
+    endpoint: &ClaudeEndpoint,
     api_key: &str,
     changed: &[ChangedMonitor<'_>],
     unchanged: &[UnchangedMonitor<'_>],
-    previous_contexts: &[String],
+    context: PromptContext<'_>,
     session_description: Option<&str>,
     image_mode: &str,
+    templates: PromptTemplates<'_>,
+    custom_fields: &[String],
+    language: Option<&str>,
+    log_responses: bool,
+    images: Option<&ImageCache>,
+    mut sent: Option<&mut SentInputs>,
 ) -> Result<TaskAnalysis, AiError> {
     if changed.is_empty() {
         return Err(AiError::ApiError("No images to analyze".to_string()));
@@ -257,27 +722,30 @@ pub async fn analyze_capture(
     );
 
     // Build content: images first, then prompt text
-    let mut content = Vec::new();
-    for cm in changed {
-        let (b64, media_type) = preprocess_and_encode(cm.image_path, image_mode)?;
-        content.push(Content::Image {
+    let mut content: Vec<Content> = encode_images(changed, image_mode, images, &mut sent)?
+        .into_iter()
+        .map(|b64| Content::Image {
             source: ImageSource {
                 source_type: "base64".to_string(),
-                media_type: media_type.to_string(),
+                media_type: IMAGE_MEDIA_TYPE.to_string(),
                 data: b64,
             },
-        });
-    }
+        })
+        .collect();
 
     let prompt = if is_multi {
-        build_multi_prompt(changed, unchanged, previous_contexts, session_description, total_monitors)
+        build_multi_prompt(changed, unchanged, context, session_description, total_monitors, templates.multi, custom_fields)?
     } else {
-        build_prompt(previous_contexts, session_description)
+        build_prompt(context, session_description, templates.single, custom_fields)?
     };
+    let prompt = with_language(prompt, language);
+    if let Some(sent) = sent {
+        sent.prompt = prompt.clone();
+    }
     content.push(Content::Text { text: prompt });
 
     let request = ClaudeRequest {
-        model: "claude-sonnet-4-5-20250929".to_string(),
+        model: CLAUDE_MODEL.to_string(),
         max_tokens: 1024,
         messages: vec![Message {
             role: "user".to_string(),
@@ -285,18 +753,15 @@ pub async fn analyze_capture(
         }],
     };
 
-    let resp = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("content-type", "application/json")
+    let resp = endpoint
+        .request(client, reqwest::Method::POST, "/v1/messages", api_key)
         .json(&request)
         .send()
         .await?;
 
     if !resp.status().is_success() {
         let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
+        let body = endpoint.redact(&resp.text().await.unwrap_or_default());
         error!("Claude API error {}: {}", status, body);
         return Err(AiError::ApiError(format!("{}: {}", status, body)));
     }
@@ -308,10 +773,12 @@ pub async fn analyze_capture(
         .and_then(|c| c.text.as_ref())
         .ok_or_else(|| AiError::ApiError("Empty response".to_string()))?;
 
-    info!("Raw AI response text: {}", text);
-    let cleaned = strip_code_fences(text);
+    if log_responses {
+        trace!("Raw AI response text: {}", text);
+    }
+    let cleaned = extract_json_object(text);
 
-    let analysis: TaskAnalysis = serde_json::from_str(cleaned).map_err(|e| {
+    let analysis: TaskAnalysis = serde_json::from_str(&cleaned).map_err(|e| {
         error!("Failed to parse AI response: {} — raw text: {}", e, cleaned);
         AiError::ApiError(format!("Parse error: {}", e))
     })?;
@@ -319,6 +786,26 @@ pub async fn analyze_capture(
     Ok(analysis)
 }
 
+/// Check that a Claude API key is accepted, using the models listing endpoint
+/// so no tokens are spent.
+pub async fn validate_api_key(client: &Client, endpoint: &ClaudeEndpoint, api_key: &str) -> Result<(), AiError> {
+    let resp = endpoint
+        .request(client, reqwest::Method::GET, "/v1/models?limit=1", api_key)
+        .send()
+        .await?;
+
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(AiError::InvalidApiKey);
+    }
+    if !status.is_success() {
+        let body = endpoint.redact(&resp.text().await.unwrap_or_default());
+        error!("Claude API key validation failed {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+    Ok(())
+}
+
 // --- Ollama types and functions ---
 
 #[derive(Debug, Serialize)]
@@ -359,33 +846,59 @@ pub(crate) struct OllamaModelInfo {
     pub(crate) name: String,
 }
 
+#[derive(Debug, Serialize)]
+struct OllamaEmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+/// Build Ollama prompt for single-monitor (same as Claude's but references the format field).
+fn build_prompt_ollama(
+    context: PromptContext<'_>,
+    session_description: Option<&str>,
+    template: Option<&str>,
+) -> Result<String, TemplateError> {
+    let context_section = build_context_section(context);
+
+    if let Some(template) = template {
+        return render_template(template, &context_section, session_description, "", OLLAMA_JSON_INSTRUCTION);
+    }
+
+    Ok(if let Some(desc) = session_description {
+        format!(
+            "The user is working on: {desc}. \
+             Look at this screenshot and briefly describe what specific step or subtask they are currently on.\n\
+             {context_section}\
+             {OLLAMA_JSON_INSTRUCTION}"
+        )
+    } else {
+        format!(
+            "Analyze this screenshot of a user's screen. Determine what task they are working on.\n\
+             {context_section}\
+             {OLLAMA_JSON_INSTRUCTION}"
+        )
+    })
+}
+
 /// Build Ollama prompt for multi-monitor (same structure as Claude but references format field).
 fn build_multi_prompt_ollama(
     changed: &[ChangedMonitor<'_>],
     unchanged: &[UnchangedMonitor<'_>],
-    previous_contexts: &[String],
+    context: PromptContext<'_>,
     session_description: Option<&str>,
     total_monitors: usize,
-) -> String {
-    let context_section = build_context_section(previous_contexts);
+    template: Option<&str>,
+) -> Result<String, TemplateError> {
+    let context_section = build_context_section(context);
+    let monitors_section = build_monitors_section(changed, unchanged);
 
-    let mut monitors_section = String::new();
-    monitors_section.push_str("MONITORS WITH NEW SCREENSHOTS (images attached in order):\n");
-    for (i, cm) in changed.iter().enumerate() {
-        let primary_tag = if cm.is_primary { ", primary" } else { "" };
-        monitors_section.push_str(&format!(
-            "- Monitor \"{}\" ({}x{}{}): see image {}\n",
-            cm.monitor_name, cm.width, cm.height, primary_tag, i + 1
-        ));
-    }
-    if !unchanged.is_empty() {
-        monitors_section.push_str("\nUNCHANGED MONITORS (text summary from last capture):\n");
-        for um in unchanged {
-            monitors_section.push_str(&format!(
-                "- Monitor \"{}\": {}\n",
-                um.monitor_name, um.summary
-            ));
-        }
+    if let Some(template) = template {
+        return render_template(template, &context_section, session_description, &monitors_section, OLLAMA_JSON_INSTRUCTION);
     }
 
     let session_ctx = if let Some(desc) = session_description {
@@ -394,7 +907,7 @@ fn build_multi_prompt_ollama(
         String::new()
     };
 
-    format!(
+    Ok(format!(
         "You are analyzing a multi-monitor desktop capture taken at a single moment.\n\
          The user has {total_monitors} monitors.\n\n\
          {monitors_section}\n\
@@ -402,19 +915,26 @@ fn build_multi_prompt_ollama(
          {context_section}\
          Analyze what the user is doing across all monitors. Focus on the changed \
          monitor(s).\n\n\
-         Respond with JSON matching the schema provided in the format field."
-    )
+         {OLLAMA_JSON_INSTRUCTION}"
+    ))
 }
 
 /// Analyze one or more monitor captures using Ollama.
+#[allow(clippy::too_many_arguments)]
 pub async fn analyze_capture_ollama(
     client: &Client,
     model: &str,
     changed: &[ChangedMonitor<'_>],
     unchanged: &[UnchangedMonitor<'_>],
-    previous_contexts: &[String],
+    context: PromptContext<'_>,
     session_description: Option<&str>,
     image_mode: &str,
+    templates: PromptTemplates<'_>,
+    custom_fields: &[String],
+    language: Option<&str>,
+    log_responses: bool,
+    images: Option<&ImageCache>,
+    mut sent: Option<&mut SentInputs>,
 ) -> Result<TaskAnalysis, AiError> {
     if changed.is_empty() {
         return Err(AiError::ApiError("No images to analyze".to_string()));
@@ -431,40 +951,26 @@ pub async fn analyze_capture_ollama(
     );
 
     // Encode all images
-    let mut b64_images = Vec::new();
-    for cm in changed {
-        let (b64, _) = preprocess_and_encode(cm.image_path, image_mode)?;
-        b64_images.push(b64);
-    }
+    let b64_images = encode_images(changed, image_mode, images, &mut sent)?;
 
     let prompt = if is_multi {
-        build_multi_prompt_ollama(changed, unchanged, previous_contexts, session_description, total_monitors)
+        build_multi_prompt_ollama(changed, unchanged, context, session_description, total_monitors, templates.multi)?
     } else {
-        let context_section = build_context_section(previous_contexts);
-        if let Some(desc) = session_description {
-            format!(
-                "The user is working on: {desc}. \
-                 Look at this screenshot and briefly describe what specific step or subtask they are currently on.\n\
-                 {context_section}\
-                 Respond with JSON matching the schema provided in the format field."
-            )
-        } else {
-            format!(
-                "Analyze this screenshot of a user's screen. Determine what task they are working on.\n\
-                 {context_section}\
-                 Respond with JSON matching the schema provided in the format field."
-            )
-        }
+        build_prompt_ollama(context, session_description, templates.single)?
     };
+    let prompt = with_language(prompt, language);
+    if let Some(sent) = sent {
+        sent.prompt = prompt.clone();
+    }
 
     let mut format_properties = serde_json::json!({
         "task_title": { "type": "string" },
         "task_description": { "type": "string" },
-        "category": { "type": "string", "enum": ["coding", "browsing", "writing", "communication", "design", "other"] },
+        "category": { "type": "string", "enum": CATEGORIES },
         "reasoning": { "type": "string" },
         "is_new_task": { "type": "boolean" }
     });
-    let mut required = vec!["task_title", "task_description", "category", "reasoning", "is_new_task"];
+    let mut required: Vec<&str> = vec!["task_title", "task_description", "category", "reasoning", "is_new_task"];
 
     if is_multi {
         format_properties.as_object_mut().unwrap().insert(
@@ -473,6 +979,19 @@ pub async fn analyze_capture_ollama(
         );
         required.push("monitor_summaries");
     }
+    if !context.session_tasks.is_empty() {
+        format_properties.as_object_mut().unwrap().insert(
+            "continues_task_title".to_string(),
+            serde_json::json!({ "type": "string" }),
+        );
+    }
+    for field in custom_fields {
+        format_properties.as_object_mut().unwrap().insert(
+            field.clone(),
+            serde_json::json!({ "type": "string" }),
+        );
+        required.push(field);
+    }
 
     let format_schema = serde_json::json!({
         "type": "object",
@@ -499,7 +1018,7 @@ pub async fn analyze_capture_ollama(
     let max_attempts = 2;
     for attempt in 1..=max_attempts {
         let resp = client
-            .post("http://localhost:11434/api/chat")
+            .post(format!("{}/api/chat", OLLAMA_API_BASE))
             .json(&request)
             .send()
             .await
@@ -507,14 +1026,16 @@ pub async fn analyze_capture_ollama(
 
         if !resp.status().is_success() {
             let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
+            let body = redact_secrets(&resp.text().await.unwrap_or_default());
             error!("Ollama API error {}: {}", status, body);
             return Err(AiError::ApiError(format!("{}: {}", status, body)));
         }
 
         let ollama_resp: OllamaResponse = resp.json().await?;
         let content = &ollama_resp.message.content;
-        info!("Raw Ollama response: {}", content);
+        if log_responses {
+            trace!("Raw Ollama response: {}", content);
+        }
 
         if content.trim().is_empty() {
             if attempt < max_attempts {
@@ -534,7 +1055,7 @@ pub async fn analyze_capture_ollama(
             ));
         }
 
-        let analysis: TaskAnalysis = serde_json::from_str(content).map_err(|e| {
+        let analysis: TaskAnalysis = serde_json::from_str(&extract_json_object(content)).map_err(|e| {
             error!(
                 "Failed to parse Ollama response: {} — raw text: {}",
                 e, content
@@ -548,9 +1069,352 @@ pub async fn analyze_capture_ollama(
     Err(AiError::ApiError("Ollama analysis failed".to_string()))
 }
 
+/// A task offered to the consolidation request, numbered by its position in the list.
+#[derive(Debug, Clone, Copy)]
+pub struct ConsolidationTask<'a> {
+    pub title: &'a str,
+    pub description: &'a str,
+}
+
+/// Response to the consolidation request: groups of list indices to merge.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MergeResponse {
+    merge_groups: Vec<Vec<usize>>,
+}
+
+/// Text-only prompt asking which adjacent tasks of a session should be merged.
+fn build_consolidation_prompt(tasks: &[ConsolidationTask<'_>]) -> String {
+    let mut prompt = String::from(
+        "These tasks were identified, in order, from consecutive screenshots of one work session:\n",
+    );
+    for (i, task) in tasks.iter().enumerate() {
+        prompt.push_str(&format!("{}. {}: {}\n", i, task.title, task.description));
+    }
+    prompt.push_str(
+        "\nSome may be the same activity split into several tasks. Which neighbouring tasks \
+         should be merged? Only group tasks that are next to each other in the list, using the \
+         numbers above. Respond ONLY with JSON, no other text: \
+         {\"merge_groups\": [[0, 1], [4, 5, 6]]}, or {\"merge_groups\": []} if nothing should be merged.",
+    );
+    prompt
+}
+
+/// Parse and validate a consolidation response for `task_count` tasks. Every group
+/// must hold at least two consecutive, in-range indices, and no index may appear in
+/// two groups; any violation rejects the whole response, since a model that invents
+/// indices can't be trusted with the rest of its answer either.
+pub fn parse_merge_groups(text: &str, task_count: usize) -> Result<Vec<Vec<usize>>, AiError> {
+    let response: MergeResponse = serde_json::from_str(&extract_json_object(text))
+        .map_err(|e| AiError::ApiError(format!("Parse error: {}", e)))?;
+    let invalid = |reason: String| Err(AiError::ApiError(format!("Invalid merge groups: {}", reason)));
+    let mut seen = vec![false; task_count];
+    for group in &response.merge_groups {
+        if group.len() < 2 {
+            return invalid(format!("{:?} has fewer than two tasks", group));
+        }
+        if group.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+            return invalid(format!("{:?} is not a run of adjacent tasks", group));
+        }
+        for &index in group {
+            match seen.get_mut(index) {
+                None => return invalid(format!("index {} is out of range (0-{})", index, task_count.saturating_sub(1))),
+                Some(true) => return invalid(format!("index {} appears in more than one group", index)),
+                Some(used) => *used = true,
+            }
+        }
+    }
+    Ok(response.merge_groups)
+}
+
+/// Ask Claude which adjacent tasks to merge (see `consolidate_every`).
+pub async fn suggest_merges(
+    client: &Client,
+    endpoint: &ClaudeEndpoint,
+    api_key: &str,
+    tasks: &[ConsolidationTask<'_>],
+    log_responses: bool,
+) -> Result<Vec<Vec<usize>>, AiError> {
+    info!("Requesting task consolidation (Claude) for {} tasks", tasks.len());
+    let request = ClaudeRequest {
+        model: CLAUDE_MODEL.to_string(),
+        max_tokens: 512,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: vec![Content::Text { text: build_consolidation_prompt(tasks) }],
+        }],
+    };
+
+    let resp = endpoint
+        .request(client, reqwest::Method::POST, "/v1/messages", api_key)
+        .json(&request)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = endpoint.redact(&resp.text().await.unwrap_or_default());
+        error!("Claude API error {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+
+    let claude_resp: ClaudeResponse = resp.json().await?;
+    let text = claude_resp
+        .content
+        .first()
+        .and_then(|c| c.text.as_ref())
+        .ok_or_else(|| AiError::ApiError("Empty response".to_string()))?;
+    if log_responses {
+        trace!("Raw consolidation response text: {}", text);
+    }
+    parse_merge_groups(text, tasks.len())
+}
+
+/// Ask Ollama which adjacent tasks to merge (see `consolidate_every`).
+pub async fn suggest_merges_ollama(
+    client: &Client,
+    model: &str,
+    tasks: &[ConsolidationTask<'_>],
+    log_responses: bool,
+) -> Result<Vec<Vec<usize>>, AiError> {
+    info!("Requesting task consolidation (Ollama {}) for {} tasks", model, tasks.len());
+    let request = OllamaRequest {
+        model: model.to_string(),
+        messages: vec![OllamaMessage {
+            role: "user".to_string(),
+            content: build_consolidation_prompt(tasks),
+            images: Vec::new(),
+        }],
+        stream: false,
+        format: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "merge_groups": {
+                    "type": "array",
+                    "items": { "type": "array", "items": { "type": "integer", "minimum": 0 } }
+                }
+            },
+            "required": ["merge_groups"]
+        }),
+        options: Some(serde_json::json!({
+            "temperature": 0.1,
+            "num_predict": 256,
+            "num_ctx": 8192
+        })),
+    };
+
+    let resp = client
+        .post(format!("{}/api/chat", OLLAMA_API_BASE))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| AiError::OllamaUnavailable(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = redact_secrets(&resp.text().await.unwrap_or_default());
+        error!("Ollama API error {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+
+    let ollama_resp: OllamaResponse = resp.json().await?;
+    if log_responses {
+        trace!("Raw Ollama consolidation response: {}", ollama_resp.message.content);
+    }
+    parse_merge_groups(&ollama_resp.message.content, tasks.len())
+}
+
+/// Most suggestions `suggest_session_description` returns.
+pub const MAX_DESCRIPTION_SUGGESTIONS: usize = 3;
+
+/// Longest suggested description kept, in characters.
+const MAX_SUGGESTION_CHARS: usize = 120;
+
+/// Most of the previous session's tasks put in the continuation prompt; the latest are kept.
+const CONTINUATION_TASK_CAP: usize = 20;
+
+/// The session a new one is likely to continue, for the continuation prompt.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviousSession<'a> {
+    pub title: Option<&'a str>,
+    pub description: Option<&'a str>,
+    /// Its tasks in order, as "title: description" lines.
+    pub tasks: &'a [String],
+}
+
+/// Response to the continuation prompt.
+#[derive(Debug, Deserialize)]
+struct SuggestionResponse {
+    suggestions: Vec<String>,
+}
+
+/// Text-only prompt asking for one-line descriptions of a session that picks up where
+/// `previous` left off.
+fn build_continuation_prompt(previous: &PreviousSession<'_>) -> String {
+    let mut prompt = String::from("The user is about to start a new work session. Their previous session was:\n");
+    if let Some(title) = previous.title.filter(|t| !t.trim().is_empty()) {
+        prompt.push_str(&format!("Title: {}\n", title.trim()));
+    }
+    if let Some(description) = previous.description.filter(|d| !d.trim().is_empty()) {
+        prompt.push_str(&format!("Description: {}\n", description.trim()));
+    }
+    if !previous.tasks.is_empty() {
+        prompt.push_str("Tasks, in order:\n");
+        for task in &previous.tasks[previous.tasks.len().saturating_sub(CONTINUATION_TASK_CAP)..] {
+            prompt.push_str(&format!("- {}\n", task));
+        }
+    }
+    prompt.push_str(&format!(
+        "\nSuggest up to {} one-line descriptions for the new session, each a likely \
+         continuation of that work, most likely first. Keep each under 15 words, written as \
+         the user would describe their own plan. Respond ONLY with JSON, no other text: \
+         {{\"suggestions\": [\"...\"]}}",
+        MAX_DESCRIPTION_SUGGESTIONS
+    ));
+    prompt
+}
+
+/// Parse a continuation response: whitespace collapsed to one line, each cut to
+/// `MAX_SUGGESTION_CHARS`, blanks and repeats dropped, at most
+/// `MAX_DESCRIPTION_SUGGESTIONS` kept. Err when nothing usable is left.
+pub fn parse_description_suggestions(text: &str) -> Result<Vec<String>, AiError> {
+    let response: SuggestionResponse = serde_json::from_str(&extract_json_object(text))
+        .map_err(|e| AiError::ApiError(format!("Parse error: {}", e)))?;
+    let mut suggestions: Vec<String> = Vec::new();
+    for suggestion in response.suggestions {
+        let line: String = suggestion.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(MAX_SUGGESTION_CHARS).collect();
+        if !line.is_empty() && !suggestions.iter().any(|s| s.eq_ignore_ascii_case(&line)) {
+            suggestions.push(line);
+        }
+    }
+    suggestions.truncate(MAX_DESCRIPTION_SUGGESTIONS);
+    if suggestions.is_empty() {
+        return Err(AiError::ApiError("No suggestions in response".to_string()));
+    }
+    Ok(suggestions)
+}
+
+/// Ask Claude for descriptions of a session continuing `previous`.
+pub async fn suggest_descriptions(
+    client: &Client,
+    endpoint: &ClaudeEndpoint,
+    api_key: &str,
+    previous: &PreviousSession<'_>,
+    log_responses: bool,
+) -> Result<Vec<String>, AiError> {
+    info!("Requesting session description suggestions (Claude)");
+    let request = ClaudeRequest {
+        model: CLAUDE_MODEL.to_string(),
+        max_tokens: 256,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: vec![Content::Text { text: build_continuation_prompt(previous) }],
+        }],
+    };
+
+    let resp = endpoint
+        .request(client, reqwest::Method::POST, "/v1/messages", api_key)
+        .json(&request)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = endpoint.redact(&resp.text().await.unwrap_or_default());
+        error!("Claude API error {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+
+    let claude_resp: ClaudeResponse = resp.json().await?;
+    let text = claude_resp
+        .content
+        .first()
+        .and_then(|c| c.text.as_ref())
+        .ok_or_else(|| AiError::ApiError("Empty response".to_string()))?;
+    if log_responses {
+        trace!("Raw description suggestion response text: {}", text);
+    }
+    parse_description_suggestions(text)
+}
+
+/// Ask Ollama for descriptions of a session continuing `previous`.
+pub async fn suggest_descriptions_ollama(
+    client: &Client,
+    model: &str,
+    previous: &PreviousSession<'_>,
+    log_responses: bool,
+) -> Result<Vec<String>, AiError> {
+    info!("Requesting session description suggestions (Ollama {})", model);
+    let request = OllamaRequest {
+        model: model.to_string(),
+        messages: vec![OllamaMessage {
+            role: "user".to_string(),
+            content: build_continuation_prompt(previous),
+            images: Vec::new(),
+        }],
+        stream: false,
+        format: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "suggestions": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["suggestions"]
+        }),
+        options: Some(serde_json::json!({
+            "temperature": 0.4,
+            "num_predict": 256,
+            "num_ctx": 8192
+        })),
+    };
+
+    let resp = client
+        .post(format!("{}/api/chat", OLLAMA_API_BASE))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| AiError::OllamaUnavailable(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = redact_secrets(&resp.text().await.unwrap_or_default());
+        error!("Ollama API error {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+
+    let ollama_resp: OllamaResponse = resp.json().await?;
+    if log_responses {
+        trace!("Raw Ollama description suggestion response: {}", ollama_resp.message.content);
+    }
+    parse_description_suggestions(&ollama_resp.message.content)
+}
+
+/// Embedding of `text` from Ollama's `/api/embeddings` with `model` (`embedding_model`).
+pub async fn embed_ollama(client: &Client, model: &str, text: &str) -> Result<Vec<f32>, AiError> {
+    let resp = client
+        .post(format!("{}/api/embeddings", OLLAMA_API_BASE))
+        .json(&OllamaEmbeddingRequest { model, prompt: text })
+        .send()
+        .await
+        .map_err(|e| AiError::OllamaUnavailable(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = redact_secrets(&resp.text().await.unwrap_or_default());
+        error!("Ollama embeddings error {}: {}", status, body);
+        return Err(AiError::ApiError(format!("{}: {}", status, body)));
+    }
+
+    let embedding = resp.json::<OllamaEmbeddingResponse>().await?.embedding;
+    if embedding.is_empty() {
+        // Models without embedding support answer with an empty vector
+        return Err(AiError::ApiError(format!("{} returned an empty embedding", model)));
+    }
+    Ok(embedding)
+}
+
 pub async fn check_ollama_connection(client: &Client) -> Result<Vec<String>, AiError> {
     let resp = client
-        .get("http://localhost:11434/api/tags")
+        .get(format!("{}/api/tags", OLLAMA_API_BASE))
         .send()
         .await
         .map_err(|e| AiError::OllamaUnavailable(e.to_string()))?;
@@ -566,6 +1430,64 @@ pub async fn check_ollama_connection(client: &Client) -> Result<Vec<String>, AiE
     Ok(tags.models.into_iter().map(|m| m.name).collect())
 }
 
+// --- Health probes ---
+
+/// Timeout of each `get_provider_health` probe.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn unreachable_probe(error: String) -> ProviderProbe {
+    ProviderProbe { reachable: false, latency_ms: None, authenticated: None, error: Some(error) }
+}
+
+/// Probe Claude at `endpoint` with the free models listing, telling a rejected key
+/// (401/403) apart from a network failure.
+pub async fn probe_claude(client: &Client, endpoint: &ClaudeEndpoint, api_key: &str, timeout: Duration) -> ProviderProbe {
+    let started = Instant::now();
+    let resp = match endpoint
+        .request(client, reqwest::Method::GET, "/v1/models?limit=1", api_key)
+        .timeout(timeout)
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => return unreachable_probe(endpoint.redact(&e.to_string())),
+    };
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+    let status = resp.status();
+    let (authenticated, error) = if status.is_success() {
+        (Some(true), None)
+    } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        (Some(false), Some(AiError::InvalidApiKey.to_string()))
+    } else {
+        (None, Some(format!("HTTP {}", status)))
+    };
+    ProviderProbe { reachable: true, latency_ms, authenticated, error }
+}
+
+/// Probe Ollama at `base_url` by listing its models, which are returned alongside.
+pub async fn probe_ollama(client: &Client, base_url: &str, timeout: Duration) -> (ProviderProbe, Vec<String>) {
+    let started = Instant::now();
+    let resp = match client.get(format!("{}/api/tags", base_url)).timeout(timeout).send().await {
+        Ok(resp) => resp,
+        Err(e) => return (unreachable_probe(e.to_string()), Vec::new()),
+    };
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+    if !resp.status().is_success() {
+        let probe = ProviderProbe { reachable: true, latency_ms, authenticated: None, error: Some(format!("HTTP {}", resp.status())) };
+        return (probe, Vec::new());
+    }
+    match resp.json::<OllamaTagsResponse>().await {
+        Ok(tags) => (
+            ProviderProbe { reachable: true, latency_ms, authenticated: None, error: None },
+            tags.models.into_iter().map(|m| m.name).collect(),
+        ),
+        Err(e) => (
+            ProviderProbe { reachable: true, latency_ms, authenticated: None, error: Some(format!("Unexpected response: {}", e)) },
+            Vec::new(),
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -584,6 +1506,7 @@ mod tests {
         assert_eq!(analysis.category, "coding");
         assert!(analysis.is_new_task);
         assert!(analysis.monitor_summaries.is_empty());
+        assert!(analysis.extra.is_empty());
     }
 
     #[test]
@@ -607,6 +1530,142 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_task_analysis_extra_fields() {
+        let json = r#"{
+            "task_title": "Writing code",
+            "task_description": "User is editing a Rust file",
+            "category": "coding",
+            "reasoning": "IDE is open with Rust code",
+            "is_new_task": false,
+            "monitor_summaries": {"DISPLAY1": "VS Code"},
+            "project": "rlcollector",
+            "confidence": 0.9,
+            "ticket": null
+        }"#;
+        let analysis: TaskAnalysis = serde_json::from_str(json).unwrap();
+        assert_eq!(analysis.task_title, "Writing code");
+        assert!(!analysis.is_new_task);
+        assert_eq!(analysis.monitor_summaries.len(), 1);
+        assert_eq!(analysis.extra.get("project").unwrap(), "rlcollector");
+        assert_eq!(analysis.extra.get("confidence").unwrap(), "0.9");
+        assert_eq!(analysis.extra.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_custom_fields() {
+        assert_eq!(parse_custom_fields(" project, ticket_id ,").unwrap(), vec!["project", "ticket_id"]);
+        assert!(parse_custom_fields("").unwrap().is_empty());
+        assert!(parse_custom_fields("my project").is_err());
+        assert!(parse_custom_fields("1st").is_err());
+        assert!(parse_custom_fields("category").unwrap_err().contains("built-in"));
+        assert!(parse_custom_fields("project,project").is_err());
+    }
+
+    #[test]
+    fn test_prompt_includes_custom_fields() {
+        let fields = vec!["project".to_string()];
+        let prompt = build_prompt(PromptContext::default(), None, None, &fields).unwrap();
+        assert!(prompt.contains("\"is_new_task\": true/false, \"project\": \"project value\"}"));
+    }
+
+    #[test]
+    fn test_prompt_language_instruction() {
+        let prompt = build_prompt(PromptContext::default(), None, None, &[]).unwrap();
+        assert_eq!(with_language(prompt.clone(), None), prompt);
+
+        let localized = with_language(prompt.clone(), Some("German"));
+        assert!(localized.starts_with(&prompt));
+        assert!(localized.ends_with(
+            "Respond in German: write task_title, task_description, reasoning and any other \
+             text values in German, but keep the JSON keys and the category value in English."
+        ));
+    }
+
+    #[test]
+    fn test_consolidation_prompt_numbers_tasks() {
+        let tasks = [
+            ConsolidationTask { title: "Fixing login bug", description: "Editing auth.rs" },
+            ConsolidationTask { title: "Fixing login", description: "Running tests" },
+        ];
+        let prompt = build_consolidation_prompt(&tasks);
+        assert!(prompt.contains("0. Fixing login bug: Editing auth.rs\n1. Fixing login: Running tests\n"));
+        assert!(prompt.contains("merge_groups"));
+    }
+
+    #[test]
+    fn test_parse_merge_groups_validation() {
+        assert_eq!(parse_merge_groups(r#"{"merge_groups": [[0, 1], [3, 4, 5]]}"#, 6).unwrap(), vec![vec![0, 1], vec![3, 4, 5]]);
+        assert!(parse_merge_groups("```json\n{\"merge_groups\": []}\n```", 3).unwrap().is_empty());
+
+        // Hallucinated or unsafe answers are rejected outright
+        assert!(parse_merge_groups(r#"{"merge_groups": [[0, 1], [5, 6]]}"#, 6).is_err(), "out of range");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[0, 2]]}"#, 6).is_err(), "not adjacent");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[1, 0]]}"#, 6).is_err(), "not in order");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[2]]}"#, 6).is_err(), "single task");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[0, 1], [1, 2]]}"#, 6).is_err(), "overlapping");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[-1, 0]]}"#, 6).is_err(), "negative");
+        assert!(parse_merge_groups(r#"{"merge_groups": [["0", "1"]]}"#, 6).is_err(), "strings");
+        assert!(parse_merge_groups(r#"{"merge_groups": [[0, 1]], "note": "x"}"#, 6).is_err(), "unknown field");
+        assert!(parse_merge_groups(r#"{"groups": [[0, 1]]}"#, 6).is_err(), "missing field");
+    }
+
+    #[test]
+    fn test_continuation_prompt() {
+        let tasks: Vec<String> = (0..25).map(|i| format!("Task {}: step {}", i, i)).collect();
+        let prompt = build_continuation_prompt(&PreviousSession { title: Some("Parser rewrite"), description: Some("  "), tasks: &tasks });
+        assert!(prompt.contains("Title: Parser rewrite\n"));
+        assert!(!prompt.contains("Description:"));
+        // Only the latest tasks are listed
+        assert!(prompt.contains("- Task 24: step 24\n"));
+        assert!(prompt.contains("- Task 5: step 5\n"));
+        assert!(!prompt.contains("- Task 4: step 4\n"));
+        assert!(prompt.contains("up to 3 one-line descriptions"));
+    }
+
+    #[test]
+    fn test_parse_description_suggestions() {
+        let parsed = parse_description_suggestions(
+            "<think>hmm</think>{\"suggestions\": [\"Finish the\\nparser  tests\", \"finish the parser tests\", \" \", \"Review PR\", \"Docs\", \"Extra\"]}",
+        ).unwrap();
+        assert_eq!(parsed, vec!["Finish the parser tests", "Review PR", "Docs"]);
+        assert_eq!(parse_description_suggestions(&format!("{{\"suggestions\": [\"{}\"]}}", "x".repeat(300))).unwrap()[0].len(), MAX_SUGGESTION_CHARS);
+        assert!(parse_description_suggestions("{\"suggestions\": [\"\"]}").is_err());
+        assert!(parse_description_suggestions("no json").is_err());
+    }
+
+    #[test]
+    fn test_encode_images_reuses_cached_payloads() {
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let preprocess = |path: &Path, _mode: &str, _scale: f64| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(path.to_string_lossy().as_bytes().to_vec())
+        };
+        let monitor = |path: &'static str| ChangedMonitor {
+            monitor_name: "DISPLAY1",
+            image_path: Path::new(path),
+            width: 100,
+            height: 100,
+            is_primary: true,
+            previous_active: false,
+            scale_factor: 1.0,
+        };
+        let cache = ImageCache::default();
+        let mut sent = SentInputs::default();
+
+        let first = encode_images_with(&[monitor("a.webp"), monitor("b.webp")], "downscale", Some(&cache), &mut None, preprocess).unwrap();
+        // The same path again within the run is a cache hit, and `sent` still gets its bytes
+        let again = encode_images_with(&[monitor("a.webp")], "downscale", Some(&cache), &mut Some(&mut sent), preprocess).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+        assert_eq!(again[0], first[0]);
+        assert_eq!(sent.images, vec![b"a.webp".to_vec()]);
+
+        // Another image mode, or no cache, preprocesses again
+        encode_images_with(&[monitor("a.webp")], "active_window", Some(&cache), &mut None, preprocess).unwrap();
+        encode_images_with(&[monitor("a.webp")], "downscale", None, &mut None, preprocess).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 4);
+    }
+
     #[test]
     fn test_claude_request_serialization() {
         let request = ClaudeRequest {
@@ -699,6 +1758,171 @@ mod tests {
         assert!(text.is_none());
     }
 
+    /// Answer one connection on a local port with `response` (a raw HTTP response,
+    /// or None to accept and never answer). Returns the base URL.
+    async fn serve_once(response: Option<&'static str>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            match response {
+                Some(response) => socket.write_all(response.as_bytes()).await.unwrap(),
+                None => tokio::time::sleep(Duration::from_secs(30)).await,
+            }
+        });
+        base
+    }
+
+    /// Like `serve_once`, but also hands back the raw request it received.
+    async fn serve_recording(response: &'static str) -> (String, tokio::sync::oneshot::Receiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let n = socket.read(&mut request).await.unwrap_or(0);
+            let _ = tx.send(String::from_utf8_lossy(&request[..n]).to_string());
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        (base, rx)
+    }
+
+    /// A base URL nothing listens on.
+    async fn closed_port() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[tokio::test]
+    async fn test_probe_claude() {
+        let client = Client::new();
+        let ok = serve_once(Some("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"data\":[]}")).await;
+        let endpoint = |base_url: &str| ClaudeEndpoint { base_url: base_url.to_string(), ..Default::default() };
+        let probe = probe_claude(&client, &endpoint(&ok), "sk-ant-test", TIMEOUT).await;
+        assert!(probe.reachable && probe.latency_ms.is_some());
+        assert_eq!((probe.authenticated, probe.error), (Some(true), None));
+
+        let rejected = serve_once(Some("HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n")).await;
+        let probe = probe_claude(&client, &endpoint(&rejected), "sk-ant-bad", TIMEOUT).await;
+        assert!(probe.reachable);
+        assert_eq!((probe.authenticated, probe.error.as_deref()), (Some(false), Some("Invalid API key")));
+
+        let overloaded = serve_once(Some("HTTP/1.1 529 Overloaded\r\nContent-Length: 0\r\n\r\n")).await;
+        let probe = probe_claude(&client, &endpoint(&overloaded), "sk-ant-test", TIMEOUT).await;
+        assert_eq!(probe.authenticated, None);
+        assert!(probe.error.unwrap().contains("529"));
+
+        let probe = probe_claude(&client, &endpoint(&closed_port().await), "sk-ant-test", TIMEOUT).await;
+        assert!(!probe.reachable);
+        assert_eq!((probe.latency_ms, probe.authenticated), (None, None));
+    }
+
+    #[test]
+    fn test_claude_endpoint_url() {
+        let endpoint = |base_url: &str| ClaudeEndpoint { base_url: base_url.to_string(), ..Default::default() };
+        assert_eq!(ClaudeEndpoint::default().url("/v1/messages"), "https://api.anthropic.com/v1/messages");
+        assert_eq!(endpoint("https://gw.corp/").url("/v1/messages"), "https://gw.corp/v1/messages");
+        assert_eq!(endpoint("https://gw.corp/anthropic//").url("/v1/messages"), "https://gw.corp/anthropic/v1/messages");
+    }
+
+    #[test]
+    fn test_parse_claude_settings() {
+        assert_eq!(parse_claude_base_url("").unwrap(), ANTHROPIC_API_BASE);
+        assert_eq!(parse_claude_base_url(" https://gw.corp/anthropic/ ").unwrap(), "https://gw.corp/anthropic/");
+        assert!(parse_claude_base_url("gw.corp").is_err());
+        assert!(parse_claude_base_url("ftp://gw.corp").is_err());
+        assert!(parse_claude_base_url("https://gw.corp/?key=x").is_err());
+
+        assert!(parse_extra_headers("").unwrap().is_empty());
+        let headers = parse_extra_headers(r#"{"X-Gateway-Token": "tok-123", "anthropic-version": "2024-01-01"}"#).unwrap();
+        assert_eq!(headers.get("x-gateway-token").unwrap(), "tok-123");
+        assert!(parse_extra_headers("[]").is_err());
+        assert!(parse_extra_headers(r#"{"bad header": "x"}"#).unwrap_err().contains("header name"));
+        assert!(parse_extra_headers(r#"{"X-Retries": 3}"#).unwrap_err().contains("string"));
+        assert!(parse_extra_headers(r#"{"Content-Type": "text/plain"}"#).is_err());
+        // Invalid values are rejected without echoing them
+        let err = parse_extra_headers(r#"{"X-Token": "sec\nret"}"#).unwrap_err();
+        assert!(!err.contains("sec"));
+    }
+
+    #[tokio::test]
+    async fn test_claude_endpoint_injects_headers() {
+        let (base, request) = serve_recording("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"data\":[]}").await;
+        let endpoint = ClaudeEndpoint {
+            base_url: format!("{}/anthropic/", base),
+            extra_headers: parse_extra_headers(r#"{"X-Gateway-Token": "tok-123", "anthropic-version": "2024-01-01"}"#).unwrap(),
+        };
+        validate_api_key(&Client::new(), &endpoint, "sk-ant-test").await.unwrap();
+
+        let request = request.await.unwrap().to_ascii_lowercase();
+        assert!(request.starts_with("get /anthropic/v1/models?limit=1 http/1.1\r\n"), "{}", request);
+        assert!(request.contains("x-gateway-token: tok-123\r\n"));
+        assert!(request.contains("x-api-key: sk-ant-test\r\n"));
+        // The override replaces the default version instead of sending both
+        assert!(request.contains("anthropic-version: 2024-01-01\r\n"));
+        assert!(!request.contains(ANTHROPIC_VERSION));
+
+        assert_eq!(
+            endpoint.redact("upstream rejected token tok-123 (x-api-key: sk-ant-test)"),
+            "upstream rejected token [REDACTED] (x-api-key: [REDACTED])"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_ollama() {
+        let client = Client::new();
+        let ok = serve_once(Some("HTTP/1.1 200 OK\r\nContent-Length: 36\r\n\r\n{\"models\":[{\"name\":\"llava:latest\"}]}")).await;
+        let (probe, models) = probe_ollama(&client, &ok, TIMEOUT).await;
+        assert!(probe.reachable && probe.error.is_none() && probe.authenticated.is_none());
+        assert_eq!(models, vec!["llava:latest"]);
+
+        let (probe, models) = probe_ollama(&client, &serve_once(Some("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nno")).await, TIMEOUT).await;
+        assert!(probe.reachable);
+        assert!(probe.error.unwrap().starts_with("Unexpected response"));
+        assert!(models.is_empty());
+
+        let (probe, _) = probe_ollama(&client, &closed_port().await, TIMEOUT).await;
+        assert!(!probe.reachable);
+
+        // A server that never answers is reported unreachable once the timeout passes
+        let hung = serve_once(None).await;
+        let (probe, _) = probe_ollama(&client, &hung, Duration::from_millis(200)).await;
+        assert!(!probe.reachable);
+    }
+
+    #[test]
+    fn test_extract_json_object_strips_think_blocks() {
+        let json = r#"{"task_title": "Coding", "is_new_task": true}"#;
+        assert_eq!(extract_json_object(&format!("<think>\nThe editor shows {{ braces }}.\n</think>\n{json}")), json);
+        assert_eq!(extract_json_object(&format!("<think>a</think><think>b</think>```json\n{json}\n```")), json);
+        // Chat templates that open the block themselves only emit the closing tag
+        assert_eq!(extract_json_object(&format!("The user is coding.\n</think>\n\n{json}")), json);
+    }
+
+    #[test]
+    fn test_extract_json_object_skips_reasoning_prose() {
+        let json = r#"{"task_title": "Fix \"}\" bug", "monitor_summaries": {"DP-1": "editor"}}"#;
+        assert_eq!(extract_json_object(&format!("Let me look at the screen.\nHere is my answer: {json}\nHope that helps!")), json);
+        assert_eq!(extract_json_object(json), json);
+        // Nothing to extract: returned as is so the parse error shows it
+        assert_eq!(extract_json_object("  no json here "), "no json here");
+        assert_eq!(extract_json_object("prefix {\"truncated\": "), "{\"truncated\":");
+
+        let parsed: TaskAnalysis = serde_json::from_str(&extract_json_object(
+            "<think>Terminal and editor open.</think>\nSure! {\"task_title\": \"Coding\", \"task_description\": \"d\", \
+             \"category\": \"coding\", \"reasoning\": \"r\", \"is_new_task\": false}",
+        )).unwrap();
+        assert_eq!(parsed.task_title, "Coding");
+        assert!(parse_merge_groups("<think>0 and 1 match</think>{\"merge_groups\": [[0, 1]]}", 3).is_ok());
+    }
+
     #[test]
     fn test_strip_code_fences() {
         assert_eq!(strip_code_fences("hello"), "hello");
@@ -709,14 +1933,60 @@ mod tests {
 
     #[test]
     fn test_build_prompt_no_context() {
-        let prompt = build_prompt(&[], None);
+        let prompt = build_prompt(PromptContext::default(), None, None, &[]).unwrap();
         assert!(prompt.contains("Analyze this screenshot"));
         assert!(prompt.contains("task_title"));
     }
 
+    #[test]
+    fn test_build_prompt_with_session_task_list() {
+        let recent = vec!["Coding: Editing ai.rs".to_string()];
+        let tasks = vec!["Fixing login bug (coding)".to_string(), "Reading API docs (browsing)".to_string()];
+        let prompt = build_prompt(PromptContext { recent: &recent, session_tasks: &tasks, ..Default::default() }, None, None, &[]).unwrap();
+        assert!(prompt.contains("1. Coding: Editing ai.rs\n"));
+        assert!(prompt.contains("Tasks already identified in this session:\n  - Fixing login bug (coding)\n  - Reading API docs (browsing)\n"));
+        assert!(prompt.contains("\"continues_task_title\""));
+
+        let without = build_prompt(PromptContext { recent: &recent, ..Default::default() }, None, None, &[]).unwrap();
+        assert!(!without.contains("continues_task_title"));
+    }
+
+    #[test]
+    fn test_build_prompt_with_carried_tasks() {
+        let titles = vec!["Writing report".to_string(), "Reviewing PRs".to_string()];
+        let carried = CarriedTasks { titles: &titles, gap_minutes: 65 };
+        let prompt = build_prompt(PromptContext { carried: Some(carried), ..Default::default() }, None, None, &[]).unwrap();
+        assert!(prompt.contains(
+            "The user recently worked on: Writing report; Reviewing PRs (in a previous session that ended 1h 05m before this one started).\n"
+        ));
+        assert_eq!(format_gap(1), "1 minute");
+        assert_eq!(format_gap(45), "45 minutes");
+
+        let none = CarriedTasks { titles: &[], gap_minutes: 5 };
+        let prompt = build_prompt(PromptContext { carried: Some(none), ..Default::default() }, None, None, &[]).unwrap();
+        assert!(!prompt.contains("recently worked on"));
+    }
+
+    #[test]
+    fn test_build_prompt_with_activity() {
+        let activity = vec!["Firefox: PR #12 - GitHub".to_string(), "main.rs".to_string()];
+        let prompt = build_prompt(PromptContext { activity: &activity, ..Default::default() }, None, None, &[]).unwrap();
+        assert!(prompt.contains("(from their activity log):\n  - Firefox: PR #12 - GitHub\n  - main.rs\n"));
+
+        let without = build_prompt(PromptContext::default(), None, None, &[]).unwrap();
+        assert!(!without.contains("activity log"));
+    }
+
+    #[test]
+    fn test_build_prompt_with_overlap() {
+        let overlap = vec!["10:01:30 main.rs - Editor → Coding".to_string()];
+        let prompt = build_prompt(PromptContext { overlap: &overlap, ..Default::default() }, None, None, &[]).unwrap();
+        assert!(prompt.contains("ended with these captures, oldest first:\n  - 10:01:30 main.rs - Editor → Coding\n"));
+    }
+
     #[test]
     fn test_build_prompt_with_session() {
-        let prompt = build_prompt(&[], Some("writing a blog post"));
+        let prompt = build_prompt(PromptContext::default(), Some("writing a blog post"), None, &[]).unwrap();
         assert!(prompt.contains("writing a blog post"));
     }
 
@@ -729,6 +1999,17 @@ mod tests {
                 width: 1920,
                 height: 1080,
                 is_primary: true,
+                previous_active: false,
+                scale_factor: 1.0,
+            },
+            ChangedMonitor {
+                monitor_name: "DISPLAY3",
+                image_path: Path::new("left.webp"),
+                width: 2560,
+                height: 1440,
+                is_primary: false,
+                previous_active: true,
+                scale_factor: 1.0,
             },
         ];
         let unchanged = vec![
@@ -737,12 +2018,81 @@ mod tests {
                 summary: "Browser with docs",
             },
         ];
-        let prompt = build_multi_prompt(&changed, &unchanged, &[], None, 2);
-        assert!(prompt.contains("2 monitors"));
+        let prompt = build_multi_prompt(&changed, &unchanged, PromptContext::default(), None, 3, None, &[]).unwrap();
+        assert!(prompt.contains("3 monitors"));
+        assert!(prompt.contains("- Monitor \"DISPLAY1\" (1920x1080, primary): see image 1"));
+        assert!(prompt.contains("- Monitor \"DISPLAY3\" (2560x1440, previously active — the user just switched away from it): see image 2"));
         assert!(prompt.contains("DISPLAY1"));
         assert!(prompt.contains("1920x1080"));
         assert!(prompt.contains("DISPLAY2"));
         assert!(prompt.contains("Browser with docs"));
         assert!(prompt.contains("monitor_summaries"));
     }
+
+    #[test]
+    fn test_build_prompt_with_template() {
+        let contexts = vec!["Coding: Editing ai.rs".to_string()];
+        let prompt = build_prompt(
+            PromptContext { recent: &contexts, ..Default::default() },
+            Some("shipping v2"),
+            Some("Always mention the language. Goal: {session_description}\n{context_section}{json_schema}"),
+            &[],
+        ).unwrap();
+        assert!(prompt.starts_with("Always mention the language. Goal: shipping v2\n"));
+        assert!(prompt.contains("1. Coding: Editing ai.rs"));
+        assert!(prompt.contains("\"is_new_task\": true/false"));
+
+        let err = build_prompt(PromptContext::default(), None, Some("{language}"), &[]).unwrap_err();
+        assert_eq!(err, TemplateError::UnknownPlaceholder("language".to_string()));
+    }
+
+    #[test]
+    fn test_build_multi_prompt_ollama_with_template() {
+        let changed = vec![ChangedMonitor {
+            monitor_name: "DISPLAY1",
+            image_path: Path::new("test.webp"),
+            width: 1920,
+            height: 1080,
+            is_primary: true,
+            previous_active: false,
+            scale_factor: 1.0,
+        }];
+        let prompt = build_multi_prompt_ollama(&changed, &[], PromptContext::default(), None, 1, Some("{monitors_section}|{categories}|{json_schema}")).unwrap();
+        assert!(prompt.contains("- Monitor \"DISPLAY1\" (1920x1080, primary): see image 1"));
+        assert!(prompt.contains("|coding|browsing|writing|communication|design|other|"));
+        assert!(prompt.ends_with(OLLAMA_JSON_INSTRUCTION));
+    }
+
+    #[test]
+    fn test_redact_secrets_anthropic_error_payloads() {
+        // Plain Anthropic auth error: mentions the header but carries no secret
+        let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        assert_eq!(redact_secrets(body), body);
+
+        // A proxy echoing request headers back as JSON
+        let body = r#"{"error":"bad gateway","request_headers":{"x-api-key":"sk-ant-api03-AbC_123-xyz","anthropic-version":"2023-06-01"}}"#;
+        assert_eq!(
+            redact_secrets(body),
+            r#"{"error":"bad gateway","request_headers":{"x-api-key":"[REDACTED]","anthropic-version":"2023-06-01"}}"#
+        );
+
+        // Raw header dump
+        let body = "X-Api-Key: sk-ant-api03-secret\nAuthorization: Bearer abc.def.ghi\ncontent-type: application/json";
+        assert_eq!(
+            redact_secrets(body),
+            "X-Api-Key: [REDACTED]\nAuthorization: Bearer [REDACTED]\ncontent-type: application/json"
+        );
+    }
+
+    #[test]
+    fn test_redact_secrets_bare_keys_and_query_strings() {
+        assert_eq!(
+            redact_secrets("request with sk-ant-api03-Zz9_-Q failed"),
+            "request with [REDACTED] failed"
+        );
+        assert_eq!(redact_secrets("GET /v1?api_key=s3cret&model=x"), "GET /v1?api_key=[REDACTED]&model=x");
+        // Mentioning a key name without a value leaves the text alone
+        assert_eq!(redact_secrets("x-api-key header is required"), "x-api-key header is required");
+        assert_eq!(redact_secrets("überlastet: sk-ant-abc"), "überlastet: [REDACTED]");
+    }
 }
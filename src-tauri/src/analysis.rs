@@ -0,0 +1,2409 @@
+use crate::activity;
+use crate::ai::{self, AiError, CarriedTasks, ChangedMonitor, ConsolidationTask, PromptContext, PromptTemplates, SentInputs, TaskAnalysis, UnchangedMonitor};
+use crate::coalesce::{LogCoalescer, LOG_REPEAT_WINDOW};
+use crate::commands::{lock_recovered, MonitorState};
+use crate::corrections;
+use crate::evaluation;
+use crate::image_cache::ImageCache;
+use crate::permissions;
+use crate::models::{AnalysisProgress, CategoryCorrection, Screenshot, Task};
+use crate::storage::Database;
+use crate::time;
+use log::{debug, error, info, warn};
+use reqwest::Client;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Everything a provider needs to analyze one capture group.
+pub struct AnalysisRequest<'a> {
+    pub changed: &'a [ChangedMonitor<'a>],
+    pub unchanged: &'a [UnchangedMonitor<'a>],
+    pub previous_contexts: &'a [String],
+    /// "Title (category)" of the session's tasks so far; empty unless `include_session_task_list`.
+    pub session_tasks: &'a [String],
+    pub session_description: Option<&'a str>,
+    pub image_mode: &'a str,
+    pub templates: PromptTemplates<'a>,
+    pub custom_fields: &'a [String],
+    /// Language for the human-readable values (`prompt_language`); None is English.
+    pub language: Option<&'a str>,
+    /// The previous session's last tasks, for a session without its own yet.
+    pub carried: Option<CarriedTasks<'a>>,
+    /// Activity log lines recorded around the capture group (`activity::prompt_line`).
+    pub activity: &'a [String],
+    /// The previous batch's last screenshots (`overlap_lines`), for a batch's first group.
+    pub overlap: &'a [String],
+    /// The run's preprocessed images, reused when a file is sent again.
+    pub images: Option<&'a ImageCache>,
+    /// Filled with the rendered prompt and preprocessed images (`save_analysis_inputs`).
+    pub sent: Option<&'a mut SentInputs>,
+}
+
+pub type AnalysisFuture<'a> = Pin<Box<dyn Future<Output = Result<TaskAnalysis, AiError>> + Send + 'a>>;
+
+/// Groups of task indices to merge, from `Provider::suggest_merges`.
+pub type MergeFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Vec<usize>>, AiError>> + Send + 'a>>;
+
+/// One-line session descriptions, from `Provider::suggest_descriptions`.
+pub type SuggestionFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<String>, AiError>> + Send + 'a>>;
+
+/// An AI backend that turns a capture group into a task analysis.
+/// Boxed futures keep the trait object-safe so the orchestrator can take `&dyn Provider`.
+pub trait Provider: Send + Sync {
+    fn analyze<'a>(&'a self, req: AnalysisRequest<'a>) -> AnalysisFuture<'a>;
+
+    /// Text-only consolidation request: which adjacent `tasks` should be merged.
+    fn suggest_merges<'a>(&'a self, tasks: &'a [ConsolidationTask<'a>]) -> MergeFuture<'a>;
+
+    /// Text-only request: descriptions for a new session continuing `previous`.
+    fn suggest_descriptions<'a>(&'a self, previous: ai::PreviousSession<'a>) -> SuggestionFuture<'a>;
+
+    /// "provider/model" label recorded as `analyzed_by` in task metadata.
+    fn analyzed_by(&self) -> String;
+}
+
+pub struct ClaudeProvider {
+    pub client: Client,
+    pub endpoint: ai::ClaudeEndpoint,
+    pub api_key: String,
+    /// Log raw response bodies (`log_ai_responses`).
+    pub log_responses: bool,
+}
+
+impl Provider for ClaudeProvider {
+    fn analyze<'a>(&'a self, req: AnalysisRequest<'a>) -> AnalysisFuture<'a> {
+        Box::pin(ai::analyze_capture(
+            &self.client,
+            &self.endpoint,
+            &self.api_key,
+            req.changed,
+            req.unchanged,
+            PromptContext { recent: req.previous_contexts, session_tasks: req.session_tasks, carried: req.carried, activity: req.activity, overlap: req.overlap },
+            req.session_description,
+            req.image_mode,
+            req.templates,
+            req.custom_fields,
+            req.language,
+            self.log_responses,
+            req.images,
+            req.sent,
+        ))
+    }
+
+    fn suggest_merges<'a>(&'a self, tasks: &'a [ConsolidationTask<'a>]) -> MergeFuture<'a> {
+        Box::pin(ai::suggest_merges(&self.client, &self.endpoint, &self.api_key, tasks, self.log_responses))
+    }
+
+    fn suggest_descriptions<'a>(&'a self, previous: ai::PreviousSession<'a>) -> SuggestionFuture<'a> {
+        Box::pin(async move { ai::suggest_descriptions(&self.client, &self.endpoint, &self.api_key, &previous, self.log_responses).await })
+    }
+
+    fn analyzed_by(&self) -> String {
+        format!("claude/{}", ai::CLAUDE_MODEL)
+    }
+}
+
+pub struct OllamaProvider {
+    pub client: Client,
+    pub model: String,
+    /// Log raw response bodies (`log_ai_responses`).
+    pub log_responses: bool,
+}
+
+impl Provider for OllamaProvider {
+    fn analyze<'a>(&'a self, req: AnalysisRequest<'a>) -> AnalysisFuture<'a> {
+        Box::pin(ai::analyze_capture_ollama(
+            &self.client,
+            &self.model,
+            req.changed,
+            req.unchanged,
+            PromptContext { recent: req.previous_contexts, session_tasks: req.session_tasks, carried: req.carried, activity: req.activity, overlap: req.overlap },
+            req.session_description,
+            req.image_mode,
+            req.templates,
+            req.custom_fields,
+            req.language,
+            self.log_responses,
+            req.images,
+            req.sent,
+        ))
+    }
+
+    fn suggest_merges<'a>(&'a self, tasks: &'a [ConsolidationTask<'a>]) -> MergeFuture<'a> {
+        Box::pin(ai::suggest_merges_ollama(&self.client, &self.model, tasks, self.log_responses))
+    }
+
+    fn suggest_descriptions<'a>(&'a self, previous: ai::PreviousSession<'a>) -> SuggestionFuture<'a> {
+        Box::pin(async move { ai::suggest_descriptions_ollama(&self.client, &self.model, &previous, self.log_responses).await })
+    }
+
+    fn analyzed_by(&self) -> String {
+        format!("ollama/{}", self.model)
+    }
+}
+
+/// Embedding vector of a task description, from `Embedder::embed`.
+pub type EmbedFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<f32>, AiError>> + Send + 'a>>;
+
+/// Computes text embeddings for similarity-based continuation (`embedding_model`).
+pub trait Embedder: Send + Sync {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a>;
+    /// Identifies the vectors' space, so cached ones from another model are recomputed.
+    fn model(&self) -> &str;
+}
+
+pub struct OllamaEmbedder {
+    pub client: Client,
+    pub model: String,
+}
+
+impl Embedder for OllamaEmbedder {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a> {
+        Box::pin(ai::embed_ollama(&self.client, &self.model, text))
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// The embedder for `embedding_model`, or None when it's unset.
+pub fn embedder_from_settings(db: &Database) -> Result<Option<OllamaEmbedder>, String> {
+    let model = db.get_setting_string("embedding_model", "").map_err(|e| e.to_string())?;
+    let model = model.trim();
+    Ok((!model.is_empty()).then(|| OllamaEmbedder { client: Client::new(), model: model.to_string() }))
+}
+
+/// Cosine similarity of two embeddings; 0 if their lengths differ or either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// The candidate task most similar to `embedding`, if its similarity reaches
+/// `threshold`. Candidates are most recent first, so ties go to the most recent.
+pub fn most_similar_task(embedding: &[f32], candidates: &[(i64, Vec<f32>)], threshold: f32) -> Option<(i64, f32)> {
+    candidates
+        .iter()
+        .map(|(id, other)| (*id, cosine_similarity(embedding, other)))
+        .filter(|(_, similarity)| *similarity >= threshold)
+        .fold(None, |best: Option<(i64, f32)>, (id, similarity)| match best {
+            Some((_, b)) if b >= similarity => best,
+            _ => Some((id, similarity)),
+        })
+}
+
+/// Recent session tasks compared against a new task's embedding.
+pub const EMBEDDING_CANDIDATES: i64 = 5;
+
+/// Text embedded for a task: the same "title: description" used as prompt context.
+fn embedding_text(title: &str, description: &str) -> String {
+    format!("{}: {}", title, description)
+}
+
+/// Embed a would-be new task and look for a recent session task it actually continues.
+/// Returns the match (if any) and the embedding, to cache on the task if one is created.
+/// Candidate embeddings missing from the cache are computed and stored on the way.
+async fn similar_session_task(
+    db: &Database,
+    embedder: &dyn Embedder,
+    session_id: i64,
+    text: &str,
+    threshold: f32,
+) -> Result<(Option<(i64, f32)>, Vec<f32>), AiError> {
+    let embedding = embedder.embed(text).await?;
+    let recent = db.get_recent_tasks_for_session(session_id, EMBEDDING_CANDIDATES).unwrap_or_else(|e| {
+        warn!("Failed to load recent tasks of session {}: {}", session_id, e);
+        Vec::new()
+    });
+    let mut candidates = Vec::with_capacity(recent.len());
+    for task in recent {
+        let cached = db.get_task_embedding(task.id, embedder.model()).unwrap_or(None);
+        let task_embedding = match cached {
+            Some(e) => e,
+            None => {
+                let e = embedder.embed(&embedding_text(&task.title, task.description.as_deref().unwrap_or(""))).await?;
+                if let Err(err) = db.set_task_embedding(task.id, embedder.model(), &e) {
+                    warn!("Failed to cache embedding of task {}: {}", task.id, err);
+                }
+                e
+            }
+        };
+        candidates.push((task.id, task_embedding));
+    }
+    Ok((most_similar_task(&embedding, &candidates, threshold), embedding))
+}
+
+/// The Claude endpoint configured by `claude_base_url` and `claude_extra_headers`.
+pub fn claude_endpoint(db: &Database) -> Result<ai::ClaudeEndpoint, String> {
+    let base_url = db.get_setting_string("claude_base_url", ai::ANTHROPIC_API_BASE).map_err(|e| e.to_string())?;
+    let headers = db.get_setting_string("claude_extra_headers", "").map_err(|e| e.to_string())?;
+    Ok(ai::ClaudeEndpoint {
+        base_url: ai::parse_claude_base_url(&base_url).map_err(|e| format!("Invalid claude_base_url: {}", e))?,
+        extra_headers: ai::parse_extra_headers(&headers).map_err(|e| format!("Invalid claude_extra_headers: {}", e))?,
+    })
+}
+
+/// Build the provider named by the `ai_provider` setting ("ollama", anything else is Claude).
+pub fn provider_from_settings(db: &Database, provider: &str) -> Result<Box<dyn Provider>, String> {
+    let client = Client::new();
+    let log_responses = db.get_setting_bool("log_ai_responses", false).map_err(|e| e.to_string())?;
+    if provider == "ollama" {
+        let model = db.get_setting_string("ollama_model", "").map_err(|e| e.to_string())?;
+        Ok(Box::new(OllamaProvider { client, model, log_responses }))
+    } else {
+        let api_key = db.get_setting("ai_api_key")
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "No API key configured".to_string())?;
+        let endpoint = claude_endpoint(db)?;
+        Ok(Box::new(ClaudeProvider { client, endpoint, api_key, log_responses }))
+    }
+}
+
+/// Which screenshots of a capture group are sent to the AI (`analyze_monitor_scope`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorScope {
+    /// Every screenshot in the group. Groups only hold monitors that changed on that
+    /// tick, so this currently matches `ChangedOnly`.
+    All,
+    /// Only the primary monitor's screenshot; the others are linked to its task.
+    PrimaryOnly,
+    /// Only monitors that changed on that tick.
+    ChangedOnly,
+}
+
+impl MonitorScope {
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "primary_only" => MonitorScope::PrimaryOnly,
+            "changed_only" => MonitorScope::ChangedOnly,
+            _ => MonitorScope::All,
+        }
+    }
+}
+
+/// Split a group into screenshots to analyze and screenshots that are only linked
+/// to the resulting task, by the primary flag recorded at capture. A group with no
+/// primary screenshot but some unrecorded flags (captured before they were recorded)
+/// is analyzed whole rather than held back.
+pub fn scope_group<'a>(
+    group: &[&'a Screenshot],
+    scope: MonitorScope,
+) -> (Vec<&'a Screenshot>, Vec<&'a Screenshot>) {
+    match scope {
+        MonitorScope::All | MonitorScope::ChangedOnly => (group.to_vec(), Vec::new()),
+        MonitorScope::PrimaryOnly => {
+            let has_primary = group.iter().any(|ss| ss.is_primary == Some(true));
+            if !has_primary && group.iter().any(|ss| ss.is_primary.is_none()) {
+                return (group.to_vec(), Vec::new());
+            }
+            group.iter().partition(|ss| ss.is_primary == Some(true))
+        }
+    }
+}
+
+/// Split off screenshots that mirror another screenshot in the group (`mirrored_of`),
+/// which are linked to the group's task without being sent to the AI. A mirror whose
+/// original isn't in the group is analyzed as usual.
+pub fn split_mirrored<'a>(group: &[&'a Screenshot]) -> (Vec<&'a Screenshot>, Vec<&'a Screenshot>) {
+    let ids: HashSet<i64> = group.iter().map(|ss| ss.id).collect();
+    group.iter().partition(|ss| !ss.mirrored_of.is_some_and(|of| ids.contains(&of)))
+}
+
+/// Name and last summary of each monitor not in `group` that has a summary, for the
+/// prompt's unchanged-monitors section. Empty when `include` is off
+/// (`include_unchanged_context`), which drops that section from the prompt.
+pub fn unchanged_summaries(
+    monitor_states: &HashMap<u32, MonitorState>,
+    group: &[&Screenshot],
+    include: bool,
+) -> Vec<(String, String)> {
+    if !include {
+        return Vec::new();
+    }
+    let group_monitor_ids: HashSet<i32> = group.iter().map(|ss| ss.monitor_index).collect();
+    monitor_states
+        .iter()
+        .filter(|(id, _)| !group_monitor_ids.contains(&(**id as i32)))
+        .filter(|(_, s)| !s.last_summary.is_empty())
+        .map(|(_, s)| (s.name.clone(), s.last_summary.clone()))
+        .collect()
+}
+
+/// Per-run inputs for `run_analysis`.
+pub struct AnalysisOptions<'a> {
+    pub session_id: Option<i64>,
+    pub session_description: Option<&'a str>,
+    pub image_mode: &'a str,
+    /// Analyze every Nth capture group (see `analysis_sampling`).
+    pub sampling: usize,
+    pub screenshots_dir: &'a Path,
+    pub templates: PromptTemplates<'a>,
+    pub monitor_scope: MonitorScope,
+    /// Extra string fields requested from the AI (see `custom_fields`).
+    pub custom_fields: &'a [String],
+    /// Language for the human-readable values (`prompt_language`); None is English.
+    pub language: Option<&'a str>,
+    /// Describe unchanged monitors to the AI (`include_unchanged_context`).
+    pub include_unchanged_context: bool,
+    /// Recent task contexts sent with each request (`context_depth`).
+    pub context_depth: usize,
+    /// List the session's tasks in the prompt (`include_session_task_list`).
+    pub include_session_task_list: bool,
+    /// Run a consolidation pass after every this many analyzed groups; 0 is off
+    /// (`consolidate_every`).
+    pub consolidate_every: usize,
+    /// Links a would-be new task to a similar recent session task (`embedding_model`);
+    /// None is off.
+    pub embedder: Option<&'a dyn Embedder>,
+    /// Cosine similarity that counts as a match (`embedding_similarity_threshold`).
+    pub similarity_threshold: f32,
+    /// Called after each capture group's screenshots are linked to a task.
+    pub on_progress: Option<&'a (dyn Fn(&AnalysisProgress) + Send + Sync)>,
+    /// Called when the provider fails to analyze a capture group.
+    pub on_error: Option<&'a (dyn Fn(&AiError) + Send + Sync)>,
+    /// Start a session that has no tasks yet from the previous session's last tasks
+    /// when it ended at most this many minutes earlier (`carry_context_from_previous`);
+    /// None is off.
+    pub carry_context_window: Option<u64>,
+    /// Record what each request sent (`save_analysis_inputs`); None is off.
+    pub save_inputs: Option<SaveInputs<'a>>,
+    /// Also write the images each request sent next to the screenshots, in
+    /// `ANALYSIS_IMAGES_DIR` (`keep_analysis_images`).
+    pub keep_images: bool,
+    /// Describe this many of the session's last analyzed screenshots before this batch to
+    /// its first capture group (`context_overlap`); 0 is off.
+    pub context_overlap: usize,
+    /// Re-analyzing captures from the middle of the session: recent task contexts and
+    /// the task a continuation joins come from tasks started before this time rather
+    /// than the session's latest ones. None for a normal run.
+    pub context_before: Option<&'a str>,
+}
+
+/// Directory under the app data dir holding the images `save_analysis_inputs` records.
+pub const ANALYSIS_DEBUG_DIR: &str = "analysis_debug";
+
+/// Where `save_analysis_inputs` records what each request sent.
+#[derive(Debug, Clone, Copy)]
+pub struct SaveInputs<'a> {
+    /// The app data dir; images go in its `ANALYSIS_DEBUG_DIR`.
+    pub data_dir: &'a Path,
+    /// The run's start time. Identifies the run in `analysis_debug` and is the rows'
+    /// `created_at` for the retention sweep.
+    pub run_id: &'a str,
+}
+
+/// Write the images a request sent to `analysis_debug/<run>/<screenshot id>.webp` on
+/// the blocking pool, then record them with the prompt. Each run gets its own
+/// directory, so re-analysis never overwrites images older records point to. Failures
+/// are only logged; they never fail the analysis.
+async fn save_sent_inputs(db: &Database, save: SaveInputs<'_>, capture_group: &str, screenshot_ids: &[i64], sent: SentInputs) {
+    if sent.prompt.is_empty() {
+        // The request failed before its prompt was built, so nothing was sent
+        return;
+    }
+    // The run id is a timestamp; colons aren't allowed in Windows file names
+    let run_dir = format!("{}/{}", ANALYSIS_DEBUG_DIR, save.run_id.replace(':', "-"));
+    let files: Vec<(String, Vec<u8>)> = screenshot_ids.iter()
+        .zip(sent.images)
+        .map(|(id, bytes)| (format!("{}/{}.webp", run_dir, id), bytes))
+        .collect();
+    let paths: Vec<String> = files.iter().map(|(path, _)| path.clone()).collect();
+    let data_dir = save.data_dir.to_path_buf();
+    let written = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        let dir = data_dir.join(ANALYSIS_DEBUG_DIR);
+        std::fs::create_dir_all(&dir)?;
+        permissions::restrict_dir(&dir);
+        std::fs::create_dir_all(data_dir.join(run_dir))?;
+        for (path, bytes) in files {
+            std::fs::write(data_dir.join(path), bytes)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result.map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        warn!("Failed to save analysis inputs of {}: {}", capture_group, e);
+        return;
+    }
+    if let Err(e) = db.insert_analysis_debug(save.run_id, capture_group, &sent.prompt, &paths, save.run_id) {
+        warn!("Failed to record analysis inputs of {}: {}", capture_group, e);
+    }
+}
+
+/// Subdirectory of the screenshots directory holding the images `keep_analysis_images`
+/// keeps.
+pub const ANALYSIS_IMAGES_DIR: &str = "analysis";
+
+/// Write the exact images a request sent to `screenshots/analysis/<screenshot id>.webp`,
+/// one per monitor, on the blocking pool, so what the AI saw can be compared with the
+/// capture. A re-analysis overwrites them. Failures are only logged.
+async fn keep_sent_images(screenshots_dir: &Path, capture_group: &str, screenshot_ids: &[i64], images: &[Vec<u8>]) {
+    let dir = screenshots_dir.join(ANALYSIS_IMAGES_DIR);
+    let files: Vec<(PathBuf, Vec<u8>)> = screenshot_ids.iter()
+        .zip(images)
+        .map(|(id, bytes)| (dir.join(format!("{}.webp", id)), bytes.clone()))
+        .collect();
+    if files.is_empty() {
+        return;
+    }
+    let written = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        for (path, bytes) in files {
+            std::fs::write(path, bytes)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result.map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        warn!("Failed to keep the analysis images of {}: {}", capture_group, e);
+    }
+}
+
+/// Delete the images `keep_analysis_images` kept for these screenshots, wherever
+/// screenshots leave the `screenshots` table (deleted or trashed): their ids can be
+/// reused, and a leftover image would then pass for a later screenshot's.
+pub fn remove_kept_images(screenshots_dir: &Path, screenshot_ids: &[i64]) {
+    let dir = screenshots_dir.join(ANALYSIS_IMAGES_DIR);
+    for id in screenshot_ids {
+        let path = dir.join(format!("{}.webp", id));
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                debug!("Could not remove analysis image {}: {}", path.display(), e);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Delete kept images last written before `cutoff` (Unix seconds), for the
+/// `analysis_debug_retention_days` sweep. Returns how many were removed.
+pub fn sweep_kept_images(screenshots_dir: &Path, cutoff: i64) -> usize {
+    let Ok(entries) = std::fs::read_dir(screenshots_dir.join(ANALYSIS_IMAGES_DIR)) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.metadata().and_then(|m| m.modified()).is_ok_and(|at| time::unix_secs(at) < cutoff))
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Group screenshots by capture_group. Screenshots with no group form individual groups.
+pub fn group_by_capture_group(screenshots: &[Screenshot]) -> Vec<Vec<&Screenshot>> {
+    let mut groups: BTreeMap<String, Vec<&Screenshot>> = BTreeMap::new();
+    let mut ungrouped = Vec::new();
+
+    for ss in screenshots {
+        match &ss.capture_group {
+            Some(group) => groups.entry(group.clone()).or_default().push(ss),
+            None => ungrouped.push(ss),
+        }
+    }
+
+    let mut result: Vec<Vec<&Screenshot>> = groups.into_values().collect();
+    for ss in ungrouped {
+        result.push(vec![ss]);
+    }
+    result
+}
+
+/// Pick every `every`th group for analysis. Each sampled group is paired with the
+/// skipped groups that follow it, which get linked to the sampled group's task.
+pub fn sample_groups<'a>(
+    groups: Vec<Vec<&'a Screenshot>>,
+    every: usize,
+) -> Vec<(Vec<&'a Screenshot>, Vec<&'a Screenshot>)> {
+    let every = every.max(1);
+    let mut sampled: Vec<(Vec<&'a Screenshot>, Vec<&'a Screenshot>)> = Vec::new();
+    for (i, group) in groups.into_iter().enumerate() {
+        if i % every == 0 {
+            sampled.push((group, Vec::new()));
+        } else if let Some((_, skipped)) = sampled.last_mut() {
+            skipped.extend(group);
+        }
+    }
+    sampled
+}
+
+/// Override an AI-assigned category with a rule learned from user corrections.
+/// Returns the category to store and, when overridden, the `category_override`
+/// metadata entry recording it.
+pub fn apply_category_correction(title: &str, ai_category: &str, rules: &[CategoryCorrection]) -> (String, Option<serde_json::Value>) {
+    match corrections::find_match(title, rules) {
+        Some(rule) if rule.category != ai_category => {
+            let entry = serde_json::json!({
+                "ai_category": ai_category,
+                "category": rule.category,
+                "rule_id": rule.id,
+            });
+            (rule.category.clone(), Some(entry))
+        }
+        _ => (ai_category.to_string(), None),
+    }
+}
+
+/// Metadata JSON stored on AI-created tasks. Of the response's extra fields only
+/// the configured `custom_fields` are kept, under `custom_fields`.
+fn task_metadata(
+    analyzed_by: &str,
+    category_override: Option<serde_json::Value>,
+    extra: &HashMap<String, String>,
+    custom_fields: &[String],
+) -> String {
+    let mut metadata = serde_json::json!({ "analyzed_by": analyzed_by });
+    if let Some(entry) = category_override {
+        metadata["category_override"] = entry;
+    }
+    let custom: serde_json::Map<String, serde_json::Value> = custom_fields
+        .iter()
+        .filter_map(|f| extra.get(f).map(|v| (f.clone(), serde_json::Value::from(v.as_str()))))
+        .collect();
+    if !custom.is_empty() {
+        metadata["custom_fields"] = serde_json::Value::Object(custom);
+    }
+    metadata.to_string()
+}
+
+/// Subdirectory of the screenshots directory holding quarantined screenshots.
+pub const CORRUPT_DIR: &str = "corrupt";
+
+/// Check that a screenshot's file decodes. A file that exists but can't be decoded
+/// (e.g. truncated by a crash mid-write) would fail analysis forever, so it is moved
+/// into `corrupt/` and its row marked excluded. Returns whether the screenshot is usable.
+/// Unreadable files are left to the provider, which reports them as before.
+pub fn decode_or_quarantine(db: &Database, screenshots_dir: &Path, ss: &Screenshot) -> bool {
+    let filename = ss.filepath
+        .strip_prefix("screenshots/")
+        .unwrap_or(&ss.filepath);
+    let path = screenshots_dir.join(filename);
+    let Ok(bytes) = std::fs::read(&path) else {
+        return true;
+    };
+    let Err(e) = image::load_from_memory(&bytes) else {
+        return true;
+    };
+
+    warn!("Quarantining screenshot {} ({}): {}", ss.id, path.display(), e);
+    let corrupt_dir = screenshots_dir.join(CORRUPT_DIR);
+    let file_name = Path::new(filename).file_name().unwrap_or_default();
+    let quarantined = std::fs::create_dir_all(&corrupt_dir)
+        .and_then(|_| std::fs::rename(&path, corrupt_dir.join(file_name)));
+    let filepath = match quarantined {
+        Ok(()) => format!("screenshots/{}/{}", CORRUPT_DIR, file_name.to_string_lossy()),
+        Err(e) => {
+            error!("Failed to move corrupt screenshot {}: {}", path.display(), e);
+            ss.filepath.clone()
+        }
+    };
+    if let Err(e) = db.quarantine_screenshot(ss.id, &filepath) {
+        error!("Failed to exclude corrupt screenshot {}: {}", ss.id, e);
+    }
+    false
+}
+
+/// Most tasks listed in the prompt under `include_session_task_list`; the most recent are kept.
+const SESSION_TASK_LIST_CAP: usize = 20;
+
+/// Minimum title keyword overlap for `continues_task_title` to match a task fuzzily.
+const CONTINUES_MATCH_THRESHOLD: f64 = 0.5;
+
+/// A task already identified in the session, as listed in the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTask {
+    pub id: i64,
+    pub title: String,
+    pub category: String,
+}
+
+impl From<Task> for SessionTask {
+    fn from(task: Task) -> Self {
+        SessionTask {
+            id: task.id,
+            title: task.title,
+            category: task.category.unwrap_or_else(|| "other".to_string()),
+        }
+    }
+}
+
+/// The session task (oldest first in `tasks`) a `continues_task_title` refers to: an
+/// exact case-insensitive title match, else the best title keyword overlap of at least
+/// `CONTINUES_MATCH_THRESHOLD`, ties going to the more recent task. None leaves the
+/// caller to its usual new-task / most-recent linking.
+pub fn resolve_continued_task(title: &str, tasks: &[SessionTask]) -> Option<i64> {
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+    if let Some(task) = tasks.iter().rev().find(|t| t.title.trim().eq_ignore_ascii_case(title)) {
+        return Some(task.id);
+    }
+    tasks
+        .iter()
+        .map(|t| (evaluation::title_overlap(title, &t.title), t))
+        .filter(|(score, _)| *score >= CONTINUES_MATCH_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, t)| t.id)
+}
+
+/// The suggested merge groups that may be applied to `tasks` (the list offered to the
+/// provider): runs of at least two adjacent, in-range tasks that aren't already claimed
+/// by an earlier group. Runs with a user-verified task are dropped, since merging
+/// would rewrite or delete a task the user confirmed.
+pub fn mergeable_groups(groups: &[Vec<usize>], tasks: &[Task]) -> Vec<Vec<usize>> {
+    let mut claimed = vec![false; tasks.len()];
+    let mut mergeable = Vec::new();
+    for group in groups {
+        let adjacent = group.len() >= 2 && group.windows(2).all(|pair| pair[1] == pair[0] + 1);
+        let in_range = group.iter().all(|&i| i < tasks.len());
+        if !adjacent || !in_range || group.iter().any(|&i| claimed[i]) {
+            warn!("Ignoring invalid merge group {:?}", group);
+            continue;
+        }
+        if group.iter().any(|&i| tasks[i].user_verified) {
+            info!("Not merging {:?}: it includes a user-verified task", group);
+            continue;
+        }
+        for &i in group {
+            claimed[i] = true;
+        }
+        mergeable.push(group.clone());
+    }
+    mergeable
+}
+
+/// Consolidation pass (`consolidate_every`): offer the session's last `window` tasks to
+/// the provider and merge each mergeable run it suggests into the run's first task,
+/// recording the merge under `consolidated` in that task's metadata. Failures are
+/// logged and leave the tasks alone. Returns merged task id → kept task id.
+pub async fn consolidate_tasks(db: &Database, provider: &dyn Provider, session_id: i64, window: usize) -> HashMap<i64, i64> {
+    let mut merged_into = HashMap::new();
+    let tasks = match db.get_session_tasks(session_id) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            warn!("Failed to load session {} tasks for consolidation: {}", session_id, e);
+            return merged_into;
+        }
+    };
+    let tasks = &tasks[tasks.len().saturating_sub(window)..];
+    if tasks.len() < 2 {
+        return merged_into;
+    }
+    let offered: Vec<ConsolidationTask<'_>> = tasks.iter()
+        .map(|t| ConsolidationTask { title: &t.title, description: t.description.as_deref().unwrap_or("") })
+        .collect();
+    let groups = match provider.suggest_merges(&offered).await {
+        Ok(groups) => groups,
+        Err(e) => {
+            warn!("Task consolidation failed: {}", e);
+            return merged_into;
+        }
+    };
+
+    for group in mergeable_groups(&groups, tasks) {
+        let keep = &tasks[group[0]];
+        let merged: Vec<&Task> = group[1..].iter().map(|&i| &tasks[i]).collect();
+        let merged_ids: Vec<i64> = merged.iter().map(|t| t.id).collect();
+        if let Err(e) = db.merge_tasks(keep.id, &merged_ids) {
+            error!("Failed to merge tasks {:?} into {}: {}", merged_ids, keep.id, e);
+            continue;
+        }
+        info!("Consolidated tasks {:?} into task {} ({})", merged_ids, keep.id, keep.title);
+
+        let mut metadata = keep.metadata.as_deref()
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .filter(|m| m.is_object())
+            .unwrap_or_else(|| serde_json::json!({}));
+        let entry = serde_json::json!({
+            "by": provider.analyzed_by(),
+            "merged": merged.iter()
+                .map(|t| serde_json::json!({ "id": t.id, "title": t.title }))
+                .collect::<Vec<_>>(),
+        });
+        match metadata.get_mut("consolidated").and_then(|c| c.as_array_mut()) {
+            Some(entries) => entries.push(entry),
+            None => metadata["consolidated"] = serde_json::json!([entry]),
+        }
+        if let Err(e) = db.set_task_metadata(keep.id, &metadata.to_string()) {
+            error!("Failed to record consolidation for task {}: {}", keep.id, e);
+        }
+        for id in merged_ids {
+            merged_into.insert(id, keep.id);
+        }
+    }
+    merged_into
+}
+
+/// What a `run_analysis` call did, for the session's savings stats.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisRun {
+    /// Capture groups analyzed by the provider.
+    pub processed: u32,
+    /// Screenshots linked to a task without being sent to the provider
+    /// (sampled out, or out of the monitor scope).
+    pub linked_without_ai: u32,
+    /// Links that failed to save, leaving their screenshots unanalyzed.
+    pub failed_links: u32,
+}
+
+/// Screenshots linked to one task for one capture group.
+struct GroupLinks {
+    task_id: i64,
+    screenshot_ids: Vec<i64>,
+    failed: u32,
+}
+
+impl GroupLinks {
+    fn new(task_id: i64) -> Self {
+        Self { task_id, screenshot_ids: Vec::new(), failed: 0 }
+    }
+
+    /// Link `ss` to the task; failures are logged with both ids and counted.
+    fn link(&mut self, db: &Database, ss: &Screenshot) -> bool {
+        match db.link_screenshot_to_task(self.task_id, ss.id) {
+            Ok(()) => {
+                self.screenshot_ids.push(ss.id);
+                true
+            }
+            Err(e) => {
+                error!("Failed to link screenshot {} to task {}: {}", ss.id, self.task_id, e);
+                self.failed += 1;
+                false
+            }
+        }
+    }
+
+    fn progress(self, opts: &AnalysisOptions<'_>, capture_group: Option<&str>, new_task: bool, done: usize, total: usize) -> AnalysisProgress {
+        AnalysisProgress {
+            session_id: opts.session_id,
+            capture_group: capture_group.map(str::to_string),
+            task_id: self.task_id,
+            new_task,
+            screenshot_ids: self.screenshot_ids,
+            failed_links: self.failed,
+            done: done as u32,
+            total: total as u32,
+        }
+    }
+}
+
+/// Task a capture the AI didn't mark as new continues: the latest task of its session
+/// (started before `before`, when given), or the latest task overall when there's no
+/// session or it has no tasks yet.
+fn continuation_target(db: &Database, session_id: Option<i64>, before: Option<&str>) -> Option<i64> {
+    if let Some(sid) = session_id {
+        let latest = match before {
+            Some(before) => db.get_recent_tasks_before(sid, before, 1),
+            None => db.get_recent_tasks_for_session(sid, 1),
+        };
+        match latest {
+            Ok(tasks) if !tasks.is_empty() => return Some(tasks[0].id),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to load the latest task of session {}: {}", sid, e),
+        }
+    }
+    db.get_tasks(1, 0).ok()
+        .and_then(|tasks| tasks.first().map(|t| t.id))
+}
+
+/// Prompt lines for `context_overlap`: "HH:MM:SS window title → task title", with the
+/// window title shortened like activity log titles.
+fn overlap_lines(rows: &[(String, Option<String>, String)]) -> Vec<String> {
+    rows.iter()
+        .map(|(captured_at, window, task)| {
+            let time = captured_at.get(11..19).unwrap_or(captured_at);
+            match window.as_deref().map(activity::sanitize).filter(|w| !w.is_empty()) {
+                Some(window) => format!("{} {} → {}", time, window, task),
+                None => format!("{} → {}", time, task),
+            }
+        })
+        .collect()
+}
+
+/// Tasks carried from the previous session (`carry_context_from_previous`).
+pub const CARRIED_TASKS: i64 = 2;
+
+/// The last `CARRIED_TASKS` tasks (most recent first) of the latest session that ended
+/// before `session_id` started and has tasks, with the gap in minutes, if that gap is
+/// at most `window_minutes`.
+fn previous_session_tasks(db: &Database, session_id: i64, window_minutes: u64) -> Option<(Vec<Task>, i64)> {
+    let started_at = match db.get_session(session_id) {
+        Ok(session) => session.started_at,
+        Err(e) => {
+            warn!("Failed to load session {} to carry context into it: {}", session_id, e);
+            return None;
+        }
+    };
+    let (previous, gap_seconds) = match db.get_previous_session_with_tasks(&started_at) {
+        Ok(Some(found)) if found.0 != session_id => found,
+        Ok(_) => return None,
+        Err(e) => {
+            warn!("Failed to find the session before {}: {}", session_id, e);
+            return None;
+        }
+    };
+    if gap_seconds > window_minutes as i64 * 60 {
+        return None;
+    }
+    match db.get_recent_tasks_for_session(previous, CARRIED_TASKS) {
+        Ok(tasks) if !tasks.is_empty() => Some((tasks, gap_seconds.max(0) / 60)),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Failed to load the tasks of session {}: {}", previous, e);
+            None
+        }
+    }
+}
+
+/// Analyze screenshots group by group with `provider`, creating or continuing tasks,
+/// linking screenshots, and propagating monitor summaries. Stops early when `cancel`
+/// is set.
+pub async fn run_analysis(
+    db: &Database,
+    provider: &dyn Provider,
+    screenshots: &[Screenshot],
+    monitor_states: &Mutex<HashMap<u32, MonitorState>>,
+    cancel: &AtomicBool,
+    opts: &AnalysisOptions<'_>,
+) -> AnalysisRun {
+    // Dropped when the run ends, so cached images never outlive it
+    let images = ImageCache::default();
+    let mut processed = 0u32;
+    let mut linked_without_ai = 0u32;
+
+    // Seed recent_contexts from existing tasks in this session
+    let mut recent_contexts: VecDeque<String> = VecDeque::with_capacity(opts.context_depth);
+    if let Some(sid) = opts.session_id {
+        let seed = match opts.context_before {
+            Some(before) => db.get_recent_tasks_before(sid, before, opts.context_depth as i64),
+            None => db.get_recent_tasks_for_session(sid, opts.context_depth as i64),
+        };
+        if let Ok(seed_tasks) = seed {
+            for task in &seed_tasks {
+                let desc = task.description.as_deref().unwrap_or("");
+                recent_contexts.push_back(format!("{}: {}", task.title, desc));
+            }
+        }
+    }
+
+    // A session with no tasks yet picks up where the previous one left off; the prompt
+    // mentions it until the first capture group is analyzed
+    let mut carried: Option<(Vec<String>, i64)> = None;
+    if let (Some(sid), Some(window)) = (opts.session_id, opts.carry_context_window) {
+        let has_tasks = db.get_recent_tasks_for_session(sid, 1).map(|t| !t.is_empty()).unwrap_or(true);
+        if !has_tasks {
+            if let Some((tasks, gap_minutes)) = previous_session_tasks(db, sid, window) {
+                info!("Carrying {} task(s) over from a session that ended {} minutes earlier", tasks.len(), gap_minutes);
+                for task in tasks.iter().take(opts.context_depth.saturating_sub(recent_contexts.len())) {
+                    let desc = task.description.as_deref().unwrap_or("");
+                    recent_contexts.push_back(format!("{}: {}", task.title, desc));
+                }
+                carried = Some((tasks.into_iter().map(|t| t.title).collect(), gap_minutes));
+            }
+        }
+    }
+
+    // The end of the previous batch, mentioned until this batch's first analysis succeeds
+    let mut overlap: Vec<String> = match (opts.session_id, screenshots.iter().map(|ss| ss.captured_at.as_str()).min()) {
+        (Some(sid), Some(batch_start)) if opts.context_overlap > 0 => db
+            .get_analyzed_screenshots_before(sid, batch_start, opts.context_overlap)
+            .map(|rows| overlap_lines(&rows))
+            .unwrap_or_else(|e| {
+                warn!("Failed to load the previous batch for context_overlap: {}", e);
+                Vec::new()
+            }),
+        _ => Vec::new(),
+    };
+
+    // Tasks listed in the prompt, which `continues_task_title` is resolved against
+    let mut session_tasks: Vec<SessionTask> = match opts.session_id {
+        Some(sid) if opts.include_session_task_list => db
+            .get_session_tasks(sid)
+            .map(|tasks| tasks.into_iter().map(SessionTask::from).collect())
+            .unwrap_or_else(|e| {
+                warn!("Failed to load session {} tasks for the prompt: {}", sid, e);
+                Vec::new()
+            }),
+        _ => Vec::new(),
+    };
+
+    // Group screenshots by capture_group for multi-monitor awareness
+    let groups = sample_groups(group_by_capture_group(screenshots), opts.sampling);
+    let mut skipped_linked = 0u32;
+    // A bad key or a down server fails every group the same way
+    let mut error_log = LogCoalescer::new(LOG_REPEAT_WINDOW);
+    let mut last_task_id: Option<i64> = None;
+    let mut unscoped: Vec<&Screenshot> = Vec::new();
+    let mut failed_links = 0u32;
+    let mut previous_captured_at: Option<String> = None;
+    let report = |links: GroupLinks, capture_group: Option<&str>, new_task: bool, done: usize| -> u32 {
+        let progress = links.progress(opts, capture_group, new_task, done, groups.len());
+        if let Some(on_progress) = opts.on_progress {
+            on_progress(&progress);
+        }
+        progress.failed_links
+    };
+
+    for (index, (group, skipped)) in groups.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            info!("Analysis cancelled by user after {} groups", processed);
+            break;
+        }
+
+        // Drop undecodable screenshots so the rest of the group still gets analyzed
+        let group: Vec<&Screenshot> = group.iter()
+            .copied()
+            .filter(|ss| decode_or_quarantine(db, opts.screenshots_dir, ss))
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+
+        let capture_group = group[0].capture_group.clone();
+        // Imported activity counts towards this group from the previous one onwards
+        let activity_after = previous_captured_at.replace(group[0].captured_at.clone());
+
+        let (group, mut linked_only) = scope_group(&group, opts.monitor_scope);
+        let (group, mirrors) = split_mirrored(&group);
+        linked_only.extend(mirrors);
+        if group.is_empty() {
+            // Nothing in scope this tick (e.g. only a secondary monitor changed):
+            // attach to the latest task from this run, or hold until there is one
+            match last_task_id {
+                Some(task_id) => {
+                    let mut links = GroupLinks::new(task_id);
+                    for ss in linked_only.iter().chain(skipped.iter()) {
+                        if links.link(db, ss) {
+                            linked_without_ai += 1;
+                        }
+                    }
+                    failed_links += report(links, capture_group.as_deref(), false, index + 1);
+                }
+                None => {
+                    unscoped.extend(linked_only);
+                    unscoped.extend(skipped.iter().copied());
+                }
+            }
+            continue;
+        }
+
+        // Build image paths for this group
+        let mut image_infos: Vec<(PathBuf, String)> = Vec::new();
+        for ss in &group {
+            let filename = ss.filepath
+                .strip_prefix("screenshots/")
+                .unwrap_or(&ss.filepath);
+            let path = opts.screenshots_dir.join(filename);
+            // Use monitor name from monitor_states if available
+            let monitor_name = {
+                let ms = lock_recovered(monitor_states);
+                ms.get(&(ss.monitor_index as u32))
+                    .map(|s| s.name.clone())
+                    .unwrap_or_else(|| format!("Monitor {}", ss.monitor_index))
+            };
+            image_infos.push((path, monitor_name));
+        }
+
+        // Build changed monitors list
+        let changed: Vec<ChangedMonitor<'_>> = image_infos.iter()
+            .zip(&group)
+            .map(|((path, name), ss)| ChangedMonitor {
+                monitor_name: name.as_str(),
+                image_path: path.as_path(),
+                width: 0,
+                height: 0,
+                is_primary: false,
+                previous_active: ss.previous_active,
+                scale_factor: ss.scale_factor.unwrap_or(1.0),
+            })
+            .collect();
+
+        let unchanged_data = unchanged_summaries(
+            &lock_recovered(monitor_states),
+            &group,
+            opts.include_unchanged_context,
+        );
+        let unchanged: Vec<UnchangedMonitor<'_>> = unchanged_data.iter()
+            .map(|(name, summary)| UnchangedMonitor {
+                monitor_name: name.as_str(),
+                summary: summary.as_str(),
+            })
+            .collect();
+
+        let contexts_vec: Vec<String> = recent_contexts.iter().cloned().collect();
+        let task_list: Vec<String> = session_tasks[session_tasks.len().saturating_sub(SESSION_TASK_LIST_CAP)..]
+            .iter()
+            .map(|t| format!("{} ({})", t.title, t.category))
+            .collect();
+
+        let activity_lines: Vec<String> = db
+            .get_activity_in_window(activity_after.as_deref(), &group[0].captured_at, activity::ACTIVITY_LOOKBACK_SECS, activity::PROMPT_ACTIVITY_CAP)
+            .map(|rows| rows.iter().map(|(title, app)| activity::prompt_line(title, app)).collect())
+            .unwrap_or_else(|e| {
+                warn!("Failed to load activity samples for the prompt: {}", e);
+                Vec::new()
+            });
+
+        let mut sent = (opts.save_inputs.is_some() || opts.keep_images).then(SentInputs::default);
+        let result = provider.analyze(AnalysisRequest {
+            changed: &changed,
+            unchanged: &unchanged,
+            previous_contexts: &contexts_vec,
+            session_tasks: &task_list,
+            session_description: opts.session_description,
+            image_mode: opts.image_mode,
+            templates: opts.templates,
+            custom_fields: opts.custom_fields,
+            language: opts.language,
+            carried: carried.as_ref().map(|(titles, gap_minutes)| CarriedTasks { titles, gap_minutes: *gap_minutes }),
+            activity: &activity_lines,
+            overlap: &overlap,
+            images: Some(&images),
+            sent: sent.as_mut(),
+        }).await;
+
+        if let Some(sent) = sent {
+            let ids: Vec<i64> = group.iter().map(|ss| ss.id).collect();
+            if opts.keep_images {
+                let label = capture_group.as_deref().unwrap_or(&group[0].captured_at);
+                keep_sent_images(opts.screenshots_dir, label, &ids, &sent.images).await;
+            }
+            if let (Some(save), Some(key)) = (opts.save_inputs, capture_group.as_deref()) {
+                save_sent_inputs(db, save, key, &ids, sent).await;
+            }
+        }
+
+        match result {
+            Ok(analysis) => {
+                let continued = analysis.continues_task_title.as_deref()
+                    .and_then(|title| resolve_continued_task(title, &session_tasks));
+                // A "new" task whose description matches a recent session task continues it
+                let (similar, new_embedding) = match (opts.embedder, opts.session_id) {
+                    (Some(embedder), Some(sid)) if continued.is_none() && analysis.is_new_task => {
+                        let text = embedding_text(&analysis.task_title, &analysis.task_description);
+                        match similar_session_task(db, embedder, sid, &text, opts.similarity_threshold).await {
+                            Ok((similar, embedding)) => (similar, Some(embedding)),
+                            Err(e) => {
+                                warn!("Embedding similarity unavailable, going by is_new_task: {}", e);
+                                (None, None)
+                            }
+                        }
+                    }
+                    _ => (None, None),
+                };
+                let linked_task_id = if let Some(task_id) = continued {
+                    info!("AI continued session task {} ({:?})", task_id, analysis.continues_task_title);
+                    Some(task_id)
+                } else if let Some((task_id, similarity)) = similar {
+                    info!("{:?} is {:.2} similar to task {}, continuing it", analysis.task_title, similarity, task_id);
+                    Some(task_id)
+                } else if analysis.is_new_task {
+                    let ts = &group[0].captured_at;
+                    let rules = db.get_category_corrections().unwrap_or_default();
+                    let (category, category_override) = apply_category_correction(&analysis.task_title, &analysis.category, &rules);
+                    match db.insert_full_task(
+                        &analysis.task_title,
+                        &analysis.task_description,
+                        &category,
+                        ts,
+                        &analysis.reasoning,
+                    ) {
+                        Ok(task_id) => {
+                            let metadata = task_metadata(&provider.analyzed_by(), category_override, &analysis.extra, opts.custom_fields);
+                            if let Err(e) = db.set_task_metadata(task_id, &metadata) {
+                                error!("Failed to record metadata for task {}: {}", task_id, e);
+                            }
+                            if let (Some(embedding), Some(embedder)) = (&new_embedding, opts.embedder) {
+                                if let Err(e) = db.set_task_embedding(task_id, embedder.model(), embedding) {
+                                    warn!("Failed to cache embedding of task {}: {}", task_id, e);
+                                }
+                            }
+                            if opts.include_session_task_list {
+                                session_tasks.push(SessionTask { id: task_id, title: analysis.task_title.clone(), category });
+                            }
+                            Some(task_id)
+                        }
+                        Err(e) => {
+                            error!("Failed to insert task: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    continuation_target(db, opts.session_id, opts.context_before)
+                };
+
+                if let Some(task_id) = linked_task_id {
+                    let mut links = GroupLinks::new(task_id);
+                    for ss in &group {
+                        links.link(db, ss);
+                    }
+                    // Captures skipped by sampling inherit this capture's task
+                    for ss in skipped {
+                        if links.link(db, ss) {
+                            skipped_linked += 1;
+                            linked_without_ai += 1;
+                        }
+                    }
+                    // Out-of-scope monitors share the analyzed monitor's task
+                    for ss in linked_only.iter().chain(unscoped.iter()) {
+                        if links.link(db, ss) {
+                            linked_without_ai += 1;
+                        }
+                    }
+                    unscoped.clear();
+                    last_task_id = Some(task_id);
+                    let new_task = continued.is_none() && similar.is_none() && analysis.is_new_task;
+                    failed_links += report(links, capture_group.as_deref(), new_task, index + 1);
+                }
+
+                // Update monitor_states with returned summaries
+                if !analysis.monitor_summaries.is_empty() {
+                    let mut ms = lock_recovered(monitor_states);
+                    for (name, summary) in &analysis.monitor_summaries {
+                        // Find the monitor state by name and update its summary
+                        for (_, monitor_state) in ms.iter_mut() {
+                            if monitor_state.name == *name {
+                                monitor_state.last_summary = summary.clone();
+                            }
+                        }
+                    }
+                }
+
+                let new_ctx = format!("{}: {}", analysis.task_title, analysis.task_description);
+                recent_contexts.push_front(new_ctx);
+                recent_contexts.truncate(opts.context_depth);
+                carried = None;
+                overlap.clear();
+
+                processed += 1;
+
+                if opts.consolidate_every > 0 && (processed as usize).is_multiple_of(opts.consolidate_every) {
+                    if let Some(sid) = opts.session_id {
+                        let merged_into = consolidate_tasks(db, provider, sid, opts.consolidate_every).await;
+                        if let Some(keep) = last_task_id.and_then(|id| merged_into.get(&id)) {
+                            last_task_id = Some(*keep);
+                        }
+                        session_tasks.retain(|t| !merged_into.contains_key(&t.id));
+                    }
+                }
+            }
+            Err(e) => {
+                if let Some(line) = error_log.offer("AI analysis failed for capture group", &e.to_string(), Instant::now()) {
+                    error!("{}", line);
+                }
+                if let Some(on_error) = opts.on_error {
+                    on_error(&e);
+                }
+            }
+        }
+    }
+
+    if skipped_linked > 0 {
+        info!("Linked {} sampled-out screenshots to neighboring tasks", skipped_linked);
+    }
+    if failed_links > 0 {
+        warn!("{} screenshot(s) could not be linked to their task and are still pending", failed_links);
+    }
+    AnalysisRun { processed, linked_without_ai, failed_links }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TaskUpdate;
+
+    /// What the mock provider saw for one call.
+    struct SeenRequest {
+        monitors: Vec<String>,
+        unchanged: Vec<(String, String)>,
+        previous_contexts: Vec<String>,
+        session_tasks: Vec<String>,
+        carried: Option<(Vec<String>, i64)>,
+        activity: Vec<String>,
+        overlap: Vec<String>,
+    }
+
+    /// Provider that replays canned responses and records each request.
+    /// Optionally sets `cancel` once `cancel_after` calls have been made.
+    struct MockProvider<'c> {
+        responses: Mutex<VecDeque<Result<TaskAnalysis, AiError>>>,
+        seen: Mutex<Vec<SeenRequest>>,
+        cancel_after: Option<(usize, &'c AtomicBool)>,
+        /// Canned `suggest_merges` answers (no merges once exhausted) and the titles offered.
+        merges: Mutex<VecDeque<Vec<Vec<usize>>>>,
+        offered: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl<'c> MockProvider<'c> {
+        fn new(responses: Vec<Result<TaskAnalysis, AiError>>) -> Self {
+            Self {
+                responses: Mutex::new(responses.into()),
+                seen: Mutex::new(Vec::new()),
+                cancel_after: None,
+                merges: Mutex::new(VecDeque::new()),
+                offered: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Provider for MockProvider<'_> {
+        fn analyze<'a>(&'a self, req: AnalysisRequest<'a>) -> AnalysisFuture<'a> {
+            let calls = {
+                let mut seen = self.seen.lock().unwrap();
+                seen.push(SeenRequest {
+                    monitors: req.changed.iter().map(|c| c.monitor_name.to_string()).collect(),
+                    unchanged: req.unchanged.iter()
+                        .map(|u| (u.monitor_name.to_string(), u.summary.to_string()))
+                        .collect(),
+                    previous_contexts: req.previous_contexts.to_vec(),
+                    session_tasks: req.session_tasks.to_vec(),
+                    carried: req.carried.map(|c| (c.titles.to_vec(), c.gap_minutes)),
+                    activity: req.activity.to_vec(),
+                    overlap: req.overlap.to_vec(),
+                });
+                seen.len()
+            };
+            if let Some((n, cancel)) = self.cancel_after {
+                if calls >= n {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            }
+            if let Some(sent) = req.sent {
+                let monitors: Vec<&str> = req.changed.iter().map(|c| c.monitor_name).collect();
+                sent.prompt = format!("prompt for {}", monitors.join(", "));
+                sent.images = monitors.iter().map(|m| m.as_bytes().to_vec()).collect();
+            }
+            let response = self.responses.lock().unwrap().pop_front()
+                .unwrap_or_else(|| Err(AiError::ApiError("no canned response".to_string())));
+            Box::pin(async move { response })
+        }
+        fn suggest_merges<'a>(&'a self, tasks: &'a [ConsolidationTask<'a>]) -> MergeFuture<'a> {
+            self.offered.lock().unwrap().push(tasks.iter().map(|t| t.title.to_string()).collect());
+            let groups = self.merges.lock().unwrap().pop_front().unwrap_or_default();
+            Box::pin(async move { Ok(groups) })
+        }
+        fn suggest_descriptions<'a>(&'a self, _previous: ai::PreviousSession<'a>) -> SuggestionFuture<'a> {
+            Box::pin(async { Err(AiError::ApiError("no canned suggestions".to_string())) })
+        }
+        fn analyzed_by(&self) -> String {
+            "mock/test-model".to_string()
+        }
+    }
+
+    fn analysis(title: &str, is_new_task: bool) -> Result<TaskAnalysis, AiError> {
+        Ok(TaskAnalysis {
+            task_title: title.to_string(),
+            task_description: format!("{} description", title),
+            category: "coding".to_string(),
+            reasoning: "mock".to_string(),
+            is_new_task,
+            monitor_summaries: HashMap::new(),
+            continues_task_title: None,
+            extra: HashMap::new(),
+        })
+    }
+
+    fn screenshot(db: &Database, session_id: i64, ts: &str, monitor: i32) -> Screenshot {
+        let group = ts.replace(':', "-");
+        let id = db.insert_screenshot(&format!("screenshots/{}_{}.webp", group, monitor), ts, None, monitor, Some(session_id), Some(&group)).unwrap();
+        db.get_screenshot(id).unwrap()
+    }
+
+    fn options(session_id: i64) -> AnalysisOptions<'static> {
+        AnalysisOptions {
+            session_id: Some(session_id),
+            session_description: None,
+            image_mode: "downscale",
+            sampling: 1,
+            screenshots_dir: Path::new("/tmp/screenshots"),
+            templates: PromptTemplates::default(),
+            monitor_scope: MonitorScope::All,
+            custom_fields: &[],
+            language: None,
+            include_unchanged_context: true,
+            context_depth: 2,
+            include_session_task_list: false,
+            consolidate_every: 0,
+            embedder: None,
+            similarity_threshold: 0.85,
+            on_progress: None,
+            on_error: None,
+            carry_context_window: None,
+            save_inputs: None,
+            keep_images: false,
+            context_overlap: 0,
+            context_before: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_inserts_and_continues_tasks() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+        ];
+        let provider = MockProvider::new(vec![
+            analysis("Writing code", true),
+            analysis("Writing code", false),
+            analysis("Reading docs", true),
+        ]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        let processed = run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await.processed;
+        assert_eq!(processed, 3);
+
+        let tasks = db.get_session_tasks(sid).unwrap();
+        assert_eq!(tasks.len(), 2);
+        let first = db.get_task_for_screenshot(screenshots[0].id).unwrap().unwrap();
+        assert_eq!(first.title, "Writing code");
+        assert_eq!(first.started_at, "2025-01-01T10:00:00");
+        // Continuation links to the existing task
+        let second = db.get_task_for_screenshot(screenshots[1].id).unwrap().unwrap();
+        assert_eq!(second.id, first.id);
+        let third = db.get_task_for_screenshot(screenshots[2].id).unwrap().unwrap();
+        assert_eq!(third.title, "Reading docs");
+        let metadata: serde_json::Value = serde_json::from_str(third.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["analyzed_by"], "mock/test-model");
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_stops_when_cancelled() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+        ];
+        let cancel = AtomicBool::new(false);
+        let mut provider = MockProvider::new(vec![
+            analysis("A", true),
+            analysis("B", true),
+            analysis("C", true),
+        ]);
+        provider.cancel_after = Some((1, &cancel));
+        let states = Mutex::new(HashMap::new());
+
+        let processed = run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await.processed;
+        assert_eq!(processed, 1);
+        assert_eq!(provider.seen.lock().unwrap().len(), 1);
+        assert!(db.get_task_for_screenshot(screenshots[1].id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_propagates_monitor_summaries() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 2),
+        ];
+        let mut first = analysis("Coding", true).unwrap();
+        first.monitor_summaries.insert("Left".to_string(), "VS Code with Rust".to_string());
+        let provider = MockProvider::new(vec![Ok(first), analysis("Coding", false)]);
+        let states = Mutex::new(HashMap::from([
+            (1, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Left".to_string(), next_due_ms: 0, last_distance: None }),
+            (2, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Right".to_string(), next_due_ms: 0, last_distance: None }),
+        ]));
+        let cancel = AtomicBool::new(false);
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+
+        assert_eq!(states.lock().unwrap()[&1].last_summary, "VS Code with Rust");
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen[0].monitors, vec!["Left"]);
+        assert!(seen[0].unchanged.is_empty());
+        // The second group (Right monitor) sees Left's summary as an unchanged monitor
+        assert_eq!(seen[1].monitors, vec!["Right"]);
+        assert_eq!(seen[1].unchanged, vec![("Left".to_string(), "VS Code with Rust".to_string())]);
+    }
+
+    #[test]
+    fn test_unchanged_summaries_respects_setting() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let right = screenshot(&db, sid, "2025-01-01T10:00:00", 2);
+        let states = HashMap::from([
+            (1, MonitorState { last_hash: [0; 32], last_summary: "VS Code with Rust".to_string(), name: "Left".to_string(), next_due_ms: 0, last_distance: None }),
+            (2, MonitorState { last_hash: [0; 32], last_summary: "Docs".to_string(), name: "Right".to_string(), next_due_ms: 0, last_distance: None }),
+            (3, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Spare".to_string(), next_due_ms: 0, last_distance: None }),
+        ]);
+
+        assert_eq!(
+            unchanged_summaries(&states, &[&right], true),
+            vec![("Left".to_string(), "VS Code with Rust".to_string())]
+        );
+        assert!(unchanged_summaries(&states, &[&right], false).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_without_unchanged_context() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 2),
+        ];
+        let mut first = analysis("Coding", true).unwrap();
+        first.monitor_summaries.insert("Left".to_string(), "VS Code with Rust".to_string());
+        let provider = MockProvider::new(vec![Ok(first), analysis("Coding", false)]);
+        let states = Mutex::new(HashMap::from([
+            (1, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Left".to_string(), next_due_ms: 0, last_distance: None }),
+            (2, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Right".to_string(), next_due_ms: 0, last_distance: None }),
+        ]));
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { include_unchanged_context: false, ..options(sid) };
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+
+        // Summaries are still tracked, just not sent
+        assert_eq!(states.lock().unwrap()[&1].last_summary, "VS Code with Rust");
+        assert!(provider.seen.lock().unwrap()[1].unchanged.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_seeds_and_rolls_contexts() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let earlier = screenshot(&db, sid, "2025-01-01T09:59:00", 0);
+        let seed = db.insert_full_task("Earlier task", "Before", "coding", "2025-01-01T09:59:00", "r").unwrap();
+        db.link_screenshot_to_task(seed, earlier.id).unwrap();
+
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+        ];
+        let provider = MockProvider::new(vec![
+            analysis("One", true),
+            analysis("Two", true),
+            analysis("Three", true),
+        ]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen[0].previous_contexts, vec!["Earlier task: Before"]);
+        assert_eq!(seen[1].previous_contexts, vec!["One: One description", "Earlier task: Before"]);
+        // Only the two most recent contexts are kept
+        assert_eq!(seen[2].previous_contexts, vec!["Two: Two description", "One: One description"]);
+        assert!(seen[0].session_tasks.is_empty(), "no task list unless enabled");
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_context_depth() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots: Vec<Screenshot> = (0..4)
+            .map(|i| screenshot(&db, sid, &format!("2025-01-01T10:0{}:00", i), 0))
+            .collect();
+        let provider = MockProvider::new(vec![
+            analysis("One", true),
+            analysis("Two", true),
+            analysis("Three", true),
+            analysis("Four", true),
+        ]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { context_depth: 3, ..options(sid) };
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(
+            seen[3].previous_contexts,
+            vec!["Three: Three description", "Two: Two description", "One: One description"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_continued_task_order() {
+        let task = |id: i64, title: &str| SessionTask { id, title: title.to_string(), category: "coding".to_string() };
+        let tasks = vec![
+            task(1, "Fixing login bug"),
+            task(2, "Reading API docs"),
+            task(3, "Fixing the login bug in auth service"),
+            task(4, "Reviewing pull requests"),
+        ];
+        // Exact (case-insensitive) beats a closer-looking fuzzy candidate
+        assert_eq!(resolve_continued_task(" fixing LOGIN bug ", &tasks), Some(1));
+        // Fuzzy: best keyword overlap
+        assert_eq!(resolve_continued_task("Reading the API docs again", &tasks), Some(2));
+        // Equal overlap goes to the more recent task
+        let tied = vec![task(5, "Writing report draft"), task(6, "Writing report summary")];
+        assert_eq!(resolve_continued_task("Writing report", &tied), Some(6));
+        // Nothing close enough: caller falls back to most recent
+        assert_eq!(resolve_continued_task("Editing photos", &tasks), None);
+        assert_eq!(resolve_continued_task("  ", &tasks), None);
+        assert_eq!(resolve_continued_task("Fixing login bug", &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_links_continued_session_task() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+        ];
+        let mut back_to_first = analysis("Fixing login bug (again)", true);
+        if let Ok(a) = &mut back_to_first {
+            a.continues_task_title = Some("fixing login bug".to_string());
+        }
+        let provider = MockProvider::new(vec![
+            analysis("Fixing login bug", true),
+            analysis("Reading docs", true),
+            back_to_first,
+        ]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { include_session_task_list: true, ..options(sid) };
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+
+        let first = db.get_task_for_screenshot(screenshots[0].id).unwrap().unwrap();
+        let third = db.get_task_for_screenshot(screenshots[2].id).unwrap().unwrap();
+        assert_eq!(third.id, first.id, "linked to the named task, not the most recent one");
+        assert_eq!(db.get_session_tasks(sid).unwrap().len(), 2, "no near-duplicate task created");
+
+        let seen = provider.seen.lock().unwrap();
+        assert!(seen[0].session_tasks.is_empty());
+        assert_eq!(seen[2].session_tasks, vec!["Fixing login bug (coding)", "Reading docs (coding)"]);
+    }
+
+    fn task(db: &Database, title: &str, started_at: &str, verified: bool) -> Task {
+        let id = db.insert_full_task(title, "desc", "coding", started_at, "reason").unwrap();
+        if verified {
+            db.update_task(id, &TaskUpdate { title: None, description: None, category: None, ended_at: None, user_verified: Some(true) }).unwrap();
+        }
+        db.get_task(id).unwrap()
+    }
+
+    #[test]
+    fn test_mergeable_groups_guards() {
+        let db = Database::in_memory().unwrap();
+        let tasks = vec![
+            task(&db, "A", "2025-01-01T10:00:00", false),
+            task(&db, "B", "2025-01-01T10:01:00", false),
+            task(&db, "C", "2025-01-01T10:02:00", true),
+            task(&db, "D", "2025-01-01T10:03:00", false),
+            task(&db, "E", "2025-01-01T10:04:00", false),
+        ];
+        let groups = vec![
+            vec![0, 1],    // ok
+            vec![1, 2],    // B already claimed
+            vec![2, 3],    // C is user-verified
+            vec![3, 5],    // not adjacent, 5 out of range
+            vec![4],       // single task
+            vec![3, 4],    // ok
+        ];
+        assert_eq!(mergeable_groups(&groups, &tasks), vec![vec![0, 1], vec![3, 4]]);
+        assert!(mergeable_groups(&[vec![0, 2]], &tasks).is_empty());
+        assert!(mergeable_groups(&[vec![4, 5]], &tasks).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_consolidates_every_n_groups() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots: Vec<Screenshot> = (0..4)
+            .map(|i| screenshot(&db, sid, &format!("2025-01-01T10:0{}:00", i), 0))
+            .collect();
+        let provider = MockProvider::new(vec![
+            analysis("Fixing bug", true),
+            analysis("Fixing bug further", true),
+            analysis("Reading docs", true),
+            analysis("Reading more docs", true),
+        ]);
+        provider.merges.lock().unwrap().extend([vec![vec![0, 1]], vec![vec![0, 1]]]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { consolidate_every: 2, ..options(sid) };
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        assert_eq!(run.processed, 4);
+
+        // Offered the last two tasks each time, after the first merge had been applied
+        let offered = provider.offered.lock().unwrap();
+        assert_eq!(*offered, vec![
+            vec!["Fixing bug".to_string(), "Fixing bug further".to_string()],
+            vec!["Reading docs".to_string(), "Reading more docs".to_string()],
+        ]);
+
+        let tasks = db.get_session_tasks(sid).unwrap();
+        assert_eq!(tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), vec!["Fixing bug", "Reading docs"]);
+        for (i, ss) in screenshots.iter().enumerate() {
+            assert_eq!(db.get_task_for_screenshot(ss.id).unwrap().unwrap().id, tasks[i / 2].id);
+        }
+        let metadata: serde_json::Value = serde_json::from_str(tasks[0].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["analyzed_by"], "mock/test-model");
+        assert_eq!(metadata["consolidated"][0]["merged"][0]["title"], "Fixing bug further");
+        assert_eq!(metadata["consolidated"][0]["by"], "mock/test-model");
+    }
+
+    #[tokio::test]
+    async fn test_consolidation_leaves_verified_tasks() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let first = task(&db, "Fixing bug", "2025-01-01T10:00:00", true);
+        let second = task(&db, "Fixing bug further", "2025-01-01T10:01:00", false);
+        for (i, t) in [&first, &second].iter().enumerate() {
+            let ss = screenshot(&db, sid, &format!("2025-01-01T10:0{}:00", i), 0);
+            db.link_screenshot_to_task(t.id, ss.id).unwrap();
+        }
+        let provider = MockProvider::new(Vec::new());
+        provider.merges.lock().unwrap().push_back(vec![vec![0, 1]]);
+
+        let merged_into = consolidate_tasks(&db, &provider, sid, 5).await;
+
+        assert!(merged_into.is_empty());
+        assert_eq!(db.get_session_tasks(sid).unwrap().len(), 2);
+    }
+
+    /// Embeds text as (mentions "report", mentions "docs", 0.1) and counts calls.
+    struct KeywordEmbedder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Embedder for KeywordEmbedder {
+        fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a> {
+            self.calls.lock().unwrap().push(text.to_string());
+            let has = |word: &str| if text.to_lowercase().contains(word) { 1.0 } else { 0.0 };
+            let embedding = vec![has("report"), has("docs"), 0.1];
+            Box::pin(async move { Ok(embedding) })
+        }
+
+        fn model(&self) -> &str {
+            "keywords"
+        }
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0]), 0.0, "length mismatch");
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0, "zero vector");
+    }
+
+    #[test]
+    fn test_most_similar_task_threshold() {
+        let candidates = vec![(3, vec![1.0, 0.0]), (2, vec![0.8, 0.6]), (1, vec![1.0, 0.0])];
+        // cos to (0.8, 0.6) is 0.8; exact matches tie and go to the most recent (first)
+        assert_eq!(most_similar_task(&[1.0, 0.0], &candidates, 0.85).map(|m| m.0), Some(3));
+        assert_eq!(most_similar_task(&[0.8, 0.6], &candidates, 0.85).map(|m| m.0), Some(2));
+        assert_eq!(most_similar_task(&[0.0, 1.0], &candidates, 0.85), None);
+        // Exactly at the threshold counts
+        assert_eq!(most_similar_task(&[0.0, 1.0], &candidates[1..2], 0.6).map(|m| m.0), Some(2));
+        assert_eq!(most_similar_task(&[1.0, 0.0], &[], 0.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_continues_similar_task_despite_is_new_task() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots: Vec<Screenshot> = (0..3)
+            .map(|i| screenshot(&db, sid, &format!("2025-01-01T10:0{}:00", i), 0))
+            .collect();
+        let provider = MockProvider::new(vec![
+            analysis("Writing report", true),
+            // Called new, but about the same report
+            analysis("Editing the quarterly report", true),
+            analysis("Reading docs", true),
+        ]);
+        let embedder = KeywordEmbedder { calls: Mutex::new(Vec::new()) };
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { embedder: Some(&embedder), ..options(sid) };
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+
+        let tasks = db.get_session_tasks(sid).unwrap();
+        assert_eq!(tasks.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), vec!["Writing report", "Reading docs"]);
+        assert_eq!(db.get_task_for_screenshot(screenshots[1].id).unwrap().unwrap().id, tasks[0].id);
+        // New tasks' embeddings were cached, so existing tasks were never re-embedded
+        assert!(db.get_task_embedding(tasks[1].id, "keywords").unwrap().is_some());
+        assert_eq!(embedder.calls.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_without_embedder_trusts_is_new_task() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots: Vec<Screenshot> = (0..2)
+            .map(|i| screenshot(&db, sid, &format!("2025-01-01T10:0{}:00", i), 0))
+            .collect();
+        let provider = MockProvider::new(vec![analysis("Writing report", true), analysis("Editing the quarterly report", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+
+        assert_eq!(db.get_session_tasks(sid).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_reports_linked_screenshots_per_group() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 1),
+        ];
+        // Deleted while the run was in flight, so its link fails
+        db.delete_screenshot(screenshots[4].id).unwrap();
+        let provider = MockProvider::new(vec![
+            analysis("Coding", true),
+            analysis("Coding", false),
+            analysis("Reading docs", true),
+        ]);
+        let events = Mutex::new(Vec::new());
+        let on_progress = |p: &AnalysisProgress| events.lock().unwrap().push(p.clone());
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { on_progress: Some(&on_progress), ..options(sid) };
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        assert_eq!(run.failed_links, 1);
+
+        let events = events.into_inner().unwrap();
+        let summary: Vec<(Option<&str>, bool, Vec<i64>, u32)> = events.iter()
+            .map(|e| (e.capture_group.as_deref(), e.new_task, e.screenshot_ids.clone(), e.failed_links))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some("2025-01-01T10-00-00"), true, vec![screenshots[0].id, screenshots[1].id], 0),
+            (Some("2025-01-01T10-00-30"), false, vec![screenshots[2].id], 0),
+            (Some("2025-01-01T10-01-00"), true, vec![screenshots[3].id], 1),
+        ]);
+        assert_eq!(events[1].task_id, events[0].task_id, "continuation reports the continued task");
+        assert_eq!((events[2].done, events[2].total, events[2].session_id), (3, 3, Some(sid)));
+        assert_eq!(db.get_task_for_screenshot(screenshots[3].id).unwrap().unwrap().id, events[2].task_id);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_saves_sent_inputs() {
+        let data_dir = std::env::temp_dir().join(format!("rlcollector_test_inputs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+        ];
+        // The second request fails, but what it sent is still recorded
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions {
+            save_inputs: Some(SaveInputs { data_dir: &data_dir, run_id: "2025-01-02T09:00:00" }),
+            ..options(sid)
+        };
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        assert_eq!(run.processed, 1);
+
+        let first = db.get_analysis_debug("2025-01-01T10-00-00").unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].prompt, "prompt for Monitor 0, Monitor 1");
+        assert_eq!(first[0].run_id, "2025-01-02T09:00:00");
+        assert_eq!(first[0].image_paths, vec![
+            format!("analysis_debug/2025-01-02T09-00-00/{}.webp", screenshots[0].id),
+            format!("analysis_debug/2025-01-02T09-00-00/{}.webp", screenshots[1].id),
+        ]);
+        assert_eq!(std::fs::read(data_dir.join(&first[0].image_paths[1])).unwrap(), b"Monitor 1");
+        assert_eq!(db.get_analysis_debug("2025-01-01T10-00-30").unwrap().len(), 1);
+
+        // Re-analysis in a later run writes its own files; the first run's stay as recorded
+        db.unlink_capture_group("2025-01-01T10-00-00").unwrap();
+        let provider = MockProvider::new(vec![analysis("Reviewing", true)]);
+        let opts = AnalysisOptions {
+            save_inputs: Some(SaveInputs { data_dir: &data_dir, run_id: "2025-01-03T09:00:00" }),
+            ..options(sid)
+        };
+        run_analysis(&db, &provider, &screenshots[..2], &states, &cancel, &opts).await;
+        let records = db.get_analysis_debug("2025-01-01T10-00-00").unwrap();
+        assert_eq!(records.len(), 2);
+        let paths: HashSet<&String> = records.iter().flat_map(|r| &r.image_paths).collect();
+        assert_eq!(paths.len(), 4, "each run's images have their own paths");
+        assert!(paths.iter().all(|p| data_dir.join(p).exists()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_keeps_sent_images() {
+        let screenshots_dir = std::env::temp_dir().join(format!("rlcollector_test_keep_images_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&screenshots_dir);
+        std::fs::create_dir_all(&screenshots_dir).unwrap();
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+        ];
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let analysis_dir = screenshots_dir.join(ANALYSIS_IMAGES_DIR);
+
+        // Off (the default): nothing is written
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let opts = AnalysisOptions { screenshots_dir: &screenshots_dir, ..options(sid) };
+        assert_eq!(run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await.processed, 1);
+        assert!(!analysis_dir.exists());
+
+        // On: each monitor's image, exactly as sent, under its own screenshot id
+        db.unlink_capture_group("2025-01-01T10-00-00").unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let opts = AnalysisOptions { screenshots_dir: &screenshots_dir, keep_images: true, ..options(sid) };
+        assert_eq!(run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await.processed, 1);
+        for (ss, sent) in screenshots.iter().zip([b"Monitor 0", b"Monitor 1"]) {
+            assert_eq!(std::fs::read(analysis_dir.join(format!("{}.webp", ss.id))).unwrap(), sent);
+        }
+        assert_eq!(std::fs::read_dir(&analysis_dir).unwrap().count(), 2);
+        // Only the images are kept; the prompt records are `save_analysis_inputs`
+        assert!(db.get_analysis_debug("2025-01-01T10-00-00").unwrap().is_empty());
+
+        // Deleting a screenshot takes its image along; the sweep drops images older than the cutoff
+        remove_kept_images(&screenshots_dir, &[screenshots[0].id]);
+        assert!(!analysis_dir.join(format!("{}.webp", screenshots[0].id)).exists());
+        let kept = analysis_dir.join(format!("{}.webp", screenshots[1].id));
+        let written_at = time::unix_secs(std::fs::metadata(&kept).unwrap().modified().unwrap());
+        assert_eq!(sweep_kept_images(&screenshots_dir, written_at), 0);
+        assert_eq!(sweep_kept_images(&screenshots_dir, written_at + 1), 1);
+        assert!(!kept.exists());
+        let _ = std::fs::remove_dir_all(&screenshots_dir);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_without_save_inputs_records_nothing() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+        ];
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+        assert_eq!(run.processed, 1);
+        assert!(db.get_analysis_debug("2025-01-01T10-00-00").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_input_save_does_not_fail_analysis() {
+        // A file where the data dir should be, so the images can't be written
+        let data_dir = std::env::temp_dir().join(format!("rlcollector_test_inputs_file_{}", std::process::id()));
+        std::fs::write(&data_dir, b"").unwrap();
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![screenshot(&db, sid, "2025-01-01T10:00:00", 0)];
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions {
+            save_inputs: Some(SaveInputs { data_dir: &data_dir, run_id: "2025-01-02T09:00:00" }),
+            ..options(sid)
+        };
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        assert_eq!(run.processed, 1);
+        assert!(db.get_task_for_screenshot(screenshots[0].id).unwrap().is_some());
+        assert!(db.get_analysis_debug("2025-01-01T10-00-00").unwrap().is_empty());
+        let _ = std::fs::remove_file(&data_dir);
+    }
+
+    /// A session from 09:00 to `ended_at` whose tasks are "Writing report" then "Reviewing PRs".
+    fn ended_session_with_tasks(db: &Database, ended_at: &str) -> i64 {
+        let sid = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        for (title, ts) in [("Writing report", "2025-01-01T09:00:00"), ("Reviewing PRs", "2025-01-01T09:30:00")] {
+            let t = task(db, title, ts, false);
+            db.link_screenshot_to_task(t.id, screenshot(db, sid, ts, 0).id).unwrap();
+        }
+        db.end_session(sid, ended_at).unwrap();
+        sid
+    }
+
+    #[tokio::test]
+    async fn test_carry_context_from_recent_session() {
+        let db = Database::in_memory().unwrap();
+        ended_session_with_tasks(&db, "2025-01-01T11:00:00");
+        let sid = db.create_session("2025-01-01T11:45:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T11:45:00", 0),
+            screenshot(&db, sid, "2025-01-01T11:45:30", 0),
+        ];
+        let provider = MockProvider::new(vec![analysis("Writing report", true), analysis("Writing report", false)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { carry_context_window: Some(120), ..options(sid) };
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        let seen = provider.seen.into_inner().unwrap();
+        assert_eq!(seen[0].previous_contexts, vec!["Reviewing PRs: desc", "Writing report: desc"]);
+        assert_eq!(seen[0].carried, Some((vec!["Reviewing PRs".to_string(), "Writing report".to_string()], 45)));
+        // Only the session's first analysis mentions the previous session
+        assert_eq!(seen[1].carried, None);
+        assert_eq!(seen[1].previous_contexts[0], "Writing report: Writing report description");
+    }
+
+    #[tokio::test]
+    async fn test_activity_log_lines_follow_group_windows() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+        ];
+        let at = |t: &str, title: &str, app: &str| activity::ActivityRecord {
+            timestamp: activity::ActivityTime::Text(t.to_string()),
+            title: title.to_string(),
+            app: app.to_string(),
+        };
+        db.insert_activity_samples(&[
+            at("2025-01-01T09:57:00", "Too old", ""),
+            at("2025-01-01T09:59:00", "Issue #4", "Firefox"),
+            at("2025-01-01T10:00:10", "lib.rs", "Editor"),
+        ]).unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true), analysis("Coding", false)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+        let seen = provider.seen.into_inner().unwrap();
+        assert_eq!(seen[0].activity, vec!["Firefox: Issue #4"]);
+        // The second group's window starts at the first group
+        assert_eq!(seen[1].activity, vec!["Editor: lib.rs"]);
+    }
+
+    #[test]
+    fn test_overlap_lines() {
+        let rows = vec![
+            ("2025-01-01T10:01:30".to_string(), Some("main.rs\n- Editor".to_string()), "Coding".to_string()),
+            ("2025-01-01T10:02:00".to_string(), None, "Coding".to_string()),
+        ];
+        assert_eq!(overlap_lines(&rows), vec!["10:01:30 main.rs - Editor → Coding", "10:02:00 → Coding"]);
+    }
+
+    #[tokio::test]
+    async fn test_context_overlap_seeds_first_group_of_next_batch() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { context_overlap: 2, ..options(sid) };
+
+        // First batch: nothing before it
+        let first = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:01:00", 0),
+        ];
+        let provider = MockProvider::new(vec![analysis("Coding", true), analysis("Coding", false), analysis("Reading docs", true)]);
+        run_analysis(&db, &provider, &first, &states, &cancel, &opts).await;
+        assert!(provider.seen.into_inner().unwrap().iter().all(|r| r.overlap.is_empty()));
+
+        // Second batch: its first group hears about the last two screenshots, later ones don't
+        let second = vec![
+            screenshot(&db, sid, "2025-01-01T10:01:30", 0),
+            screenshot(&db, sid, "2025-01-01T10:02:00", 0),
+        ];
+        let provider = MockProvider::new(vec![analysis("Reading docs", false), analysis("Reading docs", false)]);
+        run_analysis(&db, &provider, &second, &states, &cancel, &opts).await;
+        let seen = provider.seen.into_inner().unwrap();
+        assert_eq!(seen[0].overlap, vec!["10:00:30 → Coding", "10:01:00 → Reading docs"]);
+        assert!(seen[1].overlap.is_empty());
+        // Recent contexts are re-seeded from the committed first batch
+        assert_eq!(seen[0].previous_contexts[0], "Reading docs: Reading docs description");
+    }
+
+    #[tokio::test]
+    async fn test_carry_context_respects_window_and_own_tasks() {
+        let db = Database::in_memory().unwrap();
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        // No earlier session at all
+        let first = db.create_session("2025-01-01T08:00:00", None, None).unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let opts = AnalysisOptions { carry_context_window: Some(120), ..options(first) };
+        run_analysis(&db, &provider, &[screenshot(&db, first, "2025-01-01T08:00:00", 0)], &states, &cancel, &opts).await;
+        assert_eq!(provider.seen.into_inner().unwrap()[0].carried, None);
+        db.end_session(first, "2025-01-01T08:30:00").unwrap();
+
+        ended_session_with_tasks(&db, "2025-01-01T11:00:00");
+
+        // Ended 2h01m before the new session: outside the window
+        let late = db.create_session("2025-01-01T13:01:00", None, None).unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let opts = AnalysisOptions { carry_context_window: Some(120), ..options(late) };
+        run_analysis(&db, &provider, &[screenshot(&db, late, "2025-01-01T13:01:00", 0)], &states, &cancel, &opts).await;
+        let seen = provider.seen.into_inner().unwrap();
+        assert_eq!(seen[0].carried, None);
+        assert!(seen[0].previous_contexts.is_empty());
+
+        // Exactly at the window edge it carries over, but not once the session has tasks
+        let edge = db.create_session("2025-01-01T13:00:00", None, None).unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true), analysis("Coding", false)]);
+        let opts = AnalysisOptions { carry_context_window: Some(120), ..options(edge) };
+        run_analysis(&db, &provider, &[screenshot(&db, edge, "2025-01-01T13:00:00", 0)], &states, &cancel, &opts).await;
+        run_analysis(&db, &provider, &[screenshot(&db, edge, "2025-01-01T13:00:30", 0)], &states, &cancel, &opts).await;
+        let seen = provider.seen.into_inner().unwrap();
+        assert_eq!(seen[0].carried.as_ref().map(|c| c.1), Some(120));
+        assert_eq!(seen[1].carried, None);
+
+        // Off unless the setting is on
+        let off = db.create_session("2025-01-01T11:10:00", None, None).unwrap();
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        run_analysis(&db, &provider, &[screenshot(&db, off, "2025-01-01T11:10:00", 0)], &states, &cancel, &options(off)).await;
+        assert_eq!(provider.seen.into_inner().unwrap()[0].carried, None);
+    }
+
+    #[tokio::test]
+    async fn test_continuation_links_to_own_session_task() {
+        let db = Database::in_memory().unwrap();
+        let s1 = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let s2 = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        let own = task(&db, "Writing report", "2025-01-01T10:00:00", false);
+        db.link_screenshot_to_task(own.id, screenshot(&db, s1, "2025-01-01T10:00:00", 0).id).unwrap();
+        // Another session's task is newer, so it's the globally most recent one
+        let other = task(&db, "Reviewing PRs", "2025-01-01T11:00:00", false);
+        db.link_screenshot_to_task(other.id, screenshot(&db, s2, "2025-01-01T11:00:00", 0).id).unwrap();
+
+        let ss = screenshot(&db, s1, "2025-01-01T10:05:00", 0);
+        let provider = MockProvider::new(vec![analysis("Writing report", false)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        run_analysis(&db, &provider, std::slice::from_ref(&ss), &states, &cancel, &options(s1)).await;
+
+        assert_eq!(db.get_task_for_screenshot(ss.id).unwrap().unwrap().id, own.id);
+
+        // A session with no tasks yet still falls back to the latest task overall
+        let s3 = db.create_session("2025-01-01T12:00:00", None, None).unwrap();
+        assert_eq!(continuation_target(&db, Some(s3), None), Some(other.id));
+        assert_eq!(continuation_target(&db, None, None), Some(other.id));
+    }
+
+    #[tokio::test]
+    async fn test_reanalysis_uses_context_before_the_group() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let mut shots = Vec::new();
+        for (title, ts) in [("Writing report", "2025-01-01T10:00:00"), ("Email", "2025-01-01T10:05:00"), ("Reviewing PRs", "2025-01-01T10:10:00")] {
+            let t = task(&db, title, ts, false);
+            let ss = screenshot(&db, sid, ts, 0);
+            db.link_screenshot_to_task(t.id, ss.id).unwrap();
+            shots.push((t, ss));
+        }
+        // Re-analyze the middle group: it was wrongly "Email" and really continues the report
+        db.unlink_capture_group(shots[1].1.capture_group.as_deref().unwrap()).unwrap();
+        let provider = MockProvider::new(vec![analysis("Writing report", false)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { context_before: Some("2025-01-01T10:05:00"), ..options(sid) };
+        let run = run_analysis(&db, &provider, std::slice::from_ref(&shots[1].1), &states, &cancel, &opts).await;
+        assert_eq!(run.processed, 1);
+
+        // Only what came before the group is context, and the continuation joins it
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen[0].previous_contexts.len(), 1);
+        assert!(seen[0].previous_contexts[0].starts_with("Writing report"));
+        assert_eq!(db.get_task_for_screenshot(shots[1].1.id).unwrap().unwrap().id, shots[0].0.id);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_skips_failed_groups() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:30", 0),
+        ];
+        let provider = MockProvider::new(vec![
+            Err(AiError::ApiError("boom".to_string())),
+            analysis("Recovered", true),
+        ]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+
+        let processed = run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await.processed;
+        assert_eq!(processed, 1);
+        assert!(db.get_task_for_screenshot(screenshots[0].id).unwrap().is_none());
+        assert!(db.get_task_for_screenshot(screenshots[1].id).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_sends_one_copy_of_mirrored_displays() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let main = screenshot(&db, sid, "2025-01-01T10:00:00", 0);
+        let projector = screenshot(&db, sid, "2025-01-01T10:00:00", 1);
+        // Both displays show the same slide; the capture loop flags the second one
+        let slide = || image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, 0, 255]));
+        let saved = [(main.id, crate::capture::perceptual_hash(&slide()))];
+        let original = crate::capture::find_mirror(&crate::capture::perceptual_hash(&slide()), &saved, 4);
+        assert_eq!(original, Some(main.id));
+        db.mark_mirrored(projector.id, main.id).unwrap();
+        let screenshots = vec![main, db.get_screenshot(projector.id).unwrap()];
+        assert_eq!(screenshots[1].mirrored_of, Some(screenshots[0].id));
+
+        let provider = MockProvider::new(vec![analysis("Presenting", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &options(sid)).await;
+
+        assert_eq!(run, AnalysisRun { processed: 1, linked_without_ai: 1, failed_links: 0 });
+        assert_eq!(provider.seen.lock().unwrap()[0].monitors, vec!["Monitor 0"]);
+        let task = db.get_task_for_screenshot(screenshots[0].id).unwrap().unwrap();
+        assert_eq!(db.get_task_for_screenshot(screenshots[1].id).unwrap().unwrap().id, task.id);
+    }
+
+    #[test]
+    fn test_split_mirrored_keeps_mirror_without_original() {
+        let ss = |id, mirrored_of| Screenshot {
+            id, filepath: String::new(), captured_at: String::new(),
+            active_window_title: None, monitor_index: 0, capture_group: None, mirrored_of,
+            previous_active: false, scale_factor: None, is_primary: None,
+        };
+        let (original, mirror, orphan) = (ss(1, None), ss(2, Some(1)), ss(3, Some(99)));
+        let (analyzed, linked) = split_mirrored(&[&original, &mirror, &orphan]);
+        assert_eq!(analyzed.iter().map(|s| s.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(linked.iter().map(|s| s.id).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_primary_only_links_whole_group() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots: Vec<Screenshot> = [
+            screenshot(&db, sid, "2025-01-01T10:00:00", 7),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 3),
+            // Only the secondary monitor changed on this tick
+            screenshot(&db, sid, "2025-01-01T10:00:30", 3),
+        ]
+        .iter()
+        .map(|ss| {
+            db.set_is_primary(ss.id, ss.monitor_index == 7).unwrap();
+            db.get_screenshot(ss.id).unwrap()
+        })
+        .collect();
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions {
+            monitor_scope: MonitorScope::PrimaryOnly,
+            ..options(sid)
+        };
+
+        let run = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await;
+        assert_eq!(run, AnalysisRun { processed: 1, linked_without_ai: 2, failed_links: 0 });
+        let seen = provider.seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].monitors, vec!["Monitor 7"]);
+
+        let task = db.get_task_for_screenshot(screenshots[0].id).unwrap().unwrap();
+        for ss in &screenshots[1..] {
+            assert_eq!(db.get_task_for_screenshot(ss.id).unwrap().unwrap().id, task.id);
+        }
+        assert!(db.get_unanalyzed_screenshots_for_session(sid, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_scope_group() {
+        let ss = |id, is_primary| Screenshot {
+            id, filepath: format!("{}.webp", id), captured_at: "2025-01-01T10:00:00".to_string(),
+            active_window_title: None, monitor_index: id as i32, capture_group: Some("g".to_string()), mirrored_of: None, previous_active: false, scale_factor: None, is_primary,
+        };
+        let ids = |list: &[&Screenshot]| list.iter().map(|s| s.id).collect::<Vec<_>>();
+
+        let (secondary, primary) = (ss(0, Some(false)), ss(1, Some(true)));
+        let (analyzed, linked) = scope_group(&[&secondary, &primary], MonitorScope::ChangedOnly);
+        assert_eq!((analyzed.len(), linked.len()), (2, 0));
+        let (analyzed, linked) = scope_group(&[&secondary, &primary], MonitorScope::PrimaryOnly);
+        assert_eq!((ids(&analyzed), ids(&linked)), (vec![1], vec![0]));
+        // Only a known secondary changed: nothing to analyze
+        let (analyzed, linked) = scope_group(&[&secondary], MonitorScope::PrimaryOnly);
+        assert_eq!((ids(&analyzed), ids(&linked)), (vec![], vec![0]));
+        // Flags not recorded: analyze the whole group instead of holding it back
+        let (legacy_a, legacy_b) = (ss(2, None), ss(3, None));
+        let (analyzed, linked) = scope_group(&[&legacy_a, &legacy_b], MonitorScope::PrimaryOnly);
+        assert_eq!((ids(&analyzed), ids(&linked)), (vec![2, 3], vec![]));
+        assert_eq!(MonitorScope::from_setting("primary_only"), MonitorScope::PrimaryOnly);
+        assert_eq!(MonitorScope::from_setting("bogus"), MonitorScope::All);
+    }
+
+    /// Fresh screenshots dir with one valid and one truncated WebP, named like `screenshot()` rows.
+    fn screenshots_dir_with_corrupt_file(name: &str, valid: &str, corrupt: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = image::RgbaImage::from_pixel(16, 16, image::Rgba([10, 20, 30, 255]));
+        crate::capture::save_image_as_webp(&image, &dir.join(valid)).unwrap();
+        let bytes = std::fs::read(dir.join(valid)).unwrap();
+        std::fs::write(dir.join(corrupt), &bytes[..bytes.len() / 2]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_decode_or_quarantine_truncated_file() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let good = screenshot(&db, sid, "2025-01-01T10:00:00", 0);
+        let bad = screenshot(&db, sid, "2025-01-01T10:00:00", 1);
+        let dir = screenshots_dir_with_corrupt_file("quarantine", "2025-01-01T10-00-00_0.webp", "2025-01-01T10-00-00_1.webp");
+
+        assert!(decode_or_quarantine(&db, &dir, &good));
+        assert!(!decode_or_quarantine(&db, &dir, &bad));
+
+        assert!(!dir.join("2025-01-01T10-00-00_1.webp").exists());
+        assert!(dir.join("corrupt/2025-01-01T10-00-00_1.webp").exists());
+        assert_eq!(db.get_screenshot(bad.id).unwrap().filepath, "screenshots/corrupt/2025-01-01T10-00-00_1.webp");
+        let unanalyzed: Vec<i64> = db.get_unanalyzed_screenshots_for_session(sid, 10).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(unanalyzed, vec![good.id]);
+
+        // Missing files aren't treated as corrupt
+        let missing = screenshot(&db, sid, "2025-01-01T10:01:00", 0);
+        assert!(decode_or_quarantine(&db, &dir, &missing));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_run_analysis_continues_past_corrupt_screenshot() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let screenshots = vec![
+            screenshot(&db, sid, "2025-01-01T10:00:00", 0),
+            screenshot(&db, sid, "2025-01-01T10:00:00", 1),
+        ];
+        let dir = screenshots_dir_with_corrupt_file("corrupt_group", "2025-01-01T10-00-00_1.webp", "2025-01-01T10-00-00_0.webp");
+        let provider = MockProvider::new(vec![analysis("Coding", true)]);
+        let states = Mutex::new(HashMap::new());
+        let cancel = AtomicBool::new(false);
+        let opts = AnalysisOptions { screenshots_dir: &dir, ..options(sid) };
+
+        let processed = run_analysis(&db, &provider, &screenshots, &states, &cancel, &opts).await.processed;
+        assert_eq!(processed, 1);
+        assert_eq!(provider.seen.lock().unwrap()[0].monitors, vec!["Monitor 1"]);
+        assert!(db.get_task_for_screenshot(screenshots[1].id).unwrap().is_some());
+        assert!(db.get_unanalyzed_screenshots_for_session(sid, 10).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_task_metadata_custom_fields() {
+        let extra = HashMap::from([
+            ("project".to_string(), "rlcollector".to_string()),
+            ("unrequested".to_string(), "x".to_string()),
+        ]);
+        let fields = vec!["project".to_string(), "ticket".to_string()];
+        let metadata: serde_json::Value = serde_json::from_str(&task_metadata("mock/test-model", None, &extra, &fields)).unwrap();
+        assert_eq!(metadata["custom_fields"], serde_json::json!({ "project": "rlcollector" }));
+
+        let metadata: serde_json::Value = serde_json::from_str(&task_metadata("mock/test-model", None, &extra, &[])).unwrap();
+        assert!(metadata.get("custom_fields").is_none());
+    }
+
+    #[test]
+    fn test_apply_category_correction() {
+        let rules = vec![CategoryCorrection {
+            id: 7,
+            keywords: vec!["reviewing".to_string(), "prs".to_string()],
+            category: "coding".to_string(),
+            count: 2,
+        }];
+
+        let (category, entry) = apply_category_correction("Reviewing PRs", "browsing", &rules);
+        assert_eq!(category, "coding");
+        let metadata: serde_json::Value = serde_json::from_str(&task_metadata("mock/test-model", entry, &HashMap::new(), &[])).unwrap();
+        assert_eq!(metadata["analyzed_by"], "mock/test-model");
+        assert_eq!(metadata["category_override"]["ai_category"], "browsing");
+        assert_eq!(metadata["category_override"]["rule_id"], 7);
+
+        // Already matching the rule: no override recorded
+        assert_eq!(apply_category_correction("Reviewing PRs", "coding", &rules), ("coding".to_string(), None));
+        // No matching rule
+        assert_eq!(apply_category_correction("Writing docs", "writing", &rules), ("writing".to_string(), None));
+    }
+
+    #[test]
+    fn test_group_by_capture_group() {
+        let screenshots = vec![
+            Screenshot {
+                id: 1, filepath: "a.webp".to_string(), captured_at: "2025-01-01T10:00:00".to_string(),
+                active_window_title: None, monitor_index: 0, capture_group: Some("g1".to_string()), mirrored_of: None, previous_active: false, scale_factor: None, is_primary: None,
+            },
+            Screenshot {
+                id: 2, filepath: "b.webp".to_string(), captured_at: "2025-01-01T10:00:00".to_string(),
+                active_window_title: None, monitor_index: 1, capture_group: Some("g1".to_string()), mirrored_of: None, previous_active: false, scale_factor: None, is_primary: None,
+            },
+            Screenshot {
+                id: 3, filepath: "c.webp".to_string(), captured_at: "2025-01-01T10:00:30".to_string(),
+                active_window_title: None, monitor_index: 0, capture_group: Some("g2".to_string()), mirrored_of: None, previous_active: false, scale_factor: None, is_primary: None,
+            },
+            Screenshot {
+                id: 4, filepath: "d.webp".to_string(), captured_at: "2025-01-01T10:01:00".to_string(),
+                active_window_title: None, monitor_index: 0, capture_group: None, mirrored_of: None, previous_active: false, scale_factor: None, is_primary: None,
+            },
+        ];
+
+        let groups = group_by_capture_group(&screenshots);
+        assert_eq!(groups.len(), 3); // g1 (2 items), g2 (1 item), ungrouped (1 item)
+        assert_eq!(groups[0].len(), 2); // g1
+        assert_eq!(groups[1].len(), 1); // g2
+        assert_eq!(groups[2].len(), 1); // ungrouped
+    }
+
+    #[test]
+    fn test_sample_groups_every_nth() {
+        let screenshots: Vec<Screenshot> = (0..7)
+            .map(|i| Screenshot {
+                id: i, filepath: format!("{}.webp", i), captured_at: format!("2025-01-01T10:00:0{}", i),
+                active_window_title: None, monitor_index: 0, capture_group: Some(format!("g{}", i)), mirrored_of: None, previous_active: false, scale_factor: None, is_primary: None,
+            })
+            .collect();
+
+        let sampled = sample_groups(group_by_capture_group(&screenshots), 3);
+        assert_eq!(sampled.len(), 3);
+        let sampled_ids: Vec<i64> = sampled.iter().map(|(g, _)| g[0].id).collect();
+        assert_eq!(sampled_ids, vec![0, 3, 6]);
+        let skipped_ids: Vec<i64> = sampled[0].1.iter().map(|ss| ss.id).collect();
+        assert_eq!(skipped_ids, vec![1, 2]);
+        assert!(sampled[2].1.is_empty());
+
+        // Sampling of 1 keeps every group
+        let all = sample_groups(group_by_capture_group(&screenshots), 1);
+        assert_eq!(all.len(), 7);
+        assert!(all.iter().all(|(_, skipped)| skipped.is_empty()));
+    }
+}
@@ -0,0 +1,86 @@
+//! Application names from window titles, for `get_session_top_apps`.
+
+use crate::models::AppUsage;
+use std::collections::HashMap;
+
+/// How many apps `get_session_top_apps` returns.
+pub const TOP_APPS: usize = 5;
+
+/// Separators between a window's document and its app ("main.rs - Visual Studio Code",
+/// "Inbox — Mozilla Firefox", "Notes – Obsidian").
+const SEPARATORS: &[&str] = &[" — ", " – ", " - "];
+
+/// The app name in a window title: the text after the last separator, or the whole
+/// title when there is none. None for a blank title or one ending in a separator.
+pub fn app_name(title: &str) -> Option<String> {
+    let tail = SEPARATORS
+        .iter()
+        .filter_map(|sep| title.rfind(sep).map(|i| &title[i + sep.len()..]))
+        .min_by_key(|tail| tail.len())
+        .unwrap_or(title)
+        .trim();
+    (!tail.is_empty()).then(|| tail.to_string())
+}
+
+/// The `limit` most frequent apps among `(title, screenshot count)` pairs, with their
+/// share of all titled screenshots. Ties go to the alphabetically first app so the
+/// order is stable.
+pub fn top_apps(titles: &[(String, u32)], limit: usize) -> Vec<AppUsage> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for (title, count) in titles {
+        if let Some(app) = app_name(title) {
+            *counts.entry(app).or_default() += count;
+        }
+    }
+    let total: u32 = titles.iter().map(|(_, count)| count).sum();
+    let mut ranked: Vec<(String, u32)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(app, screenshots)| AppUsage {
+            app,
+            screenshots,
+            share: screenshots as f64 / total as f64,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_name() {
+        assert_eq!(app_name("main.rs - my-project - Visual Studio Code").as_deref(), Some("Visual Studio Code"));
+        assert_eq!(app_name("Inbox (3) — Mozilla Firefox").as_deref(), Some("Mozilla Firefox"));
+        assert_eq!(app_name("Daily note – Obsidian").as_deref(), Some("Obsidian"));
+        // The last separator wins even when the kinds are mixed
+        assert_eq!(app_name("a-b - Docs — Google Chrome").as_deref(), Some("Google Chrome"));
+        assert_eq!(app_name("Docs — x - Slack").as_deref(), Some("Slack"));
+        // Hyphens inside words aren't separators
+        assert_eq!(app_name("  Task-Manager  ").as_deref(), Some("Task-Manager"));
+        assert_eq!(app_name("Untitled - "), None);
+        assert_eq!(app_name("   "), None);
+    }
+
+    #[test]
+    fn test_top_apps_ranking() {
+        let titles = vec![
+            ("main.rs - Visual Studio Code".to_string(), 4),
+            ("lib.rs - Visual Studio Code".to_string(), 3),
+            ("Inbox — Mozilla Firefox".to_string(), 5),
+            ("Slack".to_string(), 2),
+            ("Zoom".to_string(), 2),
+            (" - ".to_string(), 4),
+        ];
+        let top = top_apps(&titles, 3);
+        let names: Vec<&str> = top.iter().map(|a| a.app.as_str()).collect();
+        // Titles of the same app merge; the Slack/Zoom tie is broken alphabetically
+        assert_eq!(names, vec!["Visual Studio Code", "Mozilla Firefox", "Slack"]);
+        assert_eq!(top[0].screenshots, 7);
+        // Shares are of all titled screenshots, including ones with no app name
+        assert!((top[0].share - 7.0 / 20.0).abs() < 1e-9);
+        assert!(top_apps(&[], TOP_APPS).is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::models::{CaptureSession, SessionArchive, Task};
+use crate::storage::Database;
+use log::warn;
+use serde::Serialize;
+use std::io::BufWriter;
+use std::path::Path;
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+/// Progress is reported after this many files, and after the last one.
+pub const PROGRESS_EVERY: usize = 25;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Failed to write archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to write archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Failed to serialize metadata: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("The archive can't be saved inside the screenshots directory")]
+    InsideScreenshotsDir,
+}
+
+/// `metadata.json` at the root of a session archive.
+#[derive(Debug, Serialize)]
+struct ArchiveMetadata {
+    session: CaptureSession,
+    tasks: Vec<Task>,
+    files: Vec<ArchiveFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct ArchiveFile {
+    name: String,
+    captured_at: String,
+    active_window_title: Option<String>,
+    monitor_index: i32,
+    task_id: Option<i64>,
+}
+
+/// Already-compressed images are stored; anything else is deflated.
+pub fn compression_for(name: &str) -> CompressionMethod {
+    let ext = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    match ext.as_str() {
+        "webp" | "png" | "jpg" | "jpeg" | "gif" => CompressionMethod::Stored,
+        _ => CompressionMethod::Deflated,
+    }
+}
+
+/// Refuse destinations inside `screenshots_dir`, where the archive would sit among
+/// (and could end up zipping) the files it archives. Symlinks are resolved where the
+/// paths exist.
+pub fn validate_destination(dest: &Path, screenshots_dir: &Path) -> Result<(), ArchiveError> {
+    let resolve = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let parent = dest.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if resolve(parent).starts_with(resolve(screenshots_dir)) {
+        return Err(ArchiveError::InsideScreenshotsDir);
+    }
+    Ok(())
+}
+
+/// Zip a session's screenshot files into `dest`, streaming each file from disk.
+/// Files that are missing or unreadable are skipped and listed in the result. With
+/// `include_metadata`, a `metadata.json` describes the session, its tasks and each
+/// file. `progress(done, total)` runs every `PROGRESS_EVERY` files. A failed export
+/// removes the partial archive.
+pub fn export_session_archive(
+    db: &Database,
+    session_id: i64,
+    screenshots_dir: &Path,
+    dest: &Path,
+    include_metadata: bool,
+    progress: impl FnMut(usize, usize),
+) -> Result<SessionArchive, ArchiveError> {
+    validate_destination(dest, screenshots_dir)?;
+    let session = db.get_session(session_id)?;
+    let result = write_archive(db, session, screenshots_dir, dest, include_metadata, progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(dest);
+    }
+    result
+}
+
+fn write_archive(
+    db: &Database,
+    session: CaptureSession,
+    screenshots_dir: &Path,
+    dest: &Path,
+    include_metadata: bool,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<SessionArchive, ArchiveError> {
+    let screenshots = db.get_session_screenshots(session.id)?;
+    let task_ids = db.get_session_screenshot_tasks(session.id)?;
+    let mut zip = zip::ZipWriter::new(BufWriter::new(std::fs::File::create(dest)?));
+    let mut summary = SessionArchive { files_added: 0, missing_files: Vec::new(), bytes_written: 0 };
+    let mut files = Vec::new();
+
+    let total = screenshots.len();
+    for (i, ss) in screenshots.into_iter().enumerate() {
+        let name = ss.filepath.strip_prefix("screenshots/").unwrap_or(&ss.filepath).to_string();
+        match std::fs::File::open(screenshots_dir.join(&name)) {
+            Ok(mut file) => {
+                zip.start_file(name.as_str(), SimpleFileOptions::default().compression_method(compression_for(&name)))?;
+                std::io::copy(&mut file, &mut zip)?;
+                summary.files_added += 1;
+                files.push(ArchiveFile {
+                    task_id: task_ids.get(&ss.id).copied(),
+                    name,
+                    captured_at: ss.captured_at,
+                    active_window_title: ss.active_window_title,
+                    monitor_index: ss.monitor_index,
+                });
+            }
+            Err(e) => {
+                warn!("Leaving {} out of the archive: {}", name, e);
+                summary.missing_files.push(name);
+            }
+        }
+        if (i + 1) % PROGRESS_EVERY == 0 || i + 1 == total {
+            progress(i + 1, total);
+        }
+    }
+
+    if include_metadata {
+        let tasks = db.get_session_tasks(session.id)?;
+        zip.start_file("metadata.json", SimpleFileOptions::default().compression_method(CompressionMethod::Deflated))?;
+        serde_json::to_writer_pretty(&mut zip, &ArchiveMetadata { session, tasks, files })?;
+    }
+
+    zip.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    summary.bytes_written = std::fs::metadata(dest)?.len();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_archive_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("screenshots")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_compression_for() {
+        assert_eq!(compression_for("2025-01-01T10-00-00_0.webp"), CompressionMethod::Stored);
+        assert_eq!(compression_for("shot.PNG"), CompressionMethod::Stored);
+        assert_eq!(compression_for("metadata.json"), CompressionMethod::Deflated);
+        assert_eq!(compression_for("no_extension"), CompressionMethod::Deflated);
+    }
+
+    #[test]
+    fn test_validate_destination() {
+        let dir = test_dir("validate");
+        let shots = dir.join("screenshots");
+        assert!(matches!(
+            validate_destination(&shots.join("out.zip"), &shots),
+            Err(ArchiveError::InsideScreenshotsDir)
+        ));
+        std::fs::create_dir_all(shots.join("corrupt")).unwrap();
+        assert!(validate_destination(&shots.join("corrupt").join("out.zip"), &shots).is_err());
+        assert!(validate_destination(&shots.join("..").join("screenshots").join("out.zip"), &shots).is_err());
+        assert!(validate_destination(&dir.join("out.zip"), &shots).is_ok());
+        // A sibling whose name merely starts with "screenshots" is fine
+        std::fs::create_dir_all(dir.join("screenshots_backup")).unwrap();
+        assert!(validate_destination(&dir.join("screenshots_backup").join("out.zip"), &shots).is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_session_archive() {
+        let dir = test_dir("export");
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", Some("Auth work"), None).unwrap();
+        let a = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", Some("auth.rs - Code"), 0, Some(sid), None).unwrap();
+        db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 0, Some(sid), None).unwrap();
+        db.insert_screenshot("screenshots/gone.webp", "2025-01-01T10:01:00", None, 0, Some(sid), None).unwrap();
+        std::fs::write(dir.join("screenshots/a.webp"), "aaa").unwrap();
+        std::fs::write(dir.join("screenshots/b.webp"), "bbb").unwrap();
+        let task = db.insert_full_task("Writing code", "", "coding", "2025-01-01T10:00:00", "").unwrap();
+        db.link_screenshot_to_task(task, a).unwrap();
+
+        let dest = dir.join("session.zip");
+        let mut reported = Vec::new();
+        let summary = export_session_archive(&db, sid, &dir.join("screenshots"), &dest, true, |done, total| {
+            reported.push((done, total))
+        })
+        .unwrap();
+        assert_eq!(summary.files_added, 2);
+        assert_eq!(summary.missing_files, vec!["gone.webp"]);
+        assert_eq!(summary.bytes_written, std::fs::metadata(&dest).unwrap().len());
+        assert_eq!(reported, vec![(3, 3)]);
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&dest).unwrap()).unwrap();
+        assert_eq!(zip.len(), 3);
+        {
+            let mut contents = String::new();
+            let mut entry = zip.by_name("a.webp").unwrap();
+            assert_eq!(entry.compression(), CompressionMethod::Stored);
+            entry.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, "aaa");
+        }
+
+        let mut json = String::new();
+        zip.by_name("metadata.json").unwrap().read_to_string(&mut json).unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(metadata["session"]["description"], "Auth work");
+        assert_eq!(metadata["tasks"][0]["title"], "Writing code");
+        assert_eq!(metadata["files"][0]["name"], "a.webp");
+        assert_eq!(metadata["files"][0]["task_id"], task);
+        assert_eq!(metadata["files"][0]["active_window_title"], "auth.rs - Code");
+        assert!(metadata["files"][1]["task_id"].is_null());
+        assert_eq!(metadata["files"].as_array().unwrap().len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_without_metadata() {
+        let dir = test_dir("no_metadata");
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        std::fs::write(dir.join("screenshots/a.webp"), "aaa").unwrap();
+
+        let dest = dir.join("session.zip");
+        export_session_archive(&db, sid, &dir.join("screenshots"), &dest, false, |_, _| {}).unwrap();
+        let zip = zip::ZipArchive::new(std::fs::File::open(&dest).unwrap()).unwrap();
+        assert_eq!(zip.file_names().collect::<Vec<_>>(), vec!["a.webp"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
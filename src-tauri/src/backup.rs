@@ -0,0 +1,209 @@
+use crate::settings;
+use crate::storage::{Database, RowMap};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// Version of the dump format. Bump when a table or column change would make older
+/// dumps restore incorrectly; `import_all` refuses dumps newer than this.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Tables included in a dump, parents before children so inserts satisfy foreign keys.
+/// `analysis_debug` and `activity_samples` are deliberately left out: saved analysis inputs
+/// and imported browsing history stay on this machine.
+const TABLES: &[&str] = &[
+    "capture_sessions",
+    "screenshots",
+    "tasks",
+    "task_screenshots",
+    "labels",
+    "settings",
+    "category_corrections",
+    "capture_events",
+];
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Failed to access dump file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid dump file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Dump schema version {0} is newer than this app supports ({SCHEMA_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("Database already contains data; import with force to replace it")]
+    NotEmpty,
+}
+
+/// The whole database as one JSON document. Screenshot files are referenced by
+/// path only and are not included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseDump {
+    pub schema_version: u32,
+    pub exported_at: String,
+    pub tables: BTreeMap<String, Vec<RowMap>>,
+}
+
+/// Snapshot every table into a dump. `settings::SECRET_SETTINGS` rows are only
+/// included with `include_secrets`.
+pub fn export_all(db: &Database, include_secrets: bool, exported_at: &str) -> Result<DatabaseDump, BackupError> {
+    let mut tables = BTreeMap::new();
+    for table in TABLES {
+        let mut rows = db.table_rows(table)?;
+        if *table == "settings" && !include_secrets {
+            rows.retain(|row| !is_secret_row(row));
+        }
+        tables.insert(table.to_string(), rows);
+    }
+    Ok(DatabaseDump {
+        schema_version: SCHEMA_VERSION,
+        exported_at: exported_at.to_string(),
+        tables,
+    })
+}
+
+/// Restore a dump, replacing all dumped tables. Refuses a database that already has
+/// sessions, screenshots or tasks unless `force` is set. Tables missing from the dump
+/// end up empty; unknown tables in the dump are ignored. Secret settings the dump
+/// leaves out (see `export_all`) keep their current values.
+pub fn import_all(db: &Database, dump: &DatabaseDump, force: bool) -> Result<(), BackupError> {
+    if dump.schema_version > SCHEMA_VERSION {
+        return Err(BackupError::UnsupportedVersion(dump.schema_version));
+    }
+    if !force && db.has_data()? {
+        return Err(BackupError::NotEmpty);
+    }
+    let mut settings_rows = dump.tables.get("settings").cloned().unwrap_or_default();
+    let dumped: Vec<Option<&str>> = settings_rows.iter().filter(|row| is_secret_row(row)).map(row_key).collect();
+    let kept: Vec<RowMap> = db
+        .table_rows("settings")?
+        .into_iter()
+        .filter(|row| is_secret_row(row) && !dumped.contains(&row_key(row)))
+        .collect();
+    settings_rows.extend(kept);
+
+    let tables: Vec<(&str, &[RowMap])> = TABLES
+        .iter()
+        .map(|&t| match t {
+            "settings" => (t, settings_rows.as_slice()),
+            _ => (t, dump.tables.get(t).map(Vec::as_slice).unwrap_or_default()),
+        })
+        .collect();
+    db.replace_tables(&tables)?;
+    Ok(())
+}
+
+fn row_key(row: &RowMap) -> Option<&str> {
+    row.get("key").and_then(|v| v.as_str())
+}
+
+fn is_secret_row(row: &RowMap) -> bool {
+    row_key(row).is_some_and(|key| settings::SECRET_SETTINGS.contains(&key))
+}
+
+pub fn write_dump(dump: &DatabaseDump, path: &Path) -> Result<(), BackupError> {
+    std::fs::write(path, serde_json::to_vec_pretty(dump)?)?;
+    Ok(())
+}
+
+pub fn read_dump(path: &Path) -> Result<DatabaseDump, BackupError> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SessionStats, TaskUpdate};
+
+    /// A database with at least one row in every dumped table.
+    fn populated_db() -> Database {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", Some("Shipping login"), Some("Auth")).unwrap();
+        db.add_session_stats(sid, &SessionStats { ticks: 4, ..Default::default() }).unwrap();
+        let s1 = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", Some("Editor"), 0, Some(sid), Some("g1")).unwrap();
+        let s2 = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 1, Some(sid), Some("g2")).unwrap();
+        db.insert_screenshot("screenshots/c.webp", "2025-01-01T09:00:00", None, 0, None, None).unwrap();
+        let task = db.insert_full_task("Writing code", "Editing auth.rs", "coding", "2025-01-01T10:00:00", "IDE open").unwrap();
+        db.set_task_metadata(task, r#"{"analyzed_by":"claude/test"}"#).unwrap();
+        db.link_screenshot_to_task(task, s1).unwrap();
+        db.link_screenshot_to_task(task, s2).unwrap();
+        db.set_screenshot_label(s1, "Editing auth", "coding", "2025-01-02T09:00:00").unwrap();
+        db.set_setting("ai_provider", "ollama").unwrap();
+        db.set_setting("ai_api_key", "sk-source").unwrap();
+        let reviewing = db.insert_full_task("Reviewing PRs", "On GitHub", "browsing", "2025-01-01T10:05:00", "Browser open").unwrap();
+        db.update_task(reviewing, &TaskUpdate {
+            title: None,
+            description: None,
+            category: Some("coding".to_string()),
+            ended_at: None,
+            user_verified: None,
+        }).unwrap();
+        db.record_capture_event(Some(sid), "2025-01-01T10:01:00", "skipped_private", Some("keyword index 0")).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = populated_db();
+        let dump = export_all(&source, false, "2025-01-02T00:00:00").unwrap();
+        assert_eq!(dump.schema_version, SCHEMA_VERSION);
+        for table in TABLES {
+            assert!(!dump.tables[*table].is_empty(), "{} should have rows", table);
+        }
+
+        let json = serde_json::to_string(&dump).unwrap();
+        let restored = Database::in_memory().unwrap();
+        import_all(&restored, &serde_json::from_str(&json).unwrap(), false).unwrap();
+
+        let again = export_all(&restored, false, "2025-01-02T00:00:00").unwrap();
+        assert_eq!(again.tables, dump.tables);
+        let session = &restored.get_sessions(10, 0).unwrap()[0];
+        assert_eq!(session.title.as_deref(), Some("Auth"));
+        assert_eq!(session.stats.ticks, 4);
+        assert_eq!(restored.get_setting("ai_provider").unwrap().as_deref(), Some("ollama"));
+    }
+
+    #[test]
+    fn test_import_rejects_non_empty_without_force() {
+        let dump = export_all(&populated_db(), false, "2025-01-02T00:00:00").unwrap();
+        let target = Database::in_memory().unwrap();
+        target.create_session("2024-12-31T08:00:00", None, None).unwrap();
+
+        assert!(matches!(import_all(&target, &dump, false), Err(BackupError::NotEmpty)));
+        import_all(&target, &dump, true).unwrap();
+        let sessions = target.get_sessions(10, 0).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].started_at, "2025-01-01T10:00:00");
+    }
+
+    #[test]
+    fn test_import_rejects_newer_schema() {
+        let mut dump = export_all(&populated_db(), false, "2025-01-02T00:00:00").unwrap();
+        dump.schema_version = SCHEMA_VERSION + 1;
+        let target = Database::in_memory().unwrap();
+        assert!(matches!(import_all(&target, &dump, false), Err(BackupError::UnsupportedVersion(_))));
+        assert!(!target.has_data().unwrap());
+    }
+
+    #[test]
+    fn test_secrets_only_with_include_secrets() {
+        let source = populated_db();
+        let has_key = |dump: &DatabaseDump| dump.tables["settings"].iter().any(|row| row_key(row) == Some("ai_api_key"));
+        let dump = export_all(&source, false, "2025-01-02T00:00:00").unwrap();
+        assert!(!has_key(&dump));
+        assert!(!serde_json::to_string(&dump).unwrap().contains("sk-source"));
+        let with_secrets = export_all(&source, true, "2025-01-02T00:00:00").unwrap();
+        assert!(has_key(&with_secrets));
+
+        // A dump without secrets keeps the target's own key; one with secrets replaces it
+        let target = Database::in_memory().unwrap();
+        target.set_setting("ai_api_key", "sk-target").unwrap();
+        import_all(&target, &dump, false).unwrap();
+        assert_eq!(target.get_setting("ai_api_key").unwrap().as_deref(), Some("sk-target"));
+        assert_eq!(target.get_setting("ai_provider").unwrap().as_deref(), Some("ollama"));
+        import_all(&target, &with_secrets, true).unwrap();
+        assert_eq!(target.get_setting("ai_api_key").unwrap().as_deref(), Some("sk-source"));
+    }
+}
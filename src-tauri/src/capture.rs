@@ -1,9 +1,11 @@
-use crate::models::MonitorInfo;
-use log::{error, info, warn};
-use std::io::Cursor;
-use std::path::Path;
+use crate::models::{CaptureCapability, MonitorInfo};
+use log::{error, trace, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
-use xcap::Monitor;
+use xcap::{Monitor, Window};
 use image::RgbaImage;
 use image::codecs::webp::WebPEncoder;
 use image::imageops::FilterType;
@@ -23,20 +25,399 @@ pub struct CapturedMonitor {
     pub monitor_id: u32,
     pub monitor_name: String,
     pub image: RgbaImage,
+    /// Captured because the user just switched away from it ("active" mode with
+    /// `capture_previous_active`), not because the cursor is on it.
+    pub previous_active: bool,
+    /// Physical pixels per logical pixel (1.0 unscaled, 2.0 on a 200% display).
+    pub scale_factor: f64,
+    /// The OS primary monitor (always true for a directory source).
+    pub is_primary: bool,
 }
 
-/// Save an RGBA image as WebP to the given path.
+/// Suffix of in-progress screenshot writes; leftovers are removed by `sweep_temp_files`.
+const TEMP_SUFFIX: &str = ".partial";
+
+/// Temporary path for writing `path`: a hidden sibling named after the final file,
+/// so concurrent writes of different screenshots never share a temp file.
+fn temp_path(path: &Path) -> std::path::PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}{}", name, TEMP_SUFFIX))
+}
+
+/// Write `bytes` to `path` so it either doesn't exist or is complete: write and fsync
+/// a temp file in the same directory, then rename it into place (and on Unix fsync
+/// the directory so the rename survives a power loss).
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = temp_path(path);
+    let written = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()
+    })();
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Save an RGBA image as WebP to the given path. The file appears atomically,
+/// so a crash mid-write never leaves a truncated screenshot behind.
 pub fn save_image_as_webp(image: &RgbaImage, path: &Path) -> Result<(), CaptureError> {
-    let mut buf = Cursor::new(Vec::new());
-    let encoder = WebPEncoder::new_lossless(&mut buf);
-    image
-        .write_with_encoder(encoder)
-        .map_err(|e| CaptureError::SaveFailed(e.to_string()))?;
-    std::fs::write(path, buf.into_inner())
-        .map_err(|e| CaptureError::SaveFailed(e.to_string()))?;
+    let bytes = encode_webp_bytes(image)?;
+    write_atomic(path, &bytes).map_err(|e| CaptureError::SaveFailed(e.to_string()))
+}
+
+/// Remove temp files left in `dir` by writes interrupted by a crash. Returns how many
+/// were removed.
+pub fn sweep_temp_files(dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| is_temp_file(&entry.file_name().to_string_lossy()))
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Whether a file name is one of `write_atomic`'s temp files.
+pub fn is_temp_file(name: &str) -> bool {
+    name.starts_with('.') && name.ends_with(TEMP_SUFFIX)
+}
+
+/// Check `dir` can hold screenshots: an absolute path to a directory (created if
+/// missing) that we can write to, probed with a throwaway file.
+pub fn validate_screenshots_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_absolute() {
+        return Err(format!("{} is not an absolute path", dir.display()));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let probe = dir.join(".rlcollector_write_test");
+    File::create(&probe)
+        .and_then(|mut f| f.write_all(b"ok"))
+        .map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
     Ok(())
 }
 
+/// Move everything in `from` (screenshots and subdirectories like `corrupt/`) into
+/// `to`, renaming where possible and copying then deleting across drives. Returns the
+/// number of files moved. If any entry fails, the ones already moved are moved back.
+pub fn move_dir_contents(from: &Path, to: &Path) -> std::io::Result<u64> {
+    let mut moved = 0;
+    let mut done = Vec::new();
+    let result = std::fs::read_dir(from).and_then(|entries| {
+        for entry in entries {
+            let name = entry?.file_name();
+            moved += move_entry(&from.join(&name), &to.join(&name))?;
+            done.push(name);
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        for name in done.iter().rev() {
+            if let Err(back) = move_entry(&to.join(name), &from.join(name)) {
+                error!("Failed to move {} back to {}: {}", to.join(name).display(), from.display(), back);
+            }
+        }
+        return Err(e);
+    }
+    Ok(moved)
+}
+
+/// Move one file or directory for `move_dir_contents`, leaving nothing behind in
+/// `to` on failure.
+fn move_entry(from: &Path, to: &Path) -> std::io::Result<u64> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(if to.is_dir() { count_files(to) } else { 1 });
+    }
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        let moved = move_dir_contents(from, to).inspect_err(|_| {
+            let _ = std::fs::remove_dir(to);
+        })?;
+        if let Err(e) = std::fs::remove_dir(from) {
+            let _ = move_dir_contents(to, from);
+            let _ = std::fs::remove_dir(to);
+            return Err(e);
+        }
+        Ok(moved)
+    } else {
+        std::fs::copy(from, to)?;
+        if let Err(e) = std::fs::remove_file(from) {
+            let _ = std::fs::remove_file(to);
+            return Err(e);
+        }
+        Ok(1)
+    }
+}
+
+fn count_files(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| if e.path().is_dir() { count_files(&e.path()) } else { 1 })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Whether a captured image is large enough to be a real screen. Captures taken
+/// mid display-mode switch can come back 0×0 or a few pixels wide.
+pub fn is_valid_capture(image: &RgbaImage, min_w: u32, min_h: u32) -> bool {
+    image.width() >= min_w && image.height() >= min_h && image.width() > 0 && image.height() > 0
+}
+
+/// Next capture interval for adaptive capture: snap to `min` after a tick with a
+/// change, otherwise back off by 1.5x per unchanged tick up to `max`.
+pub fn next_interval(current: u64, changed: bool, min: u64, max: u64) -> u64 {
+    let max = max.max(min);
+    if changed {
+        min
+    } else {
+        (current.saturating_mul(3) / 2).clamp(min, max)
+    }
+}
+
+/// Bounds for a `monitor_intervals` override, in ms.
+pub const MIN_MONITOR_INTERVAL_MS: u64 = 1_000;
+pub const MAX_MONITOR_INTERVAL_MS: u64 = 86_400_000;
+
+/// Parse the `monitor_intervals` setting: comma-separated `monitor_id=ms` pairs, e.g.
+/// `2=600000`, giving those monitors their own capture interval.
+pub fn parse_monitor_intervals(setting: &str) -> Result<HashMap<u32, u64>, String> {
+    let mut intervals = HashMap::new();
+    for pair in setting.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (id, ms) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not a monitor_id=ms pair", pair))?;
+        let id: u32 = id.trim().parse().map_err(|_| format!("'{}' is not a monitor id", id.trim()))?;
+        let ms: u64 = ms.trim().parse().map_err(|_| format!("'{}' is not a number of ms", ms.trim()))?;
+        if !(MIN_MONITOR_INTERVAL_MS..=MAX_MONITOR_INTERVAL_MS).contains(&ms) {
+            return Err(format!(
+                "interval for monitor {} must be between {} and {} ms",
+                id, MIN_MONITOR_INTERVAL_MS, MAX_MONITOR_INTERVAL_MS
+            ));
+        }
+        if intervals.insert(id, ms).is_some() {
+            return Err(format!("monitor {} is listed twice", id));
+        }
+    }
+    Ok(intervals)
+}
+
+/// Capture intervals for one tick: the loop's regular interval plus per-monitor overrides.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitorIntervals {
+    /// Interval of monitors without an override: the capture interval, or the current
+    /// adaptive one.
+    pub default_ms: u64,
+    pub overrides: HashMap<u32, u64>,
+}
+
+impl MonitorIntervals {
+    pub fn interval_for(&self, monitor_id: u32) -> u64 {
+        self.overrides.get(&monitor_id).copied().unwrap_or(self.default_ms)
+    }
+
+    /// How long the loop sleeps between ticks: the shortest interval of any monitor, so
+    /// each is captured on the first tick after it's due.
+    pub fn loop_interval(&self) -> u64 {
+        self.overrides.values().copied().fold(self.default_ms, u64::min)
+    }
+}
+
+/// What the capture loop should do after a tick, per `NoMonitorState`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NoMonitorTransition {
+    Unchanged,
+    /// Too many ticks in a row found no monitors: pause the session.
+    Pause,
+    /// Monitors are back after a `Pause`: resume the session.
+    Resume,
+}
+
+/// Tracks consecutive `NoMonitors` ticks (headless or disconnected RDP sessions) so
+/// the loop pauses after `no_monitor_pause_after` of them instead of retrying forever.
+#[derive(Debug, Default)]
+pub struct NoMonitorState {
+    consecutive: u32,
+    paused: bool,
+}
+
+impl NoMonitorState {
+    pub fn on_no_monitors(&mut self, threshold: u32) -> NoMonitorTransition {
+        self.consecutive = self.consecutive.saturating_add(1);
+        if !self.paused && self.consecutive >= threshold {
+            self.paused = true;
+            NoMonitorTransition::Pause
+        } else {
+            NoMonitorTransition::Unchanged
+        }
+    }
+
+    pub fn on_monitors_found(&mut self) -> NoMonitorTransition {
+        self.consecutive = 0;
+        if std::mem::take(&mut self.paused) {
+            NoMonitorTransition::Resume
+        } else {
+            NoMonitorTransition::Unchanged
+        }
+    }
+
+    /// Whether capture is currently paused because no monitors were found.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+/// The capture loop counts as stalled after this many intervals without a tick...
+pub const STALL_INTERVALS: u64 = 3;
+/// ...but never sooner than this, so one slow tick at a short interval isn't a stall.
+pub const MIN_STALL_MS: u64 = 30_000;
+/// Loop restarts the watchdog attempts before giving up and stopping capture.
+pub const MAX_RECOVERIES: u32 = 2;
+
+/// Whether a loop that last ticked at `last_tick_ms` has stalled by `now_ms`.
+/// A clock that went backwards never counts as a stall.
+pub fn is_stalled(last_tick_ms: u64, now_ms: u64, interval_ms: u64) -> bool {
+    let threshold = interval_ms.saturating_mul(STALL_INTERVALS).max(MIN_STALL_MS);
+    now_ms.saturating_sub(last_tick_ms) > threshold
+}
+
+/// What the watchdog should do after a check, per `Watchdog`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum WatchdogAction {
+    Healthy,
+    /// No recent tick: restart the capture loop on the same session.
+    Restart,
+    /// `MAX_RECOVERIES` restarts didn't bring ticks back: stop capture and end the session.
+    Stop,
+}
+
+/// Tracks restarts of a stalled capture loop. A restart counts as successful once the
+/// loop ticks after it, which resets the count.
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    recoveries: u32,
+    restarted_at_ms: Option<u64>,
+}
+
+impl Watchdog {
+    pub fn check(&mut self, last_tick_ms: u64, now_ms: u64, interval_ms: u64) -> WatchdogAction {
+        if let Some(at) = self.restarted_at_ms {
+            if last_tick_ms >= at {
+                self.recoveries = 0;
+                self.restarted_at_ms = None;
+            }
+        }
+        // A restarted loop gets a full stall period before it's judged again
+        let since = last_tick_ms.max(self.restarted_at_ms.unwrap_or(0));
+        if !is_stalled(since, now_ms, interval_ms) {
+            return WatchdogAction::Healthy;
+        }
+        if self.recoveries >= MAX_RECOVERIES {
+            return WatchdogAction::Stop;
+        }
+        self.recoveries += 1;
+        self.restarted_at_ms = Some(now_ms);
+        WatchdogAction::Restart
+    }
+}
+
+/// Reduce a monitor name to a short lowercase ASCII slug, e.g. `\\.\DISPLAY1` -> `display1`.
+/// Never contains path separators or characters that are invalid in Windows filenames.
+pub fn monitor_slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .take(16)
+        .collect();
+    if slug.is_empty() {
+        "mon".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Build a collision-free screenshot filename inside `dir`.
+/// Names sort by tick timestamp, then by the zero-padded sequence within the tick,
+/// and use the same scheme regardless of capture mode:
+/// `screenshot_{timestamp}_{seq}_{monitor slug}.webp`. If the name already exists,
+/// a numeric suffix is appended until an unused name is found.
+pub fn screenshot_filename(dir: &Path, timestamp: &str, seq: usize, monitor_name: &str) -> String {
+    let ts: String = timestamp
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let stem = format!("screenshot_{}_{:02}_{}", ts, seq, monitor_slug(monitor_name));
+
+    let mut filename = format!("{}.webp", stem);
+    let mut n = 1;
+    while dir.join(&filename).exists() {
+        filename = format!("{}_{}.webp", stem, n);
+        n += 1;
+    }
+    filename
+}
+
+/// Titles of all non-minimized windows, used to screen out private content.
+pub fn visible_window_titles() -> Result<Vec<String>, CaptureError> {
+    let windows = Window::all().map_err(|e| CaptureError::CaptureFailed(e.to_string()))?;
+    Ok(windows
+        .iter()
+        .filter(|w| !w.is_minimized())
+        .map(|w| w.title().to_string())
+        .filter(|t| !t.is_empty())
+        .collect())
+}
+
+/// Title of the focused window; None if no window has focus or it has no title.
+#[cfg(target_os = "windows")]
+pub fn active_window_title() -> Result<Option<String>, CaptureError> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW};
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() {
+            return Ok(None);
+        }
+        let mut buf = [0u16; 512];
+        let len = GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32);
+        let title = String::from_utf16_lossy(&buf[..len.max(0) as usize]);
+        Ok(Some(title).filter(|t| !t.is_empty()))
+    }
+}
+
+/// Title of the focused window; None if no window has focus or it has no title.
+#[cfg(target_os = "linux")]
+pub fn active_window_title() -> Result<Option<String>, CaptureError> {
+    use std::process::Command;
+    let output = Command::new("xdotool")
+        .args(["getactivewindow", "getwindowname"])
+        .output()
+        .map_err(|e| CaptureError::CaptureFailed(format!("xdotool: {}", e)))?;
+    if !output.status.success() {
+        // Also fails when nothing has focus (e.g. an empty desktop)
+        return Ok(None);
+    }
+    let title = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(title).filter(|t| !t.is_empty()))
+}
+
+/// Title of the frontmost window: xcap lists windows front to back, so the first
+/// visible titled one.
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn active_window_title() -> Result<Option<String>, CaptureError> {
+    Ok(visible_window_titles()?.into_iter().next())
+}
+
 /// List all available monitors.
 pub fn list_monitors() -> Result<Vec<MonitorInfo>, CaptureError> {
     let monitors = Monitor::all().map_err(|e| CaptureError::CaptureFailed(e.to_string()))?;
@@ -54,23 +435,116 @@ pub fn list_monitors() -> Result<Vec<MonitorInfo>, CaptureError> {
         .collect())
 }
 
+// --- Capture capability ---
+
+/// Why the environment can't capture before even trying: headless Linux has no
+/// display server to enumerate monitors from.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn display_server_missing(display: Option<&str>, wayland_display: Option<&str>) -> Option<String> {
+    let set = |v: Option<&str>| v.is_some_and(|v| !v.trim().is_empty());
+    if set(display) || set(wayland_display) {
+        None
+    } else {
+        Some("No display server: neither DISPLAY nor WAYLAND_DISPLAY is set".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn environment_blocker() -> Option<String> {
+    display_server_missing(
+        std::env::var("DISPLAY").ok().as_deref(),
+        std::env::var("WAYLAND_DISPLAY").ok().as_deref(),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn environment_blocker() -> Option<String> {
+    None
+}
+
+/// Whether this is a remote desktop session, where capture often yields nothing once
+/// the client is minimized or disconnected.
+#[cfg(target_os = "windows")]
+pub fn is_remote_session() -> bool {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_REMOTESESSION};
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_remote_session() -> bool {
+    false
+}
+
+/// Combine the individual checks into a report. `monitors` is the enumeration result
+/// and `probe` the result of capturing one monitor; either is None if not attempted.
+pub fn assess_capability(
+    blocker: Option<String>,
+    remote_session: bool,
+    monitors: Option<Result<usize, String>>,
+    probe: Option<Result<(), String>>,
+) -> CaptureCapability {
+    let monitor_count = match &monitors {
+        Some(Ok(n)) => *n as u32,
+        _ => 0,
+    };
+    let remote_note = if remote_session { " (remote desktop session)" } else { "" };
+    let reason = blocker.or_else(|| match (&monitors, &probe) {
+        (Some(Err(e)), _) => Some(format!("Could not enumerate monitors{}: {}", remote_note, e)),
+        (Some(Ok(0)), _) => Some(format!("No monitors found{}", remote_note)),
+        (_, Some(Err(e))) => Some(format!("Test capture failed{}: {}", remote_note, e)),
+        (None, _) | (_, None) => Some("Capture was not tested".to_string()),
+        _ => None,
+    });
+    CaptureCapability {
+        can_capture: reason.is_none(),
+        reason,
+        remote_session,
+        monitor_count,
+        probe_ok: matches!(probe, Some(Ok(()))),
+    }
+}
+
+/// Check whether screen capture works here: the environment, monitor enumeration and
+/// one probe capture of the primary monitor. Blocks for the length of a capture.
+pub fn check_capability() -> CaptureCapability {
+    let remote_session = is_remote_session();
+    if let Some(blocker) = environment_blocker() {
+        return assess_capability(Some(blocker), remote_session, None, None);
+    }
+    let monitors = match Monitor::all() {
+        Ok(monitors) => monitors,
+        Err(e) => return assess_capability(None, remote_session, Some(Err(e.to_string())), None),
+    };
+    let count = monitors.len();
+    let probe = find_primary(monitors).ok().map(|mut primary| {
+        match primary.swap_remove(0).capture_image() {
+            Ok(image) if is_valid_capture(&image, 1, 1) => Ok(()),
+            Ok(image) => Err(format!("empty {}x{} image", image.width(), image.height())),
+            Err(e) => Err(e.to_string()),
+        }
+    });
+    assess_capability(None, remote_session, Some(Ok(count)), probe)
+}
+
 // --- Cursor position (platform-specific) ---
+// None when the position can't be read. Can block (xdotool subprocess on Linux),
+// so async callers should run it on the blocking pool.
 
 #[cfg(target_os = "windows")]
-pub fn get_cursor_position() -> (i32, i32) {
+pub fn get_cursor_position() -> Option<(i32, i32)> {
     unsafe {
         let mut point = windows_sys::Win32::Foundation::POINT { x: 0, y: 0 };
         if windows_sys::Win32::UI::WindowsAndMessaging::GetCursorPos(&mut point) != 0 {
-            (point.x, point.y)
+            Some((point.x, point.y))
         } else {
-            warn!("GetCursorPos failed, falling back to (0, 0)");
-            (0, 0)
+            warn!("GetCursorPos failed");
+            None
         }
     }
 }
 
 #[cfg(target_os = "macos")]
-pub fn get_cursor_position() -> (i32, i32) {
+pub fn get_cursor_position() -> Option<(i32, i32)> {
     #[repr(C)]
     struct CGPoint {
         x: f64,
@@ -86,44 +560,87 @@ pub fn get_cursor_position() -> (i32, i32) {
         if !event.is_null() {
             let point = CGEventGetLocation(event);
             CFRelease(event);
-            (point.x as i32, point.y as i32)
+            Some((point.x as i32, point.y as i32))
         } else {
-            warn!("CGEventCreate failed, falling back to (0, 0)");
-            (0, 0)
+            warn!("CGEventCreate failed");
+            None
         }
     }
 }
 
 #[cfg(target_os = "linux")]
-pub fn get_cursor_position() -> (i32, i32) {
+pub fn get_cursor_position() -> Option<(i32, i32)> {
     use std::process::Command;
     match Command::new("xdotool")
         .args(["getmouselocation"])
         .output()
     {
         Ok(output) if output.status.success() => {
-            let text = String::from_utf8_lossy(&output.stdout);
-            let mut x = 0i32;
-            let mut y = 0i32;
-            for part in text.split_whitespace() {
-                if let Some(val) = part.strip_prefix("x:") {
-                    x = val.parse().unwrap_or(0);
-                } else if let Some(val) = part.strip_prefix("y:") {
-                    y = val.parse().unwrap_or(0);
-                }
+            let position = parse_mouse_location(&String::from_utf8_lossy(&output.stdout));
+            if position.is_none() {
+                warn!("Unexpected xdotool getmouselocation output");
             }
-            (x, y)
+            position
         }
         _ => {
-            warn!("xdotool getmouselocation failed, falling back to (0, 0)");
-            (0, 0)
+            warn!("xdotool getmouselocation failed");
+            None
         }
     }
 }
 
 #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-pub fn get_cursor_position() -> (i32, i32) {
-    (0, 0)
+pub fn get_cursor_position() -> Option<(i32, i32)> {
+    None
+}
+
+/// Parse `xdotool getmouselocation` output ("x:1200 y:340 screen:0 window:123").
+/// None unless both coordinates are present.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mouse_location(text: &str) -> Option<(i32, i32)> {
+    let mut x = None;
+    let mut y = None;
+    for part in text.split_whitespace() {
+        if let Some(val) = part.strip_prefix("x:") {
+            x = val.parse().ok();
+        } else if let Some(val) = part.strip_prefix("y:") {
+            y = val.parse().ok();
+        }
+    }
+    Some((x?, y?))
+}
+
+/// Where the cursor was taken to be for an "active" mode tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorFix {
+    /// Read this tick.
+    Live(i32, i32),
+    /// The read failed; the last position that was read successfully.
+    Cached(i32, i32),
+    /// The read failed and no position has been read yet; the primary monitor is used.
+    Unknown,
+}
+
+impl CursorFix {
+    pub fn position(self) -> Option<(i32, i32)> {
+        match self {
+            CursorFix::Live(x, y) | CursorFix::Cached(x, y) => Some((x, y)),
+            CursorFix::Unknown => None,
+        }
+    }
+}
+
+/// Resolve this tick's cursor `read`, falling back to `last_good` when it failed.
+/// A successful read replaces `last_good`.
+pub fn resolve_cursor(read: Option<(i32, i32)>, last_good: &mut Option<(i32, i32)>) -> CursorFix {
+    match (read, *last_good) {
+        (Some((x, y)), _) => {
+            *last_good = Some((x, y));
+            CursorFix::Live(x, y)
+        }
+        (None, Some((x, y))) => CursorFix::Cached(x, y),
+        (None, None) => CursorFix::Unknown,
+    }
 }
 
 // --- Monitor selection helpers ---
@@ -138,13 +655,119 @@ fn find_primary(monitors: Vec<Monitor>) -> Result<Vec<Monitor>, CaptureError> {
     Ok(vec![primary])
 }
 
+/// Monitors to capture in "active" mode: the `active` one, plus the `previous` one
+/// (flagged true) on the first tick after the cursor moves to another monitor.
+/// Callers pass the last tick's active monitor as `previous`, or None to disable.
+pub fn active_mode_targets(active: u32, previous: Option<u32>) -> Vec<(u32, bool)> {
+    let mut targets = vec![(active, false)];
+    if let Some(prev) = previous.filter(|&p| p != active) {
+        targets.push((prev, true));
+    }
+    targets
+}
+
+/// Monitor id of the frames a `directory:` capture source plays back.
+pub const DIRECTORY_MONITOR_ID: u32 = 0;
+
+/// Files a directory capture source plays back, by extension.
+const DIRECTORY_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "bmp"];
+
+/// Where the capture loop gets its frames (`capture_source`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// The real screen, through `capture_monitors`.
+    Screen,
+    /// Image files replayed by a `DirectoryCaptureSource`.
+    Directory(PathBuf),
+}
+
+/// Parse the `capture_source` setting: `screen` (or empty) or `directory:<path>`. The
+/// directory isn't checked here; a missing one fails each tick instead.
+pub fn parse_capture_source(setting: &str) -> Result<CaptureSource, String> {
+    let setting = setting.trim();
+    if setting.is_empty() || setting == "screen" {
+        return Ok(CaptureSource::Screen);
+    }
+    match setting.strip_prefix("directory:").map(str::trim) {
+        Some("") => Err("directory: needs a path".to_string()),
+        Some(path) => Ok(CaptureSource::Directory(PathBuf::from(path))),
+        None => Err(format!("expected \"screen\" or \"directory:<path>\", got \"{}\"", setting)),
+    }
+}
+
+/// Replays a directory of images as a single monitor, one per tick in file name order,
+/// starting over after the last. For developing and testing the pipeline without a
+/// screen. The directory is listed on every frame, so files added meanwhile join in.
+pub struct DirectoryCaptureSource {
+    dir: PathBuf,
+    next: usize,
+}
+
+impl DirectoryCaptureSource {
+    pub fn new(dir: PathBuf) -> Self {
+        DirectoryCaptureSource { dir, next: 0 }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The next image as a capture of `DIRECTORY_MONITOR_ID`. A file that can't be
+    /// decoded fails its own tick only; the next call moves on to the following file.
+    pub fn next_frame(&mut self) -> Result<CapturedMonitor, CaptureError> {
+        let files = self.image_files()?;
+        if files.is_empty() {
+            return Err(CaptureError::CaptureFailed(format!("No images in {}", self.dir.display())));
+        }
+        let index = self.next % files.len();
+        self.next = index + 1;
+        let path = &files[index];
+        trace!("Replaying {}", path.display());
+        let image = image::open(path)
+            .map_err(|e| CaptureError::CaptureFailed(format!("Failed to read {}: {}", path.display(), e)))?
+            .to_rgba8();
+        Ok(CapturedMonitor {
+            monitor_id: DIRECTORY_MONITOR_ID,
+            monitor_name: "directory".to_string(),
+            image,
+            previous_active: false,
+            scale_factor: 1.0,
+            is_primary: true,
+        })
+    }
+
+    /// Image files directly in the directory, sorted by file name.
+    fn image_files(&self) -> Result<Vec<PathBuf>, CaptureError> {
+        let entries = std::fs::read_dir(&self.dir)
+            .map_err(|e| CaptureError::CaptureFailed(format!("Failed to list {}: {}", self.dir.display(), e)))?;
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| DIRECTORY_IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
 /// Capture monitors based on the configured mode.
 /// Returns captured images in memory (caller is responsible for saving to disk).
+/// In "active" mode the monitor under `cursor` is captured (the primary one if the
+/// position is unknown), plus `previous_active` if the cursor has left it. Monitors in
+/// `skip` (not due yet under `monitor_intervals`) are left out.
 pub fn capture_monitors(
     mode: &str,
     specific_id: Option<u32>,
+    previous_active: Option<u32>,
+    cursor: Option<(i32, i32)>,
+    skip: &[u32],
 ) -> Result<Vec<CapturedMonitor>, CaptureError> {
-    info!("Capturing monitors: mode={}, specific_id={:?}", mode, specific_id);
+    trace!("Capturing monitors: mode={}, specific_id={:?}", mode, specific_id);
     let monitors = Monitor::all().map_err(|e| {
         error!("Failed to enumerate monitors: {}", e);
         CaptureError::CaptureFailed(e.to_string())
@@ -153,7 +776,7 @@ pub fn capture_monitors(
         return Err(CaptureError::NoMonitors);
     }
 
-    let selected: Vec<Monitor> = match mode {
+    let mut selected: Vec<(Monitor, bool)> = match mode {
         "specific" => {
             let id = specific_id.ok_or_else(|| {
                 CaptureError::CaptureFailed("No monitor ID for 'specific' mode".into())
@@ -161,25 +784,36 @@ pub fn capture_monitors(
             monitors
                 .into_iter()
                 .find(|m| m.id() == id)
-                .map(|m| vec![m])
+                .map(|m| vec![(m, false)])
                 .ok_or_else(|| CaptureError::CaptureFailed(format!("Monitor {} not found", id)))?
         }
         "active" => {
-            let (cx, cy) = get_cursor_position();
-            match Monitor::from_point(cx, cy) {
-                Ok(m) => vec![m],
-                Err(e) => {
-                    warn!("from_point({}, {}) failed: {}, using primary", cx, cy, e);
-                    find_primary(monitors)?
+            let under_cursor = cursor.and_then(|(cx, cy)| {
+                Monitor::from_point(cx, cy)
+                    .map_err(|e| warn!("from_point({}, {}) failed: {}, using primary", cx, cy, e))
+                    .ok()
+            });
+            let active = match under_cursor {
+                Some(m) => m,
+                None => find_primary(monitors.clone())?.swap_remove(0),
+            };
+            let mut selected = vec![];
+            for (id, previous) in active_mode_targets(active.id(), previous_active) {
+                if !previous {
+                    selected.push((active.clone(), false));
+                } else if let Some(m) = monitors.iter().find(|m| m.id() == id) {
+                    selected.push((m.clone(), true));
                 }
             }
+            selected
         }
-        "all" => monitors,
-        _ => find_primary(monitors)?, // "default"
+        "all" => monitors.into_iter().map(|m| (m, false)).collect(),
+        _ => find_primary(monitors)?.into_iter().map(|m| (m, false)).collect(), // "default"
     };
+    selected.retain(|(monitor, _)| !skip.contains(&monitor.id()));
 
     let mut results = Vec::with_capacity(selected.len());
-    for monitor in &selected {
+    for (monitor, previous_active) in &selected {
         let image = monitor.capture_image().map_err(|e| {
             error!("Capture failed for monitor {}: {}", monitor.name(), e);
             CaptureError::CaptureFailed(e.to_string())
@@ -188,6 +822,9 @@ pub fn capture_monitors(
             monitor_id: monitor.id(),
             monitor_name: monitor.name().to_string(),
             image,
+            previous_active: *previous_active,
+            scale_factor: monitor.scale_factor() as f64,
+            is_primary: monitor.is_primary(),
         });
     }
     Ok(results)
@@ -219,6 +856,27 @@ pub fn perceptual_hash(image: &RgbaImage) -> [u8; 32] {
     hash
 }
 
+/// Width images are shrunk to before hashing when wider than the
+/// `hash_prescale_threshold` setting.
+pub const HASH_PRESCALE_WIDTH: u32 = 256;
+
+/// `perceptual_hash`, first shrinking images wider than `threshold` pixels to
+/// `HASH_PRESCALE_WIDTH` wide. Resizing a 4K/8K frame straight to 16x16 is a
+/// measurable per-tick cost; `thumbnail`'s integer box filter gets it to 256px in
+/// about half the time (a filtered `resize_for_analysis` costs more than it saves)
+/// and yields an essentially identical hash. A threshold of 0 always hashes at full
+/// resolution.
+pub fn perceptual_hash_prescaled(image: &RgbaImage, threshold: u32) -> [u8; 32] {
+    if threshold == 0 || image.width() <= threshold {
+        return perceptual_hash(image);
+    }
+    let height = (image.height() as u64 * HASH_PRESCALE_WIDTH as u64 / image.width() as u64).max(1) as u32;
+    perceptual_hash(&image::imageops::thumbnail(image, HASH_PRESCALE_WIDTH, height))
+}
+
+/// Hash distance at which the capture loop counts a monitor's frame as changed.
+pub const CHANGE_THRESHOLD: u32 = 10;
+
 /// Compute the hamming distance between two perceptual hashes.
 pub fn hash_distance(a: &[u8; 32], b: &[u8; 32]) -> u32 {
     a.iter()
@@ -227,8 +885,34 @@ pub fn hash_distance(a: &[u8; 32], b: &[u8; 32]) -> u32 {
         .sum()
 }
 
+/// The screenshot saved earlier in the same tick that this capture duplicates, e.g.
+/// when displays are mirrored for a presentation: the first of `saved` (screenshot id,
+/// hash) within `threshold` bits of `hash`. A threshold of 0 turns this off.
+pub fn find_mirror(hash: &[u8; 32], saved: &[(i64, [u8; 32])], threshold: u32) -> Option<i64> {
+    if threshold == 0 {
+        return None;
+    }
+    saved
+        .iter()
+        .find(|(_, other)| hash_distance(hash, other) <= threshold)
+        .map(|(id, _)| *id)
+}
+
 // --- Image processing utilities ---
 
+/// Convert to grayscale (BT.601 luma, the weights `perceptual_hash` uses) while keeping
+/// the RGBA container, so saving, hashing and analysis work unchanged. Alpha is kept.
+pub fn to_grayscale_rgba(image: &RgbaImage) -> RgbaImage {
+    let mut gray = image.clone();
+    for pixel in gray.pixels_mut() {
+        let luma = ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000) as u8;
+        pixel[0] = luma;
+        pixel[1] = luma;
+        pixel[2] = luma;
+    }
+    gray
+}
+
 /// Downscale an image so its width is at most `max_width` pixels,
 /// preserving aspect ratio. Returns the original image if already small enough.
 pub fn resize_for_analysis(image: &RgbaImage, max_width: u32) -> RgbaImage {
@@ -240,21 +924,40 @@ pub fn resize_for_analysis(image: &RgbaImage, max_width: u32) -> RgbaImage {
     image::imageops::resize(image, max_width, new_height, FilterType::Triangle)
 }
 
-/// Attempt to crop to the active window on Linux using xdotool.
+/// Attempt to crop to the active window on Linux using xdotool. `scale_factor` is that
+/// of the display the image was captured on (see `scaled_crop_rect`).
 /// Falls back to the full image on failure or non-Linux platforms.
-pub fn crop_active_window(image: &RgbaImage) -> RgbaImage {
+pub fn crop_active_window(image: &RgbaImage, scale_factor: f64) -> RgbaImage {
     #[cfg(target_os = "linux")]
     {
-        if let Some(cropped) = crop_active_window_linux(image) {
+        if let Some(cropped) = crop_active_window_linux(image, scale_factor) {
             return cropped;
         }
     }
-    let _ = image; // suppress unused warning on non-linux
+    let _ = (image, scale_factor); // suppress unused warning on non-linux
     image.clone()
 }
 
+/// The window at `(x, y, width, height)` as a crop of an `image`-sized (width, height)
+/// capture. Window geometry is in logical pixels and the capture in physical ones, so
+/// each edge is scaled by `scale_factor` (1.0 if it isn't a positive number) and
+/// rounded, then clamped to the image. None if nothing of the window is on it.
+pub fn scaled_crop_rect(window: (i32, i32, u32, u32), scale_factor: f64, image: (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+    let scale = if scale_factor.is_finite() && scale_factor > 0.0 { scale_factor } else { 1.0 };
+    let (x, y, width, height) = window;
+    let edge = |v: f64, max: u32| (v * scale).round().clamp(0.0, max as f64) as u32;
+    let left = edge(x as f64, image.0);
+    let right = edge(x as f64 + width as f64, image.0);
+    let top = edge(y as f64, image.1);
+    let bottom = edge(y as f64 + height as f64, image.1);
+    if right == left || bottom == top {
+        return None;
+    }
+    Some((left, top, right - left, bottom - top))
+}
+
 #[cfg(target_os = "linux")]
-fn crop_active_window_linux(image: &RgbaImage) -> Option<RgbaImage> {
+fn crop_active_window_linux(image: &RgbaImage, scale_factor: f64) -> Option<RgbaImage> {
     use std::process::Command;
 
     let window_id_output = Command::new("xdotool")
@@ -279,8 +982,8 @@ fn crop_active_window_linux(image: &RgbaImage) -> Option<RgbaImage> {
     }
     let geom_str = String::from_utf8_lossy(&geom_output.stdout);
 
-    let mut x: u32 = 0;
-    let mut y: u32 = 0;
+    let mut x: i32 = 0;
+    let mut y: i32 = 0;
     let mut width: u32 = 0;
     let mut height: u32 = 0;
     for line in geom_str.lines() {
@@ -300,16 +1003,7 @@ fn crop_active_window_linux(image: &RgbaImage) -> Option<RgbaImage> {
         return None;
     }
 
-    let (img_w, img_h) = image.dimensions();
-    let x = x.min(img_w.saturating_sub(1));
-    let y = y.min(img_h.saturating_sub(1));
-    let width = width.min(img_w - x);
-    let height = height.min(img_h - y);
-
-    if width == 0 || height == 0 {
-        return None;
-    }
-
+    let (x, y, width, height) = scaled_crop_rect((x, y, width, height), scale_factor, image.dimensions())?;
     Some(image::imageops::crop_imm(image, x, y, width, height).to_image())
 }
 
@@ -333,6 +1027,110 @@ mod tests {
         assert!(monitors.is_ok() || monitors.is_err());
     }
 
+    #[test]
+    fn test_is_valid_capture_boundary() {
+        assert!(is_valid_capture(&RgbaImage::new(100, 100), 100, 100));
+        assert!(!is_valid_capture(&RgbaImage::new(99, 100), 100, 100));
+        assert!(!is_valid_capture(&RgbaImage::new(100, 99), 100, 100));
+        assert!(!is_valid_capture(&RgbaImage::new(0, 0), 0, 0));
+    }
+
+    #[test]
+    fn test_is_valid_capture_normal_image() {
+        assert!(is_valid_capture(&RgbaImage::new(1920, 1080), 100, 100));
+    }
+
+    #[test]
+    fn test_next_interval_backs_off_when_unchanged() {
+        let mut interval = 10_000;
+        let mut seen = Vec::new();
+        for _ in 0..6 {
+            interval = next_interval(interval, false, 10_000, 60_000);
+            seen.push(interval);
+        }
+        assert_eq!(seen, vec![15_000, 22_500, 33_750, 50_625, 60_000, 60_000]);
+    }
+
+    #[test]
+    fn test_next_interval_speeds_up_on_change() {
+        assert_eq!(next_interval(60_000, true, 10_000, 60_000), 10_000);
+        assert_eq!(next_interval(10_000, true, 10_000, 60_000), 10_000);
+    }
+
+    #[test]
+    fn test_next_interval_clamps_to_bounds() {
+        // A current interval outside the bounds (e.g. after the settings changed) is pulled back in
+        assert_eq!(next_interval(1_000, false, 10_000, 60_000), 10_000);
+        assert_eq!(next_interval(120_000, false, 10_000, 60_000), 60_000);
+        // max below min is treated as min
+        assert_eq!(next_interval(10_000, false, 10_000, 5_000), 10_000);
+    }
+
+    #[test]
+    fn test_parse_capture_source() {
+        assert_eq!(parse_capture_source("screen"), Ok(CaptureSource::Screen));
+        assert_eq!(parse_capture_source(""), Ok(CaptureSource::Screen));
+        assert_eq!(parse_capture_source(" directory: /tmp/frames "), Ok(CaptureSource::Directory(PathBuf::from("/tmp/frames"))));
+        assert!(parse_capture_source("directory:  ").is_err());
+        assert!(parse_capture_source("camera").is_err());
+    }
+
+    #[test]
+    fn test_directory_capture_source_order_and_loop() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_directory_source_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut source = DirectoryCaptureSource::new(dir.clone());
+        assert!(matches!(source.next_frame(), Err(CaptureError::CaptureFailed(_))));
+
+        // Written out of order; played back by file name. Other files are ignored.
+        for (name, shade) in [("frame_2.png", 20u8), ("frame_1.png", 10), ("frame_3.PNG", 30)] {
+            RgbaImage::from_pixel(8, 4, image::Rgba([shade, 0, 0, 255])).save_with_format(dir.join(name), image::ImageFormat::Png).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+
+        let shades: Vec<u8> = (0..7)
+            .map(|_| {
+                let frame = source.next_frame().unwrap();
+                assert_eq!((frame.monitor_id, frame.image.dimensions()), (DIRECTORY_MONITOR_ID, (8, 4)));
+                frame.image.get_pixel(0, 0)[0]
+            })
+            .collect();
+        assert_eq!(shades, vec![10, 20, 30, 10, 20, 30, 10]);
+
+        // An undecodable file fails its own tick and playback moves past it
+        std::fs::write(dir.join("frame_2.png"), b"corrupt").unwrap();
+        assert!(source.next_frame().is_err());
+        assert_eq!(source.next_frame().unwrap().image.get_pixel(0, 0)[0], 30);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_monitor_intervals() {
+        assert_eq!(parse_monitor_intervals("").unwrap(), HashMap::new());
+        assert_eq!(
+            parse_monitor_intervals(" 2=600000, 1 = 5000 ,").unwrap(),
+            HashMap::from([(2, 600_000), (1, 5_000)])
+        );
+        assert!(parse_monitor_intervals("2").is_err());
+        assert!(parse_monitor_intervals("two=5000").is_err());
+        assert!(parse_monitor_intervals("2=-5").is_err());
+        assert!(parse_monitor_intervals("2=500").is_err());
+        assert!(parse_monitor_intervals("2=5000,2=6000").is_err());
+    }
+
+    #[test]
+    fn test_monitor_intervals_loop_interval() {
+        let intervals = MonitorIntervals {
+            default_ms: 30_000,
+            overrides: HashMap::from([(2, 600_000), (3, 10_000)]),
+        };
+        assert_eq!(intervals.interval_for(1), 30_000);
+        assert_eq!(intervals.interval_for(2), 600_000);
+        // The loop ticks as often as the fastest monitor needs
+        assert_eq!(intervals.loop_interval(), 10_000);
+        assert_eq!(MonitorIntervals { default_ms: 30_000, overrides: HashMap::new() }.loop_interval(), 30_000);
+    }
+
     #[test]
     fn test_list_monitors() {
         // On machines with displays, should return a non-empty list
@@ -381,6 +1179,325 @@ mod tests {
         let _ = std::fs::remove_dir(&temp_dir);
     }
 
+    #[test]
+    fn test_monitor_slug() {
+        assert_eq!(monitor_slug("\\\\.\\DISPLAY1"), "display1");
+        assert_eq!(monitor_slug("DP-2 / HDMI:1"), "dp2hdmi1");
+        assert_eq!(monitor_slug("???"), "mon");
+        assert_eq!(monitor_slug("A Very Long Monitor Name Indeed"), "averylongmonitor");
+    }
+
+    #[test]
+    fn test_no_monitor_state_machine() {
+        let mut state = NoMonitorState::default();
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Unchanged);
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Unchanged);
+        // A tick with monitors resets the streak
+        assert_eq!(state.on_monitors_found(), NoMonitorTransition::Unchanged);
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Unchanged);
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Unchanged);
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Pause);
+        assert!(state.is_paused());
+        // Pauses only once per outage
+        assert_eq!(state.on_no_monitors(3), NoMonitorTransition::Unchanged);
+        assert_eq!(state.on_monitors_found(), NoMonitorTransition::Resume);
+        assert!(!state.is_paused());
+        assert_eq!(state.on_monitors_found(), NoMonitorTransition::Unchanged);
+    }
+
+    #[test]
+    fn test_is_stalled() {
+        let t = 1_700_000_000_000;
+        // 30s interval: stalled only after more than 90s without a tick
+        assert!(!is_stalled(t, t + 5_000, 30_000));
+        assert!(!is_stalled(t, t + 90_000, 30_000));
+        assert!(is_stalled(t, t + 90_001, 30_000));
+        // Short intervals get the MIN_STALL_MS floor
+        assert!(!is_stalled(t, t + 20_000, 1_000));
+        assert!(is_stalled(t, t + 30_001, 1_000));
+        // Clock went backwards
+        assert!(!is_stalled(t, t - 60_000, 1_000));
+    }
+
+    #[test]
+    fn test_watchdog_restart_then_recover() {
+        let t = 1_700_000_000_000;
+        let mut dog = Watchdog::default();
+        assert_eq!(dog.check(t, t + 30_000, 30_000), WatchdogAction::Healthy);
+        assert_eq!(dog.check(t, t + 100_000, 30_000), WatchdogAction::Restart);
+        // The restarted loop gets a grace period even though it hasn't ticked yet
+        assert_eq!(dog.check(t, t + 130_000, 30_000), WatchdogAction::Healthy);
+        // It ticked, so the recovery worked; a later stall starts over with a restart
+        assert_eq!(dog.check(t + 100_500, t + 130_000, 30_000), WatchdogAction::Healthy);
+        for _ in 0..MAX_RECOVERIES {
+            let now = t + 1_000_000 + dog.recoveries as u64 * 200_000;
+            assert_eq!(dog.check(t + 100_500, now, 30_000), WatchdogAction::Restart);
+        }
+    }
+
+    #[test]
+    fn test_watchdog_stops_after_failed_recoveries() {
+        let t = 1_700_000_000_000;
+        let mut dog = Watchdog::default();
+        assert_eq!(dog.check(t, t + 100_000, 30_000), WatchdogAction::Restart);
+        // No tick within a stall period of the restart: the recovery failed
+        assert_eq!(dog.check(t, t + 200_000, 30_000), WatchdogAction::Restart);
+        assert_eq!(dog.check(t, t + 300_000, 30_000), WatchdogAction::Stop);
+    }
+
+    #[test]
+    fn test_temp_path_naming() {
+        let dir = Path::new("/data/screenshots");
+        let a = temp_path(&dir.join("screenshot_2025-01-01T10-00-00_00_DISPLAY1.webp"));
+        let b = temp_path(&dir.join("screenshot_2025-01-01T10-00-00_01_DISPLAY2.webp"));
+        assert_ne!(a, b, "monitors of one tick must not share a temp file");
+        // Same directory so the rename is atomic; hidden and not a .webp until renamed
+        assert_eq!(a.parent(), Some(dir));
+        assert_eq!(a.file_name().unwrap(), ".screenshot_2025-01-01T10-00-00_00_DISPLAY1.webp.partial");
+    }
+
+    #[test]
+    fn test_save_image_as_webp_never_leaves_partial_file() {
+        let temp_dir = std::env::temp_dir().join(format!("rlcollector_test_atomic_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let image = RgbaImage::from_pixel(8, 8, image::Rgba([1, 2, 3, 255]));
+        let output_path = temp_dir.join("shot.webp");
+
+        // Block the temp file so the write fails before anything is renamed
+        std::fs::create_dir(temp_path(&output_path)).unwrap();
+        assert!(save_image_as_webp(&image, &output_path).is_err());
+        assert!(!output_path.exists());
+        std::fs::remove_dir(temp_path(&output_path)).unwrap();
+
+        save_image_as_webp(&image, &output_path).unwrap();
+        assert!(image::open(&output_path).is_ok());
+        assert!(!temp_path(&output_path).exists());
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_sweep_temp_files() {
+        let temp_dir = std::env::temp_dir().join(format!("rlcollector_test_sweep_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join(".a.webp.partial"), b"RIFF").unwrap();
+        std::fs::write(temp_dir.join("b.webp"), b"RIFF").unwrap();
+
+        assert_eq!(sweep_temp_files(&temp_dir), 1);
+        assert!(temp_dir.join("b.webp").exists());
+        assert_eq!(sweep_temp_files(&temp_dir.join("missing")), 0);
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_parse_mouse_location() {
+        assert_eq!(parse_mouse_location("x:1200 y:340 screen:0 window:52428807\n"), Some((1200, 340)));
+        assert_eq!(parse_mouse_location("x:-1920 y:0 screen:0"), Some((-1920, 0)));
+        assert_eq!(parse_mouse_location("x:1200 screen:0"), None);
+        assert_eq!(parse_mouse_location(""), None);
+    }
+
+    #[test]
+    fn test_display_server_missing() {
+        assert!(display_server_missing(None, None).unwrap().contains("DISPLAY"));
+        assert!(display_server_missing(Some(""), Some("  ")).is_some());
+        assert_eq!(display_server_missing(Some(":0"), None), None);
+        assert_eq!(display_server_missing(None, Some("wayland-0")), None);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_remote_session_only_detected_on_windows() {
+        assert!(!is_remote_session());
+    }
+
+    #[test]
+    fn test_assess_capability() {
+        let ok = assess_capability(None, false, Some(Ok(2)), Some(Ok(())));
+        assert!(ok.can_capture && ok.probe_ok);
+        assert_eq!((ok.reason, ok.monitor_count), (None, 2));
+
+        // A remote session that can capture is reported but not blocked
+        let remote = assess_capability(None, true, Some(Ok(1)), Some(Ok(())));
+        assert!(remote.can_capture && remote.remote_session);
+
+        let headless = assess_capability(Some("No display server".into()), false, None, None);
+        assert!(!headless.can_capture);
+        assert_eq!(headless.reason.as_deref(), Some("No display server"));
+
+        let none = assess_capability(None, true, Some(Ok(0)), None);
+        assert_eq!(none.reason.as_deref(), Some("No monitors found (remote desktop session)"));
+
+        let enum_failed = assess_capability(None, false, Some(Err("XGetImage".into())), None);
+        assert_eq!(enum_failed.reason.as_deref(), Some("Could not enumerate monitors: XGetImage"));
+
+        let probe_failed = assess_capability(None, true, Some(Ok(1)), Some(Err("access denied".into())));
+        assert!(!probe_failed.can_capture && !probe_failed.probe_ok);
+        assert_eq!(probe_failed.monitor_count, 1);
+        assert_eq!(
+            probe_failed.reason.as_deref(),
+            Some("Test capture failed (remote desktop session): access denied")
+        );
+    }
+
+    #[test]
+    fn test_resolve_cursor_falls_back_to_last_good() {
+        let mut last_good = None;
+        // Nothing read yet: no position rather than (0, 0), which is always the top-left monitor
+        assert_eq!(resolve_cursor(None, &mut last_good), CursorFix::Unknown);
+        assert_eq!(CursorFix::Unknown.position(), None);
+
+        assert_eq!(resolve_cursor(Some((2500, 400)), &mut last_good), CursorFix::Live(2500, 400));
+        assert_eq!(resolve_cursor(None, &mut last_good), CursorFix::Cached(2500, 400));
+        assert_eq!(resolve_cursor(None, &mut last_good).position(), Some((2500, 400)));
+        assert_eq!(resolve_cursor(Some((10, 10)), &mut last_good), CursorFix::Live(10, 10));
+        assert_eq!(last_good, Some((10, 10)));
+    }
+
+    #[test]
+    fn test_active_mode_targets_across_ticks() {
+        // Tick 1: cursor on monitor 1, nothing before it
+        let mut last_active = None;
+        assert_eq!(active_mode_targets(1, last_active), vec![(1, false)]);
+        last_active = Some(1);
+
+        // Tick 2: switched to monitor 2, so monitor 1 comes along once, flagged
+        assert_eq!(active_mode_targets(2, last_active), vec![(2, false), (1, true)]);
+        last_active = Some(2);
+
+        // Tick 3: still on monitor 2, back to a single capture
+        assert_eq!(active_mode_targets(2, last_active), vec![(2, false)]);
+
+        // Disabled: callers pass None
+        assert_eq!(active_mode_targets(1, None), vec![(1, false)]);
+    }
+
+    #[test]
+    fn test_to_grayscale_rgba() {
+        let image = RgbaImage::from_fn(4, 4, |x, y| image::Rgba([(x * 60) as u8, (y * 60) as u8, 200, 128]));
+        let gray = to_grayscale_rgba(&image);
+        assert_eq!(gray.dimensions(), (4, 4));
+        for pixel in gray.pixels() {
+            assert_eq!(pixel[0], pixel[1]);
+            assert_eq!(pixel[1], pixel[2]);
+            assert_eq!(pixel[3], 128);
+        }
+        // Pure red, green and blue keep their luma weights
+        let primaries = RgbaImage::from_fn(3, 1, |x, _| {
+            let mut p = [0, 0, 0, 255];
+            p[x as usize] = 255;
+            image::Rgba(p)
+        });
+        let gray = to_grayscale_rgba(&primaries);
+        assert_eq!(gray.get_pixel(0, 0)[0], 76);
+        assert_eq!(gray.get_pixel(1, 0)[0], 149);
+        assert_eq!(gray.get_pixel(2, 0)[0], 29);
+    }
+
+    #[test]
+    fn test_grayscale_hash_ignores_color_flicker() {
+        // A highlight changing hue at the same brightness: red and gray share a luma of 87
+        let frame = |highlight: [u8; 3]| {
+            RgbaImage::from_fn(64, 64, |x, y| {
+                let [r, g, b] = if (x / 8 + y / 8) % 2 == 0 { highlight } else { [240, 240, 240] };
+                image::Rgba([r, g, b, 255])
+            })
+        };
+        let before = to_grayscale_rgba(&frame([200, 40, 40]));
+        let after = to_grayscale_rgba(&frame([87, 87, 87]));
+        assert_eq!(before, after);
+        assert_eq!(hash_distance(&perceptual_hash(&before), &perceptual_hash(&after)), 0);
+        // Hashing already works on luma, so turning grayscale on doesn't register as a change
+        let color = frame([200, 40, 40]);
+        assert!(hash_distance(&perceptual_hash(&color), &perceptual_hash(&before)) < 10);
+    }
+
+    #[test]
+    fn test_validate_screenshots_dir() {
+        let base = std::env::temp_dir().join(format!("rlcollector_test_dirs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+
+        let nested = base.join("a").join("b");
+        validate_screenshots_dir(&nested).unwrap();
+        assert!(nested.is_dir());
+        assert_eq!(std::fs::read_dir(&nested).unwrap().count(), 0, "probe file should be removed");
+
+        assert!(validate_screenshots_dir(Path::new("relative/shots")).unwrap_err().contains("absolute"));
+
+        let file = base.join("not_a_dir");
+        std::fs::write(&file, "x").unwrap();
+        assert!(validate_screenshots_dir(&file).is_err());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_move_dir_contents() {
+        let base = std::env::temp_dir().join(format!("rlcollector_test_move_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (from, to) = (base.join("from"), base.join("to"));
+        std::fs::create_dir_all(from.join("corrupt")).unwrap();
+        std::fs::create_dir_all(&to).unwrap();
+        std::fs::write(from.join("a.webp"), "a").unwrap();
+        std::fs::write(from.join("b.webp"), "b").unwrap();
+        std::fs::write(from.join("corrupt").join("c.webp"), "c").unwrap();
+
+        assert_eq!(move_dir_contents(&from, &to).unwrap(), 3);
+        assert_eq!(std::fs::read_to_string(to.join("a.webp")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(to.join("corrupt").join("c.webp")).unwrap(), "c");
+        assert_eq!(std::fs::read_dir(&from).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_move_dir_contents_rolls_back_on_failure() {
+        let base = std::env::temp_dir().join(format!("rlcollector_test_move_back_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (from, to) = (base.join("from"), base.join("to"));
+        std::fs::create_dir_all(from.join("corrupt")).unwrap();
+        std::fs::write(from.join("a.webp"), "a").unwrap();
+        std::fs::write(from.join("b.webp"), "b").unwrap();
+        std::fs::write(from.join("corrupt").join("c.webp"), "c").unwrap();
+        // A file can't replace the non-empty directory of the same name in `to`
+        std::fs::write(from.join("blocked"), "x").unwrap();
+        std::fs::create_dir_all(to.join("blocked")).unwrap();
+        std::fs::write(to.join("blocked").join("keep"), "k").unwrap();
+
+        assert!(move_dir_contents(&from, &to).is_err());
+        assert_eq!(std::fs::read_to_string(from.join("a.webp")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(from.join("b.webp")).unwrap(), "b");
+        assert_eq!(std::fs::read_to_string(from.join("corrupt").join("c.webp")).unwrap(), "c");
+        assert_eq!(std::fs::read_to_string(from.join("blocked")).unwrap(), "x");
+        let left: Vec<_> = std::fs::read_dir(&to).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(left, vec![std::ffi::OsString::from("blocked")]);
+        assert!(to.join("blocked").join("keep").exists());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_screenshot_filename_is_safe_and_unique() {
+        let temp_dir = std::env::temp_dir().join("rlcollector_test_filename");
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let name = screenshot_filename(&temp_dir, "2025-01-01T10-00-00", 1, "\\\\.\\DISPLAY<2>");
+        assert_eq!(name, "screenshot_2025-01-01T10-00-00_01_display2.webp");
+        assert!(!name.chars().any(|c| "<>:\"/\\|?*".contains(c)));
+
+        // Unsafe characters in the timestamp are replaced too
+        let name = screenshot_filename(&temp_dir, "2025-01-01T10:00:00", 0, "eDP-1");
+        assert_eq!(name, "screenshot_2025-01-01T10-00-00_00_edp1.webp");
+
+        // An existing file forces a suffixed name
+        std::fs::write(temp_dir.join(&name), b"x").unwrap();
+        let second = screenshot_filename(&temp_dir, "2025-01-01T10:00:00", 0, "eDP-1");
+        assert_eq!(second, "screenshot_2025-01-01T10-00-00_00_edp1_1.webp");
+
+        let _ = std::fs::remove_file(temp_dir.join(&name));
+        let _ = std::fs::remove_dir(&temp_dir);
+    }
+
     #[test]
     fn test_resize_for_analysis_already_small() {
         let image = RgbaImage::from_raw(100, 50, vec![128u8; 100 * 50 * 4]).unwrap();
@@ -399,10 +1516,26 @@ mod tests {
     #[test]
     fn test_crop_active_window_fallback() {
         let image = RgbaImage::from_raw(100, 50, vec![128u8; 100 * 50 * 4]).unwrap();
-        let cropped = crop_active_window(&image);
+        let cropped = crop_active_window(&image, 1.0);
         assert_eq!(cropped.dimensions(), (100, 50));
     }
 
+    #[test]
+    fn test_scaled_crop_rect() {
+        let window = (100, 50, 400, 300);
+        assert_eq!(scaled_crop_rect(window, 1.0, (1920, 1080)), Some((100, 50, 400, 300)));
+        // At 200% the image has twice the pixels of the window geometry
+        assert_eq!(scaled_crop_rect(window, 2.0, (3840, 2160)), Some((200, 100, 800, 600)));
+        // 1.25 × 50 = 62.5 → 63, 1.25 × 350 = 437.5 → 438
+        assert_eq!(scaled_crop_rect(window, 1.25, (2400, 1350)), Some((125, 63, 500, 375)));
+        // Partly off the image: clamped; entirely off: nothing to crop
+        assert_eq!(scaled_crop_rect((-50, 900, 400, 300), 2.0, (3840, 2160)), Some((0, 1800, 700, 360)));
+        assert_eq!(scaled_crop_rect((2000, 0, 400, 300), 2.0, (3840, 2160)), None);
+        // Unknown scale factors fall back to 1.0
+        assert_eq!(scaled_crop_rect(window, 0.0, (1920, 1080)), Some((100, 50, 400, 300)));
+        assert_eq!(scaled_crop_rect(window, f64::NAN, (1920, 1080)), Some((100, 50, 400, 300)));
+    }
+
     #[test]
     fn test_encode_webp_bytes() {
         let image = RgbaImage::from_raw(10, 10, vec![128u8; 10 * 10 * 4]).unwrap();
@@ -432,6 +1565,51 @@ mod tests {
         let _dist = hash_distance(&h_white, &h_black);
     }
 
+    #[test]
+    fn test_perceptual_hash_prescaled_matches_full_resolution() {
+        // A 4K "desktop": gradient background with a few windows and some text-like rows
+        let (w, h) = (3840, 2160);
+        let image = RgbaImage::from_fn(w, h, |x, y| {
+            let in_window = (200..1800).contains(&x) && (150..1300).contains(&y)
+                || (2100..3600).contains(&x) && (600..2000).contains(&y);
+            let text_row = in_window && y % 40 < 12 && x % 97 < 70;
+            let v = if text_row {
+                20
+            } else if in_window {
+                235
+            } else {
+                ((x + y) * 255 / (w + h)) as u8
+            };
+            image::Rgba([v, v / 2 + 60, 255 - v, 255])
+        });
+
+        let full = perceptual_hash(&image);
+        assert_eq!(perceptual_hash_prescaled(&image, 2560), full);
+        // Below the threshold (or with it off) nothing changes
+        assert_eq!(perceptual_hash_prescaled(&image, 0), full);
+        assert_eq!(perceptual_hash_prescaled(&image, 3840), full);
+    }
+
+    #[test]
+    fn test_find_mirror() {
+        let slide = |w: u32, h: u32, invert: bool| {
+            RgbaImage::from_fn(w, h, |x, y| {
+                let v = ((x * 255 / w) ^ (y * 255 / h)) as u8;
+                let v = if invert { 255 - v } else { v };
+                image::Rgba([v, v, v, 255])
+            })
+        };
+        let main = perceptual_hash(&slide(1920, 1080, false));
+        // A mirrored projector showing the same slide at a lower resolution
+        let projector = perceptual_hash(&slide(1280, 720, false));
+        let other = perceptual_hash(&slide(1920, 1080, true));
+        let saved = [(7, other), (8, main)];
+
+        assert_eq!(find_mirror(&projector, &saved, 4), Some(8));
+        assert_eq!(find_mirror(&main, &saved[..1], 4), None);
+        assert_eq!(find_mirror(&projector, &saved, 0), None, "0 turns detection off");
+    }
+
     #[test]
     fn test_hash_distance_identical() {
         let h = [0xABu8; 32];
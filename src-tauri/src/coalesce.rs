@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long repeats of one log message are counted before they're reported again.
+pub const LOG_REPEAT_WINDOW: Duration = Duration::from_secs(600);
+
+/// Most distinct messages `LogCoalescer` tracks; the one seen longest ago is forgotten first.
+const MAX_TRACKED_MESSAGES: usize = 64;
+
+/// Thins a high-frequency stream of payloads to at most one delivery per `window`.
+/// Payloads offered inside the window are dropped; the final one, offered through
+/// `finish`, is always delivered.
+#[derive(Debug)]
+pub struct Coalescer {
+    window: Duration,
+    last_delivered: Option<Instant>,
+}
+
+impl Coalescer {
+    /// A zero `window` delivers every payload.
+    pub fn new(window: Duration) -> Self {
+        Coalescer { window, last_delivered: None }
+    }
+
+    /// Offer `payload` at `now`. Returns it if a window has passed since the last
+    /// delivery (or nothing has been delivered yet), otherwise drops it.
+    pub fn offer<T>(&mut self, payload: T, now: Instant) -> Option<T> {
+        match self.last_delivered {
+            Some(last) if now.saturating_duration_since(last) < self.window => None,
+            _ => {
+                self.last_delivered = Some(now);
+                Some(payload)
+            }
+        }
+    }
+
+    /// Offer the final payload of a stream. It is always delivered, and the next
+    /// stream starts with a fresh window.
+    pub fn finish<T>(&mut self, payload: T) -> T {
+        self.last_delivered = None;
+        payload
+    }
+}
+
+/// Keeps a persistently failing loop from logging the same line every iteration. The
+/// first occurrence of a message is returned for logging at once; identical ones within
+/// `window` of the last logged one are only counted, and the first after the window is
+/// returned with the count ("context (x42 in last 10m): detail"). Repeats still counted
+/// when a failure stops are never reported.
+#[derive(Debug)]
+pub struct LogCoalescer {
+    window: Duration,
+    /// Keyed by the full "context: detail" line.
+    seen: HashMap<String, Repeats>,
+}
+
+#[derive(Debug)]
+struct Repeats {
+    logged_at: Instant,
+    /// Occurrences since `logged_at`, not counting the logged one.
+    suppressed: u32,
+    last_seen: Instant,
+}
+
+impl LogCoalescer {
+    pub fn new(window: Duration) -> Self {
+        LogCoalescer { window, seen: HashMap::new() }
+    }
+
+    /// Offer `detail` (an error) under `context` ("Screenshot capture failed") at `now`.
+    /// Returns the line to log, or None while it's being counted.
+    pub fn offer(&mut self, context: &str, detail: &str, now: Instant) -> Option<String> {
+        let line = format!("{}: {}", context, detail);
+        if let Some(repeats) = self.seen.get_mut(&line) {
+            repeats.last_seen = now;
+            if now.saturating_duration_since(repeats.logged_at) < self.window {
+                repeats.suppressed += 1;
+                return None;
+            }
+            let occurrences = repeats.suppressed + 1;
+            repeats.logged_at = now;
+            repeats.suppressed = 0;
+            if occurrences == 1 {
+                return Some(line);
+            }
+            return Some(format!("{} (x{} in last {}): {}", context, occurrences, format_window(self.window), detail));
+        }
+        if self.seen.len() >= MAX_TRACKED_MESSAGES {
+            if let Some(oldest) = self.seen.iter().min_by_key(|(_, r)| r.last_seen).map(|(line, _)| line.clone()) {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.seen.insert(line.clone(), Repeats { logged_at: now, suppressed: 0, last_seen: now });
+        Some(line)
+    }
+}
+
+/// "10m", or "90s" for windows that aren't whole minutes.
+fn format_window(window: Duration) -> String {
+    let secs = window.as_secs();
+    if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(500);
+
+    fn ms(start: Instant, offset: u64) -> Instant {
+        start + Duration::from_millis(offset)
+    }
+
+    #[test]
+    fn test_first_payload_delivered_then_window_drops() {
+        let start = Instant::now();
+        let mut c = Coalescer::new(WINDOW);
+        assert_eq!(c.offer(1, ms(start, 0)), Some(1));
+        assert_eq!(c.offer(2, ms(start, 100)), None);
+        assert_eq!(c.offer(3, ms(start, 499)), None);
+        // Window reopens 500ms after the last delivery, not the last offer
+        assert_eq!(c.offer(4, ms(start, 500)), Some(4));
+        assert_eq!(c.offer(5, ms(start, 600)), None);
+    }
+
+    #[test]
+    fn test_deliveries_keep_order() {
+        let start = Instant::now();
+        let mut c = Coalescer::new(WINDOW);
+        let delivered: Vec<u64> = (0..=2000).step_by(50).filter_map(|t| c.offer(t, ms(start, t))).collect();
+        assert_eq!(delivered, vec![0, 500, 1000, 1500, 2000]);
+    }
+
+    #[test]
+    fn test_finish_always_delivers() {
+        let start = Instant::now();
+        let mut c = Coalescer::new(WINDOW);
+        assert_eq!(c.offer("10%", ms(start, 0)), Some("10%"));
+        assert_eq!(c.offer("50%", ms(start, 10)), None);
+        assert_eq!(c.finish("100%"), "100%");
+        // A new stream right away isn't held back by the old window
+        assert_eq!(c.offer("0%", ms(start, 20)), Some("0%"));
+    }
+
+    #[test]
+    fn test_log_coalescer_counts_repeats() {
+        let start = Instant::now();
+        let mut log = LogCoalescer::new(Duration::from_secs(600));
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert_eq!(log.offer("Screenshot capture failed", "disk full", at(0)).as_deref(), Some("Screenshot capture failed: disk full"));
+        for tick in 1..=41 {
+            assert_eq!(log.offer("Screenshot capture failed", "disk full", at(tick * 10)), None);
+        }
+        // The first repeat after the window reports everything since the last line
+        assert_eq!(
+            log.offer("Screenshot capture failed", "disk full", at(600)).as_deref(),
+            Some("Screenshot capture failed (x42 in last 10m): disk full")
+        );
+        assert_eq!(log.offer("Screenshot capture failed", "disk full", at(610)), None);
+    }
+
+    #[test]
+    fn test_log_coalescer_window_reset_and_distinct_messages() {
+        let start = Instant::now();
+        let mut log = LogCoalescer::new(Duration::from_secs(90));
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert!(log.offer("Save failed", "disk full", at(0)).is_some());
+        // Different details or contexts aren't held back by each other
+        assert_eq!(log.offer("Save failed", "permission denied", at(1)).as_deref(), Some("Save failed: permission denied"));
+        assert_eq!(log.offer("Insert failed", "disk full", at(2)).as_deref(), Some("Insert failed: disk full"));
+        assert_eq!(log.offer("Save failed", "disk full", at(30)), None);
+        assert_eq!(log.offer("Save failed", "disk full", at(95)).as_deref(), Some("Save failed (x2 in last 90s): disk full"));
+        // A message that didn't repeat within its window is logged plainly again
+        assert_eq!(log.offer("Insert failed", "disk full", at(200)).as_deref(), Some("Insert failed: disk full"));
+
+        // Tracking is bounded; a forgotten message counts as new
+        let mut log = LogCoalescer::new(Duration::from_secs(600));
+        for i in 0..=MAX_TRACKED_MESSAGES as u64 {
+            assert!(log.offer("Failed", &i.to_string(), at(i)).is_some());
+        }
+        assert_eq!(log.seen.len(), MAX_TRACKED_MESSAGES);
+        assert!(log.offer("Failed", "0", at(100)).is_some());
+        assert_eq!(log.offer("Failed", &MAX_TRACKED_MESSAGES.to_string(), at(101)), None);
+    }
+
+    #[test]
+    fn test_zero_window_delivers_everything() {
+        let now = Instant::now();
+        let mut c = Coalescer::new(Duration::ZERO);
+        assert_eq!(c.offer(1, now), Some(1));
+        assert_eq!(c.offer(2, now), Some(2));
+    }
+}
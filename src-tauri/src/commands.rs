@@ -1,28 +1,143 @@
+use crate::activity;
+use crate::analysis;
+use crate::apps;
+use crate::archive;
+use crate::backup;
 use crate::capture;
-use crate::models::{AnalysisStatus, CaptureSession, CaptureStatus, MonitorInfo, OllamaStatus, Screenshot, Task, TaskUpdate};
+use crate::coalesce::{Coalescer, LogCoalescer, LOG_REPEAT_WINDOW};
+use crate::diagnostics;
+use crate::estimate;
+use crate::evaluation;
+use crate::models::{ActivityImport, AnalysisComplete, AppUsage, DailyTaskCount, AnalysisDebugEntry, AnalysisCostEstimate, AnalysisProgress, AnalysisEstimate, AnalysisStatus, ArchivedTask, CaptureCapability, CaptureEvent, CaptureGroupSummary, CaptureRegion, CaptureSession, CaptureStatus, CaptureWarning, CategoryCorrection, DiagnosticsReport, EvaluationReport, ExportProgress, FactoryReset, GroupReanalysis, LabeledScreenshot, MonitorChangeState, MonitorInfo, OllamaModelsDir, OllamaStatus, PendingCleared, PreviewFrame, ProviderHealth, RecentError, RegionSelection, SavingsSummary, Screenshot, ScreenshotLabel, SessionArchive, SessionGap, ScreenshotNeighbors, SessionStats, SessionStatus, SettingSchema, SettingsImport, SystemLoad, Task, TaskDetail, TaskUpdate, TaskWithProvider, TrainingExport, TrashEntry, TrashKind, UnassignedSummary};
 use crate::ollama_sidecar::{self, OllamaProcess};
+use crate::preview;
+use crate::privacy;
+use crate::profile;
+use crate::prompt_template;
+use crate::region;
+use crate::reset;
+use crate::savings;
+use crate::settings;
+use crate::time;
+use crate::timelapse;
+use crate::telemetry;
+use crate::training;
+use crate::trash;
 use crate::storage::Database;
-use log::{debug, error, info};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use log::{debug, error, info, trace, warn};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
-use tauri::{Manager, State, WebviewUrl, WebviewWindowBuilder};
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{Emitter, Listener, Manager, State, WebviewUrl, WebviewWindowBuilder};
 
 /// Per-monitor state for change detection and summary tracking.
 pub struct MonitorState {
     pub last_hash: [u8; 32],
     pub last_summary: String,
     pub name: String,
+    /// Unix ms from which the monitor is captured again (see `monitors_not_due`).
+    pub next_due_ms: u64,
+    /// Distance of the most recent frame from the hash before it; None until a second
+    /// frame has been hashed. For `get_monitor_change_state`.
+    pub last_distance: Option<u32>,
+}
+
+/// Hash distance of a new frame from the monitor's last hash; None for its first frame.
+fn frame_distance(state: Option<&MonitorState>, hash: &[u8; 32]) -> Option<u32> {
+    state.map(|ms| capture::hash_distance(hash, &ms.last_hash))
+}
+
+/// A frame's distance from its monitor's last hash, and whether it is saved: a first
+/// frame or one past `CHANGE_THRESHOLD` is. So is any `previous_active` capture, which
+/// is there to show where the user just was; that monitor has usually not changed.
+fn frame_change(state: Option<&MonitorState>, hash: &[u8; 32], previous_active: bool) -> (Option<u32>, bool) {
+    let distance = frame_distance(state, hash);
+    let changed = match distance {
+        Some(distance) => previous_active || distance >= capture::CHANGE_THRESHOLD,
+        None => true, // first capture for this monitor
+    };
+    (distance, changed)
+}
+
+/// `get_monitor_change_state` rows for `states`, by monitor id.
+fn monitor_change_states(states: &HashMap<u32, MonitorState>) -> Vec<MonitorChangeState> {
+    let mut rows: Vec<MonitorChangeState> = states
+        .iter()
+        .map(|(&monitor_id, ms)| MonitorChangeState {
+            monitor_id,
+            monitor_name: ms.name.clone(),
+            last_hash: ms.last_hash.iter().map(|b| format!("{:02x}", b)).collect(),
+            last_distance: ms.last_distance,
+        })
+        .collect();
+    rows.sort_by_key(|row| row.monitor_id);
+    rows
+}
+
+/// Monitors to leave out of a tick at `now_ms`: those whose next capture isn't due yet.
+/// Monitors without state (not captured yet this session) are always due. A wait longer
+/// than the monitor's whole current interval means the interval was shortened since,
+/// so the monitor is due right away.
+pub fn monitors_not_due(now_ms: u64, states: &HashMap<u32, MonitorState>, intervals: &capture::MonitorIntervals) -> Vec<u32> {
+    let mut skip: Vec<u32> = states
+        .iter()
+        .filter(|(id, state)| {
+            state.next_due_ms > now_ms && state.next_due_ms - now_ms <= intervals.interval_for(**id)
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    skip.sort_unstable();
+    skip
+}
+
+/// The tick's intervals: `default_ms` for most monitors, overridden per monitor by the
+/// `monitor_intervals` setting.
+fn monitor_intervals(db: &Database, default_ms: u64) -> capture::MonitorIntervals {
+    let setting = db.get_setting_string("monitor_intervals", "").unwrap_or_default();
+    let overrides = capture::parse_monitor_intervals(&setting).unwrap_or_else(|e| {
+        warn!("Ignoring monitor_intervals: {}", e);
+        HashMap::new()
+    });
+    capture::MonitorIntervals { default_ms, overrides }
+}
+
+/// Lock `mutex`, recovering it if a panic poisoned it while held. The shared state
+/// behind these locks (monitor hashes, counters) stays usable after a partial update,
+/// whereas propagating the poison would panic every later capture tick.
+pub fn lock_recovered<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        warn!("Recovering from a poisoned lock");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// The message of a caught panic payload, if it has one.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
 }
 
 pub struct AppState {
     pub db: Database,
     pub capturing: AtomicBool,
+    /// Capture loop keeps running but skips ticks while paused.
+    pub paused: AtomicBool,
     pub capture_interval_ms: AtomicU64,
+    /// Screenshots saved since the app was installed; seeded by `load_capture_count`
+    /// and persisted by `persist_capture_count`.
     pub capture_count: AtomicU64,
-    pub screenshots_dir: PathBuf,
+    /// Screenshots saved in the current session, used to enforce `max_screenshots_per_session`.
+    pub session_screenshot_count: AtomicU64,
+    /// Where screenshots are saved; changed at runtime by `set_screenshots_dir`.
+    /// Read through `AppState::screenshots_dir()`.
+    pub screenshots_dir: Mutex<PathBuf>,
     pub current_session_id: AtomicI64,
     pub app_data_dir: PathBuf,
     pub ollama_process: OllamaProcess,
@@ -30,322 +145,1637 @@ pub struct AppState {
     pub analyzing_session_id: AtomicI64,
     pub cancel_analysis: AtomicBool,
     pub monitor_states: Mutex<HashMap<u32, MonitorState>>,
+    /// Unix ms of the capture loop's latest iteration, watched by the capture watchdog.
+    pub last_tick_at_ms: AtomicU64,
+    /// Bumped by each `start_capture`; a watchdog exits once it no longer matches.
+    pub capture_run: AtomicU64,
+    /// Bumped whenever a capture loop is spawned; a loop exits once it no longer
+    /// matches, so a stalled loop that wakes up after a restart doesn't run twice.
+    pub capture_loop: AtomicU64,
+    /// Held by a capture tick while it saves and inserts its screenshots. `cancel_capture`
+    /// takes it after halting the loop, so a tick still in flight is done with the session
+    /// before it is deleted.
+    pub capture_tick: Mutex<()>,
+    /// Monitor the cursor was on at the last "active" mode tick.
+    pub last_active_monitor: Mutex<Option<u32>>,
+    /// Last cursor position read successfully; stands in when a read fails.
+    pub last_cursor_position: Mutex<Option<(i32, i32)>>,
+    /// Capture counters for the current session, written to the DB when it ends.
+    pub session_stats: Mutex<SessionStats>,
+    /// Held by `ensure_ollama` so concurrent calls don't each spawn a sidecar.
+    pub ollama_startup: tokio::sync::Mutex<()>,
+    /// One per high-frequency event name; see `AppState::emit_coalesced`.
+    pub event_coalescers: Mutex<HashMap<&'static str, Coalescer>>,
+    /// The last `RECENT_ERRORS_CAP` analysis failures, oldest first, for `get_recent_errors`.
+    pub recent_errors: Mutex<VecDeque<RecentError>>,
+    /// When each provider last analyzed something (since startup), for `get_provider_health`.
+    pub last_analysis_success: Mutex<HashMap<String, String>>,
+    /// Bumped by each `highlight_monitors` call (and `close_highlights`); overlay labels
+    /// carry it, so a call only ever closes its own overlays.
+    pub highlight_generation: AtomicU64,
+    /// The latest `highlight_monitors` call's pending overlay cleanup, aborted when a
+    /// newer call takes over.
+    pub highlight_cleanup: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// When `get_capture_preview` last ran, for its rate limit.
+    pub last_capture_preview: Mutex<Option<Instant>>,
+    /// Playback position of a `directory:` capture source; reset when capture starts.
+    pub directory_source: Mutex<Option<capture::DirectoryCaptureSource>>,
+    /// `suggest_session_description`'s last answer, reused for `SUGGESTION_CACHE_TTL`.
+    pub description_suggestions: Mutex<Option<CachedSuggestions>>,
+}
+
+/// How long `suggest_session_description` reuses an answer, so reopening the
+/// start-capture dialog doesn't ask the provider again.
+const SUGGESTION_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Longest wait for the provider's suggestions before falling back.
+const SUGGESTION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Suggestions computed at `at` from the completed session `session_id`.
+pub struct CachedSuggestions {
+    pub at: Instant,
+    pub session_id: i64,
+    pub suggestions: Vec<String>,
+}
+
+/// The cached suggestions, if they were made from `session_id` less than
+/// `SUGGESTION_CACHE_TTL` before `now`. A newer completed session misses the cache.
+fn cached_suggestions(cache: &Option<CachedSuggestions>, session_id: i64, now: Instant) -> Option<Vec<String>> {
+    cache
+        .as_ref()
+        .filter(|c| c.session_id == session_id && now.saturating_duration_since(c.at) < SUGGESTION_CACHE_TTL)
+        .map(|c| c.suggestions.clone())
+}
+
+/// How many analysis errors `get_recent_errors` keeps.
+pub const RECENT_ERRORS_CAP: usize = 50;
+
+/// Append `item`, dropping the oldest entries so at most `cap` remain.
+fn push_bounded<T>(buffer: &mut VecDeque<T>, item: T, cap: usize) {
+    buffer.push_back(item);
+    while buffer.len() > cap {
+        buffer.pop_front();
+    }
+}
+
+/// The capture loop persists `capture_count` each time it crosses a multiple of this.
+const CAPTURE_COUNT_FLUSH_EVERY: u64 = 25;
+
+/// The lifetime capture count stored in the database, for `AppState.capture_count`.
+pub fn load_capture_count(db: &Database) -> AtomicU64 {
+    AtomicU64::new(db.get_capture_count().unwrap_or_else(|e| {
+        warn!("Failed to read the stored capture count: {}", e);
+        0
+    }))
+}
+
+impl AppState {
+    pub fn screenshots_dir(&self) -> PathBuf {
+        lock_recovered(&self.screenshots_dir).clone()
+    }
+
+    /// Emit a high-frequency event (progress, per-tick notifications) at most once per
+    /// `event_throttle_ms`, dropping payloads in between. `last` marks the final state
+    /// of the stream, which is always delivered.
+    pub fn emit_coalesced<S: serde::Serialize + Clone>(&self, app: &tauri::AppHandle, event: &'static str, payload: S, last: bool) {
+        let deliver = {
+            let mut coalescers = lock_recovered(&self.event_coalescers);
+            if last {
+                // The next stream of this event starts with a fresh window (and re-reads the setting)
+                let mut coalescer = coalescers.remove(event).unwrap_or_else(|| Coalescer::new(Duration::ZERO));
+                Some(coalescer.finish(payload))
+            } else {
+                coalescers
+                    .entry(event)
+                    .or_insert_with(|| {
+                        let ms = self.db.get_setting_u64("event_throttle_ms", 500).unwrap_or(500);
+                        Coalescer::new(Duration::from_millis(ms))
+                    })
+                    .offer(payload, Instant::now())
+            }
+        };
+        if let Some(payload) = deliver {
+            let _ = app.emit(event, payload);
+        }
+    }
+
+    /// Write `capture_count` to the database so the total survives a restart.
+    pub fn persist_capture_count(&self) {
+        if let Err(e) = self.db.set_capture_count(self.capture_count.load(Ordering::Relaxed)) {
+            warn!("Failed to persist the capture count: {}", e);
+        }
+    }
+
+    /// Remember an analysis failure for `get_recent_errors`.
+    pub fn record_error(&self, session_id: Option<i64>, provider: &str, message: &str) {
+        let error = RecentError {
+            timestamp: time::format_for_db(SystemTime::now()),
+            session_id,
+            provider: provider.to_string(),
+            message: message.to_string(),
+        };
+        push_bounded(&mut lock_recovered(&self.recent_errors), error, RECENT_ERRORS_CAP);
+    }
+
+    /// True while `low_overhead_mode` is on and an analysis is running, when cosmetic
+    /// UI work is skipped to leave the CPU/GPU to the model.
+    pub fn low_overhead(&self) -> bool {
+        self.analyzing.load(Ordering::Relaxed)
+            && self.db.get_setting_bool("low_overhead_mode", false).unwrap_or(false)
+    }
+
+    /// Claim the analysis status for a run on `session_id`; see `AnalysisClaim`.
+    fn claim_analysis(&self, session_id: Option<i64>) -> Result<AnalysisClaim<'_>, String> {
+        AnalysisClaim::new(&self.analyzing, &self.analyzing_session_id, session_id)
+    }
+}
+
+/// Ownership of `AppState.analyzing` / `analyzing_session_id` for one analysis run.
+/// Runs share those flags, so only one may run at a time: claiming while another run
+/// holds them fails, and the flags are cleared when the claim is dropped.
+struct AnalysisClaim<'a> {
+    analyzing: &'a AtomicBool,
+    session: &'a AtomicI64,
+}
+
+impl<'a> AnalysisClaim<'a> {
+    fn new(analyzing: &'a AtomicBool, session: &'a AtomicI64, session_id: Option<i64>) -> Result<Self, String> {
+        if analyzing.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            let busy = session.load(Ordering::Acquire);
+            return Err(match session_id {
+                Some(sid) if sid == busy => format!("Session {} is already being analyzed", sid),
+                _ => "Analysis is already running".to_string(),
+            });
+        }
+        session.store(session_id.unwrap_or(0), Ordering::Release);
+        Ok(AnalysisClaim { analyzing, session })
+    }
+
+    /// Record the session once a run knows it (`run_pending_analysis`).
+    fn set_session(&self, session_id: Option<i64>) {
+        self.session.store(session_id.unwrap_or(0), Ordering::Release);
+    }
+}
+
+impl Drop for AnalysisClaim<'_> {
+    fn drop(&mut self) {
+        self.session.store(0, Ordering::Release);
+        self.analyzing.store(false, Ordering::Release);
+    }
+}
+
+/// Change detection as the capture loop sees it, per monitor captured this session: the
+/// last perceptual hash (hex) and how far the most recent frame was from the one before.
+/// A frame counts as changed at a distance of `capture::CHANGE_THRESHOLD` or more.
+#[tauri::command]
+pub fn get_monitor_change_state(state: State<'_, Arc<AppState>>) -> Vec<MonitorChangeState> {
+    monitor_change_states(&lock_recovered(&state.monitor_states))
+}
+
+#[tauri::command]
+pub fn get_capture_status(state: State<'_, Arc<AppState>>) -> CaptureStatus {
+    let mode = state.db.get_setting_string("capture_monitor_mode", "default").unwrap_or_default();
+    let mode_is_active = mode == "active";
+    let monitors_captured = {
+        let ms = lock_recovered(&state.monitor_states);
+        ms.len() as u32
+    };
+    CaptureStatus {
+        active: state.capturing.load(Ordering::Relaxed),
+        paused: state.paused.load(Ordering::Relaxed),
+        interval_ms: state.capture_interval_ms.load(Ordering::Relaxed),
+        count: state.capture_count.load(Ordering::Relaxed),
+        monitor_mode: mode,
+        monitors_captured,
+        session_screenshot_count: state.session_screenshot_count.load(Ordering::Relaxed),
+        max_screenshots_per_session: max_screenshots_per_session(&state.db),
+        active_monitor_id: if mode_is_active { *lock_recovered(&state.last_active_monitor) } else { None },
+    }
+}
+
+#[tauri::command]
+pub fn get_monitors() -> Result<Vec<MonitorInfo>, String> {
+    capture::list_monitors().map_err(|e| e.to_string())
+}
+
+/// Whether capture can work here (display server, monitors, a test capture), so the
+/// UI can explain the problem before the user starts a session.
+#[tauri::command]
+pub async fn get_capture_capability() -> Result<CaptureCapability, String> {
+    tauri::async_runtime::spawn_blocking(capture::check_capability)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// With `require_analysis_confirmation`, nothing is analyzed unless the user starts it.
+fn requires_confirmation(db: &Database) -> bool {
+    db.get_setting_bool("require_analysis_confirmation", false).unwrap_or(false)
+}
+
+fn max_screenshots_per_session(db: &Database) -> u64 {
+    db.get_setting_u64("max_screenshots_per_session", 0).unwrap_or(0)
+}
+
+/// End a session and kick off post-capture analysis for whatever it left unanalyzed.
+/// `analyze` overrides `require_analysis_confirmation`, which otherwise decides.
+fn finish_session(state: &Arc<AppState>, app_handle: &tauri::AppHandle, session_id: i64, analyze: Option<bool>) {
+    let stats = std::mem::take(&mut *lock_recovered(&state.session_stats));
+    close_session(&state.db, session_id, &stats);
+
+    match analyze {
+        Some(false) => return,
+        None if requires_confirmation(&state.db) => {
+            info!("Post-capture analysis skipped for session {}: require_analysis_confirmation is on", session_id);
+            return;
+        }
+        _ => {}
+    }
+    spawn_session_analysis(state, app_handle, session_id);
+}
+
+/// Add the capture loop's `stats` to a session and end it now. Failures are logged.
+fn close_session(db: &Database, session_id: i64, stats: &SessionStats) {
+    if let Err(e) = db.add_session_stats(session_id, stats) {
+        error!("Failed to save stats for session {}: {}", session_id, e);
+    }
+
+    let ended_at = time::format_for_db(SystemTime::now());
+    if let Err(e) = db.end_session(session_id, &ended_at) {
+        error!("Failed to end capture session {}: {}", session_id, e);
+    } else {
+        info!("Ended capture session {}", session_id);
+    }
+}
+
+/// Analyze a session's unanalyzed screenshots in the background, logging the outcome.
+/// Waits for any analysis already running (e.g. auto-analysis of the same session) to
+/// finish first, since only one run may hold the analysis status.
+fn spawn_session_analysis(state: &Arc<AppState>, app_handle: &tauri::AppHandle, session_id: i64) {
+    let analysis_state = Arc::clone(state);
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let claim = loop {
+            match analysis_state.claim_analysis(Some(session_id)) {
+                Ok(claim) => break claim,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_secs(1)).await,
+            }
+        };
+        match run_claimed_session_analysis(&app_handle, &analysis_state, &claim, session_id, 0).await {
+            Ok(n) if n > 0 => info!("Post-capture analysis: analyzed {} screenshots for session {}", n, session_id),
+            Ok(_) => info!("Post-capture analysis: no unanalyzed screenshots for session {}", session_id),
+            Err(e) => error!("Post-capture analysis failed for session {}: {}", session_id, e),
+        }
+    });
+}
+
+/// Create the session that continues `old_id`, with its title and description.
+fn create_rotated_session(db: &Database, old_id: i64) -> Result<i64, String> {
+    let old = db.get_session(old_id).map_err(|e| e.to_string())?;
+    let started_at = time::format_for_db(SystemTime::now());
+    db.create_session(&started_at, old.description.as_deref(), old.title.as_deref())
+        .map_err(|e| format!("Failed to create capture session: {}", e))
+}
+
+/// Continue capturing into `new_id` and finish `old_id` (see `finish_session`).
+fn switch_session(state: &Arc<AppState>, app_handle: &tauri::AppHandle, old_id: i64, new_id: i64, analyze: Option<bool>) {
+    state.current_session_id.store(new_id, Ordering::Relaxed);
+    state.session_screenshot_count.store(0, Ordering::Relaxed);
+    info!("Rotated capture session {} -> {}", old_id, new_id);
+    finish_session(state, app_handle, old_id, analyze);
+}
+
+/// What the capture loop does after a tick that left the session at `count` screenshots.
+#[derive(Debug, PartialEq)]
+enum SessionLimitAction {
+    Continue,
+    Rotate,
+    Stop,
+}
+
+/// Decide on `max_screenshots_per_session` (`limit`, 0 = unlimited).
+fn session_limit_action(limit: u64, count: u64, auto_rotate: bool) -> SessionLimitAction {
+    if limit == 0 || count < limit {
+        SessionLimitAction::Continue
+    } else if auto_rotate {
+        SessionLimitAction::Rotate
+    } else {
+        SessionLimitAction::Stop
+    }
+}
+
+/// Enforce `max_screenshots_per_session` after a tick: rotate to a fresh session,
+/// or stop capturing and warn the UI when rotation is disabled or fails.
+fn enforce_session_limit(state: &Arc<AppState>, app_handle: &tauri::AppHandle) {
+    let count = state.session_screenshot_count.load(Ordering::Relaxed);
+    let session_id = state.current_session_id.load(Ordering::Relaxed);
+
+    match apply_session_limit(&state.db, session_id, count) {
+        Ok(None) => {}
+        Ok(Some(new_id)) => switch_session(state, app_handle, session_id, new_id, None),
+        Err(message) => {
+            error!("{}", message);
+            end_capture(state, app_handle);
+            let _ = app_handle.emit("capture-warning", CaptureWarning { session_id, message });
+        }
+    }
+}
+
+/// The database side of `enforce_session_limit` for a session at `count` screenshots:
+/// Ok(None) to carry on, Ok(Some(id)) with the session created to rotate into, or
+/// Err with the warning when capture must stop.
+fn apply_session_limit(db: &Database, session_id: i64, count: u64) -> Result<Option<i64>, String> {
+    let limit = max_screenshots_per_session(db);
+    let auto_rotate = db.get_setting_bool("session_auto_rotate", true).unwrap_or(true);
+
+    let reason = match session_limit_action(limit, count, auto_rotate) {
+        SessionLimitAction::Continue => return Ok(None),
+        SessionLimitAction::Rotate => match create_rotated_session(db, session_id) {
+            Ok(new_id) => return Ok(Some(new_id)),
+            Err(e) => format!("could not start a new session: {}", e),
+        },
+        SessionLimitAction::Stop => "session rotation is disabled".to_string(),
+    };
+
+    Err(format!(
+        "Capture stopped: session reached the limit of {} screenshots and {}",
+        limit, reason
+    ))
+}
+
+/// Why the current tick must be skipped under `capture_allowlist` or, without one,
+/// `blocked_title_keywords`, if it must. Only a keyword's index is reported so the
+/// keyword itself never reaches logs. Fails closed: if window titles can't be read,
+/// the tick is skipped too.
+fn private_skip_reason(allowlist: &[String], blocklist: &[String]) -> Option<String> {
+    match privacy::title_filter(allowlist, blocklist) {
+        privacy::TitleFilter::None => None,
+        privacy::TitleFilter::Allow(allowlist) => match capture::active_window_title() {
+            Ok(title) => privacy::allowlist_skip_reason(title.as_deref(), allowlist),
+            Err(e) => {
+                warn!("Cannot read the active window title: {}", e);
+                Some("active window title unavailable".to_string())
+            }
+        },
+        privacy::TitleFilter::Block(keywords) => match capture::visible_window_titles() {
+            Ok(titles) => privacy::matches_patterns(&titles, keywords).map(|i| format!("keyword index {}", i)),
+            Err(e) => {
+                warn!("Cannot enumerate window titles: {}", e);
+                Some("window titles unavailable".to_string())
+            }
+        },
+    }
+}
+
+#[tauri::command]
+pub async fn start_capture(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>, interval_ms: Option<u64>, description: Option<String>, title: Option<String>) -> Result<(), String> {
+    // Guard against spawning multiple capture loops
+    if state.capturing.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    // Fail now rather than start a session that will never get a screenshot. A directory
+    // source needs no screen, which is the point of it. The probe takes a test capture,
+    // so it runs on the blocking pool.
+    if capture_source(&state.db) == capture::CaptureSource::Screen {
+        let capability = tauri::async_runtime::spawn_blocking(capture::check_capability)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(reason) = capability.reason {
+            warn!("Not starting capture: {}", reason);
+            return Err(format!("Cannot capture the screen: {}", reason));
+        }
+        if capability.remote_session {
+            warn!("Starting capture in a remote desktop session; capture may stop if it's minimized or disconnected");
+        }
+        // Another start may have won while the probe ran
+        if state.capturing.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+    }
+    *lock_recovered(&state.directory_source) = None;
+
+    let interval = interval_ms.unwrap_or_else(|| state.capture_interval_ms.load(Ordering::Relaxed));
+    info!("Starting capture with interval {}ms", interval);
+
+    if let Some(ms) = interval_ms {
+        state.capture_interval_ms.store(ms, Ordering::Relaxed);
+    }
+
+    // Create a new capture session
+    let session_timestamp = time::format_for_db(SystemTime::now());
+    let desc_ref = description.as_deref().filter(|s| !s.trim().is_empty());
+    let title_ref = title.as_deref().filter(|s| !s.trim().is_empty());
+    let session_id = state.db.create_session(&session_timestamp, desc_ref, title_ref)
+        .map_err(|e| format!("Failed to create capture session: {}", e))?;
+    state.current_session_id.store(session_id, Ordering::Relaxed);
+    info!("Created capture session {}", session_id);
+
+    let existing = state.db.get_session(session_id)
+        .map(|s| s.screenshot_count as u64)
+        .unwrap_or(0);
+    state.session_screenshot_count.store(existing, Ordering::Relaxed);
+
+    state.paused.store(false, Ordering::Relaxed);
+    state.capturing.store(true, Ordering::Relaxed);
+    // A monitor preview must never end up in a screenshot
+    close_highlights(&app_handle, &state);
+
+    // Clear monitor states for fresh session
+    {
+        let mut ms = lock_recovered(&state.monitor_states);
+        ms.clear();
+    }
+    *lock_recovered(&state.last_active_monitor) = None;
+    *lock_recovered(&state.session_stats) = SessionStats::default();
+
+    // Ensure screenshots directory exists
+    std::fs::create_dir_all(state.screenshots_dir())
+        .map_err(|e| {
+            error!("Failed to create screenshots directory: {}", e);
+            format!("Failed to create screenshots directory: {}", e)
+        })?;
+
+    let run = state.capture_run.fetch_add(1, Ordering::Relaxed) + 1;
+    state.last_tick_at_ms.store(unix_ms(SystemTime::now()), Ordering::Relaxed);
+    spawn_capture_loop(app_handle.clone(), Arc::clone(&state));
+    spawn_capture_watchdog(app_handle, Arc::clone(&state), run);
+
+    Ok(())
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Spawn the capture loop for the current session, replacing any earlier loop.
+fn spawn_capture_loop(app_handle: tauri::AppHandle, app_state: Arc<AppState>) {
+    let loop_id = app_state.capture_loop.fetch_add(1, Ordering::Relaxed) + 1;
+    let capture_handle = tauri::async_runtime::spawn(async move {
+        // Current interval while adaptive capture is on; None until the first adaptive tick
+        let mut adaptive_current: Option<u64> = None;
+        let mut no_monitors = capture::NoMonitorState::default();
+        // Persistent failures (unwritable directory, capture driver errors) repeat every tick
+        let mut error_log = LogCoalescer::new(LOG_REPEAT_WINDOW);
+        loop {
+            if !app_state.capturing.load(Ordering::Relaxed) {
+                info!("Capture loop stopped");
+                break;
+            }
+            if app_state.capture_loop.load(Ordering::Relaxed) != loop_id {
+                warn!("Capture loop {} superseded by a newer one, exiting", loop_id);
+                break;
+            }
+            app_state.last_tick_at_ms.store(unix_ms(SystemTime::now()), Ordering::Relaxed);
+
+            if app_state.paused.load(Ordering::Relaxed) {
+                // Paused because monitors disappeared: resume as soon as they're back
+                if no_monitors.is_paused() && capture::list_monitors().is_ok_and(|m| !m.is_empty()) {
+                    no_monitors.on_monitors_found();
+                    let sid = app_state.current_session_id.load(Ordering::Relaxed);
+                    if sid > 0 {
+                        let resumed_at = time::format_for_db(SystemTime::now());
+                        if let Err(e) = app_state.db.resume_session(sid, &resumed_at) {
+                            error!("Failed to resume session {}: {}", sid, e);
+                        }
+                    }
+                    app_state.paused.store(false, Ordering::Relaxed);
+                    info!("Monitors found again, resuming capture for session {}", sid);
+                    let _ = app_handle.emit("capture://monitors-restored", CaptureWarning {
+                        session_id: sid,
+                        message: "Monitors found again; capture resumed".to_string(),
+                    });
+                    continue;
+                }
+                let interval = app_state.capture_interval_ms.load(Ordering::Relaxed);
+                tokio::time::sleep(std::time::Duration::from_millis(interval)).await;
+                continue;
+            }
+            if no_monitors.is_paused() {
+                // Resumed by the user during an outage: start counting again
+                no_monitors = capture::NoMonitorState::default();
+            }
+
+            let adaptive = app_state.db.get_setting_bool("adaptive_interval", false).unwrap_or(false);
+            let min = app_state.db.get_setting_u64("adaptive_interval_min_ms", 10_000).unwrap_or(10_000);
+            let max = app_state.db.get_setting_u64("adaptive_interval_max_ms", 60_000).unwrap_or(60_000);
+            if !adaptive {
+                adaptive_current = None;
+            }
+            let base_interval = match adaptive_current {
+                Some(current) => current,
+                None if adaptive => min,
+                None => app_state.capture_interval_ms.load(Ordering::Relaxed),
+            };
+            let intervals = monitor_intervals(&app_state.db, base_interval);
+
+            let cursor = active_mode_cursor(&app_state).await;
+            let default_changed = match std::panic::catch_unwind(AssertUnwindSafe(|| {
+                run_capture_tick(&app_state, &app_handle, &mut no_monitors, &mut error_log, cursor, &intervals)
+            })) {
+                Ok(changed) => changed,
+                Err(panic) => {
+                    // Logged by the panic hook too; record it so a crashing tick is visible per session
+                    let message = panic_message(panic.as_ref());
+                    if let Some(line) = error_log.offer("Capture tick panicked, continuing", &message, Instant::now()) {
+                        error!("{}", line);
+                    }
+                    let sid = app_state.current_session_id.load(Ordering::Relaxed);
+                    let at = time::format_for_db(SystemTime::now());
+                    if let Err(e) = app_state.db.record_capture_event((sid > 0).then_some(sid), &at, "tick_panicked", Some(&message)) {
+                        error!("Failed to record capture event: {}", e);
+                    }
+                    None
+                }
+            };
+
+            // Ticks that only captured monitors with their own interval say nothing about
+            // the rest of the screen, so the adaptive interval holds
+            let interval = if let (true, Some(changed)) = (adaptive, default_changed) {
+                let next = capture::next_interval(base_interval, changed, min, max);
+                if next != base_interval {
+                    debug!("Adaptive capture interval: {}ms -> {}ms", base_interval, next);
+                }
+                adaptive_current = Some(next);
+                next
+            } else {
+                base_interval
+            };
+            // Tick as often as the fastest monitor needs; the others are skipped until due
+            let interval = capture::MonitorIntervals { default_ms: interval, ..intervals }.loop_interval();
+            tokio::time::sleep(std::time::Duration::from_millis(interval)).await;
+        }
+    });
+
+    // Monitor the capture task for panics
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = capture_handle.await {
+            error!("Capture task failed: {}", e);
+        }
+    });
+}
+
+/// Where the cursor is for this tick, if the monitor mode is "active". The lookup can
+/// block (xdotool on Linux), so it runs on the blocking pool; when it fails, the last
+/// good position is used.
+async fn active_mode_cursor(state: &AppState) -> Option<capture::CursorFix> {
+    if state.db.get_setting_string("capture_monitor_mode", "default").unwrap_or_default() != "active" {
+        return None;
+    }
+    let read = tauri::async_runtime::spawn_blocking(capture::get_cursor_position)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Cursor lookup task failed: {}", e);
+            None
+        });
+    Some(capture::resolve_cursor(read, &mut lock_recovered(&state.last_cursor_position)))
+}
+
+/// Longest the loop may legitimately sleep between iterations: the capture interval,
+/// or the slowest adaptive interval if that's longer.
+fn longest_tick_interval(state: &AppState) -> u64 {
+    let interval = state.capture_interval_ms.load(Ordering::Relaxed);
+    if state.db.get_setting_bool("adaptive_interval", false).unwrap_or(false) {
+        interval.max(state.db.get_setting_u64("adaptive_interval_max_ms", 60_000).unwrap_or(60_000))
+    } else {
+        interval
+    }
+}
+
+/// Watch capture run `run` for a loop that stopped ticking (a panic outside the tick's
+/// `catch_unwind`, a hung capture call) while `capturing` is still set. A stall is
+/// logged, recorded as a `capture_stalled` event and emitted as `capture-stalled`, and
+/// the loop is restarted on the same session. After `capture::MAX_RECOVERIES` failed
+/// restarts, capture is stopped and the session ended so what was captured can be analyzed.
+fn spawn_capture_watchdog(app_handle: tauri::AppHandle, state: Arc<AppState>, run: u64) {
+    tauri::async_runtime::spawn(async move {
+        let mut watchdog = capture::Watchdog::default();
+        loop {
+            let interval = longest_tick_interval(&state);
+            tokio::time::sleep(std::time::Duration::from_millis(interval.max(1_000))).await;
+            if !state.capturing.load(Ordering::Relaxed) || state.capture_run.load(Ordering::Relaxed) != run {
+                break;
+            }
+
+            let now = SystemTime::now();
+            let last_tick = state.last_tick_at_ms.load(Ordering::Relaxed);
+            let action = watchdog.check(last_tick, unix_ms(now), longest_tick_interval(&state));
+            if action == capture::WatchdogAction::Healthy {
+                continue;
+            }
+
+            let session_id = state.current_session_id.load(Ordering::Relaxed);
+            let stalled_for = unix_ms(now).saturating_sub(last_tick) / 1000;
+            let message = if action == capture::WatchdogAction::Restart {
+                format!("Capture stalled (no tick for {}s); restarting the capture loop", stalled_for)
+            } else {
+                format!("Capture stalled (no tick for {}s) and restarting didn't help; capture stopped", stalled_for)
+            };
+            error!("{}", message);
+            let at = time::format_for_db(now);
+            if let Err(e) = state.db.record_capture_event((session_id > 0).then_some(session_id), &at, "capture_stalled", Some(&message)) {
+                error!("Failed to record capture event: {}", e);
+            }
+            let _ = app_handle.emit("capture-stalled", CaptureWarning { session_id, message });
+
+            if action == capture::WatchdogAction::Restart {
+                spawn_capture_loop(app_handle.clone(), Arc::clone(&state));
+            } else {
+                // Stops the stalled loop too, should it ever wake up
+                end_capture(&state, &app_handle);
+                break;
+            }
+        }
+    });
+}
+
+/// The `capture_source` setting; the screen if it's unreadable.
+fn capture_source(db: &Database) -> capture::CaptureSource {
+    let setting = db.get_setting_string("capture_source", "screen").unwrap_or_default();
+    capture::parse_capture_source(&setting).unwrap_or_else(|e| {
+        warn!("Invalid capture_source setting, capturing the screen: {}", e);
+        capture::CaptureSource::Screen
+    })
+}
+
+/// This tick's frame from a `directory:` capture source, continuing the playback in
+/// `AppState.directory_source` (restarted when the directory changes). Nothing while
+/// the source's monitor isn't due.
+fn next_directory_frame(app_state: &AppState, dir: &Path, skip: &[u32]) -> Result<Vec<capture::CapturedMonitor>, capture::CaptureError> {
+    if skip.contains(&capture::DIRECTORY_MONITOR_ID) {
+        return Ok(Vec::new());
+    }
+    let mut source = lock_recovered(&app_state.directory_source);
+    if source.as_ref().is_some_and(|s| s.dir() != dir) {
+        *source = None;
+    }
+    source
+        .get_or_insert_with(|| capture::DirectoryCaptureSource::new(dir.to_path_buf()))
+        .next_frame()
+        .map(|frame| vec![frame])
+}
+
+/// One capture tick: privacy check, capture, change detection, saving, stats and
+/// auto-analysis scheduling. Synchronous so the loop can contain a panic in it with
+/// `catch_unwind`. `cursor` is set in "active" mode. Only monitors that are due under
+/// `intervals` are captured. Returns whether a screenshot was saved for a monitor on
+/// the default interval, or None if no such monitor was captured this tick.
+fn run_capture_tick(
+    app_state: &Arc<AppState>,
+    app_handle: &tauri::AppHandle,
+    no_monitors: &mut capture::NoMonitorState,
+    error_log: &mut LogCoalescer,
+    cursor: Option<capture::CursorFix>,
+    intervals: &capture::MonitorIntervals,
+) -> Option<bool> {
+    // Read monitor mode settings
+    let mode = app_state.db.get_setting_string("capture_monitor_mode", "default").unwrap_or_default();
+    let specific_id: Option<u32> = app_state.db.get_setting("capture_monitor_id")
+        .unwrap_or(None)
+        .and_then(|v| v.parse().ok());
+    let capture_previous_active = app_state.db.get_setting_bool("capture_previous_active", false).unwrap_or(false);
+    let previous_active = if capture_previous_active {
+        *lock_recovered(&app_state.last_active_monitor)
+    } else {
+        None
+    };
+    // Clamped to the registry range, so it fits in a u32
+    let min_dimension = app_state.db.get_setting_u64("min_capture_dimension", 100).unwrap_or(100) as u32;
+    let hash_prescale_threshold = app_state.db.get_setting_u64("hash_prescale_threshold", 2560).unwrap_or(2560) as u32;
+    let mirror_hash_threshold = app_state.db.get_setting_u64("mirror_hash_threshold", 0).unwrap_or(0) as u32;
+    let grayscale = app_state.db.get_setting_bool("grayscale_capture", false).unwrap_or(false);
+    let source = capture_source(&app_state.db);
+
+    let blocked_keywords = privacy::parse_keywords(
+        &app_state.db.get_setting_string("blocked_title_keywords", "").unwrap_or_default(),
+    );
+    let allowed_keywords = privacy::parse_keywords(
+        &app_state.db.get_setting_string("capture_allowlist", "").unwrap_or_default(),
+    );
+
+    let now = SystemTime::now();
+    let filename_ts = time::format_for_screenshot(now);
+    let db_timestamp = time::format_for_db(now);
+    let capture_group = time::format_for_filename(now);
+    let sid = app_state.current_session_id.load(Ordering::Relaxed);
+    let session_opt = if sid > 0 { Some(sid) } else { None };
+
+    // Check titles before capturing, and again afterwards in case a private
+    // window appeared mid-capture; in that case the images are dropped unsaved.
+    let mut skip_reason = private_skip_reason(&allowed_keywords, &blocked_keywords);
+    let captured = if skip_reason.is_some() {
+        None
+    } else {
+        let not_due = monitors_not_due(unix_ms(now), &lock_recovered(&app_state.monitor_states), intervals);
+        if !not_due.is_empty() {
+            trace!("Monitors not due this tick: {:?}", not_due);
+        }
+        let result = match &source {
+            capture::CaptureSource::Directory(dir) => next_directory_frame(app_state, dir, &not_due),
+            capture::CaptureSource::Screen => {
+                capture::capture_monitors(&mode, specific_id, previous_active, cursor.and_then(|c| c.position()), &not_due)
+            }
+        };
+        if mode == "active" && source == capture::CaptureSource::Screen {
+            if let Some(active) = result.iter().flatten().find(|c| !c.previous_active) {
+                debug!("Active monitor for this tick: {} ({}), cursor {:?}", active.monitor_id, active.monitor_name, cursor);
+                *lock_recovered(&app_state.last_active_monitor) = Some(active.monitor_id);
+            }
+            let fallback = match cursor {
+                Some(capture::CursorFix::Cached(x, y)) => {
+                    Some(format!("Cursor lookup failed; used the last known position ({}, {})", x, y))
+                }
+                Some(capture::CursorFix::Unknown) => {
+                    Some("Cursor lookup failed and no earlier position is known; captured the primary monitor".to_string())
+                }
+                _ => None,
+            };
+            // A failing lookup fails every tick; record it once per window with a count
+            if let Some(detail) = fallback.and_then(|d| error_log.offer("Cursor fallback", &d, Instant::now())) {
+                if let Err(e) = app_state.db.record_capture_event(session_opt, &db_timestamp, "cursor_fallback", Some(&detail)) {
+                    error!("Failed to record capture event: {}", e);
+                }
+            }
+        }
+        skip_reason = private_skip_reason(&allowed_keywords, &blocked_keywords);
+        if skip_reason.is_some() { None } else { Some(result) }
+    };
+    if let Some(reason) = &skip_reason {
+        debug!("Skipping capture tick: window title filter ({})", reason);
+        if let Err(e) = app_state.db.record_capture_event(session_opt, &db_timestamp, "skipped_private", Some(reason)) {
+            error!("Failed to record capture event: {}", e);
+        }
+    }
+
+    let mut saved_count = 0u32;
+    let mut unchanged_count = 0u32;
+    let mut bytes_written = 0u64;
+    let mut default_changed = None;
+    match captured {
+        None => {}
+        Some(Ok(captures)) => {
+            no_monitors.on_monitors_found();
+
+            let _tick = lock_recovered(&app_state.capture_tick);
+            if app_state.current_session_id.load(Ordering::Relaxed) != sid {
+                debug!("Capture was halted during the tick; dropping its images");
+                return None;
+            }
+            let mut monitor_states = lock_recovered(&app_state.monitor_states);
+            // Screenshots saved this tick, to spot mirrored displays
+            let mut saved_hashes: Vec<(i64, [u8; 32])> = Vec::new();
+
+            for (seq, cap) in captures.iter().enumerate() {
+                if !capture::is_valid_capture(&cap.image, min_dimension, min_dimension) {
+                    warn!(
+                        "Skipping {}x{} capture from {} (minimum {}x{})",
+                        cap.image.width(), cap.image.height(), cap.monitor_name, min_dimension, min_dimension
+                    );
+                    continue;
+                }
+                let on_default_interval = !intervals.overrides.contains_key(&cap.monitor_id);
+                if on_default_interval {
+                    default_changed.get_or_insert(false);
+                }
+                let converted;
+                let image = if grayscale {
+                    converted = capture::to_grayscale_rgba(&cap.image);
+                    &converted
+                } else {
+                    &cap.image
+                };
+                let hash = capture::perceptual_hash_prescaled(image, hash_prescale_threshold);
+                let (distance, changed) = frame_change(monitor_states.get(&cap.monitor_id), &hash, cap.previous_active);
+                if let Some(ms) = monitor_states.get_mut(&cap.monitor_id) {
+                    ms.last_distance = distance;
+                }
+
+                if changed {
+                    let screenshots_dir = app_state.screenshots_dir();
+                    let filename = capture::screenshot_filename(
+                        &screenshots_dir,
+                        &filename_ts,
+                        seq,
+                        &cap.monitor_name,
+                    );
+
+                    let path = screenshots_dir.join(&filename);
+                    if let Err(e) = capture::save_image_as_webp(image, &path) {
+                        if let Some(line) = error_log.offer("Failed to save screenshot", &e.to_string(), Instant::now()) {
+                            error!("{}", line);
+                        }
+                        continue;
+                    }
+                    bytes_written += std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+                    let relative_path = format!("screenshots/{}", filename);
+                    match app_state.db.insert_screenshot(
+                        &relative_path,
+                        &db_timestamp,
+                        None,
+                        cap.monitor_id as i32,
+                        session_opt,
+                        Some(&capture_group),
+                    ) {
+                        Ok(screenshot_id) => {
+                            if cap.previous_active {
+                                if let Err(e) = app_state.db.mark_previous_active(screenshot_id) {
+                                    error!("Failed to flag previous-monitor screenshot: {}", e);
+                                }
+                            }
+                            if let Err(e) = app_state.db.set_scale_factor(screenshot_id, cap.scale_factor) {
+                                error!("Failed to record screenshot scale factor: {}", e);
+                            }
+                            if let Err(e) = app_state.db.set_is_primary(screenshot_id, cap.is_primary) {
+                                error!("Failed to record primary-monitor flag: {}", e);
+                            }
+                            if let Some(original) = capture::find_mirror(&hash, &saved_hashes, mirror_hash_threshold) {
+                                debug!("{} mirrors screenshot {}", cap.monitor_name, original);
+                                if let Err(e) = app_state.db.mark_mirrored(screenshot_id, original) {
+                                    error!("Failed to flag mirrored screenshot: {}", e);
+                                }
+                            } else {
+                                saved_hashes.push((screenshot_id, hash));
+                            }
+                            let prev_summary = monitor_states
+                                .get(&cap.monitor_id)
+                                .map(|s| s.last_summary.clone())
+                                .unwrap_or_default();
+                            monitor_states.insert(cap.monitor_id, MonitorState {
+                                last_hash: hash,
+                                last_summary: prev_summary,
+                                name: cap.monitor_name.clone(),
+                                next_due_ms: unix_ms(now) + intervals.interval_for(cap.monitor_id),
+                                last_distance: distance,
+                            });
+                            saved_count += 1;
+                            if on_default_interval {
+                                default_changed = Some(true);
+                            }
+                        }
+                        Err(e) => {
+                            if let Some(line) = error_log.offer("Failed to insert screenshot into DB", &e.to_string(), Instant::now()) {
+                                error!("{}", line);
+                            }
+                        }
+                    }
+                } else {
+                    // Unchanged — just update the hash
+                    if let Some(ms) = monitor_states.get_mut(&cap.monitor_id) {
+                        ms.last_hash = hash;
+                        ms.next_due_ms = unix_ms(now) + intervals.interval_for(cap.monitor_id);
+                    }
+                    unchanged_count += 1;
+                }
+            }
+            drop(monitor_states);
+
+            {
+                let mut stats = lock_recovered(&app_state.session_stats);
+                stats.ticks += 1;
+                if saved_count == 0 && unchanged_count > 0 {
+                    stats.ticks_unchanged += 1;
+                }
+                stats.screenshots_saved += saved_count as u64;
+                stats.screenshots_unchanged += unchanged_count as u64;
+                stats.bytes_written += bytes_written;
+            }
+
+            if saved_count > 0 {
+                let count = app_state.capture_count.fetch_add(saved_count as u64, Ordering::Relaxed) + saved_count as u64;
+                app_state.session_screenshot_count.fetch_add(saved_count as u64, Ordering::Relaxed);
+                if count / CAPTURE_COUNT_FLUSH_EVERY != (count - saved_count as u64) / CAPTURE_COUNT_FLUSH_EVERY {
+                    app_state.persist_capture_count();
+                }
+                trace!("Captured {} screenshots (total: {})", saved_count, count);
+
+                // Auto-analysis logic
+                let analysis_mode = app_state.db.get_setting_string("analysis_mode", "batch").unwrap_or_default();
+                // Clamped to 1..=100, so never zero
+                let batch_size = app_state.db.get_setting_u64("batch_size", 10).unwrap_or(10);
+
+                let should_analyze = if requires_confirmation(&app_state.db) {
+                    false
+                } else if analysis_mode == "realtime" {
+                    !app_state.analyzing.load(Ordering::Relaxed)
+                } else {
+                    count % batch_size == 0
+                };
+
+                if should_analyze {
+                    let analysis_state = Arc::clone(app_state);
+                    let analysis_app = app_handle.clone();
+                    let session_for_analysis = sid;
+                    let limit = if analysis_mode == "realtime" { 1 } else { batch_size as i64 };
+                    tauri::async_runtime::spawn(async move {
+                        if session_for_analysis > 0 {
+                            match run_session_analysis(&analysis_app, &analysis_state, session_for_analysis, limit).await {
+                                Ok(n) if n > 0 => info!("Auto-analyzed {} screenshots for session {}", n, session_for_analysis),
+                                Ok(_) => {}
+                                Err(e) => debug!("Auto-analysis skipped: {}", e),
+                            }
+                        }
+                    });
+                }
+
+                enforce_session_limit(app_state, app_handle);
+            }
+        }
+        Some(Err(e)) => {
+            if let Some(line) = error_log.offer("Screenshot capture failed", &e.to_string(), Instant::now()) {
+                error!("{}", line);
+            }
+            if matches!(e, capture::CaptureError::NoMonitors) {
+                let threshold = app_state.db.get_setting_u64("no_monitor_pause_after", 3).unwrap_or(3) as u32;
+                if no_monitors.on_no_monitors(threshold) == capture::NoMonitorTransition::Pause {
+                    if let Some(sid) = session_opt {
+                        if let Err(e) = app_state.db.pause_session(sid, &db_timestamp) {
+                            error!("Failed to pause session {}: {}", sid, e);
+                        }
+                    }
+                    app_state.paused.store(true, Ordering::Relaxed);
+                    let message = format!(
+                        "No monitors found for {} capture(s) in a row; capture paused until a monitor is available",
+                        threshold
+                    );
+                    warn!("{}", message);
+                    let _ = app_handle.emit("capture://no-monitors", CaptureWarning { session_id: sid, message });
+                }
+            }
+        }
+    }
+
+    default_changed
+}
+
+#[tauri::command]
+pub fn stop_capture(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>) {
+    info!("Stopping capture");
+    end_capture(&state, &app_handle);
+}
+
+/// Stop capturing and finish the session being captured. Shared by `stop_capture` and
+/// the loop's own stops (session limit, watchdog).
+fn end_capture(state: &Arc<AppState>, app_handle: &tauri::AppHandle) {
+    let session_id = halt_capture(state);
+    if session_id > 0 {
+        finish_session(state, app_handle, session_id, None);
+    }
+}
+
+/// Stop the capture loop and detach the current session; a tick already running
+/// carries on until it returns (wait for it on `capture_tick`). Returns the session that
+/// was being captured, 0 if none.
+fn halt_capture(state: &AppState) -> i64 {
+    state.capturing.store(false, Ordering::Relaxed);
+    state.paused.store(false, Ordering::Relaxed);
+    state.capture_loop.fetch_add(1, Ordering::Relaxed);
+    state.persist_capture_count();
+    state.current_session_id.swap(0, Ordering::Relaxed)
+}
+
+/// Stop capturing and permanently delete the session just captured, screenshots and
+/// all, instead of ending it like `stop_capture`. Refused while an analysis runs (the
+/// session is claimed for the deletion, so none of it can start meanwhile), and when it
+/// already has user-verified tasks unless `force`. Returns the number of screenshots
+/// removed.
+#[tauri::command]
+pub fn cancel_capture(state: State<'_, Arc<AppState>>, force: Option<bool>) -> Result<u32, String> {
+    let session_id = state.current_session_id.load(Ordering::Relaxed);
+    if session_id <= 0 {
+        return Err("Capture is not running".to_string());
+    }
+    let tasks = state.db.get_session_tasks(session_id).map_err(|e| e.to_string())?;
+    refuse_verified(&tasks, force.unwrap_or(false), "discard the session")?;
+    let _claim = state.claim_analysis(Some(session_id))?;
+
+    info!("Cancelling capture session {}", session_id);
+    // Stops the loop before the session goes. A tick that got to `capture_tick` first is
+    // waited for (its screenshots are deleted with the session); a later one sees the
+    // halt and saves nothing
+    let halted = halt_capture(&state);
+    if halted != session_id {
+        return Err(format!("Capture session {} was stopped before it could be cancelled; nothing was deleted", session_id));
+    }
+    let _tick = lock_recovered(&state.capture_tick);
+    *lock_recovered(&state.session_stats) = SessionStats::default();
+
+    let deleted = state.db.delete_session(session_id).map_err(|e| e.to_string())?;
+    remove_screenshot_files(&state.screenshots_dir(), &deleted);
+    info!("Discarded session {} ({} screenshots removed)", session_id, deleted.len());
+    Ok(deleted.len() as u32)
+}
+
+/// Err naming the first user-verified task in `tasks` unless `force`, for commands
+/// that would throw verified work away; `action` completes "pass force to ... anyway".
+/// A running analysis is refused separately, by the claim on the analysis status.
+fn refuse_verified(tasks: &[Task], force: bool, action: &str) -> Result<(), String> {
+    match tasks.iter().find(|t| t.user_verified) {
+        Some(task) if !force => Err(format!("Task {} (\"{}\") is verified; pass force to {} anyway", task.id, task.title, action)),
+        _ => Ok(()),
+    }
+}
+
+/// Pause capturing without ending the session. Paused time is excluded from the
+/// session's duration.
+#[tauri::command]
+pub fn pause_capture(state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    if !state.capturing.load(Ordering::Relaxed) {
+        return Err("Capture is not running".to_string());
+    }
+    if state.paused.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let session_id = state.current_session_id.load(Ordering::Relaxed);
+    if session_id > 0 {
+        let paused_at = time::format_for_db(SystemTime::now());
+        state.db.pause_session(session_id, &paused_at).map_err(|e| e.to_string())?;
+    }
+    info!("Paused capture for session {}", session_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resume_capture(state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    if !state.paused.swap(false, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let session_id = state.current_session_id.load(Ordering::Relaxed);
+    if session_id > 0 {
+        let resumed_at = time::format_for_db(SystemTime::now());
+        state.db.resume_session(session_id, &resumed_at).map_err(|e| e.to_string())?;
+    }
+    info!("Resumed capture for session {}", session_id);
+    Ok(())
+}
+
+/// End a session without touching the capture toggle, for scripts that manage sessions
+/// themselves. If it is the session being captured, capture carries on in a new session
+/// with the same title and description, whose id is returned. Its unanalyzed screenshots
+/// are analyzed afterwards only with `analyze`.
+#[tauri::command]
+pub fn finalize_session(
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    session_id: i64,
+    analyze: Option<bool>,
+) -> Result<Option<i64>, String> {
+    let current_id = state.current_session_id.load(Ordering::Relaxed);
+    let finalized = finalize_session_records(&state.db, current_id, session_id, analyze)?;
+    match finalized.rotated_into {
+        Some(new_id) => switch_session(&state, &app_handle, session_id, new_id, Some(finalized.analyze)),
+        None if finalized.analyze => spawn_session_analysis(&state, &app_handle, session_id),
+        None => {}
+    }
+    Ok(finalized.rotated_into)
+}
+
+/// What `finalize_session` has left to do once the database is updated.
+#[derive(Debug, PartialEq)]
+struct Finalized {
+    /// The session created to continue capture, when the finalized one was being captured.
+    rotated_into: Option<i64>,
+    /// Analyze the finalized session's unanalyzed screenshots.
+    analyze: bool,
+}
+
+/// The database side of `finalize_session`: a session being captured (`current_id`)
+/// gets its continuation created (`switch_session` then ends it); any other open
+/// session is ended here. Analysis is opt-in, whatever `require_analysis_confirmation` says.
+fn finalize_session_records(db: &Database, current_id: i64, session_id: i64, analyze: Option<bool>) -> Result<Finalized, String> {
+    check_session_open(db, session_id)?;
+    let analyze = analyze.unwrap_or(false);
+    if current_id == session_id {
+        let new_id = create_rotated_session(db, session_id)?;
+        return Ok(Finalized { rotated_into: Some(new_id), analyze });
+    }
+
+    db.end_session(session_id, &time::format_for_db(SystemTime::now()))
+        .map_err(|e| e.to_string())?;
+    info!("Finalized session {}", session_id);
+    Ok(Finalized { rotated_into: None, analyze })
+}
+
+/// Err unless `session_id` exists and has not ended yet.
+fn check_session_open(db: &Database, session_id: i64) -> Result<(), String> {
+    let session = match db.get_session(session_id) {
+        Ok(session) => session,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Err(format!("Session {} not found", session_id)),
+        Err(e) => return Err(e.to_string()),
+    };
+    match session.ended_at {
+        Some(ended_at) => Err(format!("Session {} already ended at {}", session_id, ended_at)),
+        None => Ok(()),
+    }
+}
+
+/// A session's capture groups, oldest first, with ungrouped legacy screenshots
+/// under `storage::UNGROUPED_CAPTURE_GROUP`.
+#[tauri::command]
+pub fn get_session_capture_groups(
+    state: State<'_, Arc<AppState>>,
+    session_id: i64,
+) -> Result<Vec<CaptureGroupSummary>, String> {
+    state.db.get_session_capture_groups(session_id).map_err(|e| e.to_string())
+}
+
+/// Group a session's legacy (pre-capture_group) screenshots by timestamp so
+/// simultaneous multi-monitor captures analyze together. Returns rows updated.
+#[tauri::command]
+pub fn backfill_capture_groups(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<usize, String> {
+    state.db.backfill_capture_groups(session_id).map_err(|e| e.to_string())
+}
+
+/// Category rules learned from the user recategorizing AI-created tasks.
+#[tauri::command]
+pub fn get_category_corrections(state: State<'_, Arc<AppState>>) -> Result<Vec<CategoryCorrection>, String> {
+    state.db.get_category_corrections().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_category_correction(state: State<'_, Arc<AppState>>, id: i64) -> Result<(), String> {
+    state.db.delete_category_correction(id).map_err(|e| e.to_string())
+}
+
+/// Active duration of a session in seconds, excluding paused time.
+#[tauri::command]
+pub fn get_session_duration(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<i64, String> {
+    let now = time::format_for_db(SystemTime::now());
+    state.db.get_session_duration(session_id, &now).map_err(|e| e.to_string())
+}
+
+/// Capture interruptions in a session: spans between consecutive screenshots longer
+/// than twice `expected_interval_ms` (failed ticks, pauses, sleep).
+#[tauri::command]
+pub fn get_session_gaps(
+    state: State<'_, Arc<AppState>>,
+    session_id: i64,
+    expected_interval_ms: i64,
+) -> Result<Vec<SessionGap>, String> {
+    if expected_interval_ms <= 0 {
+        return Err("expected_interval_ms must be positive".to_string());
+    }
+    state.db
+        .get_session_gaps(session_id, expected_interval_ms.saturating_mul(2))
+        .map_err(|e| e.to_string())
+}
+
+/// The applications a session's screenshots were mostly taken in, most frequent first,
+/// going by the app name at the end of each window title.
+#[tauri::command]
+pub fn get_session_top_apps(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<Vec<AppUsage>, String> {
+    let titles = state.db.get_session_window_titles(session_id).map_err(|e| e.to_string())?;
+    Ok(apps::top_apps(&titles, apps::TOP_APPS))
+}
+
+/// Trim a session's recorded start (and end, once it has ended) to its first and
+/// last screenshot, so durations and timelines match what was actually captured.
+#[tauri::command]
+pub fn normalize_session_bounds(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<CaptureSession, String> {
+    let updated = state.db.normalize_session_bounds(session_id).map_err(|e| e.to_string())?;
+    if !updated {
+        return Err(format!("Session {} has no screenshots", session_id));
+    }
+    info!("Normalized bounds of session {} to its screenshots", session_id);
+    state.db.get_session(session_id).map_err(|e| e.to_string())
+}
+
+/// Record the ground-truth title and category of a screenshot, replacing any
+/// earlier label. The category must be one the AI can answer with.
+#[tauri::command]
+pub fn set_screenshot_label(
+    state: State<'_, Arc<AppState>>,
+    screenshot_id: i64,
+    title: String,
+    category: String,
+) -> Result<ScreenshotLabel, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Label title can't be empty".to_string());
+    }
+    let category = category.trim().to_lowercase();
+    if !crate::ai::CATEGORIES.contains(&category.as_str()) {
+        return Err(format!("Unknown category {:?}; expected one of {}", category, crate::ai::CATEGORIES.join(", ")));
+    }
+    let labeled_at = time::format_for_db(SystemTime::now());
+    state.db
+        .set_screenshot_label(screenshot_id, title, &category, &labeled_at)
+        .map_err(|e| e.to_string())?;
+    Ok(ScreenshotLabel { screenshot_id, label_title: title.to_string(), label_category: category, labeled_at })
+}
+
+#[tauri::command]
+pub fn get_screenshot_label(state: State<'_, Arc<AppState>>, screenshot_id: i64) -> Result<Option<ScreenshotLabel>, String> {
+    state.db.get_screenshot_label(screenshot_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_labeled_screenshots(
+    state: State<'_, Arc<AppState>>,
+    session_id: Option<i64>,
+) -> Result<Vec<LabeledScreenshot>, String> {
+    state.db.list_labeled_screenshots(session_id).map_err(|e| e.to_string())
+}
+
+/// Score a session's analysis against its labeled screenshots.
+#[tauri::command]
+pub fn evaluate_analysis(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<EvaluationReport, String> {
+    let pairs = state.db.get_session_label_tasks(session_id).map_err(|e| e.to_string())?;
+    Ok(evaluation::evaluate(pairs))
+}
+
+#[tauri::command]
+pub fn get_current_session(state: State<'_, Arc<AppState>>) -> Result<Option<CaptureSession>, String> {
+    let session_id = state.current_session_id.load(Ordering::Relaxed);
+    if session_id <= 0 {
+        return Ok(None);
+    }
+    match state.db.get_session(session_id) {
+        Ok(session) => Ok(Some(session)),
+        Err(e) => {
+            if e.to_string().contains("Query returned no rows") {
+                Ok(None)
+            } else {
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_tasks(
+    state: State<'_, Arc<AppState>>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<Task>, String> {
+    state
+        .db
+        .get_tasks(limit.unwrap_or(50), offset.unwrap_or(0))
+        .map_err(|e| e.to_string())
 }
 
-/// Format a SystemTime as an ISO 8601 string suitable for filenames.
-/// Uses hyphens instead of colons so the filename is valid on all platforms.
-fn format_timestamp_for_filename(time: SystemTime) -> String {
-    let duration = time
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default();
-    let secs = duration.as_secs();
+/// Every task in `category` started between `start` and `end` (inclusive; a bare
+/// `end` date covers that whole day), across sessions, in chronological order.
+#[tauri::command]
+pub fn get_tasks_by_category_in_range(
+    state: State<'_, Arc<AppState>>,
+    category: String,
+    start: String,
+    end: String,
+) -> Result<Vec<Task>, String> {
+    let category = category.trim();
+    validate_task_range(category, &start, &end)?;
+    let zone = time_zone(&state.db);
+    state
+        .db
+        .get_tasks_by_category_in_range(category, &range_bound(&zone, &start, false), &range_bound(&zone, &end, true))
+        .map_err(|e| e.to_string())
+}
 
-    let days = secs / 86400;
-    let time_of_day = secs % 86400;
-    let hours = time_of_day / 3600;
-    let minutes = (time_of_day % 3600) / 60;
-    let seconds = time_of_day % 60;
+/// Longest range `get_daily_task_counts` returns, in days: ten years of heatmap.
+const MAX_DAILY_COUNT_DAYS: i64 = 3660;
 
-    let (year, month, day) = days_to_ymd(days);
+/// Tasks started on each date from `start` to `end` (`YYYY-MM-DD`, inclusive), grouped
+/// by local day in the `timezone` setting's zone, with days without tasks reported as
+/// 0. For the activity heatmap.
+#[tauri::command]
+pub fn get_daily_task_counts(
+    state: State<'_, Arc<AppState>>,
+    start: String,
+    end: String,
+) -> Result<Vec<DailyTaskCount>, String> {
+    let (start_day, end_day) = daily_count_range(&start, &end)?;
+    state.db.get_daily_task_counts(&time_zone(&state.db), start_day, end_day).map_err(|e| e.to_string())
+}
 
-    format!(
-        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
-        year, month, day, hours, minutes, seconds
-    )
+/// The days since the epoch `get_daily_task_counts` covers.
+fn daily_count_range(start: &str, end: &str) -> Result<(i64, i64), String> {
+    let day = |name: &str, value: &str| {
+        time::parse_date(value).ok_or_else(|| format!("Invalid {} '{}': expected YYYY-MM-DD", name, value))
+    };
+    let (start_day, end_day) = (day("start", start)?, day("end", end)?);
+    if start_day > end_day {
+        return Err(format!("Range start {} is after end {}", start, end));
+    }
+    if end_day - start_day >= MAX_DAILY_COUNT_DAYS {
+        return Err(format!("Range {} to {} is longer than {} days", start, end, MAX_DAILY_COUNT_DAYS));
+    }
+    Ok((start_day, end_day))
 }
 
-/// Format a SystemTime as an ISO 8601 string for database storage.
-fn format_timestamp_for_db(time: SystemTime) -> String {
-    let duration = time
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default();
-    let secs = duration.as_secs();
+/// Check a category/date-range query before it reaches the database.
+fn validate_task_range(category: &str, start: &str, end: &str) -> Result<(), String> {
+    if category.is_empty() {
+        return Err("Category must not be empty".to_string());
+    }
+    for (name, value) in [("start", start), ("end", end)] {
+        if !starts_with_date(value) && time::parse_iso_week(value).is_none() {
+            return Err(format!("Invalid {} '{}': expected YYYY-MM-DD, YYYY-Www or an ISO 8601 timestamp", name, value));
+        }
+    }
+    // A week compares as its first or last day
+    let week_day = |value: &str, last: bool| {
+        time::parse_iso_week(value).map(|monday| time::format_date(monday + if last { 6 } else { 0 }))
+    };
+    let (start_day, end_day) = (week_day(start, false), week_day(end, true));
+    let (start, end) = (start_day.as_deref().unwrap_or(start), end_day.as_deref().unwrap_or(end));
+    // Bytes, since the caller's input may have a multi-byte character at the cut
+    let (start_bytes, end_bytes) = (start.as_bytes(), end.as_bytes());
+    if start_bytes[..end_bytes.len().min(start_bytes.len())] > *end_bytes {
+        return Err(format!("Range start {} is after end {}", start, end));
+    }
+    Ok(())
+}
 
-    let days = secs / 86400;
-    let time_of_day = secs % 86400;
-    let hours = time_of_day / 3600;
-    let minutes = (time_of_day % 3600) / 60;
-    let seconds = time_of_day % 60;
+/// The `timezone` setting's zone; the system's if the setting is unreadable.
+fn time_zone(db: &Database) -> time::TimeZone {
+    let setting = db.get_setting_string("timezone", "system").unwrap_or_default();
+    time::TimeZone::from_setting(&setting).unwrap_or_else(|e| {
+        warn!("Invalid timezone setting, using the system zone: {}", e);
+        time::TimeZone::system()
+    })
+}
 
-    let (year, month, day) = days_to_ymd(days);
+/// The diagnostics "time zone" step: fails when local days silently fall back to UTC,
+/// i.e. the `timezone` setting is invalid or is `system` and the system zone can't be
+/// read (see `TimeZone::system`).
+fn time_zone_summary(db: &Database) -> Result<String, String> {
+    let setting = db.get_setting_string("timezone", "system").map_err(|e| e.to_string())?;
+    let setting = setting.trim();
+    if !setting.eq_ignore_ascii_case("system") {
+        return time::TimeZone::from_setting(setting).map(|_| format!("Using the timezone setting '{}'", setting));
+    }
+    match time::TimeZone::detect_system() {
+        Some(_) => Ok("Using the system time zone".to_string()),
+        None => Err("Could not read the system time zone, so local days are UTC; set the timezone setting".to_string()),
+    }
+}
 
-    format!(
-        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
-        year, month, day, hours, minutes, seconds
-    )
+/// One bound of an inclusive date-range query as a UTC database timestamp: a bare
+/// `YYYY-MM-DD` or ISO week `YYYY-Www` is the first (or, for the `end`, last) second of
+/// that day or week in `zone`, and a full timestamp is converted to UTC. Anything else
+/// is passed through as given.
+fn range_bound(zone: &time::TimeZone, value: &str, end: bool) -> String {
+    let bounds = time::parse_date(value)
+        .map(|day| zone.day_bounds(day))
+        .or_else(|| time::parse_iso_week(value).map(|monday| zone.week_bounds(monday)));
+    if let Some((start, next)) = bounds {
+        return time::format_db(if end { next - 1 } else { start });
+    }
+    time::parse_timestamp(value).map(time::format_db).unwrap_or_else(|| value.to_string())
 }
 
-/// Convert days since Unix epoch to (year, month, day).
-/// Algorithm based on Howard Hinnant's civil_from_days.
-fn days_to_ymd(days: u64) -> (u64, u64, u64) {
-    let z = days as i64 + 719468;
-    let era = if z >= 0 { z } else { z - 146096 } / 146097;
-    let doe = (z - era * 146097) as u64;
-    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
-    let y = yoe as i64 + era * 400;
-    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
-    let mp = (5 * doy + 2) / 153;
-    let d = doy - (153 * mp + 2) / 5 + 1;
-    let m = if mp < 10 { mp + 3 } else { mp - 9 };
-    let y = if m <= 2 { y + 1 } else { y };
-    (y as u64, m, d)
+/// Retention cutoff for `days` of retention: the start of the local day `days` days
+/// before today, so whatever was added on one day expires together. 0 keeps nothing.
+fn retention_cutoff(db: &Database, days: u64) -> String {
+    retention_cutoff_at(&time_zone(db), time::unix_secs(SystemTime::now()), days)
 }
 
-#[tauri::command]
-pub fn get_capture_status(state: State<'_, Arc<AppState>>) -> CaptureStatus {
-    let mode = state
-        .db
-        .get_setting("capture_monitor_mode")
-        .unwrap_or(None)
-        .unwrap_or_else(|| "default".to_string());
-    let monitors_captured = {
-        let ms = state.monitor_states.lock().unwrap();
-        ms.len() as u32
-    };
-    CaptureStatus {
-        active: state.capturing.load(Ordering::Relaxed),
-        interval_ms: state.capture_interval_ms.load(Ordering::Relaxed),
-        count: state.capture_count.load(Ordering::Relaxed),
-        monitor_mode: mode,
-        monitors_captured,
+fn retention_cutoff_at(zone: &time::TimeZone, now: i64, days: u64) -> String {
+    if days == 0 {
+        return time::format_db(now);
     }
+    time::format_db(zone.day_bounds(zone.local_day(now) - days as i64).0)
 }
 
-#[tauri::command]
-pub fn get_monitors() -> Result<Vec<MonitorInfo>, String> {
-    capture::list_monitors().map_err(|e| e.to_string())
+/// Whether `value` begins with a `YYYY-MM-DD` date.
+fn starts_with_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() >= 10
+        && bytes[..10].iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
 }
 
+/// Write the whole database (sessions, screenshots, tasks, links, settings) to one
+/// versioned JSON file. Screenshot image files are not included, and neither are the
+/// API key and extra headers unless `include_secrets` is set.
 #[tauri::command]
-pub fn start_capture(state: State<'_, Arc<AppState>>, interval_ms: Option<u64>, description: Option<String>, title: Option<String>) -> Result<(), String> {
-    // Guard against spawning multiple capture loops
-    if state.capturing.load(Ordering::Relaxed) {
-        return Ok(());
-    }
-
-    let interval = interval_ms.unwrap_or_else(|| state.capture_interval_ms.load(Ordering::Relaxed));
-    info!("Starting capture with interval {}ms", interval);
+pub fn export_all(state: State<'_, Arc<AppState>>, output_path: String, include_secrets: Option<bool>) -> Result<(), String> {
+    let exported_at = time::format_for_db(SystemTime::now());
+    let dump = backup::export_all(&state.db, include_secrets.unwrap_or(false), &exported_at).map_err(|e| e.to_string())?;
+    backup::write_dump(&dump, Path::new(&output_path)).map_err(|e| e.to_string())?;
+    info!("Exported database to {}", output_path);
+    Ok(())
+}
 
-    if let Some(ms) = interval_ms {
-        state.capture_interval_ms.store(ms, Ordering::Relaxed);
+/// Restore a dump written by `export_all`. Refuses a database that already has data
+/// unless `force` is set, in which case everything is replaced.
+#[tauri::command]
+pub fn import_all(state: State<'_, Arc<AppState>>, input_path: String, force: Option<bool>) -> Result<(), String> {
+    if state.capturing.load(Ordering::Relaxed) || state.analyzing.load(Ordering::Relaxed) {
+        return Err("Stop capture and analysis before importing".to_string());
     }
+    let dump = backup::read_dump(Path::new(&input_path)).map_err(|e| e.to_string())?;
+    backup::import_all(&state.db, &dump, force.unwrap_or(false)).map_err(|e| e.to_string())?;
+    info!("Imported database dump from {} (exported {})", input_path, dump.exported_at);
+    Ok(())
+}
 
-    // Create a new capture session
-    let session_timestamp = format_timestamp_for_db(SystemTime::now());
-    let desc_ref = description.as_deref().filter(|s| !s.trim().is_empty());
-    let title_ref = title.as_deref().filter(|s| !s.trim().is_empty());
-    let session_id = state.db.create_session(&session_timestamp, desc_ref, title_ref)
-        .map_err(|e| format!("Failed to create capture session: {}", e))?;
-    state.current_session_id.store(session_id, Ordering::Relaxed);
-    info!("Created capture session {}", session_id);
-
-    state.capturing.store(true, Ordering::Relaxed);
+/// Write the user's settings to `path` as pretty JSON. The API key is left out
+/// unless `include_secrets` is set. Returns the number of settings written.
+#[tauri::command]
+pub fn export_settings(state: State<'_, Arc<AppState>>, path: String, include_secrets: Option<bool>) -> Result<usize, String> {
+    let exported_at = time::format_for_db(SystemTime::now());
+    let profile = profile::export_profile(&state.db, include_secrets.unwrap_or(false), &exported_at)
+        .map_err(|e| e.to_string())?;
+    profile::write_profile(&profile, Path::new(&path)).map_err(|e| e.to_string())?;
+    info!("Exported {} settings to {}", profile.settings.len(), path);
+    Ok(profile.settings.len())
+}
 
-    // Clear monitor states for fresh session
-    {
-        let mut ms = state.monitor_states.lock().unwrap();
-        ms.clear();
+/// Apply a settings profile written by `export_settings`. Nothing is written if any
+/// value fails validation; the report says which keys were applied, skipped or rejected.
+#[tauri::command]
+pub fn import_settings(state: State<'_, Arc<AppState>>, path: String, overwrite: bool) -> Result<SettingsImport, String> {
+    let profile = profile::read_profile(Path::new(&path)).map_err(|e| e.to_string())?;
+    let report = profile::import_profile(&state.db, &profile, overwrite).map_err(|e| e.to_string())?;
+    if report.applied.iter().any(|k| k == "log_level") {
+        apply_log_level(&profile.settings["log_level"]);
     }
+    info!(
+        "Imported settings from {}: {} applied, {} unknown, {} existing, {} rejected",
+        path, report.applied.len(), report.skipped_unknown.len(), report.skipped_existing.len(), report.rejected.len()
+    );
+    Ok(report)
+}
 
-    // Ensure screenshots directory exists
-    std::fs::create_dir_all(&state.screenshots_dir)
-        .map_err(|e| {
-            error!("Failed to create screenshots directory: {}", e);
-            format!("Failed to create screenshots directory: {}", e)
-        })?;
+/// Import a browser/editor activity log (`format` "jsonl", the default) of
+/// `{timestamp, title, app}` records, used as extra prompt context for the capture
+/// groups they overlap. Records matching `blocked_title_keywords` are dropped.
+#[tauri::command]
+pub fn import_activity_log(state: State<'_, Arc<AppState>>, path: String, format: Option<String>) -> Result<ActivityImport, String> {
+    let format = format.unwrap_or_else(|| "jsonl".to_string());
+    let result = activity::import_activity_log(&state.db, Path::new(&path), &format, &blocked_title_keywords(&state.db))
+        .map_err(|e| e.to_string())?;
+    info!(
+        "Imported {} activity samples from {} ({} in a session; {} malformed, {} blocked, {} duplicate)",
+        result.imported, path, result.in_session, result.skipped_malformed, result.skipped_blocked, result.skipped_duplicate
+    );
+    Ok(result)
+}
 
-    let app_state = Arc::clone(&state);
+fn blocked_title_keywords(db: &Database) -> Vec<String> {
+    privacy::parse_keywords(&db.get_setting_string("blocked_title_keywords", "").unwrap_or_default())
+}
 
-    let capture_handle = tauri::async_runtime::spawn(async move {
-        loop {
-            if !app_state.capturing.load(Ordering::Relaxed) {
-                info!("Capture loop stopped");
-                break;
-            }
+/// How long `factory_reset` waits for a running analysis to notice the cancel flag.
+const RESET_ANALYSIS_TIMEOUT: Duration = Duration::from_secs(15);
 
-            // Read monitor mode settings
-            let mode = app_state.db.get_setting("capture_monitor_mode")
-                .unwrap_or(None)
-                .unwrap_or_else(|| "default".to_string());
-            let specific_id: Option<u32> = app_state.db.get_setting("capture_monitor_id")
-                .unwrap_or(None)
-                .and_then(|v| v.parse().ok());
+/// Delete everything the app stores: the database (and its WAL files), the screenshots
+/// directory with its `trash/` and `corrupt/` folders, saved analysis inputs and the
+/// activity inbox. A custom screenshots directory is the user's, so only the files the
+/// app wrote there are deleted (`reset::clear_custom_dir`). `confirmation` must be the database
+/// file name. Capture, analysis and the managed Ollama are stopped first, and the
+/// database is recreated empty in place, so the app keeps running. Each item is
+/// attempted and reported on its own; a final pass lists anything left behind. If it
+/// gives up before deleting anything, the session that was being captured is ended and
+/// the error says so.
+#[tauri::command]
+pub async fn factory_reset(state: State<'_, Arc<AppState>>, confirmation: String) -> Result<FactoryReset, String> {
+    if confirmation != crate::DB_FILE {
+        return Err("Confirmation does not match the database file name; nothing was deleted".to_string());
+    }
+    warn!("Factory reset requested");
 
-            let now = SystemTime::now();
-            let filename_ts = format_timestamp_for_filename(now);
-            let db_timestamp = format_timestamp_for_db(now);
-            let capture_group = filename_ts.clone();
+    // The session is about to be deleted, so it is not finished (or analyzed) here
+    let session_id = halt_capture(&state);
+    state.session_screenshot_count.store(0, Ordering::Relaxed);
+    // Until the database goes, bailing out keeps everything: the halted session is
+    // ended properly rather than left open, and the error says capture stopped
+    let abort = |reason: String| {
+        if session_id <= 0 {
+            return format!("{}; nothing was deleted", reason);
+        }
+        let stats = std::mem::take(&mut *lock_recovered(&state.session_stats));
+        close_session(&state.db, session_id, &stats);
+        format!("{}; nothing was deleted, but capture was stopped and session {} ended", reason, session_id)
+    };
 
-            match capture::capture_monitors(&mode, specific_id) {
-                Ok(captures) => {
-                    let sid = app_state.current_session_id.load(Ordering::Relaxed);
-                    let session_opt = if sid > 0 { Some(sid) } else { None };
-                    let single = captures.len() == 1;
-                    let mut saved_count = 0u32;
-
-                    let mut monitor_states = app_state.monitor_states.lock().unwrap();
-
-                    for cap in &captures {
-                        let hash = capture::perceptual_hash(&cap.image);
-                        let changed = match monitor_states.get(&cap.monitor_id) {
-                            Some(ms) => capture::hash_distance(&hash, &ms.last_hash) >= 10,
-                            None => true, // first capture for this monitor
-                        };
-
-                        if changed {
-                            let filename = if single {
-                                format!("screenshot_{}.webp", filename_ts)
-                            } else {
-                                format!("screenshot_{}_mon{}.webp", filename_ts, cap.monitor_id)
-                            };
+    state.cancel_analysis.store(true, Ordering::Relaxed);
+    let deadline = Instant::now() + RESET_ANALYSIS_TIMEOUT;
+    while state.analyzing.load(Ordering::Relaxed) {
+        if Instant::now() >= deadline {
+            return Err(abort("Analysis did not stop in time".to_string()));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
 
-                            let path = app_state.screenshots_dir.join(&filename);
-                            if let Err(e) = capture::save_image_as_webp(&cap.image, &path) {
-                                error!("Failed to save screenshot: {}", e);
-                                continue;
-                            }
+    // Read before the database goes: it's the record of which files in a custom
+    // screenshots directory are ours
+    let default_dir = state.app_data_dir.join("screenshots");
+    let screenshots_dir = state.screenshots_dir();
+    let custom_files = if screenshots_dir != default_dir {
+        Some(state.db.all_screenshot_filepaths().map_err(|e| abort(format!("Failed to list screenshots: {}", e)))?)
+    } else {
+        None
+    };
+    state.ollama_process.stop();
 
-                            let relative_path = format!("screenshots/{}", filename);
-                            match app_state.db.insert_screenshot(
-                                &relative_path,
-                                &db_timestamp,
-                                None,
-                                cap.monitor_id as i32,
-                                session_opt,
-                                Some(&capture_group),
-                            ) {
-                                Ok(_) => {
-                                    let prev_summary = monitor_states
-                                        .get(&cap.monitor_id)
-                                        .map(|s| s.last_summary.clone())
-                                        .unwrap_or_default();
-                                    monitor_states.insert(cap.monitor_id, MonitorState {
-                                        last_hash: hash,
-                                        last_summary: prev_summary,
-                                        name: cap.monitor_name.clone(),
-                                    });
-                                    saved_count += 1;
-                                }
-                                Err(e) => error!("Failed to insert screenshot into DB: {}", e),
-                            }
-                        } else {
-                            // Unchanged — just update the hash
-                            if let Some(ms) = monitor_states.get_mut(&cap.monitor_id) {
-                                ms.last_hash = hash;
-                            }
-                        }
-                    }
-                    drop(monitor_states);
-
-                    if saved_count > 0 {
-                        let count = app_state.capture_count.fetch_add(saved_count as u64, Ordering::Relaxed) + saved_count as u64;
-                        debug!("Captured {} screenshots (total: {})", saved_count, count);
-
-                        // Auto-analysis logic
-                        let analysis_mode = app_state.db.get_setting("analysis_mode")
-                            .unwrap_or(None)
-                            .unwrap_or_else(|| "batch".to_string());
-                        let batch_size: u64 = app_state.db.get_setting("batch_size")
-                            .unwrap_or(None)
-                            .and_then(|v| v.parse().ok())
-                            .unwrap_or(10)
-                            .max(1)
-                            .min(100);
-
-                        let should_analyze = if analysis_mode == "realtime" {
-                            !app_state.analyzing.load(Ordering::Relaxed)
-                        } else {
-                            count % batch_size == 0
-                        };
-
-                        if should_analyze {
-                            let analysis_state = Arc::clone(&app_state);
-                            let session_for_analysis = sid;
-                            let limit = if analysis_mode == "realtime" { 1 } else { batch_size as i64 };
-                            tauri::async_runtime::spawn(async move {
-                                if session_for_analysis > 0 {
-                                    match run_session_analysis(&analysis_state, session_for_analysis, limit).await {
-                                        Ok(n) if n > 0 => info!("Auto-analyzed {} screenshots for session {}", n, session_for_analysis),
-                                        Ok(_) => {}
-                                        Err(e) => debug!("Auto-analysis skipped: {}", e),
-                                    }
-                                }
-                            });
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Screenshot capture failed: {}", e);
-                }
-            }
+    let mut items = Vec::new();
+    let db_path = state.app_data_dir.join(crate::DB_FILE);
+    let removed = state.db.recreate(&db_path).map_err(|e| format!("Failed to recreate the database: {}", e))?;
+    items.extend(removed.into_iter().map(|(file, result)| reset::item(&file, "deleted", result)));
 
-            let interval = app_state.capture_interval_ms.load(Ordering::Relaxed);
-            tokio::time::sleep(std::time::Duration::from_millis(interval)).await;
-        }
-    });
+    let cleared = vec![
+        default_dir.clone(),
+        state.app_data_dir.join(analysis::ANALYSIS_DEBUG_DIR),
+        state.app_data_dir.join(activity::ACTIVITY_INBOX_DIR),
+    ];
+    items.extend(cleared.iter().map(|dir| reset::remove_dir(dir)));
+    if let Some(files) = &custom_files {
+        items.extend(reset::clear_custom_dir(&screenshots_dir, files));
+    }
+    // The custom directory setting went with the database
+    items.push(reset::recreate_dir(&default_dir));
+    *lock_recovered(&state.screenshots_dir) = default_dir;
 
-    // Monitor the capture task for panics
-    tauri::async_runtime::spawn(async move {
-        if let Err(e) = capture_handle.await {
-            error!("Capture task failed: {}", e);
-        }
-    });
+    state.capture_count.store(0, Ordering::Relaxed);
+    *lock_recovered(&state.session_stats) = SessionStats::default();
+    lock_recovered(&state.monitor_states).clear();
+    lock_recovered(&state.recent_errors).clear();
+    lock_recovered(&state.last_analysis_success).clear();
 
-    Ok(())
+    let mut leftovers = reset::leftovers(&cleared);
+    if let Some(files) = &custom_files {
+        leftovers.extend(reset::custom_dir_leftovers(&screenshots_dir, files));
+    }
+    match state.db.has_data() {
+        Ok(false) => {}
+        Ok(true) => leftovers.push(format!("{}: still has sessions, screenshots or tasks", db_path.display())),
+        Err(e) => leftovers.push(format!("{}: {}", db_path.display(), e)),
+    }
+    let failed = items.iter().filter(|item| item.error.is_some()).count();
+    let verified = failed == 0 && leftovers.is_empty();
+    if verified {
+        info!("Factory reset complete: {} items removed", items.len());
+    } else {
+        error!("Factory reset incomplete: {} items failed, {} left over", failed, leftovers.len());
+    }
+    Ok(FactoryReset { items, verified, leftovers })
 }
 
+/// Write analyzed screenshots and their task labels to `path` as JSONL for training.
+/// With `copy_images`, images are copied into an `images/` directory next to the file.
 #[tauri::command]
-pub fn stop_capture(state: State<'_, Arc<AppState>>) {
-    info!("Stopping capture");
-    state.capturing.store(false, Ordering::Relaxed);
-
-    let session_id = state.current_session_id.swap(0, Ordering::Relaxed);
-    if session_id > 0 {
-        let ended_at = format_timestamp_for_db(SystemTime::now());
-        if let Err(e) = state.db.end_session(session_id, &ended_at) {
-            error!("Failed to end capture session {}: {}", session_id, e);
-        } else {
-            info!("Ended capture session {}", session_id);
-        }
+pub fn export_training_data(
+    state: State<'_, Arc<AppState>>,
+    path: String,
+    include_unverified: bool,
+    copy_images: bool,
+) -> Result<TrainingExport, String> {
+    let summary = training::export_training_data(
+        &state.db,
+        Path::new(&path),
+        &state.screenshots_dir(),
+        include_unverified,
+        copy_images,
+    )
+    .map_err(|e| e.to_string())?;
+    info!(
+        "Exported {} training rows ({} bytes, {} images copied) to {}",
+        summary.rows, summary.bytes_written, summary.images_copied, path
+    );
+    Ok(summary)
+}
 
-        let analysis_state = Arc::clone(&state);
-        tauri::async_runtime::spawn(async move {
-            match run_session_analysis(&analysis_state, session_id, 0).await {
-                Ok(n) if n > 0 => info!("Post-capture analysis: analyzed {} screenshots for session {}", n, session_id),
-                Ok(_) => info!("Post-capture analysis: no unanalyzed screenshots for session {}", session_id),
-                Err(e) => error!("Post-capture analysis failed for session {}: {}", session_id, e),
-            }
-        });
+/// Change-detection savings across sessions started between `from` and `to`
+/// (inclusive; either may be omitted).
+#[tauri::command]
+pub fn get_savings_summary(
+    state: State<'_, Arc<AppState>>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<SavingsSummary, String> {
+    let zone = time_zone(&state.db);
+    let from = from.map(|from| range_bound(&zone, &from, false));
+    let to = to.map(|to| range_bound(&zone, &to, true));
+    let sessions = state.db
+        .get_session_stats_between(from.as_deref(), to.as_deref())
+        .map_err(|e| e.to_string())?;
+    let mut total = SessionStats::default();
+    for stats in &sessions {
+        savings::merge(&mut total, stats);
     }
+    Ok(savings::estimate(sessions.len() as i64, total))
 }
 
+/// Capture events (e.g. `skipped_private` ticks) recorded for a session.
 #[tauri::command]
-pub fn get_current_session(state: State<'_, Arc<AppState>>) -> Result<Option<CaptureSession>, String> {
-    let session_id = state.current_session_id.load(Ordering::Relaxed);
-    if session_id <= 0 {
-        return Ok(None);
-    }
-    match state.db.get_session(session_id) {
-        Ok(session) => Ok(Some(session)),
-        Err(e) => {
-            if e.to_string().contains("Query returned no rows") {
-                Ok(None)
-            } else {
-                Err(e.to_string())
-            }
-        }
-    }
+pub fn get_capture_events(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<Vec<CaptureEvent>, String> {
+    state.db.get_capture_events(session_id).map_err(|e| e.to_string())
 }
 
+/// Tasks with the provider/model that analyzed them, for auditing mixed-provider history.
 #[tauri::command]
-pub fn get_tasks(
+pub fn get_tasks_with_provider(
     state: State<'_, Arc<AppState>>,
     limit: Option<i64>,
     offset: Option<i64>,
-) -> Result<Vec<Task>, String> {
+) -> Result<Vec<TaskWithProvider>, String> {
     state
         .db
-        .get_tasks(limit.unwrap_or(50), offset.unwrap_or(0))
+        .get_tasks_with_provider(limit.unwrap_or(50), offset.unwrap_or(0))
         .map_err(|e| e.to_string())
 }
 
@@ -354,6 +1784,29 @@ pub fn get_task(state: State<'_, Arc<AppState>>, id: i64) -> Result<Task, String
     state.db.get_task(id).map_err(|e| e.to_string())
 }
 
+/// A task with its screenshots and parsed metadata, for the task detail view.
+#[tauri::command]
+pub fn get_task_detail(state: State<'_, Arc<AppState>>, task_id: i64) -> Result<TaskDetail, String> {
+    state
+        .db
+        .get_task_detail(task_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Task {} not found", task_id))
+}
+
+/// What each analysis of a capture group sent to the AI (`save_analysis_inputs`),
+/// newest first, with absolute image paths for display.
+#[tauri::command]
+pub fn get_analysis_debug(state: State<'_, Arc<AppState>>, capture_group: String) -> Result<Vec<AnalysisDebugEntry>, String> {
+    let mut entries = state.db.get_analysis_debug(&capture_group).map_err(|e| e.to_string())?;
+    for entry in &mut entries {
+        for path in &mut entry.image_paths {
+            *path = state.app_data_dir.join(&*path).to_string_lossy().into_owned();
+        }
+    }
+    Ok(entries)
+}
+
 #[tauri::command]
 pub fn update_task(
     state: State<'_, Arc<AppState>>,
@@ -370,16 +1823,147 @@ pub fn delete_task(state: State<'_, Arc<AppState>>, id: i64) -> Result<(), Strin
 
 #[tauri::command]
 pub fn get_setting(state: State<'_, Arc<AppState>>, key: String) -> Result<Option<String>, String> {
-    state.db.get_setting(&key).map_err(|e| e.to_string())
+    state.db.get_setting_or_default(&key).map_err(|e| e.to_string())
 }
 
+/// Persist a setting after validating it against the settings registry.
+/// Unknown keys are rejected unless `allow_unknown` is true.
 #[tauri::command]
 pub fn update_setting(
     state: State<'_, Arc<AppState>>,
     key: String,
     value: String,
+    allow_unknown: Option<bool>,
+) -> Result<(), String> {
+    settings::validate(&key, &value, allow_unknown.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+    state.db.set_setting(&key, &value).map_err(|e| e.to_string())?;
+    if key == "log_level" {
+        apply_log_level(&value);
+    }
+    Ok(())
+}
+
+/// The settings to write for `set_monitor_mode`: the mode, plus the monitor id in
+/// "specific" mode (which requires one, and which no other mode takes). Switching away
+/// from "specific" leaves the stored id alone for switching back.
+fn monitor_mode_settings(mode: &str, monitor_id: Option<u32>) -> Result<Vec<(String, String)>, String> {
+    settings::validate("capture_monitor_mode", mode, false).map_err(|e| e.to_string())?;
+    match (mode, monitor_id) {
+        ("specific", None) => Err("\"specific\" mode needs a monitor_id".to_string()),
+        ("specific", Some(id)) => Ok(vec![
+            ("capture_monitor_mode".to_string(), mode.to_string()),
+            ("capture_monitor_id".to_string(), id.to_string()),
+        ]),
+        (_, Some(_)) => Err(format!("monitor_id only applies to \"specific\" mode, not \"{}\"", mode)),
+        (_, None) => Ok(vec![("capture_monitor_mode".to_string(), mode.to_string())]),
+    }
+}
+
+/// Set the monitor mode (and, for "specific", the monitor) in one write, after checking
+/// the combination and that the monitor is connected. With `preview`, the new selection
+/// is outlined with `highlight_monitors`.
+#[tauri::command]
+pub async fn set_monitor_mode(
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    mode: String,
+    monitor_id: Option<u32>,
+    preview: Option<bool>,
+) -> Result<(), String> {
+    let values = monitor_mode_settings(&mode, monitor_id)?;
+    if let Some(id) = monitor_id {
+        // Only checked when monitors can be listed, so a flaky listing can't block the change
+        if let Ok(monitors) = capture::list_monitors() {
+            if !monitors.iter().any(|m| m.id == id) {
+                return Err(format!("Monitor {} is not connected", id));
+            }
+        }
+    }
+    state.db.set_settings(&values).map_err(|e| e.to_string())?;
+    info!("Monitor mode set to {}{}", mode, monitor_id.map(|id| format!(" (monitor {})", id)).unwrap_or_default());
+
+    if preview.unwrap_or(false) {
+        if let Err(e) = highlight_monitors(app_handle, state, mode, monitor_id).await {
+            warn!("Failed to preview monitor selection: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Change the log level without restarting ("error" … "trace") and remember it.
+#[tauri::command]
+pub fn set_log_level(state: State<'_, Arc<AppState>>, level: String) -> Result<(), String> {
+    let level = level.trim().to_ascii_lowercase();
+    settings::validate("log_level", &level, false).map_err(|e| e.to_string())?;
+    state.db.set_setting("log_level", &level).map_err(|e| e.to_string())?;
+    apply_log_level(&level);
+    info!("Log level set to {}", level);
+    Ok(())
+}
+
+/// Set the global max log level from a `log_level` setting value. The log plugin is
+/// built at trace so this filter alone decides what gets written.
+pub fn apply_log_level(level: &str) {
+    match level.parse::<log::LevelFilter>() {
+        Ok(filter) => log::set_max_level(filter),
+        Err(_) => warn!("Ignoring invalid log level {:?}", level),
+    }
+}
+
+/// Run `validator` against a new API key and persist it only if validation succeeds,
+/// so a bad key never replaces a working one.
+async fn validate_then_persist_api_key<F, Fut>(
+    db: &Database,
+    key: &str,
+    validator: F,
+) -> Result<(), String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<(), crate::ai::AiError>>,
+{
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API key must not be empty".to_string());
+    }
+    validator(key.to_string()).await.map_err(|e| match e {
+        crate::ai::AiError::RequestFailed(e) => format!("Could not reach the provider to validate the key: {}", e),
+        e => format!("API key validation failed: {}", e),
+    })?;
+    db.set_setting("ai_api_key", key).map_err(|e| e.to_string())
+}
+
+/// Validate a new Claude API key with a minimal API call before saving it.
+#[tauri::command]
+pub async fn set_and_validate_api_key(
+    state: State<'_, Arc<AppState>>,
+    key: String,
 ) -> Result<(), String> {
-    state.db.set_setting(&key, &value).map_err(|e| e.to_string())
+    let client = reqwest::Client::new();
+    let endpoint = analysis::claude_endpoint(&state.db)?;
+    validate_then_persist_api_key(&state.db, &key, |k| async move {
+        crate::ai::validate_api_key(&client, &endpoint, &k).await
+    })
+    .await?;
+    info!("Validated and saved new API key");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings_schema() -> Vec<SettingSchema> {
+    settings::schema()
+}
+
+/// Render a prompt template against synthetic data (`sample`) or empty values,
+/// so the settings UI can check a template before saving it.
+#[tauri::command]
+pub fn render_prompt_preview(template: String, sample: bool) -> Result<String, String> {
+    let vars = if sample {
+        prompt_template::PromptVars::sample()
+    } else {
+        prompt_template::PromptVars::default()
+    };
+    prompt_template::render(&template, &vars).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -437,214 +2021,289 @@ pub fn get_task_for_screenshot(
 }
 
 #[tauri::command]
-pub fn get_screenshots_dir(state: State<'_, Arc<AppState>>) -> String {
-    state.screenshots_dir.to_string_lossy().into_owned()
+pub fn get_screenshot_neighbors(
+    state: State<'_, Arc<AppState>>,
+    screenshot_id: i64,
+) -> Result<ScreenshotNeighbors, String> {
+    let (prev, next) = state
+        .db
+        .get_screenshot_neighbors(screenshot_id)
+        .map_err(|e| e.to_string())?;
+    Ok(ScreenshotNeighbors { prev, next })
 }
 
-// --- Analysis pipeline ---
+/// Most recent screenshot, for a live preview; load it relative to `get_screenshots_dir`.
+#[tauri::command]
+pub fn get_latest_screenshot(state: State<'_, Arc<AppState>>) -> Result<Option<Screenshot>, String> {
+    state.db.get_latest_screenshot().map_err(|e| e.to_string())
+}
 
-/// Group screenshots by capture_group. Screenshots with no group form individual groups.
-fn group_by_capture_group(screenshots: &[Screenshot]) -> Vec<Vec<&Screenshot>> {
-    let mut groups: std::collections::BTreeMap<String, Vec<&Screenshot>> = std::collections::BTreeMap::new();
-    let mut ungrouped = Vec::new();
+#[tauri::command]
+pub fn get_screenshots_dir(state: State<'_, Arc<AppState>>) -> String {
+    state.screenshots_dir().to_string_lossy().into_owned()
+}
 
-    for ss in screenshots {
-        match &ss.capture_group {
-            Some(group) => groups.entry(group.clone()).or_default().push(ss),
-            None => ungrouped.push(ss),
-        }
+/// Point screenshot storage at `dir`, persisting the choice across restarts.
+/// With `move_existing`, everything in the current directory is moved over first;
+/// without it, only allowed while no screenshots are stored, since their relative paths
+/// would resolve under the new directory. Returns the number of files moved.
+#[tauri::command]
+pub fn set_screenshots_dir(state: State<'_, Arc<AppState>>, path: String, move_existing: bool) -> Result<u64, String> {
+    if state.capturing.load(Ordering::Relaxed) || state.analyzing.load(Ordering::Relaxed) {
+        return Err("Stop capture and analysis before changing the screenshots directory".to_string());
     }
+    let moved = change_screenshots_dir(&state.db, &state.screenshots_dir, Path::new(path.trim()), move_existing)?;
+    info!("Screenshots directory set to {} ({} files moved)", path.trim(), moved);
+    Ok(moved)
+}
 
-    let mut result: Vec<Vec<&Screenshot>> = groups.into_values().collect();
-    for ss in ungrouped {
-        result.push(vec![ss]);
+/// Validate `new_dir`, optionally move the contents of the current directory into it,
+/// then persist it and update `current`. Nothing is persisted if validation or the
+/// move fails, and a failed move is rolled back.
+fn change_screenshots_dir(db: &Database, current: &Mutex<PathBuf>, new_dir: &Path, move_existing: bool) -> Result<u64, String> {
+    let old_dir = lock_recovered(current).clone();
+    if new_dir == old_dir {
+        return Ok(0);
+    }
+    if !move_existing && db.has_screenshot_files().map_err(|e| e.to_string())? {
+        return Err("Screenshots are stored in the current directory; move them along to change it".to_string());
     }
-    result
+    if move_existing && (new_dir.starts_with(&old_dir) || old_dir.starts_with(new_dir)) {
+        return Err("The new directory can't be inside the current one, or the other way round".to_string());
+    }
+    capture::validate_screenshots_dir(new_dir)?;
+    let moved = if move_existing && old_dir.is_dir() {
+        capture::move_dir_contents(&old_dir, new_dir)
+            .map_err(|e| format!("Failed to move screenshots to {}: {}", new_dir.display(), e))?
+    } else {
+        0
+    };
+    db.set_screenshots_dir(new_dir).map_err(|e| e.to_string())?;
+    *lock_recovered(current) = new_dir.to_path_buf();
+    Ok(moved)
 }
 
-/// Shared analysis helper: processes screenshots with AI, grouping by capture_group.
+// --- Analysis pipeline ---
+
+/// Shared analysis helper: reads analysis settings, builds the configured provider,
+/// and runs the analysis pipeline under the caller's `claim` on the analysis status.
+/// Local runs sample machine load; the result is emitted as `analysis-complete`.
+/// `context_before` is `AnalysisOptions.context_before`.
 async fn analyze_screenshots(
+    app_handle: &tauri::AppHandle,
     state: &AppState,
+    claim: &AnalysisClaim<'_>,
     screenshots: &[crate::models::Screenshot],
     session_id: Option<i64>,
     session_description: Option<&str>,
+    context_before: Option<&str>,
 ) -> Result<u32, String> {
     if screenshots.is_empty() {
         return Ok(0);
     }
 
-    let provider = state.db.get_setting("ai_provider")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "claude".to_string());
-
-    let image_mode = state.db.get_setting("image_mode")
-        .map_err(|e| e.to_string())?
-        .unwrap_or_else(|| "downscale".to_string());
-
-    info!("Analyzing {} screenshots with provider: {}, image_mode: {}, session_desc: {:?}",
-        screenshots.len(), provider, image_mode, session_description);
-
-    state.analyzing.store(true, Ordering::Relaxed);
-    if let Some(sid) = session_id {
-        state.analyzing_session_id.store(sid, Ordering::Relaxed);
-    }
-    state.cancel_analysis.store(false, Ordering::Relaxed);
+    let provider = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let image_mode = state.db.get_setting_string("image_mode", "downscale").map_err(|e| e.to_string())?;
+    let sampling = state.db.get_setting_u64("analysis_sampling", 1).map_err(|e| e.to_string())? as usize;
 
-    let client = reqwest::Client::new();
-    let mut processed = 0u32;
+    info!("Analyzing {} screenshots with provider: {}, image_mode: {}, sampling: {}, session_desc: {:?}",
+        screenshots.len(), provider, image_mode, sampling, session_description);
 
-    // Seed recent_contexts from existing tasks in this session
-    let mut recent_contexts: std::collections::VecDeque<String> = std::collections::VecDeque::with_capacity(2);
-    if let Some(sid) = session_id {
-        if let Ok(seed_tasks) = state.db.get_recent_tasks_for_session(sid, 2) {
-            for task in &seed_tasks {
-                let desc = task.description.as_deref().unwrap_or("");
-                recent_contexts.push_back(format!("{}: {}", task.title, desc));
-            }
-        }
+    // Pick up activity logs dropped in since the last run, so this run can use them
+    let inbox = activity::import_inbox(&state.db, &state.app_data_dir.join(activity::ACTIVITY_INBOX_DIR), &blocked_title_keywords(&state.db));
+    if inbox.imported > 0 {
+        info!("Imported {} activity samples from the inbox", inbox.imported);
     }
 
-    // Group screenshots by capture_group for multi-monitor awareness
-    let groups = group_by_capture_group(screenshots);
+    // Empty templates mean "use the built-in prompt", an empty language means English
+    let template_setting = |key: &str| -> Result<Option<String>, String> {
+        Ok(state.db.get_setting(key)
+            .map_err(|e| e.to_string())?
+            .filter(|t| !t.trim().is_empty()))
+    };
+    let template_single = template_setting("prompt_template_single")?;
+    let template_multi = template_setting("prompt_template_multi")?;
+    let language = template_setting("prompt_language")?;
 
-    for group in &groups {
-        if state.cancel_analysis.load(Ordering::Relaxed) {
-            info!("Analysis cancelled by user after {} groups", processed);
-            break;
-        }
+    let monitor_scope = analysis::MonitorScope::from_setting(
+        &state.db.get_setting_string("analyze_monitor_scope", "all").map_err(|e| e.to_string())?,
+    );
 
-        // Build image paths for this group
-        let mut image_infos: Vec<(PathBuf, String, u32, u32, bool)> = Vec::new();
-        for ss in group {
-            let filename = ss.filepath
-                .strip_prefix("screenshots/")
-                .unwrap_or(&ss.filepath);
-            let path = state.screenshots_dir.join(filename);
-            // Use monitor name from monitor_states if available
-            let monitor_name = {
-                let ms = state.monitor_states.lock().unwrap();
-                ms.get(&(ss.monitor_index as u32))
-                    .map(|s| s.name.clone())
-                    .unwrap_or_else(|| format!("Monitor {}", ss.monitor_index))
-            };
-            image_infos.push((path, monitor_name, 0, 0, false));
-        }
+    let custom_fields = crate::ai::parse_custom_fields(
+        &state.db.get_setting_string("custom_fields", "").map_err(|e| e.to_string())?,
+    )?;
 
-        // Build changed monitors list
-        let changed: Vec<crate::ai::ChangedMonitor<'_>> = image_infos.iter()
-            .map(|(path, name, w, h, primary)| crate::ai::ChangedMonitor {
-                monitor_name: name.as_str(),
-                image_path: path.as_path(),
-                width: *w,
-                height: *h,
-                is_primary: *primary,
-            })
-            .collect();
+    let include_unchanged_context = state.db.get_setting_bool("include_unchanged_context", true)
+        .map_err(|e| e.to_string())?;
+    let context_depth = state.db.get_setting_u64("context_depth", 2).map_err(|e| e.to_string())? as usize;
+    let include_session_task_list = state.db.get_setting_bool("include_session_task_list", false)
+        .map_err(|e| e.to_string())?;
+    let consolidate_every = state.db.get_setting_u64("consolidate_every", 0).map_err(|e| e.to_string())? as usize;
+    let embedder = analysis::embedder_from_settings(&state.db)?;
+    let similarity_threshold = state.db.get_setting_u64("embedding_similarity_threshold", 85)
+        .map_err(|e| e.to_string())? as f32 / 100.0;
+    let carry_context_window = if state.db.get_setting_bool("carry_context_from_previous", false).map_err(|e| e.to_string())? {
+        Some(state.db.get_setting_u64("carry_context_window_minutes", 120).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let save_inputs = state.db.get_setting_bool("save_analysis_inputs", false).map_err(|e| e.to_string())?;
+    let keep_images = state.db.get_setting_bool("keep_analysis_images", false).map_err(|e| e.to_string())?;
+    let context_overlap = state.db.get_setting_u64("context_overlap", 0).map_err(|e| e.to_string())? as usize;
+    let run_id = time::format_for_db(SystemTime::now());
 
-        // Build unchanged monitors list from monitor_states
-        let unchanged_data: Vec<(String, String)> = {
-            let ms = state.monitor_states.lock().unwrap();
-            let group_monitor_ids: std::collections::HashSet<i32> =
-                group.iter().map(|ss| ss.monitor_index).collect();
-            ms.iter()
-                .filter(|(id, _)| !group_monitor_ids.contains(&(**id as i32)))
-                .filter(|(_, s)| !s.last_summary.is_empty())
-                .map(|(_, s)| (s.name.clone(), s.last_summary.clone()))
-                .collect()
-        };
-        let unchanged: Vec<crate::ai::UnchangedMonitor<'_>> = unchanged_data.iter()
-            .map(|(name, summary)| crate::ai::UnchangedMonitor {
-                monitor_name: name.as_str(),
-                summary: summary.as_str(),
-            })
-            .collect();
+    let provider_name = provider.clone();
+    let model = analysis_model(&state.db, &provider_name)?;
+    let provider = analysis::provider_from_settings(&state.db, &provider)
+        .inspect_err(|e| state.record_error(session_id, &provider_name, e))?;
 
-        let contexts_vec: Vec<String> = recent_contexts.iter().cloned().collect();
+    claim.set_session(session_id);
+    state.cancel_analysis.store(false, Ordering::Relaxed);
 
-        let result = if provider == "ollama" {
-            let model = state.db.get_setting("ollama_model")
-                .map_err(|e| e.to_string())?
-                .unwrap_or_else(|| "qwen3-vl:8b".to_string());
-            crate::ai::analyze_capture_ollama(
-                &client, &model, &changed, &unchanged,
-                &contexts_vec, session_description, &image_mode,
-            ).await
-        } else {
-            let api_key = state.db.get_setting("ai_api_key")
-                .map_err(|e| e.to_string())?
-                .ok_or_else(|| "No API key configured".to_string())?;
-            crate::ai::analyze_capture(
-                &client, &api_key, &changed, &unchanged,
-                &contexts_vec, session_description, &image_mode,
-            ).await
-        };
+    let screenshots_dir = state.screenshots_dir();
+    let on_progress = |progress: &AnalysisProgress| {
+        if progress.new_task {
+            let _ = app_handle.emit("task-created", progress);
+        }
+        let _ = app_handle.emit("analysis-progress", progress);
+    };
+    let on_error = |e: &crate::ai::AiError| state.record_error(session_id, &provider_name, &e.to_string());
+    let opts = analysis::AnalysisOptions {
+        session_id,
+        session_description,
+        image_mode: &image_mode,
+        sampling,
+        screenshots_dir: &screenshots_dir,
+        templates: crate::ai::PromptTemplates {
+            single: template_single.as_deref(),
+            multi: template_multi.as_deref(),
+        },
+        monitor_scope,
+        custom_fields: &custom_fields,
+        language: language.as_deref().map(str::trim),
+        include_unchanged_context,
+        context_depth,
+        include_session_task_list,
+        consolidate_every,
+        embedder: embedder.as_ref().map(|e| e as &dyn analysis::Embedder),
+        similarity_threshold,
+        on_progress: Some(&on_progress),
+        on_error: Some(&on_error),
+        carry_context_window,
+        save_inputs: save_inputs.then_some(analysis::SaveInputs {
+            data_dir: &state.app_data_dir,
+            run_id: &run_id,
+        }),
+        keep_images,
+        context_overlap,
+        context_before,
+    };
+    let started = Instant::now();
+    let (run, resources) = telemetry::sampled(
+        load_sampler(state, &provider_name),
+        telemetry::SAMPLE_INTERVAL,
+        analysis::run_analysis(
+            &state.db,
+            provider.as_ref(),
+            screenshots,
+            &state.monitor_states,
+            &state.cancel_analysis,
+            &opts,
+        ),
+    ).await;
 
-        match result {
-            Ok(analysis) => {
-                if analysis.is_new_task {
-                    let ts = &group[0].captured_at;
-                    match state.db.insert_full_task(
-                        &analysis.task_title,
-                        &analysis.task_description,
-                        &analysis.category,
-                        ts,
-                        &analysis.reasoning,
-                    ) {
-                        Ok(task_id) => {
-                            for ss in group {
-                                let _ = state.db.link_screenshot_to_task(task_id, ss.id);
-                            }
-                        }
-                        Err(e) => error!("Failed to insert task: {}", e),
-                    }
-                } else {
-                    // Link to most recent task
-                    if let Ok(tasks) = state.db.get_tasks(1, 0) {
-                        if let Some(task) = tasks.first() {
-                            for ss in group {
-                                let _ = state.db.link_screenshot_to_task(task.id, ss.id);
-                            }
-                        }
-                    }
-                }
+    if let Some(sid) = session_id {
+        let delta = SessionStats {
+            groups_analyzed: run.processed as u64,
+            screenshots_linked_without_ai: run.linked_without_ai as u64,
+            analysis_resources: resources.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = state.db.add_session_stats(sid, &delta) {
+            error!("Failed to save analysis stats for session {}: {}", sid, e);
+        }
+    }
+    if run.processed > 0 {
+        lock_recovered(&state.last_analysis_success)
+            .insert(provider_name.clone(), time::format_for_db(SystemTime::now()));
+        let ms_per_group = started.elapsed().as_millis() as f64 / run.processed as f64;
+        if let Err(e) = state.db.record_analysis_latency(&provider_name, &model, ms_per_group) {
+            error!("Failed to record analysis latency: {}", e);
+        }
+    }
+    info!("Analyzed {} capture groups", run.processed);
+    let _ = app_handle.emit("analysis-complete", AnalysisComplete {
+        session_id,
+        processed: run.processed,
+        failed_links: run.failed_links,
+        resources,
+    });
+    Ok(run.processed)
+}
 
-                // Update monitor_states with returned summaries
-                if !analysis.monitor_summaries.is_empty() {
-                    let mut ms = state.monitor_states.lock().unwrap();
-                    for (name, summary) in &analysis.monitor_summaries {
-                        // Find the monitor state by name and update its summary
-                        for (_, monitor_state) in ms.iter_mut() {
-                            if monitor_state.name == *name {
-                                monitor_state.last_summary = summary.clone();
-                            }
-                        }
-                    }
-                }
+/// Load sampler for an analysis run with `provider`. Cloud providers don't load this
+/// machine, so they get none.
+fn load_sampler(state: &AppState, provider: &str) -> Option<Box<dyn telemetry::LoadSampler>> {
+    if provider != "ollama" {
+        return None;
+    }
+    Some(Box::new(telemetry::SysinfoSampler::new(state.ollama_process.pid())))
+}
 
-                let new_ctx = format!("{}: {}", analysis.task_title, analysis.task_description);
-                recent_contexts.push_front(new_ctx);
-                if recent_contexts.len() > 2 {
-                    recent_contexts.pop_back();
-                }
+/// Current machine load for the status bar: system CPU and memory, this app, and
+/// the managed Ollama process.
+#[tauri::command]
+pub async fn get_system_load(state: State<'_, Arc<AppState>>) -> Result<SystemLoad, String> {
+    let ollama_pid = state.ollama_process.pid();
+    tauri::async_runtime::spawn_blocking(move || {
+        use telemetry::LoadSampler;
+        let mut sampler = telemetry::SysinfoSampler::new(ollama_pid);
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        sampler.sample()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
 
-                processed += 1;
-            }
-            Err(e) => {
-                error!("AI analysis failed for capture group: {}", e);
-            }
-        }
+/// Model name the configured provider analyzes with, for latency history and pricing.
+fn analysis_model(db: &Database, provider: &str) -> Result<String, String> {
+    if provider == "ollama" {
+        db.get_setting_string("ollama_model", "").map_err(|e| e.to_string())
+    } else {
+        Ok(crate::ai::CLAUDE_MODEL.to_string())
     }
+}
+
+/// Projected token use and cost of `analyze_session(session_id)` with the current
+/// provider, to confirm before spending money.
+#[tauri::command]
+pub fn estimate_analysis_cost(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<AnalysisCostEstimate, String> {
+    let provider = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let model = analysis_model(&state.db, &provider)?;
+    let sampling = state.db.get_setting_u64("analysis_sampling", 1).map_err(|e| e.to_string())? as usize;
+    let tokens_per_call = state.db.get_setting_u64("analysis_tokens_per_call", 2300).map_err(|e| e.to_string())? as i64;
+    let pending = state.db.count_session_pending_groups(session_id).map_err(|e| e.to_string())?;
+    Ok(estimate::session_cost(session_id, &provider, &model, pending.groups, sampling, tokens_per_call))
+}
 
-    state.analyzing.store(false, Ordering::Relaxed);
-    state.analyzing_session_id.store(0, Ordering::Relaxed);
-    info!("Analyzed {} capture groups", processed);
-    Ok(processed)
+/// Rough time and cost of "Analyze all pending" with the current provider, from
+/// recent runs' latency. Unknown parts are null rather than guessed.
+#[tauri::command]
+pub fn estimate_pending_analysis(state: State<'_, Arc<AppState>>) -> Result<AnalysisEstimate, String> {
+    let provider = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let model = analysis_model(&state.db, &provider)?;
+    let sampling = state.db.get_setting_u64("analysis_sampling", 1).map_err(|e| e.to_string())? as usize;
+    let pending = state.db.count_pending_groups().map_err(|e| e.to_string())?;
+    let history = state.db.get_analysis_latencies(&provider, &model).map_err(|e| e.to_string())?;
+    Ok(estimate::estimate(&provider, &model, pending, sampling, &history))
 }
 
 /// Core analysis logic for all unanalyzed screenshots globally.
-async fn run_pending_analysis(state: &AppState, limit: i64) -> Result<u32, String> {
+async fn run_pending_analysis(app_handle: &tauri::AppHandle, state: &AppState, limit: i64, include_unassigned: bool) -> Result<u32, String> {
+    // Claimed before fetching, so a run that finishes meanwhile can't hand us its groups
+    let claim = state.claim_analysis(None)?;
     let fetch_limit = if limit > 0 { limit } else { i64::MAX };
-    let screenshots = state.db.get_unanalyzed_screenshots(fetch_limit)
+    let screenshots = state.db.get_unanalyzed_screenshots(fetch_limit, include_unassigned)
         .map_err(|e| e.to_string())?;
 
     let session_id: Option<i64> = screenshots.first()
@@ -656,11 +2315,24 @@ async fn run_pending_analysis(state: &AppState, limit: i64) -> Result<u32, Strin
         .and_then(|sid| state.db.get_session(sid).ok())
         .and_then(|session| session.description);
 
-    analyze_screenshots(state, &screenshots, session_id, session_description.as_deref()).await
+    analyze_screenshots(app_handle, state, &claim, &screenshots, session_id, session_description.as_deref(), None).await
 }
 
 /// Session-scoped analysis: process unanalyzed screenshots for a specific session.
-async fn run_session_analysis(state: &AppState, session_id: i64, limit: i64) -> Result<u32, String> {
+async fn run_session_analysis(app_handle: &tauri::AppHandle, state: &AppState, session_id: i64, limit: i64) -> Result<u32, String> {
+    let claim = state.claim_analysis(Some(session_id))?;
+    run_claimed_session_analysis(app_handle, state, &claim, session_id, limit).await
+}
+
+/// `run_session_analysis` for callers that claimed the analysis status themselves,
+/// to change what is unanalyzed first.
+async fn run_claimed_session_analysis(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    claim: &AnalysisClaim<'_>,
+    session_id: i64,
+    limit: i64,
+) -> Result<u32, String> {
     let fetch_limit = if limit > 0 { limit } else { i64::MAX };
     let screenshots = state.db.get_unanalyzed_screenshots_for_session(session_id, fetch_limit)
         .map_err(|e| e.to_string())?;
@@ -669,26 +2341,159 @@ async fn run_session_analysis(state: &AppState, session_id: i64, limit: i64) ->
         .ok()
         .and_then(|s| s.description);
 
-    analyze_screenshots(state, &screenshots, Some(session_id), session_description.as_deref()).await
+    analyze_screenshots(app_handle, state, claim, &screenshots, Some(session_id), session_description.as_deref(), None).await
 }
 
+/// Analyze every unanalyzed screenshot. Screenshots with no session are analyzed without
+/// any session context, so they're only included with `include_unassigned`.
 #[tauri::command]
-pub async fn analyze_pending(state: State<'_, Arc<AppState>>) -> Result<u32, String> {
-    run_pending_analysis(&state, 0).await
+pub async fn analyze_pending(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>, include_unassigned: Option<bool>) -> Result<u32, String> {
+    run_pending_analysis(&app_handle, &state, 0, include_unassigned.unwrap_or(false)).await
 }
 
+/// Analyze only the unanalyzed screenshots that belong to no session, without any
+/// session context: the "Unassigned" bucket's button.
 #[tauri::command]
-pub async fn analyze_session(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<u32, String> {
-    run_session_analysis(&state, session_id, 0).await
+pub async fn analyze_unassigned(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<u32, String> {
+    let claim = state.claim_analysis(None)?;
+    let screenshots = state.db.get_unassigned_unanalyzed_screenshots(i64::MAX)
+        .map_err(|e| e.to_string())?;
+    analyze_screenshots(&app_handle, &state, &claim, &screenshots, None, None, None).await
+}
+
+/// Count and date range of unanalyzed screenshots that belong to no session: the
+/// "Unassigned" bucket shown with the pending sessions.
+#[tauri::command]
+pub fn get_unassigned_summary(state: State<'_, Arc<AppState>>) -> Result<UnassignedSummary, String> {
+    state.db.get_unassigned_summary().map_err(|e| e.to_string())
+}
+
+/// Move sessionless screenshots into an existing session so they're analyzed with its
+/// context. Screenshots that already belong to a session are left alone. Returns how
+/// many were assigned.
+#[tauri::command]
+pub fn assign_screenshots_to_session(state: State<'_, Arc<AppState>>, screenshot_ids: Vec<i64>, session_id: i64) -> Result<u32, String> {
+    match state.db.assign_screenshots_to_session(&screenshot_ids, session_id) {
+        Ok(assigned) => {
+            info!("Assigned {} of {} screenshot(s) to session {}", assigned, screenshot_ids.len(), session_id);
+            Ok(assigned as u32)
+        }
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err(format!("Session {} not found", session_id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn analyze_session(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>, session_id: i64) -> Result<u32, String> {
+    run_session_analysis(&app_handle, &state, session_id, 0).await
+}
+
+/// Analyze what the live capture session has accumulated so far, without stopping it.
+/// Returns the number of screenshots processed.
+#[tauri::command]
+pub async fn analyze_current_session(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<u32, String> {
+    analyze_current(&app_handle, &state).await
+}
+
+/// Shared by `analyze_current_session` and the tray's "Analyze Now".
+pub async fn analyze_current(app_handle: &tauri::AppHandle, state: &AppState) -> Result<u32, String> {
+    let session_id = current_session_to_analyze(
+        state.capturing.load(Ordering::Relaxed),
+        state.current_session_id.load(Ordering::Relaxed),
+    )?;
+    info!("Analyzing current session {} on request", session_id);
+    run_session_analysis(app_handle, state, session_id, 0).await
+}
+
+/// The live session to analyze: errors when nothing is being captured. A run already
+/// analyzing it is refused when `run_session_analysis` claims the analysis status.
+fn current_session_to_analyze(capturing: bool, current_session_id: i64) -> Result<i64, String> {
+    if !capturing || current_session_id <= 0 {
+        return Err("No capture session is active".to_string());
+    }
+    Ok(current_session_id)
+}
+
+/// Re-analyze a session's screenshots that belong to tasks the user hasn't verified:
+/// unlinks them, deletes the unverified tasks left empty, and runs session analysis
+/// on the freed screenshots. Verified tasks are left untouched.
+#[tauri::command]
+pub async fn reanalyze_unverified(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>, session_id: i64) -> Result<u32, String> {
+    let claim = state.claim_analysis(Some(session_id))?;
+    let unlinked = state.db.unlink_unverified_tasks(session_id)
+        .map_err(|e| e.to_string())?;
+    info!("Unlinked {} screenshot(s) from unverified tasks in session {}", unlinked, session_id);
+    run_claimed_session_analysis(&app_handle, &state, &claim, session_id, 0).await
+}
+
+/// Re-analyze one capture group whose classification was wrong: unlinks its
+/// screenshots (deleting their task if that leaves it empty and unverified), then
+/// analyzes the group alone with the context it originally had, the session's tasks
+/// started before it. Refused while any analysis runs, since runs share the analysis
+/// status, and when the group's task is user-verified unless `force`.
+#[tauri::command]
+pub async fn reanalyze_capture_group(
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    capture_group: String,
+    force: Option<bool>,
+) -> Result<GroupReanalysis, String> {
+    let screenshots = state.db.get_capture_group(&capture_group).map_err(|e| e.to_string())?;
+    let Some(first) = screenshots.first() else {
+        return Err(format!("Capture group {} not found", capture_group));
+    };
+    let session_id = state.db.get_screenshot_session_id(first.id).map_err(|e| e.to_string())?;
+    // Claim first so no analysis run relinks the group between the verified check and the unlink
+    let claim = state.claim_analysis(session_id)?;
+    let mut old_tasks = Vec::new();
+    for ss in &screenshots {
+        if let Some(task) = state.db.get_task_for_screenshot(ss.id).map_err(|e| e.to_string())? {
+            old_tasks.push(task);
+        }
+    }
+    refuse_verified(&old_tasks, force.unwrap_or(false), "re-analyze it")?;
+
+    let old_links = state.db.unlink_capture_group(&capture_group).map_err(|e| e.to_string())?;
+    info!("Unlinked {} screenshot(s) of capture group {} for re-analysis", old_links.len(), capture_group);
+    let session_description = session_id
+        .and_then(|sid| state.db.get_session(sid).ok())
+        .and_then(|session| session.description);
+    let result = analyze_screenshots(&app_handle, &state, &claim, &screenshots, session_id, session_description.as_deref(), Some(&first.captured_at)).await;
+
+    // The old task is only given up once the group has a new one
+    let processed = match result {
+        Ok(processed) if processed > 0 => processed,
+        failed => {
+            match state.db.relink_screenshots(&old_links) {
+                Ok(restored) => info!("Restored {} link(s) of capture group {} after a failed re-analysis", restored, capture_group),
+                Err(e) => error!("Failed to restore the links of capture group {}: {}", capture_group, e),
+            }
+            return Err(failed.err().unwrap_or_else(|| format!("Capture group {} was not re-analyzed; its task is unchanged", capture_group)));
+        }
+    };
+    let mut old_task_ids: Vec<i64> = old_links.iter().map(|&(_, task_id)| task_id).collect();
+    old_task_ids.sort_unstable();
+    old_task_ids.dedup();
+    match state.db.delete_orphaned_tasks(&old_task_ids) {
+        Ok(deleted) => debug!("Re-analyzed {} screenshot(s) of capture group {}; deleted {} emptied task(s)", processed, capture_group, deleted),
+        Err(e) => error!("Failed to delete the old tasks of capture group {}: {}", capture_group, e),
+    }
+
+    let new_task_id = state.db.get_task_for_screenshot(first.id).map_err(|e| e.to_string())?.map(|t| t.id);
+    Ok(GroupReanalysis {
+        capture_group,
+        old_task_id: old_tasks.first().map(|t| t.id),
+        new_task_id,
+    })
 }
 
 #[tauri::command]
-pub async fn analyze_all_pending(state: State<'_, Arc<AppState>>) -> Result<u32, String> {
+pub async fn analyze_all_pending(app_handle: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<u32, String> {
     let pending = state.db.get_pending_sessions(100, 0)
         .map_err(|e| e.to_string())?;
     let mut total = 0u32;
     for session in &pending {
-        match run_session_analysis(&state, session.id, 0).await {
+        match run_session_analysis(&app_handle, &state, session.id, 0).await {
             Ok(n) => total += n,
             Err(e) => {
                 error!("Analysis failed for session {}: {}", session.id, e);
@@ -723,24 +2528,220 @@ pub fn get_completed_sessions(
         .map_err(|e| e.to_string())
 }
 
+/// Whether a session is active, pending analysis, completed or empty, by the same rule
+/// as `get_pending_sessions` and `get_completed_sessions`.
+#[tauri::command]
+pub fn get_session_status(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<SessionStatus, String> {
+    match state.db.get_session_status(session_id) {
+        Ok(status) => Ok(status),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err(format!("Session {} not found", session_id)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Up to three descriptions for a new session, continuing the latest completed one: asked
+/// of the configured provider from that session's title, description and tasks. Without a
+/// usable provider, the previous session's description (else title) is the only suggestion.
+/// Empty when no session has completed yet. Answers are cached for a few minutes.
+#[tauri::command]
+pub async fn suggest_session_description(state: State<'_, Arc<AppState>>) -> Result<Vec<String>, String> {
+    let Some(previous) = state.db.get_completed_sessions(1, 0).map_err(|e| e.to_string())?.into_iter().next() else {
+        return Ok(Vec::new());
+    };
+    if let Some(cached) = cached_suggestions(&lock_recovered(&state.description_suggestions), previous.id, Instant::now()) {
+        return Ok(cached);
+    }
+    let tasks = state.db.get_session_tasks(previous.id).map_err(|e| e.to_string())?;
+    let provider_name = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let provider = match analysis::provider_from_settings(&state.db, &provider_name) {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            debug!("No provider for description suggestions: {}", e);
+            None
+        }
+    };
+    let suggestions = description_suggestions(provider.as_deref(), &previous, &tasks).await;
+    *lock_recovered(&state.description_suggestions) = Some(CachedSuggestions {
+        at: Instant::now(),
+        session_id: previous.id,
+        suggestions: suggestions.clone(),
+    });
+    Ok(suggestions)
+}
+
+/// `suggest_session_description` for `previous` and its `tasks`: the provider's
+/// suggestions, or `previous`'s own description (else title) verbatim when there's no
+/// provider or it fails or times out.
+async fn description_suggestions(provider: Option<&dyn analysis::Provider>, previous: &CaptureSession, tasks: &[Task]) -> Vec<String> {
+    if let Some(provider) = provider {
+        let lines: Vec<String> = tasks
+            .iter()
+            .map(|t| match t.description.as_deref().filter(|d| !d.is_empty()) {
+                Some(description) => format!("{}: {}", t.title, description),
+                None => t.title.clone(),
+            })
+            .collect();
+        let request = crate::ai::PreviousSession {
+            title: previous.title.as_deref(),
+            description: previous.description.as_deref(),
+            tasks: &lines,
+        };
+        match tokio::time::timeout(SUGGESTION_TIMEOUT, provider.suggest_descriptions(request)).await {
+            Ok(Ok(suggestions)) => return suggestions,
+            Ok(Err(e)) => warn!("Description suggestions failed, using the previous session's: {}", e),
+            Err(_) => warn!("Description suggestions timed out, using the previous session's"),
+        }
+    }
+    [&previous.description, &previous.title]
+        .into_iter()
+        .filter_map(|text| text.as_deref().map(str::trim).filter(|t| !t.is_empty()))
+        .take(1)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Delete a session, moving it to the trash (see `restore_from_trash`) unless
+/// `permanent`. Returns the number of screenshots it held.
+#[tauri::command]
+pub fn delete_session(state: State<'_, Arc<AppState>>, session_id: i64, permanent: Option<bool>) -> Result<u32, String> {
+    let screenshots_dir = state.screenshots_dir();
+    if permanent.unwrap_or(false) {
+        let deleted = state.db.delete_session(session_id)
+            .map_err(|e| e.to_string())?;
+        remove_screenshot_files(&screenshots_dir, &deleted);
+        info!("Deleted session {} ({} screenshots removed)", session_id, deleted.len());
+        return Ok(deleted.len() as u32);
+    }
+
+    let (ids, paths): (Vec<_>, Vec<_>) = state.db.trash_session(session_id, &time::format_for_db(SystemTime::now()))
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .unzip();
+    trash::move_to_trash(&screenshots_dir, &paths);
+    analysis::remove_kept_images(&screenshots_dir, &ids);
+    info!("Moved session {} to the trash ({} screenshots)", session_id, paths.len());
+    Ok(paths.len() as u32)
+}
+
+/// Delete one screenshot, moving it to the trash unless `permanent`.
 #[tauri::command]
-pub fn delete_session(state: State<'_, Arc<AppState>>, session_id: i64) -> Result<u32, String> {
-    let paths = state.db.delete_session(session_id)
+pub fn delete_screenshot(state: State<'_, Arc<AppState>>, screenshot_id: i64, permanent: Option<bool>) -> Result<(), String> {
+    let screenshots_dir = state.screenshots_dir();
+    if permanent.unwrap_or(false) {
+        let path = state.db.delete_screenshot(screenshot_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Screenshot {} not found", screenshot_id))?;
+        remove_screenshot_files(&screenshots_dir, &[(screenshot_id, path)]);
+        info!("Deleted screenshot {}", screenshot_id);
+        return Ok(());
+    }
+
+    let paths = state.db.trash_screenshots(&[screenshot_id], &time::format_for_db(SystemTime::now()))
         .map_err(|e| e.to_string())?;
-    let count = paths.len() as u32;
+    if paths.is_empty() {
+        return Err(format!("Screenshot {} not found", screenshot_id));
+    }
+    trash::move_to_trash(&screenshots_dir, &paths);
+    analysis::remove_kept_images(&screenshots_dir, &[screenshot_id]);
+    info!("Moved screenshot {} to the trash", screenshot_id);
+    Ok(())
+}
+
+/// Bring a trashed session or screenshot back, with its files, task links and labels.
+/// Restoring a screenshot whose session is also in the trash restores the session.
+/// Returns the number of screenshots restored.
+#[tauri::command]
+pub fn restore_from_trash(state: State<'_, Arc<AppState>>, kind: TrashKind, trash_id: i64) -> Result<u32, String> {
+    let paths = state.db.restore_from_trash(kind, trash_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Trash entry {} not found", trash_id))?;
+    trash::restore_files(&state.screenshots_dir(), &paths);
+    info!("Restored {:?} {} from the trash ({} screenshots)", kind, trash_id, paths.len());
+    Ok(paths.len() as u32)
+}
+
+#[tauri::command]
+pub fn get_trash(state: State<'_, Arc<AppState>>) -> Result<Vec<TrashEntry>, String> {
+    state.db.list_trash().map_err(|e| e.to_string())
+}
+
+/// Permanently delete trash older than `trash_retention_days`, or all of it with `all`.
+/// Returns the number of screenshots purged.
+#[tauri::command]
+pub fn purge_trash(state: State<'_, Arc<AppState>>, all: Option<bool>) -> Result<u32, String> {
+    let purged = if all.unwrap_or(false) {
+        purge_trash_before(&state.db, &state.screenshots_dir(), None)
+    } else {
+        sweep_trash(&state.db, &state.screenshots_dir())
+    }?;
+    Ok(purged as u32)
+}
 
+/// Purge trash older than `trash_retention_days`. Runs at startup and from `purge_trash`.
+pub fn sweep_trash(db: &Database, screenshots_dir: &Path) -> Result<usize, String> {
+    let days = db.get_setting_u64("trash_retention_days", 30).map_err(|e| e.to_string())?;
+    purge_trash_before(db, screenshots_dir, Some(&retention_cutoff(db, days)))
+}
+
+/// Delete `save_analysis_inputs` records and images, and `keep_analysis_images` images,
+/// older than `analysis_debug_retention_days`. Runs at startup alongside `sweep_trash`.
+pub fn sweep_analysis_debug(db: &Database, app_data_dir: &Path, screenshots_dir: &Path) -> Result<usize, String> {
+    let days = db.get_setting_u64("analysis_debug_retention_days", 7).map_err(|e| e.to_string())?;
+    let cutoff = retention_cutoff(db, days);
+    if let Some(cutoff) = time::parse_timestamp(&cutoff) {
+        let kept = analysis::sweep_kept_images(screenshots_dir, cutoff);
+        if kept > 0 {
+            info!("Removed {} expired kept analysis image(s)", kept);
+        }
+    }
+    let paths = db.purge_analysis_debug_before(&cutoff).map_err(|e| e.to_string())?;
     for rel_path in &paths {
-        let filename = rel_path
-            .strip_prefix("screenshots/")
-            .unwrap_or(rel_path);
-        let full_path = state.screenshots_dir.join(filename);
+        let full_path = app_data_dir.join(rel_path);
+        if let Err(e) = std::fs::remove_file(&full_path) {
+            debug!("Could not remove file {}: {}", full_path.display(), e);
+        }
+        // The run's directory goes with its last image
+        if let Some(run_dir) = full_path.parent() {
+            let _ = std::fs::remove_dir(run_dir);
+        }
+    }
+    if !paths.is_empty() {
+        info!("Removed {} expired analysis debug image(s)", paths.len());
+    }
+    Ok(paths.len())
+}
+
+fn purge_trash_before(db: &Database, screenshots_dir: &Path, cutoff: Option<&str>) -> Result<usize, String> {
+    let paths = db.purge_trash(cutoff).map_err(|e| e.to_string())?;
+    trash::remove_files(screenshots_dir, &paths);
+    if !paths.is_empty() {
+        info!("Purged {} screenshot(s) from the trash", paths.len());
+    }
+    Ok(paths.len())
+}
+
+/// Delete the files of permanently deleted screenshots, by id and filepath, with any
+/// kept analysis images; missing files are ignored.
+fn remove_screenshot_files(screenshots_dir: &Path, screenshots: &[(i64, String)]) {
+    for (_, rel_path) in screenshots {
+        let full_path = trash::live_path(screenshots_dir, rel_path);
         if let Err(e) = std::fs::remove_file(&full_path) {
             debug!("Could not remove file {}: {}", full_path.display(), e);
         }
     }
+    let ids: Vec<i64> = screenshots.iter().map(|(id, _)| *id).collect();
+    analysis::remove_kept_images(screenshots_dir, &ids);
+}
 
-    info!("Deleted session {} ({} screenshots removed)", session_id, count);
-    Ok(count)
+/// Move sessionless screenshots from pre-session builds into per-day "Imported" sessions.
+/// Also runs once automatically at startup; calling it again picks up any stragglers.
+#[tauri::command]
+pub fn reconcile_legacy_screenshots(state: State<'_, Arc<AppState>>) -> Result<Vec<CaptureSession>, String> {
+    let ids = state.db.reconcile_legacy_screenshots().map_err(|e| e.to_string())?;
+    info!("Reconciled legacy screenshots into {} imported session(s)", ids.len());
+    ids.into_iter()
+        .map(|id| state.db.get_session(id).map_err(|e| e.to_string()))
+        .collect()
 }
 
 #[tauri::command]
@@ -753,30 +2754,274 @@ pub fn get_analysis_status(state: State<'_, Arc<AppState>>) -> AnalysisStatus {
     }
 }
 
+/// Recent analysis failures (e.g. an invalid API key, Ollama not running), newest first.
+#[tauri::command]
+pub fn get_recent_errors(state: State<'_, Arc<AppState>>) -> Vec<RecentError> {
+    lock_recovered(&state.recent_errors).iter().rev().cloned().collect()
+}
+
 #[tauri::command]
 pub fn cancel_analysis(state: State<'_, Arc<AppState>>) {
     info!("Cancelling analysis");
     state.cancel_analysis.store(true, Ordering::Relaxed);
 }
 
+/// Remove unanalyzed screenshots, moving them to the trash unless `permanent`. Only
+/// `session_id` if given, else every session; the session being captured is left alone
+/// unless `exclude_active` is false. Returns counts per session.
+#[tauri::command]
+pub fn clear_pending(
+    state: State<'_, Arc<AppState>>,
+    session_id: Option<i64>,
+    exclude_active: Option<bool>,
+    permanent: Option<bool>,
+) -> Result<Vec<PendingCleared>, String> {
+    let active = state.current_session_id.load(Ordering::Relaxed);
+    let exclude = (exclude_active.unwrap_or(true) && active > 0).then_some(active);
+    let screenshots_dir = state.screenshots_dir();
+    let sessions: Vec<Option<i64>> = if permanent.unwrap_or(false) {
+        let deleted = state.db.delete_unanalyzed_screenshots_for_session(session_id, exclude)
+            .map_err(|e| e.to_string())?;
+        let (sessions, files): (Vec<_>, Vec<_>) = deleted.into_iter().map(|(id, session, path)| (session, (id, path))).unzip();
+        remove_screenshot_files(&screenshots_dir, &files);
+        sessions
+    } else {
+        let (ids, sessions): (Vec<_>, Vec<_>) = state.db.get_unanalyzed_screenshot_ids(session_id, exclude)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .unzip();
+        let paths = state.db.trash_screenshots(&ids, &time::format_for_db(SystemTime::now()))
+            .map_err(|e| e.to_string())?;
+        trash::move_to_trash(&screenshots_dir, &paths);
+        analysis::remove_kept_images(&screenshots_dir, &ids);
+        sessions
+    };
+
+    info!("Cleared {} pending screenshots", sessions.len());
+    Ok(count_by_session(&sessions))
+}
+
+/// Tally screenshots per session, in order of first appearance.
+fn count_by_session(sessions: &[Option<i64>]) -> Vec<PendingCleared> {
+    let mut counts: Vec<PendingCleared> = Vec::new();
+    for &session_id in sessions {
+        match counts.iter_mut().find(|c| c.session_id == session_id) {
+            Some(entry) => entry.count += 1,
+            None => counts.push(PendingCleared { session_id, count: 1 }),
+        }
+    }
+    counts
+}
+
+/// Link unanalyzed screenshots to a catch-all task instead of deleting them,
+/// so the session completes without any AI calls. Without a session_id,
+/// one task is created per session. The session being captured is left alone.
 #[tauri::command]
-pub fn clear_pending(state: State<'_, Arc<AppState>>) -> Result<u32, String> {
-    let paths = state.db.delete_unanalyzed_screenshots()
+pub fn archive_pending_as_task(
+    state: State<'_, Arc<AppState>>,
+    session_id: Option<i64>,
+    title: String,
+) -> Result<Vec<ArchivedTask>, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Task title must not be empty".to_string());
+    }
+    let active = state.current_session_id.load(Ordering::Relaxed);
+    if active > 0 && session_id == Some(active) {
+        return Err("Stop capture before archiving the session being captured".to_string());
+    }
+    let archived = state.db.archive_unanalyzed_screenshots(session_id, (active > 0).then_some(active), title)
         .map_err(|e| e.to_string())?;
-    let count = paths.len() as u32;
+    let total: u32 = archived.iter().map(|a| a.screenshot_count).sum();
+    info!("Archived {} pending screenshots into {} task(s)", total, archived.len());
+    Ok(archived)
+}
 
-    for rel_path in &paths {
-        let filename = rel_path
-            .strip_prefix("screenshots/")
-            .unwrap_or(rel_path);
-        let full_path = state.screenshots_dir.join(filename);
-        if let Err(e) = std::fs::remove_file(&full_path) {
-            debug!("Could not remove file {}: {}", full_path.display(), e);
+/// Where `run_diagnostics` writes its test capture (under the app data dir); deleted before returning.
+const DIAGNOSTIC_CAPTURE: &str = "diagnostic_capture.webp";
+
+/// Zip a session's screenshot files to `path`, optionally with a `metadata.json` of
+/// the session, its tasks and per-file info. Refuses the session being captured and
+/// destinations inside the screenshots directory. Emits `archive-progress`.
+#[tauri::command]
+pub async fn export_session_archive(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    session_id: i64,
+    path: String,
+    include_metadata: bool,
+) -> Result<SessionArchive, String> {
+    if state.capturing.load(Ordering::Relaxed) && state.current_session_id.load(Ordering::Relaxed) == session_id {
+        return Err("Stop capture before exporting the session being captured".to_string());
+    }
+    let app_state = Arc::clone(&state);
+    let dest = PathBuf::from(&path);
+    let summary = tokio::task::spawn_blocking(move || {
+        archive::export_session_archive(
+            &app_state.db,
+            session_id,
+            &app_state.screenshots_dir(),
+            &dest,
+            include_metadata,
+            |done, total| {
+                app_state.emit_coalesced(&app, "archive-progress", ExportProgress { session_id, done, total }, done == total);
+            },
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    info!(
+        "Exported session {} to {} ({} files, {} missing)",
+        session_id, path, summary.files_added, summary.missing_files.len()
+    );
+    Ok(summary)
+}
+
+/// Render a session's screenshots as a timelapse at `fps`: `.gif` is encoded
+/// in-process, `.webp` (animated) and `.mp4` need ffmpeg on PATH. Mixed resolutions
+/// are letterboxed to a common size. Emits `timelapse-progress` per frame and returns
+/// the number of frames written.
+#[tauri::command]
+pub async fn generate_timelapse(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    session_id: i64,
+    fps: u32,
+    output_path: String,
+) -> Result<usize, String> {
+    let screenshots = state.db.get_session_screenshots(session_id).map_err(|e| e.to_string())?;
+    let screenshots = timelapse::order_frames(screenshots);
+    let screenshots_dir = state.screenshots_dir();
+    let output = PathBuf::from(&output_path);
+    let app_state = Arc::clone(&state);
+
+    let frames = tokio::task::spawn_blocking(move || {
+        timelapse::generate(&screenshots, &screenshots_dir, fps, &output, |done, total| {
+            app_state.emit_coalesced(&app, "timelapse-progress", ExportProgress { session_id, done, total }, done == total);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+    info!("Wrote {}-frame timelapse of session {} to {}", frames, session_id, output_path);
+    Ok(frames)
+}
+
+/// One-click "is it working?" check: capture the primary monitor, save it and decode it
+/// back as WebP, check the configured AI provider, and analyze the capture, then report
+/// whether other users can read the database or screenshots. Nothing is written to the
+/// database and the test capture is deleted afterwards.
+#[tauri::command]
+pub async fn run_diagnostics(state: State<'_, Arc<AppState>>) -> Result<DiagnosticsReport, String> {
+    let mut report = diagnostics::ReportBuilder::default();
+    let path = state.app_data_dir.join(DIAGNOSTIC_CAPTURE);
+
+    let captured = match capture::capture_monitors("default", None, None, None, &[]) {
+        Ok(mut monitors) if !monitors.is_empty() => {
+            let c = monitors.swap_remove(0);
+            report.record("capture", Ok(format!(
+                "Captured {} ({}x{})", c.monitor_name, c.image.width(), c.image.height()
+            )));
+            Some(c)
+        }
+        Ok(_) => {
+            report.record("capture", Err("No monitor was captured".to_string()));
+            None
         }
+        Err(e) => {
+            report.record("capture", Err(e.to_string()));
+            None
+        }
+    };
+
+    match &captured {
+        Some(c) => report.record("save", save_and_verify_webp(&c.image, &path)),
+        None => report.skip("save"),
+    }
+
+    // Independent of the capture, so checked even if it failed
+    let provider_name = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let health = if provider_name == "ollama" {
+        ollama_health(&state, true).await
+    } else {
+        claude_health(&state, true).await
+    }?;
+    report.record("provider", health_summary(&health));
+
+    match &captured {
+        Some(c) if !report.failed() => {
+            let result = diagnostic_analysis(&state.db, &provider_name, c, &path).await;
+            report.record("analysis", result);
+        }
+        _ => report.skip("analysis"),
     }
 
-    info!("Cleared {} pending screenshots", count);
-    Ok(count)
+    // Last, so a permissions or time zone problem doesn't keep the analysis step from running
+    report.record(
+        "permissions",
+        crate::permissions::describe(&state.app_data_dir.join(crate::DB_FILE), &state.screenshots_dir()),
+    );
+    report.record("time zone", time_zone_summary(&state.db));
+
+    if let Err(e) = std::fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove diagnostic capture {}: {}", path.display(), e);
+        }
+    }
+
+    let report = report.finish();
+    info!("Diagnostics {}", if report.passed { "passed" } else { "failed" });
+    Ok(report)
+}
+
+/// Save `image` as WebP and check the file decodes back as a WebP.
+fn save_and_verify_webp(image: &image::RgbaImage, path: &Path) -> Result<String, String> {
+    capture::save_image_as_webp(image, path).map_err(|e| e.to_string())?;
+    let bytes = std::fs::read(path).map_err(|e| format!("Saved file is unreadable: {}", e))?;
+    image::load_from_memory_with_format(&bytes, image::ImageFormat::WebP)
+        .map_err(|e| format!("Saved file is not a valid WebP: {}", e))?;
+    Ok(format!("Saved and decoded a {} byte WebP", bytes.len()))
+}
+
+/// Analyze the diagnostic capture with the configured provider and built-in prompt.
+async fn diagnostic_analysis(
+    db: &Database,
+    provider_name: &str,
+    captured: &capture::CapturedMonitor,
+    path: &Path,
+) -> Result<String, String> {
+    let provider = analysis::provider_from_settings(db, provider_name)?;
+    let image_mode = db.get_setting_string("image_mode", "downscale").map_err(|e| e.to_string())?;
+    let changed = [crate::ai::ChangedMonitor {
+        monitor_name: &captured.monitor_name,
+        image_path: path,
+        width: captured.image.width(),
+        height: captured.image.height(),
+        is_primary: true,
+        previous_active: false,
+        scale_factor: captured.scale_factor,
+    }];
+    let result = provider.analyze(analysis::AnalysisRequest {
+        changed: &changed,
+        unchanged: &[],
+        previous_contexts: &[],
+        session_tasks: &[],
+        session_description: None,
+        image_mode: &image_mode,
+        templates: crate::ai::PromptTemplates::default(),
+        custom_fields: &[],
+        language: None,
+        carried: None,
+        activity: &[],
+        overlap: &[],
+        images: None,
+        sent: None,
+    }).await.map_err(|e| e.to_string())?;
+    Ok(format!(
+        "{} described it as \"{}\" ({})",
+        provider.analyzed_by(), result.task_title, result.category
+    ))
 }
 
 #[tauri::command]
@@ -803,30 +3048,218 @@ pub async fn check_ollama(state: State<'_, Arc<AppState>>) -> Result<OllamaStatu
     }
 }
 
+/// Status of both providers for the settings badges: a quick reachability (and, for
+/// Claude, API key) probe, the configured model, and the latest success and error.
+#[tauri::command]
+pub async fn get_provider_health(state: State<'_, Arc<AppState>>) -> Result<Vec<ProviderHealth>, String> {
+    let active = state.db.get_setting_string("ai_provider", "claude").map_err(|e| e.to_string())?;
+    let (claude, ollama) = tokio::join!(claude_health(&state, active != "ollama"), ollama_health(&state, active == "ollama"));
+    Ok(vec![claude?, ollama?])
+}
+
+/// `ProviderHealth` fields that come from this run of the app rather than a probe.
+fn health_history(state: &AppState, provider: &str) -> (Option<String>, Option<RecentError>) {
+    let last_success = lock_recovered(&state.last_analysis_success).get(provider).cloned();
+    let last_error = lock_recovered(&state.recent_errors).iter().rev().find(|e| e.provider == provider).cloned();
+    (last_success, last_error)
+}
+
+async fn claude_health(state: &AppState, active: bool) -> Result<ProviderHealth, String> {
+    let api_key = state.db.get_setting("ai_api_key")
+        .map_err(|e| e.to_string())?
+        .filter(|key| !key.trim().is_empty());
+    let endpoint = analysis::claude_endpoint(&state.db)?;
+    let probe = match &api_key {
+        Some(key) => Some(crate::ai::probe_claude(
+            &reqwest::Client::new(), &endpoint, key, crate::ai::PROBE_TIMEOUT,
+        ).await),
+        None => None,
+    };
+    let (last_success_at, last_error) = health_history(state, "claude");
+    Ok(ProviderHealth {
+        provider: "claude".to_string(),
+        active,
+        configured: api_key.is_some(),
+        model: Some(crate::ai::CLAUDE_MODEL.to_string()),
+        probe,
+        model_available: None,
+        ollama_source: None,
+        last_success_at,
+        last_error,
+    })
+}
+
+async fn ollama_health(state: &AppState, active: bool) -> Result<ProviderHealth, String> {
+    let model = state.db.get_setting_string("ollama_model", "").map_err(|e| e.to_string())?;
+    let (probe, models) = crate::ai::probe_ollama(
+        &reqwest::Client::new(), crate::ai::OLLAMA_API_BASE, crate::ai::PROBE_TIMEOUT,
+    ).await;
+    let managed = state.ollama_process.is_managed();
+    // Only look for a binary when nothing is running; `find_binary` may spawn `which`
+    let binary = if managed || probe.reachable {
+        None
+    } else {
+        let dir = state.app_data_dir.clone();
+        tokio::task::spawn_blocking(move || OllamaProcess::find_binary(&dir))
+            .await
+            .unwrap_or(None)
+    };
+    let source = ollama_source(managed, probe.reachable, binary.as_deref(), &state.app_data_dir);
+    let model_available = (probe.reachable && probe.error.is_none() && !model.is_empty())
+        .then(|| ollama_has_model(&models, &model));
+    let (last_success_at, last_error) = health_history(state, "ollama");
+    Ok(ProviderHealth {
+        provider: "ollama".to_string(),
+        active,
+        configured: !model.is_empty(),
+        model: (!model.is_empty()).then_some(model),
+        probe: Some(probe),
+        model_available,
+        ollama_source: Some(source.to_string()),
+        last_success_at,
+        last_error,
+    })
+}
+
+/// Where Ollama comes from: our own process, one running on its own, or a binary we
+/// could start (`bundled` in the app data dir, `installed` on PATH).
+fn ollama_source(managed: bool, reachable: bool, binary: Option<&Path>, app_data_dir: &Path) -> &'static str {
+    if managed {
+        "managed"
+    } else if reachable {
+        "external"
+    } else {
+        match binary {
+            Some(path) if path.starts_with(app_data_dir) => "bundled",
+            Some(_) => "installed",
+            None => "missing",
+        }
+    }
+}
+
+/// Ollama lists models with a tag ("llava:latest"); the setting may omit it.
+fn ollama_has_model(models: &[String], model: &str) -> bool {
+    models.iter().any(|m| m == model || m.split(':').next() == Some(model))
+}
+
+/// The diagnostics "provider" step for a provider's health.
+fn health_summary(health: &ProviderHealth) -> Result<String, String> {
+    let model = health.model.as_deref().unwrap_or("");
+    let Some(probe) = health.probe.as_ref().filter(|_| health.configured) else {
+        return Err(if health.provider == "ollama" { "No Ollama model configured" } else { "No API key configured" }.to_string());
+    };
+    let error = probe.error.as_deref().unwrap_or("unknown error");
+    if !probe.reachable {
+        return Err(if health.provider == "ollama" {
+            format!("Ollama is not available: {}", error)
+        } else {
+            format!("Claude is unreachable: {}", error)
+        });
+    }
+    let latency = probe.latency_ms.unwrap_or(0);
+    if health.provider == "ollama" {
+        match health.model_available {
+            Some(true) => Ok(format!("Ollama is running with model {} ({} ms)", model, latency)),
+            Some(false) => Err(format!("Ollama is running but model {} is not pulled", model)),
+            None => Err(format!("Ollama answered with an error: {}", error)),
+        }
+    } else {
+        match probe.authenticated {
+            Some(true) => Ok(format!("Claude accepted the API key ({} ms)", latency)),
+            Some(false) => Err("Claude rejected the API key".to_string()),
+            None => Err(format!("Claude answered with an error: {}", error)),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn ensure_ollama(state: State<'_, Arc<AppState>>) -> Result<OllamaStatus, String> {
+    ensure_managed_ollama(&state, &reqwest::Client::new()).await
+}
+
+/// Stop the managed Ollama (e.g. wedged after a GPU error), wait for its port to free,
+/// then start it again as `ensure_ollama` would. An external Ollama is never touched.
 #[tauri::command]
-pub async fn ensure_ollama(state: State<'_, Arc<AppState>>) -> Result<OllamaStatus, String> {
+pub async fn restart_ollama(state: State<'_, Arc<AppState>>) -> Result<OllamaStatus, String> {
     let client = reqwest::Client::new();
+    {
+        let _startup = state.ollama_startup.lock().await;
+        let managed = state.ollama_process.is_managed();
+        // A wedged managed Ollama may not answer, so only probe when we don't own one
+        let reachable = !managed && crate::ai::check_ollama_connection(&client).await.is_ok();
+        check_restartable(managed, reachable)?;
+
+        info!("Restarting managed Ollama");
+        state.ollama_process.stop();
+        ollama_sidecar::wait_for_port_free(20).await?;
+    }
+    ensure_managed_ollama(&state, &client).await
+}
+
+/// Whether `restart_ollama` may go ahead: only an Ollama we started ourselves is restarted.
+fn check_restartable(managed: bool, reachable: bool) -> Result<(), String> {
+    match (managed, reachable) {
+        (true, _) => Ok(()),
+        (false, true) => Err("Ollama is running externally (not started by RLCollector); restart it yourself".to_string()),
+        (false, false) => Err("Ollama isn't running; start it instead of restarting".to_string()),
+    }
+}
+
+/// `ensure_ollama`: start the managed Ollama unless one is already reachable.
+async fn ensure_managed_ollama(state: &AppState, client: &reqwest::Client) -> Result<OllamaStatus, String> {
+    ensure_ollama_serialized(
+        &state.ollama_startup,
+        || state.ollama_process.is_managed(),
+        || async move {
+            crate::ai::check_ollama_connection(client).await.map_err(|e| e.to_string())
+        },
+        || async move {
+            let binary_path = OllamaProcess::find_binary(&state.app_data_dir)
+                .ok_or_else(|| "Ollama binary not found. Place it in the app data directory or install it on your system PATH.".to_string())?;
+            state.ollama_process.start(&binary_path, &ollama_sidecar::models_dir(&state.app_data_dir))?;
+            ollama_sidecar::wait_for_ready(client, 20).await?;
+            info!("Ollama started successfully from {}", binary_path.display());
+            Ok(())
+        },
+    )
+    .await
+}
 
-    if let Ok(models) = crate::ai::check_ollama_connection(&client).await {
-        info!("Ollama already running externally");
+/// The check-then-start sequence behind `ensure_ollama`, serialized on `startup` so a
+/// second caller waits for the first's start (and readiness wait) and then finds
+/// Ollama up, instead of racing it into a second spawn.
+async fn ensure_ollama_serialized<M, C, CFut, S, SFut>(
+    startup: &tokio::sync::Mutex<()>,
+    is_managed: M,
+    check: C,
+    start: S,
+) -> Result<OllamaStatus, String>
+where
+    M: Fn() -> bool,
+    C: Fn() -> CFut,
+    CFut: std::future::Future<Output = Result<Vec<String>, String>>,
+    S: FnOnce() -> SFut,
+    SFut: std::future::Future<Output = Result<(), String>>,
+{
+    let _startup = startup.lock().await;
+
+    if let Ok(models) = check().await {
+        let managed = is_managed();
+        if !managed {
+            info!("Ollama already running externally");
+        }
         return Ok(OllamaStatus {
             available: true,
             models,
-            source: "external".to_string(),
+            source: if managed { "bundled" } else { "external" }.to_string(),
         });
     }
 
-    let binary_path = OllamaProcess::find_binary(&state.app_data_dir)
-        .ok_or_else(|| "Ollama binary not found. Place it in the app data directory or install it on your system PATH.".to_string())?;
+    start().await?;
 
-    state.ollama_process.start(&binary_path)?;
-    ollama_sidecar::wait_for_ready(&client, 20).await?;
-
-    let models = crate::ai::check_ollama_connection(&client)
+    let models = check()
         .await
         .map_err(|e| format!("Ollama started but failed to connect: {}", e))?;
-
-    info!("Ollama started successfully from {}", binary_path.display());
     Ok(OllamaStatus {
         available: true,
         models,
@@ -834,6 +3267,28 @@ pub async fn ensure_ollama(state: State<'_, Arc<AppState>>) -> Result<OllamaStat
     })
 }
 
+/// Location and size of the models directory used by the managed Ollama.
+#[tauri::command]
+pub fn get_ollama_models_dir(state: State<'_, Arc<AppState>>) -> OllamaModelsDir {
+    let dir = ollama_sidecar::models_dir(&state.app_data_dir);
+    OllamaModelsDir {
+        path: dir.to_string_lossy().into_owned(),
+        size_bytes: ollama_sidecar::dir_size(&dir),
+    }
+}
+
+/// Stop the managed Ollama and delete its downloaded models. Returns bytes freed.
+/// An externally-run Ollama is neither stopped nor touched.
+#[tauri::command]
+pub fn purge_ollama_models(state: State<'_, Arc<AppState>>) -> Result<u64, String> {
+    state.ollama_process.stop();
+    let dir = ollama_sidecar::models_dir(&state.app_data_dir);
+    let freed = ollama_sidecar::purge_dir_contents(&dir)
+        .map_err(|e| format!("Failed to purge Ollama models: {}", e))?;
+    info!("Purged Ollama models directory {} ({} bytes freed)", dir.display(), freed);
+    Ok(freed)
+}
+
 #[tauri::command]
 pub async fn ollama_pull(model: String) -> Result<(), String> {
     info!("Pulling Ollama model: {}", model);
@@ -858,43 +3313,139 @@ pub async fn ollama_pull(model: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Find the Tauri monitor (which carries DPI-aware physical geometry) matching an xcap monitor id.
+fn find_tauri_monitor(app_handle: &tauri::AppHandle, monitor_id: u32) -> Result<Option<tauri::Monitor>, String> {
+    let xcap_monitors = capture::list_monitors().map_err(|e| e.to_string())?;
+    let Some(xcap_mon) = xcap_monitors.iter().find(|m| m.id == monitor_id) else {
+        return Ok(None);
+    };
+    Ok(app_handle
+        .available_monitors()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|m| m.name().as_deref() == Some(&xcap_mon.name)))
+}
+
+/// Create a hidden, borderless, transparent, always-on-top window covering `monitor`
+/// and showing `page`. Non-interactive overlays let clicks pass through; interactive
+/// ones take focus so the page receives mouse and keyboard input.
+fn build_overlay_window(
+    app_handle: &tauri::AppHandle,
+    label: &str,
+    page: &str,
+    monitor: &tauri::Monitor,
+    interactive: bool,
+) -> tauri::Result<tauri::WebviewWindow> {
+    let window = WebviewWindowBuilder::new(app_handle, label, WebviewUrl::App(page.into()))
+        .transparent(true)
+        .background_color(tauri::window::Color(0, 0, 0, 0))
+        .decorations(false)
+        .shadow(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .focused(interactive)
+        .visible(false)
+        .build()?;
+    let pos = monitor.position();
+    let size = monitor.size();
+    let _ = window.set_position(tauri::Position::Physical(
+        tauri::PhysicalPosition::new(pos.x, pos.y),
+    ));
+    let _ = window.set_size(tauri::Size::Physical(
+        tauri::PhysicalSize::new(size.width, size.height),
+    ));
+    if !interactive {
+        let _ = window.set_ignore_cursor_events(true);
+    }
+    Ok(window)
+}
+
+const HIGHLIGHT_LABEL_PREFIX: &str = "highlight_";
+
+/// Label of overlay `index` of highlight call `generation`.
+fn highlight_label(generation: u64, index: usize) -> String {
+    format!("{}{}_{}", HIGHLIGHT_LABEL_PREFIX, generation, index)
+}
+
+/// Highlight overlays among window `labels` left by calls before `generation`.
+/// Labels without a readable generation count as old.
+fn stale_highlights<'a>(labels: impl IntoIterator<Item = &'a str>, generation: u64) -> Vec<String> {
+    labels
+        .into_iter()
+        .filter_map(|label| {
+            let rest = label.strip_prefix(HIGHLIGHT_LABEL_PREFIX)?;
+            let created = rest.split_once('_').and_then(|(g, _)| g.parse::<u64>().ok()).unwrap_or(0);
+            (created < generation).then(|| label.to_string())
+        })
+        .collect()
+}
+
+fn close_windows(app_handle: &tauri::AppHandle, labels: &[String]) {
+    for label in labels {
+        if let Some(window) = app_handle.get_webview_window(label) {
+            let _ = window.close();
+        }
+    }
+}
+
+/// Close every highlight overlay and cancel the pending cleanup. Returns the new
+/// generation; a `highlight_monitors` call still building its overlays sees it's been
+/// superseded and closes them itself.
+fn close_highlights(app_handle: &tauri::AppHandle, state: &AppState) -> u64 {
+    let generation = state.highlight_generation.fetch_add(1, Ordering::Relaxed) + 1;
+    if let Some(cleanup) = lock_recovered(&state.highlight_cleanup).take() {
+        cleanup.abort();
+    }
+    let labels: Vec<String> = app_handle.webview_windows().into_keys().collect();
+    close_windows(app_handle, &stale_highlights(labels.iter().map(String::as_str), generation));
+    generation
+}
+
+/// Briefly outline the monitors `mode` would capture. Skipped in low-overhead mode
+/// while analysis is running: creating transparent overlay webviews stutters a desktop
+/// whose GPU is busy with the model.
 #[tauri::command]
 pub async fn highlight_monitors(
     app_handle: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
     mode: String,
     monitor_id: Option<u32>,
 ) -> Result<(), String> {
-    // Close any existing highlight windows
-    for (label, window) in app_handle.webview_windows() {
-        if label.starts_with("highlight_") {
-            let _ = window.close();
-        }
+    // Replaces any earlier highlight, including its pending cleanup
+    let generation = close_highlights(&app_handle, &state);
+    if state.low_overhead() {
+        debug!("Skipping monitor highlight: low-overhead mode during analysis");
+        return Ok(());
     }
 
     // Use Tauri's monitor API for DPI-aware physical coordinates
     let tauri_monitors = app_handle
         .available_monitors()
         .map_err(|e| e.to_string())?;
-    let primary = app_handle.primary_monitor().map_err(|e| e.to_string())?;
-
     if tauri_monitors.is_empty() {
         return Ok(());
     }
+    // The first monitor when the OS names no primary, like `capture::find_primary`
+    let primary = app_handle
+        .primary_monitor()
+        .map_err(|e| e.to_string())?
+        .or_else(|| tauri_monitors.first().cloned());
 
     // Select target monitors based on mode
-    let targets: Vec<&tauri::Monitor> = match mode.as_str() {
-        "default" => {
-            if let Some(ref p) = primary {
-                vec![p]
-            } else {
-                tauri_monitors.first().into_iter().collect()
-            }
-        }
+    let targets: Vec<tauri::Monitor> = match mode.as_str() {
+        "default" => primary.into_iter().collect(),
         "active" => {
-            let (cx, cy) = capture::get_cursor_position();
+            let read = tauri::async_runtime::spawn_blocking(capture::get_cursor_position)
+                .await
+                .map_err(|e| e.to_string())?;
+            // Same fallback as the capture loop, so the preview matches what gets captured
+            let cursor = capture::resolve_cursor(read, &mut lock_recovered(&state.last_cursor_position)).position();
             let active: Vec<_> = tauri_monitors
-                .iter()
+                .into_iter()
                 .filter(|m| {
+                    let Some((cx, cy)) = cursor else {
+                        return false;
+                    };
                     let pos = m.position();
                     let size = m.size();
                     cx >= pos.x
@@ -903,29 +3454,17 @@ pub async fn highlight_monitors(
                         && cy < pos.y + size.height as i32
                 })
                 .collect();
+            // Cursor unknown or off every monitor: the primary, which capture_monitors captures then
             if active.is_empty() {
-                if let Some(ref p) = primary {
-                    vec![p]
-                } else {
-                    vec![]
-                }
+                primary.into_iter().collect()
             } else {
                 active
             }
         }
-        "all" => tauri_monitors.iter().collect(),
+        "all" => tauri_monitors,
         "specific" => {
             if let Some(id) = monitor_id {
-                let xcap_monitors = capture::list_monitors().map_err(|e| e.to_string())?;
-                if let Some(xcap_mon) = xcap_monitors.iter().find(|m| m.id == id) {
-                    tauri_monitors
-                        .iter()
-                        .find(|m| m.name().as_deref() == Some(&xcap_mon.name))
-                        .into_iter()
-                        .collect()
-                } else {
-                    vec![]
-                }
+                find_tauri_monitor(&app_handle, id)?.into_iter().collect()
             } else {
                 return Ok(());
             }
@@ -939,32 +3478,9 @@ pub async fn highlight_monitors(
 
     let mut labels = Vec::new();
     for (i, monitor) in targets.iter().enumerate() {
-        let label = format!("highlight_{}", i);
-        let url = WebviewUrl::App("overlay.html".into());
-
-        match WebviewWindowBuilder::new(&app_handle, &label, url)
-            .transparent(true)
-            .background_color(tauri::window::Color(0, 0, 0, 0))
-            .decorations(false)
-            .shadow(false)
-            .always_on_top(true)
-            .skip_taskbar(true)
-            .focused(false)
-            .visible(false)
-            .build()
-        {
-            Ok(window) => {
-                let pos = monitor.position();
-                let size = monitor.size();
-                let _ = window.set_position(tauri::Position::Physical(
-                    tauri::PhysicalPosition::new(pos.x, pos.y),
-                ));
-                let _ = window.set_size(tauri::Size::Physical(
-                    tauri::PhysicalSize::new(size.width, size.height),
-                ));
-                let _ = window.set_ignore_cursor_events(true);
-                labels.push(label);
-            }
+        let label = highlight_label(generation, i);
+        match build_overlay_window(&app_handle, &label, "overlay.html", monitor, false) {
+            Ok(_) => labels.push(label),
             Err(e) => {
                 error!("Failed to create highlight window: {}", e);
             }
@@ -973,6 +3489,11 @@ pub async fn highlight_monitors(
 
     // Brief delay for WebView2 to render content, then show all at once
     tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+    if state.highlight_generation.load(Ordering::Relaxed) != generation {
+        // A newer call (or capture starting) took over while these were being built
+        close_windows(&app_handle, &labels);
+        return Ok(());
+    }
     for label in &labels {
         if let Some(window) = app_handle.get_webview_window(label) {
             let _ = window.show();
@@ -980,68 +3501,830 @@ pub async fn highlight_monitors(
     }
 
     // Close overlay windows after 4 seconds
-    tauri::async_runtime::spawn(async move {
+    let cleanup_app = app_handle.clone();
+    let cleanup = tauri::async_runtime::spawn(async move {
         tokio::time::sleep(std::time::Duration::from_secs(4)).await;
-        for label in &labels {
-            if let Some(window) = app_handle.get_webview_window(label) {
-                let _ = window.close();
-            }
-        }
+        close_windows(&cleanup_app, &labels);
     });
+    let mut pending = lock_recovered(&state.highlight_cleanup);
+    if state.highlight_generation.load(Ordering::Relaxed) == generation {
+        *pending = Some(cleanup);
+    }
+    // Otherwise a newer call has started; this cleanup still only touches our own labels
 
     Ok(())
 }
 
+/// Capture once in `mode` and return what the pipeline would make of each monitor
+/// (grayscale, active-window crop), downsized to `max_width`, as base64 WebP. Nothing
+/// is saved and the capture loop's state is left alone; at most one call per
+/// `preview::MIN_PREVIEW_GAP`.
+#[tauri::command]
+pub async fn get_capture_preview(
+    state: State<'_, Arc<AppState>>,
+    mode: String,
+    monitor_id: Option<u32>,
+    max_width: u32,
+) -> Result<Vec<PreviewFrame>, String> {
+    monitor_mode_settings(&mode, monitor_id)?;
+    if max_width == 0 {
+        return Err("max_width must be positive".to_string());
+    }
+    preview::claim_slot(&mut lock_recovered(&state.last_capture_preview), Instant::now())
+        .map_err(|wait| format!("Preview requested too often; try again in {}ms", wait.as_millis()))?;
+
+    let options = preview::PreviewOptions {
+        grayscale: state.db.get_setting_bool("grayscale_capture", false).unwrap_or(false),
+        active_window: state.db.get_setting_string("image_mode", "downscale").unwrap_or_default() == "active_window",
+        min_dimension: state.db.get_setting_u64("min_capture_dimension", 100).unwrap_or(100) as u32,
+        max_width,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        preview::capture_preview(
+            || {
+                // The cursor is read directly so the loop's last known position stays its own
+                let cursor = if mode == "active" { capture::get_cursor_position() } else { None };
+                capture::capture_monitors(&mode, monitor_id, None, cursor, &[])
+            },
+            &options,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// The capture region last chosen with `select_capture_region`, if any.
+#[tauri::command]
+pub fn get_capture_region(state: State<'_, Arc<AppState>>) -> Result<Option<CaptureRegion>, String> {
+    state.db.get_capture_region().map_err(|e| e.to_string())
+}
+
+/// Label of the region selection overlay window.
+const REGION_WINDOW_LABEL: &str = "region_select";
+/// How long the region overlay waits for a selection before giving up.
+const REGION_SELECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Open an interactive overlay on a monitor and let the user drag out a capture
+/// region. The page (`region.html`) emits `region-selected` with a CSS-pixel rect
+/// or `region-cancelled` on Escape; the rect is converted to physical pixels with
+/// the monitor's scale factor and stored as the capture region. Escape and the
+/// timeout both resolve to `RegionSelection::Cancelled`.
+#[tauri::command]
+pub async fn select_capture_region(
+    app_handle: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    monitor_id: u32,
+) -> Result<RegionSelection, String> {
+    let monitor = find_tauri_monitor(&app_handle, monitor_id)?
+        .ok_or_else(|| format!("Monitor {} not found", monitor_id))?;
+    let scale_factor = monitor.scale_factor();
+    let bounds = (monitor.size().width, monitor.size().height);
+
+    if let Some(existing) = app_handle.get_webview_window(REGION_WINDOW_LABEL) {
+        let _ = existing.close();
+    }
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<Option<region::CssRect>>();
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let send = move |result: Option<region::CssRect>| {
+        if let Some(tx) = tx.lock().ok().and_then(|mut t| t.take()) {
+            let _ = tx.send(result);
+        }
+    };
+    let on_cancel = send.clone();
+    let selected_id = app_handle.listen_any("region-selected", move |event| {
+        match serde_json::from_str::<region::CssRect>(event.payload()) {
+            Ok(rect) => send(Some(rect)),
+            Err(e) => warn!("Ignoring malformed region-selected payload: {}", e),
+        }
+    });
+    let cancelled_id = app_handle.listen_any("region-cancelled", move |_| on_cancel(None));
+
+    let result = match build_overlay_window(&app_handle, REGION_WINDOW_LABEL, "region.html", &monitor, true) {
+        Ok(window) => {
+            // Brief delay for WebView2 to render content before showing
+            tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+            let _ = window.show();
+            let _ = window.set_focus();
+            // A dropped sender (e.g. the listener went away) counts as a cancel
+            tokio::time::timeout(REGION_SELECT_TIMEOUT, rx).await.ok().and_then(|r| r.ok().flatten())
+        }
+        Err(e) => {
+            app_handle.unlisten(selected_id);
+            app_handle.unlisten(cancelled_id);
+            return Err(format!("Failed to create region overlay: {}", e));
+        }
+    };
+
+    app_handle.unlisten(selected_id);
+    app_handle.unlisten(cancelled_id);
+    if let Some(window) = app_handle.get_webview_window(REGION_WINDOW_LABEL) {
+        let _ = window.close();
+    }
+
+    let Some(rect) = result else {
+        info!("Region selection on monitor {} cancelled", monitor_id);
+        return Ok(RegionSelection::Cancelled);
+    };
+    let region = region::css_to_physical(&rect, scale_factor, bounds, monitor_id)
+        .ok_or("Selected region is empty")?;
+    state.db.set_capture_region(&region).map_err(|e| e.to_string())?;
+    info!(
+        "Capture region set on monitor {}: {}x{} at ({}, {})",
+        monitor_id, region.width, region.height, region.x, region.y
+    );
+    Ok(RegionSelection::Selected { region })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+
     #[test]
-    fn test_format_timestamp_for_filename() {
-        let epoch = SystemTime::UNIX_EPOCH;
-        let result = format_timestamp_for_filename(epoch);
-        assert_eq!(result, "1970-01-01T00-00-00");
+    fn test_refuse_verified() {
+        let task = |id: i64, verified: bool| Task {
+            id,
+            title: format!("Task {}", id),
+            description: None,
+            category: None,
+            started_at: "2025-01-01T10:00:00".to_string(),
+            ended_at: None,
+            ai_reasoning: None,
+            user_verified: verified,
+            metadata: None,
+        };
+        let cases: [(Vec<Task>, bool, Result<(), String>); 5] = [
+            (vec![], false, Ok(())),
+            (vec![task(1, false)], false, Ok(())),
+            (
+                vec![task(1, false), task(2, true), task(3, true)],
+                false,
+                Err("Task 2 (\"Task 2\") is verified; pass force to discard the session anyway".to_string()),
+            ),
+            (vec![task(2, true)], true, Ok(())),
+            (vec![task(1, false)], true, Ok(())),
+        ];
+        for (tasks, force, expected) in cases {
+            assert_eq!(refuse_verified(&tasks, force, "discard the session"), expected, "force={} tasks={:?}", force, tasks);
+        }
     }
 
     #[test]
-    fn test_format_timestamp_for_db() {
-        let epoch = SystemTime::UNIX_EPOCH;
-        let result = format_timestamp_for_db(epoch);
-        assert_eq!(result, "1970-01-01T00:00:00");
+    fn test_session_limit_action() {
+        assert_eq!(session_limit_action(0, 50_000, false), SessionLimitAction::Continue);
+        assert_eq!(session_limit_action(100, 99, false), SessionLimitAction::Continue);
+        assert_eq!(session_limit_action(100, 100, true), SessionLimitAction::Rotate);
+        assert_eq!(session_limit_action(100, 100, false), SessionLimitAction::Stop);
+        assert_eq!(session_limit_action(100, 250, false), SessionLimitAction::Stop);
     }
 
     #[test]
-    fn test_days_to_ymd() {
-        assert_eq!(days_to_ymd(0), (1970, 1, 1));
-        assert_eq!(days_to_ymd(365), (1971, 1, 1));
-        assert_eq!(days_to_ymd(18262), (2020, 1, 1));
+    fn test_finalize_session_records() {
+        let db = Database::in_memory().unwrap();
+        let current = db.create_session("2025-01-01T10:00:00", Some("Refactoring"), Some("Parser")).unwrap();
+        let other = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+
+        // Not being captured: ended here, analyzed only when asked
+        assert_eq!(
+            finalize_session_records(&db, current, other, None),
+            Ok(Finalized { rotated_into: None, analyze: false }),
+        );
+        assert!(db.get_session(other).unwrap().ended_at.is_some());
+        let err = finalize_session_records(&db, current, other, Some(true)).unwrap_err();
+        assert!(err.contains("already ended"), "{}", err);
+        let err = finalize_session_records(&db, current, 999, None).unwrap_err();
+        assert_eq!(err, "Session 999 not found");
+        let third = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        assert_eq!(
+            finalize_session_records(&db, current, third, Some(true)),
+            Ok(Finalized { rotated_into: None, analyze: true }),
+        );
+
+        // Being captured: rotates into a session with the same title and description,
+        // even with require_analysis_confirmation off
+        db.set_setting("require_analysis_confirmation", "false").unwrap();
+        let finalized = finalize_session_records(&db, current, current, None).unwrap();
+        assert!(!finalized.analyze);
+        let new = db.get_session(finalized.rotated_into.unwrap()).unwrap();
+        assert_eq!((new.title.as_deref(), new.description.as_deref()), (Some("Parser"), Some("Refactoring")));
+        assert!(new.ended_at.is_none());
+        assert!(finalize_session_records(&db, current, current, Some(true)).unwrap().analyze);
     }
 
     #[test]
-    fn test_group_by_capture_group() {
-        let screenshots = vec![
-            Screenshot {
-                id: 1, filepath: "a.webp".to_string(), captured_at: "2025-01-01T10:00:00".to_string(),
-                active_window_title: None, monitor_index: 0, capture_group: Some("g1".to_string()),
-            },
-            Screenshot {
-                id: 2, filepath: "b.webp".to_string(), captured_at: "2025-01-01T10:00:00".to_string(),
-                active_window_title: None, monitor_index: 1, capture_group: Some("g1".to_string()),
-            },
-            Screenshot {
-                id: 3, filepath: "c.webp".to_string(), captured_at: "2025-01-01T10:00:30".to_string(),
-                active_window_title: None, monitor_index: 0, capture_group: Some("g2".to_string()),
-            },
-            Screenshot {
-                id: 4, filepath: "d.webp".to_string(), captured_at: "2025-01-01T10:01:00".to_string(),
-                active_window_title: None, monitor_index: 0, capture_group: None,
-            },
-        ];
+    fn test_apply_session_limit() {
+        let db = Database::in_memory().unwrap();
+        let old_id = db.create_session("2025-01-01T10:00:00", Some("Refactoring"), Some("Parser")).unwrap();
+        db.set_setting("max_screenshots_per_session", "2").unwrap();
+
+        assert_eq!(apply_session_limit(&db, old_id, 1), Ok(None));
+
+        // Rotation: the new session carries on with the old one's title and description
+        let new_id = apply_session_limit(&db, old_id, 2).unwrap().unwrap();
+        let new = db.get_session(new_id).unwrap();
+        assert_eq!((new.title.as_deref(), new.description.as_deref()), (Some("Parser"), Some("Refactoring")));
+        assert!(new.ended_at.is_none());
+        let stats = SessionStats { ticks: 3, screenshots_saved: 2, ..Default::default() };
+        close_session(&db, old_id, &stats);
+        let old = db.get_session(old_id).unwrap();
+        assert!(old.ended_at.is_some());
+        assert_eq!(old.stats.screenshots_saved, 2);
+
+        // Rotation disabled: stop without creating a session
+        db.set_setting("session_auto_rotate", "false").unwrap();
+        let sessions = db.get_sessions(100, 0).unwrap().len();
+        assert_eq!(
+            apply_session_limit(&db, new_id, 2),
+            Err("Capture stopped: session reached the limit of 2 screenshots and session rotation is disabled".to_string()),
+        );
+        assert_eq!(db.get_sessions(100, 0).unwrap().len(), sessions);
+        // A failed rotation stops too
+        db.set_setting("session_auto_rotate", "true").unwrap();
+        let err = apply_session_limit(&db, 999, 2).unwrap_err();
+        assert!(err.contains("could not start a new session"), "{}", err);
+    }
+
+    #[test]
+    fn test_daily_count_range() {
+        assert_eq!(daily_count_range("1970-01-02", "1970-01-05"), Ok((1, 4)));
+        assert_eq!(daily_count_range("2025-01-06", "2025-01-06").map(|(a, b)| b - a), Ok(0));
+        assert!(daily_count_range("2025-01-06T00:00:00", "2025-01-07").unwrap_err().contains("start"));
+        assert!(daily_count_range("2025-01-06", "2025-02-30").unwrap_err().contains("end"));
+        assert!(daily_count_range("2025-01-07", "2025-01-06").unwrap_err().contains("after"));
+        assert!(daily_count_range("2000-01-01", "2025-01-01").unwrap_err().contains("longer"));
+    }
+
+    #[test]
+    fn test_range_bounds_and_retention_in_local_days() {
+        let zone = time::TimeZone::parse_posix("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        // Bare dates are whole local days; timestamps are only normalized to UTC
+        assert_eq!(range_bound(&zone, "2025-01-06", false), "2025-01-06T05:00:00");
+        assert_eq!(range_bound(&zone, "2025-07-06", true), "2025-07-07T03:59:59");
+        assert_eq!(range_bound(&zone, "2025-01-06T10:00:00", false), "2025-01-06T10:00:00");
+        assert_eq!(range_bound(&zone, "2025-01-06T10:00:00+02:00", true), "2025-01-06T08:00:00");
+        assert_eq!(range_bound(&zone, "2025-01", true), "2025-01");
+        assert_eq!(range_bound(&zone, "2025-W02", false), "2025-01-06T05:00:00");
+        assert_eq!(range_bound(&zone, "2025-W02", true), "2025-01-13T04:59:59");
+
+        // 01:00 UTC on the 10th is still the 9th in New York
+        let now = time::parse_timestamp("2025-03-10T01:00:00").unwrap();
+        assert_eq!(retention_cutoff_at(&zone, now, 1), "2025-03-08T05:00:00");
+        assert_eq!(retention_cutoff_at(&zone, now, 0), "2025-03-10T01:00:00");
+    }
+
+    #[test]
+    fn test_same_second_captures_get_distinct_files() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_same_second_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = SystemTime::UNIX_EPOCH + Duration::from_millis(1_735_725_600_120);
+        let second = first + Duration::from_millis(450);
+
+        // Same second, so the same capture group, but the files differ
+        assert_eq!(time::format_for_filename(first), time::format_for_filename(second));
+        let a = capture::screenshot_filename(&dir, &time::format_for_screenshot(first), 0, "eDP-1");
+        std::fs::write(dir.join(&a), b"x").unwrap();
+        let b = capture::screenshot_filename(&dir, &time::format_for_screenshot(second), 0, "eDP-1");
+        assert_eq!(a, "screenshot_2025-01-01T10-00-00-120_00_edp1.webp");
+        assert_eq!(b, "screenshot_2025-01-01T10-00-00-570_00_edp1.webp");
 
-        let groups = group_by_capture_group(&screenshots);
-        assert_eq!(groups.len(), 3); // g1 (2 items), g2 (1 item), ungrouped (1 item)
-        assert_eq!(groups[0].len(), 2); // g1
-        assert_eq!(groups[1].len(), 1); // g2
-        assert_eq!(groups[2].len(), 1); // ungrouped
+        // Even within the same millisecond, the existing file forces a new name
+        let c = capture::screenshot_filename(&dir, &time::format_for_screenshot(first), 0, "eDP-1");
+        assert_ne!(c, a);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn health(provider: &str, configured: bool, probe: Option<crate::models::ProviderProbe>, model_available: Option<bool>) -> ProviderHealth {
+        ProviderHealth {
+            provider: provider.to_string(),
+            active: true,
+            configured,
+            model: Some("llava".to_string()),
+            probe,
+            model_available,
+            ollama_source: None,
+            last_success_at: None,
+            last_error: None,
+        }
+    }
+
+    fn probe(reachable: bool, authenticated: Option<bool>, error: Option<&str>) -> Option<crate::models::ProviderProbe> {
+        Some(crate::models::ProviderProbe {
+            reachable,
+            latency_ms: reachable.then_some(42),
+            authenticated,
+            error: error.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn test_health_summary() {
+        assert_eq!(health_summary(&health("claude", false, None, None)).unwrap_err(), "No API key configured");
+        assert_eq!(health_summary(&health("claude", true, probe(true, Some(true), None), None)).unwrap(), "Claude accepted the API key (42 ms)");
+        assert_eq!(health_summary(&health("claude", true, probe(true, Some(false), Some("Invalid API key")), None)).unwrap_err(), "Claude rejected the API key");
+        assert_eq!(health_summary(&health("claude", true, probe(false, None, Some("dns error")), None)).unwrap_err(), "Claude is unreachable: dns error");
+
+        assert_eq!(health_summary(&health("ollama", false, probe(true, None, None), None)).unwrap_err(), "No Ollama model configured");
+        assert_eq!(health_summary(&health("ollama", true, probe(true, None, None), Some(true))).unwrap(), "Ollama is running with model llava (42 ms)");
+        assert_eq!(health_summary(&health("ollama", true, probe(true, None, None), Some(false))).unwrap_err(), "Ollama is running but model llava is not pulled");
+        assert!(health_summary(&health("ollama", true, probe(false, None, Some("refused")), None)).unwrap_err().starts_with("Ollama is not available"));
+    }
+
+    #[test]
+    fn test_ollama_source_and_model_match() {
+        let data = Path::new("/data/rlcollector");
+        assert_eq!(ollama_source(true, true, None, data), "managed");
+        assert_eq!(ollama_source(false, true, None, data), "external");
+        assert_eq!(ollama_source(false, false, Some(&data.join("ollama")), data), "bundled");
+        assert_eq!(ollama_source(false, false, Some(Path::new("/usr/bin/ollama")), data), "installed");
+        assert_eq!(ollama_source(false, false, None, data), "missing");
+
+        let models = vec!["llava:latest".to_string(), "qwen2.5vl:7b".to_string()];
+        assert!(ollama_has_model(&models, "llava"));
+        assert!(ollama_has_model(&models, "qwen2.5vl:7b"));
+        assert!(!ollama_has_model(&models, "qwen2.5vl:3b"));
+    }
+
+    #[test]
+    fn test_check_session_open() {
+        let db = Database::in_memory().unwrap();
+        let active = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let ended = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        db.end_session(ended, "2025-01-01T09:30:00").unwrap();
+
+        assert!(check_session_open(&db, active).is_ok());
+        assert_eq!(check_session_open(&db, ended).unwrap_err(), format!("Session {} already ended at 2025-01-01T09:30:00", ended));
+        assert_eq!(check_session_open(&db, 999).unwrap_err(), "Session 999 not found");
+
+        // Finalizing is a one-shot: once ended, the session is no longer open
+        db.end_session(active, "2025-01-01T10:45:00").unwrap();
+        assert!(check_session_open(&db, active).is_err());
+    }
+
+    #[test]
+    fn test_count_by_session() {
+        assert_eq!(
+            count_by_session(&[Some(2), None, Some(2), Some(1), None, Some(2)]),
+            vec![
+                PendingCleared { session_id: Some(2), count: 3 },
+                PendingCleared { session_id: None, count: 2 },
+                PendingCleared { session_id: Some(1), count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_capture_count_seeds_from_db() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(load_capture_count(&db).load(Ordering::Relaxed), 0);
+
+        db.set_capture_count(1234).unwrap();
+        assert_eq!(load_capture_count(&db).load(Ordering::Relaxed), 1234);
+
+        db.set_setting("capture_count", "garbage").unwrap();
+        assert_eq!(load_capture_count(&db).load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_push_bounded_evicts_oldest() {
+        let mut buffer = VecDeque::new();
+        for i in 0..RECENT_ERRORS_CAP + 3 {
+            push_bounded(&mut buffer, i, RECENT_ERRORS_CAP);
+        }
+        assert_eq!(buffer.len(), RECENT_ERRORS_CAP);
+        assert_eq!(buffer.front(), Some(&3));
+        assert_eq!(buffer.back(), Some(&(RECENT_ERRORS_CAP + 2)));
+
+        push_bounded(&mut buffer, 99, 2);
+        assert_eq!(buffer, VecDeque::from([RECENT_ERRORS_CAP + 2, 99]));
+    }
+
+
+
+    #[test]
+    fn test_highlight_label_bookkeeping() {
+        // Each call gets its own labels, so a cleanup can't close a later call's overlays
+        let first: Vec<String> = (0..2).map(|i| highlight_label(1, i)).collect();
+        let second: Vec<String> = (0..2).map(|i| highlight_label(2, i)).collect();
+        assert_eq!(first, vec!["highlight_1_0", "highlight_1_1"]);
+        assert!(first.iter().all(|label| !second.contains(label)));
+
+        let open = ["main", "region_select", "highlight_1_0", "highlight_1_1", "highlight_2_0", "highlight_0", "highlight_x_1"];
+        // Starting call 2 closes call 1's overlays (and unreadable/legacy ones), never its own or other windows
+        assert_eq!(
+            stale_highlights(open, 2),
+            vec!["highlight_1_0", "highlight_1_1", "highlight_0", "highlight_x_1"]
+        );
+        // close_highlights bumps past every call, so all overlays go
+        assert_eq!(stale_highlights(open, 3).len(), 5);
+        assert!(stale_highlights(["main"], 5).is_empty());
+    }
+
+    #[test]
+    fn test_monitor_mode_settings() {
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            monitor_mode_settings("specific", Some(2)).unwrap(),
+            vec![pair("capture_monitor_mode", "specific"), pair("capture_monitor_id", "2")]
+        );
+        for mode in ["default", "active", "all"] {
+            assert_eq!(monitor_mode_settings(mode, None).unwrap(), vec![pair("capture_monitor_mode", mode)]);
+            assert!(monitor_mode_settings(mode, Some(2)).unwrap_err().contains("only applies"));
+        }
+        assert!(monitor_mode_settings("specific", None).unwrap_err().contains("needs a monitor_id"));
+        // Typos are rejected instead of falling through to "default"
+        assert!(monitor_mode_settings("al", None).unwrap_err().contains("expected one of"));
+        assert!(monitor_mode_settings("Specific", Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_validate_task_range() {
+        assert!(validate_task_range("coding", "2025-01-06", "2025-01-10").is_ok());
+        assert!(validate_task_range("coding", "2025-01-06T09:00:00", "2025-01-06").is_ok());
+        assert!(validate_task_range("", "2025-01-06", "2025-01-10").unwrap_err().contains("Category"));
+        assert!(validate_task_range("coding", "last week", "2025-01-10").unwrap_err().contains("start"));
+        assert!(validate_task_range("coding", "2025-01-06", "2025-1-10").unwrap_err().contains("end"));
+        assert!(validate_task_range("coding", "2025-01-10", "2025-01-06").unwrap_err().contains("after"));
+        assert!(validate_task_range("coding", "2025-W02", "2025-01-12").is_ok());
+        assert!(validate_task_range("coding", "2025-01-10", "2025-W02").is_ok());
+        assert!(validate_task_range("coding", "2025-W03", "2025-01-12").unwrap_err().contains("after"));
+        assert!(validate_task_range("coding", "2025-W54", "2025-W55").unwrap_err().contains("start"));
+        // Multi-byte characters where the values are cut to compare must not panic
+        assert!(validate_task_range("coding", "2025-01-01é", "2025-01-01T").unwrap_err().contains("after"));
+        assert!(validate_task_range("coding", "2025-01-01T", "2025-01-02é").is_ok());
+    }
+
+    #[test]
+    fn test_lock_recovered_after_poison() {
+        let states: Arc<Mutex<HashMap<u32, MonitorState>>> = Arc::new(Mutex::new(HashMap::new()));
+        let poisoner = Arc::clone(&states);
+        let result = std::thread::spawn(move || {
+            let mut ms = poisoner.lock().unwrap();
+            ms.insert(1, MonitorState { last_hash: [1; 32], last_summary: String::new(), name: "Left".to_string(), next_due_ms: 0, last_distance: None });
+            panic!("tick failed while holding monitor_states");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(states.is_poisoned());
+
+        // The write made before the panic is still there, and the lock is usable again
+        assert_eq!(lock_recovered(&states)[&1].name, "Left");
+        assert!(!states.is_poisoned());
+        lock_recovered(&states).clear();
+        assert!(states.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_frame_distance_and_change_state() {
+        // Crafted frames: left half bright, then top half bright, then the top one again
+        let frame = |bright: fn(u32, u32) -> bool| {
+            image::RgbaImage::from_fn(64, 64, |x, y| if bright(x, y) { image::Rgba([255, 255, 255, 255]) } else { image::Rgba([0, 0, 0, 255]) })
+        };
+        let left = capture::perceptual_hash(&frame(|x, _| x < 32));
+        let top = capture::perceptual_hash(&frame(|_, y| y < 32));
+
+        let mut states: HashMap<u32, MonitorState> = HashMap::new();
+        assert_eq!(frame_distance(states.get(&2), &left), None);
+        states.insert(2, MonitorState { last_hash: left, last_summary: String::new(), name: "Right".to_string(), next_due_ms: 0, last_distance: None });
+        states.insert(1, MonitorState { last_hash: [0; 32], last_summary: String::new(), name: "Left".to_string(), next_due_ms: 0, last_distance: None });
+
+        let distance = frame_distance(states.get(&2), &top);
+        assert_eq!(distance, Some(capture::hash_distance(&top, &left)));
+        assert!(distance.unwrap() >= capture::CHANGE_THRESHOLD);
+        let ms = states.get_mut(&2).unwrap();
+        ms.last_distance = distance;
+        ms.last_hash = top;
+        assert_eq!(frame_distance(states.get(&2), &top), Some(0));
+
+        let rows = monitor_change_states(&states);
+        assert_eq!(rows.iter().map(|r| r.monitor_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!((rows[0].monitor_name.as_str(), rows[0].last_distance), ("Left", None));
+        assert_eq!(rows[0].last_hash, "0".repeat(64));
+        assert_eq!(rows[1].last_distance, distance);
+        assert_eq!(rows[1].last_hash.len(), 64);
+        assert_eq!(rows[1].last_hash[..2], format!("{:02x}", top[0]));
+    }
+
+    #[test]
+    fn test_previous_active_frame_saved_unchanged() {
+        let frame = |bright: fn(u32, u32) -> bool| {
+            image::RgbaImage::from_fn(64, 64, |x, y| if bright(x, y) { image::Rgba([255, 255, 255, 255]) } else { image::Rgba([0, 0, 0, 255]) })
+        };
+        let captured = |monitor_id, image, previous_active| capture::CapturedMonitor {
+            monitor_id,
+            monitor_name: format!("Monitor {}", monitor_id),
+            image,
+            previous_active,
+            scale_factor: 1.0,
+            is_primary: false,
+        };
+        let state = |image: &image::RgbaImage| MonitorState {
+            last_hash: capture::perceptual_hash(image),
+            last_summary: String::new(),
+            name: String::new(),
+            next_due_ms: 0,
+            last_distance: None,
+        };
+        // The user moved from monitor 2 to monitor 1; monitor 2 still shows what it did
+        let states = HashMap::from([(1, state(&frame(|x, _| x < 32))), (2, state(&frame(|_, y| y < 32)))]);
+        let tick = [captured(1, frame(|_, y| y >= 32), false), captured(2, frame(|_, y| y < 32), true)];
+        let saved: Vec<(u32, Option<u32>, bool)> = tick
+            .iter()
+            .map(|cap| {
+                let (distance, changed) = frame_change(states.get(&cap.monitor_id), &capture::perceptual_hash(&cap.image), cap.previous_active);
+                (cap.monitor_id, distance, changed)
+            })
+            .collect();
+        assert!(saved[0].2);
+        assert_eq!(saved[1], (2, Some(0), true));
+
+        // The same unchanged frame on a tick where monitor 2 is captured normally is dropped
+        let (distance, changed) = frame_change(states.get(&2), &capture::perceptual_hash(&tick[1].image), false);
+        assert_eq!((distance, changed), (Some(0), false));
+        // A monitor's first frame is always saved
+        assert_eq!(frame_change(None, &[0; 32], false), (None, true));
+    }
+
+    #[test]
+    fn test_monitors_not_due() {
+        let state = |next_due_ms| MonitorState { last_hash: [0; 32], last_summary: String::new(), name: String::new(), next_due_ms, last_distance: None };
+        let intervals = capture::MonitorIntervals {
+            default_ms: 30_000,
+            overrides: HashMap::from([(2, 600_000)]),
+        };
+        // Monitor 1 was captured at t=0, monitor 2 (10-minute override) too
+        let states = HashMap::from([(1, state(30_000)), (2, state(600_000))]);
+        assert_eq!(monitors_not_due(10_000, &states, &intervals), vec![1, 2]);
+        // At 30s only the default-interval monitor is due
+        assert_eq!(monitors_not_due(30_000, &states, &intervals), vec![2]);
+        assert_eq!(monitors_not_due(600_000, &states, &intervals), Vec::<u32>::new());
+
+        // Shortening monitor 2's override makes it due instead of waiting out the old interval
+        let shortened = capture::MonitorIntervals {
+            default_ms: 30_000,
+            overrides: HashMap::from([(2, 60_000)]),
+        };
+        assert_eq!(monitors_not_due(40_000, &states, &shortened), Vec::<u32>::new());
+        // Monitors without state (never captured) are always due
+        assert!(monitors_not_due(0, &HashMap::new(), &intervals).is_empty());
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let caught = std::panic::catch_unwind(|| panic!("boom {}", 42)).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "boom 42");
+        let caught = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "static");
+        let caught = std::panic::catch_unwind(|| std::panic::panic_any(7u8)).unwrap_err();
+        assert_eq!(panic_message(caught.as_ref()), "unknown panic");
+    }
+
+    #[tokio::test]
+    async fn test_ensure_ollama_serialized_spawns_once() {
+        use std::sync::atomic::AtomicUsize;
+
+        let startup = Arc::new(tokio::sync::Mutex::new(()));
+        let running = Arc::new(AtomicBool::new(false));
+        let spawns = Arc::new(AtomicUsize::new(0));
+
+        let calls: Vec<_> = (0..16)
+            .map(|_| {
+                let (startup, running, spawns) = (startup.clone(), running.clone(), spawns.clone());
+                tokio::spawn(async move {
+                    let running = &running;
+                    ensure_ollama_serialized(
+                        &startup,
+                        || spawns.load(Ordering::SeqCst) > 0,
+                        || async move {
+                            if running.load(Ordering::SeqCst) {
+                                Ok(vec!["llava".to_string()])
+                            } else {
+                                Err("connection refused".to_string())
+                            }
+                        },
+                        || async {
+                            spawns.fetch_add(1, Ordering::SeqCst);
+                            // Stand-in for wait_for_ready: yield so other callers get to run
+                            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                            running.store(true, Ordering::SeqCst);
+                            Ok(())
+                        },
+                    )
+                    .await
+                })
+            })
+            .collect();
+
+        for call in calls {
+            let status = call.await.unwrap().unwrap();
+            assert_eq!(status.source, "bundled");
+            assert_eq!(status.models, vec!["llava".to_string()]);
+        }
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_ollama_serialized_reports_external_and_start_errors() {
+        let startup = tokio::sync::Mutex::new(());
+        let status = ensure_ollama_serialized(
+            &startup,
+            || false,
+            || async { Ok(vec![]) },
+            || async { panic!("must not start when Ollama is already reachable") },
+        )
+        .await
+        .unwrap();
+        assert_eq!(status.source, "external");
+
+        let err = ensure_ollama_serialized(
+            &startup,
+            || false,
+            || async { Err("connection refused".to_string()) },
+            || async { Err("Ollama binary not found".to_string()) },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err, "Ollama binary not found");
+    }
+
+    #[test]
+    fn test_current_session_to_analyze() {
+        assert_eq!(current_session_to_analyze(true, 7), Ok(7));
+        assert_eq!(current_session_to_analyze(false, 7), Err("No capture session is active".to_string()));
+        assert_eq!(current_session_to_analyze(true, 0), Err("No capture session is active".to_string()));
+    }
+
+    #[test]
+    fn test_analysis_claim_is_exclusive() {
+        let (analyzing, session) = (AtomicBool::new(false), AtomicI64::new(0));
+        let claim = AnalysisClaim::new(&analyzing, &session, Some(7)).unwrap();
+        assert!(analyzing.load(Ordering::Relaxed));
+        assert_eq!(session.load(Ordering::Relaxed), 7);
+        assert_eq!(
+            AnalysisClaim::new(&analyzing, &session, Some(7)).err(),
+            Some("Session 7 is already being analyzed".to_string())
+        );
+        // Runs share the status, so another session has to wait too
+        assert_eq!(AnalysisClaim::new(&analyzing, &session, Some(3)).err(), Some("Analysis is already running".to_string()));
+        assert_eq!(AnalysisClaim::new(&analyzing, &session, None).err(), Some("Analysis is already running".to_string()));
+        // A refused claim leaves the holder's status alone
+        assert_eq!(session.load(Ordering::Relaxed), 7);
+
+        drop(claim);
+        assert!(!analyzing.load(Ordering::Relaxed));
+        assert_eq!(session.load(Ordering::Relaxed), 0);
+        let pending = AnalysisClaim::new(&analyzing, &session, None).unwrap();
+        pending.set_session(Some(3));
+        assert_eq!(session.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_check_restartable_only_managed() {
+        assert!(check_restartable(true, false).is_ok());
+        assert!(check_restartable(true, true).is_ok());
+        assert!(check_restartable(false, true).unwrap_err().contains("externally"));
+        assert!(check_restartable(false, false).unwrap_err().contains("isn't running"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_then_persist_api_key() {
+        let db = Database::in_memory().unwrap();
+        db.set_setting("ai_api_key", "old-key").unwrap();
+
+        // Failed validation leaves the existing key alone
+        let result = validate_then_persist_api_key(&db, "bad-key", |_| async {
+            Err(crate::ai::AiError::InvalidApiKey)
+        })
+        .await;
+        assert!(result.unwrap_err().contains("Invalid API key"));
+        assert_eq!(db.get_setting("ai_api_key").unwrap(), Some("old-key".to_string()));
+
+        // Empty keys are rejected without calling the validator
+        let result = validate_then_persist_api_key(&db, "  ", |_| async {
+            panic!("validator should not run for an empty key")
+        })
+        .await;
+        assert!(result.is_err());
+
+        // Successful validation persists the trimmed key
+        let result = validate_then_persist_api_key(&db, " new-key ", |k| async move {
+            assert_eq!(k, "new-key");
+            Ok(())
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(db.get_setting("ai_api_key").unwrap(), Some("new-key".to_string()));
+    }
+
+    #[test]
+    fn test_change_screenshots_dir() {
+        let base = std::env::temp_dir().join(format!("rlcollector_test_set_dir_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (old_dir, new_dir) = (base.join("old"), base.join("new"));
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::write(old_dir.join("a.webp"), "a").unwrap();
+        let db = Database::in_memory().unwrap();
+        let current = Mutex::new(old_dir.clone());
+
+        // Invalid paths change nothing
+        assert!(change_screenshots_dir(&db, &current, Path::new("relative"), true).is_err());
+        assert!(change_screenshots_dir(&db, &current, &old_dir.join("nested"), true).is_err());
+        assert_eq!(db.get_screenshots_dir().unwrap(), None);
+        assert_eq!(*current.lock().unwrap(), old_dir);
+
+        assert_eq!(change_screenshots_dir(&db, &current, &new_dir, true).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(new_dir.join("a.webp")).unwrap(), "a");
+        assert!(!old_dir.join("a.webp").exists());
+        assert_eq!(db.get_screenshots_dir().unwrap(), Some(new_dir.clone()));
+        assert_eq!(*current.lock().unwrap(), new_dir);
+
+        // Switching back without moving leaves the files behind
+        assert_eq!(change_screenshots_dir(&db, &current, &old_dir, false).unwrap(), 0);
+        assert!(new_dir.join("a.webp").exists());
+        assert_eq!(db.get_screenshots_dir().unwrap(), Some(old_dir.clone()));
+
+        // ...but not once rows point at files there
+        db.insert_screenshot("a.webp", "2025-01-01T10:00:00", None, 0, None, None).unwrap();
+        assert!(change_screenshots_dir(&db, &current, &new_dir, false).unwrap_err().contains("move them"));
+        assert_eq!(*current.lock().unwrap(), old_dir);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Provider stub for `description_suggestions`: a canned answer, recording the prompt input.
+    struct SuggestingProvider {
+        answer: Result<Vec<String>, String>,
+        seen: Mutex<Vec<(Option<String>, Vec<String>)>>,
+    }
+
+    impl analysis::Provider for SuggestingProvider {
+        fn analyze<'a>(&'a self, _req: analysis::AnalysisRequest<'a>) -> analysis::AnalysisFuture<'a> {
+            unreachable!()
+        }
+        fn suggest_merges<'a>(&'a self, _tasks: &'a [crate::ai::ConsolidationTask<'a>]) -> analysis::MergeFuture<'a> {
+            unreachable!()
+        }
+        fn suggest_descriptions<'a>(&'a self, previous: crate::ai::PreviousSession<'a>) -> analysis::SuggestionFuture<'a> {
+            self.seen.lock().unwrap().push((previous.title.map(str::to_string), previous.tasks.to_vec()));
+            let answer = self.answer.clone().map_err(crate::ai::AiError::ApiError);
+            Box::pin(async move { answer })
+        }
+        fn analyzed_by(&self) -> String {
+            "stub/test".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_description_suggestions_and_fallback() {
+        let mut previous = CaptureSession {
+            id: 7,
+            started_at: "2025-01-01T10:00:00".to_string(),
+            ended_at: Some("2025-01-01T11:00:00".to_string()),
+            screenshot_count: 3,
+            description: Some("Rewrite the parser".to_string()),
+            title: Some("Parser".to_string()),
+            unanalyzed_count: 0,
+            duration_seconds: Some(3600),
+            stats: SessionStats::default(),
+        };
+        let task = |title: &str, description: Option<&str>| Task {
+            id: 1,
+            title: title.to_string(),
+            description: description.map(str::to_string),
+            category: None,
+            started_at: "2025-01-01T10:00:00".to_string(),
+            ended_at: None,
+            ai_reasoning: None,
+            user_verified: false,
+            metadata: None,
+        };
+        let tasks = vec![task("Lexer", Some("Token types")), task("Tests", None)];
+
+        let provider = SuggestingProvider { answer: Ok(vec!["Finish the parser".to_string()]), seen: Mutex::new(Vec::new()) };
+        assert_eq!(description_suggestions(Some(&provider), &previous, &tasks).await, vec!["Finish the parser"]);
+        assert_eq!(
+            provider.seen.lock().unwrap()[0],
+            (Some("Parser".to_string()), vec!["Lexer: Token types".to_string(), "Tests".to_string()])
+        );
+
+        // A failing or missing provider falls back to the previous description, then title
+        let failing = SuggestingProvider { answer: Err("offline".to_string()), seen: Mutex::new(Vec::new()) };
+        assert_eq!(description_suggestions(Some(&failing), &previous, &tasks).await, vec!["Rewrite the parser"]);
+        assert_eq!(description_suggestions(None, &previous, &tasks).await, vec!["Rewrite the parser"]);
+        previous.description = Some("  ".to_string());
+        assert_eq!(description_suggestions(None, &previous, &tasks).await, vec!["Parser"]);
+        previous.title = None;
+        assert!(description_suggestions(None, &previous, &tasks).await.is_empty());
+    }
+
+    #[test]
+    fn test_cached_suggestions() {
+        let now = Instant::now();
+        let cache = Some(CachedSuggestions { at: now, session_id: 7, suggestions: vec!["Parser".to_string()] });
+        assert_eq!(cached_suggestions(&cache, 7, now + Duration::from_secs(60)), Some(vec!["Parser".to_string()]));
+        assert_eq!(cached_suggestions(&cache, 7, now + SUGGESTION_CACHE_TTL), None);
+        // A newer completed session isn't served the old answer
+        assert_eq!(cached_suggestions(&cache, 8, now), None);
+        assert_eq!(cached_suggestions(&None, 7, now), None);
     }
 }
@@ -0,0 +1,110 @@
+use crate::models::CategoryCorrection;
+
+/// Minimum share of a rule's keywords that must appear in a title for the rule to apply.
+pub const MATCH_THRESHOLD: f64 = 0.6;
+
+/// Only the first few keywords of a title take part in matching, so long
+/// AI-generated titles don't match everything.
+const TOP_K: usize = 5;
+
+const STOPWORDS: &[&str] = &[
+    "and", "the", "for", "with", "from", "into", "onto", "about", "via", "using", "over", "new",
+];
+
+/// Normalize a task title into the keywords used for matching: lowercase
+/// alphanumeric words of 3+ characters, stopwords dropped, deduplicated,
+/// in order of first appearance, truncated to the top K.
+pub fn keywords(title: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for word in title
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_lowercase())
+    {
+        if word.chars().count() < 3 || STOPWORDS.contains(&word.as_str()) || out.contains(&word) {
+            continue;
+        }
+        out.push(word);
+        if out.len() == TOP_K {
+            break;
+        }
+    }
+    out
+}
+
+/// Share of the rule's keywords present in `title_keywords` (0.0–1.0).
+fn overlap(title_keywords: &[String], rule_keywords: &[String]) -> f64 {
+    if rule_keywords.is_empty() {
+        return 0.0;
+    }
+    let hits = rule_keywords.iter().filter(|k| title_keywords.contains(k)).count();
+    hits as f64 / rule_keywords.len() as f64
+}
+
+/// Find the learned rule that best matches `title`, if any clears the threshold.
+/// Ties go to the rule the user has applied more often.
+pub fn find_match<'a>(title: &str, rules: &'a [CategoryCorrection]) -> Option<&'a CategoryCorrection> {
+    let title_keywords = keywords(title);
+    if title_keywords.is_empty() {
+        return None;
+    }
+    rules
+        .iter()
+        .map(|rule| (overlap(&title_keywords, &rule.keywords), rule))
+        .filter(|(score, _)| *score >= MATCH_THRESHOLD)
+        .max_by(|(a, ra), (b, rb)| a.total_cmp(b).then(ra.count.cmp(&rb.count)))
+        .map(|(_, rule)| rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, keywords: &[&str], category: &str, count: i64) -> CategoryCorrection {
+        CategoryCorrection {
+            id,
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            category: category.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_keywords_normalizes_title() {
+        assert_eq!(keywords("Reviewing PRs on GitHub"), vec!["reviewing", "prs", "github"]);
+        assert_eq!(keywords("Fix the bug, fix the BUG"), vec!["fix", "bug"]);
+        assert!(keywords("a an to").is_empty());
+    }
+
+    #[test]
+    fn test_keywords_top_k() {
+        let kw = keywords("one two three four five six seven");
+        assert_eq!(kw.len(), TOP_K);
+        assert_eq!(kw[0], "one");
+    }
+
+    #[test]
+    fn test_find_match_above_threshold() {
+        let rules = vec![rule(1, &["reviewing", "prs"], "coding", 3)];
+        let m = find_match("Reviewing PRs in the browser", &rules).unwrap();
+        assert_eq!(m.category, "coding");
+    }
+
+    #[test]
+    fn test_find_match_below_threshold() {
+        let rules = vec![rule(1, &["reviewing", "prs"], "coding", 3)];
+        assert!(find_match("Reviewing design docs", &rules).is_none());
+        assert!(find_match("", &rules).is_none());
+    }
+
+    #[test]
+    fn test_find_match_prefers_best_overlap_then_count() {
+        let rules = vec![
+            rule(1, &["reviewing", "prs", "github"], "browsing", 9),
+            rule(2, &["reviewing", "prs"], "coding", 1),
+            rule(3, &["reviewing", "prs"], "communication", 4),
+        ];
+        // Full overlap on rules 2 and 3 beats 2/3 on rule 1; rule 3 has more corrections
+        let m = find_match("Reviewing PRs", &rules).unwrap();
+        assert_eq!(m.id, 3);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::models::{DiagnosticStep, DiagnosticsReport, StepStatus};
+
+/// Collects `run_diagnostics` step results in order. Steps that need the output of a
+/// failed step are recorded with `skip` instead of being run.
+#[derive(Default)]
+pub struct ReportBuilder {
+    steps: Vec<DiagnosticStep>,
+}
+
+impl ReportBuilder {
+    /// Whether any step so far has failed.
+    pub fn failed(&self) -> bool {
+        self.steps.iter().any(|s| s.status == StepStatus::Failed)
+    }
+
+    /// Record a step that ran: `Ok` holds the success message, `Err` the failure.
+    pub fn record(&mut self, name: &str, result: Result<String, String>) {
+        let (status, message) = match result {
+            Ok(message) => (StepStatus::Passed, message),
+            Err(message) => (StepStatus::Failed, message),
+        };
+        self.steps.push(DiagnosticStep { name: name.to_string(), status, message });
+    }
+
+    /// Record a step that wasn't run because of an earlier failure.
+    pub fn skip(&mut self, name: &str) {
+        let failed = self
+            .steps
+            .iter()
+            .find(|s| s.status == StepStatus::Failed)
+            .map(|s| s.name.as_str())
+            .unwrap_or("an earlier step");
+        self.steps.push(DiagnosticStep {
+            name: name.to_string(),
+            status: StepStatus::Skipped,
+            message: format!("Skipped because {} failed", failed),
+        });
+    }
+
+    pub fn finish(self) -> DiagnosticsReport {
+        DiagnosticsReport {
+            passed: !self.steps.is_empty() && self.steps.iter().all(|s| s.status == StepStatus::Passed),
+            steps: self.steps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_steps_pass() {
+        let mut report = ReportBuilder::default();
+        report.record("capture", Ok("Captured DISPLAY1 (1920x1080)".to_string()));
+        report.record("save", Ok("Saved 48 KB WebP".to_string()));
+        assert!(!report.failed());
+
+        let report = report.finish();
+        assert!(report.passed);
+        assert_eq!(report.steps.len(), 2);
+        assert_eq!(report.steps[1].message, "Saved 48 KB WebP");
+    }
+
+    #[test]
+    fn test_failure_skips_remaining_steps() {
+        let mut report = ReportBuilder::default();
+        report.record("capture", Ok("ok".to_string()));
+        report.record("provider", Err("No API key configured".to_string()));
+        assert!(report.failed());
+        report.skip("analysis");
+
+        let report = report.finish();
+        assert!(!report.passed);
+        let statuses: Vec<StepStatus> = report.steps.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![StepStatus::Passed, StepStatus::Failed, StepStatus::Skipped]);
+        assert_eq!(report.steps[1].message, "No API key configured");
+        assert_eq!(report.steps[2].message, "Skipped because provider failed");
+    }
+
+    #[test]
+    fn test_empty_report_does_not_pass() {
+        assert!(!ReportBuilder::default().finish().passed);
+    }
+
+    #[test]
+    fn test_report_serializes_lowercase_status() {
+        let mut report = ReportBuilder::default();
+        report.record("capture", Err("No monitors found".to_string()));
+        let json = serde_json::to_value(report.finish()).unwrap();
+        assert_eq!(json["steps"][0]["status"], "failed");
+        assert_eq!(json["passed"], false);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::ai::CLAUDE_MODEL;
+use crate::models::{AnalysisCostEstimate, AnalysisEstimate, PendingGroups};
+
+/// Per-group latencies kept for each provider/model; older runs drop off.
+pub const LATENCY_WINDOW: usize = 10;
+
+/// Images are resized to 1280px wide before sending; a 16:9 frame costs about
+/// 1280 × 720 / 750 tokens.
+const IMAGE_TOKENS: f64 = 1229.0;
+/// System prompt, context and instructions sent with every request.
+const PROMPT_TOKENS: f64 = 1000.0;
+/// A terse reply, and the `max_tokens` cap.
+const OUTPUT_TOKENS: (f64, f64) = (100.0, 1024.0);
+
+/// USD per million (input, output) tokens, for models we know the price of.
+fn pricing(model: &str) -> Option<(f64, f64)> {
+    (model == CLAUDE_MODEL).then_some((3.0, 15.0))
+}
+
+/// Add a run's per-group latency (ms) to `history`, keeping the last `LATENCY_WINDOW`.
+pub fn push_latency(history: &mut Vec<f64>, ms_per_group: f64) {
+    history.push(ms_per_group);
+    if history.len() > LATENCY_WINDOW {
+        history.drain(..history.len() - LATENCY_WINDOW);
+    }
+}
+
+/// Estimate analyzing `pending` with `provider`/`model`. Time comes from `history`
+/// (the fastest and slowest recent per-group latency); it's None with no history.
+/// Cost is zero for Ollama and None for Claude models without known pricing.
+/// Ranges assume between one image per group (everything else unchanged) and every
+/// pending screenshot being sent.
+pub fn estimate(provider: &str, model: &str, pending: PendingGroups, sampling: usize, history: &[f64]) -> AnalysisEstimate {
+    let sampling = sampling.max(1) as i64;
+    let requests = (pending.groups + sampling - 1) / sampling;
+    let images_max = (pending.screenshots + sampling - 1) / sampling;
+
+    let minutes = |ms: f64| requests as f64 * ms / 60_000.0;
+    let fastest = history.iter().copied().reduce(f64::min);
+    let slowest = history.iter().copied().reduce(f64::max);
+
+    let cost = |images: i64, output: f64| {
+        let input = requests as f64 * PROMPT_TOKENS + images as f64 * IMAGE_TOKENS;
+        move |(in_price, out_price): (f64, f64)| {
+            (input * in_price + requests as f64 * output * out_price) / 1_000_000.0
+        }
+    };
+    let price = if provider == "ollama" { Some((0.0, 0.0)) } else { pricing(model) };
+
+    AnalysisEstimate {
+        provider: provider.to_string(),
+        model: model.to_string(),
+        sessions: pending.sessions,
+        groups: pending.groups,
+        screenshots: pending.screenshots,
+        requests,
+        minutes_min: fastest.map(minutes),
+        minutes_max: slowest.map(minutes),
+        cost_min_usd: price.map(cost(requests, OUTPUT_TOKENS.0)),
+        cost_max_usd: price.map(cost(images_max, OUTPUT_TOKENS.1)),
+        runs_recorded: history.len(),
+    }
+}
+
+/// Projected cost of analyzing `groups` pending capture groups of one session, at
+/// `tokens_per_call` input tokens per request. Replies are priced at the `max_tokens`
+/// cap, so the figure errs high.
+pub fn session_cost(
+    session_id: i64,
+    provider: &str,
+    model: &str,
+    groups: i64,
+    sampling: usize,
+    tokens_per_call: i64,
+) -> AnalysisCostEstimate {
+    let sampling = sampling.max(1) as i64;
+    let requests = (groups + sampling - 1) / sampling;
+    let input_tokens = requests * tokens_per_call;
+    let output_tokens = requests * OUTPUT_TOKENS.1 as i64;
+    let price = if provider == "ollama" { Some((0.0, 0.0)) } else { pricing(model) };
+    AnalysisCostEstimate {
+        session_id,
+        provider: provider.to_string(),
+        model: model.to_string(),
+        groups,
+        requests,
+        input_tokens,
+        output_tokens,
+        cost_usd: price.map(|(in_price, out_price)| {
+            (input_tokens as f64 * in_price + output_tokens as f64 * out_price) / 1_000_000.0
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(groups: i64, screenshots: i64) -> PendingGroups {
+        PendingGroups { sessions: 2, groups, screenshots }
+    }
+
+    #[test]
+    fn test_push_latency_keeps_window() {
+        let mut history = Vec::new();
+        for ms in 1..=12 {
+            push_latency(&mut history, ms as f64);
+        }
+        assert_eq!(history.len(), LATENCY_WINDOW);
+        assert_eq!(history[0], 3.0);
+        assert_eq!(history[LATENCY_WINDOW - 1], 12.0);
+    }
+
+    #[test]
+    fn test_estimate_without_history_is_unknown() {
+        let est = estimate("ollama", "qwen3-vl:8b", pending(10, 20), 1, &[]);
+        assert_eq!(est.requests, 10);
+        assert_eq!(est.minutes_min, None);
+        assert_eq!(est.minutes_max, None);
+        assert_eq!(est.cost_min_usd, Some(0.0));
+        assert_eq!(est.runs_recorded, 0);
+    }
+
+    #[test]
+    fn test_estimate_minutes_from_history() {
+        let est = estimate("ollama", "qwen3-vl:8b", pending(30, 60), 1, &[4_000.0, 6_000.0, 5_000.0]);
+        assert_eq!(est.minutes_min, Some(2.0));
+        assert_eq!(est.minutes_max, Some(3.0));
+
+        // Sampling every 3rd group sends a third of the requests
+        let sampled = estimate("ollama", "qwen3-vl:8b", pending(30, 60), 3, &[6_000.0]);
+        assert_eq!(sampled.requests, 10);
+        assert_eq!(sampled.minutes_max, Some(1.0));
+    }
+
+    #[test]
+    fn test_estimate_claude_cost() {
+        let est = estimate("claude", CLAUDE_MODEL, pending(100, 300), 1, &[]);
+        // 100 × (1000 + 1229) input at $3/M + 100 × 100 output at $15/M
+        assert!((est.cost_min_usd.unwrap() - 0.8187).abs() < 1e-9);
+        // (100 × 1000 + 300 × 1229) input at $3/M + 100 × 1024 output at $15/M
+        assert!((est.cost_max_usd.unwrap() - 2.9421).abs() < 1e-9);
+
+        let unknown = estimate("claude", "claude-unreleased", pending(100, 300), 1, &[]);
+        assert_eq!(unknown.cost_min_usd, None);
+    }
+
+    #[test]
+    fn test_session_cost() {
+        let est = session_cost(7, "claude", CLAUDE_MODEL, 50, 1, 2500);
+        assert_eq!(est.requests, 50);
+        assert_eq!(est.input_tokens, 125_000);
+        assert_eq!(est.output_tokens, 51_200);
+        // 125k input at $3/M + 51.2k output at $15/M
+        assert!((est.cost_usd.unwrap() - 1.143).abs() < 1e-9);
+
+        // Sampling every 4th group rounds the request count up
+        let sampled = session_cost(7, "claude", CLAUDE_MODEL, 50, 4, 2500);
+        assert_eq!(sampled.requests, 13);
+        assert_eq!(sampled.input_tokens, 32_500);
+
+        assert_eq!(session_cost(7, "ollama", "qwen3-vl:8b", 50, 1, 2500).cost_usd, Some(0.0));
+        assert_eq!(session_cost(7, "claude", "claude-unreleased", 50, 1, 2500).cost_usd, None);
+        assert_eq!(session_cost(7, "claude", CLAUDE_MODEL, 0, 1, 2500).cost_usd, Some(0.0));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::corrections;
+use crate::models::{EvaluationReport, EvaluationRow, ScreenshotLabel, Task};
+
+/// Jaccard overlap (0.0–1.0) of the title keywords (see `corrections::keywords`) of a
+/// label and an AI task title. Titles with no keywords overlap nothing.
+pub fn title_overlap(label: &str, task: &str) -> f64 {
+    let a = corrections::keywords(label);
+    let b = corrections::keywords(task);
+    let shared = a.iter().filter(|k| b.contains(k)).count();
+    let union = a.len() + b.len() - shared;
+    if union == 0 {
+        return 0.0;
+    }
+    shared as f64 / union as f64
+}
+
+/// Compare each human label with the task the AI linked its screenshot to. Labeled
+/// screenshots with no task yet are counted but left out of the accuracy figures,
+/// which are None when nothing could be compared.
+pub fn evaluate(pairs: Vec<(ScreenshotLabel, Option<Task>)>) -> EvaluationReport {
+    let labeled = pairs.len();
+    let rows: Vec<EvaluationRow> = pairs
+        .into_iter()
+        .filter_map(|(label, task)| {
+            let task = task?;
+            let task_category = task.category.unwrap_or_default();
+            Some(EvaluationRow {
+                screenshot_id: label.screenshot_id,
+                category_match: task_category.trim().eq_ignore_ascii_case(label.label_category.trim()),
+                title_overlap: title_overlap(&label.label_title, &task.title),
+                label_title: label.label_title,
+                label_category: label.label_category,
+                task_id: task.id,
+                task_title: task.title,
+                task_category,
+            })
+        })
+        .collect();
+
+    let compared = rows.len();
+    let category_matches = rows.iter().filter(|r| r.category_match).count();
+    let (category_accuracy, mean_title_overlap) = if compared == 0 {
+        (None, None)
+    } else {
+        (
+            Some(category_matches as f64 / compared as f64),
+            Some(rows.iter().map(|r| r.title_overlap).sum::<f64>() / compared as f64),
+        )
+    };
+
+    EvaluationReport {
+        labeled,
+        compared,
+        unanalyzed: labeled - compared,
+        category_matches,
+        category_accuracy,
+        mean_title_overlap,
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(screenshot_id: i64, title: &str, category: &str) -> ScreenshotLabel {
+        ScreenshotLabel {
+            screenshot_id,
+            label_title: title.to_string(),
+            label_category: category.to_string(),
+            labeled_at: "2025-01-02T09:00:00".to_string(),
+        }
+    }
+
+    fn task(id: i64, title: &str, category: &str) -> Option<Task> {
+        Some(Task {
+            id,
+            title: title.to_string(),
+            description: None,
+            category: Some(category.to_string()),
+            started_at: "2025-01-01T10:00:00".to_string(),
+            ended_at: None,
+            ai_reasoning: None,
+            user_verified: false,
+            metadata: None,
+        })
+    }
+
+    #[test]
+    fn test_title_overlap() {
+        assert_eq!(title_overlap("Fixing login bug", "Fixing login bug"), 1.0);
+        // {fixing, login, bug} vs {debugging, login, bug}: 2 shared of 4
+        assert_eq!(title_overlap("Fixing login bug", "Debugging the login bug"), 0.5);
+        assert_eq!(title_overlap("Reading docs", "Writing email"), 0.0);
+        assert_eq!(title_overlap("", "a b"), 0.0);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let report = evaluate(vec![
+            (label(1, "Fixing login bug", "coding"), task(10, "Debugging the login bug", "coding")),
+            (label(2, "Reading API docs", "browsing"), task(11, "Reading API docs", "Coding ")),
+            (label(3, "Writing email", "communication"), None),
+        ]);
+        assert_eq!(report.labeled, 3);
+        assert_eq!(report.compared, 2);
+        assert_eq!(report.unanalyzed, 1);
+        assert_eq!(report.category_matches, 1);
+        assert_eq!(report.category_accuracy, Some(0.5));
+        assert_eq!(report.mean_title_overlap, Some(0.75));
+        assert!(report.rows[0].category_match);
+        assert!(!report.rows[1].category_match);
+        assert_eq!(report.rows[1].task_id, 11);
+    }
+
+    #[test]
+    fn test_evaluate_case_insensitive_category() {
+        let report = evaluate(vec![(label(1, "Coding", "coding"), task(10, "Coding", " CODING"))]);
+        assert_eq!(report.category_accuracy, Some(1.0));
+    }
+
+    #[test]
+    fn test_evaluate_nothing_analyzed() {
+        let report = evaluate(vec![(label(1, "Writing email", "communication"), None)]);
+        assert_eq!(report.compared, 0);
+        assert_eq!(report.category_accuracy, None);
+        assert_eq!(report.mean_title_overlap, None);
+        assert!(evaluate(vec![]).rows.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! Preprocessed images of one analysis run, so a file sent to the provider more than once
+//! in a run (retried groups, re-analysis) is only decoded, resized and encoded once.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Most images kept at once.
+pub const MAX_CACHED_IMAGES: usize = 32;
+
+/// Most base64 bytes kept at once. A payload bigger than this on its own isn't cached.
+pub const MAX_CACHED_BYTES: usize = 64 * 1024 * 1024;
+
+struct Entry {
+    path: PathBuf,
+    image_mode: String,
+    payload: String,
+}
+
+/// Base64 image payloads keyed by file path and `image_mode`, least recently used evicted
+/// first once `MAX_CACHED_IMAGES` or `MAX_CACHED_BYTES` is exceeded. Created for one run
+/// and dropped with it; nothing outlives the run.
+pub struct ImageCache {
+    max_images: usize,
+    max_bytes: usize,
+    /// Oldest use first.
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::with_limits(MAX_CACHED_IMAGES, MAX_CACHED_BYTES)
+    }
+}
+
+impl ImageCache {
+    pub fn with_limits(max_images: usize, max_bytes: usize) -> Self {
+        ImageCache { max_images, max_bytes, entries: Mutex::new(VecDeque::new()) }
+    }
+
+    /// The cached payload for `path` in `image_mode`, or the one `make` produces, which is
+    /// then cached. Errors from `make` are returned and nothing is cached.
+    pub fn get_or_try_insert<E>(&self, path: &Path, image_mode: &str, make: impl FnOnce() -> Result<String, E>) -> Result<String, E> {
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(i) = entries.iter().position(|e| e.path == path && e.image_mode == image_mode) {
+                let entry = entries.remove(i).expect("position is in range");
+                let payload = entry.payload.clone();
+                entries.push_back(entry);
+                return Ok(payload);
+            }
+        }
+        // Not under the lock: preprocessing takes a while and other requests may hit meanwhile
+        let payload = make()?;
+        if payload.len() <= self.max_bytes {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            entries.push_back(Entry { path: path.to_path_buf(), image_mode: image_mode.to_string(), payload: payload.clone() });
+            let mut bytes: usize = entries.iter().map(|e| e.payload.len()).sum();
+            while entries.len() > self.max_images || bytes > self.max_bytes {
+                let Some(evicted) = entries.pop_front() else { break };
+                bytes -= evicted.payload.len();
+            }
+        }
+        Ok(payload)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_hits_and_lru_eviction() {
+        let cache = ImageCache::with_limits(2, 1000);
+        let made = Cell::new(0);
+        let get = |path: &str, mode: &str| {
+            cache.get_or_try_insert(Path::new(path), mode, || {
+                made.set(made.get() + 1);
+                Ok::<_, ()>(format!("{}:{}", path, mode))
+            }).unwrap()
+        };
+
+        assert_eq!(get("a.webp", "downscale"), "a.webp:downscale");
+        assert_eq!(get("a.webp", "downscale"), "a.webp:downscale");
+        assert_eq!(made.get(), 1);
+        // The mode is part of the key
+        get("a.webp", "active_window");
+        assert_eq!(made.get(), 2);
+
+        // a/downscale was used least recently, so it's the one evicted
+        get("a.webp", "active_window");
+        get("b.webp", "downscale");
+        assert_eq!(cache.len(), 2);
+        get("a.webp", "active_window");
+        assert_eq!(made.get(), 3);
+        get("a.webp", "downscale");
+        assert_eq!(made.get(), 4);
+    }
+
+    #[test]
+    fn test_byte_bound_and_errors() {
+        let cache = ImageCache::with_limits(10, 10);
+        let payload = |n: usize| move || Ok::<_, String>("x".repeat(n));
+        cache.get_or_try_insert(Path::new("a"), "m", payload(6)).unwrap();
+        cache.get_or_try_insert(Path::new("b"), "m", payload(6)).unwrap();
+        // Over 10 bytes together: the older one goes
+        assert_eq!(cache.len(), 1);
+        // Too big to cache at all, but still returned
+        assert_eq!(cache.get_or_try_insert(Path::new("c"), "m", payload(11)).unwrap().len(), 11);
+        assert_eq!(cache.len(), 1);
+
+        assert_eq!(cache.get_or_try_insert(Path::new("d"), "m", || Err("unreadable".to_string())), Err("unreadable".to_string()));
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -1,17 +1,51 @@
+mod activity;
 mod ai;
+mod analysis;
+mod apps;
+mod archive;
+mod backup;
 mod capture;
+mod coalesce;
 mod commands;
+mod corrections;
+mod diagnostics;
+mod estimate;
+mod evaluation;
+mod image_cache;
 mod models;
 mod ollama_sidecar;
+mod permissions;
+mod preview;
+mod privacy;
+mod profile;
+mod prompt_template;
+mod region;
+mod reset;
+mod savings;
+mod settings;
 mod storage;
+mod telemetry;
+mod time;
+mod timelapse;
+mod training;
+mod trash;
 mod tray;
 
 use commands::AppState;
-use log::info;
-use std::collections::HashMap;
+use log::{error, info, warn};
+use models::SessionStats;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64};
 use std::sync::{Arc, Mutex};
-use tauri_plugin_log::{Target, TargetKind};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+/// The log file is rotated at this size, keeping `LOG_FILES_KEPT` old files, so logs
+/// stay under ~25 MB.
+const LOG_FILE_MAX_BYTES: u128 = 5_000_000;
+const LOG_FILES_KEPT: usize = 4;
+
+/// Database file name in the app data directory.
+pub(crate) const DB_FILE: &str = "rlcollector.db";
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -27,8 +61,9 @@ pub fn run() {
         eprintln!("Failed to create screenshots directory: {}", e);
         return;
     }
+    permissions::restrict_dir(&app_data_dir.join("screenshots"));
 
-    let db_path = app_data_dir.join("rlcollector.db");
+    let db_path = app_data_dir.join(DB_FILE);
     let db = match storage::Database::new(&db_path) {
         Ok(db) => db,
         Err(e) => {
@@ -37,12 +72,28 @@ pub fn run() {
         }
     };
 
+    // A custom directory that can't be created (e.g. an unplugged drive) falls back
+    // to the default rather than stopping the app from starting.
+    let screenshots_dir = match db.get_screenshots_dir() {
+        Ok(Some(dir)) => match std::fs::create_dir_all(&dir) {
+            Ok(()) => dir,
+            Err(e) => {
+                eprintln!("Screenshots directory {} unavailable ({}), using default", dir.display(), e);
+                app_data_dir.join("screenshots")
+            }
+        },
+        _ => app_data_dir.join("screenshots"),
+    };
+
+    let capture_count = commands::load_capture_count(&db);
     let state = Arc::new(AppState {
         db,
         capturing: AtomicBool::new(false),
+        paused: AtomicBool::new(false),
         capture_interval_ms: AtomicU64::new(30_000),
-        capture_count: AtomicU64::new(0),
-        screenshots_dir: app_data_dir.join("screenshots"),
+        capture_count,
+        session_screenshot_count: AtomicU64::new(0),
+        screenshots_dir: Mutex::new(screenshots_dir),
         current_session_id: AtomicI64::new(0),
         app_data_dir: app_data_dir.clone(),
         ollama_process: ollama_sidecar::OllamaProcess::new(),
@@ -50,8 +101,25 @@ pub fn run() {
         analyzing_session_id: AtomicI64::new(0),
         cancel_analysis: AtomicBool::new(false),
         monitor_states: Mutex::new(HashMap::new()),
+        last_tick_at_ms: AtomicU64::new(0),
+        capture_run: AtomicU64::new(0),
+        capture_loop: AtomicU64::new(0),
+        capture_tick: Mutex::new(()),
+        last_active_monitor: Mutex::new(None),
+        last_cursor_position: Mutex::new(None),
+        session_stats: Mutex::new(SessionStats::default()),
+        ollama_startup: tokio::sync::Mutex::new(()),
+        event_coalescers: Mutex::new(HashMap::new()),
+        recent_errors: Mutex::new(VecDeque::new()),
+        last_analysis_success: Mutex::new(HashMap::new()),
+        highlight_generation: AtomicU64::new(0),
+        highlight_cleanup: Mutex::new(None),
+        last_capture_preview: Mutex::new(None),
+        directory_source: Mutex::new(None),
+        description_suggestions: Mutex::new(None),
     });
 
+    let setup_state = state.clone();
     let app = tauri::Builder::default()
         .plugin(
             tauri_plugin_log::Builder::new()
@@ -59,7 +127,14 @@ pub fn run() {
                     Target::new(TargetKind::Stdout),
                     Target::new(TargetKind::LogDir { file_name: None }),
                 ])
-                .level(log::LevelFilter::Debug)
+                // Everything reaches the plugin; `log_level` sets the effective
+                // filter at startup and via `set_log_level`.
+                .level(log::LevelFilter::Trace)
+                .level_for("hyper", log::LevelFilter::Info)
+                .level_for("hyper_util", log::LevelFilter::Info)
+                .level_for("reqwest", log::LevelFilter::Info)
+                .max_file_size(LOG_FILE_MAX_BYTES)
+                .rotation_strategy(RotationStrategy::KeepSome(LOG_FILES_KEPT))
                 .build(),
         )
         .plugin(tauri_plugin_opener::init())
@@ -68,32 +143,100 @@ pub fn run() {
             commands::get_capture_status,
             commands::start_capture,
             commands::stop_capture,
+            commands::cancel_capture,
+            commands::pause_capture,
+            commands::resume_capture,
+            commands::finalize_session,
+            commands::get_session_duration,
+            commands::get_session_gaps,
+            commands::get_session_top_apps,
+            commands::normalize_session_bounds,
+            commands::set_screenshot_label,
+            commands::get_screenshot_label,
+            commands::list_labeled_screenshots,
+            commands::evaluate_analysis,
+            commands::get_session_capture_groups,
+            commands::backfill_capture_groups,
+            commands::get_category_corrections,
+            commands::delete_category_correction,
             commands::get_current_session,
             commands::get_tasks,
+            commands::get_tasks_by_category_in_range,
+            commands::get_daily_task_counts,
             commands::get_task,
+            commands::get_task_detail,
+            commands::get_analysis_debug,
+            commands::get_tasks_with_provider,
+            commands::get_capture_events,
+            commands::get_savings_summary,
+            commands::export_all,
+            commands::import_all,
+            commands::export_settings,
+            commands::import_settings,
+            commands::import_activity_log,
+            commands::factory_reset,
+            commands::export_training_data,
+            commands::export_session_archive,
             commands::update_task,
             commands::delete_task,
             commands::get_setting,
             commands::update_setting,
+            commands::get_settings_schema,
+            commands::render_prompt_preview,
+            commands::set_and_validate_api_key,
             commands::analyze_pending,
+            commands::analyze_unassigned,
             commands::analyze_session,
+            commands::analyze_current_session,
+            commands::reanalyze_unverified,
+            commands::reanalyze_capture_group,
             commands::analyze_all_pending,
+            commands::get_system_load,
+            commands::estimate_pending_analysis,
+            commands::estimate_analysis_cost,
             commands::delete_session,
+            commands::delete_screenshot,
+            commands::restore_from_trash,
+            commands::get_trash,
+            commands::purge_trash,
+            commands::reconcile_legacy_screenshots,
             commands::get_analysis_status,
+            commands::get_recent_errors,
             commands::cancel_analysis,
             commands::clear_pending,
+            commands::archive_pending_as_task,
             commands::get_pending_sessions,
             commands::get_completed_sessions,
+            commands::get_session_status,
+            commands::suggest_session_description,
+            commands::get_unassigned_summary,
+            commands::assign_screenshots_to_session,
             commands::get_log_path,
+            commands::set_log_level,
             commands::get_sessions,
             commands::get_session_screenshots,
             commands::get_session_tasks,
             commands::get_task_for_screenshot,
+            commands::get_screenshot_neighbors,
+            commands::get_latest_screenshot,
             commands::get_screenshots_dir,
+            commands::set_screenshots_dir,
             commands::get_monitors,
+            commands::get_capture_capability,
             commands::highlight_monitors,
+            commands::set_monitor_mode,
+            commands::get_capture_preview,
+            commands::get_monitor_change_state,
+            commands::select_capture_region,
+            commands::get_capture_region,
+            commands::run_diagnostics,
+            commands::generate_timelapse,
             commands::check_ollama,
+            commands::get_provider_health,
             commands::ensure_ollama,
+            commands::restart_ollama,
+            commands::get_ollama_models_dir,
+            commands::purge_ollama_models,
             commands::ollama_pull,
         ])
         .setup(move |app| {
@@ -102,7 +245,32 @@ pub fn run() {
                 log::error!("PANIC: {}", info);
             }));
 
+            match setup_state.db.get_setting_string("log_level", "info") {
+                Ok(level) => commands::apply_log_level(&level),
+                Err(e) => error!("Failed to read log level: {}", e),
+            }
             info!("RLCollector started, data dir: {}", app_data_dir.display());
+            let swept = capture::sweep_temp_files(&setup_state.screenshots_dir());
+            if swept > 0 {
+                info!("Removed {} partially written screenshot(s) left by a previous crash", swept);
+            }
+            if let Err(e) = commands::sweep_trash(&setup_state.db, &setup_state.screenshots_dir()) {
+                error!("Failed to purge old trash: {}", e);
+            }
+            if let Err(e) = commands::sweep_analysis_debug(&setup_state.db, &app_data_dir, &setup_state.screenshots_dir()) {
+                error!("Failed to remove expired analysis debug data: {}", e);
+            }
+            let fixed = permissions::repair(&app_data_dir.join(DB_FILE), &setup_state.screenshots_dir());
+            if fixed > 0 {
+                warn!("Restricted permissions of {} file(s) other users could read", fixed);
+            }
+            match setup_state.db.reconcile_legacy_screenshots_once() {
+                Ok(Some(ids)) if !ids.is_empty() => {
+                    warn!("Moved sessionless screenshots from an older version into {} imported session(s)", ids.len());
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to reconcile legacy screenshots: {}", e),
+            }
             tray::setup_tray(app.handle())?;
 
             Ok(())
@@ -113,6 +281,7 @@ pub fn run() {
     app.run(move |_app_handle, event| {
         if let tauri::RunEvent::Exit = event {
             info!("Application exiting, stopping managed Ollama process");
+            state.persist_capture_count();
             state.ollama_process.stop();
         }
     });
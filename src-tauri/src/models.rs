@@ -8,6 +8,20 @@ pub struct Screenshot {
     pub active_window_title: Option<String>,
     pub monitor_index: i32,
     pub capture_group: Option<String>,
+    /// Same-tick screenshot of a mirrored display this one duplicates; not sent to the AI.
+    pub mirrored_of: Option<i64>,
+    /// Taken of the previously active monitor (`capture_previous_active`).
+    pub previous_active: bool,
+    /// Display scale factor; None for screenshots taken before it was recorded.
+    pub scale_factor: Option<f64>,
+    /// Taken of the primary monitor; None for screenshots taken before it was recorded.
+    pub is_primary: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenshotNeighbors {
+    pub prev: Option<Screenshot>,
+    pub next: Option<Screenshot>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +35,49 @@ pub struct MonitorInfo {
     pub is_primary: bool,
 }
 
+/// Whether screen capture can work in this environment, checked before starting a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureCapability {
+    pub can_capture: bool,
+    /// Why capture can't work; None when `can_capture`.
+    pub reason: Option<String>,
+    /// Running in a remote desktop session (Windows), where capture may stop once
+    /// the client is minimized or disconnected.
+    pub remote_session: bool,
+    pub monitor_count: u32,
+    /// A test capture of the primary monitor succeeded.
+    pub probe_ok: bool,
+}
+
+/// A capture rectangle in physical pixels, relative to the top-left of its monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub monitor_id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Outcome of `select_capture_region`: the stored rectangle, or `cancelled` when the
+/// user pressed Escape or the overlay timed out.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RegionSelection {
+    Selected { region: CaptureRegion },
+    Cancelled,
+}
+
+/// One monitor's frame of `get_capture_preview`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewFrame {
+    pub monitor_id: u32,
+    pub monitor_name: String,
+    pub width: u32,
+    pub height: u32,
+    pub webp_base64: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: i64,
@@ -37,10 +94,75 @@ pub struct Task {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaptureStatus {
     pub active: bool,
+    pub paused: bool,
     pub interval_ms: u64,
     pub count: u64,
     pub monitor_mode: String,
     pub monitors_captured: u32,
+    pub session_screenshot_count: u64,
+    /// 0 means no limit.
+    pub max_screenshots_per_session: u64,
+    /// Monitor the latest "active" mode tick resolved the cursor to; None in other modes.
+    pub active_monitor_id: Option<u32>,
+}
+
+/// Payload of the `capture-warning` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureWarning {
+    pub session_id: i64,
+    pub message: String,
+}
+
+/// An analysis failure kept for `get_recent_errors`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentError {
+    pub timestamp: String,
+    pub session_id: Option<i64>,
+    /// `ai_provider` at the time ("claude" or "ollama").
+    pub provider: String,
+    pub message: String,
+}
+
+/// Result of a lightweight request to a provider's API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderProbe {
+    pub reachable: bool,
+    /// Round trip of the probe; None when unreachable.
+    pub latency_ms: Option<u64>,
+    /// Whether the credentials were accepted; None when that couldn't be told or the
+    /// provider has no authentication (Ollama).
+    pub authenticated: Option<bool>,
+    pub error: Option<String>,
+}
+
+/// One provider's status for `get_provider_health`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    /// "claude" or "ollama".
+    pub provider: String,
+    /// Whether it's the selected `ai_provider`.
+    pub active: bool,
+    /// Claude: an API key is stored. Ollama: a model is set.
+    pub configured: bool,
+    pub model: Option<String>,
+    /// None when not probed (Claude without an API key).
+    pub probe: Option<ProviderProbe>,
+    /// Ollama: whether the configured model is pulled; None if unknown.
+    pub model_available: Option<bool>,
+    /// Ollama: "managed" (our process), "external" (running on its own), "bundled" /
+    /// "installed" (binary in the app data dir / on PATH, not running) or "missing".
+    pub ollama_source: Option<String>,
+    /// Since the app started.
+    pub last_success_at: Option<String>,
+    pub last_error: Option<RecentError>,
+}
+
+/// Payload of the `timelapse-progress` and `archive-progress` events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub session_id: i64,
+    pub done: usize,
+    pub total: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +183,299 @@ pub struct CaptureSession {
     pub description: Option<String>,
     pub title: Option<String>,
     pub unanalyzed_count: i64,
+    /// Active seconds between start and end, excluding pauses. None until the session ends.
+    pub duration_seconds: Option<i64>,
+    pub stats: SessionStats,
+}
+
+/// One monitor's change-detection state, from `get_monitor_change_state`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorChangeState {
+    pub monitor_id: u32,
+    pub monitor_name: String,
+    /// Last perceptual hash, 64 hex digits.
+    pub last_hash: String,
+    /// Distance of the most recent frame from the hash before it; None after one frame.
+    pub last_distance: Option<u32>,
+}
+
+/// Where a session stands, from `Database::get_session_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    /// Not ended yet.
+    Active,
+    /// Ended with unanalyzed screenshots left.
+    Pending,
+    /// Ended, with screenshots, all of them analyzed (or excluded).
+    Completed,
+    /// Ended without a single screenshot.
+    Empty,
+}
+
+/// Change-detection and analysis counters for a session, stored as JSON in
+/// `capture_sessions.stats`. Capture counters are written when the session ends;
+/// analysis counters after each analysis run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionStats {
+    /// Capture ticks that grabbed the screen (paused and privacy-skipped ticks excluded).
+    pub ticks: u64,
+    /// Ticks where no monitor changed, so nothing was saved.
+    pub ticks_unchanged: u64,
+    pub screenshots_saved: u64,
+    /// Monitor captures discarded because they matched the previous one.
+    pub screenshots_unchanged: u64,
+    pub bytes_written: u64,
+    /// Capture groups sent to the AI.
+    pub groups_analyzed: u64,
+    /// Screenshots linked to a task without their own AI call (sampling, monitor scope).
+    pub screenshots_linked_without_ai: u64,
+    /// Machine load during local (Ollama) analysis runs, combined across runs.
+    pub analysis_resources: Option<ResourceUsage>,
+}
+
+/// CPU (percent of one core, so it can exceed 100) and resident memory of one process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessLoad {
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+}
+
+/// Instantaneous machine load. Anything that couldn't be read is None; `ollama` is
+/// None unless we manage the Ollama process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemLoad {
+    pub cpu_percent: Option<f32>,
+    pub memory_used_bytes: Option<u64>,
+    pub memory_total_bytes: Option<u64>,
+    pub app: Option<ProcessLoad>,
+    pub ollama: Option<ProcessLoad>,
+}
+
+/// Peak and average load of one process over the samples it appeared in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    pub samples: u32,
+    pub peak_cpu_percent: f32,
+    pub avg_cpu_percent: f32,
+    pub peak_memory_bytes: u64,
+    pub avg_memory_bytes: u64,
+}
+
+/// Load sampled during an analysis run (see `telemetry`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub samples: u32,
+    pub app: Option<ProcessUsage>,
+    pub ollama: Option<ProcessUsage>,
+}
+
+/// Payload of the `analysis-progress` event, sent per capture group once its screenshots
+/// are linked, and of `task-created` when the group started a new task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalysisProgress {
+    pub session_id: Option<i64>,
+    /// None for screenshots captured before capture groups existed.
+    pub capture_group: Option<String>,
+    pub task_id: i64,
+    pub new_task: bool,
+    /// Every screenshot linked to the task for this group, including sampled-out,
+    /// out-of-scope and mirrored ones.
+    pub screenshot_ids: Vec<i64>,
+    pub failed_links: u32,
+    /// Capture groups handled so far, of `total`.
+    pub done: u32,
+    pub total: u32,
+}
+
+/// Payload of the `analysis-complete` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisComplete {
+    pub session_id: Option<i64>,
+    pub processed: u32,
+    /// Screenshot-to-task links that failed to save; those screenshots stay pending.
+    pub failed_links: u32,
+    /// None for cloud providers and runs too short to sample.
+    pub resources: Option<ResourceUsage>,
+}
+
+/// Aggregate of `SessionStats` over a date range, with estimated savings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavingsSummary {
+    pub sessions: i64,
+    pub stats: SessionStats,
+    pub unchanged_tick_pct: f64,
+    pub estimated_bytes_saved: u64,
+    pub estimated_requests_saved: u64,
+    pub message: String,
+}
+
+/// A stretch of a session with no screenshots, between two consecutive captures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionGap {
+    pub start: String,
+    pub end: String,
+    pub gap_seconds: i64,
+}
+
+/// An application's share of a session, from the window titles of its screenshots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppUsage {
+    pub app: String,
+    pub screenshots: u32,
+    /// Fraction (0–1) of the session's screenshots that have a window title.
+    pub share: f64,
+}
+
+/// A settings profile entry that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedSetting {
+    pub key: String,
+    pub reason: String,
+}
+
+/// One deletion or re-creation done by `factory_reset`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResetItem {
+    pub path: String,
+    /// "deleted" or "recreated".
+    pub action: String,
+    /// None if it succeeded.
+    pub error: Option<String>,
+}
+
+/// Result of `factory_reset`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactoryReset {
+    pub items: Vec<ResetItem>,
+    /// Every item succeeded and the verification pass found nothing left.
+    pub verified: bool,
+    /// What the verification pass still found: files in the cleared directories, or
+    /// data in the database.
+    pub leftovers: Vec<String>,
+}
+
+/// Result of `import_activity_log`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityImport {
+    pub imported: u32,
+    /// Imported samples that fell within a capture session.
+    pub in_session: u32,
+    /// Bad JSON, no title, or an unreadable timestamp.
+    pub skipped_malformed: u32,
+    /// Titles matching `blocked_title_keywords`.
+    pub skipped_blocked: u32,
+    /// Already imported (same time, title and app).
+    pub skipped_duplicate: u32,
+}
+
+impl ActivityImport {
+    pub fn add(&mut self, other: &ActivityImport) {
+        self.imported += other.imported;
+        self.in_session += other.in_session;
+        self.skipped_malformed += other.skipped_malformed;
+        self.skipped_blocked += other.skipped_blocked;
+        self.skipped_duplicate += other.skipped_duplicate;
+    }
+}
+
+/// Result of `import_settings`. If anything was rejected, nothing was applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsImport {
+    pub applied: Vec<String>,
+    /// Keys this version doesn't know, e.g. from a newer build.
+    pub skipped_unknown: Vec<String>,
+    /// Keys already set here, left alone because `overwrite` was off.
+    pub skipped_existing: Vec<String>,
+    pub rejected: Vec<RejectedSetting>,
+}
+
+/// One capture group of a session; see `get_session_capture_groups`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureGroupSummary {
+    pub group: String,
+    pub screenshot_count: i64,
+    /// Earliest capture in the group.
+    pub captured_at: String,
+}
+
+/// Result of `export_session_archive`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionArchive {
+    pub files_added: usize,
+    /// Screenshot paths (relative to the screenshots dir) whose file couldn't be read.
+    pub missing_files: Vec<String>,
+    pub bytes_written: u64,
+}
+
+/// Result of `reanalyze_capture_group`: the task the group was linked to before and
+/// after. The old task may no longer exist; either is None when there was none.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupReanalysis {
+    pub capture_group: String,
+    pub old_task_id: Option<i64>,
+    pub new_task_id: Option<i64>,
+}
+
+/// Tasks started on one local date (`YYYY-MM-DD`), from `get_daily_task_counts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyTaskCount {
+    pub date: String,
+    pub count: i64,
+}
+
+/// Unanalyzed screenshots with no session, from `get_unassigned_summary`. The dates
+/// are None when there are none.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnassignedSummary {
+    pub count: i64,
+    pub first_captured_at: Option<String>,
+    pub last_captured_at: Option<String>,
+}
+
+/// Unanalyzed screenshots in ended sessions, counted by capture group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingGroups {
+    pub sessions: i64,
+    pub groups: i64,
+    pub screenshots: i64,
+}
+
+/// Rough time and cost of analyzing everything pending. Minutes are None until a run
+/// with this provider/model has been timed; cost is None for unpriced models.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisEstimate {
+    pub provider: String,
+    pub model: String,
+    pub sessions: i64,
+    pub groups: i64,
+    pub screenshots: i64,
+    /// Provider requests after `analysis_sampling`.
+    pub requests: i64,
+    pub minutes_min: Option<f64>,
+    pub minutes_max: Option<f64>,
+    pub cost_min_usd: Option<f64>,
+    pub cost_max_usd: Option<f64>,
+    /// Past runs the time estimate is based on.
+    pub runs_recorded: usize,
+}
+
+/// Projected cost of analyzing one session, shown before it's analyzed by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisCostEstimate {
+    pub session_id: i64,
+    pub provider: String,
+    pub model: String,
+    pub groups: i64,
+    /// Provider requests after `analysis_sampling`.
+    pub requests: i64,
+    /// `requests` × `analysis_tokens_per_call`.
+    pub input_tokens: i64,
+    /// `requests` × the response cap, so the cost is an upper bound.
+    pub output_tokens: i64,
+    /// None for Claude models without known pricing.
+    pub cost_usd: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,8 +485,209 @@ pub struct OllamaStatus {
     pub source: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaModelsDir {
+    pub path: String,
+    pub size_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisStatus {
     pub analyzing: bool,
     pub session_id: Option<i64>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingSchema {
+    pub key: String,
+    pub kind: String,
+    pub options: Vec<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub default: Option<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTask {
+    pub session_id: Option<i64>,
+    pub task_id: i64,
+    pub screenshot_count: u32,
+}
+
+/// How many unanalyzed screenshots `clear_pending` removed from one session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingCleared {
+    pub session_id: Option<i64>,
+    pub count: u32,
+}
+
+/// What a trash entry holds; see `restore_from_trash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Session,
+    Screenshot,
+}
+
+/// A deleted session or screenshot that can still be restored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub kind: TrashKind,
+    /// Id of the trash entry, passed to `restore_from_trash`.
+    pub trash_id: i64,
+    /// Id the session or screenshot had before it was deleted.
+    pub original_id: i64,
+    /// Session title (or start time); screenshot window title (or capture time).
+    pub label: String,
+    pub trashed_at: String,
+    pub screenshot_count: u32,
+}
+
+/// A category rule learned from the user recategorizing AI-created tasks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCorrection {
+    pub id: i64,
+    /// Normalized title keywords the rule matches on
+    pub keywords: Vec<String>,
+    pub category: String,
+    /// How many times the user has made this correction
+    pub count: i64,
+}
+
+/// A task with its linked screenshots, for the task detail view in one round-trip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskDetail {
+    pub task: Task,
+    pub screenshots: Vec<Screenshot>,
+    /// `task.metadata` parsed as JSON; `None` if absent or not valid JSON.
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A task plus the provider/model that produced it, read from `metadata.analyzed_by`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskWithProvider {
+    #[serde(flatten)]
+    pub task: Task,
+    pub analyzed_by: Option<String>,
+}
+
+/// One screenshot with the task it was assigned to, as a line of a training-data export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingRow {
+    pub screenshot_id: i64,
+    pub filepath: String,
+    pub captured_at: String,
+    pub active_window_title: Option<String>,
+    pub monitor_index: i32,
+    pub session_id: Option<i64>,
+    pub session_description: Option<String>,
+    pub task_id: i64,
+    pub task_title: String,
+    pub category: Option<String>,
+    pub user_verified: bool,
+}
+
+/// Summary returned by `export_training_data`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingExport {
+    pub rows: u64,
+    pub bytes_written: u64,
+    pub images_copied: u64,
+    /// Images that couldn't be copied; their rows are left out of the export.
+    pub missing_images: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Passed,
+    Failed,
+    /// Not run because an earlier step failed.
+    Skipped,
+}
+
+/// One step of a `run_diagnostics` smoke test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticStep {
+    pub name: String,
+    pub status: StepStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    /// True only if every step passed.
+    pub passed: bool,
+    pub steps: Vec<DiagnosticStep>,
+}
+
+/// Something notable that happened to a capture tick, e.g. a `skipped_private` tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureEvent {
+    pub id: i64,
+    pub session_id: Option<i64>,
+    pub occurred_at: String,
+    pub kind: String,
+    pub detail: Option<String>,
+}
+
+/// What one analysis request sent to the AI, saved when `save_analysis_inputs` is on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisDebugEntry {
+    pub id: i64,
+    /// Identifies the `run_analysis` call (its start time).
+    pub run_id: String,
+    pub capture_group: String,
+    /// The fully rendered prompt text.
+    pub prompt: String,
+    /// The preprocessed WebP images, in the order they were attached. Relative to the
+    /// app data dir in the database; `get_analysis_debug` returns absolute paths.
+    pub image_paths: Vec<String>,
+    pub created_at: String,
+}
+
+/// A human-assigned ground-truth title and category for one screenshot. Kept apart
+/// from tasks so re-analysis never overwrites it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenshotLabel {
+    pub screenshot_id: i64,
+    pub label_title: String,
+    pub label_category: String,
+    pub labeled_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledScreenshot {
+    pub screenshot: Screenshot,
+    pub label: ScreenshotLabel,
+}
+
+/// One labeled screenshot compared with the task the AI linked it to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationRow {
+    pub screenshot_id: i64,
+    pub label_title: String,
+    pub label_category: String,
+    pub task_id: i64,
+    pub task_title: String,
+    pub task_category: String,
+    pub category_match: bool,
+    /// Keyword overlap between the two titles, 0.0–1.0.
+    pub title_overlap: f64,
+}
+
+/// Result of `evaluate_analysis` for a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub labeled: usize,
+    /// Labeled screenshots that have a linked task.
+    pub compared: usize,
+    /// Labeled screenshots not analyzed yet; left out of the figures below.
+    pub unanalyzed: usize,
+    pub category_matches: usize,
+    /// None when nothing could be compared.
+    pub category_accuracy: Option<f64>,
+    pub mean_title_overlap: Option<f64>,
+    pub rows: Vec<EvaluationRow>,
+}
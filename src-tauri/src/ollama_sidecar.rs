@@ -4,6 +4,9 @@ use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::Mutex;
 
+/// Address the managed Ollama listens on.
+const OLLAMA_ADDR: &str = "127.0.0.1:11434";
+
 /// Manages an optional Ollama child process that we started ourselves.
 pub struct OllamaProcess {
     child: Mutex<Option<Child>>,
@@ -59,8 +62,10 @@ impl OllamaProcess {
         None
     }
 
-    /// Start Ollama serve as a child process. Returns error if already running or spawn fails.
-    pub fn start(&self, binary_path: &Path) -> Result<(), String> {
+    /// Start Ollama serve as a child process, storing models in `models_dir`.
+    /// Does nothing if our previous child is still running; returns error if spawn fails.
+    /// The lock is held from the liveness check through storing the new child.
+    pub fn start(&self, binary_path: &Path, models_dir: &Path) -> Result<(), String> {
         let mut guard = self.child.lock().map_err(|e| e.to_string())?;
 
         // Check if we already have a running child
@@ -80,17 +85,22 @@ impl OllamaProcess {
             }
         }
 
-        info!("Starting Ollama serve from {}", binary_path.display());
+        std::fs::create_dir_all(models_dir)
+            .map_err(|e| format!("Failed to create Ollama models directory: {}", e))?;
+
+        info!("Starting Ollama serve from {} (models in {})", binary_path.display(), models_dir.display());
+        // OLLAMA_MODELS only applies to our child; an externally-run Ollama keeps its own location
         let child_proc = Command::new(binary_path)
             .arg("serve")
-            .env("OLLAMA_HOST", "127.0.0.1:11434")
+            .env("OLLAMA_HOST", OLLAMA_ADDR)
+            .env("OLLAMA_MODELS", models_dir)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
             .map_err(|e| format!("Failed to start Ollama: {}", e))?;
 
         info!("Ollama process started with PID {}", child_proc.id());
-        *guard = Some(child_proc);
+        store_child(&mut guard, child_proc);
         Ok(())
     }
 
@@ -128,6 +138,31 @@ impl OllamaProcess {
             false
         }
     }
+
+    /// PID of the Ollama process we manage, if it is still running.
+    pub fn pid(&self) -> Option<u32> {
+        if !self.is_managed() {
+            return None;
+        }
+        self.child.lock().ok()?.as_ref().map(|child| child.id())
+    }
+}
+
+/// Store a freshly spawned child in `slot`. If the previous child turns out to be alive
+/// after all (its status check failed earlier), the new one is killed instead, so we
+/// never drop a `Child` handle and leak an orphan `ollama serve` fighting over the port.
+fn store_child(slot: &mut Option<Child>, mut child: Child) {
+    if let Some(previous) = slot.as_mut() {
+        if matches!(previous.try_wait(), Ok(None)) {
+            warn!("Previous Ollama process is still running; stopping duplicate PID {}", child.id());
+            if let Err(e) = child.kill() {
+                debug!("Kill returned error (may already be exited): {}", e);
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+    *slot = Some(child);
 }
 
 impl Drop for OllamaProcess {
@@ -136,6 +171,50 @@ impl Drop for OllamaProcess {
     }
 }
 
+/// Where the managed Ollama stores models, so they're removed with the app's data.
+pub fn models_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join("ollama_models")
+}
+
+/// Total size in bytes of all files under `path` (0 if it doesn't exist).
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(ft) if ft.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Delete everything inside `path`, keeping the directory itself. Returns bytes freed.
+pub fn purge_dir_contents(path: &Path) -> std::io::Result<u64> {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut freed = 0;
+    for entry in entries {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry.file_type()?.is_dir() {
+            let size = dir_size(&entry_path);
+            std::fs::remove_dir_all(&entry_path)?;
+            freed += size;
+        } else {
+            let size = entry.metadata()?.len();
+            std::fs::remove_file(&entry_path)?;
+            freed += size;
+        }
+    }
+    Ok(freed)
+}
+
 /// Poll Ollama's API until it responds, or give up after `max_attempts` tries (500ms apart).
 pub async fn wait_for_ready(client: &Client, max_attempts: u32) -> Result<(), String> {
     for attempt in 1..=max_attempts {
@@ -167,6 +246,22 @@ pub async fn wait_for_ready(client: &Client, max_attempts: u32) -> Result<(), St
     ))
 }
 
+/// Wait until nothing is listening on Ollama's port (a stopped process can hold it briefly),
+/// giving up after `max_attempts` tries (250ms apart).
+pub async fn wait_for_port_free(max_attempts: u32) -> Result<(), String> {
+    for attempt in 1..=max_attempts {
+        match std::net::TcpListener::bind(OLLAMA_ADDR) {
+            Ok(_) => {
+                debug!("Ollama port free after {} attempt(s)", attempt);
+                return Ok(());
+            }
+            Err(e) => debug!("Ollama port still in use (attempt {}/{}): {}", attempt, max_attempts, e),
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    Err(format!("Ollama port {} was not released after {} attempts", OLLAMA_ADDR, max_attempts))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -185,6 +280,24 @@ mod tests {
         let _ = result;
     }
 
+    #[test]
+    fn test_models_dir_size_and_purge() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_ollama_models_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(dir_size(&dir), 0);
+        assert_eq!(purge_dir_contents(&dir).unwrap(), 0);
+
+        std::fs::create_dir_all(dir.join("blobs")).unwrap();
+        std::fs::write(dir.join("blobs").join("sha256-abc"), vec![0u8; 1000]).unwrap();
+        std::fs::write(dir.join("manifest"), vec![0u8; 24]).unwrap();
+        assert_eq!(dir_size(&dir), 1024);
+
+        assert_eq!(purge_dir_contents(&dir).unwrap(), 1024);
+        assert!(dir.is_dir());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_stop_when_not_running() {
         let proc = OllamaProcess::new();
@@ -0,0 +1,158 @@
+//! Owner-only permissions for files holding private data: the database (which stores
+//! the API key) and the screenshots directory. Set when they're created, repaired at
+//! startup, and reported by `run_diagnostics`. Only Unix modes are managed; on Windows
+//! the per-user app data directory's ACL already keeps other users out.
+
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+/// Mode for the database and its WAL/shared-memory files.
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+/// Mode for directories holding screenshots.
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// The database file plus the `-wal` / `-shm` files SQLite keeps next to it.
+pub fn database_files(db_path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![db_path.to_path_buf()];
+    for suffix in ["-wal", "-shm"] {
+        let mut name = db_path.as_os_str().to_os_string();
+        name.push(suffix);
+        files.push(PathBuf::from(name));
+    }
+    files
+}
+
+/// Permission bits of `path` (e.g. `0o600`); None if it doesn't exist or on non-Unix.
+#[cfg(unix)]
+pub fn mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).ok().map(|m| m.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+pub fn mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Whether `mode` gives the group or other users any access.
+pub fn is_too_open(mode: u32) -> bool {
+    mode & 0o077 != 0
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Restrict `path` to `wanted` if it's more open than that. Returns the old mode when
+/// it was changed; missing paths are skipped.
+fn restrict(path: &Path, wanted: u32) -> Option<u32> {
+    let current = mode(path)?;
+    if !is_too_open(current) {
+        return None;
+    }
+    match set_mode(path, wanted) {
+        Ok(()) => Some(current),
+        Err(e) => {
+            warn!("Could not restrict permissions of {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Make the database files readable by the owner only. Returns how many were changed.
+pub fn restrict_database(db_path: &Path) -> usize {
+    database_files(db_path)
+        .iter()
+        .filter(|path| restrict(path, PRIVATE_FILE_MODE).is_some())
+        .count()
+}
+
+/// Make a screenshots directory accessible to the owner only. Returns whether it changed.
+pub fn restrict_dir(dir: &Path) -> bool {
+    restrict(dir, PRIVATE_DIR_MODE).is_some()
+}
+
+/// Check and repair the database files and screenshots directory, logging each fix.
+/// Returns how many paths were fixed.
+pub fn repair(db_path: &Path, screenshots_dir: &Path) -> usize {
+    let mut fixed = 0;
+    for path in database_files(db_path) {
+        if let Some(old) = restrict(&path, PRIVATE_FILE_MODE) {
+            info!("Restricted {} from {:o} to {:o}", path.display(), old, PRIVATE_FILE_MODE);
+            fixed += 1;
+        }
+    }
+    if let Some(old) = restrict(screenshots_dir, PRIVATE_DIR_MODE) {
+        info!("Restricted {} from {:o} to {:o}", screenshots_dir.display(), old, PRIVATE_DIR_MODE);
+        fixed += 1;
+    }
+    fixed
+}
+
+/// One line for diagnostics: the current modes, or which paths other users can read.
+pub fn describe(db_path: &Path, screenshots_dir: &Path) -> Result<String, String> {
+    let (Some(db_mode), Some(dir_mode)) = (mode(db_path), mode(screenshots_dir)) else {
+        return Ok("Permissions are not checked on this platform".to_string());
+    };
+    let mut open = Vec::new();
+    if is_too_open(db_mode) {
+        open.push(format!("database is {:o}", db_mode));
+    }
+    if is_too_open(dir_mode) {
+        open.push(format!("screenshots directory is {:o}", dir_mode));
+    }
+    if open.is_empty() {
+        Ok(format!("Database {:o}, screenshots directory {:o}", db_mode, dir_mode))
+    } else {
+        Err(format!("Readable by other users: {} (restart to repair)", open.join(", ")))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_restricts_open_paths() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_perms_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let shots = dir.join("screenshots");
+        std::fs::create_dir_all(&shots).unwrap();
+        let db = dir.join("test.db");
+        std::fs::write(&db, b"").unwrap();
+        set_mode(&db, 0o644).unwrap();
+        set_mode(&shots, 0o755).unwrap();
+
+        assert!(describe(&db, &shots).unwrap_err().contains("database is 644"));
+        // The missing -wal / -shm files are skipped
+        assert_eq!(repair(&db, &shots), 2);
+        assert_eq!(mode(&db), Some(0o600));
+        assert_eq!(mode(&shots), Some(0o700));
+        assert_eq!(describe(&db, &shots).unwrap(), "Database 600, screenshots directory 700");
+        assert_eq!(repair(&db, &shots), 0);
+
+        // Tighter than required is left alone
+        set_mode(&db, 0o400).unwrap();
+        assert_eq!(restrict_database(&db), 0);
+        assert_eq!(mode(&db), Some(0o400));
+        set_mode(&db, 0o600).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_database_files() {
+        let files = database_files(Path::new("/data/rlcollector.db"));
+        assert_eq!(files, vec![
+            PathBuf::from("/data/rlcollector.db"),
+            PathBuf::from("/data/rlcollector.db-wal"),
+            PathBuf::from("/data/rlcollector.db-shm"),
+        ]);
+    }
+}
@@ -0,0 +1,128 @@
+//! Live capture preview for the settings UI: one capture run through the pipeline's
+//! image processing, returned in memory without touching the database, the disk or
+//! the capture loop's per-monitor state.
+
+use crate::capture::{self, CaptureError, CapturedMonitor};
+use crate::models::PreviewFrame;
+use base64::Engine;
+use std::time::{Duration, Instant};
+
+/// Shortest time between two previews; captures are expensive.
+pub const MIN_PREVIEW_GAP: Duration = Duration::from_secs(1);
+
+/// Widest preview frame returned, whatever `max_width` asks for.
+pub const MAX_PREVIEW_WIDTH: u32 = 1920;
+
+/// The parts of the capture and analysis settings that change what an image looks like.
+pub struct PreviewOptions {
+    /// `grayscale_capture`.
+    pub grayscale: bool,
+    /// `image_mode = "active_window"`.
+    pub active_window: bool,
+    /// `min_capture_dimension`: smaller captures are dropped, as the capture loop does.
+    pub min_dimension: u32,
+    pub max_width: u32,
+}
+
+/// Take the preview slot at `now`, unless the last preview was less than
+/// `MIN_PREVIEW_GAP` ago; then the time left is returned.
+pub fn claim_slot(last: &mut Option<Instant>, now: Instant) -> Result<(), Duration> {
+    if let Some(previous) = *last {
+        let elapsed = now.saturating_duration_since(previous);
+        if elapsed < MIN_PREVIEW_GAP {
+            return Err(MIN_PREVIEW_GAP - elapsed);
+        }
+    }
+    *last = Some(now);
+    Ok(())
+}
+
+/// Run `capture` once and turn each monitor's image into a preview frame: grayscale and
+/// active-window crop as configured, downsized to `max_width`, WebP-encoded.
+pub fn capture_preview(
+    capture: impl FnOnce() -> Result<Vec<CapturedMonitor>, CaptureError>,
+    options: &PreviewOptions,
+) -> Result<Vec<PreviewFrame>, CaptureError> {
+    let max_width = options.max_width.clamp(1, MAX_PREVIEW_WIDTH);
+    let mut frames = Vec::new();
+    for cap in capture()? {
+        if !capture::is_valid_capture(&cap.image, options.min_dimension, options.min_dimension) {
+            continue;
+        }
+        let mut image = if options.grayscale { capture::to_grayscale_rgba(&cap.image) } else { cap.image };
+        if options.active_window {
+            image = capture::crop_active_window(&image, cap.scale_factor);
+        }
+        let image = capture::resize_for_analysis(&image, max_width);
+        let webp = capture::encode_webp_bytes(&image)?;
+        frames.push(PreviewFrame {
+            monitor_id: cap.monitor_id,
+            monitor_name: cap.monitor_name,
+            width: image.width(),
+            height: image.height(),
+            webp_base64: base64::engine::general_purpose::STANDARD.encode(&webp),
+        });
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn monitor(id: u32, width: u32, height: u32) -> CapturedMonitor {
+        CapturedMonitor {
+            monitor_id: id,
+            monitor_name: format!("DISPLAY{}", id),
+            image: RgbaImage::from_pixel(width, height, image::Rgba([200, 30, 30, 255])),
+            previous_active: false,
+            scale_factor: 1.0,
+            is_primary: false,
+        }
+    }
+
+    fn options(max_width: u32) -> PreviewOptions {
+        PreviewOptions { grayscale: false, active_window: false, min_dimension: 100, max_width }
+    }
+
+    #[test]
+    fn test_claim_slot() {
+        let start = Instant::now();
+        let mut last = None;
+        assert!(claim_slot(&mut last, start).is_ok());
+        let wait = claim_slot(&mut last, start + Duration::from_millis(300)).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(700));
+        // A rejected call doesn't push the next slot back
+        assert!(claim_slot(&mut last, start + MIN_PREVIEW_GAP).is_ok());
+        assert_eq!(last, Some(start + MIN_PREVIEW_GAP));
+    }
+
+    #[test]
+    fn test_capture_preview_payload() {
+        let frames = capture_preview(|| Ok(vec![monitor(1, 1600, 900), monitor(2, 400, 300), monitor(3, 50, 50)]), &options(800)).unwrap();
+        // The 50x50 capture is below min_capture_dimension, as in the capture loop
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].monitor_id, frames[0].monitor_name.as_str()), (1, "DISPLAY1"));
+        assert_eq!((frames[0].width, frames[0].height), (800, 450));
+        // Already narrower than max_width: left as is
+        assert_eq!((frames[1].width, frames[1].height), (400, 300));
+
+        let webp = base64::engine::general_purpose::STANDARD.decode(&frames[0].webp_base64).unwrap();
+        let decoded = image::load_from_memory(&webp).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (800, 450));
+
+        // Grayscale as configured
+        let gray = capture_preview(|| Ok(vec![monitor(1, 200, 100)]), &PreviewOptions { grayscale: true, ..options(800) }).unwrap();
+        let webp = base64::engine::general_purpose::STANDARD.decode(&gray[0].webp_base64).unwrap();
+        let pixel = image::load_from_memory(&webp).unwrap().to_rgba8().get_pixel(0, 0).0;
+        assert!(pixel[0] == pixel[1] && pixel[1] == pixel[2]);
+    }
+
+    #[test]
+    fn test_capture_preview_errors_and_width_cap() {
+        assert!(matches!(capture_preview(|| Err(CaptureError::NoMonitors), &options(800)), Err(CaptureError::NoMonitors)));
+        let frames = capture_preview(|| Ok(vec![monitor(1, 3840, 2160)]), &options(10_000)).unwrap();
+        assert_eq!(frames[0].width, MAX_PREVIEW_WIDTH);
+    }
+}
@@ -0,0 +1,134 @@
+/// Split a title pattern setting (`blocked_title_keywords`, `capture_allowlist`) into patterns. Entries are separated
+/// by commas or newlines; blank entries are dropped.
+pub fn parse_keywords(setting: &str) -> Vec<String> {
+    setting
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Case-insensitive substring match of `pattern` in `text`. `*` matches any run of
+/// characters and `?` matches exactly one.
+pub fn keyword_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    (0..=text.len()).any(|start| glob_prefix(&pattern, &text[start..]))
+}
+
+/// Whether `pattern` matches some prefix of `text`.
+fn glob_prefix(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some(('*', rest)) => (0..=text.len()).any(|skip| glob_prefix(rest, &text[skip..])),
+        Some((&p, rest)) => match text.split_first() {
+            Some((&t, text_rest)) if p == '?' || p == t => glob_prefix(rest, text_rest),
+            _ => false,
+        },
+    }
+}
+
+/// Index (into `patterns`) of the first pattern found in any of `titles`.
+/// Callers log the index, never the pattern itself.
+pub fn matches_patterns<S: AsRef<str>>(titles: &[S], patterns: &[String]) -> Option<usize> {
+    patterns
+        .iter()
+        .position(|k| titles.iter().any(|t| keyword_matches(k, t.as_ref())))
+}
+
+/// Which window-title check a capture tick goes through.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TitleFilter<'a> {
+    /// Nothing configured; every tick is captured.
+    None,
+    /// Capture only when the active window matches one of these.
+    Allow(&'a [String]),
+    /// Skip the tick when any visible window matches one of these.
+    Block(&'a [String]),
+}
+
+/// Pick the title check for a tick. A non-empty allowlist takes precedence: the
+/// blocklist isn't consulted while one is set.
+pub fn title_filter<'a>(allowlist: &'a [String], blocklist: &'a [String]) -> TitleFilter<'a> {
+    if !allowlist.is_empty() {
+        TitleFilter::Allow(allowlist)
+    } else if !blocklist.is_empty() {
+        TitleFilter::Block(blocklist)
+    } else {
+        TitleFilter::None
+    }
+}
+
+/// Why a tick is skipped under an allowlist given the active window's title, if it is.
+pub fn allowlist_skip_reason(active_title: Option<&str>, allowlist: &[String]) -> Option<String> {
+    match active_title {
+        Some(title) if matches_patterns(&[title], allowlist).is_some() => None,
+        Some(_) => Some("active window not in allowlist".to_string()),
+        None => Some("no active window".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keywords() {
+        assert_eq!(parse_keywords("Acme, Globex\n  initech  ,,"), vec!["Acme", "Globex", "initech"]);
+        assert!(parse_keywords("  ").is_empty());
+    }
+
+    #[test]
+    fn test_keyword_matches_case_insensitive_substring() {
+        assert!(keyword_matches("acme", "Q3 plan - ACME Corp - Slides"));
+        assert!(!keyword_matches("acme", "Globex"));
+        assert!(keyword_matches("", "anything"));
+    }
+
+    #[test]
+    fn test_keyword_matches_wildcards() {
+        assert!(keyword_matches("project*x", "Notes: Project Phoenix"));
+        assert!(keyword_matches("client-??", "client-42 contract"));
+        assert!(!keyword_matches("client-??", "client-4"));
+        assert!(keyword_matches("é*é", "Café Société"));
+    }
+
+    #[test]
+    fn test_matches_patterns_reports_index() {
+        let keywords = parse_keywords("acme, globex*deal");
+        assert_eq!(matches_patterns(&["Inbox", "Globex merger deal.docx"], &keywords), Some(1));
+        assert_eq!(matches_patterns(&["Inbox", "Editor"], &keywords), None);
+        assert_eq!(matches_patterns::<&str>(&[], &keywords), None);
+    }
+
+    #[test]
+    fn test_title_filter_allowlist_only() {
+        let allowlist = parse_keywords("Visual Studio Code, terminal");
+        let filter = title_filter(&allowlist, &[]);
+        assert_eq!(filter, TitleFilter::Allow(&allowlist));
+        assert_eq!(allowlist_skip_reason(Some("main.rs - Visual Studio Code"), &allowlist), None);
+        assert_eq!(allowlist_skip_reason(Some("GNOME Terminal"), &allowlist), None);
+        assert_eq!(
+            allowlist_skip_reason(Some("Inbox - Mail"), &allowlist).as_deref(),
+            Some("active window not in allowlist")
+        );
+        assert_eq!(allowlist_skip_reason(None, &allowlist).as_deref(), Some("no active window"));
+    }
+
+    #[test]
+    fn test_title_filter_blocklist_only() {
+        let blocklist = parse_keywords("bank");
+        assert_eq!(title_filter(&[], &blocklist), TitleFilter::Block(&blocklist));
+        assert_eq!(title_filter(&[], &[]), TitleFilter::None);
+    }
+
+    #[test]
+    fn test_title_filter_allowlist_takes_precedence() {
+        let allowlist = parse_keywords("code");
+        let blocklist = parse_keywords("code, bank");
+        assert_eq!(title_filter(&allowlist, &blocklist), TitleFilter::Allow(&allowlist));
+        // The blocklist would have skipped this; the allowlist keeps it
+        assert_eq!(allowlist_skip_reason(Some("lib.rs - Visual Studio Code"), &allowlist), None);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::models::{RejectedSetting, SettingsImport};
+use crate::settings::{self, SettingsError};
+use crate::storage::Database;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use thiserror::Error;
+
+/// Version of the profile format; `read_profile` refuses newer files.
+pub const PROFILE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Failed to access profile file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid profile file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Profile version {0} is newer than this app supports ({PROFILE_VERSION})")]
+    UnsupportedVersion(u32),
+}
+
+/// The user's settings as one JSON document, for setting up another machine.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub version: u32,
+    pub exported_at: String,
+    pub settings: BTreeMap<String, String>,
+}
+
+/// Collect the stored values of registry settings. Internal rows (screenshots dir,
+/// capture region, latency history, ...) are machine state and never exported;
+/// `SECRET_SETTINGS` are only included with `include_secrets`.
+pub fn export_profile(db: &Database, include_secrets: bool, exported_at: &str) -> Result<SettingsProfile, ProfileError> {
+    let settings = db
+        .get_all_settings()?
+        .into_iter()
+        .filter(|(key, _)| settings::find(key).is_some())
+        .filter(|(key, _)| include_secrets || !settings::SECRET_SETTINGS.contains(&key.as_str()))
+        .collect();
+    Ok(SettingsProfile { version: PROFILE_VERSION, exported_at: exported_at.to_string(), settings })
+}
+
+/// Apply a profile. Every entry is validated against the registry first; if any is
+/// invalid, nothing is written and the report lists what was rejected. Otherwise the
+/// accepted values are written in one transaction. Without `overwrite`, keys that
+/// already have a stored value here are skipped.
+pub fn import_profile(db: &Database, profile: &SettingsProfile, overwrite: bool) -> Result<SettingsImport, ProfileError> {
+    let existing: HashSet<String> = db.get_all_settings()?.into_iter().map(|(key, _)| key).collect();
+    let mut report = SettingsImport::default();
+    let mut accepted = Vec::new();
+
+    for (key, value) in &profile.settings {
+        match settings::validate(key, value, false) {
+            Ok(()) if !overwrite && existing.contains(key) => report.skipped_existing.push(key.clone()),
+            Ok(()) => accepted.push((key.clone(), value.clone())),
+            Err(SettingsError::UnknownKey(_)) => report.skipped_unknown.push(key.clone()),
+            Err(e) => report.rejected.push(RejectedSetting { key: key.clone(), reason: e.to_string() }),
+        }
+    }
+
+    if report.rejected.is_empty() {
+        db.set_settings(&accepted)?;
+        report.applied = accepted.into_iter().map(|(key, _)| key).collect();
+    }
+    Ok(report)
+}
+
+pub fn write_profile(profile: &SettingsProfile, path: &Path) -> Result<(), ProfileError> {
+    std::fs::write(path, serde_json::to_vec_pretty(profile)?)?;
+    Ok(())
+}
+
+pub fn read_profile(path: &Path) -> Result<SettingsProfile, ProfileError> {
+    let profile: SettingsProfile = serde_json::from_slice(&std::fs::read(path)?)?;
+    if profile.version > PROFILE_VERSION {
+        return Err(ProfileError::UnsupportedVersion(profile.version));
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured_db() -> Database {
+        let db = Database::in_memory().unwrap();
+        db.set_setting("ai_provider", "ollama").unwrap();
+        db.set_setting("batch_size", "25").unwrap();
+        db.set_setting("blocked_title_keywords", "bank, *password*").unwrap();
+        db.set_setting("ai_api_key", "sk-ant-secret").unwrap();
+        db.set_screenshots_dir(Path::new("/data/screenshots")).unwrap();
+        db.record_analysis_latency("ollama", "qwen3-vl:8b", 4000.0).unwrap();
+        db
+    }
+
+    fn profile(entries: &[(&str, &str)]) -> SettingsProfile {
+        SettingsProfile {
+            version: PROFILE_VERSION,
+            exported_at: "2025-01-01T10:00:00".to_string(),
+            settings: entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_profile_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+
+        let exported = export_profile(&configured_db(), false, "2025-01-01T10:00:00").unwrap();
+        write_profile(&exported, &path).unwrap();
+
+        let target = Database::in_memory().unwrap();
+        let report = import_profile(&target, &read_profile(&path).unwrap(), false).unwrap();
+        assert_eq!(report.applied, vec!["ai_provider", "batch_size", "blocked_title_keywords"]);
+        assert!(report.rejected.is_empty());
+        assert_eq!(target.get_setting("batch_size").unwrap().as_deref(), Some("25"));
+        assert_eq!(target.get_setting("blocked_title_keywords").unwrap().as_deref(), Some("bank, *password*"));
+        assert_eq!(
+            export_profile(&target, false, "2025-01-01T10:00:00").unwrap().settings,
+            exported.settings
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_excludes_secrets_and_internal_rows() {
+        let db = configured_db();
+        let without = export_profile(&db, false, "now").unwrap();
+        assert!(!without.settings.contains_key("ai_api_key"));
+        assert_eq!(without.settings.len(), 3, "internal rows aren't exported: {:?}", without.settings);
+
+        let with = export_profile(&db, true, "now").unwrap();
+        assert_eq!(with.settings.get("ai_api_key").map(String::as_str), Some("sk-ant-secret"));
+        let target = Database::in_memory().unwrap();
+        import_profile(&target, &with, false).unwrap();
+        assert_eq!(target.get_setting("ai_api_key").unwrap().as_deref(), Some("sk-ant-secret"));
+    }
+
+    #[test]
+    fn test_import_is_all_or_nothing() {
+        let db = Database::in_memory().unwrap();
+        let report = import_profile(
+            &db,
+            &profile(&[("ai_provider", "ollama"), ("batch_size", "500"), ("from_the_future", "x")]),
+            true,
+        )
+        .unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(report.skipped_unknown, vec!["from_the_future"]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].key, "batch_size");
+        assert_eq!(db.get_setting("ai_provider").unwrap(), None, "valid keys aren't applied either");
+    }
+
+    #[test]
+    fn test_import_overwrite() {
+        let db = Database::in_memory().unwrap();
+        db.set_setting("ai_provider", "claude").unwrap();
+        let incoming = profile(&[("ai_provider", "ollama"), ("batch_size", "5")]);
+
+        let report = import_profile(&db, &incoming, false).unwrap();
+        assert_eq!(report.applied, vec!["batch_size"]);
+        assert_eq!(report.skipped_existing, vec!["ai_provider"]);
+        assert_eq!(db.get_setting("ai_provider").unwrap().as_deref(), Some("claude"));
+
+        let report = import_profile(&db, &incoming, true).unwrap();
+        assert_eq!(report.applied, vec!["ai_provider", "batch_size"]);
+        assert_eq!(db.get_setting("ai_provider").unwrap().as_deref(), Some("ollama"));
+    }
+
+    #[test]
+    fn test_read_rejects_newer_version() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_profile_version_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let mut newer = profile(&[]);
+        newer.version = PROFILE_VERSION + 1;
+        write_profile(&newer, &path).unwrap();
+        assert!(matches!(read_profile(&path), Err(ProfileError::UnsupportedVersion(_))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,160 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("Unknown placeholder {{{0}}}; expected one of {placeholders}", placeholders = PLACEHOLDERS.join(", "))]
+    UnknownPlaceholder(String),
+    #[error("Unclosed '{{' at byte {0}")]
+    Unclosed(usize),
+    #[error("Unmatched '}}' at byte {0} (write '}}}}' for a literal brace)")]
+    UnmatchedBrace(usize),
+}
+
+/// Placeholders a prompt template may use.
+pub const PLACEHOLDERS: &[&str] = &[
+    "{context_section}",
+    "{session_description}",
+    "{categories}",
+    "{monitors_section}",
+    "{json_schema}",
+];
+
+/// Values substituted into a prompt template. Placeholders that don't apply
+/// (e.g. `monitors_section` for a single-monitor capture) render as empty strings.
+#[derive(Default)]
+pub struct PromptVars<'a> {
+    pub context_section: &'a str,
+    pub session_description: &'a str,
+    pub categories: &'a str,
+    pub monitors_section: &'a str,
+    pub json_schema: &'a str,
+}
+
+impl PromptVars<'static> {
+    /// Synthetic values for previewing a template in the settings UI.
+    pub fn sample() -> Self {
+        Self {
+            context_section: "Recent task history (most recent first):\n  \
+                1. Fixing login bug: Editing auth.rs in VS Code\n\
+                Use this context to decide if the current screenshot shows a continuation of a recent task or a new one.\n",
+            session_description: "Shipping the login page",
+            categories: "coding|browsing|writing|communication|design|other",
+            monitors_section: "MONITORS WITH NEW SCREENSHOTS (images attached in order):\n\
+                - Monitor \"DISPLAY1\" (1920x1080, primary): see image 1\n\n\
+                UNCHANGED MONITORS (text summary from last capture):\n\
+                - Monitor \"DISPLAY2\": Browser showing API docs\n",
+            json_schema: "{\"task_title\": \"short title\", \"task_description\": \"what they're doing\", \
+                \"category\": \"coding|browsing|writing|communication|design|other\", \
+                \"reasoning\": \"why you think this\", \"is_new_task\": true/false}",
+        }
+    }
+}
+
+impl PromptVars<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "context_section" => Some(self.context_section),
+            "session_description" => Some(self.session_description),
+            "categories" => Some(self.categories),
+            "monitors_section" => Some(self.monitors_section),
+            "json_schema" => Some(self.json_schema),
+            _ => None,
+        }
+    }
+}
+
+/// Render `template`, replacing `{name}` placeholders with `vars`. `{{` and `}}`
+/// produce literal braces (so templates can include JSON examples). Unknown
+/// placeholders and unbalanced braces are errors rather than passed through.
+pub fn render(template: &str, vars: &PromptVars<'_>) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' => {
+                if chars.next_if(|&(_, c)| c == '{').is_some() {
+                    out.push('{');
+                    continue;
+                }
+                let start = i + 1;
+                let end = template[start..].find('}').map(|n| start + n).ok_or(TemplateError::Unclosed(i))?;
+                let name = &template[start..end];
+                if name.contains('{') {
+                    return Err(TemplateError::Unclosed(i));
+                }
+                let value = vars.get(name).ok_or_else(|| TemplateError::UnknownPlaceholder(name.to_string()))?;
+                out.push_str(value);
+                while chars.next_if(|&(j, _)| j <= end).is_some() {}
+            }
+            '}' => {
+                if chars.next_if(|&(_, c)| c == '}').is_some() {
+                    out.push('}');
+                } else {
+                    return Err(TemplateError::UnmatchedBrace(i));
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Check a template renders, without caring about the output.
+pub fn validate(template: &str) -> Result<(), TemplateError> {
+    render(template, &PromptVars::sample()).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let vars = PromptVars {
+            session_description: "writing docs",
+            categories: "coding|other",
+            ..Default::default()
+        };
+        let out = render("Working on {session_description}. Pick one of {categories}.", &vars).unwrap();
+        assert_eq!(out, "Working on writing docs. Pick one of coding|other.");
+    }
+
+    #[test]
+    fn test_render_missing_values_are_empty() {
+        let out = render("[{monitors_section}]", &PromptVars::default()).unwrap();
+        assert_eq!(out, "[]");
+    }
+
+    #[test]
+    fn test_render_escaped_braces() {
+        let out = render("{{\"a\": 1}} {json_schema}", &PromptVars { json_schema: "{}", ..Default::default() }).unwrap();
+        assert_eq!(out, "{\"a\": 1} {}");
+    }
+
+    #[test]
+    fn test_render_unknown_placeholder() {
+        let err = render("Hello {user_name}", &PromptVars::default()).unwrap_err();
+        assert_eq!(err, TemplateError::UnknownPlaceholder("user_name".to_string()));
+        assert!(err.to_string().contains("{json_schema}"));
+    }
+
+    #[test]
+    fn test_render_unbalanced_braces() {
+        assert_eq!(render("oops {categories", &PromptVars::default()).unwrap_err(), TemplateError::Unclosed(5));
+        assert_eq!(render("a {b {categories}", &PromptVars::default()).unwrap_err(), TemplateError::Unclosed(2));
+        assert_eq!(render("done }", &PromptVars::default()).unwrap_err(), TemplateError::UnmatchedBrace(5));
+    }
+
+    #[test]
+    fn test_validate_with_sample_data() {
+        assert!(validate("{context_section}{session_description}{categories}{monitors_section}{json_schema}").is_ok());
+        assert!(validate("{session}").is_err());
+    }
+
+    #[test]
+    fn test_render_non_ascii() {
+        let out = render("→ {categories} ←", &PromptVars { categories: "código", ..Default::default() }).unwrap();
+        assert_eq!(out, "→ código ←");
+    }
+}
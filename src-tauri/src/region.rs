@@ -0,0 +1,81 @@
+use crate::models::CaptureRegion;
+use serde::Deserialize;
+
+/// A rectangle drawn on the region overlay, in CSS pixels relative to the overlay
+/// window. Width and height are negative when the user drags up or left.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CssRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Convert an overlay rectangle to physical pixels on a monitor of `bounds`
+/// (physical width, height) at `scale_factor`. Edges are rounded independently so
+/// adjacent selections don't gap or overlap, then clamped to the monitor.
+/// Returns None if nothing of the rectangle is left.
+pub fn css_to_physical(rect: &CssRect, scale_factor: f64, bounds: (u32, u32), monitor_id: u32) -> Option<CaptureRegion> {
+    let edge = |v: f64, max: u32| (v * scale_factor).round().clamp(0.0, max as f64) as u32;
+    let left = edge(rect.x.min(rect.x + rect.width), bounds.0);
+    let right = edge(rect.x.max(rect.x + rect.width), bounds.0);
+    let top = edge(rect.y.min(rect.y + rect.height), bounds.1);
+    let bottom = edge(rect.y.max(rect.y + rect.height), bounds.1);
+    if right == left || bottom == top {
+        return None;
+    }
+    Some(CaptureRegion {
+        monitor_id,
+        x: left,
+        y: top,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> CssRect {
+        CssRect { x, y, width, height }
+    }
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> Option<CaptureRegion> {
+        Some(CaptureRegion { monitor_id: 7, x, y, width, height })
+    }
+
+    #[test]
+    fn test_css_to_physical_scale_factors() {
+        let r = rect(100.0, 50.0, 200.0, 100.0);
+        assert_eq!(css_to_physical(&r, 1.0, (1920, 1080), 7), region(100, 50, 200, 100));
+        assert_eq!(css_to_physical(&r, 1.25, (2400, 1350), 7), region(125, 63, 250, 125));
+        assert_eq!(css_to_physical(&r, 1.5, (2880, 1620), 7), region(150, 75, 300, 150));
+        assert_eq!(css_to_physical(&r, 2.0, (3840, 2160), 7), region(200, 100, 400, 200));
+    }
+
+    #[test]
+    fn test_css_to_physical_fractional_edges() {
+        // 1.25 × 10.3 = 12.875 → 13, 1.25 × 20.5 = 25.625 → 26
+        let r = rect(10.3, 0.0, 10.2, 1.0);
+        assert_eq!(css_to_physical(&r, 1.25, (2400, 1350), 7), region(13, 0, 13, 1));
+    }
+
+    #[test]
+    fn test_css_to_physical_normalizes_reverse_drag() {
+        let r = rect(300.0, 150.0, -200.0, -100.0);
+        assert_eq!(css_to_physical(&r, 2.0, (3840, 2160), 7), region(200, 100, 400, 200));
+    }
+
+    #[test]
+    fn test_css_to_physical_clamps_to_monitor() {
+        let r = rect(-10.0, 1000.0, 2000.0, 200.0);
+        assert_eq!(css_to_physical(&r, 1.0, (1920, 1080), 7), region(0, 1000, 1920, 80));
+    }
+
+    #[test]
+    fn test_css_to_physical_empty() {
+        assert_eq!(css_to_physical(&rect(10.0, 10.0, 0.0, 50.0), 1.5, (1920, 1080), 7), None);
+        assert_eq!(css_to_physical(&rect(5000.0, 10.0, 50.0, 50.0), 1.0, (1920, 1080), 7), None);
+    }
+}
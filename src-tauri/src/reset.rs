@@ -0,0 +1,165 @@
+//! File-system side of `factory_reset`: every deletion is attempted and reported on its
+//! own, and a final pass lists anything that survived.
+
+use crate::analysis::{ANALYSIS_IMAGES_DIR, CORRUPT_DIR};
+use crate::capture;
+use crate::models::ResetItem;
+use crate::permissions;
+use crate::trash::{self, TRASH_DIR};
+use std::path::{Path, PathBuf};
+
+/// A `ResetItem` for `path` from the outcome of `action` on it.
+pub fn item(path: &Path, action: &str, result: std::io::Result<()>) -> ResetItem {
+    ResetItem {
+        path: path.display().to_string(),
+        action: action.to_string(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+/// Delete a directory and everything in it. One that doesn't exist counts as deleted.
+pub fn remove_dir(dir: &Path) -> ResetItem {
+    let result = match std::fs::remove_dir_all(dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    };
+    item(dir, "deleted", result)
+}
+
+/// Subdirectories the app creates in the screenshots directory.
+const APP_SUBDIRS: [&str; 3] = [TRASH_DIR, CORRUPT_DIR, ANALYSIS_IMAGES_DIR];
+
+/// Delete a file. One that doesn't exist counts as deleted.
+pub fn remove_file(path: &Path) -> ResetItem {
+    let result = match std::fs::remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    };
+    item(path, "deleted", result)
+}
+
+/// Clear a screenshots directory the user chose, which may hold their own files: only
+/// what the app wrote goes (the screenshots in `filepaths`, `trash/`, `corrupt/`,
+/// `analysis/` and leftover temp files); the directory itself and anything else in it
+/// stay.
+pub fn clear_custom_dir(dir: &Path, filepaths: &[String]) -> Vec<ResetItem> {
+    let mut items: Vec<ResetItem> = filepaths
+        .iter()
+        .map(|fp| remove_file(&trash::live_path(dir, fp)))
+        .collect();
+    items.extend(APP_SUBDIRS.iter().map(|sub| remove_dir(&dir.join(sub))));
+    items.extend(temp_files(dir).iter().map(|path| remove_file(path)));
+    items
+}
+
+/// What `clear_custom_dir` should have removed but is still there.
+pub fn custom_dir_leftovers(dir: &Path, filepaths: &[String]) -> Vec<String> {
+    let mut found: Vec<String> = filepaths
+        .iter()
+        .map(|fp| trash::live_path(dir, fp))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+    found.extend(leftovers(&APP_SUBDIRS.map(|sub| dir.join(sub))));
+    found.extend(temp_files(dir).iter().map(|path| path.display().to_string()));
+    found
+}
+
+fn temp_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| capture::is_temp_file(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Create an empty, owner-only directory.
+pub fn recreate_dir(dir: &Path) -> ResetItem {
+    let result = std::fs::create_dir_all(dir);
+    if result.is_ok() {
+        permissions::restrict_dir(dir);
+    }
+    item(dir, "recreated", result)
+}
+
+/// Everything still inside `dirs`, which should all be empty or gone. A directory that
+/// exists but can't be listed is reported itself.
+pub fn leftovers(dirs: &[PathBuf]) -> Vec<String> {
+    let mut found = Vec::new();
+    for dir in dirs {
+        match std::fs::read_dir(dir) {
+            Ok(entries) => found.extend(entries.filter_map(|e| e.ok()).map(|e| e.path().display().to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(_) => found.push(dir.display().to_string()),
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_recreate_and_verify() {
+        let base = std::env::temp_dir().join(format!("rlcollector_test_reset_{}", std::process::id()));
+        let screenshots = base.join("screenshots");
+        let debug = base.join("analysis_debug");
+        std::fs::create_dir_all(screenshots.join("trash")).unwrap();
+        std::fs::create_dir_all(&debug).unwrap();
+        std::fs::write(screenshots.join("trash").join("a.webp"), b"x").unwrap();
+        std::fs::write(debug.join("1.webp"), b"x").unwrap();
+
+        assert_eq!(leftovers(&[screenshots.clone(), debug.clone()]).len(), 2);
+
+        for dir in [&screenshots, &debug, &base.join("never_created")] {
+            let removed = remove_dir(dir);
+            assert_eq!(removed.error, None, "{}", removed.path);
+        }
+        let recreated = recreate_dir(&screenshots);
+        assert_eq!(recreated.action, "recreated");
+        assert!(recreated.error.is_none());
+
+        // The recreated directory is empty and the removed one is gone
+        assert!(screenshots.is_dir());
+        assert!(!debug.exists());
+        assert!(leftovers(&[screenshots.clone(), debug.clone()]).is_empty());
+
+        std::fs::write(screenshots.join("late.webp"), b"x").unwrap();
+        assert_eq!(leftovers(std::slice::from_ref(&screenshots)), vec![screenshots.join("late.webp").display().to_string()]);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_clear_custom_dir_keeps_user_files() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_reset_custom_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("trash")).unwrap();
+        std::fs::create_dir_all(dir.join("corrupt")).unwrap();
+        std::fs::create_dir_all(dir.join("analysis")).unwrap();
+        std::fs::create_dir_all(dir.join("photos")).unwrap();
+        for name in ["a.webp", "b.webp", ".c.webp.partial", "notes.txt", "trash/d.webp", "corrupt/e.webp", "analysis/1.webp", "photos/f.jpg"] {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+        // b.webp was already deleted by hand: a referenced file that's missing is fine
+        std::fs::remove_file(dir.join("b.webp")).unwrap();
+        let filepaths = vec!["screenshots/a.webp".to_string(), "screenshots/b.webp".to_string()];
+        assert_eq!(custom_dir_leftovers(&dir, &filepaths).len(), 5);
+
+        let items = clear_custom_dir(&dir, &filepaths);
+        assert!(items.iter().all(|item| item.error.is_none()), "{:?}", items);
+        assert!(custom_dir_leftovers(&dir, &filepaths).is_empty());
+
+        // Everything the app didn't write is untouched
+        assert!(dir.join("notes.txt").exists());
+        assert!(dir.join("photos/f.jpg").exists());
+        assert!(!dir.join("a.webp").exists());
+        assert!(!dir.join("trash").exists());
+        assert!(!dir.join("corrupt").exists());
+        assert!(!dir.join("analysis").exists());
+        assert!(!dir.join(".c.webp.partial").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
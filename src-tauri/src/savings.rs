@@ -0,0 +1,115 @@
+use crate::models::{SavingsSummary, SessionStats};
+use crate::telemetry;
+
+/// Add `delta`'s counters into `total`, combining analysis resource usage.
+pub fn merge(total: &mut SessionStats, delta: &SessionStats) {
+    total.ticks += delta.ticks;
+    total.ticks_unchanged += delta.ticks_unchanged;
+    total.screenshots_saved += delta.screenshots_saved;
+    total.screenshots_unchanged += delta.screenshots_unchanged;
+    total.bytes_written += delta.bytes_written;
+    total.groups_analyzed += delta.groups_analyzed;
+    total.screenshots_linked_without_ai += delta.screenshots_linked_without_ai;
+    total.analysis_resources = telemetry::combine(total.analysis_resources.as_ref(), delta.analysis_resources.as_ref());
+}
+
+/// Estimate what change detection saved across `sessions` sessions whose stats sum to `stats`.
+/// Bytes saved assume each unchanged capture would have been as large as the average
+/// saved screenshot; requests saved count one AI call per tick where nothing changed.
+pub fn estimate(sessions: i64, stats: SessionStats) -> SavingsSummary {
+    let unchanged_tick_pct = if stats.ticks > 0 {
+        stats.ticks_unchanged as f64 * 100.0 / stats.ticks as f64
+    } else {
+        0.0
+    };
+    let avg_bytes = stats.bytes_written.checked_div(stats.screenshots_saved).unwrap_or(0);
+    let estimated_bytes_saved = avg_bytes * stats.screenshots_unchanged;
+    let estimated_requests_saved = stats.ticks_unchanged;
+
+    let message = if stats.ticks == 0 {
+        "No capture ticks recorded in this range".to_string()
+    } else {
+        format!(
+            "change detection skipped {:.0}% of ticks, saving an estimated {} and ~{} requests",
+            unchanged_tick_pct,
+            format_bytes(estimated_bytes_saved),
+            estimated_requests_saved,
+        )
+    };
+
+    SavingsSummary {
+        sessions,
+        stats,
+        unchanged_tick_pct,
+        estimated_bytes_saved,
+        estimated_requests_saved,
+        message,
+    }
+}
+
+/// Human-readable size using decimal units ("2.1 GB").
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = u;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut total = SessionStats { ticks: 3, bytes_written: 10, ..Default::default() };
+        merge(&mut total, &SessionStats { ticks: 2, groups_analyzed: 4, ..Default::default() });
+        assert_eq!(total, SessionStats { ticks: 5, bytes_written: 10, groups_analyzed: 4, ..Default::default() });
+    }
+
+    #[test]
+    fn test_estimate() {
+        let stats = SessionStats {
+            ticks: 1000,
+            ticks_unchanged: 610,
+            screenshots_saved: 400,
+            screenshots_unchanged: 700,
+            bytes_written: 1_200_000_000,
+            ..Default::default()
+        };
+        let summary = estimate(3, stats);
+        assert_eq!(summary.sessions, 3);
+        assert!((summary.unchanged_tick_pct - 61.0).abs() < 1e-9);
+        // 3 MB average per saved screenshot × 700 unchanged captures
+        assert_eq!(summary.estimated_bytes_saved, 2_100_000_000);
+        assert_eq!(summary.estimated_requests_saved, 610);
+        assert_eq!(
+            summary.message,
+            "change detection skipped 61% of ticks, saving an estimated 2.1 GB and ~610 requests"
+        );
+    }
+
+    #[test]
+    fn test_estimate_empty() {
+        let summary = estimate(0, SessionStats::default());
+        assert_eq!(summary.unchanged_tick_pct, 0.0);
+        assert_eq!(summary.estimated_bytes_saved, 0);
+        assert_eq!(summary.message, "No capture ticks recorded in this range");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(999), "999 B");
+        assert_eq!(format_bytes(1_500), "1.5 KB");
+        assert_eq!(format_bytes(2_100_000_000), "2.1 GB");
+    }
+}
@@ -0,0 +1,569 @@
+use crate::ai;
+use crate::capture;
+use crate::models::SettingSchema;
+use crate::prompt_template;
+use crate::time;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("Unknown setting key: {0}")]
+    UnknownKey(String),
+    #[error("Invalid value {value:?} for setting '{key}': {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
+
+/// Shape of the value a setting accepts.
+pub enum SettingKind {
+    /// Free-form text.
+    Text,
+    /// One of a fixed set of values.
+    Choice(&'static [&'static str]),
+    /// An integer within an inclusive range.
+    Integer { min: i64, max: i64 },
+    /// `true` or `false`.
+    Bool,
+    /// A prompt template (see `prompt_template`); empty means the built-in prompt.
+    Template,
+    /// Comma-separated extra response field names (see `ai::parse_custom_fields`).
+    FieldList,
+    /// Comma-separated `monitor_id=ms` capture intervals (see `capture::parse_monitor_intervals`).
+    MonitorIntervals,
+    /// `screen` or `directory:<path>` (see `capture::parse_capture_source`).
+    CaptureSource,
+    /// `system`, `UTC`, `±HH:MM`, a zone name or a POSIX TZ rule (see `time::TimeZone::from_setting`).
+    TimeZone,
+    /// An http(s) base URL (see `ai::parse_claude_base_url`).
+    Url,
+    /// A JSON object of HTTP header names to values (see `ai::parse_extra_headers`).
+    Headers,
+}
+
+/// A known setting: its key, accepted values, and the default used when it is unset.
+pub struct SettingDef {
+    pub key: &'static str,
+    pub kind: SettingKind,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// Registry of every setting the app reads. This is the single source of truth
+/// for defaults — read settings through `default_value` rather than hardcoding them.
+pub const SETTINGS: &[SettingDef] = &[
+    SettingDef {
+        key: "ai_provider",
+        kind: SettingKind::Choice(&["claude", "ollama"]),
+        default: Some("claude"),
+        description: "Which AI backend to use",
+    },
+    SettingDef {
+        key: "ai_api_key",
+        kind: SettingKind::Text,
+        default: None,
+        description: "Claude API key",
+    },
+    SettingDef {
+        key: "claude_base_url",
+        kind: SettingKind::Url,
+        default: Some("https://api.anthropic.com"),
+        description: "Base URL for Claude requests, e.g. a Claude-compatible gateway",
+    },
+    SettingDef {
+        key: "claude_extra_headers",
+        kind: SettingKind::Headers,
+        default: None,
+        description: "JSON object of extra HTTP headers sent with every Claude request (e.g. gateway auth); can override anthropic-version",
+    },
+    SettingDef {
+        key: "ollama_model",
+        kind: SettingKind::Text,
+        default: Some("qwen3-vl:8b"),
+        description: "Ollama model name",
+    },
+    SettingDef {
+        key: "capture_monitor_mode",
+        kind: SettingKind::Choice(&["default", "specific", "active", "all"]),
+        default: Some("default"),
+        description: "Monitor capture strategy",
+    },
+    SettingDef {
+        key: "capture_previous_active",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "In active mode, also capture the monitor you just left for one tick after switching",
+    },
+    SettingDef {
+        key: "capture_monitor_id",
+        kind: SettingKind::Integer { min: 0, max: u32::MAX as i64 },
+        default: None,
+        description: "Monitor ID for \"specific\" mode",
+    },
+    SettingDef {
+        key: "image_mode",
+        kind: SettingKind::Choice(&["downscale", "active_window"]),
+        default: Some("downscale"),
+        description: "Image preprocessing before AI",
+    },
+    SettingDef {
+        key: "analysis_mode",
+        kind: SettingKind::Choice(&["realtime", "batch"]),
+        default: Some("batch"),
+        description: "When to trigger auto-analysis",
+    },
+    SettingDef {
+        key: "batch_size",
+        kind: SettingKind::Integer { min: 1, max: 100 },
+        default: Some("10"),
+        description: "Screenshots per batch (if batch mode)",
+    },
+    SettingDef {
+        key: "max_screenshots_per_session",
+        kind: SettingKind::Integer { min: 0, max: 1_000_000 },
+        default: Some("0"),
+        description: "Screenshots per session before rotating or stopping (0 = unlimited)",
+    },
+    SettingDef {
+        key: "session_auto_rotate",
+        kind: SettingKind::Bool,
+        default: Some("true"),
+        description: "Start a new session when the per-session limit is reached instead of stopping",
+    },
+    SettingDef {
+        key: "require_analysis_confirmation",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Never analyze automatically (during or after capture); sessions are only analyzed when you start it",
+    },
+    SettingDef {
+        key: "analysis_tokens_per_call",
+        kind: SettingKind::Integer { min: 1, max: 1_000_000 },
+        default: Some("2300"),
+        description: "Estimated input tokens per analysis request, for estimate_analysis_cost",
+    },
+    SettingDef {
+        key: "analysis_sampling",
+        kind: SettingKind::Integer { min: 1, max: 1000 },
+        default: Some("1"),
+        description: "Analyze every Nth capture; skipped captures join the preceding capture's task",
+    },
+    SettingDef {
+        key: "min_capture_dimension",
+        kind: SettingKind::Integer { min: 1, max: 4096 },
+        default: Some("100"),
+        description: "Captures narrower or shorter than this many pixels are discarded",
+    },
+    SettingDef {
+        key: "hash_prescale_threshold",
+        kind: SettingKind::Integer { min: 0, max: 16_384 },
+        default: Some("2560"),
+        description: "Captures wider than this many pixels are shrunk to 256px wide before change-detection hashing, which is much cheaper on 4K/8K displays (0 = always hash at full resolution)",
+    },
+    SettingDef {
+        key: "mirror_hash_threshold",
+        kind: SettingKind::Integer { min: 0, max: 64 },
+        default: Some("0"),
+        description: "Captures from the same tick whose change-detection hashes differ by at most this many bits are treated as a mirrored display: both are saved, but only one is sent to the AI (0 = off; similar content on separate monitors can match too)",
+    },
+    SettingDef {
+        key: "grayscale_capture",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Save captures in grayscale (smaller files, less private detail); they're hashed and sent to the AI that way too",
+    },
+    SettingDef {
+        key: "no_monitor_pause_after",
+        kind: SettingKind::Integer { min: 1, max: 1000 },
+        default: Some("3"),
+        description: "Pause capture after this many ticks in a row find no monitors; resumes when they return",
+    },
+    SettingDef {
+        key: "blocked_title_keywords",
+        kind: SettingKind::Text,
+        default: None,
+        description: "Comma-separated window title keywords (* and ? wildcards); ticks with a matching window open are skipped",
+    },
+    SettingDef {
+        key: "capture_allowlist",
+        kind: SettingKind::Text,
+        default: None,
+        description: "Comma-separated window title keywords (* and ? wildcards); when set, only ticks whose active window matches are captured, and blocked_title_keywords is ignored",
+    },
+    SettingDef {
+        key: "adaptive_interval",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Capture faster after a screen change and back off while the screen is stable",
+    },
+    SettingDef {
+        key: "adaptive_interval_min_ms",
+        kind: SettingKind::Integer { min: 1000, max: 3_600_000 },
+        default: Some("10000"),
+        description: "Fastest adaptive capture interval (used right after a change)",
+    },
+    SettingDef {
+        key: "adaptive_interval_max_ms",
+        kind: SettingKind::Integer { min: 1000, max: 3_600_000 },
+        default: Some("60000"),
+        description: "Slowest adaptive capture interval (reached after consecutive unchanged ticks)",
+    },
+    SettingDef {
+        key: "monitor_intervals",
+        kind: SettingKind::MonitorIntervals,
+        default: None,
+        description: "Per-monitor capture intervals as comma-separated monitor_id=ms pairs (e.g. 2=600000); other monitors use the capture interval",
+    },
+    SettingDef {
+        key: "capture_source",
+        kind: SettingKind::CaptureSource,
+        default: Some("screen"),
+        description: "Where captures come from: screen, or directory:<path> to replay the images in a folder one per tick in name order, looping (for testing and demos)",
+    },
+    SettingDef {
+        key: "analyze_monitor_scope",
+        kind: SettingKind::Choice(&["all", "primary_only", "changed_only"]),
+        default: Some("all"),
+        description: "Which monitors of each capture are sent to the AI; the rest share the resulting task",
+    },
+    SettingDef {
+        key: "include_unchanged_context",
+        kind: SettingKind::Bool,
+        default: Some("true"),
+        description: "Describe monitors that didn't change to the AI (from their last summary) alongside the new screenshots",
+    },
+    SettingDef {
+        key: "context_depth",
+        kind: SettingKind::Integer { min: 1, max: 10 },
+        default: Some("2"),
+        description: "How many recent task contexts are sent with each analysis request",
+    },
+    SettingDef {
+        key: "context_overlap",
+        kind: SettingKind::Integer { min: 0, max: 10 },
+        default: Some("0"),
+        description: "How many of the previous batch's last screenshots are described to the next batch's first request (0 = off)",
+    },
+    SettingDef {
+        key: "include_session_task_list",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "List the session's tasks so far (title and category, up to 20) in the prompt so the AI can name the task a screenshot returns to",
+    },
+    SettingDef {
+        key: "carry_context_from_previous",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Start a new session's analysis with the previous session's last two tasks as context, if it ended recently",
+    },
+    SettingDef {
+        key: "carry_context_window_minutes",
+        kind: SettingKind::Integer { min: 1, max: 1440 },
+        default: Some("120"),
+        description: "How recently the previous session must have ended, in minutes, for carry_context_from_previous",
+    },
+    SettingDef {
+        key: "consolidate_every",
+        kind: SettingKind::Integer { min: 0, max: 1000 },
+        default: Some("0"),
+        description: "After every this many analyzed captures, ask the AI which of the session's last that-many tasks should be merged (0 = off). Only adjacent, unverified tasks are merged",
+    },
+    SettingDef {
+        key: "embedding_model",
+        kind: SettingKind::Text,
+        default: Some(""),
+        description: "Ollama embedding model (e.g. nomic-embed-text). When set, a capture the AI calls a new task is linked to a recent session task whose description is similar enough instead (empty = off)",
+    },
+    SettingDef {
+        key: "embedding_similarity_threshold",
+        kind: SettingKind::Integer { min: 50, max: 100 },
+        default: Some("85"),
+        description: "Cosine similarity, in percent, above which an embedding match counts as continuing the task",
+    },
+    SettingDef {
+        key: "save_analysis_inputs",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Keep the exact prompt and preprocessed images sent for each analysis, for debugging (see get_analysis_debug); never included in exports",
+    },
+    SettingDef {
+        key: "keep_analysis_images",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Also write the exact image sent to the AI for each screenshot to screenshots/analysis/<screenshot id>.webp, to check what downscaling or cropping lost",
+    },
+    SettingDef {
+        key: "analysis_debug_retention_days",
+        kind: SettingKind::Integer { min: 1, max: 365 },
+        default: Some("7"),
+        description: "Days saved analysis inputs are kept before being deleted (checked at startup)",
+    },
+    SettingDef {
+        key: "trash_retention_days",
+        kind: SettingKind::Integer { min: 0, max: 3650 },
+        default: Some("30"),
+        description: "Days deleted sessions and screenshots stay in the trash before being purged for good (checked at startup and by purge_trash)",
+    },
+    SettingDef {
+        key: "timezone",
+        kind: SettingKind::TimeZone,
+        default: Some("system"),
+        description: "Time zone whose days date ranges and retention are counted in: system, UTC, an offset like +05:30, a zone name like Europe/Berlin, or a POSIX TZ rule",
+    },
+    SettingDef {
+        key: "event_throttle_ms",
+        kind: SettingKind::Integer { min: 0, max: 10_000 },
+        default: Some("500"),
+        description: "High-frequency UI events (progress) are sent at most once per this many ms; the final one always arrives (0 = no throttling)",
+    },
+    SettingDef {
+        key: "low_overhead_mode",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "While analysis is running, skip the monitor highlight overlay to keep the desktop responsive",
+    },
+    SettingDef {
+        key: "log_level",
+        kind: SettingKind::Choice(&["error", "warn", "info", "debug", "trace"]),
+        default: Some("info"),
+        description: "Most verbose log level written; change at runtime with set_log_level",
+    },
+    SettingDef {
+        key: "log_ai_responses",
+        kind: SettingKind::Bool,
+        default: Some("false"),
+        description: "Log raw AI response bodies (at trace level, so log_level must be trace)",
+    },
+    SettingDef {
+        key: "prompt_template_single",
+        kind: SettingKind::Template,
+        default: None,
+        description: "Custom prompt for single-monitor captures (empty = built-in)",
+    },
+    SettingDef {
+        key: "prompt_template_multi",
+        kind: SettingKind::Template,
+        default: None,
+        description: "Custom prompt for multi-monitor captures (empty = built-in)",
+    },
+    SettingDef {
+        key: "custom_fields",
+        kind: SettingKind::FieldList,
+        default: None,
+        description: "Comma-separated extra fields the AI fills in for each task, stored in task metadata",
+    },
+    SettingDef {
+        key: "prompt_language",
+        kind: SettingKind::Text,
+        default: None,
+        description: "Language for AI task titles and descriptions, e.g. \"German\" (empty = English)",
+    },
+];
+
+/// Settings left out of a settings profile unless secrets are asked for.
+pub const SECRET_SETTINGS: &[&str] = &["ai_api_key", "claude_extra_headers"];
+
+/// Look up a setting definition by key.
+pub fn find(key: &str) -> Option<&'static SettingDef> {
+    SETTINGS.iter().find(|def| def.key == key)
+}
+
+/// Default value for a known setting, or None if the key is unknown or has no default.
+pub fn default_value(key: &str) -> Option<&'static str> {
+    find(key).and_then(|def| def.default)
+}
+
+/// Inclusive range of an `Integer` setting, or None for other and unknown keys.
+pub fn integer_range(key: &str) -> Option<(i64, i64)> {
+    match find(key)?.kind {
+        SettingKind::Integer { min, max } => Some((min, max)),
+        _ => None,
+    }
+}
+
+/// Check that `value` is acceptable for `key`.
+/// Unknown keys are rejected unless `allow_unknown` is set.
+pub fn validate(key: &str, value: &str, allow_unknown: bool) -> Result<(), SettingsError> {
+    let Some(def) = find(key) else {
+        if allow_unknown {
+            return Ok(());
+        }
+        return Err(SettingsError::UnknownKey(key.to_string()));
+    };
+
+    let invalid = |reason: String| SettingsError::InvalidValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason,
+    };
+
+    match def.kind {
+        SettingKind::Text => Ok(()),
+        SettingKind::Choice(options) => {
+            if options.contains(&value) {
+                Ok(())
+            } else {
+                Err(invalid(format!("expected one of {}", options.join(", "))))
+            }
+        }
+        SettingKind::Integer { min, max } => {
+            let n: i64 = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("expected an integer".to_string()))?;
+            if n < min || n > max {
+                return Err(invalid(format!("expected a value between {} and {}", min, max)));
+            }
+            Ok(())
+        }
+        SettingKind::Bool => match value {
+            "true" | "false" => Ok(()),
+            _ => Err(invalid("expected true or false".to_string())),
+        },
+        SettingKind::Template => prompt_template::validate(value).map_err(|e| invalid(e.to_string())),
+        SettingKind::FieldList => ai::parse_custom_fields(value).map(|_| ()).map_err(invalid),
+        SettingKind::MonitorIntervals => capture::parse_monitor_intervals(value).map(|_| ()).map_err(invalid),
+        SettingKind::CaptureSource => capture::parse_capture_source(value).map(|_| ()).map_err(invalid),
+        SettingKind::TimeZone => time::TimeZone::from_setting(value).map(|_| ()).map_err(invalid),
+        SettingKind::Url => ai::parse_claude_base_url(value).map(|_| ()).map_err(invalid),
+        SettingKind::Headers => ai::parse_extra_headers(value).map(|_| ()).map_err(invalid),
+    }
+}
+
+/// Describe every known setting for the settings UI.
+pub fn schema() -> Vec<SettingSchema> {
+    SETTINGS
+        .iter()
+        .map(|def| {
+            let (kind, options, min, max) = match def.kind {
+                SettingKind::Text => ("text", Vec::new(), None, None),
+                SettingKind::Choice(options) => (
+                    "choice",
+                    options.iter().map(|o| o.to_string()).collect(),
+                    None,
+                    None,
+                ),
+                SettingKind::Integer { min, max } => ("integer", Vec::new(), Some(min), Some(max)),
+                SettingKind::Bool => ("bool", Vec::new(), None, None),
+                SettingKind::Template => ("template", Vec::new(), None, None),
+                SettingKind::FieldList => ("fields", Vec::new(), None, None),
+                SettingKind::MonitorIntervals => ("monitor_intervals", Vec::new(), None, None),
+                SettingKind::CaptureSource => ("capture_source", Vec::new(), None, None),
+                SettingKind::TimeZone => ("timezone", Vec::new(), None, None),
+                SettingKind::Url => ("url", Vec::new(), None, None),
+                SettingKind::Headers => ("headers", Vec::new(), None, None),
+            };
+            SettingSchema {
+                key: def.key.to_string(),
+                kind: kind.to_string(),
+                options,
+                min,
+                max,
+                default: def.default.map(|d| d.to_string()),
+                description: def.description.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_unknown_key() {
+        let err = validate("captura_monitor_mode", "all", false).unwrap_err();
+        assert!(matches!(err, SettingsError::UnknownKey(_)));
+        assert!(validate("captura_monitor_mode", "all", true).is_ok());
+    }
+
+    #[test]
+    fn test_validate_choice() {
+        assert!(validate("capture_monitor_mode", "all", false).is_ok());
+        let err = validate("capture_monitor_mode", "everything", false).unwrap_err();
+        assert!(err.to_string().contains("expected one of"));
+    }
+
+    #[test]
+    fn test_validate_integer() {
+        assert!(validate("batch_size", "5", false).is_ok());
+        assert!(validate("batch_size", "abc", false).is_err());
+        assert!(validate("batch_size", "0", false).is_err());
+        assert!(validate("batch_size", "101", false).is_err());
+    }
+
+    #[test]
+    fn test_validate_bool() {
+        assert!(validate("session_auto_rotate", "false", false).is_ok());
+        assert!(validate("session_auto_rotate", "yes", false).is_err());
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert!(validate("prompt_template_single", "", false).is_ok());
+        assert!(validate("prompt_template_single", "Context: {context_section} {json_schema}", false).is_ok());
+        let err = validate("prompt_template_multi", "{monitors} {json_schema}", false).unwrap_err();
+        assert!(err.to_string().contains("Unknown placeholder {monitors}"));
+    }
+
+    #[test]
+    fn test_validate_field_list() {
+        assert!(validate("custom_fields", "project, ticket_id", false).is_ok());
+        assert!(validate("custom_fields", "", false).is_ok());
+        let err = validate("custom_fields", "project, category", false).unwrap_err();
+        assert!(err.to_string().contains("built-in field"));
+    }
+
+    #[test]
+    fn test_validate_monitor_intervals() {
+        assert!(validate("monitor_intervals", "2=600000", false).is_ok());
+        let err = validate("monitor_intervals", "2=10", false).unwrap_err();
+        assert!(err.to_string().contains("between"));
+    }
+
+    #[test]
+    fn test_validate_capture_source() {
+        assert!(validate("capture_source", "screen", false).is_ok());
+        assert!(validate("capture_source", "directory:/tmp/frames", false).is_ok());
+        assert!(validate("capture_source", "directory:", false).is_err());
+        assert!(validate("capture_source", "webcam", false).is_err());
+    }
+
+    #[test]
+    fn test_validate_timezone() {
+        assert!(validate("timezone", "system", false).is_ok());
+        assert!(validate("timezone", "-03:00", false).is_ok());
+        assert!(validate("timezone", "EST5EDT,M3.2.0,M11.1.0", false).is_ok());
+        let err = validate("timezone", "EST5EDT,M3.2.0", false).unwrap_err();
+        assert!(err.to_string().contains("end rule"));
+        assert!(validate("timezone", "+24:00", false).is_err());
+    }
+
+    #[test]
+    fn test_default_value() {
+        assert_eq!(default_value("ai_provider"), Some("claude"));
+        assert_eq!(default_value("ai_api_key"), None);
+        assert_eq!(default_value("not_a_setting"), None);
+    }
+
+    #[test]
+    fn test_integer_range() {
+        assert_eq!(integer_range("batch_size"), Some((1, 100)));
+        assert_eq!(integer_range("ai_provider"), None);
+        assert_eq!(integer_range("not_a_setting"), None);
+    }
+
+    #[test]
+    fn test_schema_covers_registry() {
+        let schema = schema();
+        assert_eq!(schema.len(), SETTINGS.len());
+        let batch = schema.iter().find(|s| s.key == "batch_size").unwrap();
+        assert_eq!(batch.kind, "integer");
+        assert_eq!(batch.min, Some(1));
+        assert_eq!(batch.max, Some(100));
+        let mode = schema.iter().find(|s| s.key == "analysis_mode").unwrap();
+        assert_eq!(mode.options, vec!["realtime", "batch"]);
+    }
+}
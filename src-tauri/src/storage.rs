@@ -1,16 +1,105 @@
-use crate::models::{CaptureSession, Screenshot, Task, TaskUpdate};
-use rusqlite::{params, Connection, Result as SqlResult};
-use std::path::Path;
+use crate::activity::{ActivityRecord, ActivityTime};
+use crate::corrections;
+use crate::estimate;
+use crate::savings;
+use crate::time;
+use crate::permissions;
+use crate::models::{AnalysisDebugEntry, ArchivedTask, CaptureGroupSummary, DailyTaskCount, LabeledScreenshot, ScreenshotLabel, PendingGroups, SessionGap, CaptureEvent, CaptureRegion, CaptureSession, SessionStats, SessionStatus, CategoryCorrection, Screenshot, Task, TaskDetail, TaskUpdate, TaskWithProvider, TrainingRow, TrashEntry, TrashKind, UnassignedSummary};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Result as SqlResult};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+/// Settings key marking that the one-time legacy screenshot reconciliation has run.
+/// Internal bookkeeping, not a user setting, so it is not in the settings registry.
+const LEGACY_RECONCILED_KEY: &str = "legacy_screenshots_reconciled";
+
+/// Settings key holding the selected capture region as JSON. Written by the region
+/// overlay rather than the settings UI, so it is not in the registry either.
+const CAPTURE_REGION_KEY: &str = "capture_region";
+
+/// Settings key holding a user-chosen screenshots directory. Changed through
+/// `set_screenshots_dir` (which validates and optionally migrates), not `update_setting`.
+const SCREENSHOTS_DIR_KEY: &str = "screenshots_dir";
+
+/// Settings key holding the lifetime screenshot count, flushed from
+/// `AppState.capture_count` every few captures and when capture stops.
+const CAPTURE_COUNT_KEY: &str = "capture_count";
+
+/// Prefix of the internal settings rows holding recent per-group analysis latencies,
+/// one row per provider/model (`analysis_latency:ollama:qwen3-vl:8b`).
+const ANALYSIS_LATENCY_PREFIX: &str = "analysis_latency:";
+
+/// `WHERE` clause over `screenshots s` for unanalyzed screenshots, optionally only in
+/// session ?1 and never in session ?2 (a NULL parameter disables that filter).
+const UNANALYZED_IN_SESSION: &str = "NOT EXISTS (SELECT 1 FROM task_screenshots ts WHERE ts.screenshot_id = s.id)
+     AND (?1 IS NULL OR s.session_id = ?1)
+     AND (?2 IS NULL OR s.session_id IS NOT ?2)";
+
+/// SQL expression over `capture_sessions cs` giving its `SessionStatus` as text. Shared by
+/// `get_session_status` and the pending/completed session lists so they always agree.
+/// Excluded screenshots don't count as unanalyzed.
+const SESSION_STATUS: &str = "CASE
+         WHEN cs.ended_at IS NULL THEN 'active'
+         WHEN NOT EXISTS (SELECT 1 FROM screenshots s WHERE s.session_id = cs.id) THEN 'empty'
+         WHEN EXISTS (SELECT 1 FROM screenshots s
+                      WHERE s.session_id = cs.id
+                      AND s.excluded = 0
+                      AND s.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)) THEN 'pending'
+         ELSE 'completed'
+     END";
+
+/// Outcome of `insert_activity_samples`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ActivityInsert {
+    pub imported: u32,
+    /// Imported samples that fell within a capture session.
+    pub in_session: u32,
+    pub duplicates: u32,
+    pub bad_timestamps: u32,
+}
+
+/// Reported by `get_session_capture_groups` for screenshots with no capture group
+/// (captured before groups existed). Can't collide with a real, timestamp-based group.
+pub const UNGROUPED_CAPTURE_GROUP: &str = "(ungrouped)";
+
+/// Columns copied between `capture_sessions` and `trashed_sessions`.
+const SESSION_COLUMNS: &str = "id, started_at, ended_at, description, title, paused_ms, paused_at, stats";
+
+/// Columns copied between `screenshots` and `trashed_screenshots`, besides `id` and `session_id`.
+const SCREENSHOT_COLUMNS: &str = "filepath, captured_at, active_window_title, monitor_index, capture_group, excluded, previous_active, mirrored_of, scale_factor, is_primary";
+
+/// Condition matching tasks with no screenshot links, counting links held in the trash,
+/// so a task isn't deleted while its screenshots can still be restored.
+const ORPHAN_TASK: &str = "id NOT IN (SELECT DISTINCT task_id FROM task_screenshots)
+     AND id NOT IN (SELECT DISTINCT task_id FROM trashed_task_screenshots)";
+
 pub struct Database {
     conn: Mutex<Connection>,
+    /// Read-only connection for heavy queries, so they don't block the capture
+    /// loop's inserts on `conn`. WAL mode lets it read alongside the writer.
+    /// None for in-memory databases, where reads share `conn`.
+    read_conn: Option<Mutex<Connection>>,
 }
 
 impl Database {
     /// Lock the database connection, converting a poisoned mutex into a rusqlite error.
     fn conn(&self) -> SqlResult<std::sync::MutexGuard<'_, Connection>> {
-        self.conn.lock().map_err(|e| {
+        Self::lock(&self.conn)
+    }
+
+    /// Lock the read-only connection (falling back to the main connection when there
+    /// is none). Only use this for queries that never write.
+    fn read_conn(&self) -> SqlResult<std::sync::MutexGuard<'_, Connection>> {
+        match &self.read_conn {
+            Some(read_conn) => Self::lock(read_conn),
+            None => self.conn(),
+        }
+    }
+
+    fn lock(conn: &Mutex<Connection>) -> SqlResult<std::sync::MutexGuard<'_, Connection>> {
+        conn.lock().map_err(|e| {
             rusqlite::Error::SqliteFailure(
                 rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
                 Some(format!("Mutex poisoned: {}", e)),
@@ -20,10 +109,19 @@ impl Database {
 
     pub fn new(path: &Path) -> SqlResult<Self> {
         let conn = Connection::open(path)?;
-        let db = Self {
+        // The database holds the API key: owner-only before anything is written, so
+        // the WAL files SQLite creates next to it inherit the same mode
+        permissions::restrict_database(path);
+        let mut db = Self {
             conn: Mutex::new(conn),
+            read_conn: None,
         };
         db.initialize()?;
+        permissions::restrict_database(path);
+
+        // Opened after initialize() so the schema and WAL files already exist
+        let read_conn = Self::open_read_only(path)?;
+        db.read_conn = Some(Mutex::new(read_conn));
         Ok(db)
     }
 
@@ -33,13 +131,98 @@ impl Database {
         let conn = Connection::open_in_memory()?;
         let db = Self {
             conn: Mutex::new(conn),
+            read_conn: None,
         };
         db.initialize()?;
         Ok(db)
     }
 
     fn initialize(&self) -> SqlResult<()> {
-        let conn = self.conn()?;
+        Self::initialize_schema(&*self.conn()?)
+    }
+
+    /// Delete the database and start over with an empty one at `path`, in place: both
+    /// connections are closed and replaced while their locks are held, so everything
+    /// sharing this `Database` waits and then carries on against the new file. Returns
+    /// each file's deletion (`permissions::database_files`, a missing one counts as
+    /// deleted); errors only if the new database can't be opened, in which case both
+    /// connections are reopened on whatever is at `path` rather than left in memory.
+    pub fn recreate(&self, path: &Path) -> SqlResult<Vec<(PathBuf, std::io::Result<()>)>> {
+        let mut conn = self.conn()?;
+        let mut read_conn = match &self.read_conn {
+            Some(read_conn) => Some(Self::lock(read_conn)?),
+            None => None,
+        };
+        let result = Self::replace_files(&mut conn, read_conn.as_deref_mut(), path);
+        if result.is_err() {
+            Self::reopen(&mut conn, read_conn.as_deref_mut(), path);
+        }
+        result
+    }
+
+    fn replace_files(
+        conn: &mut Connection,
+        mut read_conn: Option<&mut Connection>,
+        path: &Path,
+    ) -> SqlResult<Vec<(PathBuf, std::io::Result<()>)>> {
+        // Dropping the old connections closes the files (checkpointing the WAL)
+        *conn = Connection::open_in_memory()?;
+        if let Some(read_conn) = read_conn.as_deref_mut() {
+            *read_conn = Connection::open_in_memory()?;
+        }
+
+        let removed = permissions::database_files(path)
+            .into_iter()
+            .map(|file| {
+                let result = match std::fs::remove_file(&file) {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                };
+                (file, result)
+            })
+            .collect();
+
+        let fresh = Connection::open(path)?;
+        permissions::restrict_database(path);
+        Self::initialize_schema(&fresh)?;
+        permissions::restrict_database(path);
+        *conn = fresh;
+        if let Some(read_conn) = read_conn {
+            *read_conn = Self::open_read_only(path)?;
+        }
+        Ok(removed)
+    }
+
+    /// After a failed `recreate`, put both connections back on `path` (the old database
+    /// if it was never deleted), so the app doesn't carry on against an empty in-memory
+    /// one. Best effort: a connection that can't be reopened is logged and left as is.
+    fn reopen(conn: &mut Connection, read_conn: Option<&mut Connection>, path: &Path) {
+        match Connection::open(path) {
+            Ok(reopened) => {
+                permissions::restrict_database(path);
+                if let Err(e) = Self::initialize_schema(&reopened) {
+                    log::error!("Reopened {} but could not initialize its schema: {}", path.display(), e);
+                }
+                *conn = reopened;
+            }
+            Err(e) => log::error!("Could not reopen {} after a failed recreate: {}", path.display(), e),
+        }
+        if let Some(read_conn) = read_conn {
+            match Self::open_read_only(path) {
+                Ok(reopened) => *read_conn = reopened,
+                Err(e) => log::error!("Could not reopen {} read-only after a failed recreate: {}", path.display(), e),
+            }
+        }
+    }
+
+    fn open_read_only(path: &Path) -> SqlResult<Connection> {
+        Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )
+    }
+
+    fn initialize_schema(conn: &Connection) -> SqlResult<()> {
         conn.execute_batch("PRAGMA journal_mode=WAL;")?;
         conn.execute_batch("PRAGMA foreign_keys=ON;")?;
 
@@ -79,7 +262,100 @@ impl Database {
                 id INTEGER PRIMARY KEY,
                 started_at TEXT NOT NULL,
                 ended_at TEXT
-            );",
+            );
+
+            CREATE TABLE IF NOT EXISTS capture_events (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER REFERENCES capture_sessions(id) ON DELETE CASCADE,
+                occurred_at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                detail TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS category_corrections (
+                id INTEGER PRIMARY KEY,
+                keywords TEXT NOT NULL UNIQUE,
+                category TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS labels (
+                screenshot_id INTEGER PRIMARY KEY REFERENCES screenshots(id) ON DELETE CASCADE,
+                label_title TEXT NOT NULL,
+                label_category TEXT NOT NULL,
+                labeled_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS trashed_sessions (
+                trash_id INTEGER PRIMARY KEY,
+                id INTEGER NOT NULL,
+                started_at TEXT NOT NULL,
+                ended_at TEXT,
+                description TEXT,
+                title TEXT,
+                paused_ms INTEGER NOT NULL DEFAULT 0,
+                paused_at TEXT,
+                stats TEXT,
+                trashed_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS trashed_capture_events (
+                trash_session_id INTEGER NOT NULL REFERENCES trashed_sessions(trash_id) ON DELETE CASCADE,
+                occurred_at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                detail TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS trashed_screenshots (
+                trash_id INTEGER PRIMARY KEY,
+                trash_session_id INTEGER REFERENCES trashed_sessions(trash_id) ON DELETE CASCADE,
+                id INTEGER NOT NULL,
+                filepath TEXT NOT NULL,
+                captured_at TEXT NOT NULL,
+                active_window_title TEXT,
+                monitor_index INTEGER DEFAULT 0,
+                session_id INTEGER,
+                capture_group TEXT,
+                excluded INTEGER NOT NULL DEFAULT 0,
+                previous_active INTEGER NOT NULL DEFAULT 0,
+                mirrored_of INTEGER,
+                scale_factor REAL,
+                is_primary INTEGER,
+                trashed_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS trashed_task_screenshots (
+                task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                trash_screenshot_id INTEGER NOT NULL REFERENCES trashed_screenshots(trash_id) ON DELETE CASCADE,
+                PRIMARY KEY (task_id, trash_screenshot_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS trashed_labels (
+                trash_screenshot_id INTEGER PRIMARY KEY REFERENCES trashed_screenshots(trash_id) ON DELETE CASCADE,
+                label_title TEXT NOT NULL,
+                label_category TEXT NOT NULL,
+                labeled_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS analysis_debug (
+                id INTEGER PRIMARY KEY,
+                run_id TEXT NOT NULL,
+                capture_group TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                image_paths TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_analysis_debug_group ON analysis_debug(capture_group);
+
+            CREATE TABLE IF NOT EXISTS activity_samples (
+                id INTEGER PRIMARY KEY,
+                session_id INTEGER REFERENCES capture_sessions(id) ON DELETE SET NULL,
+                recorded_at TEXT NOT NULL,
+                title TEXT NOT NULL,
+                app TEXT NOT NULL DEFAULT '',
+                UNIQUE (recorded_at, title, app)
+            );
+            CREATE INDEX IF NOT EXISTS idx_capture_sessions_started ON capture_sessions(started_at);",
         )?;
 
         // Migrate: add session_id column to screenshots if it doesn't exist
@@ -134,6 +410,122 @@ impl Database {
             )?;
         }
 
+        // Migrate: add pause tracking columns to capture_sessions if they don't exist
+        let has_paused_ms: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(capture_sessions)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "paused_ms")
+        };
+        if !has_paused_ms {
+            conn.execute_batch(
+                "ALTER TABLE capture_sessions ADD COLUMN paused_ms INTEGER NOT NULL DEFAULT 0;
+                 ALTER TABLE capture_sessions ADD COLUMN paused_at TEXT;"
+            )?;
+        }
+
+        // Migrate: add stats JSON column to capture_sessions if it doesn't exist
+        let has_stats: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(capture_sessions)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "stats")
+        };
+        if !has_stats {
+            conn.execute_batch(
+                "ALTER TABLE capture_sessions ADD COLUMN stats TEXT;"
+            )?;
+        }
+
+        // Migrate: add excluded flag to screenshots if it doesn't exist
+        let has_excluded: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(screenshots)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "excluded")
+        };
+        if !has_excluded {
+            conn.execute_batch(
+                "ALTER TABLE screenshots ADD COLUMN excluded INTEGER NOT NULL DEFAULT 0;"
+            )?;
+        }
+
+        // Migrate: add previous_active flag to screenshots if it doesn't exist
+        let has_previous_active: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(screenshots)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "previous_active")
+        };
+        if !has_previous_active {
+            conn.execute_batch(
+                "ALTER TABLE screenshots ADD COLUMN previous_active INTEGER NOT NULL DEFAULT 0;"
+            )?;
+        }
+
+        // Migrate: add cached embedding (JSON array) to tasks if it doesn't exist
+        let has_embedding: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(tasks)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "embedding")
+        };
+        if !has_embedding {
+            conn.execute_batch("ALTER TABLE tasks ADD COLUMN embedding TEXT;")?;
+        }
+
+        // Migrate: add the model that computed the cached embedding; older caches have none
+        // and are recomputed
+        let has_embedding_model: bool = {
+            let mut stmt = conn.prepare("PRAGMA table_info(tasks)")?;
+            let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            columns.iter().any(|c| c == "embedding_model")
+        };
+        if !has_embedding_model {
+            conn.execute_batch("ALTER TABLE tasks ADD COLUMN embedding_model TEXT;")?;
+        }
+
+        // Migrate: add mirrored_of to screenshots (and their trashed copies) if it doesn't exist
+        for table in ["screenshots", "trashed_screenshots"] {
+            let has_mirrored_of: bool = {
+                let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+                let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                    .collect::<SqlResult<Vec<_>>>()?;
+                columns.iter().any(|c| c == "mirrored_of")
+            };
+            if !has_mirrored_of {
+                conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN mirrored_of INTEGER;", table))?;
+            }
+        }
+
+        // Migrate: add the display scale factor to screenshots (and their trashed copies)
+        for table in ["screenshots", "trashed_screenshots"] {
+            let has_scale_factor: bool = {
+                let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+                let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                    .collect::<SqlResult<Vec<_>>>()?;
+                columns.iter().any(|c| c == "scale_factor")
+            };
+            if !has_scale_factor {
+                conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN scale_factor REAL;", table))?;
+            }
+        }
+
+        // Migrate: add the primary-monitor flag to screenshots (and their trashed copies);
+        // NULL for screenshots captured before it was recorded
+        for table in ["screenshots", "trashed_screenshots"] {
+            let has_is_primary: bool = {
+                let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+                let columns = stmt.query_map([], |row| row.get::<_, String>(1))?
+                    .collect::<SqlResult<Vec<_>>>()?;
+                columns.iter().any(|c| c == "is_primary")
+            };
+            if !has_is_primary {
+                conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN is_primary INTEGER;", table))?;
+            }
+        }
+
         Ok(())
     }
 
@@ -146,6 +538,141 @@ impl Database {
         Ok(conn.last_insert_rowid())
     }
 
+    /// Flag a screenshot as taken of the monitor the user just left (see
+    /// `capture_previous_active`), so analysis can tell the AI.
+    pub fn mark_previous_active(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("UPDATE screenshots SET previous_active = 1 WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Flag a screenshot as showing the same content as `original`, captured in the same
+    /// tick from a mirrored display. Analysis sends only the original to the AI.
+    pub fn mark_mirrored(&self, id: i64, original: i64) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("UPDATE screenshots SET mirrored_of = ?2 WHERE id = ?1", params![id, original])?;
+        Ok(())
+    }
+
+    /// Record the scale factor of the display a screenshot was taken on, for cropping
+    /// it in physical pixels (`capture::scaled_crop_rect`).
+    pub fn set_scale_factor(&self, id: i64, scale_factor: f64) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("UPDATE screenshots SET scale_factor = ?2 WHERE id = ?1", params![id, scale_factor])?;
+        Ok(())
+    }
+
+    /// Record whether a screenshot was taken of the primary monitor, for
+    /// `analyze_monitor_scope = primary_only`.
+    pub fn set_is_primary(&self, id: i64, is_primary: bool) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("UPDATE screenshots SET is_primary = ?2 WHERE id = ?1", params![id, is_primary])?;
+        Ok(())
+    }
+
+    /// Move a screenshot out of analysis: record its new (quarantined) filepath and
+    /// mark it excluded so it no longer counts as unanalyzed.
+    pub fn quarantine_screenshot(&self, id: i64, filepath: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE screenshots SET excluded = 1, filepath = ?1 WHERE id = ?2",
+            params![filepath, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_capture_event(&self, session_id: Option<i64>, occurred_at: &str, kind: &str, detail: Option<&str>) -> SqlResult<i64> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO capture_events (session_id, occurred_at, kind, detail) VALUES (?1, ?2, ?3, ?4)",
+            params![session_id, occurred_at, kind, detail],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_capture_events(&self, session_id: i64) -> SqlResult<Vec<CaptureEvent>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, occurred_at, kind, detail FROM capture_events
+             WHERE session_id = ?1 ORDER BY occurred_at ASC, id ASC",
+        )?;
+        let events = stmt.query_map(params![session_id], |row| {
+            Ok(CaptureEvent {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                occurred_at: row.get(2)?,
+                kind: row.get(3)?,
+                detail: row.get(4)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(events)
+    }
+
+    /// Record what one analysis request sent (`save_analysis_inputs`). `image_paths`
+    /// are relative to the app data dir, in the order the images were attached.
+    pub fn insert_analysis_debug(
+        &self,
+        run_id: &str,
+        capture_group: &str,
+        prompt: &str,
+        image_paths: &[String],
+        created_at: &str,
+    ) -> SqlResult<i64> {
+        let paths = serde_json::to_string(image_paths).unwrap_or_else(|_| "[]".to_string());
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO analysis_debug (run_id, capture_group, prompt, image_paths, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![run_id, capture_group, prompt, paths, created_at],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Every recorded request for a capture group, newest first.
+    pub fn get_analysis_debug(&self, capture_group: &str) -> SqlResult<Vec<AnalysisDebugEntry>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, capture_group, prompt, image_paths, created_at FROM analysis_debug
+             WHERE capture_group = ?1 ORDER BY id DESC",
+        )?;
+        let entries = stmt.query_map(params![capture_group], |row| {
+            let paths: String = row.get(4)?;
+            Ok(AnalysisDebugEntry {
+                id: row.get(0)?,
+                run_id: row.get(1)?,
+                capture_group: row.get(2)?,
+                prompt: row.get(3)?,
+                image_paths: serde_json::from_str(&paths).unwrap_or_default(),
+                created_at: row.get(5)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Delete debug records created before `cutoff`. Returns the image paths no
+    /// remaining record refers to, for the caller to delete.
+    pub fn purge_analysis_debug_before(&self, cutoff: &str) -> SqlResult<Vec<String>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let paths_of = |sql: &str| -> SqlResult<HashSet<String>> {
+            let mut stmt = tx.prepare(sql)?;
+            let rows = stmt.query_map(params![cutoff], |row| row.get::<_, String>(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            Ok(rows.iter()
+                .flat_map(|json| serde_json::from_str::<Vec<String>>(json).unwrap_or_default())
+                .collect())
+        };
+        let expired = paths_of("SELECT image_paths FROM analysis_debug WHERE created_at < ?1")?;
+        let kept = paths_of("SELECT image_paths FROM analysis_debug WHERE created_at >= ?1")?;
+        tx.execute("DELETE FROM analysis_debug WHERE created_at < ?1", params![cutoff])?;
+        tx.commit()?;
+        let mut orphaned: Vec<String> = expired.difference(&kept).cloned().collect();
+        orphaned.sort();
+        Ok(orphaned)
+    }
+
     /// Get the total number of screenshots in the database.
     #[cfg(test)]
     pub fn get_screenshot_count(&self) -> SqlResult<i64> {
@@ -158,7 +685,7 @@ impl Database {
     pub fn get_screenshot(&self, id: i64) -> SqlResult<Screenshot> {
         let conn = self.conn()?;
         conn.query_row(
-            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group FROM screenshots WHERE id = ?1",
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary FROM screenshots WHERE id = ?1",
             params![id],
             |row| {
                 Ok(Screenshot {
@@ -168,41 +695,235 @@ impl Database {
                     active_window_title: row.get(3)?,
                     monitor_index: row.get(4)?,
                     capture_group: row.get(5)?,
+                    mirrored_of: row.get(6)?,
+                    previous_active: row.get(7)?,
+                    scale_factor: row.get(8)?,
+                    is_primary: row.get(9)?,
                 })
             },
         )
     }
 
-    /// Delete all screenshots that have not been linked to any task.
-    /// Returns the filepaths of deleted rows so the caller can remove files from disk.
-    pub fn delete_unanalyzed_screenshots(&self) -> SqlResult<Vec<String>> {
+    /// The most recently captured screenshot, skipping quarantined ones.
+    pub fn get_latest_screenshot(&self) -> SqlResult<Option<Screenshot>> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT s.filepath FROM screenshots s
-             LEFT JOIN task_screenshots ts ON s.id = ts.screenshot_id
-             WHERE ts.task_id IS NULL",
+        let result = conn.query_row(
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary
+             FROM screenshots WHERE excluded = 0
+             ORDER BY captured_at DESC, id DESC LIMIT 1",
+            [],
+            |row| {
+                Ok(Screenshot {
+                    id: row.get(0)?,
+                    filepath: row.get(1)?,
+                    captured_at: row.get(2)?,
+                    active_window_title: row.get(3)?,
+                    monitor_index: row.get(4)?,
+                    capture_group: row.get(5)?,
+                    mirrored_of: row.get(6)?,
+                    previous_active: row.get(7)?,
+                    scale_factor: row.get(8)?,
+                    is_primary: row.get(9)?,
+                })
+            },
+        );
+        match result {
+            Ok(ss) => Ok(Some(ss)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Delete screenshots not linked to any task, only in `session_id` if given and never
+    /// in `exclude_session_id`. Returns each deleted row's id, session and filepath.
+    pub fn delete_unanalyzed_screenshots_for_session(
+        &self,
+        session_id: Option<i64>,
+        exclude_session_id: Option<i64>,
+    ) -> SqlResult<Vec<(i64, Option<i64>, String)>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare(&format!("SELECT s.id, s.session_id, s.filepath FROM screenshots s WHERE {}", UNANALYZED_IN_SESSION))?;
+            let rows = stmt.query_map(params![session_id, exclude_session_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            rows
+        };
+        tx.execute(
+            &format!("DELETE FROM screenshots WHERE id IN (SELECT s.id FROM screenshots s WHERE {})", UNANALYZED_IN_SESSION),
+            params![session_id, exclude_session_id],
         )?;
-        let paths = stmt.query_map([], |row| row.get::<_, String>(0))?
-            .collect::<SqlResult<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(rows)
+    }
+
+    /// Assign a capture_group to a session's legacy screenshots that predate grouping.
+    /// Screenshots sharing a `captured_at` were taken in the same tick, so the group key is
+    /// derived from the timestamp in the same format the capture loop uses
+    /// (`YYYY-MM-DDTHH-MM-SS`). Rows that already have a group are left alone.
+    /// Returns the number of screenshots updated.
+    pub fn backfill_capture_groups(&self, session_id: i64) -> SqlResult<usize> {
+        let conn = self.conn()?;
         conn.execute(
-            "DELETE FROM screenshots WHERE id IN (
-                SELECT s.id FROM screenshots s
-                LEFT JOIN task_screenshots ts ON s.id = ts.screenshot_id
-                WHERE ts.task_id IS NULL
-            )",
-            [],
+            "UPDATE screenshots SET capture_group = replace(captured_at, ':', '-')
+             WHERE session_id = ?1 AND capture_group IS NULL",
+            params![session_id],
+        )
+    }
+
+    /// Collapse unanalyzed screenshots into a single user-verified "other" task per session,
+    /// without any AI calls. With `session_id` None, every session's leftovers are archived
+    /// (sessionless screenshots get their own task), except `exclude_session_id`'s. Runs in
+    /// a single transaction.
+    pub fn archive_unanalyzed_screenshots(
+        &self,
+        session_id: Option<i64>,
+        exclude_session_id: Option<i64>,
+        title: &str,
+    ) -> SqlResult<Vec<ArchivedTask>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let mut by_session: std::collections::BTreeMap<Option<i64>, Vec<(i64, String)>> =
+            std::collections::BTreeMap::new();
+        {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT s.id, s.session_id, s.captured_at
+                 FROM screenshots s
+                 WHERE {} AND s.excluded = 0
+                 ORDER BY s.captured_at ASC",
+                UNANALYZED_IN_SESSION,
+            ))?;
+            let rows = stmt.query_map(params![session_id, exclude_session_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, String>(2)?))
+            })?;
+            for row in rows {
+                let (id, sid, captured_at) = row?;
+                by_session.entry(sid).or_default().push((id, captured_at));
+            }
+        }
+
+        let mut archived = Vec::with_capacity(by_session.len());
+        for (sid, screenshots) in &by_session {
+            let started_at = &screenshots[0].1;
+            let ended_at = &screenshots[screenshots.len() - 1].1;
+            let description = format!(
+                "Auto-archived {} unanalyzed screenshot(s) without AI analysis",
+                screenshots.len()
+            );
+            tx.execute(
+                "INSERT INTO tasks (title, description, category, started_at, ended_at, user_verified)
+                 VALUES (?1, ?2, 'other', ?3, ?4, 1)",
+                params![title, description, started_at, ended_at],
+            )?;
+            let task_id = tx.last_insert_rowid();
+            for (ss_id, _) in screenshots {
+                tx.execute(
+                    "INSERT OR IGNORE INTO task_screenshots (task_id, screenshot_id) VALUES (?1, ?2)",
+                    params![task_id, ss_id],
+                )?;
+            }
+            archived.push(ArchivedTask {
+                session_id: *sid,
+                task_id,
+                screenshot_count: screenshots.len() as u32,
+            });
+        }
+
+        tx.commit()?;
+        Ok(archived)
+    }
+
+    /// Move screenshots with no session (captured by builds that predate sessions) into
+    /// one synthetic "Imported YYYY-MM-DD" session per calendar day, spanning that day's
+    /// first to last screenshot. Returns the created session ids, oldest day first.
+    pub fn reconcile_legacy_screenshots(&self) -> SqlResult<Vec<i64>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let days = tx
+            .prepare(
+                "SELECT substr(captured_at, 1, 10) AS day, MIN(captured_at), MAX(captured_at)
+                 FROM screenshots
+                 WHERE session_id IS NULL
+                 GROUP BY day
+                 ORDER BY day ASC",
+            )?
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let mut session_ids = Vec::with_capacity(days.len());
+        for (day, started_at, ended_at) in &days {
+            tx.execute(
+                "INSERT INTO capture_sessions (started_at, ended_at, title) VALUES (?1, ?2, ?3)",
+                params![started_at, ended_at, format!("Imported {}", day)],
+            )?;
+            let session_id = tx.last_insert_rowid();
+            tx.execute(
+                "UPDATE screenshots SET session_id = ?1
+                 WHERE session_id IS NULL AND substr(captured_at, 1, 10) = ?2",
+                params![session_id, day],
+            )?;
+            session_ids.push(session_id);
+        }
+
+        tx.commit()?;
+        Ok(session_ids)
+    }
+
+    /// Run `reconcile_legacy_screenshots` unless it has already run on this database,
+    /// then record that it has. Returns None when skipped.
+    pub fn reconcile_legacy_screenshots_once(&self) -> SqlResult<Option<Vec<i64>>> {
+        if self.get_setting(LEGACY_RECONCILED_KEY)?.is_some() {
+            return Ok(None);
+        }
+        let session_ids = self.reconcile_legacy_screenshots()?;
+        self.set_setting(LEGACY_RECONCILED_KEY, "true")?;
+        Ok(Some(session_ids))
+    }
+
+    /// Get screenshots that have not been linked to any task yet. Screenshots with no
+    /// session are left out unless `include_unassigned`.
+    pub fn get_unanalyzed_screenshots(&self, limit: i64, include_unassigned: bool) -> SqlResult<Vec<Screenshot>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group, s.mirrored_of, s.previous_active, s.scale_factor, s.is_primary
+             FROM screenshots s
+             LEFT JOIN task_screenshots ts ON s.id = ts.screenshot_id
+             WHERE ts.task_id IS NULL
+             AND s.excluded = 0
+             AND (?2 OR s.session_id IS NOT NULL)
+             ORDER BY s.captured_at ASC
+             LIMIT ?1",
         )?;
-        Ok(paths)
+        let screenshots = stmt.query_map(params![limit, include_unassigned], |row| {
+            Ok(Screenshot {
+                id: row.get(0)?,
+                filepath: row.get(1)?,
+                captured_at: row.get(2)?,
+                active_window_title: row.get(3)?,
+                monitor_index: row.get(4)?,
+                capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(screenshots)
     }
 
-    /// Get screenshots that have not been linked to any task yet.
-    pub fn get_unanalyzed_screenshots(&self, limit: i64) -> SqlResult<Vec<Screenshot>> {
+    /// Get unanalyzed screenshots that belong to no session, oldest first.
+    pub fn get_unassigned_unanalyzed_screenshots(&self, limit: i64) -> SqlResult<Vec<Screenshot>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare(
-            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group, s.mirrored_of, s.previous_active, s.scale_factor, s.is_primary
              FROM screenshots s
              LEFT JOIN task_screenshots ts ON s.id = ts.screenshot_id
              WHERE ts.task_id IS NULL
+             AND s.excluded = 0
+             AND s.session_id IS NULL
              ORDER BY s.captured_at ASC
              LIMIT ?1",
         )?;
@@ -214,6 +935,10 @@ impl Database {
                 active_window_title: row.get(3)?,
                 monitor_index: row.get(4)?,
                 capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
@@ -238,7 +963,7 @@ impl Database {
     }
 
     pub fn get_tasks(&self, limit: i64, offset: i64) -> SqlResult<Vec<Task>> {
-        let conn = self.conn()?;
+        let conn = self.read_conn()?;
         let mut stmt = conn.prepare(
             "SELECT id, title, description, category, started_at, ended_at, ai_reasoning, user_verified, metadata
              FROM tasks ORDER BY started_at DESC LIMIT ?1 OFFSET ?2",
@@ -260,15 +985,105 @@ impl Database {
         Ok(tasks)
     }
 
-    pub fn get_task(&self, id: i64) -> SqlResult<Task> {
-        let conn = self.conn()?;
-        conn.query_row(
+    /// Tasks started on each local day in `zone` from `start_day` to `end_day` (days since
+    /// the epoch, inclusive), oldest first. Days without tasks are included with a count
+    /// of 0.
+    pub fn get_daily_task_counts(&self, zone: &time::TimeZone, start_day: i64, end_day: i64) -> SqlResult<Vec<DailyTaskCount>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT started_at, COUNT(*)
+             FROM tasks
+             WHERE started_at >= ?1 AND started_at < ?2
+             GROUP BY started_at",
+        )?;
+        let (from, _) = zone.day_bounds(start_day);
+        let (_, to) = zone.day_bounds(end_day);
+        let mut counts = vec![0i64; (end_day - start_day + 1).max(0) as usize];
+        let rows = stmt.query_map(params![time::format_db(from), time::format_db(to)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (started_at, count) = row?;
+            let Some(utc) = time::parse_timestamp(&started_at) else {
+                continue;
+            };
+            if let Some(slot) = counts.get_mut((zone.local_day(utc) - start_day) as usize) {
+                *slot += count;
+            }
+        }
+        Ok((start_day..=end_day)
+            .zip(counts)
+            .map(|(day, count)| DailyTaskCount { date: time::format_date(day), count })
+            .collect())
+    }
+
+    /// Tasks in `category` started in `[start, end]`, across all sessions, oldest first.
+    /// `end` is compared at its own precision, so a bare date includes that whole day.
+    pub fn get_tasks_by_category_in_range(&self, category: &str, start: &str, end: &str) -> SqlResult<Vec<Task>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
             "SELECT id, title, description, category, started_at, ended_at, ai_reasoning, user_verified, metadata
-             FROM tasks WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Task {
-                    id: row.get(0)?,
+             FROM tasks
+             WHERE category = ?1
+             AND started_at >= ?2
+             AND substr(started_at, 1, length(?3)) <= ?3
+             ORDER BY started_at ASC, id ASC",
+        )?;
+        let tasks = stmt.query_map(params![category, start, end], |row| {
+            Ok(Task {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                category: row.get(3)?,
+                started_at: row.get(4)?,
+                ended_at: row.get(5)?,
+                ai_reasoning: row.get(6)?,
+                user_verified: row.get(7)?,
+                metadata: row.get(8)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(tasks)
+    }
+
+    /// Like `get_tasks`, with `analyzed_by` pulled out of the metadata JSON
+    /// (None when absent or when metadata isn't valid JSON).
+    pub fn get_tasks_with_provider(&self, limit: i64, offset: i64) -> SqlResult<Vec<TaskWithProvider>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, description, category, started_at, ended_at, ai_reasoning, user_verified, metadata,
+                    CASE WHEN json_valid(metadata) THEN json_extract(metadata, '$.analyzed_by') END
+             FROM tasks ORDER BY started_at DESC LIMIT ?1 OFFSET ?2",
+        )?;
+        let tasks = stmt.query_map(params![limit, offset], |row| {
+            Ok(TaskWithProvider {
+                task: Task {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    description: row.get(2)?,
+                    category: row.get(3)?,
+                    started_at: row.get(4)?,
+                    ended_at: row.get(5)?,
+                    ai_reasoning: row.get(6)?,
+                    user_verified: row.get(7)?,
+                    metadata: row.get(8)?,
+                },
+                analyzed_by: row.get(9)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(tasks)
+    }
+
+    pub fn get_task(&self, id: i64) -> SqlResult<Task> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT id, title, description, category, started_at, ended_at, ai_reasoning, user_verified, metadata
+             FROM tasks WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(Task {
+                    id: row.get(0)?,
                     title: row.get(1)?,
                     description: row.get(2)?,
                     category: row.get(3)?,
@@ -292,23 +1107,126 @@ impl Database {
         Ok(conn.last_insert_rowid())
     }
 
+    /// Apply user edits to a task. Changing the category of an AI-created task
+    /// (one with `ai_reasoning`) is recorded as a category correction. A new title or
+    /// description drops the cached embedding.
     pub fn update_task(&self, id: i64, update: &TaskUpdate) -> SqlResult<()> {
         let conn = self.conn()?;
+        let previous: Option<(String, Option<String>, bool)> = if update.category.is_some() {
+            conn.query_row(
+                "SELECT title, category, ai_reasoning IS NOT NULL FROM tasks WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?
+        } else {
+            None
+        };
+
         if let Some(ref title) = update.title {
             conn.execute("UPDATE tasks SET title = ?1 WHERE id = ?2", params![title, id])?;
         }
         if let Some(ref desc) = update.description {
             conn.execute("UPDATE tasks SET description = ?1 WHERE id = ?2", params![desc, id])?;
         }
+        if update.title.is_some() || update.description.is_some() {
+            conn.execute("UPDATE tasks SET embedding = NULL, embedding_model = NULL WHERE id = ?1", params![id])?;
+        }
         if let Some(ref cat) = update.category {
             conn.execute("UPDATE tasks SET category = ?1 WHERE id = ?2", params![cat, id])?;
         }
         if let Some(ref verified) = update.user_verified {
             conn.execute("UPDATE tasks SET user_verified = ?1 WHERE id = ?2", params![verified, id])?;
         }
+
+        if let (Some((title, old_category, ai_created)), Some(new_category)) = (previous, &update.category) {
+            if ai_created && old_category.as_deref() != Some(new_category.as_str()) {
+                let title = update.title.as_deref().unwrap_or(&title);
+                Self::record_category_correction(&conn, title, new_category)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Upsert the rule for `title`'s keywords. Repeating the same correction bumps
+    /// its count; correcting to a different category replaces the rule.
+    fn record_category_correction(conn: &Connection, title: &str, category: &str) -> SqlResult<()> {
+        let keywords = corrections::keywords(title);
+        if keywords.is_empty() {
+            return Ok(());
+        }
+        conn.execute(
+            "INSERT INTO category_corrections (keywords, category) VALUES (?1, ?2)
+             ON CONFLICT(keywords) DO UPDATE SET
+                count = CASE WHEN category = excluded.category THEN count + 1 ELSE 1 END,
+                category = excluded.category",
+            params![keywords.join(" "), category],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_category_corrections(&self) -> SqlResult<Vec<CategoryCorrection>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, keywords, category, count FROM category_corrections ORDER BY count DESC, id ASC",
+        )?;
+        let rules = stmt.query_map([], |row| {
+            let keywords: String = row.get(1)?;
+            Ok(CategoryCorrection {
+                id: row.get(0)?,
+                keywords: keywords.split(' ').map(|k| k.to_string()).collect(),
+                category: row.get(2)?,
+                count: row.get(3)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(rules)
+    }
+
+    pub fn delete_category_correction(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM category_corrections WHERE id = ?1", params![id])?;
         Ok(())
     }
 
+    pub fn set_task_metadata(&self, id: i64, metadata: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("UPDATE tasks SET metadata = ?1 WHERE id = ?2", params![metadata, id])?;
+        Ok(())
+    }
+
+    /// Cache a task's embedding computed by `model` (see `embedding_model`), stored as a
+    /// JSON array.
+    pub fn set_task_embedding(&self, id: i64, model: &str, embedding: &[f32]) -> SqlResult<()> {
+        let json = serde_json::to_string(embedding).unwrap_or_default();
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE tasks SET embedding = ?1, embedding_model = ?2 WHERE id = ?3",
+            params![json, model, id],
+        )?;
+        Ok(())
+    }
+
+    /// A task's cached embedding by `model`; None if none was computed by that model (or
+    /// it doesn't parse). Vectors of different models aren't comparable even when their
+    /// dimensions match.
+    pub fn get_task_embedding(&self, id: i64, model: &str) -> SqlResult<Option<Vec<f32>>> {
+        let conn = self.conn()?;
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT embedding FROM tasks WHERE id = ?1 AND embedding_model = ?2",
+                params![id, model],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    }
+
     pub fn delete_task(&self, id: i64) -> SqlResult<()> {
         let conn = self.conn()?;
         conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
@@ -324,6 +1242,136 @@ impl Database {
         Ok(())
     }
 
+    /// Merge `merged` tasks into `keep`: their screenshot links (live and trashed) move
+    /// to `keep`, whose time range widens to cover them and whose cached embedding is
+    /// dropped, and the merged tasks are deleted. Runs in a single transaction.
+    pub fn merge_tasks(&self, keep: i64, merged: &[i64]) -> SqlResult<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        for &id in merged.iter().filter(|&&id| id != keep) {
+            tx.execute(
+                "UPDATE tasks SET
+                    started_at = MIN(started_at, (SELECT started_at FROM tasks WHERE id = ?2)),
+                    ended_at = (SELECT MAX(ended_at) FROM tasks WHERE id IN (?1, ?2))
+                 WHERE id = ?1",
+                params![keep, id],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO task_screenshots (task_id, screenshot_id)
+                 SELECT ?1, screenshot_id FROM task_screenshots WHERE task_id = ?2",
+                params![keep, id],
+            )?;
+            tx.execute(
+                "INSERT OR IGNORE INTO trashed_task_screenshots (task_id, trash_screenshot_id)
+                 SELECT ?1, trash_screenshot_id FROM trashed_task_screenshots WHERE task_id = ?2",
+                params![keep, id],
+            )?;
+            // Its remaining links go with it (ON DELETE CASCADE)
+            tx.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+        }
+        tx.execute("UPDATE tasks SET embedding = NULL, embedding_model = NULL WHERE id = ?1", params![keep])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Unlink a session's screenshots from tasks the user hasn't verified, then delete
+    /// those tasks if that left them with no screenshots; other sessions' tasks are never
+    /// touched. Verified tasks and their links are untouched. Returns the number of links
+    /// removed. Runs in a single transaction.
+    pub fn unlink_unverified_tasks(&self, session_id: i64) -> SqlResult<usize> {
+        const UNVERIFIED_LINKS: &str = "task_id IN (SELECT id FROM tasks WHERE user_verified = 0)
+             AND screenshot_id IN (SELECT id FROM screenshots WHERE session_id = ?1)";
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let task_ids = tx
+            .prepare(&format!("SELECT DISTINCT task_id FROM task_screenshots WHERE {}", UNVERIFIED_LINKS))?
+            .query_map(params![session_id], |row| row.get::<_, i64>(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let unlinked = tx.execute(
+            &format!("DELETE FROM task_screenshots WHERE {}", UNVERIFIED_LINKS),
+            params![session_id],
+        )?;
+        for task_id in task_ids {
+            tx.execute(
+                &format!("DELETE FROM tasks WHERE id = ?1 AND {}", ORPHAN_TASK),
+                params![task_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(unlinked)
+    }
+
+    /// Unlink a capture group's screenshots from their tasks, for re-analysis. Returns the
+    /// removed `(screenshot_id, task_id)` links, so a failed re-analysis can put them back
+    /// (`relink_screenshots`). Tasks left without screenshots are kept until
+    /// `delete_orphaned_tasks`. Runs in a single transaction.
+    pub fn unlink_capture_group(&self, capture_group: &str) -> SqlResult<Vec<(i64, i64)>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let links = tx
+            .prepare(
+                "SELECT ts.screenshot_id, ts.task_id FROM task_screenshots ts
+                 JOIN screenshots s ON s.id = ts.screenshot_id
+                 WHERE s.capture_group = ?1
+                 ORDER BY ts.screenshot_id, ts.task_id",
+            )?
+            .query_map(params![capture_group], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        tx.execute(
+            "DELETE FROM task_screenshots
+             WHERE screenshot_id IN (SELECT id FROM screenshots WHERE capture_group = ?1)",
+            params![capture_group],
+        )?;
+        tx.commit()?;
+        Ok(links)
+    }
+
+    /// Put back links removed by `unlink_capture_group`, for screenshots that are still
+    /// unlinked and tasks that still exist. Returns how many were restored.
+    pub fn relink_screenshots(&self, links: &[(i64, i64)]) -> SqlResult<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        // Checked before restoring any, so a screenshot that had several links gets them all
+        let mut unlinked = HashSet::new();
+        for &(screenshot_id, _) in links {
+            let linked: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM task_screenshots WHERE screenshot_id = ?1)",
+                params![screenshot_id],
+                |row| row.get(0),
+            )?;
+            if !linked {
+                unlinked.insert(screenshot_id);
+            }
+        }
+        let mut restored = 0;
+        for &(screenshot_id, task_id) in links.iter().filter(|(screenshot_id, _)| unlinked.contains(screenshot_id)) {
+            restored += tx.execute(
+                "INSERT OR IGNORE INTO task_screenshots (task_id, screenshot_id)
+                 SELECT ?1, ?2 WHERE EXISTS(SELECT 1 FROM tasks WHERE id = ?1)
+                 AND EXISTS(SELECT 1 FROM screenshots WHERE id = ?2)",
+                params![task_id, screenshot_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(restored)
+    }
+
+    /// Delete those of `task_ids` that have no screenshots left (live or trashed), unless
+    /// the user verified them. Returns how many were deleted.
+    pub fn delete_orphaned_tasks(&self, task_ids: &[i64]) -> SqlResult<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for task_id in task_ids {
+            deleted += tx.execute(
+                &format!("DELETE FROM tasks WHERE id = ?1 AND user_verified = 0 AND {}", ORPHAN_TASK),
+                params![task_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
     pub fn create_session(&self, started_at: &str, description: Option<&str>, title: Option<&str>) -> SqlResult<i64> {
         let conn = self.conn()?;
         conn.execute(
@@ -334,54 +1382,263 @@ impl Database {
     }
 
     /// Delete a session and all its associated data.
-    /// Returns the filepaths of deleted screenshots so the caller can remove files from disk.
-    pub fn delete_session(&self, id: i64) -> SqlResult<Vec<String>> {
-        let conn = self.conn()?;
-
-        // 1. Collect screenshot filepaths for this session
-        let mut stmt = conn.prepare(
-            "SELECT filepath FROM screenshots WHERE session_id = ?1",
-        )?;
-        let paths = stmt.query_map(params![id], |row| row.get::<_, String>(0))?
-            .collect::<SqlResult<Vec<_>>>()?;
-
-        // 2. Collect screenshot IDs
-        let mut stmt = conn.prepare(
-            "SELECT id FROM screenshots WHERE session_id = ?1",
+    /// Returns the ids and filepaths of deleted screenshots so the caller can remove files from disk.
+    pub fn delete_session(&self, id: i64) -> SqlResult<Vec<(i64, String)>> {
+        let mut conn = self.conn()?;
+        // One transaction, so a screenshot inserted meanwhile can't lose its row without its file
+        let tx = conn.transaction()?;
+
+        // 1. Collect screenshot IDs and filepaths for this session
+        let mut stmt = tx.prepare(
+            "SELECT id, filepath FROM screenshots WHERE session_id = ?1",
         )?;
-        let screenshot_ids = stmt.query_map(params![id], |row| row.get::<_, i64>(0))?
+        let screenshots = stmt.query_map(params![id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
             .collect::<SqlResult<Vec<_>>>()?;
+        drop(stmt);
 
-        // 3. Delete task_screenshots links for these screenshots
-        for ss_id in &screenshot_ids {
-            conn.execute(
+        // 2. Delete task_screenshots links for these screenshots
+        for (ss_id, _) in &screenshots {
+            tx.execute(
                 "DELETE FROM task_screenshots WHERE screenshot_id = ?1",
                 params![ss_id],
             )?;
         }
 
-        // 4. Delete orphaned tasks (tasks with no remaining screenshot links)
-        conn.execute(
-            "DELETE FROM tasks WHERE id NOT IN (SELECT DISTINCT task_id FROM task_screenshots)",
-            [],
-        )?;
+        // 3. Delete orphaned tasks (no remaining screenshot links, live or in the trash)
+        tx.execute(&format!("DELETE FROM tasks WHERE {}", ORPHAN_TASK), [])?;
 
-        // 5. Delete screenshots
-        conn.execute(
+        // 4. Delete screenshots
+        tx.execute(
             "DELETE FROM screenshots WHERE session_id = ?1",
             params![id],
         )?;
 
-        // 6. Delete the session
-        conn.execute(
+        // 5. Delete the session
+        tx.execute(
             "DELETE FROM capture_sessions WHERE id = ?1",
             params![id],
         )?;
 
+        tx.commit()?;
+        Ok(screenshots)
+    }
+
+    /// Delete one screenshot for good, then tasks left with no screenshot links.
+    /// Returns its filepath, or None if there's no such screenshot.
+    pub fn delete_screenshot(&self, id: i64) -> SqlResult<Option<String>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let filepath: Option<String> = tx.query_row(
+            "SELECT filepath FROM screenshots WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        ).optional()?;
+        if filepath.is_some() {
+            tx.execute("DELETE FROM screenshots WHERE id = ?1", params![id])?;
+            tx.execute(&format!("DELETE FROM tasks WHERE {}", ORPHAN_TASK), [])?;
+        }
+        tx.commit()?;
+        Ok(filepath)
+    }
+
+    /// Move a session, its screenshots (with their task links and labels) and its capture
+    /// events into the trash tables. Tasks are kept even if this leaves them without live
+    /// screenshots; `purge_trash` applies the orphan rule once the trash is emptied.
+    /// Returns the moved screenshots' ids and filepaths so the caller can move the files to
+    /// `trash/`. Runs in a single transaction; an unknown session moves nothing.
+    pub fn trash_session(&self, id: i64, trashed_at: &str) -> SqlResult<Vec<(i64, String)>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let moved = tx.execute(
+            &format!(
+                "INSERT INTO trashed_sessions ({cols}, trashed_at)
+                 SELECT {cols}, ?2 FROM capture_sessions WHERE id = ?1",
+                cols = SESSION_COLUMNS
+            ),
+            params![id, trashed_at],
+        )?;
+        if moved == 0 {
+            return Ok(Vec::new());
+        }
+        let trash_session_id = tx.last_insert_rowid();
+
+        tx.execute(
+            "INSERT INTO trashed_capture_events (trash_session_id, occurred_at, kind, detail)
+             SELECT ?2, occurred_at, kind, detail FROM capture_events WHERE session_id = ?1",
+            params![id, trash_session_id],
+        )?;
+        // Screenshots trashed on their own earlier now come back with the session
+        tx.execute(
+            "UPDATE trashed_screenshots SET trash_session_id = ?2
+             WHERE session_id = ?1 AND trash_session_id IS NULL",
+            params![id, trash_session_id],
+        )?;
+        let screenshot_ids = {
+            let mut stmt = tx.prepare("SELECT id FROM screenshots WHERE session_id = ?1")?;
+            let ids = stmt.query_map(params![id], |row| row.get::<_, i64>(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            ids
+        };
+        let mut moved = Vec::with_capacity(screenshot_ids.len());
+        for screenshot_id in screenshot_ids {
+            let path = trash_screenshot_row(&tx, screenshot_id, Some(trash_session_id), trashed_at)?;
+            moved.extend(path.map(|path| (screenshot_id, path)));
+        }
+        tx.execute("DELETE FROM capture_sessions WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    /// Move screenshots (with their task links and labels) into the trash tables, keeping
+    /// their tasks. Returns the moved filepaths; unknown ids are skipped.
+    pub fn trash_screenshots(&self, ids: &[i64], trashed_at: &str) -> SqlResult<Vec<String>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let mut paths = Vec::with_capacity(ids.len());
+        for &id in ids {
+            paths.extend(trash_screenshot_row(&tx, id, None, trashed_at)?);
+        }
+        tx.commit()?;
+        Ok(paths)
+    }
+
+    /// Count and date range of unanalyzed screenshots that belong to no session.
+    pub fn get_unassigned_summary(&self) -> SqlResult<UnassignedSummary> {
+        let conn = self.read_conn()?;
+        conn.query_row(
+            "SELECT COUNT(*), MIN(s.captured_at), MAX(s.captured_at)
+             FROM screenshots s
+             WHERE s.session_id IS NULL
+             AND s.excluded = 0
+             AND s.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)",
+            [],
+            |row| {
+                Ok(UnassignedSummary {
+                    count: row.get(0)?,
+                    first_captured_at: row.get(1)?,
+                    last_captured_at: row.get(2)?,
+                })
+            },
+        )
+    }
+
+    /// Move sessionless screenshots into `session_id`, which must exist (checked in the
+    /// same transaction). Ids that already have a session or don't exist are skipped.
+    /// Returns how many were assigned.
+    pub fn assign_screenshots_to_session(&self, ids: &[i64], session_id: i64) -> SqlResult<usize> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        tx.query_row("SELECT 1 FROM capture_sessions WHERE id = ?1", params![session_id], |_| Ok(()))?;
+        let mut assigned = 0;
+        for &id in ids {
+            assigned += tx.execute(
+                "UPDATE screenshots SET session_id = ?1 WHERE id = ?2 AND session_id IS NULL",
+                params![session_id, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(assigned)
+    }
+
+    /// Ids and sessions of screenshots not linked to any task (what `clear_pending`
+    /// removes), filtered like `delete_unanalyzed_screenshots_for_session`.
+    pub fn get_unanalyzed_screenshot_ids(
+        &self,
+        session_id: Option<i64>,
+        exclude_session_id: Option<i64>,
+    ) -> SqlResult<Vec<(i64, Option<i64>)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!("SELECT s.id, s.session_id FROM screenshots s WHERE {}", UNANALYZED_IN_SESSION))?;
+        let ids = stmt.query_map(params![session_id, exclude_session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Move a trash entry back: a trashed session with everything trashed with it, or a
+    /// single screenshot (its whole session if that is in the trash too). Rows get their
+    /// old ids back unless a newer row took them meanwhile, in which case they're
+    /// renumbered and their links follow. Task links and labels are restored for tasks
+    /// that still exist. Returns the restored screenshots' filepaths, or None if there's
+    /// no such trash entry.
+    pub fn restore_from_trash(&self, kind: TrashKind, trash_id: i64) -> SqlResult<Option<Vec<String>>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let trash_session_id = match kind {
+            TrashKind::Session => Some(trash_id),
+            TrashKind::Screenshot => {
+                let owner: Option<Option<i64>> = tx.query_row(
+                    "SELECT trash_session_id FROM trashed_screenshots WHERE trash_id = ?1",
+                    params![trash_id],
+                    |row| row.get(0),
+                ).optional()?;
+                match owner {
+                    None => return Ok(None),
+                    Some(owner) => owner,
+                }
+            }
+        };
+        let paths = match trash_session_id {
+            Some(trash_session_id) => restore_session_rows(&tx, trash_session_id)?,
+            None => Some(vec![restore_screenshot_row(&tx, trash_id, None)?]),
+        };
+        tx.commit()?;
+        Ok(paths)
+    }
+
+    /// Everything in the trash, newest first. Screenshots trashed with (or before) their
+    /// session are listed under the session.
+    pub fn list_trash(&self) -> SqlResult<Vec<TrashEntry>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT 'session', ts.trash_id, ts.id, COALESCE(ts.title, ts.started_at), ts.trashed_at,
+                    (SELECT COUNT(*) FROM trashed_screenshots s WHERE s.trash_session_id = ts.trash_id)
+             FROM trashed_sessions ts
+             UNION ALL
+             SELECT 'screenshot', trash_id, id, COALESCE(active_window_title, captured_at), trashed_at, 1
+             FROM trashed_screenshots WHERE trash_session_id IS NULL
+             ORDER BY 5 DESC, 2 DESC",
+        )?;
+        let entries = stmt.query_map([], |row| {
+            let kind: String = row.get(0)?;
+            Ok(TrashEntry {
+                kind: if kind == "session" { TrashKind::Session } else { TrashKind::Screenshot },
+                trash_id: row.get(1)?,
+                original_id: row.get(2)?,
+                label: row.get(3)?,
+                trashed_at: row.get(4)?,
+                screenshot_count: row.get(5)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Permanently delete trash entries trashed before `cutoff` (all of them for None),
+    /// then tasks left with no screenshot links anywhere. Returns the purged screenshots'
+    /// filepaths so the caller can delete the files from `trash/`.
+    pub fn purge_trash(&self, cutoff: Option<&str>) -> SqlResult<Vec<String>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let paths = {
+            let mut stmt = tx.prepare(
+                "SELECT s.filepath FROM trashed_screenshots s
+                 LEFT JOIN trashed_sessions ts ON ts.trash_id = s.trash_session_id
+                 WHERE ?1 IS NULL OR s.trashed_at < ?1 OR ts.trashed_at < ?1",
+            )?;
+            let paths = stmt.query_map(params![cutoff], |row| row.get::<_, String>(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            paths
+        };
+        // Screenshots and events trashed with a session go with it (ON DELETE CASCADE)
+        tx.execute("DELETE FROM trashed_sessions WHERE ?1 IS NULL OR trashed_at < ?1", params![cutoff])?;
+        tx.execute("DELETE FROM trashed_screenshots WHERE ?1 IS NULL OR trashed_at < ?1", params![cutoff])?;
+        tx.execute(&format!("DELETE FROM tasks WHERE {}", ORPHAN_TASK), [])?;
+        tx.commit()?;
         Ok(paths)
     }
 
     pub fn end_session(&self, id: i64, ended_at: &str) -> SqlResult<()> {
+        // Close out a pause that is still open so it doesn't count towards the duration
+        self.resume_session(id, ended_at)?;
         let conn = self.conn()?;
         conn.execute(
             "UPDATE capture_sessions SET ended_at = ?1 WHERE id = ?2",
@@ -390,8 +1647,249 @@ impl Database {
         Ok(())
     }
 
-    pub fn get_sessions(&self, limit: i64, offset: i64) -> SqlResult<Vec<CaptureSession>> {
+    /// Record the start of a pause. Does nothing if the session is already paused.
+    pub fn pause_session(&self, id: i64, paused_at: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE capture_sessions SET paused_at = ?1 WHERE id = ?2 AND paused_at IS NULL",
+            params![paused_at, id],
+        )?;
+        Ok(())
+    }
+
+    /// End an open pause, adding its length to the session's accumulated paused time.
+    pub fn resume_session(&self, id: i64, resumed_at: &str) -> SqlResult<()> {
         let conn = self.conn()?;
+        conn.execute(
+            "UPDATE capture_sessions
+             SET paused_ms = paused_ms + MAX(0, CAST(ROUND((julianday(?1) - julianday(paused_at)) * 86400000) AS INTEGER)),
+                 paused_at = NULL
+             WHERE id = ?2 AND paused_at IS NOT NULL",
+            params![resumed_at, id],
+        )?;
+        Ok(())
+    }
+
+    /// Active duration of a session in seconds, excluding paused time.
+    /// Sessions still running (or paused) are measured up to `now`.
+    pub fn get_session_duration(&self, id: i64, now: &str) -> SqlResult<i64> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT CAST(ROUND((julianday(COALESCE(ended_at, ?2)) - julianday(started_at)) * 86400) AS INTEGER)
+                    - paused_ms / 1000
+                    - CASE WHEN paused_at IS NULL THEN 0
+                           ELSE CAST(ROUND((julianday(?2) - julianday(paused_at)) * 86400) AS INTEGER)
+                      END
+             FROM capture_sessions WHERE id = ?1",
+            params![id, now],
+            |row| row.get(0),
+        )
+    }
+
+    /// Intervals longer than `min_gap_ms` between consecutive screenshots of a session,
+    /// in capture order. Screenshots of the same tick share a timestamp, so monitors
+    /// don't count as gaps.
+    pub fn get_session_gaps(&self, session_id: i64, min_gap_ms: i64) -> SqlResult<Vec<SessionGap>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT prev, captured_at,
+                    CAST(ROUND((julianday(captured_at) - julianday(prev)) * 86400) AS INTEGER)
+             FROM (
+                 SELECT captured_at, LAG(captured_at) OVER (ORDER BY captured_at, id) AS prev
+                 FROM screenshots WHERE session_id = ?1
+             )
+             WHERE prev IS NOT NULL
+             AND (julianday(captured_at) - julianday(prev)) * 86400000 > ?2
+             ORDER BY captured_at",
+        )?;
+        let gaps = stmt.query_map(params![session_id, min_gap_ms], |row| {
+            Ok(SessionGap {
+                start: row.get(0)?,
+                end: row.get(1)?,
+                gap_seconds: row.get(2)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(gaps)
+    }
+
+    /// Distinct window titles of a session's screenshots with how many screenshots had
+    /// each. Screenshots without a title are left out.
+    pub fn get_session_window_titles(&self, session_id: i64) -> SqlResult<Vec<(String, u32)>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT active_window_title, COUNT(*) FROM screenshots
+             WHERE session_id = ?1 AND active_window_title IS NOT NULL
+             GROUP BY active_window_title",
+        )?;
+        let titles = stmt
+            .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(titles)
+    }
+
+    /// Set a session's `started_at` to its earliest screenshot and, if it has ended,
+    /// `ended_at` to its latest. Paused time is capped at the new span so durations
+    /// can't go negative. Returns false (and changes nothing) if the session has no
+    /// screenshots.
+    pub fn normalize_session_bounds(&self, session_id: i64) -> SqlResult<bool> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "WITH bounds AS (
+                 SELECT MIN(captured_at) AS first, MAX(captured_at) AS last
+                 FROM screenshots WHERE session_id = ?1
+             )
+             UPDATE capture_sessions SET
+                 started_at = (SELECT first FROM bounds),
+                 ended_at = CASE WHEN ended_at IS NULL THEN NULL ELSE (SELECT last FROM bounds) END,
+                 paused_ms = CASE WHEN ended_at IS NULL THEN paused_ms ELSE MIN(paused_ms, (
+                     SELECT CAST(ROUND((julianday(last) - julianday(first)) * 86400000) AS INTEGER) FROM bounds
+                 )) END
+             WHERE id = ?1 AND (SELECT first FROM bounds) IS NOT NULL",
+            params![session_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// A session's capture groups with their screenshot count and first capture time,
+    /// oldest first. Ungrouped legacy screenshots are reported together under
+    /// `UNGROUPED_CAPTURE_GROUP`.
+    pub fn get_session_capture_groups(&self, session_id: i64) -> SqlResult<Vec<CaptureGroupSummary>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(capture_group, ?2), COUNT(*), MIN(captured_at)
+             FROM screenshots
+             WHERE session_id = ?1
+             GROUP BY capture_group
+             ORDER BY MIN(captured_at), capture_group",
+        )?;
+        let groups = stmt.query_map(params![session_id, UNGROUPED_CAPTURE_GROUP], |row| {
+            Ok(CaptureGroupSummary {
+                group: row.get(0)?,
+                screenshot_count: row.get(1)?,
+                captured_at: row.get(2)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(groups)
+    }
+
+    /// Set (or replace) the ground-truth label of a screenshot.
+    pub fn set_screenshot_label(&self, screenshot_id: i64, title: &str, category: &str, labeled_at: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO labels (screenshot_id, label_title, label_category, labeled_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(screenshot_id) DO UPDATE SET
+                 label_title = excluded.label_title,
+                 label_category = excluded.label_category,
+                 labeled_at = excluded.labeled_at",
+            params![screenshot_id, title, category, labeled_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_screenshot_label(&self, screenshot_id: i64) -> SqlResult<Option<ScreenshotLabel>> {
+        let conn = self.read_conn()?;
+        match conn.query_row(
+            "SELECT screenshot_id, label_title, label_category, labeled_at FROM labels WHERE screenshot_id = ?1",
+            params![screenshot_id],
+            |row| {
+                Ok(ScreenshotLabel {
+                    screenshot_id: row.get(0)?,
+                    label_title: row.get(1)?,
+                    label_category: row.get(2)?,
+                    labeled_at: row.get(3)?,
+                })
+            },
+        ) {
+            Ok(label) => Ok(Some(label)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Labeled screenshots in capture order, optionally limited to one session.
+    pub fn list_labeled_screenshots(&self, session_id: Option<i64>) -> SqlResult<Vec<LabeledScreenshot>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group, s.mirrored_of, s.previous_active, s.scale_factor, s.is_primary,
+                    l.label_title, l.label_category, l.labeled_at
+             FROM labels l
+             INNER JOIN screenshots s ON l.screenshot_id = s.id
+             WHERE ?1 IS NULL OR s.session_id = ?1
+             ORDER BY s.captured_at ASC, s.id ASC",
+        )?;
+        let labeled = stmt.query_map(params![session_id], |row| {
+            Ok(LabeledScreenshot {
+                screenshot: Screenshot {
+                    id: row.get(0)?,
+                    filepath: row.get(1)?,
+                    captured_at: row.get(2)?,
+                    active_window_title: row.get(3)?,
+                    monitor_index: row.get(4)?,
+                    capture_group: row.get(5)?,
+                    mirrored_of: row.get(6)?,
+                    previous_active: row.get(7)?,
+                    scale_factor: row.get(8)?,
+                    is_primary: row.get(9)?,
+                },
+                label: ScreenshotLabel {
+                    screenshot_id: row.get(0)?,
+                    label_title: row.get(10)?,
+                    label_category: row.get(11)?,
+                    labeled_at: row.get(12)?,
+                },
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(labeled)
+    }
+
+    /// Each labeled screenshot of a session with the task it's linked to (the lowest
+    /// task id if several), or None if it hasn't been analyzed.
+    pub fn get_session_label_tasks(&self, session_id: i64) -> SqlResult<Vec<(ScreenshotLabel, Option<Task>)>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT l.screenshot_id, l.label_title, l.label_category, l.labeled_at,
+                    t.id, t.title, t.description, t.category, t.started_at, t.ended_at,
+                    t.ai_reasoning, t.user_verified, t.metadata
+             FROM labels l
+             INNER JOIN screenshots s ON l.screenshot_id = s.id
+             LEFT JOIN tasks t ON t.id = (
+                 SELECT MIN(ts.task_id) FROM task_screenshots ts WHERE ts.screenshot_id = l.screenshot_id
+             )
+             WHERE s.session_id = ?1
+             ORDER BY s.captured_at ASC, s.id ASC",
+        )?;
+        let pairs = stmt.query_map(params![session_id], |row| {
+            let label = ScreenshotLabel {
+                screenshot_id: row.get(0)?,
+                label_title: row.get(1)?,
+                label_category: row.get(2)?,
+                labeled_at: row.get(3)?,
+            };
+            let task = match row.get::<_, Option<i64>>(4)? {
+                Some(id) => Some(Task {
+                    id,
+                    title: row.get(5)?,
+                    description: row.get(6)?,
+                    category: row.get(7)?,
+                    started_at: row.get(8)?,
+                    ended_at: row.get(9)?,
+                    ai_reasoning: row.get(10)?,
+                    user_verified: row.get(11)?,
+                    metadata: row.get(12)?,
+                }),
+                None => None,
+            };
+            Ok((label, task))
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(pairs)
+    }
+
+    pub fn get_sessions(&self, limit: i64, offset: i64) -> SqlResult<Vec<CaptureSession>> {
+        let conn = self.read_conn()?;
         let mut stmt = conn.prepare(
             "SELECT cs.id, cs.started_at, cs.ended_at,
                     (SELECT COUNT(*) FROM screenshots s WHERE s.session_id = cs.id) as screenshot_count,
@@ -399,7 +1897,13 @@ impl Database {
                     (SELECT COUNT(*) FROM screenshots s2
                      WHERE s2.session_id = cs.id
                      AND s2.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
-                    ) as unanalyzed_count
+                     AND s2.excluded = 0
+                    ) as unanalyzed_count,
+                    CASE WHEN cs.ended_at IS NULL THEN NULL
+                         ELSE CAST(ROUND((julianday(cs.ended_at) - julianday(cs.started_at)) * 86400) AS INTEGER)
+                              - cs.paused_ms / 1000
+                    END as duration_seconds,
+                    cs.stats
              FROM capture_sessions cs
              ORDER BY cs.started_at DESC
              LIMIT ?1 OFFSET ?2",
@@ -413,39 +1917,203 @@ impl Database {
                 description: row.get(4)?,
                 title: row.get(5)?,
                 unanalyzed_count: row.get(6)?,
+                duration_seconds: row.get(7)?,
+                stats: parse_session_stats(row.get(8)?),
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
         Ok(sessions)
     }
 
-    pub fn get_session(&self, id: i64) -> SqlResult<CaptureSession> {
+    pub fn get_session(&self, id: i64) -> SqlResult<CaptureSession> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT cs.id, cs.started_at, cs.ended_at,
+                    (SELECT COUNT(*) FROM screenshots s WHERE s.session_id = cs.id) as screenshot_count,
+                    cs.description, cs.title,
+                    (SELECT COUNT(*) FROM screenshots s2
+                     WHERE s2.session_id = cs.id
+                     AND s2.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
+                     AND s2.excluded = 0
+                    ) as unanalyzed_count,
+                    CASE WHEN cs.ended_at IS NULL THEN NULL
+                         ELSE CAST(ROUND((julianday(cs.ended_at) - julianday(cs.started_at)) * 86400) AS INTEGER)
+                              - cs.paused_ms / 1000
+                    END as duration_seconds,
+                    cs.stats
+             FROM capture_sessions cs
+             WHERE cs.id = ?1",
+            params![id],
+            |row| {
+                Ok(CaptureSession {
+                    id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    ended_at: row.get(2)?,
+                    screenshot_count: row.get(3)?,
+                    description: row.get(4)?,
+                    title: row.get(5)?,
+                    unanalyzed_count: row.get(6)?,
+                    duration_seconds: row.get(7)?,
+                    stats: parse_session_stats(row.get(8)?),
+                })
+            },
+        )
+    }
+
+    /// Add `delta` to a session's stored stats.
+    pub fn add_session_stats(&self, session_id: i64, delta: &SessionStats) -> SqlResult<()> {
+        let conn = self.conn()?;
+        let raw: Option<String> = conn.query_row(
+            "SELECT stats FROM capture_sessions WHERE id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+        let mut stats = parse_session_stats(raw);
+        savings::merge(&mut stats, delta);
+        let json = serde_json::to_string(&stats).unwrap_or_default();
+        conn.execute(
+            "UPDATE capture_sessions SET stats = ?1 WHERE id = ?2",
+            params![json, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Stats of sessions started in `[from, to]`. Either bound may be omitted; `to` is
+    /// compared at its own precision, so a bare date includes that whole day.
+    pub fn get_session_stats_between(&self, from: Option<&str>, to: Option<&str>) -> SqlResult<Vec<SessionStats>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT stats FROM capture_sessions
+             WHERE (?1 IS NULL OR started_at >= ?1)
+             AND (?2 IS NULL OR substr(started_at, 1, length(?2)) <= ?2)",
+        )?;
+        let stats = stmt.query_map(params![from, to], |row| Ok(parse_session_stats(row.get(0)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(stats)
+    }
+
+    /// Call `f` for every analyzed screenshot/task link, oldest screenshot first. Rows are
+    /// streamed from the statement rather than collected, so large databases export in
+    /// constant memory. Quarantined screenshots are skipped, as are links to unverified
+    /// tasks unless `include_unverified`. Holds the read connection until done.
+    pub fn for_each_training_row<E, F>(&self, include_unverified: bool, mut f: F) -> Result<(), E>
+    where
+        E: From<rusqlite::Error>,
+        F: FnMut(TrainingRow) -> Result<(), E>,
+    {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index,
+                    s.session_id, cs.description, t.id, t.title, t.category, t.user_verified
+             FROM screenshots s
+             JOIN task_screenshots ts ON ts.screenshot_id = s.id
+             JOIN tasks t ON t.id = ts.task_id
+             LEFT JOIN capture_sessions cs ON cs.id = s.session_id
+             WHERE s.excluded = 0
+             AND (?1 OR t.user_verified = 1)
+             ORDER BY s.captured_at ASC, s.id ASC, t.id ASC",
+        )?;
+        let mut rows = stmt.query(params![include_unverified])?;
+        while let Some(row) = rows.next()? {
+            f(TrainingRow {
+                screenshot_id: row.get(0)?,
+                filepath: row.get(1)?,
+                captured_at: row.get(2)?,
+                active_window_title: row.get(3)?,
+                monitor_index: row.get(4)?,
+                session_id: row.get(5)?,
+                session_description: row.get(6)?,
+                task_id: row.get(7)?,
+                task_title: row.get(8)?,
+                category: row.get(9)?,
+                user_verified: row.get(10)?,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Whether any sessions, screenshots or tasks exist.
+    pub fn has_data(&self) -> SqlResult<bool> {
         let conn = self.conn()?;
         conn.query_row(
-            "SELECT cs.id, cs.started_at, cs.ended_at,
-                    (SELECT COUNT(*) FROM screenshots s WHERE s.session_id = cs.id) as screenshot_count,
-                    cs.description, cs.title,
-                    (SELECT COUNT(*) FROM screenshots s2
-                     WHERE s2.session_id = cs.id
-                     AND s2.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
-                    ) as unanalyzed_count
-             FROM capture_sessions cs
-             WHERE cs.id = ?1",
-            params![id],
-            |row| {
-                Ok(CaptureSession {
-                    id: row.get(0)?,
-                    started_at: row.get(1)?,
-                    ended_at: row.get(2)?,
-                    screenshot_count: row.get(3)?,
-                    description: row.get(4)?,
-                    title: row.get(5)?,
-                    unanalyzed_count: row.get(6)?,
-                })
-            },
+            "SELECT EXISTS(SELECT 1 FROM capture_sessions)
+                 OR EXISTS(SELECT 1 FROM screenshots)
+                 OR EXISTS(SELECT 1 FROM tasks)",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Stored filepaths of every live screenshot, for deleting exactly the files the app
+    /// wrote (trashed ones are all under `trash/`).
+    pub fn all_screenshot_filepaths(&self) -> SqlResult<Vec<String>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare("SELECT filepath FROM screenshots ORDER BY id")?;
+        let paths = stmt.query_map([], |row| row.get(0))?.collect();
+        paths
+    }
+
+    /// Whether any screenshot rows, live or in the trash, point at files in the
+    /// screenshots directory.
+    pub fn has_screenshot_files(&self) -> SqlResult<bool> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM screenshots) OR EXISTS(SELECT 1 FROM trashed_screenshots)",
+            [],
+            |row| row.get(0),
         )
     }
 
+    /// Every row of `table`, all columns. `table` must be a trusted name, never user input.
+    pub fn table_rows(&self, table: &str) -> SqlResult<Vec<RowMap>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {}", table))?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let rows = stmt.query_map([], |row| {
+            let mut map = RowMap::new();
+            for (i, name) in columns.iter().enumerate() {
+                map.insert(name.clone(), sql_to_json(row.get_ref(i)?));
+            }
+            Ok(map)
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Replace the contents of `tables` (listed parents first, so deletes run in reverse)
+    /// with the given rows. Columns the current schema doesn't have are ignored.
+    /// Table names must be trusted. Runs in a single transaction.
+    pub fn replace_tables(&self, tables: &[(&str, &[RowMap])]) -> SqlResult<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        for (table, _) in tables.iter().rev() {
+            tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        for (table, rows) in tables {
+            let known: Vec<String> = tx
+                .prepare(&format!("PRAGMA table_info({})", table))?
+                .query_map([], |row| row.get::<_, String>(1))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            for row in rows.iter() {
+                let columns: Vec<&String> = row.keys().filter(|k| known.contains(k)).collect();
+                if columns.is_empty() {
+                    continue;
+                }
+                let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+                let sql = format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    columns.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", "),
+                    placeholders.join(", "),
+                );
+                tx.execute(&sql, params_from_iter(columns.iter().map(|c| json_to_sql(&row[*c]))))?;
+            }
+        }
+
+        tx.commit()
+    }
+
     /// Get the session_id for a given screenshot, if any.
     pub fn get_screenshot_session_id(&self, screenshot_id: i64) -> SqlResult<Option<i64>> {
         let conn = self.conn()?;
@@ -457,9 +2125,9 @@ impl Database {
     }
 
     pub fn get_session_screenshots(&self, session_id: i64) -> SqlResult<Vec<Screenshot>> {
-        let conn = self.conn()?;
+        let conn = self.read_conn()?;
         let mut stmt = conn.prepare(
-            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary
              FROM screenshots
              WHERE session_id = ?1
              ORDER BY captured_at ASC",
@@ -472,32 +2140,94 @@ impl Database {
                 active_window_title: row.get(3)?,
                 monitor_index: row.get(4)?,
                 capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
         Ok(screenshots)
     }
 
+    /// Get the screenshots immediately before and after the given one within its session,
+    /// ordered by `captured_at` (ties broken by id). Either side is None at the session edges.
+    pub fn get_screenshot_neighbors(&self, screenshot_id: i64) -> SqlResult<(Option<Screenshot>, Option<Screenshot>)> {
+        let conn = self.conn()?;
+        let (session_id, captured_at): (Option<i64>, String) = conn.query_row(
+            "SELECT session_id, captured_at FROM screenshots WHERE id = ?1",
+            params![screenshot_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        let neighbor = |sql: &str| -> SqlResult<Option<Screenshot>> {
+            let result = conn.query_row(
+                sql,
+                params![session_id, captured_at, screenshot_id],
+                |row| {
+                    Ok(Screenshot {
+                        id: row.get(0)?,
+                        filepath: row.get(1)?,
+                        captured_at: row.get(2)?,
+                        active_window_title: row.get(3)?,
+                        monitor_index: row.get(4)?,
+                        capture_group: row.get(5)?,
+                        mirrored_of: row.get(6)?,
+                        previous_active: row.get(7)?,
+                        scale_factor: row.get(8)?,
+                        is_primary: row.get(9)?,
+                    })
+                },
+            );
+            match result {
+                Ok(ss) => Ok(Some(ss)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            }
+        };
+
+        let prev = neighbor(
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary
+             FROM screenshots
+             WHERE session_id IS ?1
+             AND (captured_at < ?2 OR (captured_at = ?2 AND id < ?3))
+             ORDER BY captured_at DESC, id DESC
+             LIMIT 1",
+        )?;
+        let next = neighbor(
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary
+             FROM screenshots
+             WHERE session_id IS ?1
+             AND (captured_at > ?2 OR (captured_at = ?2 AND id > ?3))
+             ORDER BY captured_at ASC, id ASC
+             LIMIT 1",
+        )?;
+        Ok((prev, next))
+    }
+
     /// Get sessions that are ended and still have unanalyzed screenshots.
     pub fn get_pending_sessions(&self, limit: i64, offset: i64) -> SqlResult<Vec<CaptureSession>> {
-        let conn = self.conn()?;
-        let mut stmt = conn.prepare(
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(&format!(
             "SELECT cs.id, cs.started_at, cs.ended_at,
                     (SELECT COUNT(*) FROM screenshots s WHERE s.session_id = cs.id) as screenshot_count,
                     cs.description, cs.title,
                     (SELECT COUNT(*) FROM screenshots s2
                      WHERE s2.session_id = cs.id
                      AND s2.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
-                    ) as unanalyzed_count
+                     AND s2.excluded = 0
+                    ) as unanalyzed_count,
+                    CASE WHEN cs.ended_at IS NULL THEN NULL
+                         ELSE CAST(ROUND((julianday(cs.ended_at) - julianday(cs.started_at)) * 86400) AS INTEGER)
+                              - cs.paused_ms / 1000
+                    END as duration_seconds,
+                    cs.stats
              FROM capture_sessions cs
-             WHERE cs.ended_at IS NOT NULL
-             AND (SELECT COUNT(*) FROM screenshots s3
-                  WHERE s3.session_id = cs.id
-                  AND s3.id NOT IN (SELECT ts2.screenshot_id FROM task_screenshots ts2)
-                 ) > 0
+             WHERE {} = 'pending'
              ORDER BY cs.started_at DESC
              LIMIT ?1 OFFSET ?2",
-        )?;
+            SESSION_STATUS
+        ))?;
         let sessions = stmt.query_map(params![limit, offset], |row| {
             Ok(CaptureSession {
                 id: row.get(0)?,
@@ -507,33 +2237,73 @@ impl Database {
                 description: row.get(4)?,
                 title: row.get(5)?,
                 unanalyzed_count: row.get(6)?,
+                duration_seconds: row.get(7)?,
+                stats: parse_session_stats(row.get(8)?),
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
         Ok(sessions)
     }
 
+    /// Count unanalyzed screenshots in ended sessions by capture group, the unit
+    /// analysis sends to the provider. Screenshots without a group count individually.
+    pub fn count_pending_groups(&self) -> SqlResult<PendingGroups> {
+        self.pending_groups(None)
+    }
+
+    /// Unanalyzed screenshots of one session by capture group, whether or not the
+    /// session has ended.
+    pub fn count_session_pending_groups(&self, session_id: i64) -> SqlResult<PendingGroups> {
+        self.pending_groups(Some(session_id))
+    }
+
+    fn pending_groups(&self, session_id: Option<i64>) -> SqlResult<PendingGroups> {
+        let conn = self.read_conn()?;
+        conn.query_row(
+            "SELECT COUNT(DISTINCT session_id), COUNT(*), COALESCE(SUM(n), 0)
+             FROM (
+                 SELECT s.session_id, COUNT(*) AS n
+                 FROM screenshots s
+                 JOIN capture_sessions cs ON cs.id = s.session_id
+                 WHERE (?1 IS NULL AND cs.ended_at IS NOT NULL OR s.session_id = ?1)
+                 AND s.excluded = 0
+                 AND s.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
+                 GROUP BY s.session_id, COALESCE(s.capture_group, 'screenshot:' || s.id)
+             )",
+            params![session_id],
+            |row| {
+                Ok(PendingGroups {
+                    sessions: row.get(0)?,
+                    groups: row.get(1)?,
+                    screenshots: row.get(2)?,
+                })
+            },
+        )
+    }
+
     /// Get sessions that are ended, have screenshots, and all screenshots are analyzed.
     pub fn get_completed_sessions(&self, limit: i64, offset: i64) -> SqlResult<Vec<CaptureSession>> {
-        let conn = self.conn()?;
-        let mut stmt = conn.prepare(
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(&format!(
             "SELECT cs.id, cs.started_at, cs.ended_at,
                     (SELECT COUNT(*) FROM screenshots s WHERE s.session_id = cs.id) as screenshot_count,
                     cs.description, cs.title,
                     (SELECT COUNT(*) FROM screenshots s2
                      WHERE s2.session_id = cs.id
                      AND s2.id NOT IN (SELECT ts.screenshot_id FROM task_screenshots ts)
-                    ) as unanalyzed_count
+                     AND s2.excluded = 0
+                    ) as unanalyzed_count,
+                    CASE WHEN cs.ended_at IS NULL THEN NULL
+                         ELSE CAST(ROUND((julianday(cs.ended_at) - julianday(cs.started_at)) * 86400) AS INTEGER)
+                              - cs.paused_ms / 1000
+                    END as duration_seconds,
+                    cs.stats
              FROM capture_sessions cs
-             WHERE cs.ended_at IS NOT NULL
-             AND (SELECT COUNT(*) FROM screenshots s3 WHERE s3.session_id = cs.id) > 0
-             AND (SELECT COUNT(*) FROM screenshots s4
-                  WHERE s4.session_id = cs.id
-                  AND s4.id NOT IN (SELECT ts2.screenshot_id FROM task_screenshots ts2)
-                 ) = 0
+             WHERE {} = 'completed'
              ORDER BY cs.started_at DESC
              LIMIT ?1 OFFSET ?2",
-        )?;
+            SESSION_STATUS
+        ))?;
         let sessions = stmt.query_map(params![limit, offset], |row| {
             Ok(CaptureSession {
                 id: row.get(0)?,
@@ -543,20 +2313,40 @@ impl Database {
                 description: row.get(4)?,
                 title: row.get(5)?,
                 unanalyzed_count: row.get(6)?,
+                duration_seconds: row.get(7)?,
+                stats: parse_session_stats(row.get(8)?),
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
         Ok(sessions)
     }
 
+    /// Where a session stands: active, pending, completed or empty, by the same rule
+    /// `get_pending_sessions` and `get_completed_sessions` filter on.
+    pub fn get_session_status(&self, session_id: i64) -> SqlResult<SessionStatus> {
+        let conn = self.read_conn()?;
+        let status: String = conn.query_row(
+            &format!("SELECT {} FROM capture_sessions cs WHERE cs.id = ?1", SESSION_STATUS),
+            params![session_id],
+            |row| row.get(0),
+        )?;
+        Ok(match status.as_str() {
+            "active" => SessionStatus::Active,
+            "pending" => SessionStatus::Pending,
+            "completed" => SessionStatus::Completed,
+            _ => SessionStatus::Empty,
+        })
+    }
+
     /// Get unanalyzed screenshots for a specific session.
     pub fn get_unanalyzed_screenshots_for_session(&self, session_id: i64, limit: i64) -> SqlResult<Vec<Screenshot>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare(
-            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group, s.mirrored_of, s.previous_active, s.scale_factor, s.is_primary
              FROM screenshots s
              LEFT JOIN task_screenshots ts ON s.id = ts.screenshot_id
              WHERE ts.task_id IS NULL
+             AND s.excluded = 0
              AND s.session_id = ?1
              ORDER BY s.captured_at ASC
              LIMIT ?2",
@@ -569,6 +2359,10 @@ impl Database {
                 active_window_title: row.get(3)?,
                 monitor_index: row.get(4)?,
                 capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
@@ -607,9 +2401,69 @@ impl Database {
         }
     }
 
+    /// Screenshots linked to a task, oldest first.
+    pub fn get_task_screenshots(&self, task_id: i64) -> SqlResult<Vec<Screenshot>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.filepath, s.captured_at, s.active_window_title, s.monitor_index, s.capture_group, s.mirrored_of, s.previous_active, s.scale_factor, s.is_primary
+             FROM screenshots s
+             INNER JOIN task_screenshots ts ON s.id = ts.screenshot_id
+             WHERE ts.task_id = ?1
+             ORDER BY s.captured_at ASC, s.id ASC",
+        )?;
+        let screenshots = stmt.query_map(params![task_id], |row| {
+            Ok(Screenshot {
+                id: row.get(0)?,
+                filepath: row.get(1)?,
+                captured_at: row.get(2)?,
+                active_window_title: row.get(3)?,
+                monitor_index: row.get(4)?,
+                capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(screenshots)
+    }
+
+    /// A task with its screenshots and parsed metadata, or `None` if it doesn't exist.
+    /// Metadata that isn't valid JSON is reported as `None` rather than failing the lookup.
+    pub fn get_task_detail(&self, task_id: i64) -> SqlResult<Option<TaskDetail>> {
+        let task = match self.get_task(task_id) {
+            Ok(task) => task,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let screenshots = self.get_task_screenshots(task_id)?;
+        let metadata = task
+            .metadata
+            .as_deref()
+            .and_then(|m| serde_json::from_str(m).ok());
+        Ok(Some(TaskDetail { task, screenshots, metadata }))
+    }
+
+    /// Screenshot id → linked task id for a session's analyzed screenshots (the
+    /// lowest task id if a screenshot is linked to several).
+    pub fn get_session_screenshot_tasks(&self, session_id: i64) -> SqlResult<HashMap<i64, i64>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT ts.screenshot_id, MIN(ts.task_id)
+             FROM task_screenshots ts
+             INNER JOIN screenshots s ON ts.screenshot_id = s.id
+             WHERE s.session_id = ?1
+             GROUP BY ts.screenshot_id",
+        )?;
+        let links = stmt.query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<HashMap<_, _>>>()?;
+        Ok(links)
+    }
+
     /// Get all tasks linked to screenshots in a given session, in chronological order.
     pub fn get_session_tasks(&self, session_id: i64) -> SqlResult<Vec<Task>> {
-        let conn = self.conn()?;
+        let conn = self.read_conn()?;
         let mut stmt = conn.prepare(
             "SELECT DISTINCT t.id, t.title, t.description, t.category, t.started_at, t.ended_at,
                     t.ai_reasoning, t.user_verified, t.metadata
@@ -636,6 +2490,85 @@ impl Database {
         Ok(tasks)
     }
 
+    /// The latest session that ended at or before `before` and has tasks, with the
+    /// seconds from its end to `before`.
+    pub fn get_previous_session_with_tasks(&self, before: &str) -> SqlResult<Option<(i64, i64)>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT cs.id, CAST(ROUND((julianday(?1) - julianday(cs.ended_at)) * 86400) AS INTEGER)
+             FROM capture_sessions cs
+             WHERE cs.ended_at IS NOT NULL AND cs.ended_at <= ?1
+               AND EXISTS (
+                   SELECT 1 FROM task_screenshots ts
+                   INNER JOIN screenshots s ON ts.screenshot_id = s.id
+                   WHERE s.session_id = cs.id
+               )
+             ORDER BY cs.ended_at DESC, cs.id DESC
+             LIMIT 1",
+            params![before],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+    }
+
+    /// The last `limit` analyzed screenshots of a session captured before `before`, oldest
+    /// first, as (captured_at, window title, task title). For `context_overlap`.
+    pub fn get_analyzed_screenshots_before(
+        &self,
+        session_id: i64,
+        before: &str,
+        limit: usize,
+    ) -> SqlResult<Vec<(String, Option<String>, String)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.captured_at, s.active_window_title, MIN(t.title)
+             FROM screenshots s
+             INNER JOIN task_screenshots ts ON ts.screenshot_id = s.id
+             INNER JOIN tasks t ON t.id = ts.task_id
+             WHERE s.session_id = ?1 AND s.captured_at < ?2 AND s.excluded = 0
+             GROUP BY s.id
+             ORDER BY s.captured_at DESC, s.id DESC
+             LIMIT ?3",
+        )?;
+        let mut rows = stmt.query_map(params![session_id, before, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        rows.reverse();
+        Ok(rows)
+    }
+
+    /// Up to `limit` tasks of a session started before `before`, latest first: the
+    /// context a capture at `before` had when the session was first analyzed.
+    pub fn get_recent_tasks_before(&self, session_id: i64, before: &str, limit: i64) -> SqlResult<Vec<Task>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT t.id, t.title, t.description, t.category, t.started_at, t.ended_at,
+                    t.ai_reasoning, t.user_verified, t.metadata
+             FROM tasks t
+             INNER JOIN task_screenshots ts ON t.id = ts.task_id
+             INNER JOIN screenshots s ON ts.screenshot_id = s.id
+             WHERE s.session_id = ?1 AND t.started_at < ?2
+             ORDER BY t.started_at DESC, t.id DESC
+             LIMIT ?3",
+        )?;
+        let tasks = stmt.query_map(params![session_id, before, limit], |row| {
+            Ok(Task {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                category: row.get(3)?,
+                started_at: row.get(4)?,
+                ended_at: row.get(5)?,
+                ai_reasoning: row.get(6)?,
+                user_verified: row.get(7)?,
+                metadata: row.get(8)?,
+            })
+        })?
+        .collect::<SqlResult<Vec<_>>>()?;
+        Ok(tasks)
+    }
+
     /// Get the most recent tasks linked to screenshots in a given session.
     /// Returns up to `limit` tasks, ordered most-recent first.
     pub fn get_recent_tasks_for_session(&self, session_id: i64, limit: i64) -> SqlResult<Vec<Task>> {
@@ -667,14 +2600,14 @@ impl Database {
         Ok(tasks)
     }
 
-    /// Get all screenshots from a single capture group (same tick).
-    #[cfg(test)]
+    /// Get all screenshots from a single capture group (same tick), quarantined ones
+    /// excluded.
     pub fn get_capture_group(&self, capture_group: &str) -> SqlResult<Vec<Screenshot>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare(
-            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group
+            "SELECT id, filepath, captured_at, active_window_title, monitor_index, capture_group, mirrored_of, previous_active, scale_factor, is_primary
              FROM screenshots
-             WHERE capture_group = ?1
+             WHERE capture_group = ?1 AND excluded = 0
              ORDER BY monitor_index ASC",
         )?;
         let screenshots = stmt.query_map(params![capture_group], |row| {
@@ -685,6 +2618,10 @@ impl Database {
                 active_window_title: row.get(3)?,
                 monitor_index: row.get(4)?,
                 capture_group: row.get(5)?,
+                mirrored_of: row.get(6)?,
+                previous_active: row.get(7)?,
+                scale_factor: row.get(8)?,
+                is_primary: row.get(9)?,
             })
         })?
         .collect::<SqlResult<Vec<_>>>()?;
@@ -705,6 +2642,74 @@ impl Database {
         }
     }
 
+    /// Like `get_setting`, but falls back to the registry default for known keys.
+    pub fn get_setting_or_default(&self, key: &str) -> SqlResult<Option<String>> {
+        Ok(self
+            .get_setting(key)?
+            .or_else(|| crate::settings::default_value(key).map(|d| d.to_string())))
+    }
+
+    /// `key` as text: the stored value, else the registry default, else `default`.
+    pub fn get_setting_string(&self, key: &str, default: &str) -> SqlResult<String> {
+        Ok(self.get_setting_or_default(key)?.unwrap_or_else(|| default.to_string()))
+    }
+
+    /// `key` as a bool. Stored values other than "true" and "false" are ignored.
+    pub fn get_setting_bool(&self, key: &str, default: bool) -> SqlResult<bool> {
+        let parse = |v: &str| match v.trim() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        Ok(self.parsed_setting(key, parse)?.unwrap_or(default))
+    }
+
+    /// `key` as an integer, clamped to the registry range for `Integer` settings.
+    /// Stored values that aren't a non-negative integer are ignored.
+    pub fn get_setting_u64(&self, key: &str, default: u64) -> SqlResult<u64> {
+        let value = self.parsed_setting(key, |v| v.trim().parse::<u64>().ok())?.unwrap_or(default);
+        Ok(match crate::settings::integer_range(key) {
+            Some((min, max)) => value.clamp(min.max(0) as u64, max.max(0) as u64),
+            None => value,
+        })
+    }
+
+    /// The stored value of `key` if it parses, else its registry default if that does.
+    /// A malformed value (e.g. written by an older build or edited by hand) is logged
+    /// and treated as unset.
+    fn parsed_setting<T>(&self, key: &str, parse: impl Fn(&str) -> Option<T>) -> SqlResult<Option<T>> {
+        if let Some(stored) = self.get_setting(key)? {
+            match parse(&stored) {
+                Some(value) => return Ok(Some(value)),
+                None => log::warn!("Ignoring malformed value {:?} for setting '{}'", stored, key),
+            }
+        }
+        Ok(crate::settings::default_value(key).and_then(parse))
+    }
+
+    /// Every stored settings row, including internal ones, ordered by key.
+    pub fn get_all_settings(&self) -> SqlResult<Vec<(String, String)>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Upsert several settings in one transaction: all are written or none are.
+    pub fn set_settings(&self, values: &[(String, String)]) -> SqlResult<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        for (key, value) in values {
+            tx.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )?;
+        }
+        tx.commit()
+    }
+
     pub fn set_setting(&self, key: &str, value: &str) -> SqlResult<()> {
         let conn = self.conn()?;
         conn.execute(
@@ -714,12 +2719,332 @@ impl Database {
         )?;
         Ok(())
     }
+
+    pub fn set_capture_region(&self, region: &CaptureRegion) -> SqlResult<()> {
+        self.set_setting(CAPTURE_REGION_KEY, &serde_json::to_string(region).unwrap_or_default())
+    }
+
+    /// The stored capture region, or None if unset or unreadable.
+    pub fn get_capture_region(&self) -> SqlResult<Option<CaptureRegion>> {
+        Ok(self
+            .get_setting(CAPTURE_REGION_KEY)?
+            .and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Recent per-group latencies (ms) for `provider`/`model`, oldest first.
+    pub fn get_analysis_latencies(&self, provider: &str, model: &str) -> SqlResult<Vec<f64>> {
+        Ok(self
+            .get_setting(&format!("{}{}:{}", ANALYSIS_LATENCY_PREFIX, provider, model))?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    /// Add a run's per-group latency to the rolling history for `provider`/`model`.
+    pub fn record_analysis_latency(&self, provider: &str, model: &str, ms_per_group: f64) -> SqlResult<()> {
+        let mut history = self.get_analysis_latencies(provider, model)?;
+        estimate::push_latency(&mut history, ms_per_group);
+        self.set_setting(
+            &format!("{}{}:{}", ANALYSIS_LATENCY_PREFIX, provider, model),
+            &serde_json::to_string(&history).unwrap_or_default(),
+        )
+    }
+
+    /// Insert activity log records, each linked to the capture session running at its
+    /// timestamp (the latest-started one if several; start and end are inclusive).
+    /// Records already present (same time, title and app) are skipped.
+    pub fn insert_activity_samples(&self, records: &[ActivityRecord]) -> SqlResult<ActivityInsert> {
+        let mut result = ActivityInsert::default();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut unix = tx.prepare("SELECT strftime('%Y-%m-%dT%H:%M:%S', ?1, 'unixepoch')")?;
+            let mut text = tx.prepare("SELECT strftime('%Y-%m-%dT%H:%M:%S', ?1)")?;
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO activity_samples (session_id, recorded_at, title, app)
+                 VALUES ((SELECT id FROM capture_sessions
+                          WHERE started_at <= ?1 AND (ended_at IS NULL OR ended_at >= ?1)
+                          ORDER BY started_at DESC LIMIT 1), ?1, ?2, ?3)
+                 RETURNING session_id",
+            )?;
+            for record in records {
+                let recorded_at: Option<String> = match &record.timestamp {
+                    ActivityTime::Unix(secs) => unix.query_row(params![secs], |row| row.get(0))?,
+                    ActivityTime::Text(value) => text.query_row(params![value], |row| row.get(0))?,
+                };
+                let Some(recorded_at) = recorded_at else {
+                    result.bad_timestamps += 1;
+                    continue;
+                };
+                let inserted: Option<Option<i64>> = insert
+                    .query_row(params![recorded_at, record.title, record.app], |row| row.get(0))
+                    .optional()?;
+                match inserted {
+                    Some(session_id) => {
+                        result.imported += 1;
+                        result.in_session += u32::from(session_id.is_some());
+                    }
+                    None => result.duplicates += 1,
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Distinct (title, app) activity samples recorded in `(after, until]`, where the
+    /// window starts no earlier than `lookback_secs` before `until`. Most recent first.
+    pub fn get_activity_in_window(
+        &self,
+        after: Option<&str>,
+        until: &str,
+        lookback_secs: i64,
+        limit: usize,
+    ) -> SqlResult<Vec<(String, String)>> {
+        let conn = self.read_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT title, app FROM activity_samples
+             WHERE recorded_at <= ?2
+               AND recorded_at > max(COALESCE(?1, ''), strftime('%Y-%m-%dT%H:%M:%S', ?2, ?3))
+             GROUP BY title, app
+             ORDER BY MAX(recorded_at) DESC
+             LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![after, until, format!("-{} seconds", lookback_secs), limit as i64],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        rows.collect()
+    }
+
+    pub fn set_capture_count(&self, count: u64) -> SqlResult<()> {
+        self.set_setting(CAPTURE_COUNT_KEY, &count.to_string())
+    }
+
+    /// The persisted lifetime screenshot count, or 0 if unset or unreadable.
+    pub fn get_capture_count(&self) -> SqlResult<u64> {
+        Ok(self
+            .get_setting(CAPTURE_COUNT_KEY)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0))
+    }
+
+    pub fn set_screenshots_dir(&self, dir: &Path) -> SqlResult<()> {
+        self.set_setting(SCREENSHOTS_DIR_KEY, &dir.to_string_lossy())
+    }
+
+    /// The custom screenshots directory, or None to use the default under app data.
+    pub fn get_screenshots_dir(&self) -> SqlResult<Option<PathBuf>> {
+        Ok(self.get_setting(SCREENSHOTS_DIR_KEY)?.map(PathBuf::from))
+    }
+}
+
+/// A table row as column name → JSON value, as stored in a database dump.
+pub type RowMap = serde_json::Map<String, serde_json::Value>;
+
+/// Move one screenshot row, its task links and its label into the trash tables.
+/// Returns its filepath, or None if there's no such screenshot.
+fn trash_screenshot_row(conn: &Connection, id: i64, trash_session_id: Option<i64>, trashed_at: &str) -> SqlResult<Option<String>> {
+    let moved = conn.execute(
+        &format!(
+            "INSERT INTO trashed_screenshots (trash_session_id, id, session_id, {cols}, trashed_at)
+             SELECT ?2, id, session_id, {cols}, ?3 FROM screenshots WHERE id = ?1",
+            cols = SCREENSHOT_COLUMNS
+        ),
+        params![id, trash_session_id, trashed_at],
+    )?;
+    if moved == 0 {
+        return Ok(None);
+    }
+    let trash_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO trashed_task_screenshots (task_id, trash_screenshot_id)
+         SELECT task_id, ?2 FROM task_screenshots WHERE screenshot_id = ?1",
+        params![id, trash_id],
+    )?;
+    conn.execute(
+        "INSERT INTO trashed_labels (trash_screenshot_id, label_title, label_category, labeled_at)
+         SELECT ?2, label_title, label_category, labeled_at FROM labels WHERE screenshot_id = ?1",
+        params![id, trash_id],
+    )?;
+    let filepath: String = conn.query_row("SELECT filepath FROM screenshots WHERE id = ?1", params![id], |row| row.get(0))?;
+    // Links and label go with the row (ON DELETE CASCADE); their copies are in the trash
+    conn.execute("DELETE FROM screenshots WHERE id = ?1", params![id])?;
+    Ok(Some(filepath))
+}
+
+/// `id` if no live row in `table` has it, else None so the insert picks a fresh id.
+fn free_id(conn: &Connection, table: &str, id: i64) -> SqlResult<Option<i64>> {
+    let taken = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+        params![id],
+        |row| row.get::<_, bool>(0),
+    )?;
+    Ok(if taken { None } else { Some(id) })
+}
+
+/// Move a trashed screenshot back, relinking it to tasks that still exist and restoring
+/// its label. It joins `session_id` if given, else its old session if that still
+/// exists. Returns its filepath.
+fn restore_screenshot_row(conn: &Connection, trash_id: i64, session_id: Option<i64>) -> SqlResult<String> {
+    let (old_id, old_session_id, filepath): (i64, Option<i64>, String) = conn.query_row(
+        "SELECT id, session_id, filepath FROM trashed_screenshots WHERE trash_id = ?1",
+        params![trash_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let session_id = match (session_id, old_session_id) {
+        (Some(sid), _) => Some(sid),
+        (None, Some(old)) => conn.query_row(
+            "SELECT id FROM capture_sessions WHERE id = ?1",
+            params![old],
+            |row| row.get::<_, i64>(0),
+        ).optional()?,
+        (None, None) => None,
+    };
+    let id = free_id(conn, "screenshots", old_id)?;
+    conn.execute(
+        &format!(
+            "INSERT INTO screenshots (id, session_id, {cols})
+             SELECT ?1, ?2, {cols} FROM trashed_screenshots WHERE trash_id = ?3",
+            cols = SCREENSHOT_COLUMNS
+        ),
+        params![id, session_id, trash_id],
+    )?;
+    let restored_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT OR IGNORE INTO task_screenshots (task_id, screenshot_id)
+         SELECT task_id, ?2 FROM trashed_task_screenshots WHERE trash_screenshot_id = ?1",
+        params![trash_id, restored_id],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO labels (screenshot_id, label_title, label_category, labeled_at)
+         SELECT ?2, label_title, label_category, labeled_at FROM trashed_labels WHERE trash_screenshot_id = ?1",
+        params![trash_id, restored_id],
+    )?;
+    conn.execute("DELETE FROM trashed_screenshots WHERE trash_id = ?1", params![trash_id])?;
+    Ok(filepath)
+}
+
+/// Move a trashed session back with its capture events and every screenshot trashed
+/// with it. Returns the screenshots' filepaths, or None if there's no such entry.
+fn restore_session_rows(conn: &Connection, trash_session_id: i64) -> SqlResult<Option<Vec<String>>> {
+    let old_id: Option<i64> = conn.query_row(
+        "SELECT id FROM trashed_sessions WHERE trash_id = ?1",
+        params![trash_session_id],
+        |row| row.get(0),
+    ).optional()?;
+    let Some(old_id) = old_id else {
+        return Ok(None);
+    };
+    let id = free_id(conn, "capture_sessions", old_id)?;
+    let cols = SESSION_COLUMNS.trim_start_matches("id, ");
+    conn.execute(
+        &format!(
+            "INSERT INTO capture_sessions (id, {cols})
+             SELECT ?1, {cols} FROM trashed_sessions WHERE trash_id = ?2",
+            cols = cols
+        ),
+        params![id, trash_session_id],
+    )?;
+    let session_id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO capture_events (session_id, occurred_at, kind, detail)
+         SELECT ?2, occurred_at, kind, detail FROM trashed_capture_events WHERE trash_session_id = ?1",
+        params![trash_session_id, session_id],
+    )?;
+    let trash_ids = {
+        let mut stmt = conn.prepare("SELECT trash_id FROM trashed_screenshots WHERE trash_session_id = ?1 ORDER BY trash_id")?;
+        let ids = stmt.query_map(params![trash_session_id], |row| row.get::<_, i64>(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        ids
+    };
+    let mut paths = Vec::with_capacity(trash_ids.len());
+    for trash_id in trash_ids {
+        paths.push(restore_screenshot_row(conn, trash_id, Some(session_id))?);
+    }
+    // Its events go with it (ON DELETE CASCADE)
+    conn.execute("DELETE FROM trashed_sessions WHERE trash_id = ?1", params![trash_session_id])?;
+    Ok(Some(paths))
+}
+
+fn sql_to_json(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(n) => n.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+        // No column stores blobs
+        ValueRef::Blob(_) => serde_json::Value::Null,
+    }
+}
+
+fn json_to_sql(value: &serde_json::Value) -> SqlValue {
+    match value {
+        serde_json::Value::Null => SqlValue::Null,
+        serde_json::Value::Bool(b) => SqlValue::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Parse a `capture_sessions.stats` value; missing or malformed JSON reads as zeroes.
+fn parse_session_stats(raw: Option<String>) -> SessionStats {
+    raw.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(unix)]
+    #[test]
+    fn test_new_database_is_owner_only() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_db_mode_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+        let db = Database::new(&path).unwrap();
+        db.set_setting("ai_api_key", "sk-test").unwrap();
+
+        for file in permissions::database_files(&path) {
+            if file.exists() {
+                assert_eq!(permissions::mode(&file), Some(0o600), "{}", file.display());
+            }
+        }
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_conn_does_not_block_writes() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_read_conn_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new(&dir.join("test.db")).unwrap();
+        let session_id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM screenshots", [], |row| row.get(0)).unwrap()
+        };
+
+        // Hold a read transaction open on the read connection
+        let reader = db.read_conn().unwrap();
+        reader.execute_batch("BEGIN").unwrap();
+        assert_eq!(count(&reader), 0);
+
+        assert!(db.conn.try_lock().is_ok(), "read_conn shares the write connection");
+        db.insert_screenshot("s1.webp", "2025-01-01T10:00:00", None, 0, Some(session_id), None).unwrap();
+        // The open read transaction keeps its snapshot; the next one sees the write
+        assert_eq!(count(&reader), 0);
+        reader.execute_batch("COMMIT").unwrap();
+        assert_eq!(count(&reader), 1);
+        drop(reader);
+
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_insert_and_get_task() {
         let db = Database::in_memory().unwrap();
@@ -746,6 +3071,69 @@ mod tests {
         assert_eq!(task.user_verified, true);
     }
 
+    #[test]
+    fn test_update_task_records_category_correction() {
+        let db = Database::in_memory().unwrap();
+        let recategorize = |id, category: &str| {
+            db.update_task(id, &TaskUpdate {
+                title: None,
+                description: None,
+                category: Some(category.to_string()),
+                ended_at: None,
+                user_verified: None,
+            }).unwrap();
+        };
+
+        let ai_task = db.insert_full_task("Reviewing PRs", "On GitHub", "browsing", "2025-01-01T00:00:00", "Browser open").unwrap();
+        recategorize(ai_task, "coding");
+        let ai_task2 = db.insert_full_task("Reviewing PRs", "Again", "browsing", "2025-01-01T01:00:00", "Browser open").unwrap();
+        recategorize(ai_task2, "coding");
+
+        // Unchanged category and user-created tasks don't teach anything
+        recategorize(ai_task2, "coding");
+        let manual = db.insert_task("Reviewing PRs", "2025-01-01T02:00:00").unwrap();
+        recategorize(manual, "writing");
+
+        let rules = db.get_category_corrections().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].keywords, vec!["reviewing", "prs"]);
+        assert_eq!(rules[0].category, "coding");
+        assert_eq!(rules[0].count, 2);
+
+        // Correcting to a different category replaces the rule
+        let ai_task3 = db.insert_full_task("Reviewing PRs", "Chat", "coding", "2025-01-01T03:00:00", "Slack").unwrap();
+        recategorize(ai_task3, "communication");
+        let rules = db.get_category_corrections().unwrap();
+        assert_eq!(rules[0].category, "communication");
+        assert_eq!(rules[0].count, 1);
+
+        db.delete_category_correction(rules[0].id).unwrap();
+        assert!(db.get_category_corrections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_tasks_with_provider() {
+        let db = Database::in_memory().unwrap();
+        let claude = db.insert_full_task("A", "d", "coding", "2025-01-01T10:00:00", "r").unwrap();
+        db.set_task_metadata(claude, r#"{"analyzed_by":"claude/claude-sonnet-4-5-20250929"}"#).unwrap();
+        let ollama = db.insert_full_task("B", "d", "coding", "2025-01-01T10:01:00", "r").unwrap();
+        db.set_task_metadata(ollama, r#"{"analyzed_by":"ollama/qwen3-vl:8b","category_override":{}}"#).unwrap();
+        let other_metadata = db.insert_full_task("C", "d", "coding", "2025-01-01T10:02:00", "r").unwrap();
+        db.set_task_metadata(other_metadata, r#"{"category_override":{}}"#).unwrap();
+        let bad_metadata = db.insert_full_task("D", "d", "coding", "2025-01-01T10:03:00", "r").unwrap();
+        db.set_task_metadata(bad_metadata, "not json").unwrap();
+        db.insert_task("E", "2025-01-01T10:04:00").unwrap();
+
+        let tasks = db.get_tasks_with_provider(10, 0).unwrap();
+        let by_title = |title: &str| tasks.iter().find(|t| t.task.title == title).unwrap().analyzed_by.clone();
+        assert_eq!(tasks.len(), 5);
+        assert_eq!(by_title("A"), Some("claude/claude-sonnet-4-5-20250929".to_string()));
+        assert_eq!(by_title("B"), Some("ollama/qwen3-vl:8b".to_string()));
+        assert_eq!(by_title("C"), None);
+        assert_eq!(by_title("D"), None);
+        assert_eq!(by_title("E"), None);
+    }
+
     #[test]
     fn test_delete_task() {
         let db = Database::in_memory().unwrap();
@@ -764,6 +3152,61 @@ mod tests {
         assert_eq!(db.get_setting("foo").unwrap(), Some("baz".to_string()));
     }
 
+    #[test]
+    fn test_get_setting_or_default() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.get_setting_or_default("analysis_mode").unwrap(), Some("batch".to_string()));
+        assert_eq!(db.get_setting_or_default("ai_api_key").unwrap(), None);
+        assert_eq!(db.get_setting_or_default("foo").unwrap(), None);
+        db.set_setting("analysis_mode", "realtime").unwrap();
+        assert_eq!(db.get_setting_or_default("analysis_mode").unwrap(), Some("realtime".to_string()));
+    }
+
+    #[test]
+    fn test_get_setting_string() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.get_setting_string("analysis_mode", "x").unwrap(), "batch");
+        assert_eq!(db.get_setting_string("blocked_title_keywords", "").unwrap(), "");
+        assert_eq!(db.get_setting_string("foo", "fallback").unwrap(), "fallback");
+        db.set_setting("analysis_mode", "realtime").unwrap();
+        assert_eq!(db.get_setting_string("analysis_mode", "x").unwrap(), "realtime");
+    }
+
+    #[test]
+    fn test_get_setting_bool() {
+        let db = Database::in_memory().unwrap();
+        assert!(db.get_setting_bool("session_auto_rotate", false).unwrap(), "registry default wins over the argument");
+        assert!(!db.get_setting_bool("foo", false).unwrap());
+        db.set_setting("session_auto_rotate", "false").unwrap();
+        assert!(!db.get_setting_bool("session_auto_rotate", true).unwrap());
+        // Malformed values fall back to the registry default, then the argument
+        db.set_setting("session_auto_rotate", "yes").unwrap();
+        assert!(db.get_setting_bool("session_auto_rotate", false).unwrap());
+        db.set_setting("foo", "1").unwrap();
+        assert!(db.get_setting_bool("foo", true).unwrap());
+    }
+
+    #[test]
+    fn test_get_setting_u64() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.get_setting_u64("batch_size", 0).unwrap(), 10);
+        assert_eq!(db.get_setting_u64("foo", 7).unwrap(), 7);
+        db.set_setting("batch_size", " 25 ").unwrap();
+        assert_eq!(db.get_setting_u64("batch_size", 0).unwrap(), 25);
+        // Out-of-range values are clamped to the registry range
+        db.set_setting("batch_size", "500").unwrap();
+        assert_eq!(db.get_setting_u64("batch_size", 0).unwrap(), 100);
+        db.set_setting("batch_size", "0").unwrap();
+        assert_eq!(db.get_setting_u64("batch_size", 0).unwrap(), 1);
+        // Malformed values fall back to the registry default, then the argument
+        for bad in ["abc", "-5", "2.5", ""] {
+            db.set_setting("batch_size", bad).unwrap();
+            assert_eq!(db.get_setting_u64("batch_size", 0).unwrap(), 10, "{:?}", bad);
+        }
+        db.set_setting("foo", "ten").unwrap();
+        assert_eq!(db.get_setting_u64("foo", 7).unwrap(), 7);
+    }
+
     #[test]
     fn test_screenshot_task_link() {
         let db = Database::in_memory().unwrap();
@@ -778,7 +3221,7 @@ mod tests {
     fn test_delete_unanalyzed_screenshots() {
         let db = Database::in_memory().unwrap();
         let ss1 = db.insert_screenshot("shot1.webp", "2025-01-01T00:00:00", None, 0, None, None).unwrap();
-        let _ss2 = db.insert_screenshot("shot2.webp", "2025-01-01T00:00:01", None, 0, None, None).unwrap();
+        let ss2 = db.insert_screenshot("shot2.webp", "2025-01-01T00:00:01", None, 0, None, None).unwrap();
         let ss3 = db.insert_screenshot("shot3.webp", "2025-01-01T00:00:02", None, 0, None, None).unwrap();
 
         // Link ss1 to a task — it should NOT be deleted
@@ -789,14 +3232,434 @@ mod tests {
         db.link_screenshot_to_task(task_id, ss3).unwrap();
 
         // Only ss2 is unanalyzed
-        let deleted = db.delete_unanalyzed_screenshots().unwrap();
-        assert_eq!(deleted.len(), 1);
-        assert_eq!(deleted[0], "shot2.webp");
+        let deleted = db.delete_unanalyzed_screenshots_for_session(None, None).unwrap();
+        assert_eq!(deleted, vec![(ss2, None, "shot2.webp".to_string())]);
 
         // Verify only 2 screenshots remain
         assert_eq!(db.get_screenshot_count().unwrap(), 2);
     }
 
+    #[test]
+    fn test_delete_unanalyzed_screenshots_for_session() {
+        let db = Database::in_memory().unwrap();
+        let junk = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let active = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        let other = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        let junk1 = db.insert_screenshot("junk1.webp", "2025-01-01T10:00:00", None, 0, Some(junk), None).unwrap();
+        let junk2 = db.insert_screenshot("junk2.webp", "2025-01-01T10:00:30", None, 0, Some(junk), None).unwrap();
+        let analyzed = db.insert_screenshot("junk3.webp", "2025-01-01T10:01:00", None, 0, Some(junk), None).unwrap();
+        db.insert_screenshot("active.webp", "2025-01-01T11:00:00", None, 0, Some(active), None).unwrap();
+        let other_ss = db.insert_screenshot("other.webp", "2025-01-01T09:00:00", None, 0, Some(other), None).unwrap();
+        let loose = db.insert_screenshot("loose.webp", "2025-01-01T08:00:00", None, 0, None, None).unwrap();
+        let task = db.insert_task("Task", "2025-01-01T10:01:00").unwrap();
+        db.link_screenshot_to_task(task, analyzed).unwrap();
+
+        // One session: the others are untouched
+        let deleted = db.delete_unanalyzed_screenshots_for_session(Some(junk), Some(active)).unwrap();
+        assert_eq!(deleted, vec![(junk1, Some(junk), "junk1.webp".to_string()), (junk2, Some(junk), "junk2.webp".to_string())]);
+        assert_eq!(db.get_screenshot_count().unwrap(), 4);
+        assert_eq!(db.get_unanalyzed_screenshot_ids(Some(other), None).unwrap().len(), 1);
+
+        // The excluded session wins over an explicit session filter
+        assert!(db.delete_unanalyzed_screenshots_for_session(Some(active), Some(active)).unwrap().is_empty());
+
+        // All sessions except the active one, sessionless screenshots included
+        let mut deleted = db.delete_unanalyzed_screenshots_for_session(None, Some(active)).unwrap();
+        deleted.sort_by_key(|(_, session, _)| *session);
+        assert_eq!(deleted, vec![(loose, None, "loose.webp".to_string()), (other_ss, Some(other), "other.webp".to_string())]);
+        assert_eq!(db.get_unanalyzed_screenshot_ids(None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_archive_unanalyzed_screenshots_partial_session() {
+        let db = Database::in_memory().unwrap();
+        let session_id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.end_session(session_id, "2025-01-01T10:30:00").unwrap();
+        let ss1 = db.insert_screenshot("s1.webp", "2025-01-01T10:00:00", None, 0, Some(session_id), None).unwrap();
+        db.insert_screenshot("s2.webp", "2025-01-01T10:00:30", None, 0, Some(session_id), None).unwrap();
+        db.insert_screenshot("s3.webp", "2025-01-01T10:01:00", None, 0, Some(session_id), None).unwrap();
+        let analyzed_task = db.insert_task("Analyzed", "2025-01-01T10:00:00").unwrap();
+        db.link_screenshot_to_task(analyzed_task, ss1).unwrap();
+
+        let archived = db.archive_unanalyzed_screenshots(Some(session_id), None, "Leftovers").unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].session_id, Some(session_id));
+        assert_eq!(archived[0].screenshot_count, 2);
+
+        let task = db.get_task(archived[0].task_id).unwrap();
+        assert_eq!(task.title, "Leftovers");
+        assert_eq!(task.category, Some("other".to_string()));
+        assert!(task.user_verified);
+        assert_eq!(task.started_at, "2025-01-01T10:00:30");
+        assert_eq!(task.ended_at, Some("2025-01-01T10:01:00".to_string()));
+
+        assert_eq!(db.get_session(session_id).unwrap().unanalyzed_count, 0);
+        let completed = db.get_completed_sessions(10, 0).unwrap();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].id, session_id);
+
+        // Nothing left to archive
+        assert!(db.archive_unanalyzed_screenshots(Some(session_id), None, "Again").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_archive_unanalyzed_screenshots_global() {
+        let db = Database::in_memory().unwrap();
+        let s1 = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let s2 = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        db.insert_screenshot("a.webp", "2025-01-01T10:00:00", None, 0, Some(s1), None).unwrap();
+        db.insert_screenshot("b.webp", "2025-01-01T11:00:00", None, 0, Some(s2), None).unwrap();
+        db.insert_screenshot("c.webp", "2025-01-01T11:00:30", None, 0, Some(s2), None).unwrap();
+
+        let archived = db.archive_unanalyzed_screenshots(None, Some(s2), "Leftovers").unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].session_id, Some(s1));
+        assert_eq!(db.get_unanalyzed_screenshot_ids(None, None).unwrap().len(), 2);
+
+        let more = db.archive_unanalyzed_screenshots(None, None, "Leftovers").unwrap();
+        assert_eq!(more.len(), 1);
+        let archived = [archived, more].concat();
+        let for_s2 = archived.iter().find(|a| a.session_id == Some(s2)).unwrap();
+        assert_eq!(for_s2.screenshot_count, 2);
+        assert_ne!(archived[0].task_id, archived[1].task_id);
+        assert!(db.get_unanalyzed_screenshots(10, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_legacy_screenshots_groups_by_day() {
+        let db = Database::in_memory().unwrap();
+        let existing = db.create_session("2025-01-02T09:00:00", None, None).unwrap();
+        db.insert_screenshot("d1a.webp", "2025-01-01T08:00:00", None, 0, None, None).unwrap();
+        db.insert_screenshot("d1b.webp", "2025-01-01T17:30:00", None, 0, None, None).unwrap();
+        db.insert_screenshot("d2.webp", "2025-01-02T12:00:00", None, 0, None, None).unwrap();
+        db.insert_screenshot("s.webp", "2025-01-02T09:00:00", None, 0, Some(existing), None).unwrap();
+
+        let created = db.reconcile_legacy_screenshots().unwrap();
+        assert_eq!(created.len(), 2);
+
+        let day1 = db.get_session(created[0]).unwrap();
+        assert_eq!(day1.title.as_deref(), Some("Imported 2025-01-01"));
+        assert_eq!(day1.started_at, "2025-01-01T08:00:00");
+        assert_eq!(day1.ended_at.as_deref(), Some("2025-01-01T17:30:00"));
+        assert_eq!(day1.screenshot_count, 2);
+
+        let day2 = db.get_session(created[1]).unwrap();
+        assert_eq!(day2.title.as_deref(), Some("Imported 2025-01-02"));
+        assert_eq!(day2.screenshot_count, 1);
+        assert_eq!(db.get_session(existing).unwrap().screenshot_count, 1);
+
+        assert_eq!(db.get_unanalyzed_screenshots_for_session(created[0], 10).unwrap().len(), 2);
+        assert!(db.reconcile_legacy_screenshots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_legacy_screenshots_once() {
+        let db = Database::in_memory().unwrap();
+        db.insert_screenshot("a.webp", "2025-01-01T08:00:00", None, 0, None, None).unwrap();
+        assert_eq!(db.reconcile_legacy_screenshots_once().unwrap().map(|ids| ids.len()), Some(1));
+
+        // Later sessionless screenshots are left alone once the flag is set
+        db.insert_screenshot("b.webp", "2025-01-03T08:00:00", None, 0, None, None).unwrap();
+        assert_eq!(db.reconcile_legacy_screenshots_once().unwrap(), None);
+        assert_eq!(db.get_sessions(10, 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_get_tasks_by_category_in_range() {
+        let db = Database::in_memory().unwrap();
+        let monday = db.create_session("2025-01-06T09:00:00", None, None).unwrap();
+        let friday = db.create_session("2025-01-10T09:00:00", None, None).unwrap();
+        let before = db.insert_full_task("Old fix", "", "coding", "2025-01-05T23:59:59", "").unwrap();
+        let t1 = db.insert_full_task("Auth", "", "coding", "2025-01-06T09:00:00", "").unwrap();
+        let email = db.insert_full_task("Email", "", "communication", "2025-01-06T10:00:00", "").unwrap();
+        let t2 = db.insert_full_task("Deploy", "", "coding", "2025-01-10T17:45:00", "").unwrap();
+        let after = db.insert_full_task("Next week", "", "coding", "2025-01-13T09:00:00", "").unwrap();
+        for (task, session) in [(before, monday), (t1, monday), (email, monday), (t2, friday), (after, friday)] {
+            let ss = db.insert_screenshot("x.webp", "2025-01-06T09:00:00", None, 0, Some(session), None).unwrap();
+            db.link_screenshot_to_task(task, ss).unwrap();
+        }
+
+        let coding = db.get_tasks_by_category_in_range("coding", "2025-01-06", "2025-01-10").unwrap();
+        assert_eq!(coding.iter().map(|t| t.id).collect::<Vec<_>>(), vec![t1, t2]);
+
+        let comms = db.get_tasks_by_category_in_range("communication", "2025-01-06", "2025-01-10").unwrap();
+        assert_eq!(comms.len(), 1);
+        assert_eq!(comms[0].id, email);
+
+        assert!(db.get_tasks_by_category_in_range("design", "2025-01-06", "2025-01-10").unwrap().is_empty());
+        assert!(db.get_tasks_by_category_in_range("coding", "2025-01-07", "2025-01-09").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_daily_task_counts() {
+        let db = Database::in_memory().unwrap();
+        for started_at in [
+            "2025-01-05T23:59:59",
+            "2025-01-06T09:00:00",
+            "2025-01-06T17:30:00",
+            "2025-01-08T08:00:00",
+            "2025-01-09T00:00:00",
+        ] {
+            db.insert_full_task("Task", "", "coding", started_at, "").unwrap();
+        }
+
+        let day = |date: &str| time::parse_date(date).unwrap();
+        let counts = db.get_daily_task_counts(&time::TimeZone::UTC, day("2025-01-06"), day("2025-01-08")).unwrap();
+        let counts: Vec<(&str, i64)> = counts.iter().map(|c| (c.date.as_str(), c.count)).collect();
+        // The 7th had no tasks but is still reported; the 5th and 9th are out of range
+        assert_eq!(counts, vec![("2025-01-06", 2), ("2025-01-07", 0), ("2025-01-08", 1)]);
+
+        let single = db.get_daily_task_counts(&time::TimeZone::UTC, day("2025-01-09"), day("2025-01-09")).unwrap();
+        assert_eq!(single, vec![DailyTaskCount { date: "2025-01-09".to_string(), count: 1 }]);
+
+        // Two hours ahead of UTC, the 5th's 23:59:59 falls on the 6th and the 6th's 09:00
+        // stays there; the 9th's midnight is 02:00 local, also on the 9th
+        let ahead = time::TimeZone::fixed(2 * 3600);
+        let counts = db.get_daily_task_counts(&ahead, day("2025-01-06"), day("2025-01-09")).unwrap();
+        let counts: Vec<i64> = counts.iter().map(|c| c.count).collect();
+        assert_eq!(counts, vec![3, 0, 1, 1]);
+        // Behind UTC, the 9th's midnight is still the 8th locally
+        let behind = time::TimeZone::fixed(-5 * 3600);
+        let counts = db.get_daily_task_counts(&behind, day("2025-01-08"), day("2025-01-09")).unwrap();
+        let counts: Vec<i64> = counts.iter().map(|c| c.count).collect();
+        assert_eq!(counts, vec![2, 0]);
+    }
+
+    fn activity(timestamp: ActivityTime, title: &str) -> ActivityRecord {
+        ActivityRecord { timestamp, title: title.to_string(), app: String::new() }
+    }
+
+    #[test]
+    fn test_insert_activity_samples_matches_sessions() {
+        let db = Database::in_memory().unwrap();
+        let earlier = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        db.end_session(earlier, "2025-01-01T10:00:00").unwrap();
+        let later = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.end_session(later, "2025-01-01T11:00:00").unwrap();
+        let open = db.create_session("2025-01-01T12:00:00", None, None).unwrap();
+
+        let result = db.insert_activity_samples(&[
+            activity(ActivityTime::Text("2025-01-01T09:00:00Z".to_string()), "at earlier start"),
+            // Both sessions include 10:00:00; the one that started then wins
+            activity(ActivityTime::Text("2025-01-01T12:00:00+02:00".to_string()), "at the boundary"),
+            activity(ActivityTime::Text("2025-01-01T11:00:01".to_string()), "between sessions"),
+            activity(ActivityTime::Unix(1_735_740_000), "in the open session"),
+            activity(ActivityTime::Text("soon".to_string()), "bad time"),
+            activity(ActivityTime::Text("2025-01-01T09:00:00.000Z".to_string()), "at earlier start"),
+        ]).unwrap();
+        assert_eq!(result, ActivityInsert { imported: 4, in_session: 3, duplicates: 1, bad_timestamps: 1 });
+
+        let conn = db.conn().unwrap();
+        let rows: Vec<(Option<i64>, String, String)> = conn
+            .prepare("SELECT session_id, recorded_at, title FROM activity_samples ORDER BY recorded_at").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<SqlResult<_>>().unwrap();
+        assert_eq!(rows, vec![
+            (Some(earlier), "2025-01-01T09:00:00".to_string(), "at earlier start".to_string()),
+            (Some(later), "2025-01-01T10:00:00".to_string(), "at the boundary".to_string()),
+            (None, "2025-01-01T11:00:01".to_string(), "between sessions".to_string()),
+            (Some(open), "2025-01-01T14:00:00".to_string(), "in the open session".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_get_activity_in_window_boundaries() {
+        let db = Database::in_memory().unwrap();
+        let at = |t: &str, title: &str| activity(ActivityTime::Text(t.to_string()), title);
+        db.insert_activity_samples(&[
+            at("2025-01-01T09:57:59", "too early"),
+            at("2025-01-01T09:58:00", "lookback edge"),
+            at("2025-01-01T09:58:01", "inside"),
+            at("2025-01-01T09:59:00", "Docs"),
+            at("2025-01-01T09:59:30", "Docs"),
+            at("2025-01-01T10:00:00", "at capture"),
+            at("2025-01-01T10:00:01", "after capture"),
+        ]).unwrap();
+        let titles = |after: Option<&str>, limit: usize| -> Vec<String> {
+            db.get_activity_in_window(after, "2025-01-01T10:00:00", 120, limit).unwrap()
+                .into_iter().map(|(title, _)| title).collect()
+        };
+
+        // (until - 120s, until]: the lookback edge itself is excluded, repeats collapse
+        assert_eq!(titles(None, 10), vec!["at capture", "Docs", "inside"]);
+        // A previous group later than the lookback starts the window
+        assert_eq!(titles(Some("2025-01-01T09:59:00"), 10), vec!["at capture", "Docs"]);
+        // An earlier previous group doesn't widen it
+        assert_eq!(titles(Some("2025-01-01T09:00:00"), 10), vec!["at capture", "Docs", "inside"]);
+        assert_eq!(titles(None, 1), vec!["at capture"]);
+    }
+
+    #[test]
+    fn test_get_analyzed_screenshots_before() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let other = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        let task = db.insert_task("Coding", "2025-01-01T10:00:00").unwrap();
+        let shot = |t: &str, title: &str, session: i64, analyzed: bool| {
+            let id = db.insert_screenshot(&format!("{}.webp", t), t, Some(title), 0, Some(session), None).unwrap();
+            if analyzed {
+                db.link_screenshot_to_task(task, id).unwrap();
+            }
+            id
+        };
+        shot("2025-01-01T10:00:00", "oldest", sid, true);
+        shot("2025-01-01T10:00:30", "a", sid, true);
+        shot("2025-01-01T10:01:00", "unanalyzed", sid, false);
+        shot("2025-01-01T10:01:30", "b", sid, true);
+        shot("2025-01-01T10:01:40", "elsewhere", other, true);
+        let excluded = shot("2025-01-01T10:01:45", "excluded", sid, true);
+        db.quarantine_screenshot(excluded, "corrupt/excluded.webp").unwrap();
+        shot("2025-01-01T10:02:00", "batch start", sid, true);
+
+        let titles = |limit| -> Vec<String> {
+            db.get_analyzed_screenshots_before(sid, "2025-01-01T10:02:00", limit).unwrap()
+                .into_iter().map(|(_, window, task)| format!("{}/{}", window.unwrap(), task)).collect()
+        };
+        assert_eq!(titles(2), vec!["a/Coding", "b/Coding"]);
+        assert_eq!(titles(10), vec!["oldest/Coding", "a/Coding", "b/Coding"]);
+        assert!(db.get_analyzed_screenshots_before(sid, "2025-01-01T10:00:00", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recreate_starts_empty_in_place() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_recreate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+        let db = Database::new(&path).unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.insert_screenshot("a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        db.set_setting("ai_api_key", "sk-ant-secret").unwrap();
+        assert!(db.has_data().unwrap());
+        assert_eq!(db.all_screenshot_filepaths().unwrap(), vec!["a.webp".to_string()]);
+
+        let removed = db.recreate(&path).unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(removed.iter().all(|(_, result)| result.is_ok()));
+
+        // Same handle, new file: empty, writable, and readable through the read connection
+        assert!(!db.has_data().unwrap());
+        assert_eq!(db.get_setting("ai_api_key").unwrap(), None);
+        db.create_session("2025-01-02T10:00:00", None, None).unwrap();
+        assert_eq!(db.get_sessions(10, 0).unwrap().len(), 1);
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_capture_region_round_trip() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.get_capture_region().unwrap(), None);
+        let region = CaptureRegion { monitor_id: 2, x: 10, y: 20, width: 300, height: 400 };
+        db.set_capture_region(&region).unwrap();
+        assert_eq!(db.get_capture_region().unwrap(), Some(region));
+
+        db.set_setting("capture_region", "not json").unwrap();
+        assert_eq!(db.get_capture_region().unwrap(), None);
+    }
+
+    #[test]
+    fn test_unlink_capture_group_and_tasks_before() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let shot = |ts: &str, monitor: i32, group: &str| db.insert_screenshot("x.webp", ts, None, monitor, Some(sid), Some(group)).unwrap();
+        let a = shot("2025-01-01T10:00:00", 0, "g1");
+        let b = shot("2025-01-01T10:01:00", 0, "g2");
+        let b2 = shot("2025-01-01T10:01:00", 1, "g2");
+        let c = shot("2025-01-01T10:02:00", 0, "g3");
+        let first = db.insert_task("First", "2025-01-01T10:00:00").unwrap();
+        let wrong = db.insert_task("Wrong", "2025-01-01T10:01:00").unwrap();
+        let spanning = db.insert_task("Spanning", "2025-01-01T10:01:00").unwrap();
+        db.link_screenshot_to_task(first, a).unwrap();
+        db.link_screenshot_to_task(wrong, b).unwrap();
+        db.link_screenshot_to_task(spanning, b2).unwrap();
+        db.link_screenshot_to_task(spanning, c).unwrap();
+
+        let before: Vec<i64> = db.get_recent_tasks_before(sid, "2025-01-01T10:01:00", 5).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(before, vec![first]);
+        let before: Vec<i64> = db.get_recent_tasks_before(sid, "2025-01-01T10:05:00", 2).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(before, vec![spanning, wrong]);
+
+        let links = db.unlink_capture_group("g2").unwrap();
+        assert_eq!(links, vec![(b, wrong), (b2, spanning)]);
+        assert!(db.get_task_for_screenshot(b).unwrap().is_none());
+        assert!(db.get_task_for_screenshot(b2).unwrap().is_none());
+        // Nothing is deleted until the caller knows the re-analysis worked
+        assert!(db.get_task(wrong).is_ok());
+
+        // A failed re-analysis puts the links back, except where a screenshot got a new task
+        let other = db.insert_task("Other", "2025-01-01T10:01:00").unwrap();
+        db.link_screenshot_to_task(other, b2).unwrap();
+        assert_eq!(db.relink_screenshots(&links).unwrap(), 1);
+        assert_eq!(db.get_task_for_screenshot(b).unwrap().map(|t| t.id), Some(wrong));
+        assert_eq!(db.get_task_for_screenshot(b2).unwrap().map(|t| t.id), Some(other));
+
+        // After a successful one, the orphaned task goes; one still linked elsewhere stays
+        db.unlink_capture_group("g2").unwrap();
+        assert_eq!(db.delete_orphaned_tasks(&[wrong, spanning]).unwrap(), 1);
+        assert!(db.get_task(wrong).is_err());
+        assert_eq!(db.get_task_for_screenshot(c).unwrap().map(|t| t.id), Some(spanning));
+        assert_eq!(db.get_task_for_screenshot(a).unwrap().map(|t| t.id), Some(first));
+        // Tasks that no longer exist are skipped when relinking
+        assert_eq!(db.relink_screenshots(&links).unwrap(), 1);
+        assert!(db.get_task_for_screenshot(b).unwrap().is_none());
+
+        // A verified task is kept even when it's left without screenshots
+        db.update_task(first, &TaskUpdate {
+            title: None,
+            description: None,
+            category: None,
+            ended_at: None,
+            user_verified: Some(true),
+        }).unwrap();
+        assert_eq!(db.unlink_capture_group("g1").unwrap(), vec![(a, first)]);
+        assert_eq!(db.delete_orphaned_tasks(&[first]).unwrap(), 0);
+        assert!(db.get_task(first).unwrap().user_verified);
+    }
+
+    #[test]
+    fn test_unlink_unverified_tasks_keeps_verified() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let other = db.create_session("2025-01-01T12:00:00", None, None).unwrap();
+        let ss: Vec<i64> = (0..4)
+            .map(|i| db.insert_screenshot(&format!("{}.webp", i), &format!("2025-01-01T10:0{}:00", i), None, 0, Some(sid), None).unwrap())
+            .collect();
+        let elsewhere = db.insert_screenshot("x.webp", "2025-01-01T12:00:00", None, 0, Some(other), None).unwrap();
+
+        let good = db.insert_task("Good", "2025-01-01T10:00:00").unwrap();
+        db.update_task(good, &TaskUpdate {
+            title: None,
+            description: None,
+            category: None,
+            ended_at: None,
+            user_verified: Some(true),
+        }).unwrap();
+        let bad = db.insert_task("Bad", "2025-01-01T10:02:00").unwrap();
+        // An unverified task spanning both sessions keeps its other-session link
+        let spanning = db.insert_task("Spanning", "2025-01-01T10:03:00").unwrap();
+        db.link_screenshot_to_task(good, ss[0]).unwrap();
+        db.link_screenshot_to_task(good, ss[1]).unwrap();
+        db.link_screenshot_to_task(bad, ss[2]).unwrap();
+        db.link_screenshot_to_task(spanning, ss[3]).unwrap();
+        db.link_screenshot_to_task(spanning, elsewhere).unwrap();
+        // Another session's unverified task without screenshots isn't this re-analysis' business
+        let unrelated = db.insert_task("Unrelated", "2025-01-01T12:00:00").unwrap();
+
+        assert_eq!(db.unlink_unverified_tasks(sid).unwrap(), 2);
+        assert!(db.get_task(unrelated).is_ok());
+
+        let remaining: Vec<i64> = db.get_session_tasks(sid).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(remaining, vec![good]);
+        assert!(db.get_task(good).unwrap().user_verified);
+        assert_eq!(db.get_task_for_screenshot(ss[0]).unwrap().map(|t| t.id), Some(good));
+        assert_eq!(db.get_task_for_screenshot(ss[1]).unwrap().map(|t| t.id), Some(good));
+        assert!(db.get_task(bad).is_err());
+        assert_eq!(db.get_task_for_screenshot(elsewhere).unwrap().map(|t| t.id), Some(spanning));
+
+        let freed: Vec<i64> = db.get_unanalyzed_screenshots_for_session(sid, 10).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(freed, vec![ss[2], ss[3]]);
+    }
+
     #[test]
     fn test_get_tasks_pagination() {
         let db = Database::in_memory().unwrap();
@@ -834,10 +3697,60 @@ mod tests {
         db.link_screenshot_to_task(task_id, ss1).unwrap();
 
         // Only 2 unanalyzed screenshots should remain
-        let unanalyzed = db.get_unanalyzed_screenshots(10).unwrap();
+        let unanalyzed = db.get_unanalyzed_screenshots(10, true).unwrap();
         assert_eq!(unanalyzed.len(), 2);
         assert_eq!(unanalyzed[0].filepath, "shot2.webp");
         assert_eq!(unanalyzed[1].filepath, "shot3.webp");
+        // Sessionless screenshots are only included on request
+        assert!(db.get_unanalyzed_screenshots(10, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_unassigned_unanalyzed_screenshots() {
+        let db = Database::in_memory().unwrap();
+        let session_id = db.create_session("2025-01-01T00:00:00", None, None).unwrap();
+        let linked = db.insert_screenshot("linked.webp", "2025-01-01T00:00:00", None, 0, None, None).unwrap();
+        db.insert_screenshot("loose.webp", "2025-01-01T00:00:01", None, 0, None, None).unwrap();
+        db.insert_screenshot("session.webp", "2025-01-01T00:00:02", None, 0, Some(session_id), None).unwrap();
+        let task_id = db.insert_task("Task", "2025-01-01T00:00:00").unwrap();
+        db.link_screenshot_to_task(task_id, linked).unwrap();
+
+        // Neither analyzed ones nor ones in a session (even one being captured)
+        let unassigned = db.get_unassigned_unanalyzed_screenshots(10).unwrap();
+        assert_eq!(unassigned.iter().map(|s| s.filepath.as_str()).collect::<Vec<_>>(), vec!["loose.webp"]);
+    }
+
+    #[test]
+    fn test_unassigned_summary_and_assign() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.get_unassigned_summary().unwrap(), UnassignedSummary::default());
+
+        let session = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        let in_session = db.insert_screenshot("s.webp", "2025-01-01T09:00:00", None, 0, Some(session), None).unwrap();
+        let loose: Vec<i64> = ["2025-01-02T10:00:00", "2025-01-01T08:00:00", "2025-01-03T12:00:00"]
+            .iter()
+            .map(|t| db.insert_screenshot(&format!("{}.webp", t), t, None, 0, None, None).unwrap())
+            .collect();
+        // Analyzed and excluded screenshots aren't pending
+        let task_id = db.insert_task("Task", "2025-01-03T12:00:00").unwrap();
+        db.link_screenshot_to_task(task_id, loose[2]).unwrap();
+        let excluded = db.insert_screenshot("x.webp", "2025-01-04T00:00:00", None, 0, None, None).unwrap();
+        db.quarantine_screenshot(excluded, "quarantine/x.webp").unwrap();
+
+        assert_eq!(db.get_unassigned_summary().unwrap(), UnassignedSummary {
+            count: 2,
+            first_captured_at: Some("2025-01-01T08:00:00".to_string()),
+            last_captured_at: Some("2025-01-02T10:00:00".to_string()),
+        });
+
+        assert!(matches!(
+            db.assign_screenshots_to_session(&loose, 9999),
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        ));
+        // Screenshots already in a session keep it
+        assert_eq!(db.assign_screenshots_to_session(&[loose[0], loose[1], in_session], session).unwrap(), 2);
+        assert_eq!(db.get_unassigned_summary().unwrap().count, 0);
+        assert_eq!(db.get_session(session).unwrap().unanalyzed_count, 3);
     }
 
     #[test]
@@ -889,6 +3802,33 @@ mod tests {
         assert_eq!(sessions[0].screenshot_count, 0);
     }
 
+    #[test]
+    fn test_session_duration_excludes_pauses() {
+        let db = Database::in_memory().unwrap();
+        let id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+
+        db.pause_session(id, "2025-01-01T10:20:00").unwrap();
+        // While paused, the open pause is excluded as well
+        assert_eq!(db.get_session_duration(id, "2025-01-01T10:25:00").unwrap(), 20 * 60);
+        db.resume_session(id, "2025-01-01T10:30:00").unwrap();
+        db.end_session(id, "2025-01-01T11:00:00").unwrap();
+
+        assert_eq!(db.get_session_duration(id, "2025-01-01T12:00:00").unwrap(), 50 * 60);
+        assert_eq!(db.get_session(id).unwrap().duration_seconds, Some(50 * 60));
+    }
+
+    #[test]
+    fn test_end_session_closes_open_pause() {
+        let db = Database::in_memory().unwrap();
+        let id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.pause_session(id, "2025-01-01T10:50:00").unwrap();
+        db.end_session(id, "2025-01-01T11:00:00").unwrap();
+        assert_eq!(db.get_session(id).unwrap().duration_seconds, Some(50 * 60));
+        // Unended sessions have no fixed duration
+        let running = db.create_session("2025-01-01T12:00:00", None, None).unwrap();
+        assert_eq!(db.get_session(running).unwrap().duration_seconds, None);
+    }
+
     #[test]
     fn test_session_screenshot_count() {
         let db = Database::in_memory().unwrap();
@@ -943,6 +3883,48 @@ mod tests {
         assert_eq!(db.get_screenshot_session_id(ss_no_session).unwrap(), None);
     }
 
+    #[test]
+    fn test_get_latest_screenshot() {
+        let db = Database::in_memory().unwrap();
+        assert!(db.get_latest_screenshot().unwrap().is_none());
+
+        db.insert_screenshot("a.webp", "2025-01-01T10:00:30", None, 0, None, None).unwrap();
+        let newest = db.insert_screenshot("b.webp", "2025-01-01T10:01:00", None, 1, None, None).unwrap();
+        db.insert_screenshot("c.webp", "2025-01-01T10:00:00", None, 0, None, None).unwrap();
+        assert_eq!(db.get_latest_screenshot().unwrap().unwrap().id, newest);
+
+        // Quarantined screenshots are not shown
+        db.quarantine_screenshot(newest, "quarantine/b.webp").unwrap();
+        assert_eq!(db.get_latest_screenshot().unwrap().unwrap().filepath, "a.webp");
+    }
+
+    #[test]
+    fn test_get_screenshot_neighbors() {
+        let db = Database::in_memory().unwrap();
+        let session_id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let other = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+
+        let first = db.insert_screenshot("s1.webp", "2025-01-01T10:00:00", None, 0, Some(session_id), None).unwrap();
+        let middle = db.insert_screenshot("s2.webp", "2025-01-01T10:00:30", None, 0, Some(session_id), None).unwrap();
+        let last = db.insert_screenshot("s3.webp", "2025-01-01T10:01:00", None, 0, Some(session_id), None).unwrap();
+        // Screenshot from another session in between should be ignored
+        db.insert_screenshot("other.webp", "2025-01-01T10:00:45", None, 0, Some(other), None).unwrap();
+
+        let (prev, next) = db.get_screenshot_neighbors(middle).unwrap();
+        assert_eq!(prev.unwrap().id, first);
+        assert_eq!(next.unwrap().id, last);
+
+        let (prev, next) = db.get_screenshot_neighbors(first).unwrap();
+        assert!(prev.is_none());
+        assert_eq!(next.unwrap().id, middle);
+
+        let (prev, next) = db.get_screenshot_neighbors(last).unwrap();
+        assert_eq!(prev.unwrap().id, middle);
+        assert!(next.is_none());
+
+        assert!(db.get_screenshot_neighbors(999).is_err());
+    }
+
     #[test]
     fn test_get_sessions_pagination() {
         let db = Database::in_memory().unwrap();
@@ -1028,6 +4010,40 @@ mod tests {
         assert_eq!(completed[0].title, Some("Done".to_string()));
     }
 
+    #[test]
+    fn test_get_session_status() {
+        let db = Database::in_memory().unwrap();
+
+        let active = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.insert_screenshot("a.webp", "2025-01-01T10:00:00", None, 0, Some(active), None).unwrap();
+        assert_eq!(db.get_session_status(active).unwrap(), SessionStatus::Active);
+
+        let empty = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        db.end_session(empty, "2025-01-01T11:30:00").unwrap();
+        assert_eq!(db.get_session_status(empty).unwrap(), SessionStatus::Empty);
+
+        let session = db.create_session("2025-01-01T12:00:00", None, None).unwrap();
+        db.end_session(session, "2025-01-01T12:30:00").unwrap();
+        let ss1 = db.insert_screenshot("b.webp", "2025-01-01T12:00:00", None, 0, Some(session), None).unwrap();
+        let ss2 = db.insert_screenshot("c.webp", "2025-01-01T12:01:00", None, 0, Some(session), None).unwrap();
+        let ss3 = db.insert_screenshot("d.webp", "2025-01-01T12:02:00", None, 0, Some(session), None).unwrap();
+        assert_eq!(db.get_session_status(session).unwrap(), SessionStatus::Pending);
+
+        // Excluded screenshots don't hold a session back
+        let task_id = db.insert_task("Task", "2025-01-01T12:00:00").unwrap();
+        db.link_screenshot_to_task(task_id, ss1).unwrap();
+        db.link_screenshot_to_task(task_id, ss2).unwrap();
+        db.quarantine_screenshot(ss3, "quarantine/d.webp").unwrap();
+        assert_eq!(db.get_session_status(session).unwrap(), SessionStatus::Completed);
+
+        // The lists agree with the per-session status
+        let ids = |sessions: Vec<CaptureSession>| sessions.iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids(db.get_completed_sessions(10, 0).unwrap()), vec![session]);
+        assert!(db.get_pending_sessions(10, 0).unwrap().is_empty());
+
+        assert!(matches!(db.get_session_status(9999), Err(rusqlite::Error::QueryReturnedNoRows)));
+    }
+
     #[test]
     fn test_get_task_for_screenshot() {
         let db = Database::in_memory().unwrap();
@@ -1051,6 +4067,83 @@ mod tests {
         assert!(db.get_task_for_screenshot(ss_no_task).unwrap().is_none());
     }
 
+    #[test]
+    fn test_task_embedding_roundtrip() {
+        let db = Database::in_memory().unwrap();
+        let id = db.insert_full_task("Coding", "Writing Rust", "coding", "2025-01-01T00:00:00", "IDE open").unwrap();
+        assert_eq!(db.get_task_embedding(id, "nomic").unwrap(), None);
+        db.set_task_embedding(id, "nomic", &[0.5, -1.0, 0.25]).unwrap();
+        assert_eq!(db.get_task_embedding(id, "nomic").unwrap(), Some(vec![0.5, -1.0, 0.25]));
+        assert_eq!(db.get_task_embedding(id, "mxbai").unwrap(), None, "another model's vector");
+        assert_eq!(db.get_task_embedding(id + 1, "nomic").unwrap(), None);
+
+        // Edits to what was embedded drop the cache; other edits keep it
+        let edit = |title: Option<&str>, category: Option<&str>| TaskUpdate {
+            title: title.map(str::to_string),
+            description: None,
+            category: category.map(str::to_string),
+            ended_at: None,
+            user_verified: None,
+        };
+        db.update_task(id, &edit(None, Some("writing"))).unwrap();
+        assert!(db.get_task_embedding(id, "nomic").unwrap().is_some());
+        db.update_task(id, &edit(Some("Reviewing"), None)).unwrap();
+        assert_eq!(db.get_task_embedding(id, "nomic").unwrap(), None);
+
+        // So does a merge, for the task kept
+        let other = db.insert_full_task("Coding", "More Rust", "coding", "2025-01-01T01:00:00", "IDE open").unwrap();
+        db.set_task_embedding(id, "nomic", &[1.0]).unwrap();
+        db.merge_tasks(id, &[other]).unwrap();
+        assert_eq!(db.get_task_embedding(id, "nomic").unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_task_detail() {
+        let db = Database::in_memory().unwrap();
+        let later = db.insert_screenshot("b.webp", "2025-01-01T00:00:05", Some("Editor"), 0, None, None).unwrap();
+        let earlier = db.insert_screenshot("a.webp", "2025-01-01T00:00:00", Some("Editor"), 1, None, None).unwrap();
+        let unlinked = db.insert_screenshot("c.webp", "2025-01-01T00:00:10", None, 0, None, None).unwrap();
+        let task_id = db.insert_full_task("Coding", "Writing Rust", "coding", "2025-01-01T00:00:00", "IDE open").unwrap();
+        db.link_screenshot_to_task(task_id, later).unwrap();
+        db.link_screenshot_to_task(task_id, earlier).unwrap();
+        db.set_task_metadata(task_id, r#"{"analyzed_by":"ollama:llava"}"#).unwrap();
+
+        let detail = db.get_task_detail(task_id).unwrap().unwrap();
+        assert_eq!(detail.task.id, task_id);
+        assert_eq!(detail.task.title, "Coding");
+        let ids: Vec<i64> = detail.screenshots.iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![earlier, later]);
+        assert!(!ids.contains(&unlinked));
+        assert_eq!(detail.screenshots[0].monitor_index, 1);
+        assert_eq!(detail.metadata.unwrap()["analyzed_by"], "ollama:llava");
+
+        assert!(db.get_task_detail(task_id + 100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_analysis_debug_records_and_purge() {
+        let db = Database::in_memory().unwrap();
+        let old = vec!["analysis_debug/1.webp".to_string(), "analysis_debug/2.webp".to_string()];
+        db.insert_analysis_debug("2025-01-01T10:00:00", "g1", "old prompt", &old, "2025-01-01T10:00:00").unwrap();
+        // Re-analysis of the same group overwrote 1.webp
+        let new = vec!["analysis_debug/1.webp".to_string()];
+        db.insert_analysis_debug("2025-01-03T10:00:00", "g1", "new prompt", &new, "2025-01-03T10:00:00").unwrap();
+        db.insert_analysis_debug("2025-01-03T10:00:00", "g2", "other", &[], "2025-01-03T10:00:00").unwrap();
+
+        let entries = db.get_analysis_debug("g1").unwrap();
+        let prompts: Vec<&str> = entries.iter().map(|e| e.prompt.as_str()).collect();
+        assert_eq!(prompts, vec!["new prompt", "old prompt"]);
+        assert_eq!(entries[1].image_paths, old);
+        assert_eq!(entries[1].run_id, "2025-01-01T10:00:00");
+        assert!(db.get_analysis_debug("missing").unwrap().is_empty());
+
+        // Only the file no remaining record uses is handed back for deletion
+        let orphaned = db.purge_analysis_debug_before("2025-01-02T00:00:00").unwrap();
+        assert_eq!(orphaned, vec!["analysis_debug/2.webp".to_string()]);
+        assert_eq!(db.get_analysis_debug("g1").unwrap().len(), 1);
+        assert!(db.purge_analysis_debug_before("2025-01-02T00:00:00").unwrap().is_empty());
+    }
+
     #[test]
     fn test_delete_session() {
         let db = Database::in_memory().unwrap();
@@ -1090,6 +4183,194 @@ mod tests {
         assert_eq!(s2_screenshots[0].filepath, "s3.webp");
     }
 
+    #[test]
+    fn test_trash_and_restore_session() {
+        let db = Database::in_memory().unwrap();
+        let s1 = db.create_session("2025-01-01T10:00:00", Some("Session 1"), Some("Morning")).unwrap();
+        let s2 = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        let ss1 = db.insert_screenshot("screenshots/s1.webp", "2025-01-01T10:00:00", Some("Editor"), 0, Some(s1), Some("g1")).unwrap();
+        let ss2 = db.insert_screenshot("screenshots/s2.webp", "2025-01-01T10:00:30", None, 0, Some(s1), Some("g2")).unwrap();
+        let ss3 = db.insert_screenshot("screenshots/s3.webp", "2025-01-01T11:00:00", None, 0, Some(s2), None).unwrap();
+        let t1 = db.insert_full_task("Task A", "Only in s1", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(t1, ss1).unwrap();
+        let t2 = db.insert_full_task("Task B", "In both sessions", "coding", "2025-01-01T10:00:30", "reason").unwrap();
+        db.link_screenshot_to_task(t2, ss2).unwrap();
+        db.link_screenshot_to_task(t2, ss3).unwrap();
+        db.set_screenshot_label(ss1, "Writing code", "coding", "2025-01-02T09:00:00").unwrap();
+        db.record_capture_event(Some(s1), "2025-01-01T10:00:10", "cursor_fallback", Some("primary")).unwrap();
+
+        let moved = db.trash_session(s1, "2025-01-03T00:00:00").unwrap();
+        assert_eq!(moved, vec![(ss1, "screenshots/s1.webp".to_string()), (ss2, "screenshots/s2.webp".to_string())]);
+        assert!(db.get_session(s1).is_err());
+        assert!(db.get_screenshot(ss1).is_err());
+        // The orphan rule waits for the purge: Task A keeps its (trashed) screenshot
+        assert!(db.get_task(t1).is_ok());
+        assert_eq!(db.get_session_screenshots(s2).unwrap().len(), 1);
+
+        let trash = db.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].kind, TrashKind::Session);
+        assert_eq!(trash[0].original_id, s1);
+        assert_eq!(trash[0].label, "Morning");
+        assert_eq!(trash[0].screenshot_count, 2);
+
+        let restored = db.restore_from_trash(TrashKind::Session, trash[0].trash_id).unwrap().unwrap();
+        assert_eq!(restored.len(), 2);
+        let session = db.get_session(s1).unwrap();
+        assert_eq!(session.description.as_deref(), Some("Session 1"));
+        let screenshots = db.get_session_screenshots(s1).unwrap();
+        assert_eq!(screenshots.iter().map(|ss| ss.id).collect::<Vec<_>>(), vec![ss1, ss2]);
+        assert_eq!(screenshots[0].capture_group.as_deref(), Some("g1"));
+        assert_eq!(db.get_task_for_screenshot(ss1).unwrap().unwrap().id, t1);
+        assert_eq!(db.get_task_for_screenshot(ss2).unwrap().unwrap().id, t2);
+        assert_eq!(db.get_task_for_screenshot(ss3).unwrap().unwrap().id, t2);
+        assert_eq!(db.get_screenshot_label(ss1).unwrap().unwrap().label_title, "Writing code");
+        assert_eq!(db.get_capture_events(s1).unwrap().len(), 1);
+        assert!(db.list_trash().unwrap().is_empty());
+        assert!(db.restore_from_trash(TrashKind::Session, trash[0].trash_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_restore_renumbers_reused_ids() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let old = db.insert_screenshot("screenshots/old.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        let task = db.insert_full_task("Task", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(task, old).unwrap();
+
+        db.trash_screenshots(&[old], "2025-01-02T00:00:00").unwrap();
+        // SQLite hands the freed (highest) id to the next insert
+        let newer = db.insert_screenshot("screenshots/new.webp", "2025-01-01T10:01:00", None, 0, Some(sid), None).unwrap();
+        assert_eq!(newer, old);
+
+        let entry = db.list_trash().unwrap().remove(0);
+        assert_eq!(entry.kind, TrashKind::Screenshot);
+        db.restore_from_trash(TrashKind::Screenshot, entry.trash_id).unwrap().unwrap();
+
+        let screenshots = db.get_session_screenshots(sid).unwrap();
+        assert_eq!(screenshots.len(), 2);
+        let restored = screenshots.iter().find(|ss| ss.filepath == "screenshots/old.webp").unwrap();
+        assert_ne!(restored.id, newer);
+        assert_eq!(db.get_task_for_screenshot(restored.id).unwrap().unwrap().id, task);
+        assert!(db.get_task_for_screenshot(newer).unwrap().is_none(), "the newer row must not inherit the link");
+    }
+
+    #[test]
+    fn test_restore_screenshot_trashed_before_its_session() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let ss1 = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        let ss2 = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 0, Some(sid), None).unwrap();
+        let task = db.insert_full_task("Task", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(task, ss1).unwrap();
+
+        db.trash_screenshots(&[ss1], "2025-01-02T00:00:00").unwrap();
+        let screenshot_entry = db.list_trash().unwrap().remove(0).trash_id;
+        db.trash_session(sid, "2025-01-03T00:00:00").unwrap();
+
+        // Listed once, under the session
+        let trash = db.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].screenshot_count, 2);
+
+        // Restoring the screenshot brings back its session and everything trashed with it
+        let restored = db.restore_from_trash(TrashKind::Screenshot, screenshot_entry).unwrap().unwrap();
+        assert_eq!(restored.len(), 2);
+        let ids: Vec<i64> = db.get_session_screenshots(sid).unwrap().iter().map(|ss| ss.id).collect();
+        assert_eq!(ids, vec![ss1, ss2]);
+        assert_eq!(db.get_task_for_screenshot(ss1).unwrap().unwrap().id, task);
+        assert!(db.list_trash().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_purge_trash_applies_orphan_rule() {
+        let db = Database::in_memory().unwrap();
+        let s1 = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let s2 = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+        let ss1 = db.insert_screenshot("screenshots/s1.webp", "2025-01-01T10:00:00", None, 0, Some(s1), None).unwrap();
+        let ss2 = db.insert_screenshot("screenshots/s2.webp", "2025-01-01T11:00:00", None, 0, Some(s2), None).unwrap();
+        let ss3 = db.insert_screenshot("screenshots/s3.webp", "2025-01-01T11:00:30", None, 0, Some(s2), None).unwrap();
+        let only_s1 = db.insert_full_task("Only s1", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(only_s1, ss1).unwrap();
+        let shared = db.insert_full_task("Shared", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(shared, ss1).unwrap();
+        db.link_screenshot_to_task(shared, ss2).unwrap();
+        let deleted_meanwhile = db.insert_full_task("Deleted", "desc", "coding", "2025-01-01T11:00:30", "reason").unwrap();
+        db.link_screenshot_to_task(deleted_meanwhile, ss3).unwrap();
+
+        db.trash_session(s1, "2025-01-02T00:00:00").unwrap();
+        db.trash_screenshots(&[ss3], "2025-01-05T00:00:00").unwrap();
+
+        // Hard-deleting a task drops its trashed links; the restored screenshot comes back unlinked
+        db.delete_task(deleted_meanwhile).unwrap();
+        let ss3_entry = db.list_trash().unwrap().into_iter().find(|e| e.kind == TrashKind::Screenshot).unwrap();
+        db.restore_from_trash(TrashKind::Screenshot, ss3_entry.trash_id).unwrap().unwrap();
+        assert!(db.get_task_for_screenshot(ss3).unwrap().is_none());
+
+        // Hard-deleting s2 must not take a task whose other screenshot is in the trash
+        db.link_screenshot_to_task(shared, ss3).unwrap();
+        db.delete_session(s2).unwrap();
+        assert!(db.get_task(shared).is_ok());
+
+        // Nothing trashed before the cutoff
+        assert!(db.purge_trash(Some("2025-01-01T00:00:00")).unwrap().is_empty());
+        assert_eq!(db.list_trash().unwrap().len(), 1);
+
+        let purged = db.purge_trash(Some("2025-01-03T00:00:00")).unwrap();
+        assert_eq!(purged, vec!["screenshots/s1.webp"]);
+        assert!(db.list_trash().unwrap().is_empty());
+        assert!(db.get_task(only_s1).is_err());
+        assert!(db.get_task(shared).is_err());
+    }
+
+    #[test]
+    fn test_merge_tasks() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let ss1 = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        let ss2 = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:01:00", None, 0, Some(sid), None).unwrap();
+        let ss3 = db.insert_screenshot("screenshots/c.webp", "2025-01-01T10:02:00", None, 0, Some(sid), None).unwrap();
+        let keep = db.insert_full_task("Keep", "desc", "coding", "2025-01-01T10:01:00", "reason").unwrap();
+        db.link_screenshot_to_task(keep, ss2).unwrap();
+        let earlier = db.insert_full_task("Earlier", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(earlier, ss1).unwrap();
+        let trashed = db.insert_full_task("Trashed", "desc", "coding", "2025-01-01T10:02:00", "reason").unwrap();
+        db.link_screenshot_to_task(trashed, ss3).unwrap();
+        db.conn().unwrap().execute("UPDATE tasks SET ended_at = '2025-01-01T10:05:00' WHERE id = ?1", params![trashed]).unwrap();
+        db.trash_screenshots(&[ss3], "2025-01-02T00:00:00").unwrap();
+
+        db.merge_tasks(keep, &[earlier, trashed]).unwrap();
+
+        assert!(db.get_task(earlier).is_err());
+        assert!(db.get_task(trashed).is_err());
+        let merged = db.get_task(keep).unwrap();
+        assert_eq!(merged.started_at, "2025-01-01T10:00:00");
+        assert_eq!(merged.ended_at.as_deref(), Some("2025-01-01T10:05:00"));
+        assert_eq!(db.get_task_for_screenshot(ss1).unwrap().unwrap().id, keep);
+        // The trashed screenshot's link moved too, so restoring it lands on the kept task
+        let entry = db.list_trash().unwrap().remove(0);
+        db.restore_from_trash(entry.kind, entry.trash_id).unwrap().unwrap();
+        assert_eq!(db.get_task_for_screenshot(ss3).unwrap().unwrap().id, keep);
+    }
+
+    #[test]
+    fn test_delete_screenshot_permanently() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let ss1 = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        let ss2 = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 0, Some(sid), None).unwrap();
+        let alone = db.insert_full_task("Alone", "desc", "coding", "2025-01-01T10:00:00", "reason").unwrap();
+        db.link_screenshot_to_task(alone, ss1).unwrap();
+        let kept = db.insert_full_task("Kept", "desc", "coding", "2025-01-01T10:00:30", "reason").unwrap();
+        db.link_screenshot_to_task(kept, ss2).unwrap();
+
+        assert_eq!(db.delete_screenshot(ss1).unwrap().as_deref(), Some("screenshots/a.webp"));
+        assert!(db.get_task(alone).is_err());
+        assert!(db.get_task(kept).is_ok());
+        assert!(db.delete_screenshot(ss1).unwrap().is_none());
+        assert!(db.list_trash().unwrap().is_empty());
+    }
+
     #[test]
     fn test_get_recent_tasks_for_session() {
         let db = Database::in_memory().unwrap();
@@ -1160,6 +4441,70 @@ mod tests {
         assert_eq!(unanalyzed2[0].filepath, "s3.webp");
     }
 
+    #[test]
+    fn test_backfill_capture_groups() {
+        let db = Database::in_memory().unwrap();
+        let session_id = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let other_session = db.create_session("2025-01-01T11:00:00", None, None).unwrap();
+
+        let a = db.insert_screenshot("a.webp", "2025-01-01T10:00:00", None, 0, Some(session_id), None).unwrap();
+        let b = db.insert_screenshot("b.webp", "2025-01-01T10:00:00", None, 1, Some(session_id), None).unwrap();
+        let c = db.insert_screenshot("c.webp", "2025-01-01T10:00:30", None, 0, Some(session_id), None).unwrap();
+        let d = db.insert_screenshot("d.webp", "2025-01-01T10:00:30", None, 0, Some(session_id), Some("existing")).unwrap();
+        let e = db.insert_screenshot("e.webp", "2025-01-01T10:00:00", None, 0, Some(other_session), None).unwrap();
+
+        assert_eq!(db.backfill_capture_groups(session_id).unwrap(), 3);
+
+        let group = |id| db.get_screenshot(id).unwrap().capture_group;
+        assert_eq!(group(a), Some("2025-01-01T10-00-00".to_string()));
+        assert_eq!(group(b), group(a));
+        assert_eq!(group(c), Some("2025-01-01T10-00-30".to_string()));
+        assert_eq!(group(d), Some("existing".to_string()));
+        assert_eq!(group(e), None);
+
+        let grouped = db.get_capture_group("2025-01-01T10-00-00").unwrap();
+        assert_eq!(grouped.iter().map(|s| s.id).collect::<Vec<_>>(), vec![a, b]);
+
+        // Idempotent: nothing left to backfill
+        assert_eq!(db.backfill_capture_groups(session_id).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_session_stats() {
+        let db = Database::in_memory().unwrap();
+        let jan = db.create_session("2025-01-31T23:00:00", None, None).unwrap();
+        let feb = db.create_session("2025-02-01T09:00:00", None, None).unwrap();
+        assert_eq!(db.get_session(jan).unwrap().stats, SessionStats::default());
+
+        db.add_session_stats(jan, &SessionStats { ticks: 10, ticks_unchanged: 6, ..Default::default() }).unwrap();
+        db.add_session_stats(jan, &SessionStats { groups_analyzed: 4, ..Default::default() }).unwrap();
+        db.add_session_stats(feb, &SessionStats { ticks: 5, ..Default::default() }).unwrap();
+
+        let stats = db.get_session(jan).unwrap().stats;
+        assert_eq!((stats.ticks, stats.ticks_unchanged, stats.groups_analyzed), (10, 6, 4));
+
+        // A bare `to` date includes that whole day
+        let january = db.get_session_stats_between(Some("2025-01-01"), Some("2025-01-31")).unwrap();
+        assert_eq!(january, vec![stats]);
+        assert_eq!(db.get_session_stats_between(None, None).unwrap().len(), 2);
+        assert_eq!(db.get_session_stats_between(Some("2025-02-01"), None).unwrap()[0].ticks, 5);
+    }
+
+    #[test]
+    fn test_capture_events() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.record_capture_event(Some(sid), "2025-01-01T10:00:30", "skipped_private", Some("keyword index 1")).unwrap();
+        db.record_capture_event(Some(sid), "2025-01-01T10:00:00", "skipped_private", Some("keyword index 0")).unwrap();
+        db.record_capture_event(None, "2025-01-01T10:01:00", "skipped_private", None).unwrap();
+
+        let events = db.get_capture_events(sid).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].occurred_at, "2025-01-01T10:00:00");
+        assert_eq!(events[0].kind, "skipped_private");
+        assert_eq!(events[1].detail.as_deref(), Some("keyword index 1"));
+    }
+
     #[test]
     fn test_capture_group() {
         let db = Database::in_memory().unwrap();
@@ -1182,4 +4527,239 @@ mod tests {
         let all = db.get_session_screenshots(session).unwrap();
         assert_eq!(all.len(), 3);
     }
+
+    #[test]
+    fn test_count_pending_groups() {
+        let db = Database::in_memory().unwrap();
+        assert_eq!(db.count_pending_groups().unwrap(), PendingGroups::default());
+
+        let ended = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        // Two monitors in one group, a second group, and one ungrouped capture
+        db.insert_screenshot("screenshots/a0.webp", "2025-01-01T10:00:00", None, 0, Some(ended), Some("g1")).unwrap();
+        db.insert_screenshot("screenshots/a1.webp", "2025-01-01T10:00:00", None, 1, Some(ended), Some("g1")).unwrap();
+        db.insert_screenshot("screenshots/b0.webp", "2025-01-01T10:00:30", None, 0, Some(ended), Some("g2")).unwrap();
+        db.insert_screenshot("screenshots/c0.webp", "2025-01-01T10:01:00", None, 0, Some(ended), None).unwrap();
+        // Analyzed and quarantined screenshots aren't pending
+        let done = db.insert_screenshot("screenshots/d0.webp", "2025-01-01T10:01:30", None, 0, Some(ended), Some("g3")).unwrap();
+        let task = db.insert_task("Coding", "2025-01-01T10:01:30").unwrap();
+        db.link_screenshot_to_task(task, done).unwrap();
+        let corrupt = db.insert_screenshot("screenshots/e0.webp", "2025-01-01T10:02:00", None, 0, Some(ended), Some("g4")).unwrap();
+        db.quarantine_screenshot(corrupt, "screenshots/corrupt/e0.webp").unwrap();
+        db.end_session(ended, "2025-01-01T11:00:00").unwrap();
+
+        // The same group id in another ended session is a separate group
+        let other = db.create_session("2025-01-02T10:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/f0.webp", "2025-01-02T10:00:00", None, 0, Some(other), Some("g1")).unwrap();
+        db.end_session(other, "2025-01-02T11:00:00").unwrap();
+
+        // Sessions still capturing aren't pending yet
+        let active = db.create_session("2025-01-03T10:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/g0.webp", "2025-01-03T10:00:00", None, 0, Some(active), Some("g9")).unwrap();
+
+        assert_eq!(
+            db.count_pending_groups().unwrap(),
+            PendingGroups { sessions: 2, groups: 4, screenshots: 5 }
+        );
+        assert_eq!(
+            db.count_session_pending_groups(ended).unwrap(),
+            PendingGroups { sessions: 1, groups: 3, screenshots: 4 }
+        );
+        // A single session is counted even while it's still capturing
+        assert_eq!(
+            db.count_session_pending_groups(active).unwrap(),
+            PendingGroups { sessions: 1, groups: 1, screenshots: 1 }
+        );
+    }
+
+    #[test]
+    fn test_record_analysis_latency() {
+        let db = Database::in_memory().unwrap();
+        assert!(db.get_analysis_latencies("ollama", "qwen3-vl:8b").unwrap().is_empty());
+
+        db.record_analysis_latency("ollama", "qwen3-vl:8b", 4000.0).unwrap();
+        db.record_analysis_latency("ollama", "qwen3-vl:8b", 5000.0).unwrap();
+        db.record_analysis_latency("ollama", "llava:7b", 9000.0).unwrap();
+        assert_eq!(db.get_analysis_latencies("ollama", "qwen3-vl:8b").unwrap(), vec![4000.0, 5000.0]);
+        assert_eq!(db.get_analysis_latencies("ollama", "llava:7b").unwrap(), vec![9000.0]);
+
+        for _ in 0..estimate::LATENCY_WINDOW {
+            db.record_analysis_latency("ollama", "qwen3-vl:8b", 6000.0).unwrap();
+        }
+        let history = db.get_analysis_latencies("ollama", "qwen3-vl:8b").unwrap();
+        assert_eq!(history, vec![6000.0; estimate::LATENCY_WINDOW]);
+    }
+
+    #[test]
+    fn test_get_session_gaps() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        // 30s interval with one 5-minute hole; the 10:00:30 tick has two monitors
+        for (ts, monitor) in [
+            ("2025-01-01T10:00:00", 0),
+            ("2025-01-01T10:00:30", 0),
+            ("2025-01-01T10:00:30", 1),
+            ("2025-01-01T10:01:05", 0),
+            ("2025-01-01T10:06:05", 0),
+            ("2025-01-01T10:06:35", 0),
+        ] {
+            db.insert_screenshot("screenshots/x.webp", ts, None, monitor, Some(sid), None).unwrap();
+        }
+        let other = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/y.webp", "2025-01-01T08:00:00", None, 0, Some(other), None).unwrap();
+
+        let gaps = db.get_session_gaps(sid, 60_000).unwrap();
+        assert_eq!(gaps, vec![SessionGap {
+            start: "2025-01-01T10:01:05".to_string(),
+            end: "2025-01-01T10:06:05".to_string(),
+            gap_seconds: 300,
+        }]);
+        // A lower threshold also flags the 35s step, but never the 30s ones
+        assert_eq!(db.get_session_gaps(sid, 33_000).unwrap().len(), 2);
+        assert!(db.get_session_gaps(other, 60_000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_session_window_titles() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        for title in [Some("a.rs - Code"), None, Some("a.rs - Code"), Some("Inbox — Mail")] {
+            db.insert_screenshot("screenshots/x.webp", "2025-01-01T10:00:00", title, 0, Some(sid), None).unwrap();
+        }
+        db.insert_screenshot("screenshots/y.webp", "2025-01-01T10:00:00", Some("Other"), 0, None, None).unwrap();
+
+        let mut titles = db.get_session_window_titles(sid).unwrap();
+        titles.sort();
+        assert_eq!(titles, vec![("Inbox — Mail".to_string(), 1), ("a.rs - Code".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_get_session_capture_groups() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/b0.webp", "2025-01-01T10:00:30", None, 0, Some(sid), Some("2025-01-01T10-00-30")).unwrap();
+        db.insert_screenshot("screenshots/a0.webp", "2025-01-01T10:00:00", None, 0, Some(sid), Some("2025-01-01T10-00-00")).unwrap();
+        db.insert_screenshot("screenshots/a1.webp", "2025-01-01T10:00:00", None, 1, Some(sid), Some("2025-01-01T10-00-00")).unwrap();
+        db.insert_screenshot("screenshots/legacy.webp", "2025-01-01T09:59:00", None, 0, Some(sid), None).unwrap();
+        let other = db.create_session("2025-01-02T10:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/c0.webp", "2025-01-02T10:00:00", None, 0, Some(other), Some("2025-01-02T10-00-00")).unwrap();
+
+        let groups = db.get_session_capture_groups(sid).unwrap();
+        assert_eq!(groups, vec![
+            CaptureGroupSummary {
+                group: UNGROUPED_CAPTURE_GROUP.to_string(),
+                screenshot_count: 1,
+                captured_at: "2025-01-01T09:59:00".to_string(),
+            },
+            CaptureGroupSummary {
+                group: "2025-01-01T10-00-00".to_string(),
+                screenshot_count: 2,
+                captured_at: "2025-01-01T10:00:00".to_string(),
+            },
+            CaptureGroupSummary {
+                group: "2025-01-01T10-00-30".to_string(),
+                screenshot_count: 1,
+                captured_at: "2025-01-01T10:00:30".to_string(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_normalize_session_bounds() {
+        let db = Database::in_memory().unwrap();
+        // Recorded bounds wider than the screenshots (failed start, idle tail)
+        let ended = db.create_session("2025-01-01T09:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:30:00", None, 0, Some(ended), None).unwrap();
+        db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(ended), None).unwrap();
+        db.end_session(ended, "2025-01-01T12:00:00").unwrap();
+        // Narrower than the screenshots, with more paused time than the real span
+        let paused = db.create_session("2025-01-02T10:10:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/c.webp", "2025-01-02T10:00:00", None, 0, Some(paused), None).unwrap();
+        db.insert_screenshot("screenshots/d.webp", "2025-01-02T10:20:00", None, 0, Some(paused), None).unwrap();
+        db.pause_session(paused, "2025-01-02T10:10:00").unwrap();
+        db.end_session(paused, "2025-01-02T10:50:00").unwrap();
+        let active = db.create_session("2025-01-03T09:00:00", None, None).unwrap();
+        db.insert_screenshot("screenshots/e.webp", "2025-01-03T09:05:00", None, 0, Some(active), None).unwrap();
+        let empty = db.create_session("2025-01-04T09:00:00", None, None).unwrap();
+        db.end_session(empty, "2025-01-04T10:00:00").unwrap();
+
+        assert!(db.normalize_session_bounds(ended).unwrap());
+        let session = db.get_session(ended).unwrap();
+        assert_eq!(session.started_at, "2025-01-01T10:00:00");
+        assert_eq!(session.ended_at.as_deref(), Some("2025-01-01T10:30:00"));
+        assert_eq!(session.duration_seconds, Some(1800));
+
+        assert!(db.normalize_session_bounds(paused).unwrap());
+        let session = db.get_session(paused).unwrap();
+        assert_eq!(session.started_at, "2025-01-02T10:00:00");
+        assert_eq!(session.ended_at.as_deref(), Some("2025-01-02T10:20:00"));
+        assert_eq!(session.duration_seconds, Some(0), "paused time is capped at the span");
+
+        assert!(db.normalize_session_bounds(active).unwrap());
+        let session = db.get_session(active).unwrap();
+        assert_eq!(session.started_at, "2025-01-03T09:05:00");
+        assert_eq!(session.ended_at, None);
+
+        assert!(!db.normalize_session_bounds(empty).unwrap());
+        assert_eq!(db.get_session(empty).unwrap().started_at, "2025-01-04T09:00:00");
+    }
+
+    #[test]
+    fn test_screenshot_labels() {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", None, None).unwrap();
+        let a = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, Some(sid), None).unwrap();
+        let b = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 0, Some(sid), None).unwrap();
+        let other = db.insert_screenshot("screenshots/c.webp", "2025-01-01T09:00:00", None, 0, None, None).unwrap();
+        assert_eq!(db.get_screenshot_label(a).unwrap(), None);
+
+        db.set_screenshot_label(b, "Reading docs", "browsing", "2025-01-02T09:00:00").unwrap();
+        db.set_screenshot_label(a, "Fixing bug", "browsing", "2025-01-02T09:00:00").unwrap();
+        db.set_screenshot_label(a, "Fixing login bug", "coding", "2025-01-02T09:05:00").unwrap();
+        db.set_screenshot_label(other, "Email", "communication", "2025-01-02T09:00:00").unwrap();
+        let label = db.get_screenshot_label(a).unwrap().unwrap();
+        assert_eq!((label.label_title.as_str(), label.label_category.as_str()), ("Fixing login bug", "coding"));
+        assert_eq!(label.labeled_at, "2025-01-02T09:05:00");
+
+        let listed = db.list_labeled_screenshots(Some(sid)).unwrap();
+        let ids: Vec<i64> = listed.iter().map(|l| l.screenshot.id).collect();
+        assert_eq!(ids, vec![a, b]);
+        assert_eq!(db.list_labeled_screenshots(None).unwrap().len(), 3);
+
+        // Only `a` is analyzed; re-analysis replacing its task leaves the label alone
+        let task = db.insert_full_task("Debugging login", "", "coding", "2025-01-01T10:00:00", "").unwrap();
+        db.link_screenshot_to_task(task, a).unwrap();
+        db.delete_task(task).unwrap();
+        let task = db.insert_full_task("Login bug", "", "coding", "2025-01-01T10:00:00", "").unwrap();
+        db.link_screenshot_to_task(task, a).unwrap();
+        let pairs = db.get_session_label_tasks(sid).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].0.label_title, "Fixing login bug");
+        assert_eq!(pairs[0].1.as_ref().unwrap().id, task);
+        assert!(pairs[1].1.is_none());
+
+        db.delete_session(sid).unwrap();
+        assert_eq!(db.get_screenshot_label(a).unwrap(), None);
+    }
+
+    #[test]
+    fn test_mark_previous_active() {
+        let db = Database::in_memory().unwrap();
+        let now = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 2, None, Some("g1")).unwrap();
+        let left = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:00", None, 1, None, Some("g1")).unwrap();
+        db.mark_previous_active(left).unwrap();
+        let group = db.get_capture_group("g1").unwrap();
+        let flagged = |id| group.iter().find(|ss| ss.id == id).unwrap().previous_active;
+        assert!(!flagged(now));
+        assert!(flagged(left));
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        let db = Database::in_memory().unwrap();
+        let legacy = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", None, 0, None, None).unwrap();
+        let scaled = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:00", None, 1, None, None).unwrap();
+        db.set_scale_factor(scaled, 1.5).unwrap();
+        assert_eq!(db.get_screenshot(legacy).unwrap().scale_factor, None);
+        assert_eq!(db.get_screenshot(scaled).unwrap().scale_factor, Some(1.5));
+    }
 }
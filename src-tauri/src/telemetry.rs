@@ -0,0 +1,324 @@
+use crate::models::{ProcessLoad, ProcessUsage, ResourceUsage, SystemLoad};
+use log::warn;
+use std::future::Future;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// How often a running analysis samples machine load.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A source of load snapshots: sysinfo in the app, scripted in tests.
+pub trait LoadSampler: Send {
+    /// One snapshot. Anything the sampler couldn't read is None.
+    fn sample(&mut self) -> SystemLoad;
+}
+
+/// Samples system-wide CPU and memory, our own process, and the managed Ollama child.
+pub struct SysinfoSampler {
+    system: System,
+    app_pid: Option<Pid>,
+    ollama_pid: Option<Pid>,
+}
+
+impl SysinfoSampler {
+    /// `ollama_pid` is the managed Ollama child, if any; an Ollama we didn't start isn't tracked.
+    /// CPU usage is measured between refreshes, so the first `sample` should come at
+    /// least `sysinfo::MINIMUM_CPU_UPDATE_INTERVAL` after this.
+    pub fn new(ollama_pid: Option<u32>) -> Self {
+        let app_pid = sysinfo::get_current_pid()
+            .map_err(|e| warn!("Can't determine our own PID for load sampling: {}", e))
+            .ok();
+        let mut sampler = SysinfoSampler { system: System::new(), app_pid, ollama_pid: ollama_pid.map(Pid::from_u32) };
+        sampler.refresh();
+        sampler
+    }
+
+    fn refresh(&mut self) {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        let pids: Vec<Pid> = self.app_pid.into_iter().chain(self.ollama_pid).collect();
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&pids),
+            true,
+            ProcessRefreshKind::new().with_cpu().with_memory(),
+        );
+    }
+
+    fn process(&self, pid: Option<Pid>) -> Option<ProcessLoad> {
+        let process = self.system.process(pid?)?;
+        Some(ProcessLoad { cpu_percent: process.cpu_usage(), memory_bytes: process.memory() })
+    }
+}
+
+impl LoadSampler for SysinfoSampler {
+    fn sample(&mut self) -> SystemLoad {
+        self.refresh();
+        let total = self.system.total_memory();
+        // sysinfo reports zeroes rather than errors when it can't read the system
+        SystemLoad {
+            cpu_percent: Some(self.system.global_cpu_usage()).filter(|_| !self.system.cpus().is_empty()),
+            memory_used_bytes: Some(self.system.used_memory()).filter(|_| total > 0),
+            memory_total_bytes: Some(total).filter(|&t| t > 0),
+            app: self.process(self.app_pid),
+            ollama: self.process(self.ollama_pid),
+        }
+    }
+}
+
+/// Running peak and sum of one process's samples.
+#[derive(Debug, Default)]
+struct ProcessAccumulator {
+    samples: u32,
+    peak_cpu: f32,
+    sum_cpu: f64,
+    peak_memory: u64,
+    sum_memory: u128,
+}
+
+impl ProcessAccumulator {
+    fn add(&mut self, load: &ProcessLoad) {
+        self.samples += 1;
+        self.peak_cpu = self.peak_cpu.max(load.cpu_percent);
+        self.sum_cpu += load.cpu_percent as f64;
+        self.peak_memory = self.peak_memory.max(load.memory_bytes);
+        self.sum_memory += load.memory_bytes as u128;
+    }
+
+    fn finish(self) -> Option<ProcessUsage> {
+        if self.samples == 0 {
+            return None;
+        }
+        Some(ProcessUsage {
+            samples: self.samples,
+            peak_cpu_percent: self.peak_cpu,
+            avg_cpu_percent: (self.sum_cpu / self.samples as f64) as f32,
+            peak_memory_bytes: self.peak_memory,
+            avg_memory_bytes: (self.sum_memory / self.samples as u128) as u64,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct UsageAccumulator {
+    samples: u32,
+    app: ProcessAccumulator,
+    ollama: ProcessAccumulator,
+}
+
+impl UsageAccumulator {
+    fn add(&mut self, load: &SystemLoad) {
+        self.samples += 1;
+        if let Some(app) = &load.app {
+            self.app.add(app);
+        }
+        if let Some(ollama) = &load.ollama {
+            self.ollama.add(ollama);
+        }
+    }
+
+    fn finish(self) -> Option<ResourceUsage> {
+        if self.samples == 0 {
+            return None;
+        }
+        Some(ResourceUsage { samples: self.samples, app: self.app.finish(), ollama: self.ollama.finish() })
+    }
+}
+
+/// Merge two runs' usage: peaks are the larger, averages are weighted by sample count.
+pub fn combine(a: Option<&ResourceUsage>, b: Option<&ResourceUsage>) -> Option<ResourceUsage> {
+    fn process(a: Option<&ProcessUsage>, b: Option<&ProcessUsage>) -> Option<ProcessUsage> {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (one, other) => return one.or(other).cloned(),
+        };
+        let samples = a.samples + b.samples;
+        let weighted = |x: f64, y: f64| (x * a.samples as f64 + y * b.samples as f64) / samples as f64;
+        Some(ProcessUsage {
+            samples,
+            peak_cpu_percent: a.peak_cpu_percent.max(b.peak_cpu_percent),
+            avg_cpu_percent: weighted(a.avg_cpu_percent as f64, b.avg_cpu_percent as f64) as f32,
+            peak_memory_bytes: a.peak_memory_bytes.max(b.peak_memory_bytes),
+            avg_memory_bytes: weighted(a.avg_memory_bytes as f64, b.avg_memory_bytes as f64) as u64,
+        })
+    }
+
+    let (a, b) = match (a, b) {
+        (Some(a), Some(b)) => (a, b),
+        (one, other) => return one.or(other).cloned(),
+    };
+    Some(ResourceUsage {
+        samples: a.samples + b.samples,
+        app: process(a.app.as_ref(), b.app.as_ref()),
+        ollama: process(a.ollama.as_ref(), b.ollama.as_ref()),
+    })
+}
+
+/// A sampler running on its own thread until `stop`.
+pub struct RunningSampler {
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<Option<ResourceUsage>>,
+}
+
+impl RunningSampler {
+    /// Take a sample every `interval` until stopped.
+    pub fn start(mut sampler: Box<dyn LoadSampler>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut usage = UsageAccumulator::default();
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                usage.add(&sampler.sample());
+            }
+            usage.finish()
+        });
+        RunningSampler { stop, handle }
+    }
+
+    /// Stop sampling and return the peak/average usage. None if no sample was taken
+    /// (the run was shorter than the interval) or the sampler panicked.
+    pub fn stop(self) -> Option<ResourceUsage> {
+        let _ = self.stop.send(());
+        self.handle
+            .join()
+            .map_err(|_| warn!("Load sampler panicked; analysis resource usage is unavailable"))
+            .ok()
+            .flatten()
+    }
+}
+
+/// Await `run` while `sampler` samples machine load, stopping it as soon as `run`
+/// finishes. With no sampler (cloud providers) nothing is sampled.
+pub async fn sampled<F: Future>(
+    sampler: Option<Box<dyn LoadSampler>>,
+    interval: Duration,
+    run: F,
+) -> (F::Output, Option<ResourceUsage>) {
+    let running = sampler.map(|sampler| RunningSampler::start(sampler, interval));
+    let output = run.await;
+    (output, running.and_then(RunningSampler::stop))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const INTERVAL: Duration = Duration::from_millis(5);
+
+    /// Reports the same load every time and counts its samples.
+    struct FakeSampler {
+        load: SystemLoad,
+        count: Arc<AtomicUsize>,
+        panic: bool,
+    }
+
+    impl LoadSampler for FakeSampler {
+        fn sample(&mut self) -> SystemLoad {
+            if self.panic {
+                panic!("sysinfo blew up");
+            }
+            self.count.fetch_add(1, Ordering::SeqCst);
+            self.load.clone()
+        }
+    }
+
+    fn load(app_cpu: f32, ollama: Option<(f32, u64)>) -> SystemLoad {
+        SystemLoad {
+            cpu_percent: Some(50.0),
+            memory_used_bytes: Some(8 << 30),
+            memory_total_bytes: Some(16 << 30),
+            app: Some(ProcessLoad { cpu_percent: app_cpu, memory_bytes: 100 << 20 }),
+            ollama: ollama.map(|(cpu_percent, memory_bytes)| ProcessLoad { cpu_percent, memory_bytes }),
+        }
+    }
+
+    fn fake(load: SystemLoad, panic: bool) -> (Box<dyn LoadSampler>, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        (Box::new(FakeSampler { load, count: Arc::clone(&count), panic }), count)
+    }
+
+    #[tokio::test]
+    async fn test_sampling_stops_when_analysis_ends() {
+        let (sampler, count) = fake(load(10.0, Some((200.0, 4 << 30))), false);
+        let (processed, usage) = sampled(Some(sampler), INTERVAL, async {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            7
+        })
+        .await;
+        assert_eq!(processed, 7);
+
+        let usage = usage.expect("samples were taken during the run");
+        let taken = count.load(Ordering::SeqCst);
+        assert!(taken > 0);
+        assert_eq!(usage.samples as usize, taken);
+        let ollama = usage.ollama.unwrap();
+        assert_eq!(ollama.peak_cpu_percent, 200.0);
+        assert_eq!(ollama.avg_memory_bytes, 4 << 30);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(count.load(Ordering::SeqCst), taken, "no samples after the run ended");
+    }
+
+    #[tokio::test]
+    async fn test_no_sampler_for_cloud_providers() {
+        let (result, usage) = sampled(None, INTERVAL, async { Err::<u32, _>("API error") }).await;
+        assert!(result.is_err());
+        assert!(usage.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sampler_failures_degrade_to_nulls() {
+        // Nothing readable: the run is still counted but per-process usage is null
+        let blank = SystemLoad { cpu_percent: None, memory_used_bytes: None, memory_total_bytes: None, app: None, ollama: None };
+        let (sampler, _) = fake(blank, false);
+        let (_, usage) = sampled(Some(sampler), INTERVAL, tokio::time::sleep(Duration::from_millis(30))).await;
+        let usage = usage.unwrap();
+        assert!(usage.app.is_none());
+        assert!(usage.ollama.is_none());
+
+        // A panicking sampler doesn't take the analysis down with it
+        let (sampler, _) = fake(load(10.0, None), true);
+        let (processed, usage) = sampled(Some(sampler), INTERVAL, async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            3
+        })
+        .await;
+        assert_eq!(processed, 3);
+        assert!(usage.is_none());
+    }
+
+    #[test]
+    fn test_accumulate_and_combine() {
+        let mut acc = UsageAccumulator::default();
+        acc.add(&load(10.0, Some((100.0, 2000))));
+        acc.add(&load(30.0, None));
+        let first = acc.finish().unwrap();
+        assert_eq!(first.samples, 2);
+        let app = first.app.as_ref().unwrap();
+        assert_eq!((app.peak_cpu_percent, app.avg_cpu_percent), (30.0, 20.0));
+        assert_eq!(first.ollama.as_ref().unwrap().samples, 1);
+
+        let mut acc = UsageAccumulator::default();
+        for _ in 0..3 {
+            acc.add(&load(40.0, Some((300.0, 1000))));
+        }
+        let second = acc.finish().unwrap();
+
+        let total = combine(Some(&first), Some(&second)).unwrap();
+        assert_eq!(total.samples, 5);
+        let app = total.app.unwrap();
+        assert_eq!(app.peak_cpu_percent, 40.0);
+        assert_eq!(app.avg_cpu_percent, 32.0);
+        let ollama = total.ollama.unwrap();
+        assert_eq!(ollama.samples, 4);
+        assert_eq!(ollama.avg_cpu_percent, 250.0);
+        assert_eq!(ollama.peak_memory_bytes, 2000);
+        assert_eq!(ollama.avg_memory_bytes, 1250);
+
+        assert_eq!(combine(None, Some(&second)), Some(second));
+        assert_eq!(combine(None, None), None);
+        assert!(UsageAccumulator::default().finish().is_none());
+    }
+}
@@ -0,0 +1,845 @@
+//! Timestamps and calendar days. Everything stored is UTC; this module parses what's
+//! stored, formats new timestamps, and turns the user's local days and weeks (in the
+//! `timezone` setting's zone) into UTC bounds for queries.
+//!
+//! There is no time zone database dependency: zones are POSIX TZ rules
+//! (`CET-1CEST,M3.5.0,M10.5.0/3`), and named zones (`Europe/Berlin`, the system zone)
+//! are read from the rule at the end of their TZif file in the system's zoneinfo. On
+//! Windows, which has no zoneinfo, the system zone comes from `GetTimeZoneInformation`.
+
+use std::path::Path;
+use std::sync::Once;
+use std::time::SystemTime;
+
+pub const SECS_PER_DAY: i64 = 86_400;
+
+/// Where named zones are looked up (Linux, macOS).
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// The system zone's TZif file on Linux and macOS.
+const LOCALTIME_FILE: &str = "/etc/localtime";
+
+/// Default time of day of a POSIX rule's transitions, 02:00.
+const DEFAULT_TRANSITION_SECS: i32 = 2 * 3600;
+
+/// Seconds since the Unix epoch; 0 for times before it.
+pub fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+/// Convert days since Unix epoch to (year, month, day).
+/// Algorithm based on Howard Hinnant's civil_from_days.
+pub fn days_to_ymd(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = if m <= 2 { y + 1 } else { y };
+    (y, m as u32, d as u32)
+}
+
+/// Days since Unix epoch of a (year, month, day); the inverse of `days_to_ymd`
+/// (Hinnant's days_from_civil).
+pub fn ymd_to_days(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Day of the week of a day since the epoch, Monday = 0 (1970-01-01 was a Thursday).
+pub fn weekday(days: i64) -> u32 {
+    (days + 3).rem_euclid(7) as u32
+}
+
+/// ISO 8601 (year, week) of a day since the epoch: weeks start on Monday and week 1 is
+/// the one holding the year's first Thursday.
+pub fn iso_week(days: i64) -> (i64, u32) {
+    let thursday = days - weekday(days) as i64 + 3;
+    let (year, _, _) = days_to_ymd(thursday);
+    (year, ((thursday - ymd_to_days(year, 1, 1)) / 7 + 1) as u32)
+}
+
+/// The Monday (day since the epoch) of ISO week `YYYY-Www`. None if it's malformed or
+/// the year has no such week.
+pub fn parse_iso_week(value: &str) -> Option<i64> {
+    let (year, week) = value.split_once("-W")?;
+    if year.len() != 4 || week.len() != 2 {
+        return None;
+    }
+    let (year, week): (i64, u32) = (digits(year)?, digits(week)?);
+    // Week 1 holds January 4th
+    let jan4 = ymd_to_days(year, 1, 4);
+    let monday = jan4 - weekday(jan4) as i64 + (week as i64 - 1) * 7;
+    (week >= 1 && iso_week(monday) == (year, week)).then_some(monday)
+}
+
+/// `YYYY-MM-DDTHH:MM:SS`, the database format, for seconds since the epoch (UTC).
+pub fn format_db(secs: i64) -> String {
+    let (year, month, day) = days_to_ymd(secs.div_euclid(SECS_PER_DAY));
+    let time_of_day = secs.rem_euclid(SECS_PER_DAY);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, time_of_day / 3600, (time_of_day % 3600) / 60, time_of_day % 60
+    )
+}
+
+/// `YYYY-MM-DD` of a day since the epoch.
+pub fn format_date(days: i64) -> String {
+    let (year, month, day) = days_to_ymd(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format a SystemTime as an ISO 8601 string for database storage.
+pub fn format_for_db(time: SystemTime) -> String {
+    format_db(unix_secs(time))
+}
+
+/// Format a SystemTime as an ISO 8601 string suitable for filenames.
+/// Uses hyphens instead of colons so the filename is valid on all platforms.
+pub fn format_for_filename(time: SystemTime) -> String {
+    format_db(unix_secs(time)).replace(':', "-")
+}
+
+/// `format_for_filename` plus milliseconds, for screenshot files: two captures within
+/// the same second (and so the same `capture_group`) get distinct names.
+pub fn format_for_screenshot(time: SystemTime) -> String {
+    let millis = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().subsec_millis();
+    format!("{}-{:03}", format_for_filename(time), millis)
+}
+
+/// Day since the epoch of a `YYYY-MM-DD` date. None if it's malformed or no such day.
+pub fn parse_date(value: &str) -> Option<i64> {
+    let bytes = value.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let year: i64 = digits(&value[..4])?;
+    let month: u32 = digits(&value[5..7])?;
+    let day: u32 = digits(&value[8..10])?;
+    if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(ymd_to_days(year, month, day))
+}
+
+/// Seconds since the epoch of a stored or user-supplied timestamp: a bare date
+/// (midnight), the naive `YYYY-MM-DDTHH:MM[:SS[.fff]]` the database holds (UTC), or
+/// RFC 3339 with `Z` or a `±HH:MM` offset. A space may stand in for the `T`. Fractions
+/// of a second are dropped.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let days = parse_date(value.get(..10)?)?;
+    let rest = &value[10..];
+    if rest.is_empty() {
+        return Some(days * SECS_PER_DAY);
+    }
+    let rest = rest.strip_prefix(['T', 't', ' '])?;
+    let zone_at = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+    let (clock, zone) = rest.split_at(zone_at);
+    let clock = clock.split('.').next().unwrap_or_default();
+    let mut parts = clock.split(':');
+    let hours: i64 = digits(parts.next()?)?;
+    let minutes: i64 = digits(parts.next()?)?;
+    let seconds: i64 = parts.next().map(digits).unwrap_or(Some(0))?;
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let offset = match zone {
+        "" | "Z" | "z" => 0,
+        _ => parse_utc_offset(zone)?,
+    };
+    Some(days * SECS_PER_DAY + hours * 3600 + minutes * 60 + seconds - offset as i64)
+}
+
+/// Seconds east of UTC of `±HH:MM` (or `±HHMM`, `±HH`).
+fn parse_utc_offset(value: &str) -> Option<i32> {
+    let sign = match value.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let value = &value[1..];
+    let (hours, minutes) = match value.len() {
+        2 => (value, "0"),
+        4 => (&value[..2], &value[2..]),
+        5 if value.as_bytes()[2] == b':' => (&value[..2], &value[3..]),
+        _ => return None,
+    };
+    let (hours, minutes): (i32, i32) = (digits(hours)?, digits(minutes)?);
+    (hours <= 23 && minutes <= 59).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// An unsigned decimal made only of ASCII digits (no sign, no spaces).
+fn digits<T: std::str::FromStr>(value: &str) -> Option<T> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// A time zone: a fixed offset, or standard time with optional yearly daylight saving
+/// as a POSIX TZ rule describes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeZone {
+    /// Seconds east of UTC outside daylight saving.
+    std_offset: i32,
+    dst: Option<DaylightSaving>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DaylightSaving {
+    /// Seconds east of UTC during daylight saving.
+    offset: i32,
+    /// Starts at this local standard time...
+    start: TransitionDay,
+    start_time: i32,
+    /// ...and ends at this local daylight time.
+    end: TransitionDay,
+    end_time: i32,
+}
+
+/// The day of a year a POSIX rule changes on.
+#[derive(Debug, Clone, PartialEq)]
+enum TransitionDay {
+    /// `Jn`: day 1–365, February 29 never counted.
+    JulianNoLeap(u32),
+    /// `n`: day 0–365, February 29 counted.
+    Julian(u32),
+    /// `Mm.w.d`: weekday `d` (0 = Sunday) of week `w` (5 = last) of month `m`.
+    MonthWeekDay { month: u32, week: u32, weekday: u32 },
+}
+
+impl TransitionDay {
+    /// Day since the epoch it falls on in `year`.
+    fn in_year(&self, year: i64) -> i64 {
+        let jan1 = ymd_to_days(year, 1, 1);
+        match *self {
+            TransitionDay::JulianNoLeap(n) => jan1 + n as i64 - 1 + (is_leap(year) && n >= 60) as i64,
+            TransitionDay::Julian(n) => jan1 + n as i64,
+            TransitionDay::MonthWeekDay { month, week, weekday } => {
+                let first = ymd_to_days(year, month, 1);
+                // Sunday-based, as in the rule
+                let first_weekday = (first + 4).rem_euclid(7) as u32;
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+                while day > days_in_month(year, month) {
+                    day -= 7;
+                }
+                first + day as i64 - 1
+            }
+        }
+    }
+}
+
+impl TimeZone {
+    pub const UTC: TimeZone = TimeZone { std_offset: 0, dst: None };
+
+    /// A zone `offset` seconds east of UTC all year.
+    pub fn fixed(offset: i32) -> Self {
+        TimeZone { std_offset: offset, dst: None }
+    }
+
+    /// The zone a `timezone` setting names: `system`, `UTC`, a `±HH:MM` offset, a zone
+    /// name from the system's zoneinfo (`Europe/Berlin`) or a POSIX TZ rule.
+    pub fn from_setting(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("system") {
+            return Ok(Self::system());
+        }
+        if value.eq_ignore_ascii_case("utc") {
+            return Ok(Self::UTC);
+        }
+        if let Some(offset) = parse_utc_offset(value) {
+            return Ok(Self::fixed(offset));
+        }
+        // POSIX rules only have a '/' in their transitions, after a ','
+        if value.contains('/') && !value.contains(',') {
+            return Self::named(value);
+        }
+        Self::parse_posix(value)
+    }
+
+    /// The system zone (`detect_system`), or UTC with a warning logged once if it can't
+    /// be read.
+    pub fn system() -> Self {
+        Self::detect_system().unwrap_or_else(|| {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| log::warn!("Could not read the system time zone; local days are UTC"));
+            Self::UTC
+        })
+    }
+
+    /// The system zone: `TZ` if set and readable, else `/etc/localtime` (Windows: the
+    /// zone `GetTimeZoneInformation` reports). None if neither can be read.
+    pub fn detect_system() -> Option<Self> {
+        if let Ok(tz) = std::env::var("TZ") {
+            let tz = tz.trim_start_matches(':');
+            let parsed = if tz.starts_with('/') {
+                Self::from_tzif_file(Path::new(tz))
+            } else {
+                Self::named(tz).or_else(|_| Self::parse_posix(tz))
+            };
+            if let Ok(zone) = parsed {
+                return Some(zone);
+            }
+        }
+        Self::platform_zone()
+    }
+
+    #[cfg(not(windows))]
+    fn platform_zone() -> Option<Self> {
+        Self::from_tzif_file(Path::new(LOCALTIME_FILE)).ok()
+    }
+
+    #[cfg(windows)]
+    fn platform_zone() -> Option<Self> {
+        use windows_sys::Win32::Foundation::SYSTEMTIME;
+        use windows_sys::Win32::System::Time::{GetTimeZoneInformation, TIME_ZONE_ID_INVALID, TIME_ZONE_INFORMATION};
+
+        // SAFETY: a zeroed TIME_ZONE_INFORMATION is valid, and the call only writes to it
+        let mut info: TIME_ZONE_INFORMATION = unsafe { std::mem::zeroed() };
+        if unsafe { GetTimeZoneInformation(&mut info) } == TIME_ZONE_ID_INVALID {
+            return None;
+        }
+        let transition = |t: &SYSTEMTIME| WindowsTransition {
+            year: t.wYear,
+            month: t.wMonth,
+            week: t.wDay,
+            weekday: t.wDayOfWeek,
+            time: t.wHour as i32 * 3600 + t.wMinute as i32 * 60 + t.wSecond as i32,
+        };
+        Some(Self::from_windows(
+            info.Bias,
+            info.StandardBias,
+            info.DaylightBias,
+            &transition(&info.StandardDate),
+            &transition(&info.DaylightDate),
+        ))
+    }
+
+    /// A zone from what `GetTimeZoneInformation` reports: biases in minutes west of UTC,
+    /// and the days daylight saving starts (at local standard time) and ends (at local
+    /// daylight time), as POSIX rules have them. Without recurring transitions (no
+    /// daylight saving, or only this year's absolute dates) the zone is fixed.
+    #[cfg(any(windows, test))]
+    fn from_windows(
+        bias: i32,
+        standard_bias: i32,
+        daylight_bias: i32,
+        standard: &WindowsTransition,
+        daylight: &WindowsTransition,
+    ) -> Self {
+        let std_offset = -(bias + standard_bias) * 60;
+        match (daylight.recurring_day(), standard.recurring_day()) {
+            (Some(start), Some(end)) => TimeZone {
+                std_offset,
+                dst: Some(DaylightSaving {
+                    offset: -(bias + daylight_bias) * 60,
+                    start,
+                    start_time: daylight.time,
+                    end,
+                    end_time: standard.time,
+                }),
+            },
+            _ => Self::fixed(std_offset),
+        }
+    }
+
+    /// A zone from the system's zoneinfo by name.
+    fn named(name: &str) -> Result<Self, String> {
+        if name.is_empty() || name.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(format!("Invalid time zone name '{}'", name));
+        }
+        Self::from_tzif_file(&Path::new(ZONEINFO_DIR).join(name))
+            .map_err(|e| format!("Unknown time zone '{}': {}", name, e))
+    }
+
+    fn from_tzif_file(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let rule = tzif_footer(&bytes).ok_or_else(|| "no POSIX rule in the zone file".to_string())?;
+        Self::parse_posix(rule)
+    }
+
+    /// Parse a POSIX TZ rule: `std offset [dst [offset] [,start[/time],end[/time]]]`.
+    /// Offsets are hours west of UTC, as POSIX has them (`EST5EDT`); daylight time
+    /// defaults to an hour ahead of standard time, and a daylight zone without
+    /// transition rules uses the US ones.
+    pub fn parse_posix(rule: &str) -> Result<Self, String> {
+        let invalid = |what: &str| format!("Invalid time zone rule '{}': {}", rule, what);
+        let mut rest = rule;
+        skip_zone_name(&mut rest).ok_or_else(|| invalid("expected a zone name"))?;
+        let std_offset = -take_posix_time(&mut rest).ok_or_else(|| invalid("expected a UTC offset"))?;
+        if rest.is_empty() {
+            return Ok(Self::fixed(std_offset));
+        }
+        skip_zone_name(&mut rest).ok_or_else(|| invalid("expected a daylight saving zone name"))?;
+        let dst_offset = if rest.starts_with(',') || rest.is_empty() {
+            std_offset + 3600
+        } else {
+            -take_posix_time(&mut rest).ok_or_else(|| invalid("bad daylight saving offset"))?
+        };
+        let rules = if rest.is_empty() { ",M3.2.0,M11.1.0" } else { rest };
+        let mut rules = rules.strip_prefix(',').ok_or_else(|| invalid("expected ',' before the rules"))?.split(',');
+        let (start, start_time) = rules.next().and_then(parse_transition).ok_or_else(|| invalid("bad start rule"))?;
+        let (end, end_time) = rules.next().and_then(parse_transition).ok_or_else(|| invalid("bad end rule"))?;
+        if rules.next().is_some() {
+            return Err(invalid("unexpected text after the end rule"));
+        }
+        Ok(TimeZone {
+            std_offset,
+            dst: Some(DaylightSaving { offset: dst_offset, start, start_time, end, end_time }),
+        })
+    }
+
+    /// Seconds east of UTC at instant `utc` (seconds since the epoch).
+    pub fn offset_at(&self, utc: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let (year, _, _) = days_to_ymd((utc + self.std_offset as i64).div_euclid(SECS_PER_DAY));
+        let start = dst.start.in_year(year) * SECS_PER_DAY + dst.start_time as i64 - self.std_offset as i64;
+        let end = dst.end.in_year(year) * SECS_PER_DAY + dst.end_time as i64 - dst.offset as i64;
+        let in_dst = if start < end {
+            start <= utc && utc < end
+        } else {
+            // Southern hemisphere: daylight saving spans the new year
+            utc < end || start <= utc
+        };
+        if in_dst { dst.offset } else { self.std_offset }
+    }
+
+    /// Local wall-clock seconds (as if the local time were UTC) at instant `utc`.
+    pub fn to_local(&self, utc: i64) -> i64 {
+        utc + self.offset_at(utc) as i64
+    }
+
+    /// The first instant whose local time is `local` or later. A time repeated when
+    /// clocks go back is its first occurrence; one skipped when they go forward is the
+    /// moment of the change.
+    pub fn to_utc(&self, local: i64) -> i64 {
+        let offsets = match &self.dst {
+            None => return local - self.std_offset as i64,
+            Some(dst) => [self.std_offset.max(dst.offset), self.std_offset.min(dst.offset)],
+        };
+        // The larger offset gives the earlier instant
+        for offset in offsets {
+            let utc = local - offset as i64;
+            if self.offset_at(utc) == offset {
+                return utc;
+            }
+        }
+        // In a gap: find the change between the two candidates
+        let (mut before, mut after) = (local - offsets[0] as i64, local - offsets[1] as i64);
+        while after - before > 1 {
+            let mid = before + (after - before) / 2;
+            if self.to_local(mid) >= local {
+                after = mid;
+            } else {
+                before = mid;
+            }
+        }
+        after
+    }
+
+    /// The local day (days since the epoch) instant `utc` falls on.
+    pub fn local_day(&self, utc: i64) -> i64 {
+        self.to_local(utc).div_euclid(SECS_PER_DAY)
+    }
+
+    /// `[start, end)` in UTC seconds of local day `day`. Not always 24 hours long.
+    pub fn day_bounds(&self, day: i64) -> (i64, i64) {
+        (self.to_utc(day * SECS_PER_DAY), self.to_utc((day + 1) * SECS_PER_DAY))
+    }
+
+    /// `[start, end)` in UTC seconds of the ISO week (Monday to Sunday) holding local day `day`.
+    pub fn week_bounds(&self, day: i64) -> (i64, i64) {
+        let monday = day - weekday(day) as i64;
+        (self.day_bounds(monday).0, self.day_bounds(monday + 7).0)
+    }
+}
+
+/// A daylight saving transition as Windows describes it (a `SYSTEMTIME`): with year 0,
+/// weekday `weekday` (0 = Sunday) of week `week` (5 = last) of `month`, at `time`
+/// seconds into the local day. Month 0 means there is none.
+#[cfg(any(windows, test))]
+struct WindowsTransition {
+    year: u16,
+    month: u16,
+    week: u16,
+    weekday: u16,
+    time: i32,
+}
+
+#[cfg(any(windows, test))]
+impl WindowsTransition {
+    fn recurring_day(&self) -> Option<TransitionDay> {
+        let valid = self.year == 0 && (1..=12).contains(&self.month) && (1..=5).contains(&self.week) && self.weekday <= 6;
+        valid.then_some(TransitionDay::MonthWeekDay {
+            month: self.month as u32,
+            week: self.week as u32,
+            weekday: self.weekday as u32,
+        })
+    }
+}
+
+/// The POSIX rule at the end of a version 2+ TZif file (`\n<rule>\n`). None for
+/// version 1 files and empty rules.
+fn tzif_footer(bytes: &[u8]) -> Option<&str> {
+    if bytes.len() < 5 || &bytes[..4] != b"TZif" || bytes[4] < b'2' {
+        return None;
+    }
+    let text = bytes.strip_suffix(b"\n")?;
+    let start = text.iter().rposition(|&b| b == b'\n')? + 1;
+    std::str::from_utf8(&text[start..]).ok().filter(|rule| !rule.is_empty())
+}
+
+/// Skip a zone name: three or more letters, or anything in `<...>`.
+fn skip_zone_name(rest: &mut &str) -> Option<()> {
+    let len = if let Some(quoted) = rest.strip_prefix('<') {
+        quoted.find('>')? + 2
+    } else {
+        let letters = rest.bytes().take_while(u8::is_ascii_alphabetic).count();
+        if letters < 3 {
+            return None;
+        }
+        letters
+    };
+    *rest = &rest[len..];
+    Some(())
+}
+
+/// Take `[+-]h[h][:mm[:ss]]` off the front of `rest`, in seconds.
+fn take_posix_time(rest: &mut &str) -> Option<i32> {
+    let len = rest.find(|c: char| !(c.is_ascii_digit() || c == ':' || c == '+' || c == '-')).unwrap_or(rest.len());
+    let (time, tail) = rest.split_at(len);
+    let seconds = parse_posix_time(time)?;
+    *rest = tail;
+    Some(seconds)
+}
+
+/// `[+-]h[hh][:mm[:ss]]` in seconds; hours up to 167, as TZif footers allow.
+fn parse_posix_time(time: &str) -> Option<i32> {
+    let (sign, time) = match time.as_bytes().first()? {
+        b'-' => (-1, &time[1..]),
+        b'+' => (1, &time[1..]),
+        _ => (1, time),
+    };
+    let mut parts = time.split(':');
+    let hours: i32 = digits(parts.next()?)?;
+    let minutes: i32 = parts.next().map(digits).unwrap_or(Some(0))?;
+    let seconds: i32 = parts.next().map(digits).unwrap_or(Some(0))?;
+    if parts.next().is_some() || hours > 167 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// One transition of a POSIX rule: `Jn`, `n` or `Mm.w.d`, optionally `/time`.
+fn parse_transition(value: &str) -> Option<(TransitionDay, i32)> {
+    let (day, time) = match value.split_once('/') {
+        Some((day, time)) => (day, parse_posix_time(time)?),
+        None => (value, DEFAULT_TRANSITION_SECS),
+    };
+    let day = if let Some(n) = day.strip_prefix('J') {
+        let n: u32 = digits(n)?;
+        (1..=365).contains(&n).then_some(TransitionDay::JulianNoLeap(n))?
+    } else if let Some(mwd) = day.strip_prefix('M') {
+        let mut parts = mwd.split('.');
+        let month: u32 = digits(parts.next()?)?;
+        let week: u32 = digits(parts.next()?)?;
+        let weekday: u32 = digits(parts.next()?)?;
+        if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        TransitionDay::MonthWeekDay { month, week, weekday }
+    } else {
+        let n: u32 = digits(day)?;
+        (n <= 365).then_some(TransitionDay::Julian(n))?
+    };
+    Some((day, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const NEW_YORK: &str = "EST5EDT,M3.2.0,M11.1.0";
+    const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const SANTIAGO: &str = "<-04>4<-03>,M9.1.6/24,M4.1.6/24";
+    const SYDNEY: &str = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+
+    fn ts(value: &str) -> i64 {
+        parse_timestamp(value).unwrap()
+    }
+
+    fn zone(rule: &str) -> TimeZone {
+        TimeZone::parse_posix(rule).unwrap()
+    }
+
+    fn bounds((start, end): (i64, i64)) -> (String, String) {
+        (format_db(start), format_db(end))
+    }
+
+    #[test]
+    fn test_format_timestamp_for_filename() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        let result = format_for_filename(epoch);
+        assert_eq!(result, "1970-01-01T00-00-00");
+        let later = SystemTime::UNIX_EPOCH + Duration::from_millis(1_735_725_600_120);
+        assert_eq!(format_for_screenshot(later), "2025-01-01T10-00-00-120");
+    }
+
+    #[test]
+    fn test_format_timestamp_for_db() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        let result = format_for_db(epoch);
+        assert_eq!(result, "1970-01-01T00:00:00");
+        assert_eq!(format_db(-1), "1969-12-31T23:59:59");
+    }
+
+    #[test]
+    fn test_days_to_ymd() {
+        assert_eq!(days_to_ymd(0), (1970, 1, 1));
+        assert_eq!(days_to_ymd(365), (1971, 1, 1));
+        assert_eq!(days_to_ymd(18262), (2020, 1, 1));
+        assert_eq!(days_to_ymd(-1), (1969, 12, 31));
+        // Round trip across leap days and centuries
+        for days in (-800_000..800_000).step_by(997) {
+            let (y, m, d) = days_to_ymd(days);
+            assert_eq!(ymd_to_days(y, m, d), days);
+        }
+        assert_eq!(ymd_to_days(2024, 2, 29) + 1, ymd_to_days(2024, 3, 1));
+    }
+
+    #[test]
+    fn test_weekday_and_iso_week() {
+        assert_eq!(weekday(0), 3);
+        assert_eq!(weekday(ymd_to_days(2025, 1, 6)), 0);
+        assert_eq!(iso_week(ymd_to_days(2025, 1, 1)), (2025, 1));
+        // Belongs to the last week of the previous year, or the first of the next
+        assert_eq!(iso_week(ymd_to_days(2021, 1, 3)), (2020, 53));
+        assert_eq!(iso_week(ymd_to_days(2024, 12, 30)), (2025, 1));
+        assert_eq!(iso_week(ymd_to_days(2026, 12, 31)), (2026, 53));
+
+        assert_eq!(parse_iso_week("2025-W01"), Some(ymd_to_days(2024, 12, 30)));
+        assert_eq!(parse_iso_week("2020-W53"), Some(ymd_to_days(2020, 12, 28)));
+        assert_eq!(parse_iso_week("2025-W53"), None);
+        assert_eq!(parse_iso_week("2025-W00"), None);
+        assert_eq!(parse_iso_week("2025-W1"), None);
+        assert_eq!(parse_iso_week("2025-01-01"), None);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-02"), Some(1));
+        assert_eq!(parse_date("2024-02-29"), Some(ymd_to_days(2024, 2, 29)));
+        assert_eq!(parse_date("2025-02-29"), None);
+        assert_eq!(parse_date("2025-13-01"), None);
+        assert_eq!(parse_date("2025-1-01"), None);
+        assert_eq!(parse_date("2025-01-01T00:00:00"), None);
+        assert_eq!(parse_date("+025-01-01"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp_formats() {
+        let expected = ymd_to_days(2025, 1, 1) * SECS_PER_DAY + 10 * 3600;
+        // The naive format the database has always stored is UTC
+        assert_eq!(parse_timestamp("2025-01-01T10:00:00"), Some(expected));
+        assert_eq!(parse_timestamp("2025-01-01 10:00"), Some(expected));
+        assert_eq!(parse_timestamp("2025-01-01T10:00:00.750Z"), Some(expected));
+        assert_eq!(parse_timestamp("2025-01-01T12:00:00+02:00"), Some(expected));
+        assert_eq!(parse_timestamp("2025-01-01T05:00:00-05:00"), Some(expected));
+        assert_eq!(parse_timestamp("2025-01-01T15:30:00+0530"), Some(expected));
+        // A negative offset can move the instant to the next UTC day
+        assert_eq!(format_db(ts("2025-12-31T22:00:00-03:00")), "2026-01-01T01:00:00");
+        assert_eq!(parse_timestamp("2025-01-01"), Some(expected - 10 * 3600));
+
+        for bad in ["", "2025-01-01T", "2025-01-01T25:00:00", "2025-01-01T10", "2025-01-01T10:00:00+2", "2025-01-01X10:00", "yesterday"] {
+            assert_eq!(parse_timestamp(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_posix_rules() {
+        assert_eq!(zone("UTC0"), TimeZone::UTC);
+        assert_eq!(zone("<+0530>-5:30"), TimeZone::fixed(5 * 3600 + 1800));
+        assert_eq!(zone("<-03>3"), TimeZone::fixed(-3 * 3600));
+        let new_york = zone(NEW_YORK);
+        assert_eq!(new_york.std_offset, -5 * 3600);
+        assert_eq!(new_york.dst.as_ref().unwrap().offset, -4 * 3600);
+        // No rules: the US ones
+        assert_eq!(zone("EST5EDT"), new_york);
+        assert_eq!(zone(SANTIAGO).dst.unwrap().start_time, 24 * 3600);
+
+        for bad in ["", "5", "E5", "EST", "EST5EDT,M3.2.0", "EST5EDT,M13.2.0,M11.1.0", "EST5EDT,M3.6.0,M11.1.0", "EST5EDT,J0,J100", "EST5EDT,M3.2.0,M11.1.0,x", "<EST5"] {
+            assert!(TimeZone::parse_posix(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_from_setting() {
+        assert_eq!(TimeZone::from_setting("UTC").unwrap(), TimeZone::UTC);
+        assert_eq!(TimeZone::from_setting(" +05:30 ").unwrap(), TimeZone::fixed(19_800));
+        assert_eq!(TimeZone::from_setting("-03:00").unwrap(), TimeZone::fixed(-10_800));
+        assert_eq!(TimeZone::from_setting(BERLIN).unwrap(), zone(BERLIN));
+        assert!(TimeZone::from_setting("system").is_ok());
+        assert!(TimeZone::from_setting("America/New_York").is_ok() || !Path::new(ZONEINFO_DIR).exists());
+        assert!(TimeZone::from_setting("Nowhere/../../etc/passwd").is_err());
+        assert!(TimeZone::from_setting("Nowhere/Atlantis").is_err());
+        assert!(TimeZone::from_setting("+25:00").is_err());
+    }
+
+    #[test]
+    fn test_tzif_footer() {
+        let mut file = b"TZif2".to_vec();
+        file.extend_from_slice(&[0; 40]);
+        file.extend_from_slice(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+        assert_eq!(tzif_footer(&file), Some(BERLIN));
+        file[4] = 0;
+        assert_eq!(tzif_footer(&file), None);
+        assert_eq!(tzif_footer(b"TZif3\n\n"), None);
+        assert_eq!(tzif_footer(b"not a zone file\nUTC0\n"), None);
+    }
+
+    #[test]
+    fn test_from_windows() {
+        let at = |month, week, hour| WindowsTransition { year: 0, month, week, weekday: 0, time: hour * 3600 };
+        // W. Europe Standard Time: last Sunday of March at 02:00 to last Sunday of October at 03:00
+        assert_eq!(TimeZone::from_windows(-60, 0, -60, &at(10, 5, 3), &at(3, 5, 2)), zone(BERLIN));
+        assert_eq!(TimeZone::from_windows(300, 0, -60, &at(11, 1, 2), &at(3, 2, 2)), zone(NEW_YORK));
+        // Tokyo has no daylight saving; a zone with only absolute dates is treated the same
+        assert_eq!(TimeZone::from_windows(-540, 0, 0, &at(0, 0, 0), &at(0, 0, 0)), TimeZone::fixed(9 * 3600));
+        let absolute = WindowsTransition { year: 2025, ..at(3, 5, 2) };
+        assert_eq!(TimeZone::from_windows(-60, 0, -60, &at(10, 5, 3), &absolute), TimeZone::fixed(3600));
+    }
+
+    #[test]
+    fn test_transition_days() {
+        // Second Sunday of March, first of November, last of October
+        assert_eq!(format_date(TransitionDay::MonthWeekDay { month: 3, week: 2, weekday: 0 }.in_year(2025)), "2025-03-09");
+        assert_eq!(format_date(TransitionDay::MonthWeekDay { month: 11, week: 1, weekday: 0 }.in_year(2025)), "2025-11-02");
+        assert_eq!(format_date(TransitionDay::MonthWeekDay { month: 10, week: 5, weekday: 0 }.in_year(2025)), "2025-10-26");
+        // J60 is always March 1st; 59 is February 29th in leap years
+        assert_eq!(format_date(TransitionDay::JulianNoLeap(60).in_year(2024)), "2024-03-01");
+        assert_eq!(format_date(TransitionDay::JulianNoLeap(60).in_year(2025)), "2025-03-01");
+        assert_eq!(format_date(TransitionDay::Julian(59).in_year(2024)), "2024-02-29");
+    }
+
+    #[test]
+    fn test_offsets_around_dst_transitions() {
+        let new_york = zone(NEW_YORK);
+        // Clocks go forward at 02:00 EST = 07:00 UTC and back at 02:00 EDT = 06:00 UTC
+        assert_eq!(new_york.offset_at(ts("2025-03-09T06:59:59")), -5 * 3600);
+        assert_eq!(new_york.offset_at(ts("2025-03-09T07:00:00")), -4 * 3600);
+        assert_eq!(new_york.offset_at(ts("2025-11-02T05:59:59")), -4 * 3600);
+        assert_eq!(new_york.offset_at(ts("2025-11-02T06:00:00")), -5 * 3600);
+
+        let berlin = zone(BERLIN);
+        assert_eq!(berlin.offset_at(ts("2025-03-30T00:59:59")), 3600);
+        assert_eq!(berlin.offset_at(ts("2025-03-30T01:00:00")), 7200);
+        assert_eq!(berlin.offset_at(ts("2025-10-26T00:59:59")), 7200);
+        assert_eq!(berlin.offset_at(ts("2025-10-26T01:00:00")), 3600);
+
+        // Southern hemisphere: daylight saving across the new year
+        let sydney = zone(SYDNEY);
+        assert_eq!(sydney.offset_at(ts("2025-01-15T00:00:00")), 11 * 3600);
+        assert_eq!(sydney.offset_at(ts("2025-06-15T00:00:00")), 10 * 3600);
+        assert_eq!(sydney.offset_at(ts("2025-04-05T15:59:59")), 11 * 3600);
+        assert_eq!(sydney.offset_at(ts("2025-04-05T16:00:00")), 10 * 3600);
+        assert_eq!(sydney.offset_at(ts("2025-10-04T15:59:59")), 10 * 3600);
+        assert_eq!(sydney.offset_at(ts("2025-10-04T16:00:00")), 11 * 3600);
+    }
+
+    #[test]
+    fn test_to_utc_in_gaps_and_overlaps() {
+        let new_york = zone(NEW_YORK);
+        let local = |value: &str| ts(value);
+        // 02:30 doesn't exist on the spring-forward day: the change itself is returned
+        assert_eq!(format_db(new_york.to_utc(local("2025-03-09T02:30:00"))), "2025-03-09T07:00:00");
+        // 01:30 happens twice on the fall-back day: the first (EDT) one
+        assert_eq!(format_db(new_york.to_utc(local("2025-11-02T01:30:00"))), "2025-11-02T05:30:00");
+        for value in ["2025-01-01T00:00:00", "2025-07-01T12:00:00", "2025-11-02T03:00:00"] {
+            assert_eq!(format_db(new_york.to_local(new_york.to_utc(local(value)))), value);
+        }
+    }
+
+    #[test]
+    fn test_day_bounds_across_dst() {
+        let new_york = zone(NEW_YORK);
+        let day = |date: &str| parse_date(date).unwrap();
+        assert_eq!(
+            bounds(new_york.day_bounds(day("2025-01-15"))),
+            ("2025-01-15T05:00:00".to_string(), "2025-01-16T05:00:00".to_string())
+        );
+        // 23 and 25 hour days
+        let (start, end) = new_york.day_bounds(day("2025-03-09"));
+        assert_eq!((format_db(start), end - start), ("2025-03-09T05:00:00".to_string(), 23 * 3600));
+        let (start, end) = new_york.day_bounds(day("2025-11-02"));
+        assert_eq!((format_db(start), end - start), ("2025-11-02T04:00:00".to_string(), 25 * 3600));
+
+        // Santiago changes at midnight, so the spring-forward day starts at 01:00 local
+        let santiago = zone(SANTIAGO);
+        let (start, end) = santiago.day_bounds(day("2025-09-07"));
+        assert_eq!(format_db(start), "2025-09-07T04:00:00");
+        assert_eq!(format_db(santiago.to_local(start)), "2025-09-07T01:00:00");
+        assert_eq!(end - start, 23 * 3600);
+        // ...and the fall-back day repeats its last hour
+        let (start, end) = santiago.day_bounds(day("2025-04-05"));
+        assert_eq!(format_db(start), "2025-04-05T03:00:00");
+        assert_eq!(end - start, 25 * 3600);
+    }
+
+    #[test]
+    fn test_local_day_with_negative_offsets() {
+        let sao_paulo = TimeZone::fixed(-3 * 3600);
+        // 01:00 UTC is still the previous evening
+        assert_eq!(format_date(sao_paulo.local_day(ts("2026-01-01T01:00:00"))), "2025-12-31");
+        assert_eq!(format_date(sao_paulo.local_day(ts("2026-01-01T03:00:00"))), "2026-01-01");
+        let honolulu = TimeZone::fixed(-10 * 3600);
+        assert_eq!(
+            bounds(honolulu.day_bounds(parse_date("2025-06-30").unwrap())),
+            ("2025-06-30T10:00:00".to_string(), "2025-07-01T10:00:00".to_string())
+        );
+        // Before the epoch, too
+        assert_eq!(format_date(honolulu.local_day(5 * 3600)), "1969-12-31");
+        let kolkata = TimeZone::fixed(19_800);
+        assert_eq!(format_date(kolkata.local_day(ts("2025-06-30T18:30:00"))), "2025-07-01");
+    }
+
+    #[test]
+    fn test_week_bounds() {
+        let berlin = zone(BERLIN);
+        // The week of 2025-10-26 (a Sunday) runs Monday 20th to Monday 27th and has 169 hours
+        let (start, end) = berlin.week_bounds(parse_date("2025-10-26").unwrap());
+        assert_eq!(format_db(start), "2025-10-19T22:00:00");
+        assert_eq!(format_db(end), "2025-10-26T23:00:00");
+        assert_eq!(end - start, 7 * SECS_PER_DAY + 3600);
+        let utc = TimeZone::UTC;
+        assert_eq!(
+            bounds(utc.week_bounds(parse_date("2025-01-01").unwrap())),
+            ("2024-12-30T00:00:00".to_string(), "2025-01-06T00:00:00".to_string())
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::models::Screenshot;
+use image::codecs::gif::GifEncoder;
+use image::imageops::FilterType;
+use image::{Delay, Frame, Rgba, RgbaImage};
+use log::warn;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+/// Frames are downscaled to at most this width.
+const MAX_WIDTH: u32 = 1280;
+
+#[derive(Error, Debug)]
+pub enum TimelapseError {
+    #[error("Session has no readable screenshots")]
+    NoFrames,
+    #[error("fps must be between 1 and 60")]
+    InvalidFps,
+    #[error("Unsupported output format {0:?}; use .gif, .webp or .mp4")]
+    UnsupportedFormat(String),
+    #[error("ffmpeg is needed for .{0} output but was not found on PATH; save as .gif instead")]
+    FfmpegMissing(String),
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+    #[error("Failed to write timelapse: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to encode timelapse: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Output container, picked from the output path's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelapseFormat {
+    /// Encoded in-process.
+    Gif,
+    /// Animated WebP via ffmpeg; the image crate only encodes still WebP.
+    WebP,
+    /// H.264 via ffmpeg.
+    Mp4,
+}
+
+impl TimelapseFormat {
+    pub fn from_path(path: &Path) -> Result<Self, TimelapseError> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        match ext.as_str() {
+            "gif" => Ok(TimelapseFormat::Gif),
+            "webp" => Ok(TimelapseFormat::WebP),
+            "mp4" => Ok(TimelapseFormat::Mp4),
+            _ => Err(TimelapseError::UnsupportedFormat(ext)),
+        }
+    }
+}
+
+/// Screenshots in playback order: by capture time, then monitor, so a multi-monitor
+/// tick plays left to right rather than in insert order.
+pub fn order_frames(mut screenshots: Vec<Screenshot>) -> Vec<Screenshot> {
+    screenshots.sort_by(|a, b| {
+        (&a.captured_at, a.monitor_index, a.id).cmp(&(&b.captured_at, b.monitor_index, b.id))
+    });
+    screenshots
+}
+
+/// Common frame size for `dims`: the largest frame, downscaled to `MAX_WIDTH` and
+/// rounded down to even dimensions (H.264 requires them).
+pub fn frame_size(dims: &[(u32, u32)]) -> Option<(u32, u32)> {
+    let &(w, h) = dims.iter().max_by_key(|(w, h)| *w as u64 * *h as u64)?;
+    let (w, h) = if w > MAX_WIDTH {
+        (MAX_WIDTH, (h as f64 * MAX_WIDTH as f64 / w as f64).round() as u32)
+    } else {
+        (w, h)
+    };
+    Some(((w & !1).max(2), (h & !1).max(2)))
+}
+
+/// Scale `image` to fit `size` keeping its aspect ratio, centred on black bars.
+pub fn letterbox(image: &RgbaImage, size: (u32, u32)) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    let scale = (size.0 as f64 / w as f64).min(size.1 as f64 / h as f64);
+    let fit_w = ((w as f64 * scale).round() as u32).clamp(1, size.0);
+    let fit_h = ((h as f64 * scale).round() as u32).clamp(1, size.1);
+    let scaled = if (fit_w, fit_h) == (w, h) {
+        image.clone()
+    } else {
+        image::imageops::resize(image, fit_w, fit_h, FilterType::Triangle)
+    };
+    let mut canvas = RgbaImage::from_pixel(size.0, size.1, Rgba([0, 0, 0, 255]));
+    image::imageops::overlay(&mut canvas, &scaled, ((size.0 - fit_w) / 2) as i64, ((size.1 - fit_h) / 2) as i64);
+    canvas
+}
+
+/// Render `screenshots` (already ordered) to `output_path` at `fps`, calling
+/// `progress(done, total)` after each frame. Screenshots whose file can't be read are
+/// skipped. Returns the number of frames written.
+pub fn generate(
+    screenshots: &[Screenshot],
+    screenshots_dir: &Path,
+    fps: u32,
+    output_path: &Path,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<usize, TimelapseError> {
+    if !(1..=60).contains(&fps) {
+        return Err(TimelapseError::InvalidFps);
+    }
+    let format = TimelapseFormat::from_path(output_path)?;
+    let paths: Vec<_> = screenshots
+        .iter()
+        .map(|ss| screenshots_dir.join(ss.filepath.strip_prefix("screenshots/").unwrap_or(&ss.filepath)))
+        .collect();
+
+    // Reading headers first lets frames be decoded one at a time during encoding.
+    let readable: Vec<_> = paths
+        .iter()
+        .filter_map(|p| match image::image_dimensions(p) {
+            Ok(dims) => Some((p, dims)),
+            Err(e) => {
+                warn!("Skipping timelapse frame {}: {}", p.display(), e);
+                None
+            }
+        })
+        .collect();
+    let dims: Vec<_> = readable.iter().map(|(_, d)| *d).collect();
+    let size = frame_size(&dims).ok_or(TimelapseError::NoFrames)?;
+
+    let total = readable.len();
+    let mut frames = readable.into_iter().enumerate().filter_map(|(i, (path, _))| {
+        let frame = match image::open(path) {
+            Ok(img) => Some(letterbox(&img.to_rgba8(), size)),
+            Err(e) => {
+                warn!("Skipping timelapse frame {}: {}", path.display(), e);
+                None
+            }
+        };
+        progress(i + 1, total);
+        frame
+    });
+
+    match format {
+        TimelapseFormat::Gif => {
+            let mut encoder = GifEncoder::new(std::io::BufWriter::new(std::fs::File::create(output_path)?));
+            encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(1000, fps);
+            let mut written = 0;
+            for frame in frames.by_ref() {
+                encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+                written += 1;
+            }
+            Ok(written)
+        }
+        TimelapseFormat::WebP | TimelapseFormat::Mp4 => encode_with_ffmpeg(format, &mut frames, size, fps, output_path),
+    }
+}
+
+/// Pipe raw RGBA frames into ffmpeg.
+fn encode_with_ffmpeg(
+    format: TimelapseFormat,
+    frames: &mut dyn Iterator<Item = RgbaImage>,
+    size: (u32, u32),
+    fps: u32,
+    output_path: &Path,
+) -> Result<usize, TimelapseError> {
+    let (ext, codec_args): (&str, &[&str]) = match format {
+        TimelapseFormat::Mp4 => ("mp4", &["-c:v", "libx264", "-pix_fmt", "yuv420p"]),
+        _ => ("webp", &["-c:v", "libwebp_anim", "-loop", "0"]),
+    };
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", size.0, size.1), "-r", &fps.to_string(), "-i", "-"])
+        .args(codec_args)
+        .arg(output_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => TimelapseError::FfmpegMissing(ext.to_string()),
+            _ => TimelapseError::Io(e),
+        })?;
+
+    let mut written = 0;
+    if let Some(mut stdin) = child.stdin.take() {
+        for frame in frames {
+            if stdin.write_all(frame.as_raw()).is_err() {
+                break; // ffmpeg exited; its stderr explains why
+            }
+            written += 1;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(TimelapseError::Ffmpeg(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    if written == 0 {
+        return Err(TimelapseError::NoFrames);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot(id: i64, captured_at: &str, monitor_index: i32) -> Screenshot {
+        Screenshot {
+            id,
+            filepath: format!("screenshots/{}.webp", id),
+            captured_at: captured_at.to_string(),
+            active_window_title: None,
+            monitor_index,
+            capture_group: None,
+            mirrored_of: None,
+            previous_active: false,
+            scale_factor: None,
+            is_primary: None,
+        }
+    }
+
+    #[test]
+    fn test_order_frames() {
+        let ordered = order_frames(vec![
+            screenshot(4, "2025-01-01T10:01:00", 0),
+            screenshot(3, "2025-01-01T10:00:00", 1),
+            screenshot(1, "2025-01-01T10:00:00", 0),
+            screenshot(2, "2025-01-01T10:00:30", 0),
+        ]);
+        let ids: Vec<i64> = ordered.iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec![1, 3, 2, 4]);
+    }
+
+    #[test]
+    fn test_frame_size() {
+        assert_eq!(frame_size(&[]), None);
+        assert_eq!(frame_size(&[(800, 600), (1024, 768)]), Some((1024, 768)));
+        // Largest frame downscaled to 1280 wide: 2560×1440 → 1280×720
+        assert_eq!(frame_size(&[(1920, 1080), (2560, 1440)]), Some((1280, 720)));
+        // Odd dimensions rounded down to even
+        assert_eq!(frame_size(&[(801, 601)]), Some((800, 600)));
+    }
+
+    #[test]
+    fn test_letterbox_mixed_resolutions() {
+        let size = (200, 100);
+        // A 4:3 frame is pillarboxed: scaled to 133×100, centred with black sides
+        let boxed = letterbox(&RgbaImage::from_pixel(400, 300, Rgba([255, 0, 0, 255])), size);
+        assert_eq!(boxed.dimensions(), size);
+        assert_eq!(boxed.get_pixel(0, 50), &Rgba([0, 0, 0, 255]));
+        assert_eq!(boxed.get_pixel(100, 50), &Rgba([255, 0, 0, 255]));
+        assert_eq!(boxed.get_pixel(199, 50), &Rgba([0, 0, 0, 255]));
+
+        // An ultrawide frame is letterboxed top and bottom
+        let boxed = letterbox(&RgbaImage::from_pixel(400, 100, Rgba([0, 255, 0, 255])), size);
+        assert_eq!(boxed.get_pixel(100, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(boxed.get_pixel(100, 50), &Rgba([0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn test_generate_gif() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_timelapse_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        RgbaImage::from_pixel(40, 30, Rgba([255, 0, 0, 255])).save(dir.join("1.webp")).unwrap();
+        RgbaImage::from_pixel(20, 20, Rgba([0, 0, 255, 255])).save(dir.join("2.webp")).unwrap();
+        let frames = vec![screenshot(1, "a", 0), screenshot(2, "b", 0), screenshot(3, "c", 0)];
+
+        let mut reported = Vec::new();
+        let output = dir.join("out.gif");
+        let written = generate(&frames, &dir, 5, &output, |done, total| reported.push((done, total))).unwrap();
+        assert_eq!(written, 2, "missing frame 3 is skipped");
+        assert_eq!(reported, vec![(1, 2), (2, 2)]);
+        assert_eq!(image::image_dimensions(&output).unwrap(), (40, 30));
+
+        assert!(matches!(generate(&frames, &dir, 0, &output, |_, _| {}), Err(TimelapseError::InvalidFps)));
+        assert!(matches!(
+            generate(&frames, &dir, 5, &dir.join("out.avi"), |_, _| {}),
+            Err(TimelapseError::UnsupportedFormat(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
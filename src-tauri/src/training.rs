@@ -0,0 +1,179 @@
+use crate::models::TrainingExport;
+use crate::storage::Database;
+use log::warn;
+use std::collections::HashSet;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use thiserror::Error;
+
+/// Directory next to the export file that images are copied into with `copy_images`.
+const IMAGES_DIR: &str = "images";
+
+#[derive(Error, Debug)]
+pub enum TrainingError {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Failed to write export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize row: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Write every analyzed screenshot with its task label to `output_path` as JSONL, one
+/// `TrainingRow` per line. Paths are absolute, or `images/<file>` relative to the
+/// export when `copy_images` copies the files alongside it; rows whose image can't be
+/// copied are left out and counted in `missing_images`.
+pub fn export_training_data(
+    db: &Database,
+    output_path: &Path,
+    screenshots_dir: &Path,
+    include_unverified: bool,
+    copy_images: bool,
+) -> Result<TrainingExport, TrainingError> {
+    let images_dir = output_path.parent().unwrap_or(Path::new(".")).join(IMAGES_DIR);
+    if copy_images {
+        std::fs::create_dir_all(&images_dir)?;
+    }
+
+    let mut out = BufWriter::new(std::fs::File::create(output_path)?);
+    let mut summary = TrainingExport { rows: 0, bytes_written: 0, images_copied: 0, missing_images: 0 };
+    let mut copied = HashSet::new();
+    let mut missing = HashSet::new();
+    let mut line = Vec::new();
+
+    db.for_each_training_row(include_unverified, |mut row| -> Result<(), TrainingError> {
+        let filename = row.filepath.strip_prefix("screenshots/").unwrap_or(&row.filepath).to_string();
+        let source = screenshots_dir.join(&filename);
+        row.filepath = if copy_images {
+            if missing.contains(&filename) {
+                return Ok(());
+            }
+            if !copied.contains(&filename) {
+                if let Err(e) = std::fs::copy(&source, images_dir.join(&filename)) {
+                    warn!("Skipping training row for {}: {}", source.display(), e);
+                    summary.missing_images += 1;
+                    missing.insert(filename);
+                    return Ok(());
+                }
+                summary.images_copied += 1;
+                copied.insert(filename.clone());
+            }
+            format!("{}/{}", IMAGES_DIR, filename)
+        } else {
+            source.to_string_lossy().into_owned()
+        };
+
+        line.clear();
+        serde_json::to_writer(&mut line, &row)?;
+        line.push(b'\n');
+        out.write_all(&line)?;
+        summary.rows += 1;
+        summary.bytes_written += line.len() as u64;
+        Ok(())
+    })?;
+
+    out.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TaskUpdate, TrainingRow};
+    use std::path::PathBuf;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_training_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("screenshots")).unwrap();
+        dir
+    }
+
+    /// Two analyzed screenshots (one verified task, one not) and one unanalyzed.
+    fn populated_db(dir: &Path) -> Database {
+        let db = Database::in_memory().unwrap();
+        let sid = db.create_session("2025-01-01T10:00:00", Some("Shipping login"), None).unwrap();
+        let a = db.insert_screenshot("screenshots/a.webp", "2025-01-01T10:00:00", Some("auth.rs - Code"), 0, Some(sid), None).unwrap();
+        let b = db.insert_screenshot("screenshots/b.webp", "2025-01-01T10:00:30", None, 1, Some(sid), None).unwrap();
+        db.insert_screenshot("screenshots/c.webp", "2025-01-01T10:01:00", None, 0, Some(sid), None).unwrap();
+        for name in ["a.webp", "b.webp", "c.webp"] {
+            std::fs::write(dir.join("screenshots").join(name), name).unwrap();
+        }
+
+        let coding = db.insert_full_task("Writing code", "", "coding", "2025-01-01T10:00:00", "").unwrap();
+        db.update_task(coding, &TaskUpdate {
+            title: None,
+            description: None,
+            category: None,
+            ended_at: None,
+            user_verified: Some(true),
+        }).unwrap();
+        let docs = db.insert_full_task("Reading docs", "", "browsing", "2025-01-01T10:00:30", "").unwrap();
+        db.link_screenshot_to_task(coding, a).unwrap();
+        db.link_screenshot_to_task(docs, b).unwrap();
+        db
+    }
+
+    fn read_rows(path: &Path) -> Vec<TrainingRow> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_export_all_analyzed() {
+        let dir = test_dir("all");
+        let db = populated_db(&dir);
+        let output = dir.join("out.jsonl");
+
+        let summary = export_training_data(&db, &output, &dir.join("screenshots"), true, false).unwrap();
+        assert_eq!(summary.rows, 2);
+        assert_eq!(summary.images_copied, 0);
+        assert_eq!(summary.bytes_written, std::fs::metadata(&output).unwrap().len());
+
+        let rows = read_rows(&output);
+        assert_eq!(rows[0].task_title, "Writing code");
+        assert!(rows[0].user_verified);
+        assert_eq!(rows[0].active_window_title.as_deref(), Some("auth.rs - Code"));
+        assert_eq!(rows[0].session_description.as_deref(), Some("Shipping login"));
+        assert_eq!(PathBuf::from(&rows[0].filepath), dir.join("screenshots").join("a.webp"));
+        assert_eq!(rows[1].category.as_deref(), Some("browsing"));
+        assert!(!rows[1].user_verified);
+        assert_eq!(rows[1].monitor_index, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_verified_only_with_images() {
+        let dir = test_dir("images");
+        let db = populated_db(&dir);
+        let output = dir.join("export").join("out.jsonl");
+        std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+
+        let summary = export_training_data(&db, &output, &dir.join("screenshots"), false, true).unwrap();
+        assert_eq!(summary.rows, 1);
+        assert_eq!(summary.images_copied, 1);
+
+        let rows = read_rows(&output);
+        assert_eq!(rows[0].filepath, "images/a.webp");
+        assert_eq!(std::fs::read_to_string(dir.join("export/images/a.webp")).unwrap(), "a.webp");
+        assert!(!dir.join("export/images/b.webp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_export_skips_missing_images() {
+        let dir = test_dir("missing");
+        let db = populated_db(&dir);
+        std::fs::remove_file(dir.join("screenshots/b.webp")).unwrap();
+        let output = dir.join("out.jsonl");
+
+        let summary = export_training_data(&db, &output, &dir.join("screenshots"), true, true).unwrap();
+        assert_eq!(summary.rows, 1);
+        assert_eq!(summary.missing_images, 1);
+        assert_eq!(read_rows(&output)[0].screenshot_id, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
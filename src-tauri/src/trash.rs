@@ -0,0 +1,107 @@
+//! Files of trashed screenshots. Deleting a session or screenshot without `permanent`
+//! moves its rows into the trash tables (see `Database::trash_session`) and its files
+//! into `trash/` under the screenshots directory, mirroring their layout there, until
+//! they're restored or purged.
+
+use log::{debug, warn};
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the screenshots directory holding trashed files.
+pub const TRASH_DIR: &str = "trash";
+
+/// Where a screenshot's file lives, from its stored filepath (`screenshots/<name>`).
+pub fn live_path(screenshots_dir: &Path, filepath: &str) -> PathBuf {
+    screenshots_dir.join(relative(filepath))
+}
+
+/// Where a trashed screenshot's file is kept.
+pub fn trash_path(screenshots_dir: &Path, filepath: &str) -> PathBuf {
+    screenshots_dir.join(TRASH_DIR).join(relative(filepath))
+}
+
+fn relative(filepath: &str) -> &str {
+    filepath.strip_prefix("screenshots/").unwrap_or(filepath)
+}
+
+/// Move the files of just-trashed screenshots into `trash/`. Returns how many moved;
+/// files already missing are skipped.
+pub fn move_to_trash(screenshots_dir: &Path, filepaths: &[String]) -> usize {
+    filepaths
+        .iter()
+        .filter(|fp| move_file(&live_path(screenshots_dir, fp), &trash_path(screenshots_dir, fp)))
+        .count()
+}
+
+/// Move the files of just-restored screenshots back out of `trash/`. Returns how many moved.
+pub fn restore_files(screenshots_dir: &Path, filepaths: &[String]) -> usize {
+    filepaths
+        .iter()
+        .filter(|fp| move_file(&trash_path(screenshots_dir, fp), &live_path(screenshots_dir, fp)))
+        .count()
+}
+
+/// Delete the files of purged trash entries. Returns how many were removed.
+pub fn remove_files(screenshots_dir: &Path, filepaths: &[String]) -> usize {
+    filepaths
+        .iter()
+        .map(|fp| trash_path(screenshots_dir, fp))
+        .filter(|path| match std::fs::remove_file(path) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Could not remove trashed file {}: {}", path.display(), e);
+                false
+            }
+        })
+        .count()
+}
+
+fn move_file(from: &Path, to: &Path) -> bool {
+    if !from.exists() {
+        debug!("Screenshot file {} is missing, nothing to move", from.display());
+        return false;
+    }
+    if let Some(parent) = to.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            warn!("Could not create {}: {}", parent.display(), e);
+            return false;
+        }
+    }
+    match std::fs::rename(from, to) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Could not move {} to {}: {}", from.display(), to.display(), e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trash_and_restore_files() {
+        let dir = std::env::temp_dir().join(format!("rlcollector_test_trash_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("corrupt")).unwrap();
+        std::fs::write(dir.join("a.webp"), b"a").unwrap();
+        std::fs::write(dir.join("corrupt").join("b.webp"), b"b").unwrap();
+        let paths = vec![
+            "screenshots/a.webp".to_string(),
+            "screenshots/corrupt/b.webp".to_string(),
+            "screenshots/missing.webp".to_string(),
+        ];
+
+        assert_eq!(move_to_trash(&dir, &paths), 2);
+        assert!(!dir.join("a.webp").exists());
+        assert!(dir.join("trash").join("a.webp").is_file());
+        assert!(dir.join("trash").join("corrupt").join("b.webp").is_file());
+
+        assert_eq!(restore_files(&dir, &paths[..1]), 1);
+        assert_eq!(std::fs::read(dir.join("a.webp")).unwrap(), b"a");
+
+        assert_eq!(remove_files(&dir, &paths), 1);
+        assert!(!dir.join("trash").join("corrupt").join("b.webp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,3 +1,6 @@
+use crate::commands::{self, AppState};
+use log::{info, warn};
+use std::sync::Arc;
 use tauri::{
     menu::{Menu, MenuItem},
     tray::TrayIconBuilder,
@@ -8,9 +11,10 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let show = MenuItem::with_id(app, "show", "Show RLCollector", true, None::<&str>)?;
     let start = MenuItem::with_id(app, "start_capture", "Start Capture", true, None::<&str>)?;
     let stop = MenuItem::with_id(app, "stop_capture", "Stop Capture", true, None::<&str>)?;
+    let analyze = MenuItem::with_id(app, "analyze_current_session", "Analyze Now", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
 
-    let menu = Menu::with_items(app, &[&show, &start, &stop, &quit])?;
+    let menu = Menu::with_items(app, &[&show, &start, &stop, &analyze, &quit])?;
 
     TrayIconBuilder::new()
         .menu(&menu)
@@ -22,6 +26,16 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
                     let _ = window.set_focus();
                 }
             }
+            "analyze_current_session" => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+                    match commands::analyze_current(&app, &state).await {
+                        Ok(n) => info!("Tray analysis processed {} screenshot(s)", n),
+                        Err(e) => warn!("Tray analysis not run: {}", e),
+                    }
+                });
+            }
             "quit" => {
                 app.exit(0);
             }
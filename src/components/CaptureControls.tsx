@@ -16,6 +16,11 @@ export function CaptureControls({ onStop }: { onStop?: () => void }) {
         <span className={`indicator ${status.active ? "active" : "inactive"}`} />
         <span>{status.active ? "Recording" : "Stopped"}</span>
         {status.active && <span> — {status.count} captures</span>}
+        {status.active && status.max_screenshots_per_session > 0 && (
+          <span>
+            {" "}({status.session_screenshot_count}/{status.max_screenshots_per_session} this session)
+          </span>
+        )}
       </div>
       {error && <div className="error-msg">{error}</div>}
       <div className="controls">
@@ -154,6 +154,11 @@ export function CollectionDetail({
                     {shot.active_window_title}
                   </span>
                 )}
+                {shot.mirrored_of !== null && (
+                  <span className="badge" title={`Same content as screenshot ${shot.mirrored_of}; not sent to the AI`}>
+                    Mirrored
+                  </span>
+                )}
               </div>
             </div>
           ))}
@@ -1,8 +1,8 @@
 import { useState } from "react";
 import { useSessions } from "../hooks/useSessions";
-import { analyzeSession, analyzeAllPending, cancelAnalysis, deleteSession } from "../lib/tauri";
+import { analyzeSession, analyzeAllPending, analyzeUnassigned, cancelAnalysis, deleteSession } from "../lib/tauri";
 import { CollectionDetail } from "./CollectionDetail";
-import type { CaptureSession } from "../types";
+import type { CaptureSession, UnassignedSummary } from "../types";
 
 function SessionCard({
   session,
@@ -55,6 +55,42 @@ function SessionCard({
   );
 }
 
+function UnassignedCard({
+  summary,
+  analyzing,
+  onAnalyze,
+}: {
+  summary: UnassignedSummary;
+  analyzing: boolean;
+  onAnalyze: () => void;
+}) {
+  const first = summary.first_captured_at ? new Date(summary.first_captured_at).toLocaleString() : "";
+  const last = summary.last_captured_at ? new Date(summary.last_captured_at).toLocaleString() : "";
+
+  return (
+    <div className="session-card">
+      <div className="session-card-info">
+        <h3>Unassigned</h3>
+        <p className="session-description">Screenshots captured outside any session</p>
+        <div className="session-meta">
+          <span>{first === last ? first : `${first} – ${last}`}</span>
+          <span>{summary.count} unanalyzed</span>
+        </div>
+      </div>
+      <div className="session-card-actions">
+        <button
+          className="analyze-button"
+          onClick={onAnalyze}
+          disabled={analyzing}
+          title="Analyzes every pending screenshot, including these, without session context"
+        >
+          {analyzing ? <><span className="spinner" /> Analyzing...</> : "Analyze without context"}
+        </button>
+      </div>
+    </div>
+  );
+}
+
 function CompletedSessionCard({
   session,
   onClick,
@@ -94,6 +130,7 @@ export function Dashboard({ refreshTrigger }: { refreshTrigger?: number }) {
   const {
     pending,
     completed,
+    unassigned,
     loading,
     refresh,
     completedPage,
@@ -142,6 +179,24 @@ export function Dashboard({ refreshTrigger }: { refreshTrigger?: number }) {
     }
   };
 
+  const handleAnalyzeUnassigned = async () => {
+    setUserAnalyzeAll(true);
+    setAnalyzeMsg(null);
+    try {
+      const count = await analyzeUnassigned();
+      setAnalyzeMsg(
+        count > 0 ? `Analyzed ${count} screenshot${count > 1 ? "s" : ""}` : "No pending screenshots"
+      );
+      refresh(completedPage);
+    } catch (e) {
+      const msg = e instanceof Error ? e.message : String(e);
+      setAnalyzeMsg(`Error: ${msg}`);
+    } finally {
+      setUserAnalyzeAll(false);
+      setTimeout(() => setAnalyzeMsg(null), 4000);
+    }
+  };
+
   const handleCancelAnalysis = async () => {
     await cancelAnalysis();
   };
@@ -201,10 +256,17 @@ export function Dashboard({ refreshTrigger }: { refreshTrigger?: number }) {
             )}
           </div>
         </div>
-        {pending.length === 0 ? (
+        {pending.length === 0 && !unassigned ? (
           <p>No pending sessions. Start a capture to create one.</p>
         ) : (
           <div className="session-cards">
+            {unassigned && (
+              <UnassignedCard
+                summary={unassigned}
+                analyzing={userAnalyzeAll}
+                onAnalyze={handleAnalyzeUnassigned}
+              />
+            )}
             {pending.map((session) => (
               <SessionCard
                 key={session.id}
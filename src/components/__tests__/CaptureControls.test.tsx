@@ -28,7 +28,7 @@ describe('CaptureControls', () => {
 
   it('renders capture status indicator when stopped', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -41,7 +41,7 @@ describe('CaptureControls', () => {
 
   it('renders capture status indicator when recording', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: true, interval_ms: 30000, count: 5, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: true, paused: false, interval_ms: 30000, count: 5, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -54,7 +54,7 @@ describe('CaptureControls', () => {
 
   it('shows "Start Capture" button when not capturing', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -67,7 +67,7 @@ describe('CaptureControls', () => {
 
   it('shows "Stop Capture" button when capturing', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: true, interval_ms: 30000, count: 3, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: true, paused: false, interval_ms: 30000, count: 3, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -80,7 +80,7 @@ describe('CaptureControls', () => {
 
   it('disables Start Capture when title is empty', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -94,7 +94,7 @@ describe('CaptureControls', () => {
   it('enables Start Capture when title is provided', async () => {
     const user = userEvent.setup();
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -111,7 +111,7 @@ describe('CaptureControls', () => {
   it('calls start with title when Start Capture button is clicked', async () => {
     const user = userEvent.setup();
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -129,7 +129,7 @@ describe('CaptureControls', () => {
   it('calls stop when Stop Capture button is clicked', async () => {
     const user = userEvent.setup();
     mockUseCapture.mockReturnValue({
-      status: { active: true, interval_ms: 30000, count: 5, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: true, paused: false, interval_ms: 30000, count: 5, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -143,7 +143,7 @@ describe('CaptureControls', () => {
 
   it('shows capture count when active', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: true, interval_ms: 30000, count: 42, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: true, paused: false, interval_ms: 30000, count: 42, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -156,7 +156,7 @@ describe('CaptureControls', () => {
 
   it('displays error message when error is set', () => {
     mockUseCapture.mockReturnValue({
-      status: { active: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1 },
+      status: { active: false, paused: false, interval_ms: 30000, count: 0, monitor_mode: "default", monitors_captured: 1, session_screenshot_count: 0, max_screenshots_per_session: 0, active_monitor_id: null },
       start: mockStart,
       stop: mockStop,
       loading: false,
@@ -54,6 +54,10 @@ describe("CollectionDetail", () => {
         active_window_title: "VS Code",
         monitor_index: 0,
         capture_group: null,
+        mirrored_of: null,
+        previous_active: false,
+        scale_factor: null,
+        is_primary: null,
       },
       {
         id: 2,
@@ -62,6 +66,10 @@ describe("CollectionDetail", () => {
         active_window_title: null,
         monitor_index: 0,
         capture_group: null,
+        mirrored_of: null,
+        previous_active: false,
+        scale_factor: null,
+        is_primary: null,
       },
     ]);
     render(<CollectionDetail sessionId={1} onClose={() => {}} />);
@@ -75,6 +83,40 @@ describe("CollectionDetail", () => {
     expect(screen.getByText("VS Code")).toBeInTheDocument();
   });
 
+  it("badges mirrored screenshots", async () => {
+    mockGetSessionScreenshots.mockResolvedValue([
+      {
+        id: 1,
+        filepath: "screenshots/shot1_0.webp",
+        captured_at: "2025-01-01T10:00:00",
+        active_window_title: null,
+        monitor_index: 0,
+        capture_group: "g1",
+        mirrored_of: null,
+        previous_active: false,
+        scale_factor: null,
+        is_primary: null,
+      },
+      {
+        id: 2,
+        filepath: "screenshots/shot1_1.webp",
+        captured_at: "2025-01-01T10:00:00",
+        active_window_title: null,
+        monitor_index: 1,
+        capture_group: "g1",
+        mirrored_of: 1,
+        previous_active: false,
+        scale_factor: null,
+        is_primary: null,
+      },
+    ]);
+    render(<CollectionDetail sessionId={1} onClose={() => {}} />);
+
+    await waitFor(() => {
+      expect(screen.getAllByText("Mirrored")).toHaveLength(1);
+    });
+  });
+
   it("renders session tasks above screenshots", async () => {
     mockGetSessionScreenshots.mockResolvedValue([]);
     mockGetSessionTasks.mockResolvedValue([
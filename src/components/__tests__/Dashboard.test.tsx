@@ -2,7 +2,7 @@ import { describe, it, expect, vi, beforeEach } from 'vitest';
 import { render, screen, waitFor } from '@testing-library/react';
 import userEvent from '@testing-library/user-event';
 import { Dashboard } from '../Dashboard';
-import type { CaptureSession } from '../../types';
+import type { CaptureSession, SessionStats, UnassignedSummary } from '../../types';
 
 // Mock useSessions hook
 const mockRefresh = vi.fn();
@@ -12,6 +12,7 @@ const mockPrevCompletedPage = vi.fn();
 const mockUseSessions = vi.fn<() => {
   pending: CaptureSession[];
   completed: CaptureSession[];
+  unassigned?: UnassignedSummary | null;
   loading: boolean;
   refresh: (page?: number) => Promise<void>;
   completedPage: number;
@@ -28,10 +29,12 @@ vi.mock('../../hooks/useSessions', () => ({
 // Mock tauri module
 const mockAnalyzeSession = vi.fn<(sessionId: number) => Promise<number>>();
 const mockAnalyzeAllPending = vi.fn<() => Promise<number>>();
+const mockAnalyzeUnassigned = vi.fn<() => Promise<number>>();
 
 vi.mock('../../lib/tauri', () => ({
   analyzeSession: (...args: unknown[]) => mockAnalyzeSession(...(args as [number])),
   analyzeAllPending: (...args: unknown[]) => mockAnalyzeAllPending(...(args as [])),
+  analyzeUnassigned: (...args: unknown[]) => mockAnalyzeUnassigned(...(args as [])),
   cancelAnalysis: vi.fn(),
   deleteSession: vi.fn().mockResolvedValue(0),
   getSessionScreenshots: vi.fn().mockResolvedValue([]),
@@ -54,6 +57,17 @@ vi.mock('../CollectionDetail', () => ({
   ),
 }));
 
+const emptyStats: SessionStats = {
+  ticks: 0,
+  ticks_unchanged: 0,
+  screenshots_saved: 0,
+  screenshots_unchanged: 0,
+  bytes_written: 0,
+  groups_analyzed: 0,
+  screenshots_linked_without_ai: 0,
+  analysis_resources: null,
+};
+
 const pendingSession: CaptureSession = {
   id: 1,
   started_at: '2025-01-01T10:00:00Z',
@@ -62,6 +76,8 @@ const pendingSession: CaptureSession = {
   description: 'Working on auth',
   title: 'Auth Feature',
   unanalyzed_count: 3,
+  duration_seconds: null,
+  stats: emptyStats,
 };
 
 const completedSession: CaptureSession = {
@@ -72,6 +88,8 @@ const completedSession: CaptureSession = {
   description: 'Finished testing',
   title: 'Testing Sprint',
   unanalyzed_count: 0,
+  duration_seconds: null,
+  stats: emptyStats,
 };
 
 describe('Dashboard', () => {
@@ -79,6 +97,7 @@ describe('Dashboard', () => {
     vi.clearAllMocks();
     mockAnalyzeSession.mockResolvedValue(0);
     mockAnalyzeAllPending.mockResolvedValue(0);
+    mockAnalyzeUnassigned.mockResolvedValue(0);
   });
 
   it('renders loading state', () => {
@@ -199,6 +218,34 @@ describe('Dashboard', () => {
     });
   });
 
+  it('renders the unassigned bucket and analyzes it only on request', async () => {
+    const user = userEvent.setup();
+    mockAnalyzeUnassigned.mockResolvedValue(2);
+    mockUseSessions.mockReturnValue({
+      pending: [],
+      completed: [],
+      unassigned: { count: 2, first_captured_at: '2025-01-01T08:00:00', last_captured_at: '2025-01-02T10:00:00' },
+      loading: false,
+      refresh: mockRefresh,
+      completedPage: 0,
+      hasMoreCompleted: false,
+      nextCompletedPage: mockNextCompletedPage,
+      prevCompletedPage: mockPrevCompletedPage,
+      analyzingSessionId: null,
+    });
+    render(<Dashboard />);
+    expect(screen.getByText('Unassigned')).toBeInTheDocument();
+    expect(screen.getByText('2 unanalyzed')).toBeInTheDocument();
+    expect(screen.queryByText('No pending sessions. Start a capture to create one.')).not.toBeInTheDocument();
+    await user.click(screen.getByText('Analyze without context'));
+    await waitFor(() => {
+      expect(mockAnalyzeUnassigned).toHaveBeenCalled();
+    });
+    await waitFor(() => {
+      expect(screen.getByText('Analyzed 2 screenshots')).toBeInTheDocument();
+    });
+  });
+
   it('shows error message when analysis fails', async () => {
     const user = userEvent.setup();
     mockAnalyzeSession.mockRejectedValue(new Error('No API key configured'));
@@ -9,10 +9,14 @@ import {
 export function useCapture() {
   const [status, setStatus] = useState<CaptureStatus>({
     active: false,
+    paused: false,
     interval_ms: 30000,
     count: 0,
     monitor_mode: "default",
     monitors_captured: 0,
+    session_screenshot_count: 0,
+    max_screenshots_per_session: 0,
+    active_monitor_id: null,
   });
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
@@ -1,6 +1,6 @@
 import { useCallback, useEffect, useRef, useState } from "react";
-import type { CaptureSession } from "../types";
-import { getPendingSessions, getCompletedSessions, getAnalysisStatus } from "../lib/tauri";
+import type { CaptureSession, UnassignedSummary } from "../types";
+import { getPendingSessions, getCompletedSessions, getAnalysisStatus, getUnassignedSummary } from "../lib/tauri";
 
 const PAGE_SIZE = 20;
 const POLL_INTERVAL_MS = 3000;
@@ -8,6 +8,7 @@ const POLL_INTERVAL_MS = 3000;
 export function useSessions(refreshTrigger?: number) {
   const [pending, setPending] = useState<CaptureSession[]>([]);
   const [completed, setCompleted] = useState<CaptureSession[]>([]);
+  const [unassigned, setUnassigned] = useState<UnassignedSummary | null>(null);
   const [loading, setLoading] = useState(true);
   const [completedPage, setCompletedPage] = useState(0);
   const [hasMoreCompleted, setHasMoreCompleted] = useState(false);
@@ -18,12 +19,14 @@ export function useSessions(refreshTrigger?: number) {
     const p = cPage ?? completedPage;
     setLoading(true);
     try {
-      const [pend, comp] = await Promise.all([
+      const [pend, comp, loose] = await Promise.all([
         getPendingSessions(50, 0),
         getCompletedSessions(PAGE_SIZE, p * PAGE_SIZE),
+        getUnassignedSummary(),
       ]);
       setPending(pend);
       setCompleted(comp);
+      setUnassigned(loose.count > 0 ? loose : null);
       setHasMoreCompleted(comp.length === PAGE_SIZE);
     } finally {
       setLoading(false);
@@ -38,12 +41,14 @@ export function useSessions(refreshTrigger?: number) {
       if (status.analyzing) {
         // Refresh session data to update unanalyzed counts
         const p = completedPage;
-        const [pend, comp] = await Promise.all([
+        const [pend, comp, loose] = await Promise.all([
           getPendingSessions(50, 0),
           getCompletedSessions(PAGE_SIZE, p * PAGE_SIZE),
+          getUnassignedSummary(),
         ]);
         setPending(pend);
         setCompleted(comp);
+        setUnassigned(loose.count > 0 ? loose : null);
         setHasMoreCompleted(comp.length === PAGE_SIZE);
       }
     } catch {
@@ -92,6 +97,7 @@ export function useSessions(refreshTrigger?: number) {
   return {
     pending,
     completed,
+    unassigned,
     loading,
     refresh,
     completedPage,
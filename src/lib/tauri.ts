@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AnalysisStatus, CaptureSession, CaptureStatus, MonitorInfo, OllamaStatus, Screenshot, Task } from "../types";
+import type { ActivityImport, AnalysisCostEstimate, AnalysisDebugEntry, AnalysisEstimate, AnalysisStatus, AppUsage, ArchivedTask, CaptureCapability, CaptureEvent, CaptureGroupSummary, CaptureRegion, CaptureSession, CaptureStatus, CategoryCorrection, DailyTaskCount, DiagnosticsReport, EvaluationReport, FactoryReset, GroupReanalysis, LabeledScreenshot, MonitorChangeState, MonitorInfo, OllamaModelsDir, OllamaStatus, PendingCleared, PreviewFrame, ProviderHealth, RecentError, RegionSelection, SavingsSummary, Screenshot, ScreenshotLabel, ScreenshotNeighbors, SessionArchive, SessionGap, SessionStatus, SettingSchema, SettingsImport, SystemLoad, Task, TaskDetail, TaskWithProvider, TrainingExport, TrashEntry, TrashKind, UnassignedSummary } from "../types";
 
 export async function startCapture(intervalMs?: number, description?: string, title?: string): Promise<void> {
   return invoke("start_capture", { intervalMs, description, title });
@@ -9,6 +9,69 @@ export async function stopCapture(): Promise<void> {
   return invoke("stop_capture");
 }
 
+export async function cancelCapture(force?: boolean): Promise<number> {
+  return invoke("cancel_capture", { force });
+}
+
+export async function pauseCapture(): Promise<void> {
+  return invoke("pause_capture");
+}
+
+export async function resumeCapture(): Promise<void> {
+  return invoke("resume_capture");
+}
+
+export async function finalizeSession(sessionId: number, analyze?: boolean): Promise<number | null> {
+  return invoke("finalize_session", { sessionId, analyze });
+}
+
+export async function getSessionDuration(sessionId: number): Promise<number> {
+  return invoke("get_session_duration", { sessionId });
+}
+
+export async function getSessionGaps(
+  sessionId: number,
+  expectedIntervalMs: number
+): Promise<SessionGap[]> {
+  return invoke("get_session_gaps", { sessionId, expectedIntervalMs });
+}
+
+export async function getSessionTopApps(sessionId: number): Promise<AppUsage[]> {
+  return invoke("get_session_top_apps", { sessionId });
+}
+
+export async function normalizeSessionBounds(sessionId: number): Promise<CaptureSession> {
+  return invoke("normalize_session_bounds", { sessionId });
+}
+
+export async function setScreenshotLabel(
+  screenshotId: number,
+  title: string,
+  category: string
+): Promise<ScreenshotLabel> {
+  return invoke("set_screenshot_label", { screenshotId, title, category });
+}
+
+export async function getScreenshotLabel(screenshotId: number): Promise<ScreenshotLabel | null> {
+  return invoke("get_screenshot_label", { screenshotId });
+}
+
+export async function listLabeledScreenshots(sessionId?: number): Promise<LabeledScreenshot[]> {
+  return invoke("list_labeled_screenshots", { sessionId });
+}
+
+export async function evaluateAnalysis(sessionId: number): Promise<EvaluationReport> {
+  return invoke("evaluate_analysis", { sessionId });
+}
+
+export async function getSessionCaptureGroups(sessionId: number): Promise<CaptureGroupSummary[]> {
+  return invoke("get_session_capture_groups", { sessionId });
+}
+
+export async function backfillCaptureGroups(sessionId: number): Promise<number> {
+  return invoke("backfill_capture_groups", { sessionId });
+}
+
 export async function getCaptureStatus(): Promise<CaptureStatus> {
   return invoke("get_capture_status");
 }
@@ -19,37 +82,114 @@ export async function getSetting(key: string): Promise<string | null> {
 
 export async function updateSetting(
   key: string,
-  value: string
+  value: string,
+  allowUnknown?: boolean
 ): Promise<void> {
-  return invoke("update_setting", { key, value });
+  return invoke("update_setting", { key, value, allowUnknown });
 }
 
-export async function deleteSession(sessionId: number): Promise<number> {
-  return invoke("delete_session", { sessionId });
+export async function setAndValidateApiKey(key: string): Promise<void> {
+  return invoke("set_and_validate_api_key", { key });
+}
+
+export async function getSettingsSchema(): Promise<SettingSchema[]> {
+  return invoke("get_settings_schema");
+}
+
+export async function renderPromptPreview(template: string, sample: boolean): Promise<string> {
+  return invoke("render_prompt_preview", { template, sample });
+}
+
+export async function deleteSession(sessionId: number, permanent?: boolean): Promise<number> {
+  return invoke("delete_session", { sessionId, permanent });
+}
+
+export async function deleteScreenshot(screenshotId: number, permanent?: boolean): Promise<void> {
+  return invoke("delete_screenshot", { screenshotId, permanent });
+}
+
+export async function getTrash(): Promise<TrashEntry[]> {
+  return invoke("get_trash");
+}
+
+export async function restoreFromTrash(kind: TrashKind, trashId: number): Promise<number> {
+  return invoke("restore_from_trash", { kind, trashId });
+}
+
+export async function purgeTrash(all?: boolean): Promise<number> {
+  return invoke("purge_trash", { all });
+}
+
+export async function reconcileLegacyScreenshots(): Promise<CaptureSession[]> {
+  return invoke("reconcile_legacy_screenshots");
 }
 
 export async function getAnalysisStatus(): Promise<AnalysisStatus> {
   return invoke("get_analysis_status");
 }
 
+export async function getRecentErrors(): Promise<RecentError[]> {
+  return invoke("get_recent_errors");
+}
+
 export async function cancelAnalysis(): Promise<void> {
   return invoke("cancel_analysis");
 }
 
+export async function clearPending(
+  sessionId?: number,
+  excludeActive?: boolean,
+  permanent?: boolean
+): Promise<PendingCleared[]> {
+  return invoke("clear_pending", { sessionId, excludeActive, permanent });
+}
+
+export async function archivePendingAsTask(
+  title: string,
+  sessionId?: number
+): Promise<ArchivedTask[]> {
+  return invoke("archive_pending_as_task", { sessionId, title });
+}
+
+export async function getCategoryCorrections(): Promise<CategoryCorrection[]> {
+  return invoke("get_category_corrections");
+}
+
+export async function deleteCategoryCorrection(id: number): Promise<void> {
+  return invoke("delete_category_correction", { id });
+}
+
 export async function getLogPath(): Promise<string> {
   return invoke("get_log_path");
 }
 
+export async function setLogLevel(
+  level: "error" | "warn" | "info" | "debug" | "trace"
+): Promise<void> {
+  return invoke("set_log_level", { level });
+}
+
 export async function getSessionScreenshots(
   sessionId: number
 ): Promise<Screenshot[]> {
   return invoke("get_session_screenshots", { sessionId });
 }
 
+export async function getLatestScreenshot(): Promise<Screenshot | null> {
+  return invoke("get_latest_screenshot");
+}
+
 export async function getScreenshotsDir(): Promise<string> {
   return invoke("get_screenshots_dir");
 }
 
+export async function setScreenshotsDir(
+  path: string,
+  moveExisting: boolean
+): Promise<number> {
+  return invoke("set_screenshots_dir", { path, moveExisting });
+}
+
 export async function getSessionTasks(
   sessionId: number
 ): Promise<Task[]> {
@@ -62,14 +202,135 @@ export async function getTaskForScreenshot(
   return invoke("get_task_for_screenshot", { screenshotId });
 }
 
+export async function getTasksWithProvider(
+  limit?: number,
+  offset?: number
+): Promise<TaskWithProvider[]> {
+  return invoke("get_tasks_with_provider", { limit, offset });
+}
+
+export async function getTaskDetail(taskId: number): Promise<TaskDetail> {
+  return invoke("get_task_detail", { taskId });
+}
+
+export async function getAnalysisDebug(captureGroup: string): Promise<AnalysisDebugEntry[]> {
+  return invoke("get_analysis_debug", { captureGroup });
+}
+
+export async function getTasksByCategoryInRange(
+  category: string,
+  start: string,
+  end: string
+): Promise<Task[]> {
+  return invoke("get_tasks_by_category_in_range", { category, start, end });
+}
+
+export async function getDailyTaskCounts(start: string, end: string): Promise<DailyTaskCount[]> {
+  return invoke("get_daily_task_counts", { start, end });
+}
+
+export async function getCaptureEvents(sessionId: number): Promise<CaptureEvent[]> {
+  return invoke("get_capture_events", { sessionId });
+}
+
+export async function getSavingsSummary(from?: string, to?: string): Promise<SavingsSummary> {
+  return invoke("get_savings_summary", { from, to });
+}
+
+export async function exportAll(outputPath: string, includeSecrets?: boolean): Promise<void> {
+  return invoke("export_all", { outputPath, includeSecrets });
+}
+
+export async function importAll(inputPath: string, force?: boolean): Promise<void> {
+  return invoke("import_all", { inputPath, force });
+}
+
+export async function exportSettings(path: string, includeSecrets?: boolean): Promise<number> {
+  return invoke("export_settings", { path, includeSecrets });
+}
+
+export async function importSettings(path: string, overwrite: boolean): Promise<SettingsImport> {
+  return invoke("import_settings", { path, overwrite });
+}
+
+export async function factoryReset(confirmation: string): Promise<FactoryReset> {
+  return invoke("factory_reset", { confirmation });
+}
+
+export async function importActivityLog(path: string, format?: string): Promise<ActivityImport> {
+  return invoke("import_activity_log", { path, format });
+}
+
+export async function exportTrainingData(
+  path: string,
+  includeUnverified: boolean,
+  copyImages: boolean
+): Promise<TrainingExport> {
+  return invoke("export_training_data", { path, includeUnverified, copyImages });
+}
+
+export async function exportSessionArchive(
+  sessionId: number,
+  path: string,
+  includeMetadata: boolean
+): Promise<SessionArchive> {
+  return invoke("export_session_archive", { sessionId, path, includeMetadata });
+}
+
+export async function getScreenshotNeighbors(
+  screenshotId: number
+): Promise<ScreenshotNeighbors> {
+  return invoke("get_screenshot_neighbors", { screenshotId });
+}
+
 export async function analyzeSession(sessionId: number): Promise<number> {
   return invoke("analyze_session", { sessionId });
 }
 
+export async function analyzeCurrentSession(): Promise<number> {
+  return invoke("analyze_current_session");
+}
+
+export async function reanalyzeUnverified(sessionId: number): Promise<number> {
+  return invoke("reanalyze_unverified", { sessionId });
+}
+
+export async function reanalyzeCaptureGroup(captureGroup: string, force?: boolean): Promise<GroupReanalysis> {
+  return invoke("reanalyze_capture_group", { captureGroup, force });
+}
+
 export async function analyzeAllPending(): Promise<number> {
   return invoke("analyze_all_pending");
 }
 
+export async function analyzePending(includeUnassigned: boolean): Promise<number> {
+  return invoke("analyze_pending", { includeUnassigned });
+}
+
+export async function analyzeUnassigned(): Promise<number> {
+  return invoke("analyze_unassigned");
+}
+
+export async function getUnassignedSummary(): Promise<UnassignedSummary> {
+  return invoke("get_unassigned_summary");
+}
+
+export async function assignScreenshotsToSession(screenshotIds: number[], sessionId: number): Promise<number> {
+  return invoke("assign_screenshots_to_session", { screenshotIds, sessionId });
+}
+
+export async function getSystemLoad(): Promise<SystemLoad> {
+  return invoke("get_system_load");
+}
+
+export async function estimatePendingAnalysis(): Promise<AnalysisEstimate> {
+  return invoke("estimate_pending_analysis");
+}
+
+export async function estimateAnalysisCost(sessionId: number): Promise<AnalysisCostEstimate> {
+  return invoke("estimate_analysis_cost", { sessionId });
+}
+
 export async function getPendingSessions(
   limit?: number,
   offset?: number
@@ -84,22 +345,82 @@ export async function getCompletedSessions(
   return invoke("get_completed_sessions", { limit, offset });
 }
 
+export async function getSessionStatus(sessionId: number): Promise<SessionStatus> {
+  return invoke("get_session_status", { sessionId });
+}
+
+export async function suggestSessionDescription(): Promise<string[]> {
+  return invoke("suggest_session_description");
+}
+
+export async function runDiagnostics(): Promise<DiagnosticsReport> {
+  return invoke("run_diagnostics");
+}
+
+export async function generateTimelapse(
+  sessionId: number,
+  fps: number,
+  outputPath: string
+): Promise<number> {
+  return invoke("generate_timelapse", { sessionId, fps, outputPath });
+}
+
 export async function checkOllama(): Promise<OllamaStatus> {
   return invoke("check_ollama");
 }
 
+export async function getProviderHealth(): Promise<ProviderHealth[]> {
+  return invoke("get_provider_health");
+}
+
 export async function ensureOllama(): Promise<OllamaStatus> {
   return invoke("ensure_ollama");
 }
 
+export async function restartOllama(): Promise<OllamaStatus> {
+  return invoke("restart_ollama");
+}
+
 export async function ollamaPull(model: string): Promise<void> {
   return invoke("ollama_pull", { model });
 }
 
+export async function getOllamaModelsDir(): Promise<OllamaModelsDir> {
+  return invoke("get_ollama_models_dir");
+}
+
+export async function purgeOllamaModels(): Promise<number> {
+  return invoke("purge_ollama_models");
+}
+
 export async function getMonitors(): Promise<MonitorInfo[]> {
   return invoke("get_monitors");
 }
 
+export async function getCaptureCapability(): Promise<CaptureCapability> {
+  return invoke("get_capture_capability");
+}
+
 export async function highlightMonitors(mode: string, monitorId?: number): Promise<void> {
   return invoke("highlight_monitors", { mode, monitorId });
 }
+
+export async function getMonitorChangeState(): Promise<MonitorChangeState[]> {
+  return invoke("get_monitor_change_state");
+}
+
+export async function getCapturePreview(mode: string, monitorId: number | undefined, maxWidth: number): Promise<PreviewFrame[]> {
+  return invoke("get_capture_preview", { mode, monitorId, maxWidth });
+}
+
+export async function setMonitorMode(mode: string, monitorId?: number, preview?: boolean): Promise<void> {
+  return invoke("set_monitor_mode", { mode, monitorId, preview });
+}
+
+export async function selectCaptureRegion(monitorId: number): Promise<RegionSelection> {
+  return invoke("select_capture_region", { monitorId });
+}
+
+export async function getCaptureRegion(): Promise<CaptureRegion | null> {
+  return invoke("get_capture_region");
+}
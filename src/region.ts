@@ -0,0 +1,48 @@
+import { emit } from "@tauri-apps/api/event";
+
+// Script for the region selection overlay (region.html).
+// Coordinates are CSS pixels relative to this window; the backend converts them
+// to physical pixels with the monitor's scale factor.
+interface DragRect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+const box = document.getElementById("selection") as HTMLDivElement;
+let start: { x: number; y: number } | null = null;
+
+function rectTo(from: { x: number; y: number }, e: MouseEvent): DragRect {
+  return { x: from.x, y: from.y, width: e.clientX - from.x, height: e.clientY - from.y };
+}
+
+function draw(r: DragRect) {
+  box.style.display = "block";
+  box.style.left = Math.min(r.x, r.x + r.width) + "px";
+  box.style.top = Math.min(r.y, r.y + r.height) + "px";
+  box.style.width = Math.abs(r.width) + "px";
+  box.style.height = Math.abs(r.height) + "px";
+}
+
+window.addEventListener("mousedown", (e) => {
+  start = { x: e.clientX, y: e.clientY };
+  draw(rectTo(start, e));
+});
+window.addEventListener("mousemove", (e) => {
+  if (start) draw(rectTo(start, e));
+});
+window.addEventListener("mouseup", (e) => {
+  if (!start) return;
+  const r = rectTo(start, e);
+  start = null;
+  // Ignore clicks without a drag
+  if (Math.abs(r.width) < 2 || Math.abs(r.height) < 2) {
+    box.style.display = "none";
+    return;
+  }
+  emit("region-selected", r);
+});
+window.addEventListener("keydown", (e) => {
+  if (e.key === "Escape") emit("region-cancelled");
+});
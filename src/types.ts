@@ -5,6 +5,19 @@ export interface Screenshot {
   active_window_title: string | null;
   monitor_index: number;
   capture_group: string | null;
+  /** Same-tick screenshot of a mirrored display this one duplicates; not sent to the AI. */
+  mirrored_of: number | null;
+  /** Taken of the previously active monitor (`capture_previous_active`). */
+  previous_active: boolean;
+  /** Display scale factor; null for screenshots taken before it was recorded. */
+  scale_factor: number | null;
+  /** Taken of the primary monitor; null for screenshots taken before it was recorded. */
+  is_primary: boolean | null;
+}
+
+export interface ScreenshotNeighbors {
+  prev: Screenshot | null;
+  next: Screenshot | null;
 }
 
 export interface MonitorInfo {
@@ -17,6 +30,28 @@ export interface MonitorInfo {
   is_primary: boolean;
 }
 
+export interface CaptureCapability {
+  can_capture: boolean;
+  /** Why capture can't work; null when can_capture. */
+  reason: string | null;
+  remote_session: boolean;
+  monitor_count: number;
+  probe_ok: boolean;
+}
+
+/** Capture rectangle in physical pixels, relative to its monitor's top-left. */
+export interface CaptureRegion {
+  monitor_id: number;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export type RegionSelection =
+  | { status: "selected"; region: CaptureRegion }
+  | { status: "cancelled" };
+
 export interface Task {
   id: number;
   title: string;
@@ -29,12 +64,59 @@ export interface Task {
   metadata: string | null;
 }
 
+export interface TaskDetail {
+  task: Task;
+  screenshots: Screenshot[];
+  /** `task.metadata` parsed; null if absent or not valid JSON. */
+  metadata: Record<string, unknown> | null;
+}
+
+/** What one analysis request sent to the AI (`save_analysis_inputs`). */
+export interface AnalysisDebugEntry {
+  id: number;
+  run_id: string;
+  capture_group: string;
+  prompt: string;
+  /** Absolute paths of the preprocessed WebP images, in the order they were sent. */
+  image_paths: string[];
+  created_at: string;
+}
+
+export interface TaskWithProvider extends Task {
+  analyzed_by: string | null;
+}
+
+export interface CaptureEvent {
+  id: number;
+  session_id: number | null;
+  occurred_at: string;
+  kind: string;
+  detail: string | null;
+}
+
 export interface CaptureStatus {
   active: boolean;
+  paused: boolean;
   interval_ms: number;
   count: number;
   monitor_mode: string;
   monitors_captured: number;
+  session_screenshot_count: number;
+  max_screenshots_per_session: number;
+  /** Monitor the latest "active" mode tick resolved the cursor to; null in other modes. */
+  active_monitor_id: number | null;
+}
+
+export interface CaptureWarning {
+  session_id: number;
+  message: string;
+}
+
+/** Payload of the `timelapse-progress` and `archive-progress` events. */
+export interface ExportProgress {
+  session_id: number;
+  done: number;
+  total: number;
 }
 
 export interface CaptureSession {
@@ -45,6 +127,243 @@ export interface CaptureSession {
   description: string | null;
   title: string | null;
   unanalyzed_count: number;
+  duration_seconds: number | null;
+  stats: SessionStats;
+}
+
+export interface SessionStats {
+  ticks: number;
+  ticks_unchanged: number;
+  screenshots_saved: number;
+  screenshots_unchanged: number;
+  bytes_written: number;
+  groups_analyzed: number;
+  screenshots_linked_without_ai: number;
+  analysis_resources: ResourceUsage | null;
+}
+
+export interface ProcessLoad {
+  cpu_percent: number;
+  memory_bytes: number;
+}
+
+export interface SystemLoad {
+  cpu_percent: number | null;
+  memory_used_bytes: number | null;
+  memory_total_bytes: number | null;
+  app: ProcessLoad | null;
+  ollama: ProcessLoad | null;
+}
+
+export interface ProcessUsage {
+  samples: number;
+  peak_cpu_percent: number;
+  avg_cpu_percent: number;
+  peak_memory_bytes: number;
+  avg_memory_bytes: number;
+}
+
+export interface ResourceUsage {
+  samples: number;
+  app: ProcessUsage | null;
+  ollama: ProcessUsage | null;
+}
+
+/** Payload of `analysis-progress` (every capture group) and `task-created` (groups that started a task). */
+export interface AnalysisProgress {
+  session_id: number | null;
+  capture_group: string | null;
+  task_id: number;
+  new_task: boolean;
+  /** Every screenshot linked to the task for this group. */
+  screenshot_ids: number[];
+  failed_links: number;
+  done: number;
+  total: number;
+}
+
+/** Payload of the `analysis-complete` event. */
+export interface AnalysisComplete {
+  session_id: number | null;
+  processed: number;
+  /** Links that failed to save; those screenshots are still pending. */
+  failed_links: number;
+  resources: ResourceUsage | null;
+}
+
+export interface GroupReanalysis {
+  capture_group: string;
+  /** The group's task before; it may have been deleted. */
+  old_task_id: number | null;
+  new_task_id: number | null;
+}
+
+export interface DailyTaskCount {
+  date: string;
+  count: number;
+}
+
+export interface UnassignedSummary {
+  count: number;
+  first_captured_at: string | null;
+  last_captured_at: string | null;
+}
+
+export type SessionStatus = "active" | "pending" | "completed" | "empty";
+
+export type TrashKind = "session" | "screenshot";
+
+export interface TrashEntry {
+  kind: TrashKind;
+  trash_id: number;
+  original_id: number;
+  label: string;
+  trashed_at: string;
+  screenshot_count: number;
+}
+
+export type StepStatus = "passed" | "failed" | "skipped";
+
+export interface DiagnosticStep {
+  name: string;
+  status: StepStatus;
+  message: string;
+}
+
+export interface DiagnosticsReport {
+  passed: boolean;
+  steps: DiagnosticStep[];
+}
+
+export interface TrainingExport {
+  rows: number;
+  bytes_written: number;
+  images_copied: number;
+  missing_images: number;
+}
+
+export interface SavingsSummary {
+  sessions: number;
+  stats: SessionStats;
+  unchanged_tick_pct: number;
+  estimated_bytes_saved: number;
+  estimated_requests_saved: number;
+  message: string;
+}
+
+export interface ScreenshotLabel {
+  screenshot_id: number;
+  label_title: string;
+  label_category: string;
+  labeled_at: string;
+}
+
+export interface LabeledScreenshot {
+  screenshot: Screenshot;
+  label: ScreenshotLabel;
+}
+
+export interface EvaluationRow {
+  screenshot_id: number;
+  label_title: string;
+  label_category: string;
+  task_id: number;
+  task_title: string;
+  task_category: string;
+  category_match: boolean;
+  title_overlap: number;
+}
+
+export interface EvaluationReport {
+  labeled: number;
+  compared: number;
+  unanalyzed: number;
+  category_matches: number;
+  category_accuracy: number | null;
+  mean_title_overlap: number | null;
+  rows: EvaluationRow[];
+}
+
+export interface RejectedSetting {
+  key: string;
+  reason: string;
+}
+
+export interface SettingsImport {
+  applied: string[];
+  skipped_unknown: string[];
+  skipped_existing: string[];
+  rejected: RejectedSetting[];
+}
+
+export interface CaptureGroupSummary {
+  group: string;
+  screenshot_count: number;
+  captured_at: string;
+}
+
+export interface SessionGap {
+  start: string;
+  end: string;
+  gap_seconds: number;
+}
+
+export interface MonitorChangeState {
+  monitor_id: number;
+  monitor_name: string;
+  last_hash: string;
+  last_distance: number | null;
+}
+
+export interface PreviewFrame {
+  monitor_id: number;
+  monitor_name: string;
+  width: number;
+  height: number;
+  webp_base64: string;
+}
+
+export interface AppUsage {
+  app: string;
+  screenshots: number;
+  share: number;
+}
+
+export interface SessionArchive {
+  files_added: number;
+  missing_files: string[];
+  bytes_written: number;
+}
+
+export interface AnalysisCostEstimate {
+  session_id: number;
+  provider: string;
+  model: string;
+  groups: number;
+  requests: number;
+  input_tokens: number;
+  output_tokens: number;
+  cost_usd: number | null;
+}
+
+/** Null minutes mean no timed run yet; null cost means the model has no known pricing. */
+export interface AnalysisEstimate {
+  provider: string;
+  model: string;
+  sessions: number;
+  groups: number;
+  screenshots: number;
+  requests: number;
+  minutes_min: number | null;
+  minutes_max: number | null;
+  cost_min_usd: number | null;
+  cost_max_usd: number | null;
+  runs_recorded: number;
+}
+
+export interface OllamaModelsDir {
+  path: string;
+  size_bytes: number;
 }
 
 export interface OllamaStatus {
@@ -57,3 +376,78 @@ export interface AnalysisStatus {
   analyzing: boolean;
   session_id: number | null;
 }
+
+/** An analysis failure from `get_recent_errors`. */
+export interface RecentError {
+  timestamp: string;
+  session_id: number | null;
+  provider: string;
+  message: string;
+}
+
+export interface ProviderProbe {
+  reachable: boolean;
+  latency_ms: number | null;
+  authenticated: boolean | null;
+  error: string | null;
+}
+
+export interface ProviderHealth {
+  provider: string;
+  active: boolean;
+  configured: boolean;
+  model: string | null;
+  probe: ProviderProbe | null;
+  model_available: boolean | null;
+  ollama_source: string | null;
+  last_success_at: string | null;
+  last_error: RecentError | null;
+}
+
+export interface SettingSchema {
+  key: string;
+  kind: "text" | "choice" | "integer" | "bool" | "template" | "fields" | "monitor_intervals" | "capture_source" | "timezone" | "url" | "headers";
+  options: string[];
+  min: number | null;
+  max: number | null;
+  default: string | null;
+  description: string;
+}
+
+export interface ArchivedTask {
+  session_id: number | null;
+  task_id: number;
+  screenshot_count: number;
+}
+
+export interface ResetItem {
+  path: string;
+  action: string;
+  error: string | null;
+}
+
+export interface FactoryReset {
+  items: ResetItem[];
+  verified: boolean;
+  leftovers: string[];
+}
+
+export interface ActivityImport {
+  imported: number;
+  in_session: number;
+  skipped_malformed: number;
+  skipped_blocked: number;
+  skipped_duplicate: number;
+}
+
+export interface PendingCleared {
+  session_id: number | null;
+  count: number;
+}
+
+export interface CategoryCorrection {
+  id: number;
+  keywords: string[];
+  category: string;
+  count: number;
+}
@@ -29,4 +29,13 @@ export default defineConfig(async () => ({
       ignored: ["**/src-tauri/**"],
     },
   },
+  // The region selection overlay is its own page so it can import @tauri-apps/api
+  build: {
+    rollupOptions: {
+      input: {
+        main: "index.html",
+        region: "region.html",
+      },
+    },
+  },
 }));